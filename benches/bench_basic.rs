@@ -46,7 +46,7 @@ impl Actor for MainScene {
         // GUI
         use imgui::Metric::*;
 
-        imgui::pivot((1.0, 0.0));
+        imgui::pivot(Native(1.0, 0.0));
         imgui::label(Native(1.0, 0.0) + Pixel(-8.0, 8.0), "Testing");
     }
 }
@@ -88,7 +88,7 @@ impl Actor for MainScene {
         // GUI
         use imgui::Metric::*;
 
-        imgui::pivot((1.0, 1.0));
+        imgui::pivot(Native(1.0, 1.0));
         imgui::label(
             Native(1.0, 1.0) - Pixel(8.0, 8.0),
             &format!("[WASD ZXEC] : control camera\n[F] : toggle fullscreen (no-op in web)\n[Esc] : reload all (include assets)\ngamepad: {:?} buttons {} {} {} {}",
@@ -99,7 +99,7 @@ impl Actor for MainScene {
                 gamepad_button(0, 3))
         );
 
-        imgui::pivot((1.0, 0.0));
+        imgui::pivot(Native(1.0, 0.0));
         imgui::label(
             Native(1.0, 0.0) + Pixel(-8.0, 8.0),
             &format!("last event: {:?}", self.last_event),
@@ -115,7 +115,9 @@ impl Actor for Cube {
         let db = &mut world.asset_system();
 
         let material = Material::new(db.new_program("phong"));
-        material.set("uMaterial.diffuse", db.new_texture("tex_r.dds"));
+        let diffuse = db.new_texture("tex_r.dds");
+        diffuse.set_srgb(true);
+        material.set("uMaterial.diffuse", diffuse);
         material.set("uMaterial.shininess", 32.0);
 
         let mut mesh = Mesh::new();
@@ -338,13 +338,13 @@ impl Actor for MainScene {
         // GUI
         use imgui::Metric::*;
 
-        imgui::pivot((1.0, 1.0));
+        imgui::pivot(Native(1.0, 1.0));
         imgui::label(
             Native(1.0, 1.0) - Pixel(8.0, 8.0),
             "Click on canvas to drop new box.\n[WASD] : control camera\n[Esc]  : reload all (include assets)",
         );
 
-        imgui::pivot((1.0, 0.0));
+        imgui::pivot(Native(1.0, 0.0));
         imgui::label(
             Native(1.0, 0.0) + Pixel(-8.0, 8.0),
             &format!("last event: {:?}", self.last_event),
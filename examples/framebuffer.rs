@@ -100,13 +100,13 @@ impl Actor for MainScene {
         // GUI
         use imgui::Metric::*;
 
-        imgui::pivot((1.0, 1.0));
+        imgui::pivot(Native(1.0, 1.0));
         imgui::label(
             Native(1.0, 1.0) - Pixel(8.0, 8.0),
             "[WASD] : control camera\nF1 to turn framebuffer on/off.\n[Esc]  : reload all (include assets)",
         );
 
-        imgui::pivot((1.0, 0.0));
+        imgui::pivot(Native(1.0, 0.0));
         imgui::label(
             Native(1.0, 0.0) + Pixel(-8.0, 8.0),
             &format!("last event: {:?}", self.last_event),
@@ -160,8 +160,13 @@ impl Actor for MiniScreen {
             cam.rect = None;
 
             // render fb texture on screen
-            imgui::pivot((0.0, 1.0));
-            imgui::image(Native(0.0, 1.0), Pixel(300.0, 225.0), self.rt.as_texture());
+            imgui::pivot(Native(0.0, 1.0));
+            imgui::image(
+                Native(0.0, 1.0),
+                Pixel(300.0, 225.0),
+                self.rt.as_texture(),
+                imgui::FitMode::Stretch,
+            );
         }
     }
 }
@@ -174,7 +179,9 @@ impl Actor for Cube {
         let db = &mut world.asset_system();
 
         let material = Material::new(db.new_program("phong"));
-        material.set("uMaterial.diffuse", db.new_texture("tex_a.png"));
+        let diffuse = db.new_texture("tex_a.png");
+        diffuse.set_srgb(true);
+        material.set("uMaterial.diffuse", diffuse);
         material.set("uMaterial.shininess", 32.0);
 
         let mut mesh = Mesh::new();
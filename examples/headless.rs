@@ -48,7 +48,7 @@ impl Actor for MainScene {
         // GUI
         use imgui::Metric::*;
 
-        imgui::pivot((1.0, 0.0));
+        imgui::pivot(Native(1.0, 0.0));
         imgui::label(Native(1.0, 0.0) + Pixel(-8.0, 8.0), "Testing");
     }
 }
@@ -62,7 +62,9 @@ impl Actor for Cube {
         let db = &mut world.asset_system();
 
         let material = Material::new(db.new_program("phong"));
-        material.set("uMaterial.diffuse", db.new_texture("tex_r.dds"));
+        let diffuse = db.new_texture("tex_r.dds");
+        diffuse.set_srgb(true);
+        material.set("uMaterial.diffuse", diffuse);
         material.set("uMaterial.shininess", 32.0);
 
         let mut mesh = Mesh::new();
@@ -117,20 +117,20 @@ impl Actor for MainScene {
         use imgui::Metric::*;
         use imgui::TextAlign;
 
-        imgui::pivot((1.0, 1.0));
+        imgui::pivot(Native(1.0, 1.0));
         imgui::text_align(TextAlign::Right);
         imgui::label(
             Native(1.0, 1.0) - Pixel(8.0, 8.0 * 7.0),
             "[WASD] : control camera\n[Esc]  : reload all (include assets)",
         );
 
-        imgui::pivot((1.0, 0.0));
+        imgui::pivot(Native(1.0, 0.0));
         imgui::label(
             Native(1.0, 0.0) + Pixel(-8.0, 8.0),
             &format!("last event: {:?}", self.last_event),
         );
 
-        imgui::pivot((1.0, 1.0));
+        imgui::pivot(Native(1.0, 1.0));
         imgui::text_align(TextAlign::Right);
         imgui::label(
             Native(1.0, 1.0) - Pixel(8.0, 8.0),
@@ -98,13 +98,13 @@ impl Actor for MainScene {
         // GUI
         use imgui::Metric::*;
 
-        imgui::pivot((1.0, 1.0));
+        imgui::pivot(Native(1.0, 1.0));
         imgui::label(
             Native(1.0, 1.0) - Pixel(8.0, 8.0),
             "[WASD] : control camera\n[Esc]  : reload all (include assets)",
         );
 
-        imgui::pivot((1.0, 0.0));
+        imgui::pivot(Native(1.0, 0.0));
         imgui::label(
             Native(1.0, 0.0) + Pixel(-8.0, 8.0),
             &format!("last event: {:?}", self.last_event),
@@ -139,7 +139,9 @@ impl Actor for Cube {
                 _ => "tex_r.png",
             };
 
-            material.set("uMaterial.diffuse", db.new_texture(s));
+            let diffuse = db.new_texture(s);
+            diffuse.set_srgb(true);
+            material.set("uMaterial.diffuse", diffuse);
             material.set("uMaterial.shininess", 32.0);
 
             let mut mesh = Mesh::new();
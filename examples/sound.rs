@@ -86,7 +86,7 @@ impl Actor for SoundEmitter {
         }
         use imgui::Metric::*;
 
-        imgui::pivot((1.0, 1.0));
+        imgui::pivot(Native(1.0, 1.0));
         imgui::label(
             Native(1.0, 1.0) - Pixel(8.0, 8.0),
             "right click to start/stop playing flute\nleft click to hit with your sword!",
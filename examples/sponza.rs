@@ -212,7 +212,7 @@ impl Actor for MainScene {
         use imgui::Metric::*;
         use imgui::TextAlign::*;
 
-        imgui::pivot((1.0, 1.0));
+        imgui::pivot(Native(1.0, 1.0));
         imgui::text_align(Left);
         imgui::label(
             Native(1.0, 1.0) - Pixel(8.0, 8.0),
@@ -222,7 +222,7 @@ impl Actor for MainScene {
         let fpc_ref = world.find_component::<FirstPersonCamera>().unwrap();
         let fpc = fpc_ref.borrow_mut();
 
-        imgui::pivot((1.0, 0.0));
+        imgui::pivot(Native(1.0, 0.0));
         imgui::text_align(Right);
         imgui::label(
             Native(1.0, 0.0) + Pixel(-8.0, 8.0),
@@ -232,7 +232,7 @@ impl Actor for MainScene {
             ),
         );
 
-        imgui::pivot((0.5, 1.0));
+        imgui::pivot(Native(0.5, 1.0));
         imgui::text_align(Center);
         imgui::label(Native(0.5, 1.0) + Pixel(0.0, -8.0), "Sponza Demo");
     }
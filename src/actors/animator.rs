@@ -0,0 +1,132 @@
+use engine::{AnimationClip, AnimationProperty, GameObject, Mesh};
+use world::{Actor, World};
+
+use math::*;
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+/// Play the animation clips of a prefab on its objects, see `World::instantiate`.
+///
+/// The target indices of the channels of the clips index `targets`. A clip is sampled
+/// in each update, the channels of the targets which are gone are skipped
+#[derive(Actor)]
+pub struct Animator {
+    pub clips: Vec<Rc<AnimationClip>>,
+    pub targets: Vec<Weak<RefCell<GameObject>>>,
+    /// Time in the playing clip in seconds
+    pub time: f32,
+    /// Rate of the time, 1 by default
+    pub speed: f32,
+    /// The clip starts again once over, otherwise it stops on its last pose
+    pub looping: bool,
+
+    playing: Option<usize>,
+}
+
+impl Animator {
+    pub fn new(
+        clips: Vec<Rc<AnimationClip>>,
+        targets: Vec<Weak<RefCell<GameObject>>>,
+    ) -> Animator {
+        Animator {
+            clips,
+            targets,
+            time: 0.0,
+            speed: 1.0,
+            looping: true,
+            playing: None,
+        }
+    }
+
+    /// Play the clip `name` from its start, false if there is no such clip
+    pub fn play(&mut self, name: &str) -> bool {
+        match self.clips
+            .iter()
+            .position(|clip| clip.name.as_ref().map_or(false, |n| n == name))
+        {
+            Some(i) => {
+                self.play_index(i);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Play the clip `i` of `clips` from its start
+    pub fn play_index(&mut self, i: usize) {
+        self.playing = if i < self.clips.len() { Some(i) } else { None };
+        self.time = 0.0;
+    }
+
+    /// Stop the clip, the targets keep their current pose
+    pub fn stop(&mut self) {
+        self.playing = None;
+    }
+
+    pub fn playing(&self) -> Option<&Rc<AnimationClip>> {
+        self.playing.and_then(|i| self.clips.get(i))
+    }
+
+    /// Set the properties of the targets to the ones of `clip` at the time `t`
+    pub fn apply(&self, clip: &AnimationClip, t: f32) {
+        for channel in clip.channels.iter() {
+            let target = match self.targets.get(channel.target).and_then(|t| t.upgrade()) {
+                Some(target) => target,
+                None => continue,
+            };
+            let mut target = match target.try_borrow_mut() {
+                Ok(target) => target,
+                Err(_) => continue,
+            };
+
+            let v = channel.sample(t);
+
+            match channel.property {
+                AnimationProperty::Translation if v.len() == 3 => {
+                    let mut local = target.transform.local();
+                    local.disp = Vector3::new(v[0], v[1], v[2]);
+                    target.transform.set_local(local);
+                }
+                AnimationProperty::Rotation if v.len() == 4 => {
+                    let mut local = target.transform.local();
+                    local.rot = Quaternion::new(v[3], v[0], v[1], v[2]);
+                    target.transform.set_local(local);
+                }
+                AnimationProperty::Scale if v.len() == 3 => {
+                    target
+                        .transform
+                        .set_local_scale(Vector3::new(v[0], v[1], v[2]));
+                }
+                AnimationProperty::MorphWeights => {
+                    if let Some((mut mesh, _)) = target.find_component_mut::<Mesh>() {
+                        mesh.morph_weights = v;
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+impl Actor for Animator {
+    fn update(&mut self, _go: &mut GameObject, world: &mut World) {
+        let clip = match self.playing() {
+            Some(clip) => clip.clone(),
+            None => return,
+        };
+
+        self.time += world.delta_time() as f32 * self.speed;
+
+        if self.looping && clip.duration > 0.0 {
+            self.time %= clip.duration;
+            if self.time < 0.0 {
+                self.time += clip.duration;
+            }
+        } else if self.time >= clip.duration {
+            self.time = clip.duration;
+            self.playing = None;
+        }
+
+        self.apply(&clip, self.time);
+    }
+}
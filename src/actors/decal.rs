@@ -0,0 +1,282 @@
+use engine::{Aabb, Asset, CullMode, DepthTest, GameObject, Material, Mesh, MeshBuffer, MeshData,
+             MeshPrimitive, RenderQueue, Texture};
+use world::{Actor, World};
+
+use math::*;
+use std::f32::consts::PI;
+use std::rc::Rc;
+
+/// Half size of the box of a decal in its local space, the unit cube
+const HALF: f32 = 0.5;
+
+/// Project a texture onto the opaque meshes in the box of the object, e.g. bullet holes,
+/// splats or road markings. The box is the unit cube scaled by the transform of the
+/// object, the texture is projected down its z axis, u along x and v along y.
+///
+/// The triangles of the meshes in the box are clipped by it into the mesh of the decal,
+/// when the decal starts and after `rebuild`. It is drawn in the transparent queue
+#[derive(Actor)]
+pub struct Decal {
+    pub texture: Option<Rc<Texture>>,
+    pub color: Vector4<f32>,
+    /// Maximum angle in radians between the surfaces and the projection,
+    /// the steeper triangles are skipped since the texture would be stretched on them
+    pub max_angle: f32,
+    /// Offset of the decal towards the projector in its local space, against z-fighting
+    pub offset: f32,
+    /// Layers of the objects which receive the decal
+    pub culling_mask: u32,
+    /// Material of the decal used instead of the default one, which blends the texture
+    /// by the vertex colors. It is read when the decal starts
+    pub material: Option<Rc<Material>>,
+
+    buffer: Option<Rc<MeshBuffer>>,
+    /// The mesh is built again in next update, e.g. until all the meshes are loaded
+    dirty: bool,
+}
+
+fn empty_mesh_data() -> MeshData {
+    MeshData {
+        vertices: Vec::new(),
+        uvs: Some(Vec::new()),
+        uvs2: None,
+        normals: Some(Vec::new()),
+        indices: Vec::new(),
+        tangents: None,
+        bitangents: None,
+        colors: Some(Vec::new()),
+        joints: None,
+        weights: None,
+        morph_targets: Vec::new(),
+    }
+}
+
+fn overlaps(a: &Aabb, b: &Aabb) -> bool {
+    a.min.x <= b.max.x && a.max.x >= b.min.x && a.min.y <= b.max.y && a.max.y >= b.min.y
+        && a.min.z <= b.max.z && a.max.z >= b.min.z
+}
+
+/// Clip a convex polygon by the box of the decal
+fn clip_polygon(mut poly: Vec<Vector3<f32>>) -> Vec<Vector3<f32>> {
+    for axis in 0..3 {
+        for &sign in [1.0f32, -1.0].iter() {
+            let dist = |p: &Vector3<f32>| HALF - sign * p[axis];
+            let mut clipped = Vec::with_capacity(poly.len() + 2);
+
+            for i in 0..poly.len() {
+                let (a, b) = (poly[i], poly[(i + 1) % poly.len()]);
+                let (da, db) = (dist(&a), dist(&b));
+
+                if da >= 0.0 {
+                    clipped.push(a);
+                }
+                if (da >= 0.0) != (db >= 0.0) {
+                    clipped.push(a + (b - a) * (da / (da - db)));
+                }
+            }
+
+            poly = clipped;
+            if poly.len() < 3 {
+                return Vec::new();
+            }
+        }
+    }
+
+    poly
+}
+
+impl Decal {
+    pub fn new() -> Decal {
+        Decal {
+            texture: None,
+            color: Vector4::new(1.0, 1.0, 1.0, 1.0),
+            max_angle: PI / 3.0,
+            offset: 0.001,
+            culling_mask: !0,
+            material: None,
+
+            buffer: None,
+            dirty: true,
+        }
+    }
+
+    /// Build the mesh again in next update, e.g. after the decal or the meshes moved
+    pub fn rebuild(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Append the polygon in the decal space as a triangle fan
+    fn push_polygon(&self, data: &mut MeshData, poly: &[Vector3<f32>]) {
+        let base = (data.vertices.len() / 3) as u16;
+        let c = self.color;
+
+        for p in poly.iter() {
+            data.vertices.extend_from_slice(&[p.x, p.y, p.z + self.offset]);
+            data.uvs
+                .as_mut()
+                .unwrap()
+                .extend_from_slice(&[p.x + HALF, p.y + HALF]);
+            data.normals
+                .as_mut()
+                .unwrap()
+                .extend_from_slice(&[0.0, 0.0, 1.0]);
+            data.colors
+                .as_mut()
+                .unwrap()
+                .extend_from_slice(&[c.x, c.y, c.z, c.w]);
+        }
+
+        for i in 1..(poly.len() as u16 - 1) {
+            data.indices
+                .extend_from_slice(&[base, base + i, base + i + 1]);
+        }
+    }
+
+    /// The clipped triangles in the local space of the object, and whether all the
+    /// meshes in the box were loaded
+    fn build_mesh_data(&self, go: &GameObject, world: &World) -> (MeshData, bool) {
+        let mut data = empty_mesh_data();
+
+        let decal_m = go.transform.as_global_matrix();
+        let to_decal = match decal_m.invert() {
+            Some(m) => m,
+            None => return (data, true),
+        };
+
+        let unit = Aabb {
+            min: Vector3::new(-HALF, -HALF, -HALF),
+            max: Vector3::new(HALF, HALF, HALF),
+        };
+        let bounds = unit.transform(&decal_m);
+        let min_cos = self.max_angle.cos();
+        let mut complete = true;
+
+        for obj in world.engine().objects.iter() {
+            let obj = match obj.upgrade() {
+                Some(obj) => obj,
+                None => continue,
+            };
+            // The object of the decal is borrowed, it is skipped
+            let obj = match obj.try_borrow() {
+                Ok(obj) => obj,
+                Err(_) => continue,
+            };
+            if !obj.active || obj.layer & self.culling_mask == 0 {
+                continue;
+            }
+
+            let (mesh, _) = match obj.find_component::<Mesh>() {
+                Some(mesh) => mesh,
+                None => continue,
+            };
+
+            let model_m = obj.transform.as_global_matrix();
+            match mesh.world_bounds(&model_m) {
+                Some(ref aabb) if overlaps(aabb, &bounds) => (),
+                Some(_) => continue,
+                None => {
+                    complete = false;
+                    continue;
+                }
+            }
+
+            let m = to_decal * model_m;
+
+            for surface in mesh.surfaces.iter() {
+                if surface.material.render_queue.base() != RenderQueue::Opaque
+                    || surface.buffer.primitive() != MeshPrimitive::Triangles
+                {
+                    continue;
+                }
+
+                let mesh_data = match surface.buffer.mesh_data() {
+                    Ok(mesh_data) => mesh_data,
+                    Err(_) => {
+                        complete = false;
+                        continue;
+                    }
+                };
+
+                let v = &mesh_data.vertices;
+                for tri in mesh_data.indices.chunks(3).filter(|tri| tri.len() == 3) {
+                    let p: Vec<Vector3<f32>> = tri.iter()
+                        .map(|&i| {
+                            let i = i as usize * 3;
+                            m.transform_point(Point3::new(v[i], v[i + 1], v[i + 2]))
+                                .to_vec()
+                        })
+                        .collect();
+
+                    // Facing the projector, which looks down the z axis
+                    let n = (p[1] - p[0]).cross(p[2] - p[0]);
+                    if n.magnitude2() <= 0.0 || n.normalize().z < min_cos {
+                        continue;
+                    }
+
+                    let poly = clip_polygon(p);
+                    if poly.is_empty() {
+                        continue;
+                    }
+
+                    // The indices of the decal are u16
+                    if data.vertices.len() / 3 + poly.len() > u16::max_value() as usize {
+                        return (data, complete);
+                    }
+
+                    self.push_polygon(&mut data, &poly);
+                }
+            }
+        }
+
+        (data, complete)
+    }
+
+    fn default_material(&self, world: &World) -> Material {
+        let db = world.asset_system();
+
+        let mut material = Material::new(db.new_program("default_sprite"));
+        material.set(
+            "uDiffuse",
+            self.texture
+                .clone()
+                .unwrap_or_else(|| db.new_texture("default_white")),
+        );
+        material.render_queue = RenderQueue::Transparent;
+        material.states.alpha_blending = Some(true);
+        material.states.depth_write = Some(false);
+        material.states.depth_test = Some(DepthTest::LessEqual);
+        material.states.cull = Some(CullMode::Off);
+
+        material
+    }
+}
+
+impl Actor for Decal {
+    fn start(&mut self, go: &mut GameObject, world: &mut World) {
+        let material = self.material
+            .clone()
+            .unwrap_or_else(|| Rc::new(self.default_material(world)));
+
+        let buffer = MeshBuffer::new(empty_mesh_data());
+
+        let mut mesh = Mesh::new();
+        mesh.add_surface(buffer.clone(), material);
+        go.add_component(mesh);
+
+        self.buffer = Some(buffer);
+        self.dirty = true;
+    }
+
+    fn update(&mut self, go: &mut GameObject, world: &mut World) {
+        if !self.dirty {
+            return;
+        }
+
+        let (data, complete) = self.build_mesh_data(go, world);
+        if let Some(ref buffer) = self.buffer {
+            buffer.swap_mesh_data(data);
+        }
+
+        self.dirty = !complete;
+    }
+}
@@ -1,7 +1,15 @@
 mod skybox;
 mod shadow_pass;
 mod first_person_camera;
+mod particle_emitter;
+mod decal;
+mod terrain;
+mod animator;
 
 pub use self::skybox::SkyBox;
 pub use self::shadow_pass::ShadowPass;
 pub use self::first_person_camera::FirstPersonCamera;
+pub use self::particle_emitter::{Burst, Curve, ParticleEmitter, SimulationSpace};
+pub use self::decal::Decal;
+pub use self::terrain::{Heightmap, Terrain, TerrainLayer, MAX_TERRAIN_LAYERS};
+pub use self::animator::Animator;
@@ -0,0 +1,376 @@
+use engine::{Asset, BlendMode, CullMode, GameObject, Material, Mesh, MeshBuffer, MeshData,
+             RenderQueue, Texture};
+use world::{Actor, World};
+
+use math::*;
+use std::f32::consts::PI;
+use std::ops::{Add, Mul};
+use std::rc::Rc;
+
+/// The particles of an emitter are drawn from a single mesh buffer of u16 indices
+const MAX_PARTICLES: usize = 16384;
+
+/// Space which the particles are simulated in
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SimulationSpace {
+    /// The particles move with the emitter
+    Local,
+    /// The particles are left behind when the emitter moves
+    World,
+}
+
+/// A value over the normalized lifetime of a particle, linearly interpolated
+/// between the keys (time, value) sorted by time
+#[derive(Clone, Debug)]
+pub struct Curve<T> {
+    pub keys: Vec<(f32, T)>,
+}
+
+impl<T> Curve<T>
+where
+    T: Copy + Add<Output = T> + Mul<f32, Output = T>,
+{
+    pub fn new(keys: Vec<(f32, T)>) -> Curve<T> {
+        Curve { keys }
+    }
+
+    pub fn constant(value: T) -> Curve<T> {
+        Curve {
+            keys: vec![(0.0, value)],
+        }
+    }
+
+    /// The value at `t` (0..1), None if the curve has no keys
+    pub fn evaluate(&self, t: f32) -> Option<T> {
+        let first = self.keys.first()?;
+        if t <= first.0 {
+            return Some(first.1);
+        }
+
+        for pair in self.keys.windows(2) {
+            let ((t0, v0), (t1, v1)) = (pair[0], pair[1]);
+            if t <= t1 {
+                let f = if t1 > t0 { (t - t0) / (t1 - t0) } else { 1.0 };
+                return Some(v0 * (1.0 - f) + v1 * f);
+            }
+        }
+
+        self.keys.last().map(|k| k.1)
+    }
+}
+
+/// Particles emitted at once, `time` seconds after the start of a cycle of the emitter
+#[derive(Copy, Clone, Debug)]
+pub struct Burst {
+    pub time: f32,
+    pub count: u32,
+}
+
+struct Particle {
+    position: Vector3<f32>,
+    velocity: Vector3<f32>,
+    age: f32,
+    lifetime: f32,
+}
+
+/// Emit billboarded quads simulated on the cpu, all of them are drawn from one
+/// dynamic mesh of the object. The particles are emitted from the origin of the object
+/// in a cone around its up axis
+#[derive(Actor)]
+pub struct ParticleEmitter {
+    /// Particles emitted per second
+    pub rate: f32,
+    pub bursts: Vec<Burst>,
+    /// Length of a cycle in seconds, nothing is emitted after the first cycle unless looping
+    pub duration: f32,
+    pub looping: bool,
+
+    /// Lifetime of a particle in seconds
+    pub lifetime: f32,
+    pub start_speed: f32,
+    pub start_size: f32,
+    pub start_color: Vector4<f32>,
+    /// Half angle of the emission cone in radians
+    pub spread: f32,
+    /// Acceleration in the simulation space
+    pub gravity: Vector3<f32>,
+    /// Fraction of the velocity lost per second
+    pub drag: f32,
+
+    /// Multipliers of the start color and the start size over the lifetime
+    pub color_over_lifetime: Curve<Vector4<f32>>,
+    pub size_over_lifetime: Curve<f32>,
+
+    pub simulation_space: SimulationSpace,
+    pub max_particles: usize,
+
+    /// Texture of the default material
+    pub texture: Option<Rc<Texture>>,
+    /// Blending of the default material, e.g. `BlendMode::additive()` for fire
+    pub blend: BlendMode,
+    /// Material of the particles used instead of the default one, which blends the
+    /// texture by the vertex colors. It is read when the emitter starts
+    pub material: Option<Rc<Material>>,
+
+    particles: Vec<Particle>,
+    time: f32,
+    emit_accum: f32,
+    seed: u32,
+
+    buffer: Option<Rc<MeshBuffer>>,
+    /// Mesh data of the previous frame, its vectors are reused
+    spare: Option<MeshData>,
+}
+
+fn empty_mesh_data() -> MeshData {
+    MeshData {
+        vertices: Vec::new(),
+        uvs: Some(Vec::new()),
+        uvs2: None,
+        normals: Some(Vec::new()),
+        indices: Vec::new(),
+        tangents: None,
+        bitangents: None,
+        colors: Some(Vec::new()),
+        joints: None,
+        weights: None,
+        morph_targets: Vec::new(),
+    }
+}
+
+impl ParticleEmitter {
+    pub fn new() -> ParticleEmitter {
+        ParticleEmitter {
+            rate: 10.0,
+            bursts: Vec::new(),
+            duration: 5.0,
+            looping: true,
+
+            lifetime: 2.0,
+            start_speed: 1.0,
+            start_size: 0.1,
+            start_color: Vector4::new(1.0, 1.0, 1.0, 1.0),
+            spread: PI / 8.0,
+            gravity: Vector3::new(0.0, 0.0, 0.0),
+            drag: 0.0,
+
+            color_over_lifetime: Curve::constant(Vector4::new(1.0, 1.0, 1.0, 1.0)),
+            size_over_lifetime: Curve::constant(1.0),
+
+            simulation_space: SimulationSpace::Local,
+            max_particles: 1000,
+
+            texture: None,
+            blend: BlendMode::alpha(),
+            material: None,
+
+            particles: Vec::new(),
+            time: 0.0,
+            emit_accum: 0.0,
+            seed: 0x2545_f491,
+
+            buffer: None,
+            spare: None,
+        }
+    }
+
+    pub fn particle_count(&self) -> usize {
+        self.particles.len()
+    }
+
+    /// Pseudo random number in 0..1 of a xorshift
+    fn random(&mut self) -> f32 {
+        let mut x = self.seed;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.seed = x;
+
+        (x >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    /// Number of the particles of the bursts between `prev` and `prev + dt`
+    fn burst_count(&self, prev: f32, dt: f32) -> u32 {
+        let cycle = if self.looping && self.duration > 0.0 {
+            Some(self.duration)
+        } else {
+            None
+        };
+
+        let (t0, t1) = match cycle {
+            Some(d) => (prev % d, prev % d + dt),
+            None => (prev, prev + dt),
+        };
+
+        self.bursts
+            .iter()
+            .filter(|b| {
+                let wrapped = cycle.map_or(false, |d| b.time + d >= t0 && b.time + d < t1);
+                (b.time >= t0 && b.time < t1) || wrapped
+            })
+            .map(|b| b.count)
+            .sum()
+    }
+
+    fn emit(&mut self, count: u32, model_m: &Matrix4<f32>) {
+        let max = self.max_particles.min(MAX_PARTICLES);
+
+        for _ in 0..count {
+            if self.particles.len() >= max {
+                break;
+            }
+
+            // Uniform direction in the cone around the up axis
+            let theta = self.random() * 2.0 * PI;
+            let cos_phi = 1.0 - self.random() * (1.0 - self.spread.cos());
+            let sin_phi = (1.0 - cos_phi * cos_phi).max(0.0).sqrt();
+            let dir = Vector3::new(sin_phi * theta.cos(), cos_phi, sin_phi * theta.sin());
+
+            let (position, dir) = match self.simulation_space {
+                SimulationSpace::Local => (Vector3::new(0.0, 0.0, 0.0), dir),
+                SimulationSpace::World => {
+                    let world_dir = model_m.transform_vector(dir);
+                    let world_dir = if world_dir.magnitude2() > 0.0 {
+                        world_dir.normalize()
+                    } else {
+                        dir
+                    };
+
+                    (model_m.transform_point(Point3::origin()).to_vec(), world_dir)
+                }
+            };
+
+            self.particles.push(Particle {
+                position,
+                velocity: dir * self.start_speed,
+                age: 0.0,
+                lifetime: self.lifetime.max(1e-3),
+            });
+        }
+    }
+
+    fn simulate(&mut self, dt: f32) {
+        let gravity = self.gravity;
+        let damping = (1.0 - self.drag * dt).max(0.0);
+
+        self.particles.retain(|p| p.age + dt < p.lifetime);
+
+        for p in self.particles.iter_mut() {
+            p.age += dt;
+            p.velocity = (p.velocity + gravity * dt) * damping;
+            p.position += p.velocity * dt;
+        }
+    }
+
+    /// The quads of the particles in the local space of the object
+    fn build_mesh_data(&mut self, model_m: &Matrix4<f32>) -> MeshData {
+        let mut data = self.spare.take().unwrap_or_else(empty_mesh_data);
+        data.vertices.clear();
+        data.indices.clear();
+        data.uvs.get_or_insert_with(Vec::new).clear();
+        data.normals.get_or_insert_with(Vec::new).clear();
+        data.colors.get_or_insert_with(Vec::new).clear();
+
+        let to_local = match self.simulation_space {
+            SimulationSpace::Local => None,
+            SimulationSpace::World => model_m.invert(),
+        };
+
+        let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+        let uvs = [0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0];
+
+        for (i, p) in self.particles.iter().enumerate() {
+            let t = p.age / p.lifetime;
+            let half = self.start_size * self.size_over_lifetime.evaluate(t).unwrap_or(1.0) * 0.5;
+            let color = self.start_color.mul_element_wise(
+                self.color_over_lifetime
+                    .evaluate(t)
+                    .unwrap_or(Vector4::new(1.0, 1.0, 1.0, 1.0)),
+            );
+
+            let pos = match to_local {
+                Some(ref m) => m.transform_point(Point3::from_vec(p.position)).to_vec(),
+                None => p.position,
+            };
+
+            for &(cx, cy) in corners.iter() {
+                data.vertices.extend_from_slice(&[pos.x, pos.y, pos.z]);
+                data.normals
+                    .as_mut()
+                    .unwrap()
+                    .extend_from_slice(&[cx * half, cy * half, 0.0]);
+                data.colors
+                    .as_mut()
+                    .unwrap()
+                    .extend_from_slice(&[color.x, color.y, color.z, color.w]);
+            }
+            data.uvs.as_mut().unwrap().extend_from_slice(&uvs);
+
+            let base = (i * 4) as u16;
+            data.indices
+                .extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+
+        data
+    }
+
+    fn default_material(&self, world: &World) -> Material {
+        let db = world.asset_system();
+
+        let material = Material::new(db.new_program("default_particle"));
+        material.set(
+            "uDiffuse",
+            self.texture
+                .clone()
+                .unwrap_or_else(|| db.new_texture("default_white")),
+        );
+
+        material
+    }
+}
+
+impl Actor for ParticleEmitter {
+    fn start(&mut self, go: &mut GameObject, world: &mut World) {
+        let material = self.material.clone().unwrap_or_else(|| {
+            let mut material = self.default_material(world);
+            material.render_queue = RenderQueue::Transparent;
+            material.states.alpha_blending = Some(true);
+            material.states.blend = Some(self.blend);
+            material.states.depth_write = Some(false);
+            material.states.cull = Some(CullMode::Off);
+            Rc::new(material)
+        });
+
+        let buffer = MeshBuffer::new(empty_mesh_data());
+
+        let mut mesh = Mesh::new();
+        mesh.add_surface(buffer.clone(), material);
+        go.add_component(mesh);
+
+        self.buffer = Some(buffer);
+    }
+
+    fn update(&mut self, go: &mut GameObject, world: &mut World) {
+        let dt = world.delta_time() as f32;
+        let model_m = go.transform.as_global_matrix();
+
+        self.simulate(dt);
+
+        let prev = self.time;
+        self.time += dt;
+
+        let emitting = self.looping || prev < self.duration;
+        if emitting {
+            self.emit_accum += self.rate * dt;
+        }
+
+        let count = self.emit_accum.floor().max(0.0) as u32 + self.burst_count(prev, dt);
+        self.emit_accum -= self.emit_accum.floor().max(0.0);
+        self.emit(count, &model_m);
+
+        let data = self.build_mesh_data(&model_m);
+        if let Some(ref buffer) = self.buffer {
+            self.spare = buffer.swap_mesh_data(data);
+        }
+    }
+}
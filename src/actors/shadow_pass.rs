@@ -430,6 +430,11 @@ impl ShadowPass {
         self.shadow_maps[3].partition_z = partitions[3];
     }
 
+    /// Only the objects in the layers of `mask` cast shadows, see `GameObject::layer`
+    pub fn set_culling_mask(&mut self, mask: u32) {
+        self.light_camera.culling_mask = mask;
+    }
+
     fn apply(&self, material: &Material) {
         material.set("uShadowEnabled", true);
         material.set("uShadowMapTexture", self.rt.as_texture());
@@ -540,7 +545,7 @@ impl Actor for ShadowPass {
         use imgui;
         use imgui::Metric::*;
 
-        imgui::pivot((0.0, 1.0));
+        imgui::pivot(Native(0.0, 1.0));
         let mut mat = Material::new(world.asset_system().new_program("unrust/shadow_display"));
         mat.set("uDepthMap", self.rt.as_texture());
         mat.render_queue = RenderQueue::UI;
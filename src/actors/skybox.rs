@@ -1,20 +1,16 @@
 use world::{Actor, Processor, World};
-use engine::{GameObject, Material, Mesh, RenderQueue};
+use engine::{GameObject, Skybox};
 
+/// Add the `Skybox` of the sky cubemap, which is also the environment map of the scene
 #[derive(Component)]
 pub struct SkyBox {}
 
 impl Actor for SkyBox {
     fn start(&mut self, go: &mut GameObject, world: &mut World) {
-        let db = &mut world.asset_system();
+        let db = world.asset_system();
 
-        let mut material = Material::new(db.new_program("unrust/skybox"));
-        material.set("uSkybox", db.new_texture("unrust/skybox/sky_cubemap.dds"));
-        material.render_queue = RenderQueue::Skybox;
-
-        let mut mesh = Mesh::new();
-        mesh.add_surface(db.new_mesh_buffer("skybox"), material);
-        go.add_component(mesh);
+        let skybox = Skybox::new(db, db.new_texture("unrust/skybox/sky_cubemap.dds"));
+        go.add_component(skybox);
     }
 }
 
@@ -0,0 +1,481 @@
+use engine::{GameObject, Material, Mesh, MeshBuffer, MeshData, Texture};
+use world::{Actor, Handle, World};
+
+use math::*;
+use std::rc::Rc;
+
+/// Maximum number of the texture layers blended by the splat map, one per channel
+pub const MAX_TERRAIN_LAYERS: usize = 4;
+
+/// Heights of a grid of samples, normalized in 0..1, row by row from the top-left
+#[derive(Clone, Debug)]
+pub struct Heightmap {
+    pub width: u32,
+    pub height: u32,
+    pub heights: Vec<f32>,
+}
+
+impl Heightmap {
+    pub fn new(width: u32, height: u32, heights: Vec<f32>) -> Heightmap {
+        assert_eq!(heights.len(), (width * height) as usize);
+
+        Heightmap {
+            width,
+            height,
+            heights,
+        }
+    }
+
+    /// The heights from the red channel of the image of a texture, None until it is
+    /// loaded. The image should not be used by any material, it is freed once uploaded
+    pub fn from_texture(texture: &Texture) -> Option<Heightmap> {
+        let (w, h, red) = texture.red_channel()?;
+        if w < 2 || h < 2 {
+            return None;
+        }
+
+        Some(Heightmap::new(
+            w,
+            h,
+            red.into_iter().map(|r| r as f32 / 255.0).collect(),
+        ))
+    }
+
+    /// The height of the sample at (x, y), clamped to the grid
+    pub fn get(&self, x: i32, y: i32) -> f32 {
+        let x = x.max(0).min(self.width as i32 - 1) as usize;
+        let y = y.max(0).min(self.height as i32 - 1) as usize;
+        self.heights[y * self.width as usize + x]
+    }
+}
+
+/// A texture blended over the terrain by one channel of the splat map
+#[derive(Clone)]
+pub struct TerrainLayer {
+    pub texture: Rc<Texture>,
+    /// Repeats of the texture over the whole terrain
+    pub tiling: f32,
+}
+
+struct TerrainChunk {
+    object: Handle<GameObject>,
+    buffer: Rc<MeshBuffer>,
+    /// Range of the samples of the chunk, the last ones are included
+    x: (u32, u32),
+    z: (u32, u32),
+    /// Center of the chunk in the local space of the terrain
+    center: Vector3<f32>,
+    lod: Option<usize>,
+}
+
+/// A terrain generated from a heightmap, from the origin of the object to `size` on its
+/// x and z axes. The samples of the heightmap from its top-left are laid along +x and +z.
+///
+/// The terrain is split in chunks of `chunk_size` cells, each one a child object with a
+/// mesh, so they are culled on their own. A chunk has half the resolution for each
+/// `lod_distances` it is farther from the camera, and skirts hide the cracks between
+/// the chunks of different levels.
+///
+/// The layers are blended by the rgba channels of `splat_map` in the default material.
+/// The fields are read when the terrain starts, or after `rebuild`
+#[derive(Actor)]
+pub struct Terrain {
+    /// Texture of the heights, used until `set_heightmap` is called
+    pub heightmap_texture: Option<Rc<Texture>>,
+    /// Size of the terrain on the x and z axes
+    pub size: (f32, f32),
+    /// Height of the samples of value 1
+    pub height: f32,
+    /// Cells of the side of a chunk, a power of two up to 128
+    pub chunk_size: u32,
+    /// Distances from the camera to the center of a chunk where its resolution is halved
+    pub lod_distances: Vec<f32>,
+    /// Depth of the skirts below the edges of the chunks
+    pub skirt_depth: f32,
+    pub layers: Vec<TerrainLayer>,
+    /// Weights of the layers in its channels, the first layer only if None
+    pub splat_map: Option<Rc<Texture>>,
+    /// Material of the terrain used instead of the default one. It is read when
+    /// the chunks are built
+    pub material: Option<Rc<Material>>,
+
+    heightmap: Option<Heightmap>,
+    chunks: Vec<TerrainChunk>,
+    model: Matrix4<f32>,
+    /// The chunks are built again in next update, e.g. until the heightmap is loaded
+    dirty: bool,
+}
+
+fn empty_mesh_data() -> MeshData {
+    MeshData {
+        vertices: Vec::new(),
+        uvs: Some(Vec::new()),
+        uvs2: None,
+        normals: Some(Vec::new()),
+        indices: Vec::new(),
+        tangents: None,
+        bitangents: None,
+        colors: None,
+        joints: None,
+        weights: None,
+        morph_targets: Vec::new(),
+    }
+}
+
+/// The sample indices from `start` to `end` by `step`, the last one is always included
+fn lod_samples(start: u32, end: u32, step: u32) -> Vec<u32> {
+    let mut samples: Vec<u32> = (start..end).filter(|i| (i - start) % step == 0).collect();
+    samples.push(end);
+    samples
+}
+
+impl Terrain {
+    pub fn new() -> Terrain {
+        Terrain {
+            heightmap_texture: None,
+            size: (100.0, 100.0),
+            height: 20.0,
+            chunk_size: 32,
+            lod_distances: vec![50.0, 100.0, 200.0],
+            skirt_depth: 1.0,
+            layers: Vec::new(),
+            splat_map: None,
+            material: None,
+
+            heightmap: None,
+            chunks: Vec::new(),
+            model: Matrix4::identity(),
+            dirty: true,
+        }
+    }
+
+    /// Use the heights instead of `heightmap_texture`, e.g. ones generated by code
+    pub fn set_heightmap(&mut self, heightmap: Heightmap) {
+        self.heightmap = Some(heightmap);
+        self.dirty = true;
+    }
+
+    pub fn heightmap(&self) -> Option<&Heightmap> {
+        self.heightmap.as_ref()
+    }
+
+    /// Build the chunks again in next update, e.g. after the fields are changed
+    pub fn rebuild(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Distance between the samples on the x and z axes
+    fn cell_size(&self, hm: &Heightmap) -> (f32, f32) {
+        (
+            self.size.0 / (hm.width - 1) as f32,
+            self.size.1 / (hm.height - 1) as f32,
+        )
+    }
+
+    fn sample_normal(&self, hm: &Heightmap, x: i32, z: i32) -> Vector3<f32> {
+        let (dx, dz) = self.cell_size(hm);
+
+        let hx = (hm.get(x + 1, z) - hm.get(x - 1, z)) * self.height / (2.0 * dx);
+        let hz = (hm.get(x, z + 1) - hm.get(x, z - 1)) * self.height / (2.0 * dz);
+
+        Vector3::new(-hx, 1.0, -hz).normalize()
+    }
+
+    /// The position of (x, z) in the heightmap grid, the cell and the offset in it
+    fn grid_position(&self, hm: &Heightmap, x: f32, z: f32) -> (i32, i32, f32, f32) {
+        let (dx, dz) = self.cell_size(hm);
+
+        let gx = (x / dx).max(0.0).min((hm.width - 1) as f32);
+        let gz = (z / dz).max(0.0).min((hm.height - 1) as f32);
+
+        let cx = (gx as i32).min(hm.width as i32 - 2);
+        let cz = (gz as i32).min(hm.height as i32 - 2);
+
+        (cx, cz, gx - cx as f32, gz - cz as f32)
+    }
+
+    /// Height of the surface at (x, z) in the local space of the terrain, on the
+    /// triangles of the full resolution. None until the heightmap is loaded
+    pub fn height_at(&self, x: f32, z: f32) -> Option<f32> {
+        let hm = self.heightmap.as_ref()?;
+        let (cx, cz, fx, fz) = self.grid_position(hm, x, z);
+
+        let a = hm.get(cx, cz);
+        let b = hm.get(cx + 1, cz);
+        let c = hm.get(cx, cz + 1);
+        let d = hm.get(cx + 1, cz + 1);
+
+        // The cells are split along the diagonal from b to c, see `push_cell`
+        let h = if fx + fz <= 1.0 {
+            a + (b - a) * fx + (c - a) * fz
+        } else {
+            d + (c - d) * (1.0 - fx) + (b - d) * (1.0 - fz)
+        };
+
+        Some(h * self.height)
+    }
+
+    /// Normal of the surface at (x, z) in the local space of the terrain, smoothly
+    /// interpolated between the samples
+    pub fn normal_at(&self, x: f32, z: f32) -> Option<Vector3<f32>> {
+        let hm = self.heightmap.as_ref()?;
+        let (cx, cz, fx, fz) = self.grid_position(hm, x, z);
+
+        let top =
+            self.sample_normal(hm, cx, cz) * (1.0 - fx) + self.sample_normal(hm, cx + 1, cz) * fx;
+        let bottom = self.sample_normal(hm, cx, cz + 1) * (1.0 - fx)
+            + self.sample_normal(hm, cx + 1, cz + 1) * fx;
+
+        Some((top * (1.0 - fz) + bottom * fz).normalize())
+    }
+
+    /// The point of the surface below or above (x, z) in world space and its normal,
+    /// e.g. to place an object or to ground a character. The terrain is assumed to be
+    /// rotated around its y axis only
+    pub fn ground_at(&self, x: f32, z: f32) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        let local = self.model.invert()?.transform_point(Point3::new(x, 0.0, z));
+        let h = self.height_at(local.x, local.z)?;
+        let n = self.normal_at(local.x, local.z)?;
+
+        let p = self.model.transform_point(Point3::new(local.x, h, local.z));
+        let n = self.model.invert()?.transpose().transform_vector(n).normalize();
+
+        Some((p.to_vec(), n))
+    }
+
+    fn chunk_cells(&self) -> u32 {
+        self.chunk_size.max(1).min(128).next_power_of_two()
+    }
+
+    fn lod_count(&self) -> usize {
+        let mut count = 1;
+        while count <= self.lod_distances.len() && (1 << count) <= self.chunk_cells() {
+            count += 1;
+        }
+
+        count
+    }
+
+    fn push_vertex(&self, data: &mut MeshData, hm: &Heightmap, x: u32, z: u32, drop: f32) {
+        let (dx, dz) = self.cell_size(hm);
+        let h = hm.get(x as i32, z as i32) * self.height - drop;
+        let n = self.sample_normal(hm, x as i32, z as i32);
+
+        data.vertices
+            .extend_from_slice(&[x as f32 * dx, h, z as f32 * dz]);
+        data.normals
+            .as_mut()
+            .unwrap()
+            .extend_from_slice(&[n.x, n.y, n.z]);
+        data.uvs.as_mut().unwrap().extend_from_slice(&[
+            x as f32 / (hm.width - 1) as f32,
+            z as f32 / (hm.height - 1) as f32,
+        ]);
+    }
+
+    /// Append the quad of the vertices a (top-left), b, c and d, facing up
+    fn push_cell(data: &mut MeshData, a: u16, b: u16, c: u16, d: u16) {
+        data.indices.extend_from_slice(&[a, c, b, b, c, d]);
+    }
+
+    fn build_chunk(&self, hm: &Heightmap, chunk: &TerrainChunk, lod: usize) -> MeshData {
+        let mut data = empty_mesh_data();
+        let step = 1 << lod;
+
+        let xs = lod_samples(chunk.x.0, chunk.x.1, step);
+        let zs = lod_samples(chunk.z.0, chunk.z.1, step);
+        let row = xs.len() as u16;
+
+        for &z in zs.iter() {
+            for &x in xs.iter() {
+                self.push_vertex(&mut data, hm, x, z, 0.0);
+            }
+        }
+
+        for j in 0..(zs.len() as u16 - 1) {
+            for i in 0..(row - 1) {
+                let a = j * row + i;
+                Self::push_cell(&mut data, a, a + 1, a + row, a + row + 1);
+            }
+        }
+
+        // The skirts hang below the 4 edges, drawn from both sides
+        let edges: [Vec<(u32, u32)>; 4] = [
+            xs.iter().map(|&x| (x, chunk.z.0)).collect(),
+            xs.iter().map(|&x| (x, chunk.z.1)).collect(),
+            zs.iter().map(|&z| (chunk.x.0, z)).collect(),
+            zs.iter().map(|&z| (chunk.x.1, z)).collect(),
+        ];
+
+        for edge in edges.iter() {
+            let base = (data.vertices.len() / 3) as u16;
+            for &(x, z) in edge.iter() {
+                self.push_vertex(&mut data, hm, x, z, 0.0);
+                self.push_vertex(&mut data, hm, x, z, self.skirt_depth);
+            }
+
+            for i in 0..(edge.len() as u16 - 1) {
+                let (a, b) = (base + i * 2, base + i * 2 + 2);
+                Self::push_cell(&mut data, a, b, a + 1, b + 1);
+                Self::push_cell(&mut data, b, a, b + 1, a + 1);
+            }
+        }
+
+        data
+    }
+
+    fn default_material(&self, world: &World) -> Material {
+        let db = world.asset_system();
+        let material = Material::new(db.new_program("default_terrain"));
+
+        let white = db.new_texture("default_white");
+        let first = self.layers
+            .first()
+            .map(|l| l.texture.clone())
+            .unwrap_or_else(|| white.clone());
+
+        // Without a splat map the white one weights the 4 layers evenly,
+        // so the missing layers are the first one
+        material.set("uSplat", self.splat_map.clone().unwrap_or(white));
+
+        let mut tiling = [1.0; MAX_TERRAIN_LAYERS];
+        for i in 0..MAX_TERRAIN_LAYERS {
+            let texture = match self.layers.get(i) {
+                Some(layer) if self.splat_map.is_some() || i == 0 => {
+                    tiling[i] = layer.tiling;
+                    layer.texture.clone()
+                }
+                _ => {
+                    tiling[i] = self.layers.first().map_or(1.0, |l| l.tiling);
+                    first.clone()
+                }
+            };
+
+            texture.set_srgb(true);
+            material.set(format!("uLayer{}", i), texture);
+        }
+
+        material.set(
+            "uTiling",
+            Vector4::new(tiling[0], tiling[1], tiling[2], tiling[3]),
+        );
+
+        material
+    }
+
+    fn build_chunks(&mut self, go: &mut GameObject, world: &mut World) {
+        for chunk in self.chunks.drain(..) {
+            world.remove_game_object(&chunk.object);
+        }
+
+        let hm = match self.heightmap.take() {
+            Some(hm) => hm,
+            None => return,
+        };
+
+        let material = self.material
+            .clone()
+            .unwrap_or_else(|| Rc::new(self.default_material(world)));
+
+        let chunk_size = self.chunk_cells();
+        let (dx, dz) = self.cell_size(&hm);
+
+        for z0 in (0..hm.height - 1).filter(|z| z % chunk_size == 0) {
+            for x0 in (0..hm.width - 1).filter(|x| x % chunk_size == 0) {
+                let x = (x0, (x0 + chunk_size).min(hm.width - 1));
+                let z = (z0, (z0 + chunk_size).min(hm.height - 1));
+
+                let center_height = hm.get(((x.0 + x.1) / 2) as i32, ((z.0 + z.1) / 2) as i32);
+                let center = Vector3::new(
+                    (x.0 + x.1) as f32 * 0.5 * dx,
+                    center_height * self.height,
+                    (z.0 + z.1) as f32 * 0.5 * dz,
+                );
+
+                let buffer = MeshBuffer::new(empty_mesh_data());
+                let object = world.new_game_object();
+                {
+                    let mut object_mut = object.borrow_mut();
+
+                    let mut mesh = Mesh::new();
+                    mesh.add_surface(buffer.clone(), material.clone());
+                    object_mut.add_component(mesh);
+                    go.add_child(&object_mut);
+                }
+
+                self.chunks.push(TerrainChunk {
+                    object,
+                    buffer,
+                    x,
+                    z,
+                    center,
+                    lod: None,
+                });
+            }
+        }
+
+        self.heightmap = Some(hm);
+    }
+
+    /// Build the chunks which changed their level of detail
+    fn update_lods(&mut self, world: &World) {
+        let eye = match world.current_camera() {
+            Some(cam) => cam.borrow().eye(),
+            None => return,
+        };
+
+        let hm = match self.heightmap.take() {
+            Some(hm) => hm,
+            None => return,
+        };
+
+        let lod_count = self.lod_count();
+        let mut chunks = ::std::mem::replace(&mut self.chunks, Vec::new());
+
+        for chunk in chunks.iter_mut() {
+            let center = self.model.transform_point(Point3::from_vec(chunk.center));
+            let dist = (center.to_vec() - eye).magnitude();
+
+            let lod = self.lod_distances
+                .iter()
+                .take(lod_count - 1)
+                .filter(|&&d| dist > d)
+                .count();
+
+            if chunk.lod != Some(lod) {
+                chunk.buffer.swap_mesh_data(self.build_chunk(&hm, chunk, lod));
+                chunk.lod = Some(lod);
+            }
+        }
+
+        self.chunks = chunks;
+        self.heightmap = Some(hm);
+    }
+}
+
+impl Actor for Terrain {
+    fn start(&mut self, _go: &mut GameObject, _world: &mut World) {
+        self.dirty = true;
+    }
+
+    fn update(&mut self, go: &mut GameObject, world: &mut World) {
+        self.model = go.transform.as_global_matrix();
+
+        if self.dirty {
+            if self.heightmap.is_none() {
+                self.heightmap = self.heightmap_texture
+                    .as_ref()
+                    .and_then(|t| Heightmap::from_texture(t));
+            }
+
+            if self.heightmap.is_none() {
+                return;
+            }
+
+            self.build_chunks(go, world);
+            self.dirty = false;
+        }
+
+        self.update_lods(world);
+    }
+}
@@ -0,0 +1,72 @@
+//! Pack the files of a directory into an asset bundle, e.g. the `static` directory of a
+//! game for its release. The files are named by their paths from the directory.
+//!
+//! ```text
+//! cargo run --bin pack_bundle -- static static/assets.unb
+//! ```
+extern crate unrust;
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process;
+use unrust::engine::{BundleCompression, BundleWriter};
+
+fn add_dir(writer: &mut BundleWriter, root: &Path, dir: &Path, out: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            add_dir(writer, root, &path, out)?;
+            continue;
+        }
+
+        // The bundle itself may be written in the directory
+        if path == out {
+            continue;
+        }
+
+        let name = path.strip_prefix(root)
+            .unwrap()
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        // The compressed images and sounds are not deflated again
+        writer.compression = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("png") | Some("jpg") | Some("jpeg") | Some("ogg") | Some("mp3") => {
+                BundleCompression::Stored
+            }
+            _ => BundleCompression::Deflate,
+        };
+
+        println!("{}", name);
+        writer.add(&name, fs::read(&path)?);
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        eprintln!("usage: pack_bundle <directory> <bundle>");
+        process::exit(1);
+    }
+
+    let (root, out) = (Path::new(&args[1]), Path::new(&args[2]));
+
+    let mut writer = BundleWriter::new();
+    let bytes = add_dir(&mut writer, root, root, out).and_then(|_| {
+        writer
+            .write()
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{:?}", e)))
+    });
+
+    if let Err(e) = bytes.and_then(|bytes| fs::write(out, bytes)) {
+        eprintln!("pack_bundle: {}", e);
+        process::exit(1);
+    }
+}
@@ -1,19 +1,23 @@
 use std::rc::Rc;
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::time::SystemTime;
 
 use engine::asset::{CubeMesh, PlaneMesh, QuadMesh, SkyboxMesh};
 use engine::asset::default_font_bitmap::DEFAULT_FONT_DATA;
 use engine::asset::fs;
 use engine::asset::loader;
+use engine::asset::{AssetSource, BundleSource, DirectorySource, HttpSource, LoadHandle, LoadItem,
+                    LoadState};
 use engine::asset::Resource;
 
-use engine::{Material, MeshBuffer, ShaderFs, ShaderProgram, ShaderVs, Texture, TextureFiltering,
-             TextureImage};
+use engine::{CompressedFormat, Font, Material, MeshBuffer, ShaderFs, ShaderProgram, ShaderVs,
+             Texture, TextureAsset, TextureAtlas, TextureFiltering, TextureImage};
 use std::fmt::Debug;
 use std::ops::Deref;
-use futures::{Async, Future};
+use futures::{future, Async, Future};
 use std::boxed::FnBox;
+use uni_app;
 
 use image;
 use image::ImageBuffer;
@@ -45,12 +49,46 @@ pub trait AssetSystem {
 
     fn new_program(&self, name: &str) -> Rc<ShaderProgram>;
 
+    /// A `{fmt}` in `name` is replaced by the suffix of the first compressed format the
+    /// gpu supports, e.g. "brick.{fmt}" loads "brick.astc.ktx", "brick.etc2.ktx",
+    /// "brick.s3tc.ktx" or "brick.png" if none is, see `set_compressed_formats`
     fn new_texture(&self, name: &str) -> Rc<Texture>;
 
     fn new_mesh_buffer(&self, name: &str) -> Rc<MeshBuffer>;
 
+    fn new_font(&self, name: &str) -> Rc<Font>;
+
+    /// The atlas of a TexturePacker JSON file, see `TextureAtlas`
+    fn new_atlas(&self, name: &str) -> Rc<TextureAtlas>;
+
+    /// The compressed texture formats of the gpu in the order of preference, the engine
+    /// sets the ones of `RenderCaps::compressed_formats`
+    fn set_compressed_formats(&self, formats: &[CompressedFormat]);
+
+    /// Open the files whose names start with `mount_point` from `source`, e.g. "" for
+    /// all the files or "textures/". The sources of higher priorities are asked first,
+    /// the ones mounted last first among the same priority. The files which no source
+    /// has are opened from the file system of the engine
+    fn mount(&self, mount_point: &str, priority: i32, source: Box<AssetSource>);
+
+    /// Mount the directory `dir` of the file system of the engine, see `DirectorySource`
+    fn mount_dir(&self, mount_point: &str, priority: i32, dir: &str);
+
+    /// Mount the files under `base_url` on the web, see `HttpSource`
+    fn mount_url(&self, mount_point: &str, priority: i32, base_url: &str);
+
+    /// Mount the bundle file `name`, see `BundleSource`. The files in it are then opened
+    /// from it instead of the loose files of the same names
+    fn mount_bundle(&self, mount_point: &str, priority: i32, name: &str);
+
+    /// Load the model file `name` and give it to `f` once loaded. The materials of the
+    /// OBJ files are built by `mh`, the glTF (.gltf and .glb) files have their own
     fn new_prefab(&self, name: &str, mh: MaterialHandler, f: PrefabHandler);
 
+    /// Load the assets of `items` together, e.g. behind a loading screen. The handle
+    /// tells the progress of the group and calls its callback once it is done
+    fn load_group(&self, items: &[LoadItem]) -> LoadHandle;
+
     fn reset(&mut self);
 
     fn step(&mut self);
@@ -58,6 +96,81 @@ pub trait AssetSystem {
     fn loading_files(&self) -> Vec<String>;
 
     fn execute(&self, AssetTask);
+
+    /// Load the files of the program or the texture `name` again and swap them into
+    /// the asset once loaded. On native the changed files are reloaded automatically
+    fn reload(&self, name: &str);
+
+    /// Unload the cached assets which only the asset system holds, e.g. the ones of a
+    /// finished level, the built-in ones are kept. The gpu objects of the textures and
+    /// the mesh buffers are deleted once they are dropped. The number of the assets
+    /// unloaded is returned
+    fn unload_unused(&self) -> usize;
+
+    /// Numbers of the cached assets by type
+    fn stats(&self) -> AssetStats;
+
+    /// The type, the name and the number of handles of the cached assets which are held
+    /// outside of the asset system, e.g. to find the ones a finished level still holds
+    fn live_assets(&self) -> Vec<(&'static str, String, usize)>;
+}
+
+/// Cached assets of a type, see `AssetSystem::stats`
+#[derive(Copy, Clone, Debug, Default)]
+pub struct AssetCount {
+    pub cached: usize,
+    /// The assets held outside of the asset system, the other ones are unloaded by
+    /// `unload_unused` but the built-in ones
+    pub used: usize,
+}
+
+/// Numbers of the cached assets by type, e.g. to hunt the leaks of the levels
+#[derive(Copy, Clone, Debug, Default)]
+pub struct AssetStats {
+    pub textures: AssetCount,
+    pub programs: AssetCount,
+    pub mesh_buffers: AssetCount,
+    pub fonts: AssetCount,
+    pub atlases: AssetCount,
+    /// Estimated bytes of the images of the cached textures which are loaded
+    pub texture_memory: usize,
+}
+
+fn count_assets<R>(hm: &RefCell<HashMap<String, Rc<R>>>) -> AssetCount {
+    let hm = hm.borrow();
+
+    AssetCount {
+        cached: hm.len(),
+        used: hm.values().filter(|a| Rc::strong_count(a) > 1).count(),
+    }
+}
+
+/// Remove the assets of `hm` which only the cache holds, the names of the removed
+/// ones are returned
+fn unload_assets<R>(hm: &RefCell<HashMap<String, Rc<R>>>, keep: &HashSet<String>) -> Vec<String> {
+    let mut hm = hm.borrow_mut();
+
+    let unused: Vec<String> = hm.iter()
+        .filter(|&(name, a)| Rc::strong_count(a) == 1 && !keep.contains(name))
+        .map(|(name, _)| name.clone())
+        .collect();
+
+    for name in unused.iter() {
+        hm.remove(name);
+    }
+
+    unused
+}
+
+fn live_assets<R>(
+    kind: &'static str,
+    hm: &RefCell<HashMap<String, Rc<R>>>,
+) -> Vec<(&'static str, String, usize)> {
+    hm.borrow()
+        .iter()
+        .filter(|&(_, a)| Rc::strong_count(a) > 1)
+        .map(|(name, a)| (kind, name.clone(), Rc::strong_count(a) - 1))
+        .collect()
 }
 
 pub trait Asset {
@@ -92,15 +205,74 @@ pub trait LoadableAsset: Asset {
 
 type PrefabFuture = Box<Future<Item = loader::Prefab, Error = AssetError>>;
 
+/// Steps between the checks of the watched files
+const WATCH_INTERVAL: u32 = 30;
+
+/// The asset of a watched file and the modification time of the file when it was opened
+struct WatchedFile {
+    asset: String,
+    modified: SystemTime,
+}
+
+/// A source of `mount` under its mount point
+struct Mount {
+    point: String,
+    priority: i32,
+    source: Rc<AssetSource>,
+}
+
+/// A file opened from a source, named like the loose file
+struct SourceFile {
+    name: String,
+    file: Box<fs::File>,
+}
+
+impl fs::File for SourceFile {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn read_binary(&mut self) -> Result<Vec<u8>, fs::FileIoError> {
+        self.file.read_binary()
+    }
+}
+
+/// A task swapping in a reloaded asset, the failures are printed
+fn reload_task<F>(name: &str, poll: F) -> AssetTask
+where
+    F: FnMut() -> AssetResult<Async<()>> + 'static,
+{
+    let name = name.to_string();
+
+    Box::new(future::poll_fn(poll).or_else(move |e| {
+        uni_app::App::print(format!("Failed to reload {}, reason: {:?}\n", name, e));
+        Ok::<(), AssetError>(())
+    }))
+}
+
 pub struct AssetDatabaseContext<FS> {
     fs: FS,
     path: String,
     textures: RefCell<HashMap<String, Rc<Texture>>>,
     mesh_buffers: RefCell<HashMap<String, Rc<MeshBuffer>>>,
     programs: RefCell<HashMap<String, Rc<ShaderProgram>>>,
+    fonts: RefCell<HashMap<String, Rc<Font>>>,
+    atlases: RefCell<HashMap<String, Rc<TextureAtlas>>>,
+    /// The mounted sources by descending priority
+    mounts: RefCell<Vec<Mount>>,
+    /// The compressed texture formats of the gpu, see `AssetSystem::new_texture`
+    compressed_formats: RefCell<Vec<CompressedFormat>>,
+    /// Names of the assets made by `setup`, they are never unloaded
+    builtins: RefCell<HashSet<String>>,
 
     pending_prefabs: RefCell<Vec<(PrefabHandler, PrefabFuture)>>,
     pending_tasks: RefCell<Vec<AssetTask>>,
+    pending_groups: RefCell<Vec<LoadHandle>>,
+
+    /// The program or the texture which the files opened are gathered for
+    gathering: RefCell<Option<String>>,
+    watched_files: RefCell<HashMap<String, WatchedFile>>,
+    watch_steps: Cell<u32>,
 }
 
 pub struct AssetDatabase<FS, F>
@@ -141,17 +313,64 @@ where
     F: fs::File + 'static,
 {
     fn new_file(&self, name: &str) -> fs::FileFuture {
-        self.fs.open(&self.get_filename(name))
+        let filename = self.get_filename(name);
+
+        let sources: Vec<(Rc<AssetSource>, String)> = self.mounts
+            .borrow()
+            .iter()
+            .filter(|mount| name.starts_with(&mount.point))
+            .map(|mount| (mount.source.clone(), name[mount.point.len()..].to_string()))
+            .collect();
+
+        // Only the loose files are watched, a file under a mount point may come from a
+        // bundle or a url whose modification is not known
+        if sources.is_empty() {
+            self.watch_file(&filename);
+            return self.fs.open(&filename);
+        }
+
+        // The sources are asked in turn until one of them has the file, the file system
+        // is asked last and its failure is the one of the file
+        let db = self.clone();
+        let mut sources = sources.into_iter();
+        let mut current: Option<fs::FileFuture> = None;
+        let mut loose = false;
+
+        Box::new(future::poll_fn(move || loop {
+            if let Some(ref mut file) = current {
+                match file.poll() {
+                    Ok(Async::Ready(file)) => {
+                        let file: Box<fs::File> = Box::new(SourceFile {
+                            name: filename.clone(),
+                            file,
+                        });
+                        return Ok(Async::Ready(file));
+                    }
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    Err(_) if !loose => (),
+                    Err(e) => return Err(e),
+                }
+            }
+
+            current = match sources.next() {
+                Some((source, path)) => source.open(&path),
+                None => {
+                    loose = true;
+                    Some(db.fs.open(&filename))
+                }
+            };
+        }))
     }
 
     fn new_program(&self, name: &str) -> Rc<ShaderProgram> {
         let mut a = self.programs.borrow_mut();
-        self.new_asset(&mut a, name)
+        self.gather_watched(name, || self.new_asset(&mut a, name))
     }
 
     fn new_texture(&self, name: &str) -> Rc<Texture> {
+        let name = &self.texture_variant(name);
         let mut a = self.textures.borrow_mut();
-        self.new_asset(&mut a, name)
+        self.gather_watched(name, || self.new_asset(&mut a, name))
     }
 
     fn new_mesh_buffer(&self, name: &str) -> Rc<MeshBuffer> {
@@ -159,23 +378,127 @@ where
         self.new_asset(&mut a, name)
     }
 
+    fn new_font(&self, name: &str) -> Rc<Font> {
+        let mut a = self.fonts.borrow_mut();
+        self.new_asset(&mut a, name)
+    }
+
+    fn new_atlas(&self, name: &str) -> Rc<TextureAtlas> {
+        let mut a = self.atlases.borrow_mut();
+        self.new_asset(&mut a, name)
+    }
+
+    fn set_compressed_formats(&self, formats: &[CompressedFormat]) {
+        *self.compressed_formats.borrow_mut() = formats.to_vec();
+    }
+
+    fn mount(&self, mount_point: &str, priority: i32, source: Box<AssetSource>) {
+        let mut mounts = self.mounts.borrow_mut();
+        let i = mounts
+            .iter()
+            .position(|mount| mount.priority <= priority)
+            .unwrap_or(mounts.len());
+
+        mounts.insert(
+            i,
+            Mount {
+                point: mount_point.to_string(),
+                priority,
+                source: Rc::from(source),
+            },
+        );
+    }
+
+    fn mount_dir(&self, mount_point: &str, priority: i32, dir: &str) {
+        let source = DirectorySource::<FS>::new(dir);
+        self.mount(mount_point, priority, Box::new(source));
+    }
+
+    fn mount_url(&self, mount_point: &str, priority: i32, base_url: &str) {
+        let source = HttpSource::<FS>::new(base_url);
+        self.mount(mount_point, priority, Box::new(source));
+    }
+
+    fn mount_bundle(&self, mount_point: &str, priority: i32, name: &str) {
+        // The bundle file itself is opened from the sources mounted before
+        let source = BundleSource::new(name, self.new_file(name));
+        self.mount(mount_point, priority, Box::new(source));
+    }
+
     fn reset(&mut self) {
         self.textures.borrow_mut().clear();
         self.mesh_buffers.borrow_mut().clear();
         self.programs.borrow_mut().clear();
+        self.fonts.borrow_mut().clear();
+        self.atlases.borrow_mut().clear();
+        self.watched_files.borrow_mut().clear();
 
         self.setup();
     }
 
     fn new_prefab(&self, name: &str, mh: MaterialHandler, f: PrefabHandler) {
-        let prefab = loader::Prefab::load_future(self.clone(), self.new_file(name), mh);
+        let file = self.new_file(name);
+        let prefab = if loader::is_gltf_name(name) {
+            loader::Prefab::load_gltf_future(self.clone(), file)
+        } else {
+            loader::Prefab::load_future(self.clone(), file, mh)
+        };
+
+        let name = name.to_string();
+        let prefab: PrefabFuture = Box::new(prefab.map(move |prefab| {
+            for warning in prefab.warnings.iter() {
+                uni_app::App::print(format!("{}: {}\n", name, warning));
+            }
+            prefab
+        }));
+
         self.pending_prefabs.borrow_mut().push((f, prefab));
     }
 
+    fn load_group(&self, items: &[LoadItem]) -> LoadHandle {
+        let assets = items
+            .iter()
+            .map(|item| {
+                let asset: Rc<LoadState> = match *item {
+                    LoadItem::Texture(name) => self.new_texture(name),
+                    LoadItem::Program(name) => self.new_program(name),
+                    LoadItem::Font(name) => self.new_font(name),
+                    LoadItem::Atlas(name) => self.new_atlas(name),
+                };
+
+                (item.name().to_string(), asset)
+            })
+            .collect();
+
+        let handle = LoadHandle::new(assets);
+        self.pending_groups.borrow_mut().push(handle.clone());
+        handle
+    }
+
     fn execute(&self, task: AssetTask) {
         self.pending_tasks.borrow_mut().push(task);
     }
 
+    fn reload(&self, name: &str) {
+        let program = self.programs.borrow().get(name).cloned();
+        if let Some(program) = program {
+            let resource = self.gather_watched(name, || {
+                ShaderProgram::load(self, ShaderProgram::gather(self, name))
+            });
+
+            self.execute(reload_task(name, move || program.poll_reload(&resource)));
+        }
+
+        let texture = self.textures.borrow().get(name).cloned();
+        if let Some(texture) = texture {
+            let asset = self.gather_watched(name, || {
+                Texture::load(self, Texture::gather(self, name))
+            });
+
+            self.execute(reload_task(name, move || texture.poll_reload(&asset)));
+        }
+    }
+
     fn new() -> AssetDatabase<FS, F> {
         let mut db = AssetDatabase {
             context: Rc::new(AssetDatabaseContext {
@@ -184,8 +507,17 @@ where
                 textures: RefCell::new(HashMap::new()),
                 mesh_buffers: RefCell::new(HashMap::new()),
                 programs: RefCell::new(HashMap::new()),
+                fonts: RefCell::new(HashMap::new()),
+                atlases: RefCell::new(HashMap::new()),
+                mounts: RefCell::new(Vec::new()),
+                compressed_formats: RefCell::new(Vec::new()),
+                builtins: RefCell::new(HashSet::new()),
                 pending_prefabs: RefCell::new(Vec::new()),
                 pending_tasks: RefCell::new(Vec::new()),
+                pending_groups: RefCell::new(Vec::new()),
+                gathering: RefCell::new(None),
+                watched_files: RefCell::new(HashMap::new()),
+                watch_steps: Cell::new(0),
             }),
         };
 
@@ -194,6 +526,8 @@ where
     }
 
     fn step(&mut self) {
+        self.reload_changed_files();
+
         {
             let pending_prefabs = self.pending_prefabs
                 .borrow_mut()
@@ -237,11 +571,71 @@ where
 
             *self.pending_tasks.borrow_mut() = new_pending;
         }
+
+        {
+            // The callbacks of the groups may load other groups
+            let pending_groups = self.pending_groups
+                .borrow_mut()
+                .drain(0..)
+                .collect::<Vec<_>>();
+
+            let new_pending = pending_groups
+                .into_iter()
+                .filter(|group| !group.poll())
+                .collect::<Vec<_>>();
+
+            self.pending_groups.borrow_mut().extend(new_pending);
+        }
     }
 
     fn loading_files(&self) -> Vec<String> {
         self.fs.loading_files()
     }
+
+    fn unload_unused(&self) -> usize {
+        let keep = self.builtins.borrow();
+
+        // The atlases hold their textures, they are unloaded first
+        let mut unloaded = unload_assets(&self.atlases, &keep);
+        unloaded.extend(unload_assets(&self.fonts, &keep));
+        unloaded.extend(unload_assets(&self.textures, &keep));
+        unloaded.extend(unload_assets(&self.mesh_buffers, &keep));
+        unloaded.extend(unload_assets(&self.programs, &keep));
+
+        self.watched_files
+            .borrow_mut()
+            .retain(|_, watched| !unloaded.contains(&watched.asset));
+
+        unloaded.len()
+    }
+
+    fn stats(&self) -> AssetStats {
+        let texture_memory = self.textures
+            .borrow()
+            .values()
+            .filter_map(|tex| tex.image_size())
+            .map(|(w, h)| (w * h * 4) as usize)
+            .sum();
+
+        AssetStats {
+            textures: count_assets(&self.textures),
+            programs: count_assets(&self.programs),
+            mesh_buffers: count_assets(&self.mesh_buffers),
+            fonts: count_assets(&self.fonts),
+            atlases: count_assets(&self.atlases),
+            texture_memory,
+        }
+    }
+
+    fn live_assets(&self) -> Vec<(&'static str, String, usize)> {
+        let mut assets = live_assets("texture", &self.textures);
+        assets.extend(live_assets("program", &self.programs));
+        assets.extend(live_assets("mesh_buffer", &self.mesh_buffers));
+        assets.extend(live_assets("font", &self.fonts));
+        assets.extend(live_assets("atlas", &self.atlases));
+
+        assets
+    }
 }
 
 impl<FS, F> AssetDatabase<FS, F>
@@ -249,6 +643,75 @@ where
     FS: fs::FileSystem<File = F> + 'static,
     F: fs::File + 'static,
 {
+    /// The texture file of `name` in the preferred compressed format
+    fn texture_variant(&self, name: &str) -> String {
+        if !name.contains("{fmt}") {
+            return name.to_string();
+        }
+
+        let suffix = self.compressed_formats
+            .borrow()
+            .first()
+            .map_or("png", |format| format.file_suffix());
+
+        name.replace("{fmt}", suffix)
+    }
+
+    /// Run `f` with the files opened by it watched as the files of the asset `name`
+    fn gather_watched<T, F>(&self, name: &str, f: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        // An asset may be made while gathering the files of another one
+        let prev = self.gathering.replace(Some(name.to_string()));
+        let r = f();
+        *self.gathering.borrow_mut() = prev;
+
+        r
+    }
+
+    fn watch_file(&self, filename: &str) {
+        let gathering = self.gathering.borrow();
+        let asset = match *gathering {
+            Some(ref asset) => asset,
+            None => return,
+        };
+
+        if let Some(modified) = self.fs.modified(filename) {
+            self.watched_files.borrow_mut().insert(
+                filename.to_string(),
+                WatchedFile {
+                    asset: asset.clone(),
+                    modified,
+                },
+            );
+        }
+    }
+
+    /// Reload the assets of the watched files which are changed since they were opened
+    fn reload_changed_files(&self) {
+        let steps = self.watch_steps.get() + 1;
+        self.watch_steps.set(steps % WATCH_INTERVAL);
+        if steps < WATCH_INTERVAL {
+            return;
+        }
+
+        let mut changed: Vec<String> = Vec::new();
+        for (filename, watched) in self.watched_files.borrow().iter() {
+            let modified = self.fs.modified(filename);
+            if modified.map_or(false, |m| m != watched.modified)
+                && !changed.contains(&watched.asset)
+            {
+                changed.push(watched.asset.clone());
+            }
+        }
+
+        for name in changed.iter() {
+            uni_app::App::print(format!("Reloading {}...\n", name));
+            self.reload(name);
+        }
+    }
+
     fn new_asset<R>(&self, hm: &mut HashMap<String, Rc<R>>, name: &str) -> Rc<R>
     where
         R: LoadableAsset,
@@ -308,14 +771,88 @@ where
                 Self::new_default_color_texture([0x0, 0xff, 0x0, 0xff]),
             );
 
+            hm.insert(
+                "default_ui_frame".into(),
+                Self::new_default_color_texture([0x44, 0x44, 0x44, 0xe0]),
+            );
+
+            hm.insert(
+                "default_ui_frame_hover".into(),
+                Self::new_default_color_texture([0x66, 0x66, 0x66, 0xe0]),
+            );
+
+            hm.insert(
+                "default_ui_frame_active".into(),
+                Self::new_default_color_texture([0x22, 0x22, 0x22, 0xe0]),
+            );
+
             hm.insert("default".into(), Self::new_default_texture());
+
+            // The environment of the pbr materials without an environment map
+            let environment = Self::new_default_color_cubemap([0x80, 0x80, 0x80, 0xff]);
+            environment.set_srgb(true);
+            hm.insert("default_environment".into(), environment);
         }
 
         {
             let mut hm = self.programs.borrow_mut();
             hm.insert("default".into(), Self::new_default_program());
             hm.insert("default_ui".into(), Self::new_default_ui_program());
+            hm.insert(
+                "default_ui_color".into(),
+                Self::new_default_ui_color_program(),
+            );
+            hm.insert(
+                "default_ui_text".into(),
+                Self::new_default_ui_text_program(),
+            );
+            hm.insert(
+                "default_ui_radial".into(),
+                Self::new_default_ui_radial_program(),
+            );
+            hm.insert("default_unlit".into(), Self::new_default_unlit_program());
+            hm.insert("default_pbr".into(), Self::new_default_pbr_program());
+            hm.insert("default_terrain".into(), Self::new_default_terrain_program());
+            hm.insert("default_skybox".into(), Self::new_default_skybox_program());
+            hm.insert("default_clear".into(), Self::new_default_clear_program());
+            hm.insert(
+                "default_particle".into(),
+                Self::new_default_particle_program(),
+            );
+            hm.insert("default_sprite".into(), Self::new_default_sprite_program());
+            hm.insert("default_line".into(), Self::new_default_line_program());
+            hm.insert(
+                "default_text_billboard".into(),
+                Self::new_default_text_billboard_program(),
+            );
+
+            // The passes of the post effects, see `PostEffect`
+            let post_programs = [
+                ("default_post_threshold", "post_threshold_fs.glsl", DEFAULT_POST_THRESHOLD_FS),
+                ("default_post_blur", "post_blur_fs.glsl", DEFAULT_POST_BLUR_FS),
+                ("default_post_bloom", "post_bloom_fs.glsl", DEFAULT_POST_BLOOM_FS),
+                ("default_post_tonemap", "post_tonemap_fs.glsl", DEFAULT_POST_TONEMAP_FS),
+                ("default_post_vignette", "post_vignette_fs.glsl", DEFAULT_POST_VIGNETTE_FS),
+                ("default_post_fxaa", "post_fxaa_fs.glsl", DEFAULT_POST_FXAA_FS),
+                ("default_post_luminance", "post_luminance_fs.glsl", DEFAULT_POST_LUMINANCE_FS),
+                ("default_post_downsample", "post_downsample_fs.glsl", DEFAULT_POST_DOWNSAMPLE_FS),
+                ("default_post_adapt", "post_adapt_fs.glsl", DEFAULT_POST_ADAPT_FS),
+                (
+                    "default_post_auto_exposure",
+                    "post_auto_exposure_fs.glsl",
+                    DEFAULT_POST_AUTO_EXPOSURE_FS,
+                ),
+            ];
+
+            for &(name, filename, fs) in post_programs.iter() {
+                hm.insert(name.into(), Self::new_default_post_program(filename, fs));
+            }
         }
+
+        let mut builtins = self.builtins.borrow_mut();
+        builtins.extend(self.mesh_buffers.borrow().keys().cloned());
+        builtins.extend(self.textures.borrow().keys().cloned());
+        builtins.extend(self.programs.borrow().keys().cloned());
     }
 
     fn new_default_font_bitmap() -> Rc<Texture> {
@@ -349,6 +886,23 @@ where
         tex
     }
 
+    fn new_default_color_cubemap(color: [u8; 4]) -> Rc<Texture> {
+        let face = || {
+            Resource::new(TextureImage::Rgba(ImageBuffer::from_fn(4, 4, |_, _| {
+                image::Rgba(color)
+            })))
+        };
+
+        Texture::new_from_resource(TextureAsset::Cube([
+            face(),
+            face(),
+            face(),
+            face(),
+            face(),
+            face(),
+        ]))
+    }
+
     fn new_default_texture() -> Rc<Texture> {
         // Construct a new ImageBuffer with the specified width and height.
 
@@ -376,6 +930,98 @@ where
         ShaderProgram::new((Resource::new(vs), Resource::new(fs)))
     }
 
+    pub fn new_default_ui_color_program() -> Rc<ShaderProgram> {
+        let vs = ShaderVs::new("ui_color_vs.glsl", DEFAULT_UI_COLOR_VS);
+        let fs = ShaderFs::new("ui_color_fs.glsl", DEFAULT_UI_COLOR_FS);
+
+        ShaderProgram::new((Resource::new(vs), Resource::new(fs)))
+    }
+
+    pub fn new_default_ui_text_program() -> Rc<ShaderProgram> {
+        let vs = ShaderVs::new("ui_text_vs.glsl", DEFAULT_UI_TEXT_VS);
+        let fs = ShaderFs::new("ui_text_fs.glsl", DEFAULT_UI_TEXT_FS);
+
+        ShaderProgram::new((Resource::new(vs), Resource::new(fs)))
+    }
+
+    pub fn new_default_ui_radial_program() -> Rc<ShaderProgram> {
+        let vs = ShaderVs::new("ui_vs.glsl", DEFAULT_UI_VS);
+        let fs = ShaderFs::new("ui_radial_fs.glsl", DEFAULT_UI_RADIAL_FS);
+
+        ShaderProgram::new((Resource::new(vs), Resource::new(fs)))
+    }
+
+    pub fn new_default_unlit_program() -> Rc<ShaderProgram> {
+        let vs = ShaderVs::new("unlit_vs.glsl", DEFAULT_UNLIT_VS);
+        let fs = ShaderFs::new("unlit_fs.glsl", DEFAULT_UNLIT_FS);
+
+        ShaderProgram::new((Resource::new(vs), Resource::new(fs)))
+    }
+
+    pub fn new_default_pbr_program() -> Rc<ShaderProgram> {
+        let vs = ShaderVs::new("pbr_vs.glsl", DEFAULT_PBR_VS);
+        let fs = ShaderFs::new("pbr_fs.glsl", DEFAULT_PBR_FS);
+
+        ShaderProgram::new((Resource::new(vs), Resource::new(fs)))
+    }
+
+    pub fn new_default_skybox_program() -> Rc<ShaderProgram> {
+        let vs = ShaderVs::new("skybox_vs.glsl", DEFAULT_SKYBOX_VS);
+        let fs = ShaderFs::new("skybox_fs.glsl", DEFAULT_SKYBOX_FS);
+
+        ShaderProgram::new((Resource::new(vs), Resource::new(fs)))
+    }
+
+    pub fn new_default_clear_program() -> Rc<ShaderProgram> {
+        let vs = ShaderVs::new("clear_vs.glsl", DEFAULT_CLEAR_VS);
+        let fs = ShaderFs::new("clear_fs.glsl", DEFAULT_CLEAR_FS);
+
+        ShaderProgram::new((Resource::new(vs), Resource::new(fs)))
+    }
+
+    pub fn new_default_particle_program() -> Rc<ShaderProgram> {
+        let vs = ShaderVs::new("particle_vs.glsl", DEFAULT_PARTICLE_VS);
+        let fs = ShaderFs::new("particle_fs.glsl", DEFAULT_PARTICLE_FS);
+
+        ShaderProgram::new((Resource::new(vs), Resource::new(fs)))
+    }
+
+    /// The sprites are tinted by the vertex colors like the particles
+    pub fn new_default_terrain_program() -> Rc<ShaderProgram> {
+        let vs = ShaderVs::new("phong_vs.glsl", DEFAULT_VS);
+        let fs = ShaderFs::new("terrain_fs.glsl", DEFAULT_TERRAIN_FS);
+
+        ShaderProgram::new((Resource::new(vs), Resource::new(fs)))
+    }
+
+    pub fn new_default_sprite_program() -> Rc<ShaderProgram> {
+        let vs = ShaderVs::new("sprite_vs.glsl", DEFAULT_SPRITE_VS);
+        let fs = ShaderFs::new("particle_fs.glsl", DEFAULT_PARTICLE_FS);
+
+        ShaderProgram::new((Resource::new(vs), Resource::new(fs)))
+    }
+
+    pub fn new_default_line_program() -> Rc<ShaderProgram> {
+        let vs = ShaderVs::new("line_vs.glsl", DEFAULT_LINE_VS);
+        let fs = ShaderFs::new("line_fs.glsl", DEFAULT_LINE_FS);
+
+        ShaderProgram::new((Resource::new(vs), Resource::new(fs)))
+    }
+
+    pub fn new_default_text_billboard_program() -> Rc<ShaderProgram> {
+        let vs = ShaderVs::new("text_billboard_vs.glsl", DEFAULT_TEXT_BILLBOARD_VS);
+        let fs = ShaderFs::new("particle_fs.glsl", DEFAULT_PARTICLE_FS);
+
+        ShaderProgram::new((Resource::new(vs), Resource::new(fs)))
+    }
+
+    pub fn new_default_post_program(filename: &str, code: &str) -> Rc<ShaderProgram> {
+        let vs = ShaderVs::new("post_vs.glsl", DEFAULT_POST_VS);
+        let fs = ShaderFs::new(filename, code);
+
+        ShaderProgram::new((Resource::new(vs), Resource::new(fs)))
+    }
+
     pub fn get_filename(&self, name: &str) -> String {
         format!("{}{}", self.path, name)
     }
@@ -387,3 +1033,43 @@ const DEFAULT_FS: &'static str = include_str!("phong_fs.glsl");
 
 const DEFAULT_UI_VS: &'static str = include_str!("ui_vs.glsl");
 const DEFAULT_UI_FS: &'static str = include_str!("ui_fs.glsl");
+
+const DEFAULT_UI_COLOR_VS: &'static str = include_str!("ui_color_vs.glsl");
+const DEFAULT_UI_COLOR_FS: &'static str = include_str!("ui_color_fs.glsl");
+
+const DEFAULT_UI_TEXT_VS: &'static str = include_str!("ui_text_vs.glsl");
+const DEFAULT_UI_TEXT_FS: &'static str = include_str!("ui_text_fs.glsl");
+
+const DEFAULT_UI_RADIAL_FS: &'static str = include_str!("ui_radial_fs.glsl");
+
+const DEFAULT_UNLIT_VS: &'static str = include_str!("unlit_vs.glsl");
+const DEFAULT_UNLIT_FS: &'static str = include_str!("unlit_fs.glsl");
+
+const DEFAULT_PBR_VS: &'static str = include_str!("pbr_vs.glsl");
+const DEFAULT_PBR_FS: &'static str = include_str!("pbr_fs.glsl");
+const DEFAULT_TERRAIN_FS: &'static str = include_str!("terrain_fs.glsl");
+
+const DEFAULT_SKYBOX_VS: &'static str = include_str!("skybox_vs.glsl");
+const DEFAULT_SKYBOX_FS: &'static str = include_str!("skybox_fs.glsl");
+
+const DEFAULT_CLEAR_VS: &'static str = include_str!("clear_vs.glsl");
+const DEFAULT_CLEAR_FS: &'static str = include_str!("clear_fs.glsl");
+
+const DEFAULT_PARTICLE_VS: &'static str = include_str!("particle_vs.glsl");
+const DEFAULT_PARTICLE_FS: &'static str = include_str!("particle_fs.glsl");
+const DEFAULT_SPRITE_VS: &'static str = include_str!("sprite_vs.glsl");
+const DEFAULT_LINE_VS: &'static str = include_str!("line_vs.glsl");
+const DEFAULT_LINE_FS: &'static str = include_str!("line_fs.glsl");
+const DEFAULT_TEXT_BILLBOARD_VS: &'static str = include_str!("text_billboard_vs.glsl");
+
+const DEFAULT_POST_VS: &'static str = include_str!("post_vs.glsl");
+const DEFAULT_POST_THRESHOLD_FS: &'static str = include_str!("post_threshold_fs.glsl");
+const DEFAULT_POST_BLUR_FS: &'static str = include_str!("post_blur_fs.glsl");
+const DEFAULT_POST_BLOOM_FS: &'static str = include_str!("post_bloom_fs.glsl");
+const DEFAULT_POST_TONEMAP_FS: &'static str = include_str!("post_tonemap_fs.glsl");
+const DEFAULT_POST_VIGNETTE_FS: &'static str = include_str!("post_vignette_fs.glsl");
+const DEFAULT_POST_FXAA_FS: &'static str = include_str!("post_fxaa_fs.glsl");
+const DEFAULT_POST_LUMINANCE_FS: &'static str = include_str!("post_luminance_fs.glsl");
+const DEFAULT_POST_DOWNSAMPLE_FS: &'static str = include_str!("post_downsample_fs.glsl");
+const DEFAULT_POST_ADAPT_FS: &'static str = include_str!("post_adapt_fs.glsl");
+const DEFAULT_POST_AUTO_EXPOSURE_FS: &'static str = include_str!("post_auto_exposure_fs.glsl");
@@ -0,0 +1,374 @@
+use engine::asset::fs::FileIoError;
+use engine::asset::{AssetError, AssetResult};
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+
+const BUNDLE_MAGIC: &'static [u8; 4] = b"UNRB";
+const BUNDLE_VERSION: u32 = 1;
+
+/// How the blob of a file is stored in a bundle
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BundleCompression {
+    Stored,
+    Deflate,
+}
+
+impl BundleCompression {
+    fn tag(&self) -> u8 {
+        match *self {
+            BundleCompression::Stored => 0,
+            BundleCompression::Deflate => 1,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Option<BundleCompression> {
+        match tag {
+            0 => Some(BundleCompression::Stored),
+            1 => Some(BundleCompression::Deflate),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct BundleEntry {
+    compression: BundleCompression,
+    /// Offset of the blob in the bundle
+    offset: usize,
+    packed_len: usize,
+    len: usize,
+}
+
+/// Many asset files packed into a single one, e.g. to download one file instead of one
+/// request per asset on the web.
+///
+/// A bundle is a little endian index of the names, the compressions, the offsets and
+/// the sizes of its files followed by their blobs. It is written offline by
+/// `BundleWriter` and mounted by `AssetSystem::mount_bundle` or as a `BundleSource`, the
+/// files in it are then opened by their names like the loose ones
+#[derive(Debug)]
+pub struct Bundle {
+    entries: HashMap<String, BundleEntry>,
+    bytes: Vec<u8>,
+}
+
+/// Reads the index of a bundle
+struct IndexReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> IndexReader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let end = self.pos.checked_add(n)?;
+        let s = self.bytes.get(self.pos..end)?;
+        self.pos = end;
+        Some(s)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|s| s[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4).map(|s| {
+            s[0] as u32 | (s[1] as u32) << 8 | (s[2] as u32) << 16 | (s[3] as u32) << 24
+        })
+    }
+}
+
+fn push_u32(out: &mut Vec<u8>, v: u32) {
+    out.extend_from_slice(&[v as u8, (v >> 8) as u8, (v >> 16) as u8, (v >> 24) as u8]);
+}
+
+impl Bundle {
+    /// Read the bundle of `bytes`, `path` is the name of the bundle in the errors
+    pub fn parse(path: &str, bytes: Vec<u8>) -> AssetResult<Bundle> {
+        let invalid = |len: usize, reason: &str| AssetError::InvalidFormat {
+            path: path.to_string(),
+            len,
+            reason: reason.to_string(),
+        };
+
+        let entries = {
+            let mut r = IndexReader {
+                bytes: &bytes,
+                pos: 0,
+            };
+
+            if r.take(4) != Some(&BUNDLE_MAGIC[..]) {
+                return Err(invalid(bytes.len(), "not a bundle"));
+            }
+            if r.u32() != Some(BUNDLE_VERSION) {
+                return Err(invalid(bytes.len(), "unsupported bundle version"));
+            }
+
+            let count = r.u32()
+                .ok_or_else(|| invalid(bytes.len(), "truncated index"))?;
+
+            let mut entries = HashMap::new();
+            for _ in 0..count {
+                let (name, entry) = Self::parse_entry(&mut r)
+                    .ok_or_else(|| invalid(bytes.len(), "invalid index"))?;
+                entries.insert(name, entry);
+            }
+
+            // The offsets in the file are from the end of the index
+            for entry in entries.values_mut() {
+                entry.offset = entry
+                    .offset
+                    .checked_add(r.pos)
+                    .ok_or_else(|| invalid(bytes.len(), "invalid index"))?;
+            }
+
+            entries
+        };
+
+        let out_of_bounds = |entry: &BundleEntry| {
+            entry
+                .offset
+                .checked_add(entry.packed_len)
+                .map_or(true, |end| end > bytes.len())
+        };
+
+        if entries.values().any(out_of_bounds) {
+            return Err(invalid(bytes.len(), "truncated blobs"));
+        }
+
+        Ok(Bundle { entries, bytes })
+    }
+
+    fn parse_entry(r: &mut IndexReader) -> Option<(String, BundleEntry)> {
+        let name_len = r.u32()? as usize;
+        let name = String::from_utf8(r.take(name_len)?.to_vec()).ok()?;
+        let compression = BundleCompression::from_tag(r.u8()?)?;
+
+        let entry = BundleEntry {
+            compression,
+            offset: r.u32()? as usize,
+            packed_len: r.u32()? as usize,
+            len: r.u32()? as usize,
+        };
+
+        Some((name, entry))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    /// The names of the files in the bundle
+    pub fn names(&self) -> Vec<String> {
+        self.entries.keys().cloned().collect()
+    }
+
+    /// The content of the file `name`, None if it is not in the bundle
+    pub fn read(&self, name: &str) -> Option<Result<Vec<u8>, FileIoError>> {
+        let entry = self.entries.get(name)?;
+        let blob = &self.bytes[entry.offset..entry.offset + entry.packed_len];
+
+        let data = match entry.compression {
+            BundleCompression::Stored => Ok(blob.to_vec()),
+            BundleCompression::Deflate => {
+                // The size in the index is not trusted, deflate inflates at most 1032 times
+                let capacity = entry.len.min(entry.packed_len.saturating_mul(1032));
+                let mut data = Vec::with_capacity(capacity);
+                DeflateDecoder::new(blob)
+                    .take(entry.len as u64 + 1)
+                    .read_to_end(&mut data)
+                    .map(|_| data)
+                    .map_err(FileIoError::from)
+            }
+        };
+
+        Some(data.and_then(|data| {
+            if data.len() == entry.len {
+                Ok(data)
+            } else {
+                let reason = format!("{} is not of the size in the bundle index", name);
+                Err(io::Error::new(io::ErrorKind::InvalidData, reason).into())
+            }
+        }))
+    }
+}
+
+/// Write the files of a bundle, e.g. in the build of the release of a game
+pub struct BundleWriter {
+    /// Compression of the files added next, deflate by default
+    pub compression: BundleCompression,
+    files: Vec<(String, BundleCompression, Vec<u8>)>,
+}
+
+impl BundleWriter {
+    pub fn new() -> BundleWriter {
+        BundleWriter {
+            compression: BundleCompression::Deflate,
+            files: Vec::new(),
+        }
+    }
+
+    /// Add the file `name` of the content `data`, it replaces a file of the same name
+    pub fn add(&mut self, name: &str, data: Vec<u8>) {
+        self.files.retain(|&(ref n, _, _)| n != name);
+        self.files.push((name.to_string(), self.compression, data));
+    }
+
+    /// The bytes of the bundle of the files added
+    pub fn write(&self) -> Result<Vec<u8>, FileIoError> {
+        let mut index = Vec::new();
+        let mut blobs = Vec::new();
+
+        index.extend_from_slice(BUNDLE_MAGIC);
+        push_u32(&mut index, BUNDLE_VERSION);
+        push_u32(&mut index, self.files.len() as u32);
+
+        for &(ref name, compression, ref data) in self.files.iter() {
+            let packed = match compression {
+                BundleCompression::Stored => data.clone(),
+                BundleCompression::Deflate => {
+                    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+                    encoder.write_all(data)?;
+                    encoder.finish()?
+                }
+            };
+
+            push_u32(&mut index, name.len() as u32);
+            index.extend_from_slice(name.as_bytes());
+            index.push(compression.tag());
+            push_u32(&mut index, blobs.len() as u32);
+            push_u32(&mut index, packed.len() as u32);
+            push_u32(&mut index, data.len() as u32);
+
+            blobs.extend_from_slice(&packed);
+        }
+
+        index.extend_from_slice(&blobs);
+        Ok(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(compression: BundleCompression) -> Vec<u8> {
+        let mut writer = BundleWriter::new();
+        writer.compression = compression;
+        writer.add("a.txt", b"hello bundle".to_vec());
+        writer.add("dir/b.bin", (0..=255u8).cycle().take(3000).collect());
+        writer.add("empty", Vec::new());
+        writer.write().unwrap()
+    }
+
+    fn reason(r: AssetResult<Bundle>) -> String {
+        match r {
+            Err(AssetError::InvalidFormat { reason, .. }) => reason,
+            other => panic!("expected InvalidFormat, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        for &compression in [BundleCompression::Stored, BundleCompression::Deflate].iter() {
+            let bundle = Bundle::parse("test.unb", sample(compression)).unwrap();
+
+            let mut names = bundle.names();
+            names.sort();
+            assert_eq!(names, vec!["a.txt", "dir/b.bin", "empty"]);
+
+            assert_eq!(bundle.read("a.txt").unwrap().unwrap(), b"hello bundle".to_vec());
+            let b: Vec<u8> = (0..=255u8).cycle().take(3000).collect();
+            assert_eq!(bundle.read("dir/b.bin").unwrap().unwrap(), b);
+            assert_eq!(bundle.read("empty").unwrap().unwrap(), Vec::<u8>::new());
+            assert!(bundle.read("missing").is_none());
+            assert!(!bundle.contains("missing"));
+        }
+    }
+
+    #[test]
+    fn add_replaces_same_name() {
+        let mut writer = BundleWriter::new();
+        writer.add("a", b"1".to_vec());
+        writer.add("a", b"2".to_vec());
+
+        let bundle = Bundle::parse("test.unb", writer.write().unwrap()).unwrap();
+        assert_eq!(bundle.names(), vec!["a"]);
+        assert_eq!(bundle.read("a").unwrap().unwrap(), b"2".to_vec());
+    }
+
+    #[test]
+    fn truncated_header() {
+        let bytes = sample(BundleCompression::Deflate);
+
+        assert_eq!(reason(Bundle::parse("t", Vec::new())), "not a bundle");
+        assert_eq!(reason(Bundle::parse("t", bytes[..6].to_vec())), "unsupported bundle version");
+        assert_eq!(reason(Bundle::parse("t", bytes[..10].to_vec())), "truncated index");
+        assert_eq!(reason(Bundle::parse("t", bytes[..20].to_vec())), "invalid index");
+        assert_eq!(
+            reason(Bundle::parse("t", bytes[..bytes.len() - 1].to_vec())),
+            "truncated blobs"
+        );
+    }
+
+    #[test]
+    fn corrupt_header() {
+        let bytes = sample(BundleCompression::Stored);
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        assert_eq!(reason(Bundle::parse("t", bad_magic)), "not a bundle");
+
+        let mut bad_version = bytes.clone();
+        bad_version[4] = 2;
+        assert_eq!(reason(Bundle::parse("t", bad_version)), "unsupported bundle version");
+
+        // The first entry is named "a.txt", its tag follows the name
+        let mut bad_tag = bytes.clone();
+        bad_tag[12 + 4 + 5] = 7;
+        assert_eq!(reason(Bundle::parse("t", bad_tag)), "invalid index");
+
+        // An offset which overflows once the size of the index is added
+        let mut bad_offset = bytes.clone();
+        for b in bad_offset[12 + 4 + 5 + 1..12 + 4 + 5 + 5].iter_mut() {
+            *b = 0xff;
+        }
+        let reason = reason(Bundle::parse("t", bad_offset));
+        assert!(reason == "invalid index" || reason == "truncated blobs");
+    }
+
+    fn is_invalid_data(r: Option<Result<Vec<u8>, FileIoError>>) -> bool {
+        match r {
+            Some(Err(FileIoError::IoError(ref e))) => e.kind() == io::ErrorKind::InvalidData,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn tampered_len() {
+        for &compression in [BundleCompression::Stored, BundleCompression::Deflate].iter() {
+            let bytes = sample(compression);
+
+            // The unpacked size of "a.txt" follows its offset and its packed size
+            let len_at = 12 + 4 + 5 + 1 + 8;
+
+            let mut huge = bytes.clone();
+            for b in huge[len_at..len_at + 4].iter_mut() {
+                *b = 0xff;
+            }
+            let bundle = Bundle::parse("t", huge).unwrap();
+            assert!(is_invalid_data(bundle.read("a.txt")));
+
+            let mut short = bytes.clone();
+            short[len_at] -= 1;
+            let bundle = Bundle::parse("t", short).unwrap();
+            assert!(is_invalid_data(bundle.read("a.txt")));
+
+            // The other files are still read
+            assert_eq!(bundle.read("empty").unwrap().unwrap(), Vec::<u8>::new());
+        }
+    }
+}
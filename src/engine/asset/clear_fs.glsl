@@ -0,0 +1,11 @@
+#ifndef GL_ES
+#define gl_FragColor FragColor
+out vec4 FragColor;
+#endif
+
+// The alpha is 0 to keep the color when only the depth is cleared
+uniform vec4 uColor;
+
+void main(void) {
+    gl_FragColor = uColor;
+}
@@ -0,0 +1,10 @@
+#ifndef GL_ES
+#define attribute in
+#endif
+
+attribute vec3 aVertexPosition;
+
+void main(void) {
+    // On the far plane, such that the depth is cleared to 1
+    gl_Position = vec4(aVertexPosition.xy, 1.0, 1.0);
+}
@@ -1,6 +1,7 @@
 use std::default::Default;
 use futures::prelude::*;
 use std;
+use std::time::SystemTime;
 
 pub type FileFuture = Box<Future<Item = Box<File>, Error = FileIoError>>;
 
@@ -10,6 +11,11 @@ pub trait FileSystem: Default {
     fn open(&self, filename: &str) -> FileFuture;
 
     fn loading_files(&self) -> Vec<String>;
+
+    /// Modification time of the file, None if it could not be watched, e.g. on the web
+    fn modified(&self, _filename: &str) -> Option<SystemTime> {
+        None
+    }
 }
 
 pub trait File {
@@ -18,6 +24,35 @@ pub trait File {
     fn read_binary(&mut self) -> Result<Vec<u8>, FileIoError>;
 }
 
+/// A file whose content is in memory, e.g. opened from a bundle
+pub struct MemoryFile {
+    name: String,
+    data: Result<Vec<u8>, String>,
+}
+
+impl MemoryFile {
+    /// The file of `data`, the error of `data` is given by `read_binary`
+    pub fn new(name: &str, data: Result<Vec<u8>, FileIoError>) -> MemoryFile {
+        MemoryFile {
+            name: name.to_string(),
+            data: data.map_err(|e| format!("{:?}", e)),
+        }
+    }
+}
+
+impl File for MemoryFile {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn read_binary(&mut self) -> Result<Vec<u8>, FileIoError> {
+        match self.data {
+            Ok(ref data) => Ok(data.clone()),
+            Err(ref e) => Err(FileIoError::Unknown(e.clone())),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum FileIoError {
     NotReady,
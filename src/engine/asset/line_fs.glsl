@@ -0,0 +1,11 @@
+#ifndef GL_ES
+#define varying in
+#define gl_FragColor FragColor
+out vec4 FragColor;
+#endif
+
+varying vec4 vColor;
+
+void main(void) {
+    gl_FragColor = vColor;
+}
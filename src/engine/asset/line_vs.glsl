@@ -0,0 +1,18 @@
+#ifndef GL_ES
+#define attribute in
+#define varying out
+#endif
+
+attribute vec3 aVertexPosition;
+attribute vec4 aVertexColor;
+
+uniform mat4 uMVMatrix;
+uniform mat4 uPMatrix;
+
+varying vec4 vColor;
+
+void main(void) {
+    vColor = aVertexColor;
+
+    gl_Position = uPMatrix * uMVMatrix * vec4(aVertexPosition, 1.0);
+}
@@ -0,0 +1,188 @@
+use engine::asset::{AssetError, AssetResult};
+
+use std::boxed::FnBox;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// An asset whose loading can be waited for by a `LoadHandle`
+pub trait LoadState {
+    /// Ok once the asset is loaded, `AssetError::NotReady` while it is loading and
+    /// the error of the load if it failed
+    fn load_state(&self) -> AssetResult<()>;
+}
+
+/// An asset of a group loaded by `AssetSystem::load_group`
+#[derive(Debug, Copy, Clone)]
+pub enum LoadItem<'a> {
+    Texture(&'a str),
+    Program(&'a str),
+    Font(&'a str),
+    Atlas(&'a str),
+}
+
+impl<'a> LoadItem<'a> {
+    pub fn name(&self) -> &'a str {
+        match *self {
+            LoadItem::Texture(name)
+            | LoadItem::Program(name)
+            | LoadItem::Font(name)
+            | LoadItem::Atlas(name) => name,
+        }
+    }
+}
+
+/// The state of an asset of a group
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadStatus {
+    Loading,
+    Loaded,
+    /// The reason of the failure
+    Failed(String),
+}
+
+struct LoadEntry {
+    name: String,
+    asset: Rc<LoadState>,
+    status: LoadStatus,
+}
+
+struct LoadGroup {
+    entries: Vec<LoadEntry>,
+    on_complete: Option<Box<FnBox()>>,
+}
+
+impl LoadGroup {
+    /// Update the states of the assets still loading, true once all of them are done
+    fn poll(&mut self) -> bool {
+        for entry in self.entries.iter_mut() {
+            if entry.status != LoadStatus::Loading {
+                continue;
+            }
+
+            // A failed asset is not polled again
+            entry.status = match entry.asset.load_state() {
+                Ok(()) => LoadStatus::Loaded,
+                Err(AssetError::NotReady) => LoadStatus::Loading,
+                Err(e) => LoadStatus::Failed(format!("{:?}", e)),
+            };
+        }
+
+        self.entries
+            .iter()
+            .all(|entry| entry.status != LoadStatus::Loading)
+    }
+}
+
+/// The loading of a group of assets, e.g. the assets of a level behind a loading screen.
+///
+/// The states are updated in each step of the asset system. The progress of a file is
+/// not known while it is downloaded, an asset counts once it is loaded or failed
+#[derive(Clone)]
+pub struct LoadHandle {
+    group: Rc<RefCell<LoadGroup>>,
+}
+
+impl LoadHandle {
+    pub(crate) fn new(assets: Vec<(String, Rc<LoadState>)>) -> LoadHandle {
+        let entries = assets
+            .into_iter()
+            .map(|(name, asset)| LoadEntry {
+                name,
+                asset,
+                status: LoadStatus::Loading,
+            })
+            .collect();
+
+        LoadHandle {
+            group: Rc::new(RefCell::new(LoadGroup {
+                entries,
+                on_complete: None,
+            })),
+        }
+    }
+
+    /// Update the states of the assets and call the completion callback once all of
+    /// them are done, false while some are loading
+    pub(crate) fn poll(&self) -> bool {
+        let on_complete = {
+            let mut group = self.group.borrow_mut();
+            if !group.poll() {
+                return false;
+            }
+
+            group.on_complete.take()
+        };
+
+        // The callback may use the handle
+        if let Some(f) = on_complete {
+            f();
+        }
+
+        true
+    }
+
+    /// Fraction (0..1) of the assets which are loaded or failed, 1 for an empty group
+    pub fn progress(&self) -> f32 {
+        let group = self.group.borrow();
+        if group.entries.is_empty() {
+            return 1.0;
+        }
+
+        let done = group
+            .entries
+            .iter()
+            .filter(|entry| entry.status != LoadStatus::Loading)
+            .count();
+
+        done as f32 / group.entries.len() as f32
+    }
+
+    /// All the assets are loaded, false if any of them failed
+    pub fn is_ready(&self) -> bool {
+        self.group
+            .borrow()
+            .entries
+            .iter()
+            .all(|entry| entry.status == LoadStatus::Loaded)
+    }
+
+    /// All the assets are loaded or failed
+    pub fn is_done(&self) -> bool {
+        self.progress() >= 1.0
+    }
+
+    /// The name and the state of each asset, in the order of the group
+    pub fn statuses(&self) -> Vec<(String, LoadStatus)> {
+        self.group
+            .borrow()
+            .entries
+            .iter()
+            .map(|entry| (entry.name.clone(), entry.status.clone()))
+            .collect()
+    }
+
+    /// The names and the reasons of the assets which failed
+    pub fn errors(&self) -> Vec<(String, String)> {
+        self.statuses()
+            .into_iter()
+            .filter_map(|(name, status)| match status {
+                LoadStatus::Failed(reason) => Some((name, reason)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Call `f` once all the assets are loaded or failed, in the step of the asset
+    /// system which finds them done or at once if they are already. It replaces the
+    /// previous callback
+    pub fn on_complete<F>(&self, f: F)
+    where
+        F: FnOnce() + 'static,
+    {
+        if self.is_done() {
+            f();
+        } else {
+            self.group.borrow_mut().on_complete = Some(Box::new(f));
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use engine::asset::loader::{Loadable, Loader};
+use engine::asset::{AssetError, AssetResult, AssetSystem, File};
+use engine::render::{AtlasRegion, Texture};
+
+use serde_json;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
+
+#[derive(Debug)]
+pub struct AtlasData {
+    pub texture: Rc<Texture>,
+    /// Size of the texture in the file, the one of the image when None
+    pub size: Option<(u32, u32)>,
+    pub regions: HashMap<String, AtlasRegion>,
+}
+
+pub struct AtlasLoader {}
+
+fn parse_region(frame: &Value) -> Option<AtlasRegion> {
+    let rect = &frame["frame"];
+    let get = |key: &str| rect[key].as_u64().map(|v| v as u32);
+
+    Some(AtlasRegion {
+        x: get("x")?,
+        y: get("y")?,
+        w: get("w")?,
+        h: get("h")?,
+        rotated: frame["rotated"].as_bool().unwrap_or(false),
+    })
+}
+
+/// The regions of the frames of a TexturePacker JSON, the error is the reason
+fn parse_frames(json: &Value) -> Result<HashMap<String, AtlasRegion>, String> {
+    // TexturePacker exports the frames by name (hash) or in a list (array)
+    let frames: Vec<(String, &Value)> = match json["frames"] {
+        Value::Object(ref map) => map.iter().map(|(k, v)| (k.clone(), v)).collect(),
+        Value::Array(ref list) => list.iter()
+            .filter_map(|v| v["filename"].as_str().map(|name| (name.to_string(), v)))
+            .collect(),
+        _ => return Err("no frames".to_string()),
+    };
+
+    let mut regions = HashMap::new();
+    for (name, frame) in frames {
+        let region = parse_region(frame).ok_or_else(|| format!("invalid frame {}", name))?;
+        regions.insert(name, region);
+    }
+
+    Ok(regions)
+}
+
+impl Loader<AtlasData> for AtlasLoader {
+    fn load<A>(asys: A, mut file: Box<File>) -> AssetResult<AtlasData>
+    where
+        A: AssetSystem + Clone,
+    {
+        let bytes = file
+            .read_binary()
+            .map_err(|_| AssetError::ReadBufferFail(file.name()))?;
+
+        let invalid = |reason: String| AssetError::InvalidFormat {
+            path: file.name(),
+            len: bytes.len(),
+            reason,
+        };
+
+        let json: Value = serde_json::from_slice(&bytes).map_err(|e| invalid(format!("{:?}", e)))?;
+        let regions = parse_frames(&json).map_err(&invalid)?;
+
+        // The image is next to the file, of the same name by default
+        let name = file.name();
+        let path = Path::new(&name);
+        let image = match json["meta"]["image"].as_str() {
+            Some(image) => path.with_file_name(image),
+            None => path.with_extension("png"),
+        };
+
+        let meta_size = &json["meta"]["size"];
+        let size = match (meta_size["w"].as_u64(), meta_size["h"].as_u64()) {
+            (Some(w), Some(h)) => Some((w as u32, h as u32)),
+            _ => None,
+        };
+
+        Ok(AtlasData {
+            texture: asys.new_texture(&image.to_string_lossy()),
+            size,
+            regions,
+        })
+    }
+}
+
+impl Loadable for AtlasData {
+    type Loader = AtlasLoader;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(json: &str) -> Result<HashMap<String, AtlasRegion>, String> {
+        parse_frames(&serde_json::from_str(json).unwrap())
+    }
+
+    fn region(x: u32, y: u32, w: u32, h: u32, rotated: bool) -> AtlasRegion {
+        AtlasRegion { x, y, w, h, rotated }
+    }
+
+    #[test]
+    fn hash_frames() {
+        let regions = frames(
+            r#"{"frames": {
+                "hero.png": {"frame": {"x": 2, "y": 4, "w": 32, "h": 48}, "rotated": false},
+                "coin.png": {"frame": {"x": 40, "y": 4, "w": 16, "h": 16}}
+            }}"#,
+        ).unwrap();
+
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions["hero.png"], region(2, 4, 32, 48, false));
+        assert_eq!(regions["coin.png"], region(40, 4, 16, 16, false));
+    }
+
+    #[test]
+    fn rotated_and_trimmed_frames() {
+        let regions = frames(
+            r#"{"frames": [
+                {"filename": "tree.png", "frame": {"x": 0, "y": 0, "w": 30, "h": 60},
+                 "rotated": true, "trimmed": false,
+                 "spriteSourceSize": {"x": 0, "y": 0, "w": 30, "h": 60},
+                 "sourceSize": {"w": 30, "h": 60}},
+                {"filename": "rock.png", "frame": {"x": 60, "y": 0, "w": 20, "h": 12},
+                 "rotated": false, "trimmed": true,
+                 "spriteSourceSize": {"x": 6, "y": 10, "w": 20, "h": 12},
+                 "sourceSize": {"w": 32, "h": 32}}
+            ]}"#,
+        ).unwrap();
+
+        // A rotated frame has the size of the image, it takes h x w pixels of the texture
+        let tree = regions["tree.png"];
+        assert_eq!(tree, region(0, 0, 30, 60, true));
+        assert_eq!(tree.rect(), (0, 0, 60, 30));
+
+        // A trimmed frame is the opaque part of the image which is stored
+        assert_eq!(regions["rock.png"], region(60, 0, 20, 12, false));
+        assert_eq!(regions["rock.png"].rect(), (60, 0, 20, 12));
+    }
+
+    #[test]
+    fn invalid_frames() {
+        assert!(frames(r#"{"meta": {}}"#).is_err());
+        assert!(frames(r#"{"frames": {"a": {"frame": {"x": 0, "y": 0, "w": 4}}}}"#).is_err());
+        let negative = r#"{"frames": {"a": {"frame": {"x": -1, "y": 0, "w": 4, "h": 4}}}}"#;
+        assert!(frames(negative).is_err());
+
+        // The array frames without a name are skipped
+        let regions = frames(r#"{"frames": [{"frame": {"x": 0, "y": 0, "w": 4, "h": 4}}]}"#);
+        assert!(regions.unwrap().is_empty());
+    }
+}
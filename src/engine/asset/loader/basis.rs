@@ -0,0 +1,562 @@
+/// Decoder of the ETC1S slices of Basis Universal, as supercompressed by BasisLZ in the
+/// KTX2 files.
+///
+/// The endpoints and the selectors of the ETC1S blocks are coded by the codebooks of the
+/// global data of the file, the slices index them with the huffman tables of the global
+/// data. The blocks are decoded to rgba pixels, the gpus without ETC1 then sample them
+/// like the ones of any other image
+pub struct Etc1sDecoder {
+    endpoints: Vec<Endpoint>,
+    /// The selectors of the rows of the blocks, 2 bits per pixel from the lowest
+    selectors: Vec<[u8; 4]>,
+    tables: SliceTables,
+}
+
+/// The base color in 5 bits and the intensity table of an ETC1S block
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Endpoint {
+    color5: [u8; 3],
+    inten: u8,
+}
+
+struct SliceTables {
+    endpoint_pred: Huffman,
+    delta_endpoint: Huffman,
+    selector: Huffman,
+    selector_history_rle: Huffman,
+    selector_history_size: usize,
+}
+
+/// The modifiers of the ETC1 intensity tables, in the order of the ETC1S selectors
+const INTEN_TABLES: [[i32; 4]; 8] = [
+    [-8, -2, 2, 8],
+    [-17, -5, 5, 17],
+    [-29, -9, 9, 29],
+    [-42, -13, 13, 42],
+    [-60, -18, 18, 60],
+    [-80, -24, 24, 80],
+    [-106, -33, 33, 106],
+    [-183, -47, 47, 183],
+];
+
+const MAX_CODE_SIZE: usize = 16;
+const MAX_SYMBOLS_LOG2: u32 = 14;
+/// The code length codes, in the order their sizes are stored
+const CODE_LENGTH_ORDER: [usize; 21] = [
+    17, 18, 19, 20, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15, 16,
+];
+const SMALL_ZERO_RUN: u32 = 17;
+const BIG_ZERO_RUN: u32 = 18;
+const SMALL_REPEAT: u32 = 19;
+
+/// The previous colors up to which the first and the second delta models are used
+const COLOR5_PAL0_PREV_HI: u8 = 9;
+const COLOR5_PAL1_PREV_HI: u8 = 21;
+
+/// The predictions of the endpoints of 2x2 blocks, 2 bits each, and the symbol which
+/// repeats the last ones
+const ENDPOINT_PRED_REPEAT_LAST: u32 = 256;
+const ENDPOINT_PRED_COUNT_VLC_BITS: u32 = 4;
+const ENDPOINT_PRED_MIN_REPEAT_COUNT: u32 = 3;
+
+const SELECTOR_HISTORY_RLE_THRESH: u32 = 3;
+const SELECTOR_HISTORY_RLE_TOTAL: u32 = 64;
+
+/// Reads the bits from the lowest of each byte
+struct BitReader<'a> {
+    buff: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(buff: &'a [u8]) -> BitReader<'a> {
+        BitReader { buff, pos: 0 }
+    }
+
+    fn bit(&mut self) -> Option<u32> {
+        let byte = *self.buff.get(self.pos / 8)?;
+        let bit = (byte >> (self.pos % 8)) & 1;
+        self.pos += 1;
+
+        Some(bit as u32)
+    }
+
+    fn bits(&mut self, n: u32) -> Option<u32> {
+        let mut v = 0;
+        for i in 0..n {
+            v |= self.bit()? << i;
+        }
+
+        Some(v)
+    }
+
+    /// A value of chunks of `chunk_bits` bits, each followed by a bit telling whether
+    /// another one follows
+    fn vlc(&mut self, chunk_bits: u32) -> Option<u32> {
+        let mut v = 0;
+        let mut shift = 0;
+
+        loop {
+            let chunk = self.bits(chunk_bits + 1)?;
+            v |= (chunk & ((1 << chunk_bits) - 1)).checked_shl(shift)?;
+            shift += chunk_bits;
+
+            if chunk & (1 << chunk_bits) == 0 {
+                return Some(v);
+            }
+        }
+    }
+
+    fn huffman(&mut self, table: &Huffman) -> Option<u32> {
+        table.decode(self)
+    }
+}
+
+/// A canonical huffman code, whose codes are stored from their highest bit
+struct Huffman {
+    /// The count of the codes of each size
+    counts: [u16; MAX_CODE_SIZE + 1],
+    /// The symbols in the order of their codes
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    /// The code of the code sizes of the symbols, a size of 0 is an unused symbol.
+    /// None if there are more codes than their sizes allow
+    fn new(sizes: &[u8]) -> Option<Huffman> {
+        let mut counts = [0u16; MAX_CODE_SIZE + 1];
+        for &size in sizes.iter() {
+            if size as usize > MAX_CODE_SIZE {
+                return None;
+            }
+            counts[size as usize] += 1;
+        }
+        counts[0] = 0;
+
+        // A code of a single symbol is incomplete, which is allowed
+        let mut left = 1i32;
+        for &count in counts[1..].iter() {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return None;
+            }
+        }
+
+        let mut symbols = Vec::new();
+        for len in 1..MAX_CODE_SIZE + 1 {
+            for (sym, &size) in sizes.iter().enumerate() {
+                if size as usize == len {
+                    symbols.push(sym as u16);
+                }
+            }
+        }
+
+        Some(Huffman { counts, symbols })
+    }
+
+    /// Read a table stored by the sizes of its codes, which are themselves coded by a
+    /// table of the code length codes, with the runs of the zero and the repeated sizes
+    fn read(r: &mut BitReader) -> Option<Huffman> {
+        let total = r.bits(MAX_SYMBOLS_LOG2)? as usize;
+        if total == 0 {
+            return Huffman::new(&[]);
+        }
+
+        let count = r.bits(5)? as usize;
+        if count == 0 || count > CODE_LENGTH_ORDER.len() {
+            return None;
+        }
+
+        let mut code_length_sizes = [0u8; 21];
+        for &code in CODE_LENGTH_ORDER[..count].iter() {
+            code_length_sizes[code] = r.bits(3)? as u8;
+        }
+        let code_lengths = Huffman::new(&code_length_sizes)?;
+
+        let mut sizes = Vec::with_capacity(total);
+        while sizes.len() < total {
+            let (size, run) = match r.huffman(&code_lengths)? {
+                c if c <= 16 => (c as u8, 1),
+                SMALL_ZERO_RUN => (0, r.bits(3)? + 3),
+                BIG_ZERO_RUN => (0, r.bits(7)? + 11),
+                c => {
+                    let run = if c == SMALL_REPEAT {
+                        r.bits(2)? + 3
+                    } else {
+                        r.bits(7)? + 7
+                    };
+                    match sizes.last() {
+                        Some(&prev) if prev != 0 => (prev, run),
+                        _ => return None,
+                    }
+                }
+            };
+
+            if sizes.len() + run as usize > total {
+                return None;
+            }
+            sizes.extend((0..run).map(|_| size));
+        }
+
+        Huffman::new(&sizes)
+    }
+
+    fn decode(&self, r: &mut BitReader) -> Option<u32> {
+        let mut code = 0i32;
+        let mut first = 0i32;
+        let mut index = 0i32;
+
+        for len in 1..MAX_CODE_SIZE + 1 {
+            code |= r.bit()? as i32;
+            let count = self.counts[len] as i32;
+            if code - count < first {
+                return self.symbols
+                    .get((index + code - first) as usize)
+                    .map(|&sym| sym as u32);
+            }
+
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        None
+    }
+}
+
+/// The last selectors, the ones added replace the second half in turns and the ones
+/// used move towards the front
+struct SelectorHistory {
+    values: Vec<usize>,
+    rover: usize,
+}
+
+impl SelectorHistory {
+    fn new(size: usize) -> SelectorHistory {
+        SelectorHistory {
+            values: vec![0; size],
+            rover: size / 2,
+        }
+    }
+
+    fn add(&mut self, value: usize) {
+        self.values[self.rover] = value;
+        self.rover += 1;
+        if self.rover == self.values.len() {
+            self.rover = self.values.len() / 2;
+        }
+    }
+
+    fn use_index(&mut self, index: usize) {
+        self.values.swap(index / 2, index);
+    }
+}
+
+/// The endpoint and the 2 bits predictions of the endpoints of a block, kept for the
+/// blocks of the next row
+#[derive(Copy, Clone, Default)]
+struct BlockPred {
+    endpoint: usize,
+    pred_bits: u32,
+}
+
+impl Etc1sDecoder {
+    /// Decode the codebooks of `endpoint_count` endpoints and `selector_count` selectors
+    /// and the huffman tables of the slices, None if they are invalid
+    pub fn new(
+        endpoint_count: usize,
+        selector_count: usize,
+        endpoints: &[u8],
+        selectors: &[u8],
+        tables: &[u8],
+    ) -> Option<Etc1sDecoder> {
+        if endpoint_count == 0 || selector_count == 0 {
+            return None;
+        }
+
+        Some(Etc1sDecoder {
+            endpoints: Self::read_endpoints(endpoint_count, endpoints)?,
+            selectors: Self::read_selectors(selector_count, selectors)?,
+            tables: Self::read_tables(tables)?,
+        })
+    }
+
+    /// The endpoints are coded by their differences to the previous ones
+    fn read_endpoints(count: usize, data: &[u8]) -> Option<Vec<Endpoint>> {
+        let r = &mut BitReader::new(data);
+        let color5_delta = [Huffman::read(r)?, Huffman::read(r)?, Huffman::read(r)?];
+        let inten_delta = Huffman::read(r)?;
+        let grayscale = r.bit()? != 0;
+
+        let mut prev = Endpoint {
+            color5: [16, 16, 16],
+            inten: 0,
+        };
+        let mut endpoints = Vec::with_capacity(count);
+
+        for _ in 0..count {
+            let mut endpoint = Endpoint {
+                color5: [0; 3],
+                inten: ((r.huffman(&inten_delta)? + prev.inten as u32) & 7) as u8,
+            };
+
+            let channels = if grayscale { 1 } else { 3 };
+            for c in 0..channels {
+                let model = match prev.color5[c] {
+                    p if p <= COLOR5_PAL0_PREV_HI => &color5_delta[0],
+                    p if p <= COLOR5_PAL1_PREV_HI => &color5_delta[1],
+                    _ => &color5_delta[2],
+                };
+                endpoint.color5[c] = ((r.huffman(model)? + prev.color5[c] as u32) & 31) as u8;
+            }
+            if grayscale {
+                endpoint.color5 = [endpoint.color5[0]; 3];
+            }
+
+            // The colors of a grayscale codebook are all predicted from the first channel
+            prev = Endpoint {
+                color5: if grayscale {
+                    [endpoint.color5[0], prev.color5[1], prev.color5[2]]
+                } else {
+                    endpoint.color5
+                },
+                inten: endpoint.inten,
+            };
+            endpoints.push(endpoint);
+        }
+
+        Some(endpoints)
+    }
+
+    /// The selectors are raw, or coded by the xor of their rows with the previous ones
+    fn read_selectors(count: usize, data: &[u8]) -> Option<Vec<[u8; 4]>> {
+        let r = &mut BitReader::new(data);
+
+        // The global and the hybrid codebooks of the older files are not supported
+        if r.bit()? != 0 || r.bit()? != 0 {
+            return None;
+        }
+
+        let mut selectors = Vec::with_capacity(count);
+
+        if r.bit()? != 0 {
+            for _ in 0..count {
+                let mut rows = [0u8; 4];
+                for row in rows.iter_mut() {
+                    *row = r.bits(8)? as u8;
+                }
+                selectors.push(rows);
+            }
+            return Some(selectors);
+        }
+
+        let delta = Huffman::read(r)?;
+        let mut prev = [0u8; 4];
+
+        for i in 0..count {
+            for row in prev.iter_mut() {
+                *row = if i == 0 {
+                    r.bits(8)? as u8
+                } else {
+                    (r.huffman(&delta)? as u8) ^ *row
+                };
+            }
+            selectors.push(prev);
+        }
+
+        Some(selectors)
+    }
+
+    fn read_tables(data: &[u8]) -> Option<SliceTables> {
+        let r = &mut BitReader::new(data);
+
+        let tables = SliceTables {
+            endpoint_pred: Huffman::read(r)?,
+            delta_endpoint: Huffman::read(r)?,
+            selector: Huffman::read(r)?,
+            selector_history_rle: Huffman::read(r)?,
+            selector_history_size: r.bits(13)? as usize,
+        };
+
+        if tables.selector_history_size == 0 {
+            return None;
+        }
+        Some(tables)
+    }
+
+    /// Decode a slice of `width` x `height` pixels into the rgba `pixels`, into their
+    /// alpha channel if `alpha` is set. None if the slice is invalid
+    pub fn decode_slice(
+        &self,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        alpha: bool,
+        pixels: &mut [u8],
+    ) -> Option<()> {
+        let (width, height) = (width as usize, height as usize);
+        if pixels.len() < width.checked_mul(height)?.checked_mul(4)? {
+            return None;
+        }
+
+        let blocks_x = (width + 3) / 4;
+        let blocks_y = (height + 3) / 4;
+        let total_blocks = blocks_x * blocks_y;
+        let tables = &self.tables;
+
+        let r = &mut BitReader::new(data);
+        let mut history = SelectorHistory::new(tables.selector_history_size);
+        let history_first = self.selectors.len() as u32;
+        let history_rle = history_first + tables.selector_history_size as u32;
+
+        // The predictions of the previous and the current rows
+        let mut preds = [
+            vec![BlockPred::default(); blocks_x],
+            vec![BlockPred::default(); blocks_x],
+        ];
+
+        let mut pred_bits = 0;
+        let mut prev_pred_sym = 0;
+        let mut pred_repeat_count = 0;
+        let mut prev_endpoint = 0;
+        let mut selector_rle_count = 0;
+
+        for by in 0..blocks_y {
+            let cur = by & 1;
+
+            for bx in 0..blocks_x {
+                // The predictions of 2x2 blocks are read on their top left one
+                if bx & 1 == 0 {
+                    if by & 1 == 0 {
+                        if pred_repeat_count > 0 {
+                            pred_repeat_count -= 1;
+                            pred_bits = prev_pred_sym;
+                        } else {
+                            pred_bits = r.huffman(&tables.endpoint_pred)?;
+                            if pred_bits == ENDPOINT_PRED_REPEAT_LAST {
+                                pred_repeat_count = r.vlc(ENDPOINT_PRED_COUNT_VLC_BITS)?
+                                    + ENDPOINT_PRED_MIN_REPEAT_COUNT
+                                    - 1;
+                                pred_bits = prev_pred_sym;
+                            } else {
+                                prev_pred_sym = pred_bits;
+                            }
+                        }
+
+                        preds[cur ^ 1][bx].pred_bits = pred_bits >> 4;
+                    } else {
+                        pred_bits = preds[cur][bx].pred_bits;
+                    }
+                }
+
+                let pred = pred_bits & 3;
+                pred_bits >>= 2;
+
+                let endpoint = match pred {
+                    // The left, the upper and the upper left blocks
+                    0 if bx > 0 => prev_endpoint,
+                    1 if by > 0 => preds[cur ^ 1][bx].endpoint,
+                    2 if bx > 0 && by > 0 => preds[cur ^ 1][bx - 1].endpoint,
+                    3 => {
+                        let e = r.huffman(&tables.delta_endpoint)? as usize + prev_endpoint;
+                        if e >= self.endpoints.len() {
+                            e - self.endpoints.len()
+                        } else {
+                            e
+                        }
+                    }
+                    _ => return None,
+                };
+                if endpoint >= self.endpoints.len() {
+                    return None;
+                }
+                preds[cur][bx].endpoint = endpoint;
+                prev_endpoint = endpoint;
+
+                let mut selector_sym = if selector_rle_count > 0 {
+                    selector_rle_count -= 1;
+                    history_first
+                } else {
+                    r.huffman(&tables.selector)?
+                };
+
+                if selector_sym == history_rle {
+                    let run = r.huffman(&tables.selector_history_rle)?;
+                    selector_rle_count = if run == SELECTOR_HISTORY_RLE_TOTAL - 1 {
+                        r.vlc(7)? + SELECTOR_HISTORY_RLE_THRESH
+                    } else {
+                        run + SELECTOR_HISTORY_RLE_THRESH
+                    };
+                    if selector_rle_count as usize > total_blocks {
+                        return None;
+                    }
+
+                    selector_sym = history_first;
+                    selector_rle_count -= 1;
+                }
+
+                let selector = if selector_sym >= history_first {
+                    let index = (selector_sym - history_first) as usize;
+                    if index >= history.values.len() {
+                        return None;
+                    }
+
+                    let selector = history.values[index];
+                    if index != 0 {
+                        history.use_index(index);
+                    }
+                    selector
+                } else {
+                    history.add(selector_sym as usize);
+                    selector_sym as usize
+                };
+
+                let selector = self.selectors.get(selector)?;
+                self.write_block((bx, by), endpoint, selector, (width, height), alpha, pixels);
+            }
+        }
+
+        Some(())
+    }
+
+    /// Write the pixels of a block inside the image, the gray of the alpha slices is
+    /// taken from the green channel
+    fn write_block(
+        &self,
+        (bx, by): (usize, usize),
+        endpoint: usize,
+        selector: &[u8; 4],
+        (width, height): (usize, usize),
+        alpha: bool,
+        pixels: &mut [u8],
+    ) {
+        let endpoint = &self.endpoints[endpoint];
+        let modifiers = &INTEN_TABLES[endpoint.inten as usize];
+
+        for y in 0..4 {
+            for x in 0..4 {
+                let (px, py) = (bx * 4 + x, by * 4 + y);
+                if px >= width || py >= height {
+                    continue;
+                }
+
+                let modifier = modifiers[((selector[y] >> (x * 2)) & 3) as usize];
+                let channel = |c5: u8| {
+                    let c = ((c5 << 3) | (c5 >> 2)) as i32 + modifier;
+                    c.max(0).min(255) as u8
+                };
+
+                let offset = (py * width + px) * 4;
+                let pixel = &mut pixels[offset..offset + 4];
+                if alpha {
+                    pixel[3] = channel(endpoint.color5[1]);
+                } else {
+                    for (dst, &c5) in pixel.iter_mut().zip(endpoint.color5.iter()) {
+                        *dst = channel(c5);
+                    }
+                    pixel[3] = 255;
+                }
+            }
+        }
+    }
+}
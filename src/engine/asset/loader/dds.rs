@@ -9,6 +9,10 @@ pub struct DDSReader {}
 pub enum DDSFormat {
     DXT1,
     DXT5,
+    /// ETC2, with EAC alpha if the image has alpha
+    ETC2,
+    /// ASTC LDR of the width and the height of the blocks
+    ASTC(u32, u32),
 }
 
 #[derive(Debug, Clone)]
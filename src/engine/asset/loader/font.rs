@@ -0,0 +1,39 @@
+use engine::asset::loader::{Loadable, Loader};
+use engine::asset::{AssetError, AssetResult, AssetSystem, File};
+
+use rusttype;
+use std::fmt;
+
+pub struct FontData(pub rusttype::Font<'static>);
+
+impl fmt::Debug for FontData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "FontData {{ glyph_count: {} }}", self.0.glyph_count())
+    }
+}
+
+pub struct FontLoader {}
+
+impl Loader<FontData> for FontLoader {
+    fn load<A>(_asys: A, mut file: Box<File>) -> AssetResult<FontData>
+    where
+        A: AssetSystem + Clone,
+    {
+        let bytes = file
+            .read_binary()
+            .map_err(|_| AssetError::ReadBufferFail(file.name()))?;
+        let len = bytes.len();
+
+        let font = rusttype::Font::from_bytes(bytes).map_err(|e| AssetError::InvalidFormat {
+            path: file.name(),
+            len: len,
+            reason: format!("{:?}", e),
+        })?;
+
+        Ok(FontData(font))
+    }
+}
+
+impl Loadable for FontData {
+    type Loader = FontLoader;
+}
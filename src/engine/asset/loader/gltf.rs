@@ -0,0 +1,601 @@
+use engine::asset::{Asset, AssetError, AssetResult, AssetSystem, FileFuture, Resource};
+use engine::core::{AnimationChannel, AnimationClip, AnimationProperty, Interpolation};
+use engine::render::{Material, Mesh, MeshBuffer, MeshData, MorphTarget, StandardMaterial,
+                     Texture, TextureImage, TextureWrap};
+
+use super::prefab::{parent_path, Prefab, PrefabNode, PrefabSkin};
+
+use base64;
+use gltf;
+use gltf::animation::util::ReadOutputs;
+use gltf::buffer::Source as BufferSource;
+use gltf::image::Source as ImageSource;
+use image;
+use math::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use futures::future::*;
+
+type BufferFuture = Box<Future<Item = Vec<u8>, Error = AssetError>>;
+
+fn invalid_format<E: ::std::fmt::Debug>(path: &str, len: usize, e: E) -> AssetError {
+    AssetError::InvalidFormat {
+        path: path.to_string(),
+        len,
+        reason: format!("{:?}", e),
+    }
+}
+
+/// The bytes of a base64 data uri, None if `uri` is not one. `name` is the name of the
+/// glTF file in the errors
+fn decode_data_uri(name: &str, uri: &str) -> Option<AssetResult<Vec<u8>>> {
+    if !uri.starts_with("data:") {
+        return None;
+    }
+
+    let data = match uri.find(";base64,") {
+        Some(i) => &uri[i + ";base64,".len()..],
+        None => return Some(Err(invalid_format(name, uri.len(), "data uri not base64"))),
+    };
+
+    Some(base64::decode(data).map_err(|e| invalid_format(name, data.len(), e)))
+}
+
+/// The bytes and the name of a file
+fn read_file(file: FileFuture) -> Box<Future<Item = (Vec<u8>, String), Error = AssetError>> {
+    Box::new(file.then(|f| match f {
+        Ok(mut file) => {
+            let name = file.name();
+            let bytes = file.read_binary()
+                .map_err(|_| AssetError::ReadBufferFail(name.clone()))?;
+            Ok((bytes, name))
+        }
+        Err(e) => Err(AssetError::FileIoError(e)),
+    }))
+}
+
+fn flatten<T, A, I>(iter: I) -> Vec<T>
+where
+    T: Copy,
+    A: AsRef<[T]>,
+    I: Iterator<Item = A>,
+{
+    let mut v = Vec::new();
+    for a in iter {
+        v.extend_from_slice(a.as_ref());
+    }
+    v
+}
+
+fn texture_wrap(mode: gltf::texture::WrappingMode) -> TextureWrap {
+    match mode {
+        gltf::texture::WrappingMode::ClampToEdge => TextureWrap::ClampToEdge,
+        gltf::texture::WrappingMode::MirroredRepeat => TextureWrap::MirroredRepeat,
+        gltf::texture::WrappingMode::Repeat => TextureWrap::Repeat,
+    }
+}
+
+/// Vertex normals averaged from the faces, for the primitives without normals
+fn compute_normals(vertices: &[f32], indices: &[u16]) -> Vec<f32> {
+    let mut normals = vec![0.0; vertices.len()];
+    let p = |i: u16| {
+        let i = i as usize * 3;
+        vec3(vertices[i], vertices[i + 1], vertices[i + 2])
+    };
+
+    for tri in indices.chunks(3).filter(|tri| tri.len() == 3) {
+        let n = (p(tri[1]) - p(tri[0])).cross(p(tri[2]) - p(tri[0]));
+        for &i in tri.iter() {
+            for k in 0..3 {
+                normals[i as usize * 3 + k] += n[k];
+            }
+        }
+    }
+
+    for n in normals.chunks_mut(3) {
+        let v = vec3(n[0], n[1], n[2]);
+        if v.magnitude2() > 0.0 {
+            let v = v.normalize();
+            n.copy_from_slice(&[v.x, v.y, v.z]);
+        }
+    }
+
+    normals
+}
+
+/// Tangents and bitangents of an indexed mesh, averaged from the faces
+fn compute_tangents(
+    vertices: &[f32],
+    uvs: &[f32],
+    normals: &[f32],
+    indices: &[u16],
+) -> (Vec<f32>, Vec<f32>) {
+    let count = vertices.len() / 3;
+    let mut tangents = vec![Vector3::zero(); count];
+    let mut bitangents = vec![Vector3::zero(); count];
+
+    let p = |i: usize| vec3(vertices[i * 3], vertices[i * 3 + 1], vertices[i * 3 + 2]);
+    let uv = |i: usize| Vector2::new(uvs[i * 2], uvs[i * 2 + 1]);
+
+    for tri in indices.chunks(3).filter(|tri| tri.len() == 3) {
+        let (a, b, c) = (tri[0] as usize, tri[1] as usize, tri[2] as usize);
+
+        let edge1 = p(b) - p(a);
+        let edge2 = p(c) - p(a);
+        let delta_uv1 = uv(b) - uv(a);
+        let delta_uv2 = uv(c) - uv(a);
+
+        let d = delta_uv1.x * delta_uv2.y - delta_uv2.x * delta_uv1.y;
+        if d.abs() < 0.0001 {
+            continue;
+        }
+
+        let r = 1.0 / d;
+        let tangent = (edge1 * delta_uv2.y - edge2 * delta_uv1.y) * r;
+        let bitangent = (edge2 * delta_uv1.x - edge1 * delta_uv2.x) * r;
+
+        for &i in [a, b, c].iter() {
+            tangents[i] += tangent;
+            bitangents[i] += bitangent;
+        }
+    }
+
+    let mut t_array = Vec::with_capacity(count * 3);
+    let mut b_array = Vec::with_capacity(count * 3);
+
+    for i in 0..count {
+        let n = vec3(normals[i * 3], normals[i * 3 + 1], normals[i * 3 + 2]);
+
+        // Gram-Schmidt orthogonalize
+        let mut t = tangents[i] - n * n.dot(tangents[i]);
+        if t.magnitude2() > 0.0 {
+            t = t.normalize();
+        }
+
+        let mut b = n.cross(t);
+        if b.dot(bitangents[i]) < 0.0 {
+            b = -b;
+        }
+
+        t_array.extend_from_slice(&[t.x, t.y, t.z]);
+        b_array.extend_from_slice(&[b.x, b.y, b.z]);
+    }
+
+    (t_array, b_array)
+}
+
+struct GltfLoader<'a, A: 'a> {
+    asys: &'a A,
+    /// The name of the glTF file
+    name: &'a str,
+    parent: &'a str,
+    buffers: &'a [Vec<u8>],
+    warnings: RefCell<Vec<String>>,
+}
+
+impl<'a, A> GltfLoader<'a, A>
+where
+    A: AssetSystem + Clone + 'static,
+{
+    fn buffer_data(&self, buffer: gltf::Buffer) -> Option<&'a [u8]> {
+        self.buffers.get(buffer.index()).map(|data| &data[..])
+    }
+
+    /// Fails if an attribute has `len` values instead of the `expected` ones of its vertices
+    fn check_attribute(&self, attribute: &str, len: usize, expected: usize) -> AssetResult<()> {
+        if len == expected {
+            return Ok(());
+        }
+
+        Err(invalid_format(
+            self.name,
+            len,
+            format!("{} of {} values instead of {}", attribute, len, expected),
+        ))
+    }
+
+    fn decode_texture(&self, bytes: &[u8]) -> AssetResult<Rc<Texture>> {
+        let img = image::load_from_memory(bytes)
+            .map_err(|e| invalid_format(self.name, bytes.len(), e))?;
+
+        Ok(Texture::new(TextureImage::Rgba(img.to_rgba())))
+    }
+
+    /// The image files are loaded as any other texture, the embedded ones are decoded
+    fn load_texture(&self, texture: gltf::Texture) -> AssetResult<Rc<Texture>> {
+        let tex = match texture.source().source() {
+            ImageSource::Uri { uri, .. } => match decode_data_uri(self.name, uri) {
+                Some(bytes) => self.decode_texture(&bytes?)?,
+                None => self.asys.new_texture(&(self.parent.to_string() + uri)),
+            },
+            ImageSource::View { view, .. } => {
+                let data = self.buffer_data(view.buffer()).unwrap_or(&[]);
+                let end = view.offset() + view.length();
+                if end > data.len() {
+                    return Err(invalid_format(self.name, data.len(), "image out of buffer"));
+                }
+
+                self.decode_texture(&data[view.offset()..end])?
+            }
+        };
+
+        let sampler = texture.sampler();
+        tex.wrap_u.set(texture_wrap(sampler.wrap_s()));
+        tex.wrap_v.set(texture_wrap(sampler.wrap_t()));
+
+        Ok(tex)
+    }
+
+    fn load_material(
+        &self,
+        material: gltf::Material,
+        textures: &[Rc<Texture>],
+    ) -> (Rc<Material>, bool) {
+        let texture = |t: gltf::Texture| textures.get(t.index()).cloned();
+        let pbr = material.pbr_metallic_roughness();
+
+        let mut standard = StandardMaterial::default();
+        standard.base_color = pbr.base_color_factor().into();
+        standard.metallic = pbr.metallic_factor();
+        standard.roughness = pbr.roughness_factor();
+        standard.emissive = material.emissive_factor().into();
+
+        standard.albedo_map = pbr.base_color_texture().and_then(|i| texture(i.texture()));
+        standard.metallic_roughness_map = pbr.metallic_roughness_texture()
+            .and_then(|i| texture(i.texture()));
+        standard.normal_map = material.normal_texture().and_then(|n| texture(n.texture()));
+        standard.emissive_map = material.emissive_texture().and_then(|i| texture(i.texture()));
+
+        if let Some(occlusion) = material.occlusion_texture() {
+            standard.occlusion_map = texture(occlusion.texture());
+            standard.occlusion_strength = occlusion.strength();
+        }
+
+        match material.alpha_mode() {
+            gltf::material::AlphaMode::Opaque => (),
+            gltf::material::AlphaMode::Mask => standard.alpha_cutoff = material.alpha_cutoff(),
+            gltf::material::AlphaMode::Blend => standard.transparent = true,
+        }
+        standard.double_sided = material.double_sided();
+
+        let has_normal_map = standard.normal_map.is_some();
+        (Rc::new(standard.build(self.asys)), has_normal_map)
+    }
+
+    /// The mesh data of a triangles primitive, None if it could not be drawn. The
+    /// indices and the attributes are checked against the vertices
+    fn load_primitive(
+        &self,
+        primitive: &gltf::Primitive,
+        needs_tangents: bool,
+    ) -> AssetResult<Option<MeshData>> {
+        if primitive.mode() != gltf::mesh::Mode::Triangles {
+            return Ok(None);
+        }
+
+        let reader = primitive.reader(|buffer| self.buffer_data(buffer));
+
+        let vertices = match reader.read_positions() {
+            Some(positions) => flatten(positions),
+            None => return Ok(None),
+        };
+        let count = vertices.len() / 3;
+
+        // The indices are u16
+        if count > u16::max_value() as usize + 1 {
+            self.warnings
+                .borrow_mut()
+                .push(format!("skipped a primitive of {} vertices", count));
+            return Ok(None);
+        }
+
+        let indices: Vec<u16> = match reader.read_indices() {
+            Some(indices) => indices
+                .into_u32()
+                .map(|i| {
+                    if (i as usize) < count {
+                        Ok(i as u16)
+                    } else {
+                        Err(invalid_format(
+                            self.name,
+                            count,
+                            format!("index {} out of {} vertices", i, count),
+                        ))
+                    }
+                })
+                .collect::<AssetResult<_>>()?,
+            None => (0..count).map(|i| i as u16).collect(),
+        };
+
+        let normals = match reader.read_normals() {
+            Some(normals) => {
+                let normals = flatten(normals);
+                self.check_attribute("normals", normals.len(), count * 3)?;
+                normals
+            }
+            None => compute_normals(&vertices, &indices),
+        };
+
+        let uvs = reader.read_tex_coords(0).map(|uvs| flatten(uvs.into_f32()));
+        let uvs2 = reader.read_tex_coords(1).map(|uvs| flatten(uvs.into_f32()));
+        let colors = reader.read_colors(0).map(|c| flatten(c.into_rgba_f32()));
+        let joints = reader.read_joints(0).map(|j| flatten(j.into_u16()));
+        let weights = reader.read_weights(0).map(|w| flatten(w.into_f32()));
+
+        for &(attribute, values, size) in [
+            ("uvs", &uvs, 2),
+            ("uvs2", &uvs2, 2),
+            ("colors", &colors, 4),
+            ("weights", &weights, 4),
+        ].iter()
+        {
+            if let Some(ref values) = *values {
+                self.check_attribute(attribute, values.len(), count * size)?;
+            }
+        }
+        if let Some(ref joints) = joints {
+            self.check_attribute("joints", joints.len(), count * 4)?;
+        }
+
+        // The handedness of the bitangents is in the w of the tangents
+        let (tangents, bitangents) = match (reader.read_tangents(), &uvs) {
+            (Some(tangents), _) => {
+                let tangents: Vec<[f32; 4]> = tangents.collect();
+                self.check_attribute("tangents", tangents.len() * 4, count * 4)?;
+
+                let mut t_array = Vec::with_capacity(count * 3);
+                let mut b_array = Vec::with_capacity(count * 3);
+
+                for (t, n) in tangents.iter().zip(normals.chunks(3)) {
+                    let b = vec3(n[0], n[1], n[2]).cross(vec3(t[0], t[1], t[2])) * t[3];
+                    t_array.extend_from_slice(&t[0..3]);
+                    b_array.extend_from_slice(&[b.x, b.y, b.z]);
+                }
+
+                (Some(t_array), Some(b_array))
+            }
+            (None, &Some(ref uvs)) if needs_tangents => {
+                let (t_array, b_array) = compute_tangents(&vertices, uvs, &normals, &indices);
+                (Some(t_array), Some(b_array))
+            }
+            _ => (None, None),
+        };
+
+        let morph_targets = reader
+            .read_morph_targets()
+            .map(|(positions, normals, _)| {
+                let target = MorphTarget {
+                    name: None,
+                    positions: positions
+                        .map(flatten)
+                        .unwrap_or_else(|| vec![0.0; count * 3]),
+                    normals: normals.map(flatten),
+                };
+
+                self.check_attribute("morph positions", target.positions.len(), count * 3)?;
+                if let Some(ref normals) = target.normals {
+                    self.check_attribute("morph normals", normals.len(), count * 3)?;
+                }
+
+                Ok(target)
+            })
+            .collect::<AssetResult<_>>()?;
+
+        Ok(Some(MeshData {
+            vertices,
+            uvs,
+            uvs2,
+            normals: Some(normals),
+            indices,
+            tangents,
+            bitangents,
+            colors,
+            joints,
+            weights,
+            morph_targets,
+        }))
+    }
+
+    fn load_mesh(
+        &self,
+        mesh: gltf::Mesh,
+        materials: &[(Rc<Material>, bool)],
+    ) -> AssetResult<Mesh> {
+        let mut result = Mesh::new();
+        result.morph_weights = mesh.weights().map_or(Vec::new(), |w| w.to_vec());
+
+        for primitive in mesh.primitives() {
+            // The primitives without material get the default one, the last
+            let (material, has_normal_map) = primitive
+                .material()
+                .index()
+                .and_then(|i| materials.get(i))
+                .or_else(|| materials.last())
+                .cloned()
+                .unwrap();
+
+            if let Some(data) = self.load_primitive(&primitive, has_normal_map)? {
+                result.add_surface(
+                    MeshBuffer::new_from_resource(Resource::new(data)),
+                    material,
+                );
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn load_animation(&self, animation: gltf::Animation) -> AnimationClip {
+        let channels = animation
+            .channels()
+            .filter_map(|channel| {
+                let reader = channel.reader(|buffer| self.buffer_data(buffer));
+                let times: Vec<f32> = reader.read_inputs()?.collect();
+
+                let (property, values) = match reader.read_outputs()? {
+                    ReadOutputs::Translations(t) => (AnimationProperty::Translation, flatten(t)),
+                    ReadOutputs::Rotations(r) => {
+                        (AnimationProperty::Rotation, flatten(r.into_f32()))
+                    }
+                    ReadOutputs::Scales(s) => (AnimationProperty::Scale, flatten(s)),
+                    ReadOutputs::MorphTargetWeights(w) => {
+                        (AnimationProperty::MorphWeights, w.into_f32().collect())
+                    }
+                };
+
+                let interpolation = match channel.sampler().interpolation() {
+                    gltf::animation::Interpolation::Step => Interpolation::Step,
+                    gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+                    _ => Interpolation::Linear,
+                };
+
+                Some(AnimationChannel {
+                    target: channel.target().node().index(),
+                    property,
+                    interpolation,
+                    times,
+                    values,
+                })
+            })
+            .collect();
+
+        AnimationClip::new(animation.name().map(|n| n.to_string()), channels)
+    }
+
+    fn load(&self, doc: &gltf::Document) -> AssetResult<Prefab> {
+        let textures = doc.textures()
+            .map(|t| self.load_texture(t))
+            .collect::<AssetResult<Vec<_>>>()?;
+
+        let mut materials: Vec<_> = doc.materials()
+            .map(|m| self.load_material(m, &textures))
+            .collect();
+        materials.push((Rc::new(StandardMaterial::default().build(self.asys)), false));
+
+        let meshes = doc.meshes()
+            .map(|m| self.load_mesh(m, &materials))
+            .collect::<AssetResult<_>>()?;
+
+        let nodes = doc.nodes()
+            .map(|node| {
+                let (t, r, s) = node.transform().decomposed();
+
+                PrefabNode {
+                    name: node.name().map(|n| n.to_string()),
+                    transform: Decomposed {
+                        disp: t.into(),
+                        rot: Quaternion::new(r[3], r[0], r[1], r[2]),
+                        scale: 1.0,
+                    },
+                    scale: s.into(),
+                    mesh: node.mesh().map(|m| m.index()),
+                    skin: node.skin().map(|s| s.index()),
+                    children: node.children().map(|c| c.index()).collect(),
+                }
+            })
+            .collect();
+
+        let roots = match doc.default_scene().or_else(|| doc.scenes().next()) {
+            Some(scene) => scene.nodes().map(|n| n.index()).collect(),
+            None => {
+                let children: Vec<usize> = doc.nodes()
+                    .flat_map(|n| n.children())
+                    .map(|c| c.index())
+                    .collect();
+                (0..doc.nodes().len()).filter(|i| !children.contains(i)).collect()
+            }
+        };
+
+        let skins = doc.skins()
+            .map(|skin| {
+                let joints: Vec<usize> = skin.joints().map(|j| j.index()).collect();
+                let inverse_bind_matrices = skin.reader(|buffer| self.buffer_data(buffer))
+                    .read_inverse_bind_matrices()
+                    .map(|m| m.map(Matrix4::from).collect())
+                    .unwrap_or_else(|| vec![Matrix4::identity(); joints.len()]);
+
+                PrefabSkin {
+                    joints,
+                    inverse_bind_matrices,
+                }
+            })
+            .collect();
+
+        let animations = doc.animations()
+            .map(|a| Rc::new(self.load_animation(a)))
+            .collect();
+
+        Ok(Prefab {
+            meshes,
+            nodes,
+            roots,
+            skins,
+            animations,
+            warnings: self.warnings.replace(Vec::new()),
+        })
+    }
+}
+
+impl Prefab {
+    /// Load a glTF 2.0 file, either .gltf with its buffers and images in other files
+    /// or in data uris, or binary .glb. The materials are `StandardMaterial`
+    pub fn load_gltf_future<A>(
+        asys: A,
+        file: FileFuture,
+    ) -> Box<Future<Item = Self, Error = AssetError>>
+    where
+        A: AssetSystem + Clone + 'static,
+    {
+        let parsed = {
+            let asys = asys.clone();
+            read_file(file).and_then(move |(bytes, name)| {
+                let gltf = gltf::Gltf::from_slice(&bytes)
+                    .map_err(|e| invalid_format(&name, bytes.len(), e))?;
+                let parent = parent_path(&name);
+
+                let mut blob = gltf.blob.clone();
+                let buffers: Vec<BufferFuture> = gltf.buffers()
+                    .map(|buffer| -> BufferFuture {
+                        match buffer.source() {
+                            BufferSource::Bin => Box::new(result(blob.take().ok_or_else(|| {
+                                invalid_format(&name, bytes.len(), "missing binary chunk")
+                            }))),
+                            BufferSource::Uri(uri) => match decode_data_uri(&name, uri) {
+                                Some(data) => Box::new(result(data)),
+                                None => Box::new(
+                                    read_file(asys.new_file(&(parent.clone() + uri)))
+                                        .map(|(data, _)| data),
+                                ),
+                            },
+                        }
+                    })
+                    .collect();
+
+                Ok(join_all(buffers).map(move |buffers| (buffers, name, parent, gltf)))
+            })
+        };
+
+        Box::new(
+            parsed
+                .and_then(|r| r)
+                .and_then(move |(buffers, name, parent, gltf)| {
+                    let loader = GltfLoader {
+                        asys: &asys,
+                        name: &name,
+                        parent: &parent,
+                        buffers: &buffers,
+                        warnings: RefCell::new(Vec::new()),
+                    };
+
+                    loader.load(&gltf.document)
+                }),
+        )
+    }
+}
+
+/// Whether the file is loaded by `Prefab::load_gltf_future`
+pub fn is_gltf_name(name: &str) -> bool {
+    let lower = name.to_lowercase();
+    lower.ends_with(".gltf") || lower.ends_with(".glb")
+}
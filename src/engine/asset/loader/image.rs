@@ -1,6 +1,7 @@
 use engine::asset::loader::{Loadable, Loader};
 use engine::asset::{AssetError, AssetResult, AssetSystem, File, FileFuture};
 use engine::TextureImage;
+use image::hdr;
 use image::png;
 use image::tga;
 use image;
@@ -13,7 +14,8 @@ use std::fmt::Debug;
 use uni_app;
 use std::path::Path;
 
-use super::dds::{DDSFormat, DDSReader};
+use super::dds::{DDSFormat, DDSReader, DDS};
+use super::ktx::{KTXReader, KTX_MAGIC_BYTES};
 
 pub struct ImageLoader {}
 
@@ -304,15 +306,96 @@ where
     T: Future<Item = (Vec<u8>, String), Error = AssetError> + 'static,
 {
     let img = img_buf.and_then(|(whole_buf, file_name)| {
-        DDSReader::read(whole_buf, &file_name).map(|dds| match dds.format {
-            DDSFormat::DXT1 => TextureImage::DXT1(dds),
-            DDSFormat::DXT5 => TextureImage::DXT5(dds),
-        })
+        DDSReader::read(whole_buf, &file_name).map(compressed_image)
     });
 
     Box::new(img)
 }
 
+/// KTX files holding the S3TC formats of the dds ones, ETC2 or ASTC, or Basis Universal
+/// ETC1S which is transcoded to rgba
+fn load_future_ktx<T>(img_buf: T) -> Box<Future<Item = TextureImage, Error = AssetError>>
+where
+    T: Future<Item = (Vec<u8>, String), Error = AssetError> + 'static,
+{
+    let img = img_buf.and_then(|(whole_buf, file_name)| {
+        if KTXReader::is_basis(&whole_buf) {
+            return KTXReader::read_basis(&whole_buf, &file_name).map(TextureImage::Rgba);
+        }
+
+        KTXReader::read(whole_buf, &file_name).map(compressed_image)
+    });
+
+    Box::new(img)
+}
+
+fn compressed_image(dds: DDS) -> TextureImage {
+    match dds.format {
+        DDSFormat::DXT1 => TextureImage::DXT1(dds),
+        DDSFormat::DXT5 => TextureImage::DXT5(dds),
+        DDSFormat::ETC2 => TextureImage::ETC2(dds),
+        DDSFormat::ASTC(_, _) => TextureImage::ASTC(dds),
+    }
+}
+
+static HDR_MAGIC_BYTES: &'static [u8] = b"#?";
+
+/// Tone map a linear hdr color channel to an 8 bits srgb one
+fn tone_map(c: f32) -> u8 {
+    let c = c.max(0.0);
+    let mapped = (c / (1.0 + c)).powf(1.0 / 2.2);
+
+    (mapped * 255.0 + 0.5).min(255.0) as u8
+}
+
+/// Radiance hdr images are panoramas, they are tone mapped into an equirectangular image
+fn load_future_hdr<T>(img_buf: T) -> Box<Future<Item = TextureImage, Error = AssetError>>
+where
+    T: Future<Item = (Vec<u8>, String), Error = AssetError> + 'static,
+{
+    let img = img_buf.and_then(|(whole_buf, file_name)| {
+        let info = ImageFileInfo {
+            file_name: file_name,
+            orig_len: whole_buf.len(),
+        };
+
+        let decoder = hdr::HDRDecoder::new(io::Cursor::new(whole_buf))
+            .map_err(|e| make_invalid_format(&info, e))?;
+        let meta = decoder.metadata();
+        let pixels = decoder
+            .read_image_hdr()
+            .map_err(|e| make_invalid_format(&info, e))?;
+
+        let (w, h) = (meta.width, meta.height);
+        let img = image::ImageBuffer::from_fn(w, h, |x, y| {
+            let p = &pixels[(y * w + x) as usize];
+            image::Rgb([tone_map(p.data[0]), tone_map(p.data[1]), tone_map(p.data[2])])
+        });
+
+        Ok(TextureImage::Equirect(img))
+    });
+
+    Box::new(img)
+}
+
+/// Images named `*_equirect` are panoramas like the hdr ones
+fn is_equirect_name(file_name: &str) -> bool {
+    Path::new(file_name)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .map_or(false, |s| s.to_lowercase().ends_with("_equirect"))
+}
+
+fn into_equirect(img: TextureImage) -> TextureImage {
+    match img {
+        TextureImage::Rgb(img) => TextureImage::Equirect(img),
+        TextureImage::Rgba(img) => {
+            TextureImage::Equirect(image::DynamicImage::ImageRgba8(img).to_rgb())
+        }
+        img => img,
+    }
+}
+
 impl Loadable for TextureImage {
     type Loader = ImageLoader;
 
@@ -335,7 +418,22 @@ impl Loadable for TextureImage {
                 return load_future_dds(future::result(Ok((whole_buf, file_name))));
             }
 
-            load_future_uncompressed(future::result(Ok((whole_buf, file_name))))
+            if whole_buf.starts_with(KTX_MAGIC_BYTES) {
+                return load_future_ktx(future::result(Ok((whole_buf, file_name))));
+            }
+
+            if whole_buf.starts_with(HDR_MAGIC_BYTES) {
+                return load_future_hdr(future::result(Ok((whole_buf, file_name))));
+            }
+
+            let equirect = is_equirect_name(&file_name);
+            let img = load_future_uncompressed(future::result(Ok((whole_buf, file_name))));
+
+            if equirect {
+                return Box::new(img.map(into_equirect));
+            }
+
+            img
         }))
     }
 }
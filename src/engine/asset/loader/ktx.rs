@@ -0,0 +1,879 @@
+use engine::asset::{AssetError, AssetResult};
+use image::RgbaImage;
+
+use super::basis::Etc1sDecoder;
+use super::dds::{DDSFormat, DDSImage, DDS};
+
+/// Reads the compressed 2d textures of the KTX 1.1 and KTX 2.0 containers, the mipmap
+/// levels are read like the ones of a dds file. The formats are S3TC (DXT1, DXT5), ETC2
+/// and ASTC. The KTX2 files of Basis Universal ETC1S are transcoded by `read_basis`,
+/// the other supercompressions (e.g. UASTC in zstd) are not supported
+pub struct KTXReader {}
+
+/// Prefix shared by the identifiers of all the KTX versions, i.e. `«KTX `
+pub static KTX_MAGIC_BYTES: &'static [u8] = &[0xAB, 0x4B, 0x54, 0x58, 0x20];
+
+static KTX1_IDENTIFIER: &'static [u8] = &[
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x31, 0x31, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+static KTX2_IDENTIFIER: &'static [u8] = &[
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+const KTX_ENDIANNESS: u32 = 0x0403_0201;
+const KTX_HEADER_LEN: usize = 12 + 13 * 4;
+
+/// The identifier, 9 fields and the offsets and lengths of the data format descriptor,
+/// the key values and the supercompression data
+const KTX2_HEADER_LEN: usize = 12 + 9 * 4 + 4 * 4 + 2 * 8;
+/// The offset, the length and the uncompressed length of a level
+const KTX2_LEVEL_LEN: usize = 3 * 8;
+/// The offset of the offset and the length of the supercompression data
+const KTX2_SGD_OFFSET: usize = 12 + 9 * 4 + 4 * 4;
+
+/// The supercompression of the ETC1S slices of Basis Universal
+const KTX2_BASIS_LZ: u32 = 1;
+/// The counts of the endpoints and the selectors, and the lengths of the endpoints,
+/// the selectors, the tables and the extended data of the BasisLZ global data
+const BASIS_LZ_HEADER_LEN: usize = 2 * 2 + 4 * 4;
+/// The flags, and the offsets and the lengths of the rgb and the alpha slices
+const BASIS_LZ_IMAGE_DESC_LEN: usize = 5 * 4;
+/// The image is a P-frame of a video, predicted from the previous one
+const BASIS_LZ_P_FRAME: u32 = 0x02;
+
+const COMPRESSED_RGB_S3TC_DXT1: u32 = 0x83F0;
+const COMPRESSED_RGBA_S3TC_DXT1: u32 = 0x83F1;
+const COMPRESSED_RGBA_S3TC_DXT5: u32 = 0x83F3;
+const COMPRESSED_RGB8_ETC2: u32 = 0x9274;
+const COMPRESSED_RGBA8_ETC2_EAC: u32 = 0x9278;
+/// The other block sizes follow in the order of `ASTC_BLOCKS`
+const COMPRESSED_RGBA_ASTC_4X4: u32 = 0x93B0;
+
+const VK_FORMAT_BC1_RGB_UNORM_BLOCK: u32 = 131;
+const VK_FORMAT_BC1_RGBA_UNORM_BLOCK: u32 = 133;
+const VK_FORMAT_BC3_UNORM_BLOCK: u32 = 137;
+const VK_FORMAT_ETC2_R8G8B8_UNORM_BLOCK: u32 = 147;
+const VK_FORMAT_ETC2_R8G8B8A8_UNORM_BLOCK: u32 = 151;
+/// The unorm formats of the other block sizes follow every two, after their srgb one
+const VK_FORMAT_ASTC_4X4_UNORM_BLOCK: u32 = 157;
+
+/// Block sizes of the ASTC formats, in the order of their gl and vulkan formats
+const ASTC_BLOCKS: [(u32, u32); 14] = [
+    (4, 4),
+    (5, 4),
+    (5, 5),
+    (6, 5),
+    (6, 6),
+    (8, 5),
+    (8, 6),
+    (8, 8),
+    (10, 5),
+    (10, 6),
+    (10, 8),
+    (10, 10),
+    (12, 10),
+    (12, 12),
+];
+
+fn invalid(buff: &[u8], file_name: &String, reason: String) -> AssetError {
+    AssetError::InvalidFormat {
+        len: buff.len(),
+        path: file_name.clone(),
+        reason,
+    }
+}
+
+/// A block compressed format and the size in pixels and in bytes of its blocks
+struct BlockFormat {
+    format: DDSFormat,
+    has_alpha: bool,
+    block: (u32, u32),
+    block_bytes: usize,
+}
+
+impl BlockFormat {
+    /// The format of a gl internal format, as in the KTX 1.1 files
+    fn from_gl(internal_format: u32) -> Option<BlockFormat> {
+        let (format, has_alpha, block_bytes) = match internal_format {
+            COMPRESSED_RGB_S3TC_DXT1 => (DDSFormat::DXT1, false, 8),
+            COMPRESSED_RGBA_S3TC_DXT1 => (DDSFormat::DXT1, true, 8),
+            COMPRESSED_RGBA_S3TC_DXT5 => (DDSFormat::DXT5, true, 16),
+            COMPRESSED_RGB8_ETC2 => (DDSFormat::ETC2, false, 8),
+            COMPRESSED_RGBA8_ETC2_EAC => (DDSFormat::ETC2, true, 16),
+            f if f >= COMPRESSED_RGBA_ASTC_4X4 => {
+                let (w, h) = *ASTC_BLOCKS.get((f - COMPRESSED_RGBA_ASTC_4X4) as usize)?;
+                (DDSFormat::ASTC(w, h), true, 16)
+            }
+            _ => return None,
+        };
+
+        let block = match format {
+            DDSFormat::ASTC(w, h) => (w, h),
+            _ => (4, 4),
+        };
+
+        Some(BlockFormat {
+            format,
+            has_alpha,
+            block,
+            block_bytes,
+        })
+    }
+
+    /// The format of a vulkan format, as in the KTX 2.0 files. Only the unorm ones
+    /// are supported
+    fn from_vk(vk_format: u32) -> Option<BlockFormat> {
+        let gl_format = match vk_format {
+            VK_FORMAT_BC1_RGB_UNORM_BLOCK => COMPRESSED_RGB_S3TC_DXT1,
+            VK_FORMAT_BC1_RGBA_UNORM_BLOCK => COMPRESSED_RGBA_S3TC_DXT1,
+            VK_FORMAT_BC3_UNORM_BLOCK => COMPRESSED_RGBA_S3TC_DXT5,
+            VK_FORMAT_ETC2_R8G8B8_UNORM_BLOCK => COMPRESSED_RGB8_ETC2,
+            VK_FORMAT_ETC2_R8G8B8A8_UNORM_BLOCK => COMPRESSED_RGBA8_ETC2_EAC,
+            f if f >= VK_FORMAT_ASTC_4X4_UNORM_BLOCK
+                && (f - VK_FORMAT_ASTC_4X4_UNORM_BLOCK) % 2 == 0 =>
+            {
+                COMPRESSED_RGBA_ASTC_4X4 + (f - VK_FORMAT_ASTC_4X4_UNORM_BLOCK) / 2
+            }
+            _ => return None,
+        };
+
+        BlockFormat::from_gl(gl_format)
+    }
+
+    /// The bytes of an image of the format, the partial blocks are rounded up.
+    /// None if it overflows
+    fn image_size(&self, width: u32, height: u32) -> Option<usize> {
+        let (bw, bh) = self.block;
+        let blocks_x = (width as usize).checked_add(bw as usize - 1)? / bw as usize;
+        let blocks_y = (height as usize).checked_add(bh as usize - 1)? / bh as usize;
+
+        blocks_x.checked_mul(blocks_y)?.checked_mul(self.block_bytes)
+    }
+}
+
+fn read_u16_le(buff: &[u8], offset: usize) -> Option<u16> {
+    let b = buff.get(offset..offset.checked_add(2)?)?;
+    Some((b[0] as u16) | (b[1] as u16) << 8)
+}
+
+fn read_u32_le(buff: &[u8], offset: usize) -> Option<u32> {
+    let b = buff.get(offset..offset.checked_add(4)?)?;
+    Some((b[0] as u32) | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24)
+}
+
+/// A u64 of a KTX2 file, None if it does not fit in a usize
+fn read_usize_le(buff: &[u8], offset: usize) -> Option<usize> {
+    let low = read_u32_le(buff, offset)? as u64;
+    let high = read_u32_le(buff, offset.checked_add(4)?)? as u64;
+    let v = low | high << 32;
+
+    if v > usize::max_value() as u64 {
+        return None;
+    }
+    Some(v as usize)
+}
+
+/// The decoder of the BasisLZ global data `sgd` of a 2d texture of `levels` levels, and
+/// the rgb and the alpha slices of the data of the level 0. The alpha one is empty if
+/// the image has no alpha
+fn basis_lz_slices<'a>(
+    sgd: &'a [u8],
+    level: &'a [u8],
+    levels: usize,
+) -> Option<(Etc1sDecoder, &'a [u8], &'a [u8])> {
+    // The codebooks follow the descriptions of the images, one per level
+    let mut offset = levels
+        .checked_mul(BASIS_LZ_IMAGE_DESC_LEN)?
+        .checked_add(BASIS_LZ_HEADER_LEN)?;
+    let mut sections = Vec::new();
+    for i in 0..3 {
+        let len = read_u32_le(sgd, 4 + i * 4)? as usize;
+        let end = offset.checked_add(len)?;
+        sections.push(sgd.get(offset..end)?);
+        offset = end;
+    }
+
+    let decoder = Etc1sDecoder::new(
+        read_u16_le(sgd, 0)? as usize,
+        read_u16_le(sgd, 2)? as usize,
+        sections[0],
+        sections[1],
+        sections[2],
+    )?;
+
+    // The offsets of the slices are in the data of the level
+    let desc = |i: usize| read_u32_le(sgd, BASIS_LZ_HEADER_LEN + i * 4).map(|v| v as usize);
+    if desc(0)? as u32 & BASIS_LZ_P_FRAME != 0 {
+        return None;
+    }
+    let rgb = level.get(desc(1)?..desc(1)?.checked_add(desc(2)?)?)?;
+    let alpha = level.get(desc(3)?..desc(3)?.checked_add(desc(4)?)?)?;
+
+    Some((decoder, rgb, alpha))
+}
+
+impl KTXReader {
+    pub fn read(buff: Vec<u8>, file_name: &String) -> AssetResult<DDS> {
+        if buff.starts_with(KTX2_IDENTIFIER) {
+            return Self::read_ktx2(&buff, file_name);
+        }
+
+        if buff.len() < KTX_HEADER_LEN || !buff.starts_with(KTX1_IDENTIFIER) {
+            return Err(invalid(&buff, file_name, "Invalid KTX Header".to_owned()));
+        }
+
+        let truncated = || invalid(&buff, file_name, "Truncated KTX file".to_owned());
+
+        // The fields are in the byte order of the writer, which is told by the first one
+        let swap = read_u32_le(&buff, 12) != Some(KTX_ENDIANNESS);
+        let read_u32 = |offset: usize| -> Option<u32> {
+            let v = read_u32_le(&buff, offset)?;
+            Some(if swap { v.swap_bytes() } else { v })
+        };
+        let field = |i: usize| read_u32(12 + i * 4).unwrap_or(0);
+
+        let internal_format = field(4);
+        let (width, height, depth) = (field(6), field(7), field(8));
+        let (array_elements, faces, levels) = (field(9), field(10), field(11).max(1));
+        let key_value_len = field(12) as usize;
+
+        let format = Self::block_format(&buff, file_name, BlockFormat::from_gl(internal_format))?;
+        Self::check_2d(&buff, file_name, width, height, depth, array_elements, faces)?;
+
+        let mut data_offset = KTX_HEADER_LEN
+            .checked_add(key_value_len)
+            .ok_or_else(&truncated)?;
+        let (mut width, mut height) = (width, height);
+        let mut images = Vec::new();
+
+        for _ in 0..levels {
+            let image_size = read_u32(data_offset).ok_or_else(&truncated)? as usize;
+            data_offset = data_offset.checked_add(4).ok_or_else(&truncated)?;
+
+            let level = (data_offset, image_size);
+            let data = Self::level_data(&buff, file_name, &format, width, height, level)?;
+            images.push(DDSImage {
+                width,
+                height,
+                data,
+            });
+
+            // The levels are padded to 4 bytes
+            data_offset = image_size
+                .checked_add(3)
+                .and_then(|size| data_offset.checked_add(size & !3))
+                .ok_or_else(|| invalid(&buff, file_name, "Invalid KTX level size".to_owned()))?;
+            width = (width >> 1).max(1);
+            height = (height >> 1).max(1);
+        }
+
+        Ok(DDS {
+            format: format.format,
+            has_alpha: format.has_alpha,
+            images,
+        })
+    }
+
+    /// KTX2 files are always little endian, their levels are indexed after the header
+    fn read_ktx2(buff: &[u8], file_name: &String) -> AssetResult<DDS> {
+        if buff.len() < KTX2_HEADER_LEN {
+            return Err(invalid(buff, file_name, "Invalid KTX2 Header".to_owned()));
+        }
+
+        let field = |i: usize| read_u32_le(buff, 12 + i * 4).unwrap_or(0);
+
+        let vk_format = field(0);
+        let (width, height, depth) = (field(2), field(3), field(4));
+        let (layers, faces, levels) = (field(5), field(6), field(7).max(1));
+
+        if field(8) != 0 {
+            return Err(invalid(
+                buff,
+                file_name,
+                "Unsupported KTX2 supercompression, only Basis Universal ETC1S".to_owned(),
+            ));
+        }
+
+        let format = Self::block_format(buff, file_name, BlockFormat::from_vk(vk_format))?;
+        Self::check_2d(buff, file_name, width, height, depth, layers, faces)?;
+
+        let (mut width, mut height) = (width, height);
+        let mut images = Vec::new();
+
+        for i in 0..levels as usize {
+            let level = i.checked_mul(KTX2_LEVEL_LEN)
+                .and_then(|offset| offset.checked_add(KTX2_HEADER_LEN));
+            let index = level.and_then(|level| {
+                Some((
+                    read_usize_le(buff, level)?,
+                    read_usize_le(buff, level.checked_add(8)?)?,
+                ))
+            });
+
+            let (offset, len) = index
+                .ok_or_else(|| invalid(buff, file_name, "Truncated KTX2 file".to_owned()))?;
+
+            let data = Self::level_data(buff, file_name, &format, width, height, (offset, len))?;
+            images.push(DDSImage {
+                width,
+                height,
+                data,
+            });
+
+            width = (width >> 1).max(1);
+            height = (height >> 1).max(1);
+        }
+
+        Ok(DDS {
+            format: format.format,
+            has_alpha: format.has_alpha,
+            images,
+        })
+    }
+
+    /// Whether the file is a KTX2 one of Basis Universal ETC1S, which is read by
+    /// `read_basis` instead of `read`
+    pub fn is_basis(buff: &[u8]) -> bool {
+        buff.starts_with(KTX2_IDENTIFIER) && read_u32_le(buff, 12 + 8 * 4) == Some(KTX2_BASIS_LZ)
+    }
+
+    /// Transcode the level 0 of a KTX2 file of Basis Universal ETC1S to rgba, the gpu
+    /// generates the mipmaps like the ones of the other images
+    pub fn read_basis(buff: &[u8], file_name: &String) -> AssetResult<RgbaImage> {
+        if buff.len() < KTX2_HEADER_LEN || !Self::is_basis(buff) {
+            return Err(invalid(buff, file_name, "Invalid KTX2 Header".to_owned()));
+        }
+
+        let field = |i: usize| read_u32_le(buff, 12 + i * 4).unwrap_or(0);
+        let (width, height, depth) = (field(2), field(3), field(4));
+        let (layers, faces, levels) = (field(5), field(6), field(7).max(1));
+        Self::check_2d(buff, file_name, width, height, depth, layers, faces)?;
+
+        let truncated = || invalid(buff, file_name, "Truncated KTX2 file".to_owned());
+        let corrupted = || invalid(buff, file_name, "Invalid Basis Universal data".to_owned());
+
+        let sgd = read_usize_le(buff, KTX2_SGD_OFFSET)
+            .and_then(|offset| {
+                let len = read_usize_le(buff, KTX2_SGD_OFFSET + 8)?;
+                buff.get(offset..offset.checked_add(len)?)
+            })
+            .ok_or_else(&truncated)?;
+        let level = read_usize_le(buff, KTX2_HEADER_LEN)
+            .and_then(|offset| {
+                let len = read_usize_le(buff, KTX2_HEADER_LEN + 8)?;
+                buff.get(offset..offset.checked_add(len)?)
+            })
+            .ok_or_else(&truncated)?;
+
+        let (decoder, rgb, alpha) =
+            basis_lz_slices(sgd, level, levels as usize).ok_or_else(&corrupted)?;
+
+        let len = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|pixels| pixels.checked_mul(4))
+            .ok_or_else(&corrupted)?;
+        let mut pixels = vec![0; len];
+
+        decoder
+            .decode_slice(rgb, width, height, false, &mut pixels)
+            .ok_or_else(&corrupted)?;
+        if !alpha.is_empty() {
+            decoder
+                .decode_slice(alpha, width, height, true, &mut pixels)
+                .ok_or_else(&corrupted)?;
+        }
+
+        RgbaImage::from_raw(width, height, pixels).ok_or_else(corrupted)
+    }
+
+    fn block_format(
+        buff: &[u8],
+        file_name: &String,
+        format: Option<BlockFormat>,
+    ) -> AssetResult<BlockFormat> {
+        format.ok_or_else(|| {
+            invalid(
+                buff,
+                file_name,
+                "Unsupported Format, only support DXT1, DXT5, ETC2 and ASTC".to_owned(),
+            )
+        })
+    }
+
+    fn check_2d(
+        buff: &[u8],
+        file_name: &String,
+        width: u32,
+        height: u32,
+        depth: u32,
+        layers: u32,
+        faces: u32,
+    ) -> AssetResult<()> {
+        if width == 0 || height == 0 || depth > 1 || layers > 1 || faces > 1 {
+            return Err(invalid(
+                buff,
+                file_name,
+                "Unsupported KTX texture, only 2d textures are supported".to_owned(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// The data of a level of `width` x `height` stored at `offset` in `len` bytes
+    fn level_data(
+        buff: &[u8],
+        file_name: &String,
+        format: &BlockFormat,
+        width: u32,
+        height: u32,
+        (offset, len): (usize, usize),
+    ) -> AssetResult<Vec<u8>> {
+        let truncated = || invalid(buff, file_name, "Truncated KTX file".to_owned());
+
+        let expected = format.image_size(width, height).ok_or_else(&truncated)?;
+        if len < expected {
+            return Err(invalid(
+                buff,
+                file_name,
+                format!("KTX level of {} bytes instead of {}", len, expected),
+            ));
+        }
+
+        let end = offset.checked_add(expected).ok_or_else(&truncated)?;
+        buff.get(offset..end)
+            .map(|data| data.to_vec())
+            .ok_or_else(truncated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_u32(out: &mut Vec<u8>, v: u32) {
+        out.extend_from_slice(&[v as u8, (v >> 8) as u8, (v >> 16) as u8, (v >> 24) as u8]);
+    }
+
+    fn push_u64(out: &mut Vec<u8>, v: u64) {
+        push_u32(out, v as u32);
+        push_u32(out, (v >> 32) as u32);
+    }
+
+    /// A KTX 1.1 file of the levels of `sizes` bytes, each filled with its level index
+    fn ktx1(internal_format: u32, width: u32, height: u32, sizes: &[u32]) -> Vec<u8> {
+        let mut out = KTX1_IDENTIFIER.to_vec();
+        let fields = [
+            KTX_ENDIANNESS,
+            0,
+            1,
+            0,
+            internal_format,
+            0,
+            width,
+            height,
+            0,
+            0,
+            1,
+            sizes.len() as u32,
+            0,
+        ];
+        for &field in fields.iter() {
+            push_u32(&mut out, field);
+        }
+
+        for (i, &size) in sizes.iter().enumerate() {
+            push_u32(&mut out, size);
+            out.extend(vec![i as u8; size as usize]);
+            while out.len() % 4 != 0 {
+                out.push(0xff);
+            }
+        }
+        out
+    }
+
+    /// A KTX2 file of the levels of `sizes` bytes
+    fn ktx2(vk_format: u32, width: u32, height: u32, scheme: u32, sizes: &[u64]) -> Vec<u8> {
+        let mut out = KTX2_IDENTIFIER.to_vec();
+        let fields = [vk_format, 1, width, height, 0, 0, 1, sizes.len() as u32, scheme];
+        for &field in fields.iter() {
+            push_u32(&mut out, field);
+        }
+        for _ in 0..4 {
+            push_u32(&mut out, 0);
+        }
+        push_u64(&mut out, 0);
+        push_u64(&mut out, 0);
+
+        let mut offset = (KTX2_HEADER_LEN + sizes.len() * KTX2_LEVEL_LEN) as u64;
+        for &size in sizes.iter() {
+            push_u64(&mut out, offset);
+            push_u64(&mut out, size);
+            push_u64(&mut out, size);
+            offset += size;
+        }
+        for (i, &size) in sizes.iter().enumerate() {
+            out.extend(vec![i as u8; size as usize]);
+        }
+        out
+    }
+
+    fn read(buff: Vec<u8>) -> AssetResult<DDS> {
+        KTXReader::read(buff, &"test.ktx".to_string())
+    }
+
+    fn is_invalid(r: AssetResult<DDS>) -> bool {
+        match r {
+            Err(AssetError::InvalidFormat { .. }) => true,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn dxt1_levels() {
+        let dds = read(ktx1(COMPRESSED_RGBA_S3TC_DXT1, 8, 8, &[32, 8, 8, 8])).unwrap();
+
+        assert!(dds.has_alpha);
+        assert!(match dds.format {
+            DDSFormat::DXT1 => true,
+            _ => false,
+        });
+        let sizes: Vec<_> = dds.images.iter().map(|i| (i.width, i.height)).collect();
+        assert_eq!(sizes, vec![(8, 8), (4, 4), (2, 2), (1, 1)]);
+        assert_eq!(dds.images[1].data, vec![1; 8]);
+    }
+
+    #[test]
+    fn partial_blocks_round_up() {
+        // 5x5 is 2x2 blocks of 4x4
+        let dds = read(ktx1(COMPRESSED_RGBA_S3TC_DXT5, 5, 5, &[64, 16])).unwrap();
+        assert_eq!(dds.images[0].data.len(), 64);
+        assert_eq!(dds.images[1].data, vec![1; 16]);
+
+        // 10x10 ASTC 6x6 is 2x2 blocks
+        let dds = read(ktx1(COMPRESSED_RGBA_ASTC_4X4 + 4, 10, 10, &[64])).unwrap();
+        assert!(match dds.format {
+            DDSFormat::ASTC(6, 6) => true,
+            _ => false,
+        });
+        assert_eq!(dds.images[0].data.len(), 64);
+
+        // A 2x2 level is a whole block
+        assert!(is_invalid(read(ktx1(COMPRESSED_RGB_S3TC_DXT1, 2, 2, &[5]))));
+    }
+
+    #[test]
+    fn big_endian_fields() {
+        let mut buff = ktx1(COMPRESSED_RGB8_ETC2, 4, 4, &[8]);
+        for field in buff[12..KTX_HEADER_LEN + 4].chunks_mut(4) {
+            field.reverse();
+        }
+
+        let dds = read(buff).unwrap();
+        assert!(!dds.has_alpha);
+        assert!(match dds.format {
+            DDSFormat::ETC2 => true,
+            _ => false,
+        });
+        assert_eq!(dds.images[0].data, vec![0; 8]);
+    }
+
+    #[test]
+    fn invalid_ktx1() {
+        assert!(is_invalid(read(KTX1_IDENTIFIER.to_vec())));
+        assert!(is_invalid(read(ktx1(0x1908, 4, 4, &[64]))));
+
+        // Truncated level
+        let mut buff = ktx1(COMPRESSED_RGBA_S3TC_DXT5, 8, 8, &[64]);
+        buff.truncate(buff.len() - 1);
+        assert!(is_invalid(read(buff)));
+
+        // A level size beyond the file, the next level is not found
+        let mut buff = ktx1(COMPRESSED_RGB_S3TC_DXT1, 4, 4, &[8, 8]);
+        buff[KTX_HEADER_LEN..KTX_HEADER_LEN + 4].copy_from_slice(&[0xff; 4]);
+        assert!(is_invalid(read(buff)));
+
+        // Key values beyond the file
+        let mut buff = ktx1(COMPRESSED_RGB_S3TC_DXT1, 4, 4, &[8]);
+        buff[KTX_HEADER_LEN - 4..KTX_HEADER_LEN].copy_from_slice(&[0xff; 4]);
+        assert!(is_invalid(read(buff)));
+
+        // Cube maps are not supported
+        let mut buff = ktx1(COMPRESSED_RGB_S3TC_DXT1, 4, 4, &[8]);
+        buff[12 + 10 * 4] = 6;
+        assert!(is_invalid(read(buff)));
+    }
+
+    #[test]
+    fn ktx2_levels() {
+        let dds = read(ktx2(VK_FORMAT_ETC2_R8G8B8A8_UNORM_BLOCK, 8, 4, 0, &[32, 16])).unwrap();
+        assert!(dds.has_alpha);
+        let sizes: Vec<_> = dds.images.iter().map(|i| (i.width, i.height)).collect();
+        assert_eq!(sizes, vec![(8, 4), (4, 2)]);
+        assert_eq!(dds.images[1].data, vec![1; 16]);
+
+        let dds = read(ktx2(VK_FORMAT_ASTC_4X4_UNORM_BLOCK + 2 * 7, 8, 8, 0, &[16])).unwrap();
+        assert!(match dds.format {
+            DDSFormat::ASTC(8, 8) => true,
+            _ => false,
+        });
+    }
+
+    #[test]
+    fn invalid_ktx2() {
+        // Basis Universal ETC1S, which is transcoded by read_basis instead
+        assert!(is_invalid(read(ktx2(VK_FORMAT_BC3_UNORM_BLOCK, 4, 4, 1, &[16]))));
+        // Zstandard
+        assert!(is_invalid(read(ktx2(VK_FORMAT_BC3_UNORM_BLOCK, 4, 4, 2, &[16]))));
+        // The srgb ASTC 4x4 format
+        assert!(is_invalid(read(ktx2(VK_FORMAT_ASTC_4X4_UNORM_BLOCK + 1, 4, 4, 0, &[16]))));
+        // Level smaller than its blocks
+        assert!(is_invalid(read(ktx2(VK_FORMAT_BC3_UNORM_BLOCK, 8, 8, 0, &[16]))));
+
+        let mut buff = ktx2(VK_FORMAT_BC1_RGB_UNORM_BLOCK, 4, 4, 0, &[8]);
+        buff.truncate(KTX2_HEADER_LEN + 8);
+        assert!(is_invalid(read(buff)));
+
+        // A level offset beyond the file
+        let mut buff = ktx2(VK_FORMAT_BC1_RGB_UNORM_BLOCK, 4, 4, 0, &[8]);
+        buff[KTX2_HEADER_LEN..KTX2_HEADER_LEN + 8].copy_from_slice(&[0xff; 8]);
+        assert!(is_invalid(read(buff)));
+    }
+
+    /// Writes the bits from the lowest of each byte, like the BasisLZ streams
+    #[derive(Default)]
+    struct BitWriter {
+        out: Vec<u8>,
+        len: usize,
+    }
+
+    impl BitWriter {
+        fn bits(&mut self, v: u32, n: u32) -> &mut Self {
+            for i in 0..n {
+                if self.len % 8 == 0 {
+                    self.out.push(0);
+                }
+                self.out[self.len / 8] |= (((v >> i) & 1) as u8) << (self.len % 8);
+                self.len += 1;
+            }
+            self
+        }
+
+        /// A huffman code, from its highest bit
+        fn code(&mut self, code: u32, size: u32) -> &mut Self {
+            for i in (0..size).rev() {
+                self.bits(code >> i, 1);
+            }
+            self
+        }
+
+        /// A huffman table of `count` symbols of codes of `size` bits, the code of a
+        /// symbol is the symbol itself
+        fn table(&mut self, count: u32, size: u32) -> &mut Self {
+            let order = [
+                17, 18, 19, 20, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15, 16,
+            ];
+
+            // The sizes are coded by a single code length code of a single bit
+            self.bits(count, 14).bits(21, 5);
+            for &code in order.iter() {
+                self.bits(if code == size { 1 } else { 0 }, 3);
+            }
+            for _ in 0..count {
+                self.code(0, 1);
+            }
+            self
+        }
+    }
+
+    /// The color5 and the intensities of the endpoints, and the rows of the selectors
+    const ENDPOINTS: [([u32; 3], u32); 2] = [([20, 10, 5], 2), ([31, 0, 16], 5)];
+    const SELECTORS: [[u32; 4]; 2] = [[0x1b, 0xe4, 0x00, 0xff], [0x55, 0xaa, 0x0f, 0xf0]];
+
+    fn etc1s_codebooks() -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let mut endpoints = BitWriter::default();
+        endpoints.table(32, 5).table(32, 5).table(32, 5).table(8, 3).bits(0, 1);
+        let mut prev = ([16, 16, 16], 0);
+        for &(color5, inten) in ENDPOINTS.iter() {
+            endpoints.code((inten + 8 - prev.1) % 8, 3);
+            for c in 0..3 {
+                endpoints.code((color5[c] + 32 - prev.0[c]) % 32, 5);
+            }
+            prev = (color5, inten);
+        }
+
+        // The selectors after the first are xored with the previous ones
+        let mut selectors = BitWriter::default();
+        selectors.bits(0, 3).table(256, 8);
+        for &row in SELECTORS[0].iter() {
+            selectors.bits(row, 8);
+        }
+        for (&row, &prev) in SELECTORS[1].iter().zip(SELECTORS[0].iter()) {
+            selectors.code(row ^ prev, 8);
+        }
+
+        // The selector symbols are the 2 selectors, a history of 2 and its run
+        let mut tables = BitWriter::default();
+        tables
+            .table(257, 9)
+            .table(2, 1)
+            .table(5, 3)
+            .table(64, 6)
+            .bits(2, 13);
+
+        (endpoints.out, selectors.out, tables.out)
+    }
+
+    /// The 2x2 blocks of the rgb slice are the endpoints 0, 0, 1, 0 by the delta, the
+    /// left, the delta and the upper left predictions, and the selectors 1, 1, 0, 1
+    /// directly, from the history, directly and by a run of the history
+    fn etc1s_rgb_slice() -> Vec<u8> {
+        let mut w = BitWriter::default();
+        w.code(3 | 3 << 4 | 2 << 6, 9).code(0, 1).code(1, 3);
+        w.code(2 + 1, 3);
+        w.code(1, 1).code(0, 3);
+        w.code(4, 3).code(0, 6);
+        w.out
+    }
+
+    /// All the blocks of the alpha slice are the endpoint 1 and the selector 0
+    fn etc1s_alpha_slice() -> Vec<u8> {
+        let mut w = BitWriter::default();
+        w.code(0xff, 9).code(1, 1).code(0, 3);
+        for _ in 0..3 {
+            w.code(0, 1).code(0, 3);
+        }
+        w.out
+    }
+
+    /// A KTX2 file of Basis Universal ETC1S of a single level
+    fn ktx2_basis(width: u32, height: u32, rgb: &[u8], alpha: &[u8]) -> Vec<u8> {
+        let (endpoints, selectors, tables) = etc1s_codebooks();
+
+        let mut sgd = Vec::new();
+        sgd.extend_from_slice(&[2, 0, 2, 0]);
+        for &len in [endpoints.len(), selectors.len(), tables.len(), 0].iter() {
+            push_u32(&mut sgd, len as u32);
+        }
+        let desc = [0, 0, rgb.len(), rgb.len(), alpha.len()];
+        for &field in desc.iter() {
+            push_u32(&mut sgd, field as u32);
+        }
+        sgd.extend(endpoints);
+        sgd.extend(selectors);
+        sgd.extend(tables);
+
+        let mut out = KTX2_IDENTIFIER.to_vec();
+        let fields = [0, 1, width, height, 0, 0, 1, 1, KTX2_BASIS_LZ];
+        for &field in fields.iter() {
+            push_u32(&mut out, field);
+        }
+        for _ in 0..4 {
+            push_u32(&mut out, 0);
+        }
+        let sgd_offset = (KTX2_HEADER_LEN + KTX2_LEVEL_LEN) as u64;
+        push_u64(&mut out, sgd_offset);
+        push_u64(&mut out, sgd.len() as u64);
+
+        push_u64(&mut out, sgd_offset + sgd.len() as u64);
+        push_u64(&mut out, (rgb.len() + alpha.len()) as u64);
+        push_u64(&mut out, 0);
+
+        out.extend(sgd);
+        out.extend_from_slice(rgb);
+        out.extend_from_slice(alpha);
+        out
+    }
+
+    /// The color of the pixel `(x, y)` of a block of `endpoint` and `selector`
+    fn etc1s_pixel(endpoint: usize, selector: usize, x: u32, y: u32) -> [u8; 3] {
+        let modifiers = [8, 17, 29, 42, 60, 80, 106, 183];
+        let small = [2, 5, 9, 13, 18, 24, 33, 47];
+
+        let (color5, inten) = ENDPOINTS[endpoint];
+        let m = match (SELECTORS[selector][y as usize] >> (x * 2)) & 3 {
+            0 => -modifiers[inten as usize],
+            1 => -small[inten as usize],
+            2 => small[inten as usize],
+            _ => modifiers[inten as usize],
+        };
+
+        let mut color = [0; 3];
+        for c in 0..3 {
+            let c8 = (color5[c] << 3 | color5[c] >> 2) as i32;
+            color[c] = (c8 + m).max(0).min(255) as u8;
+        }
+        color
+    }
+
+    fn read_basis(buff: Vec<u8>) -> AssetResult<RgbaImage> {
+        KTXReader::read_basis(&buff, &"test.ktx2".to_string())
+    }
+
+    #[test]
+    fn basis_etc1s() {
+        let buff = ktx2_basis(8, 7, &etc1s_rgb_slice(), &[]);
+        assert!(KTXReader::is_basis(&buff));
+        assert!(!KTXReader::is_basis(&ktx2(VK_FORMAT_BC3_UNORM_BLOCK, 4, 4, 0, &[16])));
+
+        let img = read_basis(buff).unwrap();
+        assert_eq!(img.dimensions(), (8, 7));
+
+        let blocks = [(0, 1), (0, 1), (1, 0), (0, 1)];
+        for y in 0..7 {
+            for x in 0..8 {
+                let (endpoint, selector) = blocks[(y / 4 * 2 + x / 4) as usize];
+                let color = etc1s_pixel(endpoint, selector, x % 4, y % 4);
+
+                let p = img.get_pixel(x, y).data;
+                assert_eq!((p[0], p[1], p[2], p[3]), (color[0], color[1], color[2], 255));
+            }
+        }
+    }
+
+    #[test]
+    fn basis_etc1s_alpha() {
+        let img = read_basis(ktx2_basis(8, 8, &etc1s_rgb_slice(), &etc1s_alpha_slice()));
+        let img = img.unwrap();
+
+        for y in 0..8 {
+            for x in 0..8 {
+                let alpha = etc1s_pixel(1, 0, x % 4, y % 4)[1];
+                assert_eq!(img.get_pixel(x, y).data[3], alpha);
+            }
+        }
+        assert_eq!(img.get_pixel(4, 0).data[0], etc1s_pixel(0, 1, 0, 0)[0]);
+    }
+
+    #[test]
+    fn invalid_basis() {
+        let is_invalid = |r: AssetResult<RgbaImage>| match r {
+            Err(AssetError::InvalidFormat { .. }) => true,
+            _ => false,
+        };
+
+        // The slice ends before its blocks
+        let mut rgb = etc1s_rgb_slice();
+        rgb.truncate(2);
+        assert!(is_invalid(read_basis(ktx2_basis(8, 8, &rgb, &[]))));
+
+        // The upper prediction of the first row
+        let mut w = BitWriter::default();
+        w.code(1, 9);
+        assert!(is_invalid(read_basis(ktx2_basis(4, 4, &w.out, &[]))));
+
+        // A truncated global data
+        let mut buff = ktx2_basis(8, 8, &etc1s_rgb_slice(), &[]);
+        let sgd_len = read_usize_le(&buff, KTX2_SGD_OFFSET + 8).unwrap();
+        let mut len = Vec::new();
+        push_u64(&mut len, sgd_len as u64 - 4);
+        buff[KTX2_SGD_OFFSET + 8..KTX2_SGD_OFFSET + 16].copy_from_slice(&len);
+        assert!(is_invalid(read_basis(buff)));
+
+        // P-frames of the videos
+        let mut buff = ktx2_basis(8, 8, &etc1s_rgb_slice(), &[]);
+        let flags = KTX2_HEADER_LEN + KTX2_LEVEL_LEN + BASIS_LZ_HEADER_LEN;
+        buff[flags] = BASIS_LZ_P_FRAME as u8;
+        assert!(is_invalid(read_basis(buff)));
+    }
+}
@@ -50,9 +50,14 @@ impl Loader<MeshData> for MeshDataLoader {
             indices,
             vertices,
             uvs: Some(uvs),
+            uvs2: None,
             normals: Some(normals),
             tangents: None,
             bitangents: None,
+            colors: None,
+            joints: None,
+            weights: None,
+            morph_targets: Vec::new(),
         })
     }
 }
@@ -3,10 +3,18 @@ mod image;
 mod shader;
 mod mesh_data;
 mod prefab;
+mod gltf;
 mod dds;
+mod ktx;
+mod basis;
+mod font;
+mod atlas;
 
 pub use self::loader::{Loadable, Loader};
 pub use self::image::ImageLoader;
 pub use self::shader::{ShaderFSLoader, ShaderVSLoader};
-pub use self::prefab::{ObjMaterial, Prefab, PrefabLoader};
-pub use self::dds::DDS;
+pub use self::prefab::{ObjMaterial, Prefab, PrefabLoader, PrefabNode, PrefabSkin};
+pub use self::gltf::is_gltf_name;
+pub use self::dds::{DDSFormat, DDS};
+pub use self::font::FontData;
+pub use self::atlas::AtlasData;
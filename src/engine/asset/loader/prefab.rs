@@ -1,4 +1,5 @@
-use engine::asset::{Asset, AssetError, AssetSystem, FileFuture, Resource};
+use engine::asset::{Asset, AssetError, AssetSystem, File, FileFuture, FileIoError, Resource};
+use engine::core::AnimationClip;
 use engine::render::{Material, Mesh, MeshBuffer, MeshData};
 use std::borrow::Cow;
 use std::path::Path;
@@ -12,12 +13,44 @@ use std::rc::Rc;
 
 use futures::future::*;
 
+/// A node of the hierarchy of a prefab, an object once instantiated
+#[derive(Clone)]
+pub struct PrefabNode {
+    pub name: Option<String>,
+    pub transform: Isometry3<f32>,
+    pub scale: Vector3<f32>,
+    /// Index of the mesh of the node in `Prefab::meshes`
+    pub mesh: Option<usize>,
+    /// Index of the skin of the mesh in `Prefab::skins`
+    pub skin: Option<usize>,
+    /// Indices of the children in `Prefab::nodes`
+    pub children: Vec<usize>,
+}
+
+/// The joints of a skinned mesh, see `Skin`
+#[derive(Clone)]
+pub struct PrefabSkin {
+    /// Indices of the nodes of the joints in `Prefab::nodes`
+    pub joints: Vec<usize>,
+    pub inverse_bind_matrices: Vec<Matrix4<f32>>,
+}
+
+/// The meshes of a model file. The formats with a scene, e.g. glTF, also have the
+/// hierarchy of nodes which places them, their skins and animations, see
+/// `World::instantiate`. The nodes of an OBJ file are empty
 pub struct Prefab {
-    // TODO what is a prefab actually ??
     pub meshes: Vec<Mesh>,
+    pub nodes: Vec<PrefabNode>,
+    /// Indices of the nodes at the top of the hierarchy
+    pub roots: Vec<usize>,
+    pub skins: Vec<PrefabSkin>,
+    /// The clips animate the nodes, the targets of their channels are node indices
+    pub animations: Vec<Rc<AnimationClip>>,
+    /// Problems of the file which did not stop the import, e.g. skipped faces
+    pub warnings: Vec<String>,
 }
 
-fn parent_path(filename: &str) -> String {
+pub fn parent_path(filename: &str) -> String {
     let path = Path::new(filename);
     let parent = path.parent();
     let mut parent = parent
@@ -238,6 +271,8 @@ where
     A: AssetSystem + Clone + 'static,
 {
     map: HashMap<String, (WithNormalMap, Rc<Material>)>,
+    /// Material of the groups without one
+    default: Option<Rc<Material>>,
     parent_path: String,
     asys: A,
     builder: MaterialBuilder,
@@ -250,6 +285,7 @@ where
     fn new(asys: A, parent_path: String, builder: MaterialBuilder) -> MaterialCache<A> {
         MaterialCache {
             map: HashMap::new(),
+            default: None,
             parent_path,
             asys,
             builder,
@@ -269,6 +305,15 @@ where
         }
     }
 
+    fn get_default(&mut self) -> (WithNormalMap, Rc<Material>) {
+        if self.default.is_none() {
+            let material = (*self.builder)(&self.asys, ObjMaterial::default());
+            self.default = Some(material);
+        }
+
+        (WithNormalMap(false), self.default.clone().unwrap())
+    }
+
     fn obj_mtl(&self, gm: &obj::Material) -> (WithNormalMap, Rc<Material>) {
         let obj_mat = ObjMaterial::from(gm, &self.parent_path);
         let has_normal_map = obj_mat.normal_map.is_some();
@@ -279,125 +324,277 @@ where
     }
 }
 
+/// The vertices of a surface being built from the faces of a group
+#[derive(Default)]
+struct SurfaceBuilder {
+    indices: Vec<u16>,
+    v_array: Vec<f32>,
+    uv_array: Vec<f32>,
+    n_array: Vec<f32>,
+    has_uvs: bool,
+    /// Whether each vertex has a normal in the file
+    has_normals: Vec<bool>,
+}
+
+impl SurfaceBuilder {
+    fn vertex_count(&self) -> usize {
+        self.v_array.len() / 3
+    }
+
+    fn add_vertex(&mut self, model: &obj::Obj<SimplePolygon>, index_tuple: obj::IndexTuple) {
+        self.indices.push(self.indices.len() as u16);
+        self.v_array.extend_from_slice(&model.position[index_tuple.0]);
+
+        match index_tuple.1 {
+            Some(uv) => {
+                self.uv_array.push(model.texture[uv][0]);
+                self.uv_array.push(1.0 - model.texture[uv][1]);
+                self.has_uvs = true;
+            }
+            None => self.uv_array.extend_from_slice(&[0.0, 0.0]),
+        }
+
+        match index_tuple.2 {
+            Some(n) => {
+                self.n_array.extend_from_slice(&model.normal[n]);
+                self.has_normals.push(true);
+            }
+            None => {
+                self.n_array.extend_from_slice(&[0.0, 0.0, 0.0]);
+                self.has_normals.push(false);
+            }
+        }
+    }
+
+    /// Give the vertices without normals the ones of their faces, the faces are flat
+    fn generate_normals(&mut self) {
+        for (tri, has_normals) in self.v_array
+            .chunks(9)
+            .zip(self.has_normals.chunks(3))
+            .enumerate()
+        {
+            if has_normals.iter().all(|&b| b) {
+                continue;
+            }
+
+            let (p0, p1, p2) = (
+                from_slice_v3(0, tri),
+                from_slice_v3(1, tri),
+                from_slice_v3(2, tri),
+            );
+            let n = (p1 - p0).cross(p2 - p0);
+            let n = if n.magnitude2() > 0.0 {
+                n.normalize()
+            } else {
+                Vector3::unit_y()
+            };
+
+            for (i, &has_normal) in has_normals.iter().enumerate() {
+                if !has_normal {
+                    let k = (tri * 3 + i) * 3;
+                    self.n_array[k..k + 3].copy_from_slice(&[n.x, n.y, n.z]);
+                }
+            }
+        }
+    }
+
+    fn build(mut self, with_normal_map: WithNormalMap) -> MeshData {
+        if self.has_normals.iter().any(|&b| !b) {
+            self.generate_normals();
+        }
+
+        let uv_array = if self.has_uvs {
+            Some(self.uv_array)
+        } else {
+            None
+        };
+        let n_array = Some(self.n_array);
+
+        let tangent_space = if with_normal_map.0 {
+            compute_tangents(&self.v_array, &uv_array, &n_array, &self.indices)
+        } else {
+            TangentSpace {
+                tangents: None,
+                bitangents: None,
+            }
+        };
+
+        MeshData {
+            indices: self.indices,
+            vertices: self.v_array,
+            uvs: uv_array,
+            uvs2: None,
+            tangents: tangent_space.tangents,
+            bitangents: tangent_space.bitangents,
+            colors: None,
+            normals: n_array,
+            joints: None,
+            weights: None,
+            morph_targets: Vec::new(),
+        }
+    }
+}
+
 impl PrefabLoader {
+    /// Build a surface of each group, the groups are split by `usemtl` too. The polygons
+    /// are triangulated as fans, and a group of more vertices than the u16 indices hold
+    /// is split in several surfaces
     fn load_model<A>(
         asys: A,
         parent: String,
         model: obj::Obj<SimplePolygon>,
         builder: MaterialBuilder,
+        mut warnings: Vec<String>,
     ) -> Prefab
     where
         A: AssetSystem + Clone + 'static,
     {
-        let vertices: Vec<[f32; 3]> = model.position;
-        let uvs: Vec<[f32; 2]> = model.texture;
-        let normals: Vec<[f32; 3]> = model.normal;
-
         // create the mesh componet
         let mut mesh = Mesh::new();
 
         let mut material_cache = MaterialCache::new(asys.clone(), parent.clone(), builder);
+        let max_vertices = u16::max_value() as usize + 1;
 
-        for o in model.objects {
-            for g in o.groups {
-                if g.material.is_none() {
-                    continue;
-                }
-                let material = g.material.as_ref().unwrap();
-                let (has_normal_map, material) = material_cache.get_or_insert(&material);
-
-                let mut indices = Vec::new();
-                let mut v_array = Vec::new();
-                let mut uv_array = Vec::new();
-                let mut n_array = Vec::new();
-
-                let mut add_v = |index_tuple: obj::IndexTuple| {
-                    indices.push(indices.len() as u16);
-                    v_array.extend_from_slice(&vertices[index_tuple.0]);
-                    index_tuple.1.map(|uv| {
-                        uv_array.push(uvs[uv][0]);
-                        uv_array.push(1.0 - uvs[uv][1]);
-                    });
-                    index_tuple.2.map(|n| {
-                        n_array.extend_from_slice(&normals[n]);
-                    });
+        for o in model.objects.iter() {
+            for g in o.groups.iter() {
+                let (has_normal_map, material) = match g.material {
+                    Some(ref material) => material_cache.get_or_insert(&material),
+                    None => material_cache.get_default(),
                 };
 
-                for poly in g.polys {
-                    // assert_eq!(poly.len(), 3, "We only handle triangle obj files");
-                    match poly.len() {
-                        3 => {
-                            add_v(poly[0]);
-                            add_v(poly[1]);
-                            add_v(poly[2]);
-                        }
-                        4 => {
-                            add_v(poly[0]);
-                            add_v(poly[1]);
-                            add_v(poly[2]);
-
-                            add_v(poly[2]);
-                            add_v(poly[3]);
-                            add_v(poly[0]);
-                        }
+                let mut surfaces = Vec::new();
+                let mut surface = SurfaceBuilder::default();
+                let mut skipped = 0;
 
-                        _ => panic!("We only handle triangle or quad obj files"),
+                for poly in g.polys.iter() {
+                    let valid = poly.len() >= 3 && poly.iter().all(|t| {
+                        t.0 < model.position.len()
+                            && t.1.map_or(true, |uv| uv < model.texture.len())
+                            && t.2.map_or(true, |n| n < model.normal.len())
+                    });
+                    if !valid {
+                        skipped += 1;
+                        continue;
                     }
-                }
 
-                let uv_array = if uv_array.len() > 0 {
-                    Some(uv_array)
-                } else {
-                    None
-                };
-                let n_array = if n_array.len() > 0 {
-                    Some(n_array)
-                } else {
-                    None
-                };
+                    let count = (poly.len() - 2) * 3;
+                    if surface.vertex_count() + count > max_vertices {
+                        surfaces.push(surface);
+                        surface = SurfaceBuilder::default();
+                    }
 
-                let tangent_space = if has_normal_map.0 {
-                    compute_tangents(&v_array, &uv_array, &n_array, &indices)
-                } else {
-                    TangentSpace {
-                        tangents: None,
-                        bitangents: None,
+                    for i in 1..poly.len() - 1 {
+                        surface.add_vertex(&model, poly[0]);
+                        surface.add_vertex(&model, poly[i]);
+                        surface.add_vertex(&model, poly[i + 1]);
                     }
-                };
+                }
+                surfaces.push(surface);
 
-                let mesh_data = MeshData {
-                    indices: indices,
-                    vertices: v_array,
-                    uvs: uv_array,
-                    tangents: tangent_space.tangents,
-                    bitangents: tangent_space.bitangents,
-                    normals: n_array,
-                };
+                if skipped > 0 {
+                    warnings.push(format!(
+                        "skipped {} faces of invalid indices or less than 3 vertices in group {}",
+                        skipped, g.name
+                    ));
+                }
+                if surfaces.iter().any(|s| s.has_normals.iter().any(|&b| !b)) {
+                    warnings.push(format!("generated the missing normals of group {}", g.name));
+                }
+
+                for surface in surfaces.into_iter().filter(|s| s.vertex_count() > 0) {
+                    let mesh_data = surface.build(has_normal_map);
 
-                mesh.add_surface(
-                    MeshBuffer::new_from_resource(Resource::new(mesh_data)),
-                    material,
-                );
+                    mesh.add_surface(
+                        MeshBuffer::new_from_resource(Resource::new(mesh_data)),
+                        material.clone(),
+                    );
+                }
             }
         }
 
-        Prefab { meshes: vec![mesh] }
+        Prefab {
+            meshes: vec![mesh],
+            nodes: Vec::new(),
+            roots: Vec::new(),
+            skins: Vec::new(),
+            animations: Vec::new(),
+            warnings,
+        }
     }
 }
 
-fn get_mtl_files<A>(asys: A, basedir: &str, o: &mut obj::Obj<SimplePolygon>) -> Vec<FileFuture>
+/// Replace the negative indices of the faces, which are relative to the end of the
+/// vertices read so far, by absolute ones
+fn resolve_relative_indices(src: &str) -> Cow<str> {
+    if !src.contains("/-") && !src.contains(" -") {
+        return Cow::from(src);
+    }
+
+    let mut counts = [0isize; 3];
+    let mut out = String::with_capacity(src.len());
+
+    for line in src.lines() {
+        let mut words = line.split_whitespace();
+
+        match words.next() {
+            Some("v") => counts[0] += 1,
+            Some("vt") => counts[1] += 1,
+            Some("vn") => counts[2] += 1,
+            Some("f") => {
+                let vertices: Vec<String> = words
+                    .map(|vertex| {
+                        let parts: Vec<String> = vertex
+                            .split('/')
+                            .enumerate()
+                            .map(|(i, part)| match part.parse::<isize>() {
+                                Ok(index) if index < 0 && i < 3 => {
+                                    (counts[i] + index + 1).to_string()
+                                }
+                                _ => part.to_string(),
+                            })
+                            .collect();
+                        parts.join("/")
+                    })
+                    .collect();
+
+                out.push_str("f ");
+                out.push_str(&vertices.join(" "));
+                out.push('\n');
+                continue;
+            }
+            _ => (),
+        }
+
+        out.push_str(line);
+        out.push('\n');
+    }
+
+    Cow::from(out)
+}
+
+type MtlFuture = Box<Future<Item = (String, Option<Box<File>>), Error = FileIoError>>;
+
+/// The material libraries of the model, the ones which failed to load are None
+fn get_mtl_files<A>(asys: A, basedir: &str, o: &mut obj::Obj<SimplePolygon>) -> Vec<MtlFuture>
 where
     A: AssetSystem + Clone + 'static,
 {
-    let mut files: Vec<FileFuture> = Vec::new();
+    let mut files: Vec<MtlFuture> = Vec::new();
 
     for m in &o.material_libs {
-        files.push(asys.new_file(&(basedir.to_string() + m)));
+        let name = m.clone();
+        files.push(Box::new(
+            asys.new_file(&(basedir.to_string() + m))
+                .then(move |r| Ok::<_, FileIoError>((name, r.ok()))),
+        ));
     }
     files
 }
 
 impl Prefab {
+    /// Load a wavefront OBJ file and its MTL material libraries, the materials are built
+    /// by `builder`. The missing libraries and materials and the faces skipped are
+    /// reported in `warnings`
     pub fn load_future<A>(
         asys: A,
         objfile: FileFuture,
@@ -411,7 +608,9 @@ impl Prefab {
             let asys = asys.clone();
             objfile.and_then(move |mut f| {
                 let bytes = f.read_binary()?;
-                let mut r = BufReader::new(bytes.as_slice());
+                let src = String::from_utf8_lossy(&bytes);
+                let src = resolve_relative_indices(&src);
+                let mut r = BufReader::new(src.as_bytes());
 
                 let mut model = obj::Obj::<SimplePolygon>::load_buf(&mut r)?;
                 let parent = parent_path(&f.name());
@@ -426,8 +625,18 @@ impl Prefab {
         let allmat = allmat.and_then(|r| r);
 
         let final_future = allmat.and_then(move |(files, parent, mut model)| {
+            let mut warnings = Vec::new();
+
             let mut materials = HashMap::new();
-            for mut f in files {
+            for (name, file) in files {
+                let mut f = match file {
+                    Some(f) => f,
+                    None => {
+                        warnings.push(format!("failed to load the material library {}", name));
+                        continue;
+                    }
+                };
+
                 let bytes = f.read_binary()?;
                 let mtl = obj::Mtl::load(&mut BufReader::new(bytes.as_slice()));
                 for m in mtl.materials {
@@ -438,14 +647,15 @@ impl Prefab {
             for object in &mut model.objects {
                 for group in &mut object.groups {
                     if let Some(ref mut mat) = group.material {
-                        if let Some(newmat) = materials.get(&mat.name) {
-                            *mat = newmat.clone()
+                        match materials.get(&mat.name) {
+                            Some(newmat) => *mat = newmat.clone(),
+                            None => warnings.push(format!("material {} not found", mat.name)),
                         }
                     }
                 }
             }
 
-            Ok(PrefabLoader::load_model(asys, parent, model, builder))
+            Ok(PrefabLoader::load_model(asys, parent, model, builder, warnings))
         });
 
         // futurize
@@ -1,18 +1,24 @@
 mod asset_database;
+mod bundle;
 mod default_font_bitmap;
 mod quad;
 mod fs;
+mod load_group;
 mod primitives;
 mod resource;
 mod skybox;
+mod source;
 
 pub mod loader;
 pub use self::primitives::{CubeMesh, PlaneMesh};
 pub use self::quad::QuadMesh;
 pub use self::skybox::SkyboxMesh;
-pub use self::asset_database::{Asset, AssetDatabase, AssetError, AssetResult, AssetSystem,
-                               LoadableAsset};
-pub use self::loader::{ObjMaterial, Prefab, DDS};
+pub use self::asset_database::{Asset, AssetCount, AssetDatabase, AssetError, AssetResult,
+                               AssetStats, AssetSystem, LoadableAsset};
+pub use self::bundle::{Bundle, BundleCompression, BundleWriter};
+pub use self::load_group::{LoadHandle, LoadItem, LoadState, LoadStatus};
+pub use self::loader::{DDSFormat, ObjMaterial, Prefab, PrefabNode, PrefabSkin, DDS};
 
 pub use self::resource::Resource;
+pub use self::source::{AssetSource, BundleSource, DirectorySource, EmbeddedSource, HttpSource};
 pub use self::fs::*;
@@ -0,0 +1,26 @@
+#ifndef GL_ES
+#define attribute in
+#define varying out
+#endif
+
+// The quads are billboarded in view space, the normal is the offset of the corner
+attribute vec3 aVertexPosition;
+attribute vec3 aVertexNormal;
+attribute vec2 aTextureCoord;
+attribute vec4 aVertexColor;
+
+uniform mat4 uMVMatrix;
+uniform mat4 uPMatrix;
+
+varying vec2 vTexCoords;
+varying vec4 vColor;
+
+void main(void) {
+    vTexCoords = aTextureCoord;
+    vColor = aVertexColor;
+
+    vec4 viewPos = uMVMatrix * vec4(aVertexPosition, 1.0);
+    viewPos.xy += aVertexNormal.xy;
+
+    gl_Position = uPMatrix * viewPos;
+}
@@ -0,0 +1,371 @@
+#ifndef GL_ES
+#define varying in
+#define gl_FragColor FragColor
+out vec4 FragColor;
+#define texture2D texture
+#define textureCube texture
+#endif
+
+#define UNI_POINT_LIGHTS 4
+#define UNI_SPOT_LIGHTS 4
+
+#define PI 3.14159265
+// Mipmap bias of the environment map for the roughest surfaces
+#define ENV_MAX_BIAS 7.0
+
+struct DirectionalLight {
+    vec3 direction;
+
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+
+    // Cookie tiled over the world across the light, see `DirectionalLight::cookie`
+    bool cookie;
+    vec3 cookieUp;
+    float cookieSize;
+};
+
+struct PointLight {
+    vec3 position;
+
+    float constant;
+    float linear;
+    float quadratic;
+    float range;
+
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+
+    float rate;
+};
+
+struct SpotLight {
+    vec3 position;
+    vec3 direction;
+
+    float constant;
+    float linear;
+    float quadratic;
+    float range;
+
+    // Cosines of the inner and the outer cone
+    float cutOff;
+    float outerCutOff;
+
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+
+    // Cookie projected over the cone, see `SpotLight::cookie`
+    bool cookie;
+    vec3 cookieUp;
+    // Tangent of the half angle of the cone
+    float cookieScale;
+
+    float rate;
+};
+
+// Metallic-roughness material, the maps are multiplied by the factors.
+// The metallic-roughness map has the roughness in green and the metallic in blue
+struct Material {
+    sampler2D albedo;
+    sampler2D normal;
+    sampler2D metallicRoughness;
+    sampler2D occlusion;
+    sampler2D emissive;
+
+    vec4 baseColor;
+    float metallic;
+    float roughness;
+    float occlusionStrength;
+    vec3 emissiveFactor;
+    float alphaCutoff;
+};
+
+uniform vec3 uViewPos;
+uniform Material uMaterial;
+
+// Image based lighting
+uniform samplerCube uEnvironment;
+uniform float uEnvironmentIntensity;
+
+// The lighting is computed in linear space, see `Camera::gamma`
+uniform float uGamma;
+uniform float uCameraExposure;
+
+// Dithered cross-fade of the levels of a `LodGroup`, the incoming level is positive
+// and the outgoing one negative, 0 when not fading
+uniform float uLodFade;
+
+varying vec3 vFragPos;
+varying vec3 vNormal;
+varying vec3 vTangent;
+varying vec2 vTexCoords;
+varying vec2 vLightmapCoords;
+
+// Lights
+uniform DirectionalLight uDirectionalLight;
+uniform PointLight uPointLights[UNI_POINT_LIGHTS];
+uniform SpotLight uSpotLights[UNI_SPOT_LIGHTS];
+
+// Cookies of the main light and of a spot light, white without cookies
+uniform sampler2D uDirectionalCookie;
+uniform sampler2D uSpotCookie;
+
+// Ambient of the light probes around the object as the irradiance coefficients of
+// the spherical harmonics, see `LightProbeGroup`. Without probes the ambient is the
+// flat one of the main light
+uniform bool uLightProbeEnabled;
+uniform vec3 uLightProbeSH[9];
+
+// Baked lighting of the static lights, which replaces the ambient and the main light,
+// see `Mesh::set_lightmap`
+uniform bool uLightmapEnabled;
+uniform sampler2D uLightmap;
+
+// Fog of the scene, see `EnvironmentSettings`. The mode is 0 without fog, 1 linear,
+// 2 exponential and 3 exponential squared
+struct Fog {
+    int mode;
+    vec3 color;
+    float density;
+    float start;
+    float end;
+    float heightBase;
+    float heightFalloff;
+};
+
+uniform Fog uFog;
+
+vec3 toLinear(vec3 c) {
+    return pow(c, vec3(uGamma));
+}
+
+// The color textures are decoded by the sampling when they are sRGB ones,
+// see `Texture::set_srgb`
+vec3 texelToLinear(vec3 c) {
+#ifdef UNI_SRGB_TEXTURES
+    return c;
+#else
+    return toLinear(c);
+#endif
+}
+
+float DistributionGGX(float NdotH, float roughness) {
+    float a = roughness * roughness;
+    float a2 = a * a;
+    float d = NdotH * NdotH * (a2 - 1.0) + 1.0;
+
+    return a2 / max(PI * d * d, 0.0001);
+}
+
+float GeometrySchlickGGX(float NdotV, float roughness) {
+    float r = roughness + 1.0;
+    float k = (r * r) / 8.0;
+
+    return NdotV / (NdotV * (1.0 - k) + k);
+}
+
+vec3 FresnelSchlick(float cosTheta, vec3 F0) {
+    return F0 + (1.0 - F0) * pow(1.0 - cosTheta, 5.0);
+}
+
+vec3 FresnelSchlickRoughness(float cosTheta, vec3 F0, float roughness) {
+    return F0 + (max(vec3(1.0 - roughness), F0) - F0) * pow(1.0 - cosTheta, 5.0);
+}
+
+// Analytic approximation of the split sum BRDF (Karis 2014), instead of a lookup texture
+vec2 EnvBRDFApprox(float NdotV, float roughness) {
+    vec4 c0 = vec4(-1.0, -0.0275, -0.572, 0.022);
+    vec4 c1 = vec4(1.0, 0.0425, 1.04, -0.04);
+    vec4 r = roughness * c0 + c1;
+    float a004 = min(r.x * r.x, exp2(-9.28 * NdotV)) * r.x + r.y;
+
+    return vec2(-1.04, 1.04) * a004 + r.zw;
+}
+
+float CalcAttenuation(float constant, float linear, float quadratic, float range, float distance) {
+    float d = (constant + linear * distance + quadratic * (distance * distance));
+    // fade out smoothly towards the range
+    float window = clamp(1.0 - pow(distance / max(range, 0.001), 4.0), 0.0, 1.0);
+    return window * window / max(d, 0.001);
+}
+
+// Outgoing radiance of a light from the direction L with the incoming radiance
+vec3 CalcRadiance(vec3 L, vec3 radiance, vec3 N, vec3 V, vec3 albedo, float metallic, float roughness, vec3 F0) {
+    vec3 H = normalize(V + L);
+    float NdotL = max(dot(N, L), 0.0);
+    float NdotV = max(dot(N, V), 0.0001);
+
+    float D = DistributionGGX(max(dot(N, H), 0.0), roughness);
+    float G = GeometrySchlickGGX(NdotV, roughness) * GeometrySchlickGGX(NdotL, roughness);
+    vec3 F = FresnelSchlick(max(dot(H, V), 0.0), F0);
+
+    vec3 specular = D * G * F / max(4.0 * NdotV * NdotL, 0.0001);
+    vec3 kD = (vec3(1.0) - F) * (1.0 - metallic);
+
+    return (kD * albedo / PI + specular) * radiance * NdotL;
+}
+
+vec3 CalcNormal() {
+    vec3 N = normalize(vNormal);
+
+    if (length(vTangent) < 0.0001) {
+        return N;
+    }
+
+    vec3 T = normalize(vTangent - dot(vTangent, N) * N);
+    vec3 B = cross(N, T);
+    vec3 n = texture2D(uMaterial.normal, vTexCoords).rgb * 2.0 - 1.0;
+
+    return normalize(mat3(T, B, N) * n);
+}
+
+vec3 AmbientColor(vec3 n) {
+    if (!uLightProbeEnabled) {
+        return toLinear(uDirectionalLight.ambient);
+    }
+
+    vec3 c = uLightProbeSH[0] * 0.282095;
+    c += (uLightProbeSH[1] * n.y + uLightProbeSH[2] * n.z + uLightProbeSH[3] * n.x) * 0.488603;
+    c += (uLightProbeSH[4] * n.x * n.y + uLightProbeSH[5] * n.y * n.z + uLightProbeSH[7] * n.x * n.z) * 1.092548;
+    c += uLightProbeSH[6] * 0.315392 * (3.0 * n.z * n.z - 1.0);
+    c += uLightProbeSH[8] * 0.546274 * (n.x * n.x - n.y * n.y);
+    return max(c, vec3(0.0));
+}
+
+vec3 LightmapColor() {
+    return texelToLinear(texture2D(uLightmap, vLightmapCoords).rgb);
+}
+
+vec3 DirectionalCookie(DirectionalLight light, vec3 fragPos) {
+    if (!light.cookie) {
+        return vec3(1.0);
+    }
+
+    vec3 right = normalize(cross(light.direction, light.cookieUp));
+    vec2 uv = vec2(dot(fragPos, right), dot(fragPos, light.cookieUp)) / light.cookieSize;
+    return texture2D(uDirectionalCookie, uv).rgb;
+}
+
+vec3 SpotCookie(SpotLight light, vec3 fragPos) {
+    if (!light.cookie) {
+        return vec3(1.0);
+    }
+
+    vec3 d = fragPos - light.position;
+    float z = dot(d, light.direction);
+    if (z <= 0.0) {
+        return vec3(0.0);
+    }
+
+    vec3 right = cross(light.direction, light.cookieUp);
+    vec2 uv = vec2(dot(d, right), dot(d, light.cookieUp)) / (z * light.cookieScale) * 0.5 + 0.5;
+    return texture2D(uSpotCookie, uv).rgb;
+}
+
+vec3 ApplyFog(vec3 color, vec3 fragPos) {
+    if (uFog.mode == 0) {
+        return color;
+    }
+
+    float distance = length(uViewPos - fragPos);
+    float visibility;
+    if (uFog.mode == 1) {
+        visibility = clamp((uFog.end - distance) / max(uFog.end - uFog.start, 0.0001), 0.0, 1.0);
+    } else if (uFog.mode == 2) {
+        visibility = exp(-uFog.density * distance);
+    } else {
+        float d = uFog.density * distance;
+        visibility = exp(-d * d);
+    }
+
+    // The fog thins out above its base height
+    if (uFog.heightFalloff > 0.0) {
+        float thickness = exp(-uFog.heightFalloff * max(fragPos.y - uFog.heightBase, 0.0));
+        visibility = 1.0 - (1.0 - visibility) * thickness;
+    }
+
+    return mix(toLinear(uFog.color), color, visibility);
+}
+
+void LodFade() {
+    float dither = fract(sin(dot(floor(gl_FragCoord.xy), vec2(12.9898, 78.233))) * 43758.5453);
+    if ((uLodFade > 0.0 && dither > uLodFade) || (uLodFade < 0.0 && dither <= -uLodFade)) {
+        discard;
+    }
+}
+
+void main(void) {
+    LodFade();
+
+    vec4 albedoTexel = texture2D(uMaterial.albedo, vTexCoords);
+    vec4 base = uMaterial.baseColor * albedoTexel;
+    if (base.a < uMaterial.alphaCutoff) {
+        discard;
+    }
+    vec3 albedo = toLinear(uMaterial.baseColor.rgb) * texelToLinear(albedoTexel.rgb);
+
+    vec4 mr = texture2D(uMaterial.metallicRoughness, vTexCoords);
+    float metallic = clamp(uMaterial.metallic * mr.b, 0.0, 1.0);
+    float roughness = clamp(uMaterial.roughness * mr.g, 0.04, 1.0);
+
+    float ao = 1.0 + uMaterial.occlusionStrength * (texture2D(uMaterial.occlusion, vTexCoords).r - 1.0);
+    vec3 emissive = uMaterial.emissiveFactor * texelToLinear(texture2D(uMaterial.emissive, vTexCoords).rgb);
+
+    vec3 N = CalcNormal();
+    vec3 V = normalize(uViewPos - vFragPos);
+    vec3 F0 = mix(vec3(0.04), albedo, metallic);
+
+    // Directional Light
+    vec3 result = vec3(0.0);
+    if (!uLightmapEnabled) {
+        vec3 radiance = toLinear(uDirectionalLight.diffuse) * DirectionalCookie(uDirectionalLight, vFragPos);
+        result = CalcRadiance(normalize(-uDirectionalLight.direction), radiance, N, V, albedo, metallic, roughness, F0);
+    }
+
+    // Point Lights
+    for(int i = 0; i < UNI_POINT_LIGHTS; i++) {
+        PointLight light = uPointLights[i];
+        float distance = length(light.position - vFragPos);
+        float attenuation = CalcAttenuation(light.constant, light.linear, light.quadratic, light.range, distance);
+        vec3 L = normalize(light.position - vFragPos);
+
+        result += CalcRadiance(L, toLinear(light.diffuse) * attenuation * light.rate, N, V, albedo, metallic, roughness, F0);
+    }
+
+    // Spot Lights
+    for(int i = 0; i < UNI_SPOT_LIGHTS; i++) {
+        SpotLight light = uSpotLights[i];
+        float distance = length(light.position - vFragPos);
+        float attenuation = CalcAttenuation(light.constant, light.linear, light.quadratic, light.range, distance);
+        vec3 L = normalize(light.position - vFragPos);
+
+        float theta = dot(L, normalize(-light.direction));
+        float epsilon = max(light.cutOff - light.outerCutOff, 0.0001);
+        float intensity = clamp((theta - light.outerCutOff) / epsilon, 0.0, 1.0);
+
+        vec3 radiance = toLinear(light.diffuse) * attenuation * intensity * light.rate * SpotCookie(light, vFragPos);
+        result += CalcRadiance(L, radiance, N, V, albedo, metallic, roughness, F0);
+    }
+
+    // Image based lighting, the blurred mipmaps stand for the prefiltered environment
+    float NdotV = max(dot(N, V), 0.0);
+    vec3 F = FresnelSchlickRoughness(NdotV, F0, roughness);
+    vec3 kD = (vec3(1.0) - F) * (1.0 - metallic);
+
+    vec3 irradiance = texelToLinear(textureCube(uEnvironment, N, ENV_MAX_BIAS).rgb);
+    vec3 prefiltered = texelToLinear(textureCube(uEnvironment, reflect(-V, N), roughness * ENV_MAX_BIAS).rgb);
+    vec2 brdf = EnvBRDFApprox(NdotV, roughness);
+
+    vec3 ambient = (kD * irradiance * albedo + prefiltered * (F * brdf.x + brdf.y)) * uEnvironmentIntensity;
+    ambient += (uLightmapEnabled ? LightmapColor() : AmbientColor(N)) * albedo * (1.0 - metallic);
+
+    result = result + ambient * ao + emissive;
+
+    result = ApplyFog(result * uCameraExposure, vFragPos);
+
+    gl_FragColor = vec4(pow(result, vec3(1.0 / uGamma)), base.a);
+}
@@ -0,0 +1,57 @@
+#ifndef GL_ES
+#define attribute in
+#define varying out
+#endif
+
+attribute vec3 aVertexPosition;
+attribute vec3 aVertexNormal;
+attribute vec3 aVertexTangent;
+attribute vec2 aTextureCoord;
+attribute vec2 aTextureCoord2;
+
+#ifdef UNI_INSTANCED
+// Transform of the instance in the object, see `MeshInstances`
+attribute mat4 aInstanceMatrix;
+
+// The inverse transpose of m up to a positive scale, for the normals
+mat3 cofactor(mat3 m) {
+    mat3 c = mat3(cross(m[1], m[2]), cross(m[2], m[0]), cross(m[0], m[1]));
+    // The determinant, negative if m mirrors
+    return c * sign(dot(m[0], c[0]));
+}
+#endif
+
+uniform mat4 uMVMatrix;
+uniform mat4 uPMatrix;
+uniform mat4 uNMatrix;
+uniform mat4 uMMatrix;
+
+// Scale in xy and offset in zw of the second uvs into the lightmap
+uniform vec4 uLightmapScaleOffset;
+
+varying vec3 vFragPos;
+varying vec3 vNormal;
+varying vec3 vTangent;
+varying vec2 vTexCoords;
+varying vec2 vLightmapCoords;
+
+void main(void) {
+#ifdef UNI_INSTANCED
+    mat4 model = uMMatrix * aInstanceMatrix;
+    mat4 modelView = uMVMatrix * aInstanceMatrix;
+    mat3 normalMatrix = mat3(uNMatrix) * cofactor(mat3(aInstanceMatrix));
+#else
+    mat4 model = uMMatrix;
+    mat4 modelView = uMVMatrix;
+    mat3 normalMatrix = mat3(uNMatrix);
+#endif
+
+    vFragPos = vec3(model * vec4(aVertexPosition, 1.0));
+    vNormal = normalMatrix * aVertexNormal;
+    // Zero if the mesh has no tangents, the normal map is skipped then
+    vTangent = mat3(model) * aVertexTangent;
+    vTexCoords = aTextureCoord;
+    vLightmapCoords = aTextureCoord2 * uLightmapScaleOffset.xy + uLightmapScaleOffset.zw;
+
+    gl_Position = uPMatrix * modelView * vec4(aVertexPosition, 1.0);
+}
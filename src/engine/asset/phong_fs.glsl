@@ -6,6 +6,7 @@ out vec4 FragColor;
 #endif
 
 #define UNI_POINT_LIGHTS 4
+#define UNI_SPOT_LIGHTS 4
 
 struct DirectionalLight {
     vec3 direction;
@@ -13,6 +14,11 @@ struct DirectionalLight {
     vec3 ambient;
     vec3 diffuse;
     vec3 specular;
+
+    // Cookie tiled over the world across the light, see `DirectionalLight::cookie`
+    bool cookie;
+    vec3 cookieUp;
+    float cookieSize;
 };
 
 struct PointLight {
@@ -21,6 +27,7 @@ struct PointLight {
     float constant;
     float linear;
     float quadratic;
+    float range;
 	
     vec3 ambient;
     vec3 diffuse;
@@ -29,6 +36,32 @@ struct PointLight {
     float rate;
 };
 
+struct SpotLight {
+    vec3 position;
+    vec3 direction;
+
+    float constant;
+    float linear;
+    float quadratic;
+    float range;
+
+    // Cosines of the inner and the outer cone
+    float cutOff;
+    float outerCutOff;
+
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+
+    // Cookie projected over the cone, see `SpotLight::cookie`
+    bool cookie;
+    vec3 cookieUp;
+    // Tangent of the half angle of the cone
+    float cookieScale;
+
+    float rate;
+};
+
 struct Material {
     sampler2D diffuse;
     float shininess;
@@ -37,18 +70,154 @@ struct Material {
 uniform vec3 uViewPos;
 uniform Material uMaterial;
 
+// The lighting is computed in linear space, see `Camera::gamma`
+uniform float uGamma;
+uniform float uCameraExposure;
+
+// Dithered cross-fade of the levels of a `LodGroup`, the incoming level is positive
+// and the outgoing one negative, 0 when not fading
+uniform float uLodFade;
+
 varying vec3 vFragPos;
 varying vec2 vTexCoords;       
 varying vec3 vNormal;                       
+varying vec2 vLightmapCoords;
 
 // Lights
 uniform DirectionalLight uDirectionalLight;
 uniform PointLight uPointLights[UNI_POINT_LIGHTS];
+uniform SpotLight uSpotLights[UNI_SPOT_LIGHTS];
+
+// Cookies of the main light and of a spot light, white without cookies
+uniform sampler2D uDirectionalCookie;
+uniform sampler2D uSpotCookie;
+
+// Ambient of the light probes around the object as the irradiance coefficients of
+// the spherical harmonics, see `LightProbeGroup`. Without probes the ambient is the
+// flat one of the main light
+uniform bool uLightProbeEnabled;
+uniform vec3 uLightProbeSH[9];
+
+// Baked lighting of the static lights, which replaces the ambient and the diffuse
+// of the main light, see `Mesh::set_lightmap`
+uniform bool uLightmapEnabled;
+uniform sampler2D uLightmap;
+
+// Fog of the scene, see `EnvironmentSettings`. The mode is 0 without fog, 1 linear,
+// 2 exponential and 3 exponential squared
+struct Fog {
+    int mode;
+    vec3 color;
+    float density;
+    float start;
+    float end;
+    float heightBase;
+    float heightFalloff;
+};
+
+uniform Fog uFog;
 
 vec3 CalcDirectionalLight(DirectionalLight light, vec3 normal, vec3 viewDir);
 vec3 CalcPointLight(PointLight light, vec3 normal, vec3 fragPos, vec3 viewDir);
+vec3 CalcSpotLight(SpotLight light, vec3 normal, vec3 fragPos, vec3 viewDir);
+float CalcAttenuation(float constant, float linear, float quadratic, float range, float distance);
+
+vec3 toLinear(vec3 c) {
+    return pow(c, vec3(uGamma));
+}
+
+// The color textures are decoded by the sampling when they are sRGB ones,
+// see `Texture::set_srgb`
+vec3 texelToLinear(vec3 c) {
+#ifdef UNI_SRGB_TEXTURES
+    return c;
+#else
+    return toLinear(c);
+#endif
+}
+
+vec3 DiffuseColor() {
+    return texelToLinear(texture2D(uMaterial.diffuse, vTexCoords).rgb);
+}
+
+vec3 AmbientColor(vec3 n) {
+    if (!uLightProbeEnabled) {
+        return toLinear(uDirectionalLight.ambient);
+    }
+
+    vec3 c = uLightProbeSH[0] * 0.282095;
+    c += (uLightProbeSH[1] * n.y + uLightProbeSH[2] * n.z + uLightProbeSH[3] * n.x) * 0.488603;
+    c += (uLightProbeSH[4] * n.x * n.y + uLightProbeSH[5] * n.y * n.z + uLightProbeSH[7] * n.x * n.z) * 1.092548;
+    c += uLightProbeSH[6] * 0.315392 * (3.0 * n.z * n.z - 1.0);
+    c += uLightProbeSH[8] * 0.546274 * (n.x * n.x - n.y * n.y);
+    return max(c, vec3(0.0));
+}
+
+vec3 LightmapColor() {
+    return texelToLinear(texture2D(uLightmap, vLightmapCoords).rgb);
+}
+
+vec3 DirectionalCookie(DirectionalLight light, vec3 fragPos) {
+    if (!light.cookie) {
+        return vec3(1.0);
+    }
+
+    vec3 right = normalize(cross(light.direction, light.cookieUp));
+    vec2 uv = vec2(dot(fragPos, right), dot(fragPos, light.cookieUp)) / light.cookieSize;
+    return texture2D(uDirectionalCookie, uv).rgb;
+}
+
+vec3 SpotCookie(SpotLight light, vec3 fragPos) {
+    if (!light.cookie) {
+        return vec3(1.0);
+    }
+
+    vec3 d = fragPos - light.position;
+    float z = dot(d, light.direction);
+    if (z <= 0.0) {
+        return vec3(0.0);
+    }
+
+    vec3 right = cross(light.direction, light.cookieUp);
+    vec2 uv = vec2(dot(d, right), dot(d, light.cookieUp)) / (z * light.cookieScale) * 0.5 + 0.5;
+    return texture2D(uSpotCookie, uv).rgb;
+}
+
+vec3 ApplyFog(vec3 color, vec3 fragPos) {
+    if (uFog.mode == 0) {
+        return color;
+    }
+
+    float distance = length(uViewPos - fragPos);
+    float visibility;
+    if (uFog.mode == 1) {
+        visibility = clamp((uFog.end - distance) / max(uFog.end - uFog.start, 0.0001), 0.0, 1.0);
+    } else if (uFog.mode == 2) {
+        visibility = exp(-uFog.density * distance);
+    } else {
+        float d = uFog.density * distance;
+        visibility = exp(-d * d);
+    }
+
+    // The fog thins out above its base height
+    if (uFog.heightFalloff > 0.0) {
+        float thickness = exp(-uFog.heightFalloff * max(fragPos.y - uFog.heightBase, 0.0));
+        visibility = 1.0 - (1.0 - visibility) * thickness;
+    }
+
+    return mix(toLinear(uFog.color), color, visibility);
+}
+
+void LodFade() {
+    float dither = fract(sin(dot(floor(gl_FragCoord.xy), vec2(12.9898, 78.233))) * 43758.5453);
+    if ((uLodFade > 0.0 && dither > uLodFade) || (uLodFade < 0.0 && dither <= -uLodFade)) {
+        discard;
+    }
+}
 
 void main(void) {
+    LodFade();
+
     vec3 norm = normalize(vNormal);
     vec3 viewDir = normalize(uViewPos - vFragPos);
 
@@ -59,22 +228,37 @@ void main(void) {
     for(int i = 0; i < UNI_POINT_LIGHTS; i++)
         result += CalcPointLight(uPointLights[i], norm, vFragPos, viewDir);
 
-    gl_FragColor = vec4(result, 1.0);           
+    // Spot Lights
+    for(int i = 0; i < UNI_SPOT_LIGHTS; i++)
+        result += CalcSpotLight(uSpotLights[i], norm, vFragPos, viewDir);
+
+    result = ApplyFog(result * uCameraExposure, vFragPos);
+
+    gl_FragColor = vec4(pow(result, vec3(1.0 / uGamma)), 1.0);
 }
 
 vec3 CalcDirectionalLight(DirectionalLight light, vec3 normal, vec3 viewDir)
 {
     // diffuse
-    vec3 ambient = light.ambient * vec3(texture2D(uMaterial.diffuse, vTexCoords));
+    vec3 ambient = AmbientColor(normal) * DiffuseColor();
 
     vec3 lightDir = normalize(-light.direction);  
     float diff = max(dot(normal, lightDir), 0.0);
-    vec3 diffuse = light.diffuse * diff * texture2D(uMaterial.diffuse, vTexCoords).rgb;  
+    vec3 diffuse = toLinear(light.diffuse) * diff * DiffuseColor();  
+
+    if (uLightmapEnabled) {
+        ambient = LightmapColor() * DiffuseColor();
+        diffuse = vec3(0.0);
+    }
 
     // specular    
     vec3 reflectDir = reflect(-lightDir, normal);  
     float spec = pow(max(dot(viewDir, reflectDir), 0.0), uMaterial.shininess);
-    vec3 specular = light.specular * spec; 
+    vec3 specular = toLinear(light.specular) * spec; 
+
+    vec3 cookie = DirectionalCookie(light, vFragPos);
+    diffuse *= cookie;
+    specular *= cookie;
 
     return ambient + diffuse + specular;
 }
@@ -91,13 +275,12 @@ vec3 CalcPointLight(PointLight light, vec3 normal, vec3 fragPos, vec3 viewDir)
     
     // attenuation
     float distance = length(light.position - fragPos);
-    float d = (light.constant + light.linear * distance + light.quadratic * (distance * distance));
-    float attenuation = 1.0 / max(d, 0.001);
+    float attenuation = CalcAttenuation(light.constant, light.linear, light.quadratic, light.range, distance);
     
     // combine results
-    vec3 ambient = light.ambient * vec3(texture2D(uMaterial.diffuse, vTexCoords));
-    vec3 diffuse = light.diffuse * diff * vec3(texture2D(uMaterial.diffuse, vTexCoords));
-    vec3 specular = light.specular * spec;
+    vec3 ambient = toLinear(light.ambient) * DiffuseColor();
+    vec3 diffuse = toLinear(light.diffuse) * diff * DiffuseColor();
+    vec3 specular = toLinear(light.specular) * spec;
     
     ambient *= attenuation;
     diffuse *= attenuation;
@@ -105,3 +288,46 @@ vec3 CalcPointLight(PointLight light, vec3 normal, vec3 fragPos, vec3 viewDir)
     
     return (ambient + diffuse + specular) * light.rate;        
 }
+
+vec3 CalcSpotLight(SpotLight light, vec3 normal, vec3 fragPos, vec3 viewDir)
+{
+    vec3 lightDir = normalize(light.position - fragPos);
+
+    // diffuse shading
+    float diff = max(dot(normal, lightDir), 0.0);
+    // specular shading
+    vec3 reflectDir = reflect(-lightDir, normal);
+    float spec = pow(max(dot(viewDir, reflectDir), 0.0), uMaterial.shininess);
+
+    // attenuation
+    float distance = length(light.position - fragPos);
+    float attenuation = CalcAttenuation(light.constant, light.linear, light.quadratic, light.range, distance);
+
+    // cone
+    float theta = dot(lightDir, normalize(-light.direction));
+    float epsilon = max(light.cutOff - light.outerCutOff, 0.0001);
+    float intensity = clamp((theta - light.outerCutOff) / epsilon, 0.0, 1.0);
+
+    // combine results
+    vec3 ambient = toLinear(light.ambient) * DiffuseColor();
+    vec3 diffuse = toLinear(light.diffuse) * diff * DiffuseColor();
+    vec3 specular = toLinear(light.specular) * spec;
+
+    ambient *= attenuation;
+    diffuse *= attenuation * intensity;
+    specular *= attenuation * intensity;
+
+    vec3 cookie = SpotCookie(light, fragPos);
+    diffuse *= cookie;
+    specular *= cookie;
+
+    return (ambient + diffuse + specular) * light.rate;
+}
+
+float CalcAttenuation(float constant, float linear, float quadratic, float range, float distance)
+{
+    float d = (constant + linear * distance + quadratic * (distance * distance));
+    // fade out smoothly towards the range
+    float window = clamp(1.0 - pow(distance / max(range, 0.001), 4.0), 0.0, 1.0);
+    return window * window / max(d, 0.001);
+}
@@ -0,0 +1,19 @@
+#ifndef GL_ES
+#define varying in
+#define gl_FragColor FragColor
+out vec4 FragColor;
+#define texture2D texture
+#endif
+
+varying vec2 vTexCoords;
+// The average luminance of the frame and the one adapted until the last frame
+uniform sampler2D uScreen;
+uniform sampler2D uAdapted;
+uniform float uRate;
+
+void main(void) {
+    float current = texture2D(uScreen, vec2(0.5)).r;
+    float adapted = texture2D(uAdapted, vec2(0.5)).r;
+
+    gl_FragColor = vec4(vec3(mix(adapted, current, uRate)), 1.0);
+}
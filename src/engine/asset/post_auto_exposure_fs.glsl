@@ -0,0 +1,34 @@
+#ifndef GL_ES
+#define varying in
+#define gl_FragColor FragColor
+out vec4 FragColor;
+#define texture2D texture
+#endif
+
+#define LOG_MIN -8.0
+#define LOG_MAX 4.0
+
+varying vec2 vTexCoords;
+uniform sampler2D uScreen;
+// The adapted luminance encoded by the luminance pass
+uniform sampler2D uLuminance;
+uniform float uGamma;
+uniform float uKey;
+uniform float uMinExposure;
+uniform float uMaxExposure;
+
+// Narkowicz's fit of the ACES filmic curve
+vec3 ACESFilm(vec3 x) {
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+void main(void) {
+    vec4 color = texture2D(uScreen, vTexCoords);
+    float encoded = texture2D(uLuminance, vec2(0.5)).r;
+    float average = exp2(encoded * (LOG_MAX - LOG_MIN) + LOG_MIN);
+    float exposure = clamp(uKey / average, uMinExposure, uMaxExposure);
+
+    vec3 linear = pow(color.rgb, vec3(uGamma)) * exposure;
+
+    gl_FragColor = vec4(pow(ACESFilm(linear), vec3(1.0 / uGamma)), color.a);
+}
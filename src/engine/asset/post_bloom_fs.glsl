@@ -0,0 +1,18 @@
+#ifndef GL_ES
+#define varying in
+#define gl_FragColor FragColor
+out vec4 FragColor;
+#define texture2D texture
+#endif
+
+varying vec2 vTexCoords;
+uniform sampler2D uScreen;
+uniform sampler2D uBloom;
+uniform float uIntensity;
+
+void main(void) {
+    vec4 color = texture2D(uScreen, vTexCoords);
+    vec3 bloom = texture2D(uBloom, vTexCoords).rgb;
+
+    gl_FragColor = vec4(color.rgb + bloom * uIntensity, color.a);
+}
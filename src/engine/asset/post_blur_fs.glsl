@@ -0,0 +1,29 @@
+#ifndef GL_ES
+#define varying in
+#define gl_FragColor FragColor
+out vec4 FragColor;
+#define texture2D texture
+#endif
+
+varying vec2 vTexCoords;
+uniform sampler2D uScreen;
+uniform vec2 uScreenSize;
+// (1, 0) for the horizontal pass, (0, 1) for the vertical one
+uniform vec2 uDirection;
+
+void main(void) {
+    vec2 texel = uDirection / uScreenSize;
+
+    // 9 taps gaussian kernel
+    vec3 color = texture2D(uScreen, vTexCoords).rgb * 0.227027;
+    color += texture2D(uScreen, vTexCoords + texel * 1.0).rgb * 0.1945946;
+    color += texture2D(uScreen, vTexCoords - texel * 1.0).rgb * 0.1945946;
+    color += texture2D(uScreen, vTexCoords + texel * 2.0).rgb * 0.1216216;
+    color += texture2D(uScreen, vTexCoords - texel * 2.0).rgb * 0.1216216;
+    color += texture2D(uScreen, vTexCoords + texel * 3.0).rgb * 0.054054;
+    color += texture2D(uScreen, vTexCoords - texel * 3.0).rgb * 0.054054;
+    color += texture2D(uScreen, vTexCoords + texel * 4.0).rgb * 0.016216;
+    color += texture2D(uScreen, vTexCoords - texel * 4.0).rgb * 0.016216;
+
+    gl_FragColor = vec4(color, 1.0);
+}
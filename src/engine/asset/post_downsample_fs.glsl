@@ -0,0 +1,23 @@
+#ifndef GL_ES
+#define varying in
+#define gl_FragColor FragColor
+out vec4 FragColor;
+#define texture2D texture
+#endif
+
+varying vec2 vTexCoords;
+uniform sampler2D uScreen;
+// Size of the source image in pixels
+uniform vec2 uScreenSize;
+
+void main(void) {
+    // The 2x2 source pixels of the target pixel
+    vec2 texel = 0.5 / uScreenSize;
+
+    vec3 color = texture2D(uScreen, vTexCoords + vec2(-texel.x, -texel.y)).rgb;
+    color += texture2D(uScreen, vTexCoords + vec2(texel.x, -texel.y)).rgb;
+    color += texture2D(uScreen, vTexCoords + vec2(-texel.x, texel.y)).rgb;
+    color += texture2D(uScreen, vTexCoords + vec2(texel.x, texel.y)).rgb;
+
+    gl_FragColor = vec4(color * 0.25, 1.0);
+}
@@ -0,0 +1,50 @@
+#ifndef GL_ES
+#define varying in
+#define gl_FragColor FragColor
+out vec4 FragColor;
+#define texture2D texture
+#endif
+
+varying vec2 vTexCoords;
+uniform sampler2D uScreen;
+uniform vec2 uScreenSize;
+
+#define FXAA_REDUCE_MIN (1.0 / 128.0)
+#define FXAA_REDUCE_MUL (1.0 / 8.0)
+#define FXAA_SPAN_MAX 8.0
+
+void main(void) {
+    vec2 texel = 1.0 / uScreenSize;
+    vec3 luma = vec3(0.299, 0.587, 0.114);
+
+    vec4 color = texture2D(uScreen, vTexCoords);
+    float lumaNW = dot(texture2D(uScreen, vTexCoords + vec2(-1.0, -1.0) * texel).rgb, luma);
+    float lumaNE = dot(texture2D(uScreen, vTexCoords + vec2(1.0, -1.0) * texel).rgb, luma);
+    float lumaSW = dot(texture2D(uScreen, vTexCoords + vec2(-1.0, 1.0) * texel).rgb, luma);
+    float lumaSE = dot(texture2D(uScreen, vTexCoords + vec2(1.0, 1.0) * texel).rgb, luma);
+    float lumaM = dot(color.rgb, luma);
+
+    float lumaMin = min(lumaM, min(min(lumaNW, lumaNE), min(lumaSW, lumaSE)));
+    float lumaMax = max(lumaM, max(max(lumaNW, lumaNE), max(lumaSW, lumaSE)));
+
+    // The direction along the edge
+    vec2 dir = vec2(-((lumaNW + lumaNE) - (lumaSW + lumaSE)), (lumaNW + lumaSW) - (lumaNE + lumaSE));
+
+    float dirReduce = max((lumaNW + lumaNE + lumaSW + lumaSE) * (0.25 * FXAA_REDUCE_MUL), FXAA_REDUCE_MIN);
+    float rcpDirMin = 1.0 / (min(abs(dir.x), abs(dir.y)) + dirReduce);
+    dir = clamp(dir * rcpDirMin, vec2(-FXAA_SPAN_MAX), vec2(FXAA_SPAN_MAX)) * texel;
+
+    vec3 rgbA = 0.5 * (
+        texture2D(uScreen, vTexCoords + dir * (1.0 / 3.0 - 0.5)).rgb +
+        texture2D(uScreen, vTexCoords + dir * (2.0 / 3.0 - 0.5)).rgb);
+    vec3 rgbB = rgbA * 0.5 + 0.25 * (
+        texture2D(uScreen, vTexCoords + dir * -0.5).rgb +
+        texture2D(uScreen, vTexCoords + dir * 0.5).rgb);
+
+    float lumaB = dot(rgbB, luma);
+    if (lumaB < lumaMin || lumaB > lumaMax) {
+        gl_FragColor = vec4(rgbA, color.a);
+    } else {
+        gl_FragColor = vec4(rgbB, color.a);
+    }
+}
@@ -0,0 +1,23 @@
+#ifndef GL_ES
+#define varying in
+#define gl_FragColor FragColor
+out vec4 FragColor;
+#define texture2D texture
+#endif
+
+// The log2 luminance from LOG_MIN to LOG_MAX is stored in 0..1, so the average
+// of the downsampled chain is the geometric mean of the luminance
+#define LOG_MIN -8.0
+#define LOG_MAX 4.0
+
+varying vec2 vTexCoords;
+uniform sampler2D uScreen;
+uniform float uGamma;
+
+void main(void) {
+    vec3 color = pow(texture2D(uScreen, vTexCoords).rgb, vec3(uGamma));
+    float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    float encoded = clamp((log2(luminance + 0.0001) - LOG_MIN) / (LOG_MAX - LOG_MIN), 0.0, 1.0);
+
+    gl_FragColor = vec4(vec3(encoded), 1.0);
+}
@@ -0,0 +1,18 @@
+#ifndef GL_ES
+#define varying in
+#define gl_FragColor FragColor
+out vec4 FragColor;
+#define texture2D texture
+#endif
+
+varying vec2 vTexCoords;
+uniform sampler2D uScreen;
+uniform float uThreshold;
+
+void main(void) {
+    vec3 color = texture2D(uScreen, vTexCoords).rgb;
+    float brightness = max(color.r, max(color.g, color.b));
+    float contribution = max(brightness - uThreshold, 0.0) / max(brightness, 0.0001);
+
+    gl_FragColor = vec4(color * contribution, 1.0);
+}
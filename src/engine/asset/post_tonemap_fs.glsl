@@ -0,0 +1,23 @@
+#ifndef GL_ES
+#define varying in
+#define gl_FragColor FragColor
+out vec4 FragColor;
+#define texture2D texture
+#endif
+
+varying vec2 vTexCoords;
+uniform sampler2D uScreen;
+uniform float uExposure;
+uniform float uGamma;
+
+// Narkowicz's fit of the ACES filmic curve
+vec3 ACESFilm(vec3 x) {
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+void main(void) {
+    vec4 color = texture2D(uScreen, vTexCoords);
+    vec3 linear = pow(color.rgb, vec3(uGamma)) * uExposure;
+
+    gl_FragColor = vec4(pow(ACESFilm(linear), vec3(1.0 / uGamma)), color.a);
+}
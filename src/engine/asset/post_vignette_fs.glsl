@@ -0,0 +1,21 @@
+#ifndef GL_ES
+#define varying in
+#define gl_FragColor FragColor
+out vec4 FragColor;
+#define texture2D texture
+#endif
+
+varying vec2 vTexCoords;
+uniform sampler2D uScreen;
+uniform float uIntensity;
+uniform float uSmoothness;
+
+void main(void) {
+    vec4 color = texture2D(uScreen, vTexCoords);
+
+    // 0 at the center to 1 at the corners
+    float d = length(vTexCoords - vec2(0.5)) * 1.41421;
+    float vignette = smoothstep(1.0 - uSmoothness, 1.0, d);
+
+    gl_FragColor = vec4(color.rgb * (1.0 - vignette * uIntensity), color.a);
+}
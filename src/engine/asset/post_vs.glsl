@@ -0,0 +1,15 @@
+#ifndef GL_ES
+#define attribute in
+#define varying out
+#endif
+
+attribute vec3 aVertexPosition;
+attribute vec2 aTextureCoord;
+
+varying vec2 vTexCoords;
+
+void main(void) {
+    vTexCoords = aTextureCoord;
+
+    gl_Position = vec4(aVertexPosition.xy, 0.0, 1.0);
+}
@@ -119,10 +119,15 @@ impl CubeMesh {
         MeshData {
             vertices: vertices,
             uvs: Some(uvs),
+            uvs2: None,
             normals: Some(normals),
             indices: indices,
             tangents: None,
             bitangents: None,
+            colors: None,
+            joints: None,
+            weights: None,
+            morph_targets: Vec::new(),
         }
     }
 }
@@ -160,10 +165,15 @@ impl PlaneMesh {
         MeshData {
             vertices: vertices,
             uvs: Some(uvs),
+            uvs2: None,
             normals: Some(normals),
             indices: indices,
             tangents: None,
             bitangents: None,
+            colors: None,
+            joints: None,
+            weights: None,
+            morph_targets: Vec::new(),
         }
     }
 }
@@ -26,10 +26,15 @@ impl QuadMesh {
         MeshData {
             vertices: vertices,
             uvs: Some(uvs),
+            uvs2: None,
             normals: None,
             indices: indices,
             tangents: None,
             bitangents: None,
+            colors: None,
+            joints: None,
+            weights: None,
+            morph_targets: Vec::new(),
         }
     }
 }
@@ -104,8 +104,17 @@ impl<T: Debug + loader::Loadable> Resource<T> {
         return Ok(Ref::map(b0, |t| t.try_as_data().unwrap()));
     }
 
-    pub fn replace(&self, t: T) {
-        self.0.borrow_mut().replace(ResourceKind::Data(t));
+    /// Exchange the states of the resources, e.g. to swap in a reloaded one
+    pub fn swap(&self, other: &Resource<T>) {
+        mem::swap(&mut *self.0.borrow_mut(), &mut *other.0.borrow_mut());
+    }
+
+    /// Replace the resource by `t`, return the old data if it was loaded
+    pub fn replace(&self, t: T) -> Option<T> {
+        self.0
+            .borrow_mut()
+            .replace(ResourceKind::Data(t))
+            .try_into_data()
     }
 }
 
@@ -24,10 +24,15 @@ impl SkyboxMesh {
         MeshData {
             vertices: vertices,
             uvs: None,
+            uvs2: None,
             normals: None,
             indices: indices,
             tangents: None,
             bitangents: None,
+            colors: None,
+            joints: None,
+            weights: None,
+            morph_targets: Vec::new(),
         }
     }
 }
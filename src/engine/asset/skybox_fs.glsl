@@ -0,0 +1,22 @@
+#ifndef GL_ES
+#define varying in
+#define gl_FragColor FragColor
+#define textureCube texture
+out vec4 FragColor;
+#endif
+
+varying vec3 vTexCoords;
+uniform samplerCube uSkybox;
+uniform float uSkyboxIntensity;
+
+// The sRGB cubemap is sampled as linear, see `Texture::set_srgb`
+uniform float uGamma;
+
+void main()
+{
+    vec4 color = textureCube(uSkybox, vTexCoords);
+#ifdef UNI_SRGB_TEXTURES
+    color.rgb = pow(color.rgb, vec3(1.0 / uGamma));
+#endif
+    gl_FragColor = vec4(color.rgb * uSkyboxIntensity, 1.0);
+}
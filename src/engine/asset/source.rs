@@ -0,0 +1,199 @@
+use engine::asset::bundle::Bundle;
+use engine::asset::fs::{File, FileFuture, FileIoError, FileSystem, MemoryFile};
+use engine::asset::AssetError;
+
+use futures::{future, Async, Future};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use uni_app;
+
+/// A place the asset files are opened from, mounted in the asset system by
+/// `AssetSystem::mount`.
+///
+/// A source is asked for the files under its mount point by their paths from the mount
+/// point, the sources of higher priorities first. The next source is asked when a file
+/// is not found, the file system of the engine is asked last
+pub trait AssetSource {
+    /// Open the file `path`, None if the source knows it has no such file. The future
+    /// fails if the source finds out so only later, e.g. once a bundle is loaded
+    fn open(&self, path: &str) -> Option<FileFuture>;
+}
+
+fn memory_file(path: &str, data: Result<Vec<u8>, FileIoError>) -> FileFuture {
+    let file: Box<File> = Box::new(MemoryFile::new(path, data));
+    Box::new(future::ok(file))
+}
+
+/// The files of a directory of the file system of the engine, e.g. "static/" on native
+/// and the directory of the page on the web
+pub struct DirectorySource<FS: FileSystem> {
+    fs: FS,
+    dir: String,
+}
+
+impl<FS: FileSystem> DirectorySource<FS> {
+    /// `dir` is the path of the directory ending with a '/', "" for the root
+    pub fn new(dir: &str) -> DirectorySource<FS> {
+        DirectorySource {
+            fs: FS::default(),
+            dir: dir.to_string(),
+        }
+    }
+}
+
+impl<FS: FileSystem> AssetSource for DirectorySource<FS> {
+    fn open(&self, path: &str) -> Option<FileFuture> {
+        Some(self.fs.open(&format!("{}{}", self.dir, path)))
+    }
+}
+
+/// The files under a base url, e.g. of a CDN. They are downloaded on the web only,
+/// on native the source has no file
+pub struct HttpSource<FS: FileSystem> {
+    fs: FS,
+    base_url: String,
+}
+
+impl<FS: FileSystem> HttpSource<FS> {
+    /// `base_url` ends with a '/', e.g. "https://example.com/assets/"
+    pub fn new(base_url: &str) -> HttpSource<FS> {
+        HttpSource {
+            fs: FS::default(),
+            base_url: base_url.to_string(),
+        }
+    }
+}
+
+impl<FS: FileSystem> AssetSource for HttpSource<FS> {
+    fn open(&self, path: &str) -> Option<FileFuture> {
+        if cfg!(not(target_arch = "wasm32")) {
+            return None;
+        }
+
+        Some(self.fs.open(&format!("{}{}", self.base_url, path)))
+    }
+}
+
+/// Files embedded in the binary, e.g. by `include_bytes!`
+///
+/// ```ignore
+/// let mut source = EmbeddedSource::new();
+/// source.add("ui/frame.png", include_bytes!("../static/ui/frame.png"));
+/// ```
+pub struct EmbeddedSource {
+    files: HashMap<String, &'static [u8]>,
+}
+
+impl EmbeddedSource {
+    pub fn new() -> EmbeddedSource {
+        EmbeddedSource {
+            files: HashMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, path: &str, data: &'static [u8]) {
+        self.files.insert(path.to_string(), data);
+    }
+}
+
+impl AssetSource for EmbeddedSource {
+    fn open(&self, path: &str) -> Option<FileFuture> {
+        let data = self.files.get(path)?;
+        Some(memory_file(path, Ok(data.to_vec())))
+    }
+}
+
+enum BundleState {
+    Loading(String, FileFuture),
+    Ready(Rc<Bundle>),
+    /// The source has no file
+    Failed,
+}
+
+/// The bundle once its file is loaded, None if it failed
+fn poll_bundle(state: &RefCell<BundleState>) -> Async<Option<Rc<Bundle>>> {
+    let mut state = state.borrow_mut();
+
+    let bundle = match *state {
+        BundleState::Ready(ref bundle) => return Async::Ready(Some(bundle.clone())),
+        BundleState::Failed => return Async::Ready(None),
+        BundleState::Loading(ref name, ref mut file) => {
+            let bundle = match file.poll() {
+                Ok(Async::NotReady) => return Async::NotReady,
+                Ok(Async::Ready(mut file)) => file.read_binary()
+                    .map_err(AssetError::FileIoError)
+                    .and_then(|bytes| Bundle::parse(name, bytes)),
+                Err(e) => Err(AssetError::FileIoError(e)),
+            };
+
+            match bundle {
+                Ok(bundle) => Some(Rc::new(bundle)),
+                Err(e) => {
+                    uni_app::App::print(format!("Failed to mount {}, reason: {:?}\n", name, e));
+                    None
+                }
+            }
+        }
+    };
+
+    *state = match bundle {
+        Some(ref bundle) => BundleState::Ready(bundle.clone()),
+        None => BundleState::Failed,
+    };
+
+    Async::Ready(bundle)
+}
+
+/// The files of a bundle, see `Bundle`. The bundle file is loaded by the first file
+/// opened from the source
+pub struct BundleSource {
+    state: Rc<RefCell<BundleState>>,
+}
+
+impl BundleSource {
+    /// The bundle of the file `name` opened by `file`
+    pub fn new(name: &str, file: FileFuture) -> BundleSource {
+        BundleSource {
+            state: Rc::new(RefCell::new(BundleState::Loading(name.to_string(), file))),
+        }
+    }
+
+    /// A bundle which is already loaded, e.g. embedded in the binary
+    pub fn from_bundle(bundle: Bundle) -> BundleSource {
+        BundleSource {
+            state: Rc::new(RefCell::new(BundleState::Ready(Rc::new(bundle)))),
+        }
+    }
+}
+
+impl AssetSource for BundleSource {
+    fn open(&self, path: &str) -> Option<FileFuture> {
+        match poll_bundle(&self.state) {
+            Async::Ready(None) => None,
+            Async::Ready(Some(bundle)) => {
+                let data = bundle.read(path)?;
+                Some(memory_file(path, data))
+            }
+            Async::NotReady => {
+                let state = self.state.clone();
+                let path = path.to_string();
+
+                let file: FileFuture = Box::new(future::poll_fn(move || {
+                    let bundle = match poll_bundle(&state) {
+                        Async::NotReady => return Ok(Async::NotReady),
+                        Async::Ready(bundle) => bundle,
+                    };
+
+                    let data = bundle.and_then(|bundle| bundle.read(&path));
+                    match data {
+                        Some(data) => memory_file(&path, data).poll(),
+                        None => Err(FileIoError::NoSuchFile(path.clone())),
+                    }
+                }));
+
+                Some(file)
+            }
+        }
+    }
+}
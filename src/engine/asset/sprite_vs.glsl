@@ -0,0 +1,21 @@
+#ifndef GL_ES
+#define attribute in
+#define varying out
+#endif
+
+attribute vec3 aVertexPosition;
+attribute vec2 aTextureCoord;
+attribute vec4 aVertexColor;
+
+uniform mat4 uMVMatrix;
+uniform mat4 uPMatrix;
+
+varying vec2 vTexCoords;
+varying vec4 vColor;
+
+void main(void) {
+    vTexCoords = aTextureCoord;
+    vColor = aVertexColor;
+
+    gl_Position = uPMatrix * uMVMatrix * vec4(aVertexPosition, 1.0);
+}
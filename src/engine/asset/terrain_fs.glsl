@@ -0,0 +1,125 @@
+#ifndef GL_ES
+#define varying in
+#define gl_FragColor FragColor
+out vec4 FragColor;
+#define texture2D texture
+#endif
+
+struct DirectionalLight {
+    vec3 direction;
+
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+
+    // Cookie tiled over the world across the light, see `DirectionalLight::cookie`
+    bool cookie;
+    vec3 cookieUp;
+    float cookieSize;
+};
+
+uniform DirectionalLight uDirectionalLight;
+
+// Cookie of the main light, white without a cookie
+uniform sampler2D uDirectionalCookie;
+
+// Weights of the 4 layers in the rgba channels, over the whole terrain
+uniform sampler2D uSplat;
+uniform sampler2D uLayer0;
+uniform sampler2D uLayer1;
+uniform sampler2D uLayer2;
+uniform sampler2D uLayer3;
+// Repeats of each layer over the terrain
+uniform vec4 uTiling;
+
+// The lighting is computed in linear space, see `Camera::gamma`
+uniform float uGamma;
+uniform float uCameraExposure;
+uniform vec3 uViewPos;
+
+// Fog of the scene, see `EnvironmentSettings`. The mode is 0 without fog, 1 linear,
+// 2 exponential and 3 exponential squared
+struct Fog {
+    int mode;
+    vec3 color;
+    float density;
+    float start;
+    float end;
+    float heightBase;
+    float heightFalloff;
+};
+
+uniform Fog uFog;
+
+varying vec3 vFragPos;
+varying vec3 vNormal;
+varying vec2 vTexCoords;
+
+vec3 toLinear(vec3 c) {
+    return pow(c, vec3(uGamma));
+}
+
+// The color textures are decoded by the sampling when they are sRGB ones,
+// see `Texture::set_srgb`
+vec3 texelToLinear(vec3 c) {
+#ifdef UNI_SRGB_TEXTURES
+    return c;
+#else
+    return toLinear(c);
+#endif
+}
+
+vec3 DirectionalCookie(DirectionalLight light, vec3 fragPos) {
+    if (!light.cookie) {
+        return vec3(1.0);
+    }
+
+    vec3 right = normalize(cross(light.direction, light.cookieUp));
+    vec2 uv = vec2(dot(fragPos, right), dot(fragPos, light.cookieUp)) / light.cookieSize;
+    return texture2D(uDirectionalCookie, uv).rgb;
+}
+
+vec3 ApplyFog(vec3 color, vec3 fragPos) {
+    if (uFog.mode == 0) {
+        return color;
+    }
+
+    float distance = length(uViewPos - fragPos);
+    float visibility;
+    if (uFog.mode == 1) {
+        visibility = clamp((uFog.end - distance) / max(uFog.end - uFog.start, 0.0001), 0.0, 1.0);
+    } else if (uFog.mode == 2) {
+        visibility = exp(-uFog.density * distance);
+    } else {
+        float d = uFog.density * distance;
+        visibility = exp(-d * d);
+    }
+
+    // The fog thins out above its base height
+    if (uFog.heightFalloff > 0.0) {
+        float thickness = exp(-uFog.heightFalloff * max(fragPos.y - uFog.heightBase, 0.0));
+        visibility = 1.0 - (1.0 - visibility) * thickness;
+    }
+
+    return mix(toLinear(uFog.color), color, visibility);
+}
+
+void main(void) {
+    vec4 weights = texture2D(uSplat, vTexCoords);
+    weights /= max(dot(weights, vec4(1.0)), 0.0001);
+
+    vec3 albedo = texelToLinear(texture2D(uLayer0, vTexCoords * uTiling.x).rgb) * weights.r;
+    albedo += texelToLinear(texture2D(uLayer1, vTexCoords * uTiling.y).rgb) * weights.g;
+    albedo += texelToLinear(texture2D(uLayer2, vTexCoords * uTiling.z).rgb) * weights.b;
+    albedo += texelToLinear(texture2D(uLayer3, vTexCoords * uTiling.w).rgb) * weights.a;
+
+    vec3 normal = normalize(vNormal);
+    float diff = max(dot(normal, normalize(-uDirectionalLight.direction)), 0.0);
+
+    vec3 result = toLinear(uDirectionalLight.ambient) * albedo;
+    result += toLinear(uDirectionalLight.diffuse) * diff * albedo * DirectionalCookie(uDirectionalLight, vFragPos);
+
+    result = ApplyFog(result * uCameraExposure, vFragPos);
+
+    gl_FragColor = vec4(pow(result, vec3(1.0 / uGamma)), 1.0);
+}
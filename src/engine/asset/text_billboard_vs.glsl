@@ -0,0 +1,27 @@
+#ifndef GL_ES
+#define attribute in
+#define varying out
+#endif
+
+// The text faces the camera, its vertices are offsets in view space from the origin
+// of the object, scaled by the object
+attribute vec3 aVertexPosition;
+attribute vec2 aTextureCoord;
+attribute vec4 aVertexColor;
+
+uniform mat4 uMVMatrix;
+uniform mat4 uPMatrix;
+
+varying vec2 vTexCoords;
+varying vec4 vColor;
+
+void main(void) {
+    vTexCoords = aTextureCoord;
+    vColor = aVertexColor;
+
+    vec2 scale = vec2(length(uMVMatrix[0].xyz), length(uMVMatrix[1].xyz));
+    vec4 viewPos = uMVMatrix * vec4(0.0, 0.0, 0.0, 1.0);
+    viewPos.xy += aVertexPosition.xy * scale;
+
+    gl_Position = uPMatrix * viewPos;
+}
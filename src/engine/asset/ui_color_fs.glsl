@@ -0,0 +1,22 @@
+#ifndef GL_ES
+#define varying in
+#define gl_FragColor FragColor
+out vec4 FragColor;
+#endif
+
+varying vec4 vColor;
+varying vec2 vClipPos;
+uniform vec4 uColor;
+// (xmin, ymin, xmax, ymax) in ndc
+uniform vec4 uClipRect;
+// Opacity of the animation of the widget
+uniform float uOpacity;
+
+void main(void) {
+    if (any(lessThan(vClipPos, uClipRect.xy)) || any(greaterThan(vClipPos, uClipRect.zw))) {
+        discard;
+    }
+
+    gl_FragColor = uColor * vColor;
+    gl_FragColor.a *= uOpacity;
+}
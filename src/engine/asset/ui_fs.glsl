@@ -7,8 +7,19 @@ out vec4 FragColor;
 
 varying vec3 vColor;
 varying vec2 vTextureCoord;
+varying vec2 vClipPos;
 uniform sampler2D uDiffuse;
+uniform vec4 uColor;
+// (xmin, ymin, xmax, ymax) in ndc
+uniform vec4 uClipRect;
+// Opacity of the animation of the widget
+uniform float uOpacity;
 
 void main(void) {
-    gl_FragColor = texture2D(uDiffuse, vec2(vTextureCoord.s, vTextureCoord.t));
+    if (any(lessThan(vClipPos, uClipRect.xy)) || any(greaterThan(vClipPos, uClipRect.zw))) {
+        discard;
+    }
+
+    gl_FragColor = uColor * texture2D(uDiffuse, vec2(vTextureCoord.s, vTextureCoord.t));
+    gl_FragColor.a *= uOpacity;
 }
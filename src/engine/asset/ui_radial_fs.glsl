@@ -0,0 +1,39 @@
+#ifndef GL_ES
+#define varying in
+#define gl_FragColor FragColor
+out vec4 FragColor;
+#define texture2D texture
+#endif
+
+varying vec2 vTextureCoord;
+varying vec2 vClipPos;
+uniform sampler2D uDiffuse;
+uniform vec4 uColor;
+// (xmin, ymin, xmax, ymax) in ndc
+uniform vec4 uClipRect;
+// Opacity of the animation of the widget
+uniform float uOpacity;
+// Filled fraction (0..1) of the circle, clockwise from the top
+uniform float uFraction;
+
+const float PI = 3.14159265;
+
+void main(void) {
+    if (any(lessThan(vClipPos, uClipRect.xy)) || any(greaterThan(vClipPos, uClipRect.zw))) {
+        discard;
+    }
+
+    // The texture coordinates grow from the top-left
+    vec2 d = vTextureCoord - vec2(0.5, 0.5);
+    float t = atan(d.x, -d.y) / (2.0 * PI);
+    if (t < 0.0) {
+        t += 1.0;
+    }
+
+    if (t > uFraction) {
+        discard;
+    }
+
+    gl_FragColor = uColor * texture2D(uDiffuse, vTextureCoord);
+    gl_FragColor.a *= uOpacity;
+}
@@ -0,0 +1,21 @@
+#ifndef GL_ES
+#define attribute in
+#define varying out
+#endif
+
+attribute vec3 aVertexPosition;
+attribute vec2 aTextureCoord;
+attribute vec4 aVertexColor;
+varying vec2 vTextureCoord;
+varying vec4 vColor;
+varying vec2 vClipPos;
+uniform mat4 uMMatrix;
+// Rotation and scale of the widget
+uniform mat4 uTransform;
+
+void main(void) {
+    gl_Position = uMMatrix * uTransform * vec4(aVertexPosition, 1.0);
+    vTextureCoord = aTextureCoord;
+    vColor = aVertexColor;
+    vClipPos = gl_Position.xy;
+}
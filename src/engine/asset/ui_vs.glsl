@@ -6,9 +6,15 @@
 attribute vec3 aVertexPosition;
 attribute vec2 aTextureCoord;
 varying vec2 vTextureCoord;
+varying vec2 vClipPos;
 uniform mat4 uMMatrix;
+// Rotation and scale of the widget
+uniform mat4 uTransform;
+// Offset of the texture coordinates, e.g. the frame of a sprite sheet
+uniform vec2 uUvOffset;
             
 void main(void) {
-    gl_Position = uMMatrix * vec4(aVertexPosition, 1.0);        
-    vTextureCoord = aTextureCoord;
+    gl_Position = uMMatrix * uTransform * vec4(aVertexPosition, 1.0);        
+    vTextureCoord = aTextureCoord + uUvOffset;
+    vClipPos = gl_Position.xy;
 }
\ No newline at end of file
@@ -0,0 +1,13 @@
+#ifndef GL_ES
+#define varying in
+#define gl_FragColor FragColor
+out vec4 FragColor;
+#define texture2D texture
+#endif
+
+varying vec2 vTexCoords;
+uniform sampler2D uDiffuse;
+
+void main(void) {
+    gl_FragColor = texture2D(uDiffuse, vTexCoords);
+}
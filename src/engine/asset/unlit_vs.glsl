@@ -0,0 +1,27 @@
+#ifndef GL_ES
+#define attribute in
+#define varying out
+#endif
+
+attribute vec3 aVertexPosition;
+attribute vec2 aTextureCoord;
+
+#ifdef UNI_INSTANCED
+// Transform of the instance in the object, see `MeshInstances`
+attribute mat4 aInstanceMatrix;
+#endif
+
+uniform mat4 uMVMatrix;
+uniform mat4 uPMatrix;
+
+varying vec2 vTexCoords;
+
+void main(void) {
+    vTexCoords = aTextureCoord;
+
+#ifdef UNI_INSTANCED
+    gl_Position = uPMatrix * uMVMatrix * aInstanceMatrix * vec4(aVertexPosition, 1.0);
+#else
+    gl_Position = uPMatrix * uMVMatrix * vec4(aVertexPosition, 1.0);
+#endif
+}
@@ -1,9 +1,9 @@
 use engine::asset::AssetResult;
 use engine::core::Component;
 use engine::engine::EngineStats;
-use engine::render::{CullMode, DepthTest, Material, MaterialState, MeshBuffer, ShaderProgram,
-                     Texture};
-use std::collections::VecDeque;
+use engine::render::{BlendEquation, BlendFactor, BlendMode, CullMode, DepthTest, Fog,
+                     LightClusters, Material, MaterialState, MeshBuffer, ShaderProgram, Texture};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::rc::{Rc, Weak};
 use std::sync::Arc;
 use uni_gl;
@@ -13,6 +13,33 @@ trait ToGLState<T> {
     fn as_gl_state(&self) -> T;
 }
 
+impl ToGLState<uni_gl::BlendMode> for BlendFactor {
+    fn as_gl_state(&self) -> uni_gl::BlendMode {
+        match self {
+            &BlendFactor::Zero => uni_gl::BlendMode::Zero,
+            &BlendFactor::One => uni_gl::BlendMode::One,
+            &BlendFactor::SrcColor => uni_gl::BlendMode::SrcColor,
+            &BlendFactor::OneMinusSrcColor => uni_gl::BlendMode::OneMinusSrcColor,
+            &BlendFactor::SrcAlpha => uni_gl::BlendMode::SrcAlpha,
+            &BlendFactor::OneMinusSrcAlpha => uni_gl::BlendMode::OneMinusSrcAlpha,
+            &BlendFactor::DstColor => uni_gl::BlendMode::DstColor,
+            &BlendFactor::OneMinusDstColor => uni_gl::BlendMode::OneMinusDstColor,
+            &BlendFactor::DstAlpha => uni_gl::BlendMode::DstAlpha,
+            &BlendFactor::OneMinusDstAlpha => uni_gl::BlendMode::OneMinusDstAlpha,
+        }
+    }
+}
+
+impl ToGLState<uni_gl::BlendEquation> for BlendEquation {
+    fn as_gl_state(&self) -> uni_gl::BlendEquation {
+        match self {
+            &BlendEquation::Add => uni_gl::BlendEquation::FuncAdd,
+            &BlendEquation::Subtract => uni_gl::BlendEquation::FuncSubtract,
+            &BlendEquation::ReverseSubtract => uni_gl::BlendEquation::FuncReverseSubtract,
+        }
+    }
+}
+
 impl ToGLState<uni_gl::DepthTest> for DepthTest {
     fn as_gl_state(&self) -> uni_gl::DepthTest {
         match self {
@@ -32,6 +59,8 @@ impl ToGLState<uni_gl::DepthTest> for DepthTest {
 pub struct StateCache {
     state: MaterialState,
     curr: MaterialState,
+    /// Number of the GL states changed by `commit`
+    pub changes: u32,
 }
 
 impl StateCache {
@@ -40,6 +69,7 @@ impl StateCache {
             cull: Some(CullMode::Back),
             depth_test: Some(DepthTest::Less),
             alpha_blending: Some(false),
+            blend: Some(BlendMode::alpha()),
             depth_write: Some(true),
         }
     }
@@ -50,6 +80,7 @@ impl StateCache {
         ms.depth_write.map(|s| self.curr.depth_write = Some(s));
         ms.alpha_blending
             .map(|s| self.curr.alpha_blending = Some(s));
+        ms.blend.map(|s| self.curr.blend = Some(s));
     }
 
     pub fn commit(&mut self, gl: &WebGLRenderingContext) {
@@ -59,6 +90,25 @@ impl StateCache {
         self.curr
             .alpha_blending
             .map(|s| self.apply_alpha_blending(gl, s));
+
+        // The blend mode is only relevant while blending
+        if self.curr.alpha_blending == Some(true) {
+            self.curr.blend.map(|s| self.apply_blend(gl, &s));
+        }
+    }
+
+    fn apply_blend(&mut self, gl: &WebGLRenderingContext, bm: &BlendMode) {
+        if let Some(s) = self.state.blend {
+            if s == *bm {
+                return;
+            }
+        }
+
+        gl.blend_equation(bm.equation.as_gl_state());
+        gl.blend_func(bm.src.as_gl_state(), bm.dst.as_gl_state());
+
+        self.changes += 1;
+        self.state.blend = Some(*bm);
     }
 
     fn apply_depth_write(&mut self, gl: &WebGLRenderingContext, b: bool) {
@@ -69,6 +119,7 @@ impl StateCache {
         }
 
         gl.depth_mask(b);
+        self.changes += 1;
         self.state.depth_write = Some(b);
     }
 
@@ -85,6 +136,7 @@ impl StateCache {
             gl.disable(Flag::Blend as i32);
         }
 
+        self.changes += 1;
         self.state.alpha_blending = Some(b);
     }
 
@@ -102,6 +154,7 @@ impl StateCache {
             gl.depth_func(ct.as_gl_state());
         }
 
+        self.changes += 1;
         self.state.depth_test = Some(*ct);
     }
 
@@ -130,6 +183,7 @@ impl StateCache {
             }
         }
 
+        self.changes += 1;
         self.state.cull = Some(*cm);
     }
 }
@@ -140,17 +194,33 @@ pub struct EngineContext {
     pub textures: VecDeque<(u32, Weak<Texture>)>,
 
     pub main_light: Option<Arc<Component>>,
-    pub point_lights: Vec<Arc<Component>>,
+    /// Point and spot lights, which are culled per object
+    pub lights: Vec<Arc<Component>>,
+    /// The `lights` culled into the clusters of the camera of the pass
+    pub light_clusters: Option<LightClusters>,
+    /// Point and spot light slots bound by each program in the pass, by its id. The
+    /// slots of a program which is not in it are all bound
+    pub light_slots: HashMap<usize, (usize, usize)>,
+    /// Environment cubemap of the materials which do not set `uEnvironment`
+    pub environment: Option<Rc<Texture>>,
+    /// Light probes of the scene, which the ambient of each object is blended from
+    pub light_probes: Option<Arc<Component>>,
+    /// Fog of the scene, see `EnvironmentSettings`
+    pub fog: Option<Fog>,
 
     pub switch_mesh: u32,
     pub switch_prog: u32,
     pub switch_tex: u32,
 
     pub stats: EngineStats,
+    /// Addresses of the mesh buffers and the textures counted in the memory stats
+    pub counted_assets: HashSet<usize>,
     pub states: StateCache,
 
     pub last_light_bound: Option<Weak<ShaderProgram>>,
-    pub last_material_bound: Option<Weak<Material>>,
+    pub last_camera_bound: Option<Weak<ShaderProgram>>,
+    /// The material bound last and whether it was bound for instancing
+    pub last_material_bound: Option<(Weak<Material>, bool)>,
 }
 
 impl EngineContext {
@@ -161,16 +231,23 @@ impl EngineContext {
             textures: Default::default(),
 
             main_light: Default::default(),
-            point_lights: Default::default(),
+            lights: Default::default(),
+            light_clusters: None,
+            light_slots: HashMap::new(),
+            environment: None,
+            light_probes: None,
+            fog: None,
 
             switch_mesh: 0,
             switch_prog: 0,
             switch_tex: 0,
 
             stats: EngineStats::default(),
+            counted_assets: HashSet::new(),
 
             states: Default::default(),
             last_light_bound: None,
+            last_camera_bound: None,
             last_material_bound: None,
         }
     }
@@ -210,6 +287,12 @@ impl EngineContext {
         Ok(())
     }
 
+    /// Forget the bound textures, after the texture units are changed behind the cache
+    pub fn reset_texture_cache(&mut self) {
+        self.textures.clear();
+        self.last_material_bound = None;
+    }
+
     #[cfg_attr(feature = "flame_it", flame)]
     pub fn find_cache_tex(&self, new_tex: &Rc<Texture>) -> Option<(usize, u32)> {
         for (pos, &(u, ref tex)) in self.textures.iter().enumerate() {
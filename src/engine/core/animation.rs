@@ -0,0 +1,164 @@
+use math::*;
+
+/// Property of a target which an animation channel drives
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AnimationProperty {
+    Translation,
+    /// Quaternions in xyzw order
+    Rotation,
+    Scale,
+    /// Weights of the morph targets of the mesh of the target, see `Mesh::morph_weights`
+    MorphWeights,
+}
+
+/// How the values between two keyframes are computed
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Interpolation {
+    /// The value of the previous keyframe
+    Step,
+    /// Linear, and spherical linear for the rotations
+    Linear,
+    /// Hermite spline, each keyframe has an in tangent, a value and an out tangent
+    CubicSpline,
+}
+
+/// The keyframes of a property of a target
+#[derive(Debug, Clone)]
+pub struct AnimationChannel {
+    /// Index of the target in the targets of the animation, e.g. a node of a prefab
+    pub target: usize,
+    pub property: AnimationProperty,
+    pub interpolation: Interpolation,
+    /// Times of the keyframes in seconds, ascending
+    pub times: Vec<f32>,
+    /// Values of the keyframes one after another, each of the same number of floats
+    pub values: Vec<f32>,
+}
+
+fn hermite(v0: f32, b0: f32, a1: f32, v1: f32, t: f32, dt: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+
+    (2.0 * t3 - 3.0 * t2 + 1.0) * v0 + (t3 - 2.0 * t2 + t) * dt * b0
+        + (-2.0 * t3 + 3.0 * t2) * v1 + (t3 - t2) * dt * a1
+}
+
+impl AnimationChannel {
+    /// Number of floats of a value, e.g. 3 for a translation
+    pub fn width(&self) -> usize {
+        let per_key = match self.interpolation {
+            Interpolation::CubicSpline => 3,
+            _ => 1,
+        };
+
+        match self.times.len() * per_key {
+            0 => 0,
+            n => self.values.len() / n,
+        }
+    }
+
+    /// The floats of the value `i` of the keyframe `key`, 0 the in tangent, 1 the value and
+    /// 2 the out tangent of a cubic spline
+    fn key_value(&self, key: usize, i: usize) -> &[f32] {
+        let w = self.width();
+        let start = match self.interpolation {
+            Interpolation::CubicSpline => (key * 3 + i) * w,
+            _ => key * w,
+        };
+
+        &self.values[start..start + w]
+    }
+
+    /// The value at the time `t`, clamped to the first and the last keyframes
+    pub fn sample(&self, t: f32) -> Vec<f32> {
+        let w = self.width();
+        if w == 0 {
+            return Vec::new();
+        }
+
+        let last = self.times.len() - 1;
+        let next = self.times.iter().position(|&time| time > t).unwrap_or(last + 1);
+
+        if next == 0 {
+            return self.key_value(0, 1).to_vec();
+        }
+        if next > last {
+            return self.key_value(last, 1).to_vec();
+        }
+
+        let prev = next - 1;
+        let dt = self.times[next] - self.times[prev];
+        let s = if dt > 0.0 {
+            (t - self.times[prev]) / dt
+        } else {
+            0.0
+        };
+
+        let mut value: Vec<f32> = match self.interpolation {
+            Interpolation::Step => return self.key_value(prev, 1).to_vec(),
+            Interpolation::Linear => {
+                let (a, b) = (self.key_value(prev, 1), self.key_value(next, 1));
+
+                if self.property == AnimationProperty::Rotation && w == 4 {
+                    let qa = Quaternion::new(a[3], a[0], a[1], a[2]);
+                    let mut qb = Quaternion::new(b[3], b[0], b[1], b[2]);
+                    // The shortest path
+                    if qa.dot(qb) < 0.0 {
+                        qb = -qb;
+                    }
+
+                    let q = qa.slerp(qb, s);
+                    return vec![q.v.x, q.v.y, q.v.z, q.s];
+                }
+
+                a.iter().zip(b.iter()).map(|(a, b)| a + (b - a) * s).collect()
+            }
+            Interpolation::CubicSpline => {
+                let v0 = self.key_value(prev, 1);
+                let b0 = self.key_value(prev, 2);
+                let a1 = self.key_value(next, 0);
+                let v1 = self.key_value(next, 1);
+
+                (0..w)
+                    .map(|i| hermite(v0[i], b0[i], a1[i], v1[i], s, dt))
+                    .collect()
+            }
+        };
+
+        if self.property == AnimationProperty::Rotation && w == 4 {
+            let len = value.iter().map(|v| v * v).sum::<f32>().sqrt();
+            if len > 0.0 {
+                for v in value.iter_mut() {
+                    *v /= len;
+                }
+            }
+        }
+
+        value
+    }
+}
+
+/// An animation of the properties of some targets, e.g. the nodes of a prefab
+/// played by an `Animator`
+#[derive(Debug, Clone)]
+pub struct AnimationClip {
+    pub name: Option<String>,
+    /// Time of the last keyframe in seconds
+    pub duration: f32,
+    pub channels: Vec<AnimationChannel>,
+}
+
+impl AnimationClip {
+    pub fn new(name: Option<String>, channels: Vec<AnimationChannel>) -> AnimationClip {
+        let duration = channels
+            .iter()
+            .filter_map(|c| c.times.last().cloned())
+            .fold(0.0, f32::max);
+
+        AnimationClip {
+            name,
+            duration,
+            channels,
+        }
+    }
+}
@@ -123,6 +123,10 @@ impl GameObjectUtil {
             transform: Transform::new(node_id, tree),
             arena: Rc::downgrade(arena),
             active: true,
+            layer: 1,
+            is_static: false,
+            occluder: false,
+            occludee: true,
             components: vec![],
         }
     }
@@ -225,6 +229,17 @@ impl Transform {
 pub struct GameObject {
     pub transform: Transform,
     pub active: bool,
+    /// Bit mask of the layers of the object, it is only rendered by the cameras whose
+    /// `culling_mask` shares one of them. The layer 0 (1) by default
+    pub layer: u32,
+    /// The object does not move, its mesh is culled with the bounding volume
+    /// hierarchy of the static objects of the scene
+    pub is_static: bool,
+    /// The mesh of the object hides the ones behind it from the cameras with
+    /// `occlusion_culling`, e.g. the walls of a building. It should have few triangles
+    pub occluder: bool,
+    /// The object is not drawn when it is hidden by the occluders, true by default
+    pub occludee: bool,
     components: Vec<Arc<Component>>,
     arena: rc::Weak<ComponentArena>,
 }
@@ -235,6 +250,10 @@ impl GameObject {
         Rc::new(RefCell::new(GameObject {
             transform: Transform::new(0, rc::Weak::new()),
             active: true,
+            layer: 1,
+            is_static: false,
+            occluder: false,
+            occludee: true,
             arena: rc::Weak::new(),
             components: vec![],
         }))
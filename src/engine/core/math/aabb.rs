@@ -1,8 +1,8 @@
-use math::InnerSpace;
+use math::{EuclideanSpace, InnerSpace, Matrix4f, Point3, Transform};
 use math::Vector3f;
 use std::default::Default;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Aabb {
     pub min: Vector3f,
     pub max: Vector3f,
@@ -64,6 +64,20 @@ impl Aabb {
         ]
     }
 
+    pub fn center(&self) -> Vector3f {
+        (self.max + self.min) * 0.5
+    }
+
+    /// The aabb of this box transformed by `m`
+    pub fn transform(&self, m: &Matrix4f) -> Aabb {
+        let mut r = Aabb::empty();
+        for c in self.corners().iter() {
+            r.merge_point(&m.transform_point(Point3::from_vec(*c)).to_vec());
+        }
+
+        r
+    }
+
     pub fn sphere(&self) -> (Vector3f, f32) {
         let center = (self.max + self.min) * 0.5;
 
@@ -0,0 +1,296 @@
+use super::Aabb;
+
+const MAX_LEAF_ITEMS: usize = 4;
+
+enum BvhNode {
+    Leaf {
+        aabb: Aabb,
+        start: usize,
+        count: usize,
+    },
+    Branch {
+        aabb: Aabb,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl BvhNode {
+    fn aabb(&self) -> &Aabb {
+        match self {
+            &BvhNode::Leaf { ref aabb, .. } => aabb,
+            &BvhNode::Branch { ref aabb, .. } => aabb,
+        }
+    }
+}
+
+/// Bounding volume hierarchy of items with world space bounds,
+/// built once by median splits along the longest axis of the item centers
+pub struct Bvh<T> {
+    nodes: Vec<BvhNode>,
+    items: Vec<(Aabb, T)>,
+}
+
+impl<T> Bvh<T> {
+    pub fn new(items: Vec<(Aabb, T)>) -> Bvh<T> {
+        let mut bvh = Bvh {
+            nodes: Vec::new(),
+            items,
+        };
+
+        if !bvh.items.is_empty() {
+            let count = bvh.items.len();
+            bvh.build(0, count);
+        }
+
+        bvh
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Build the node of the items `start..start + count` and return its index
+    fn build(&mut self, start: usize, count: usize) -> usize {
+        let mut aabb = Aabb::empty();
+        let mut centers = Aabb::empty();
+        for &(ref item_aabb, _) in self.items[start..start + count].iter() {
+            aabb.merge(item_aabb);
+            centers.merge_point(&item_aabb.center());
+        }
+
+        if count <= MAX_LEAF_ITEMS {
+            self.nodes.push(BvhNode::Leaf { aabb, start, count });
+            return self.nodes.len() - 1;
+        }
+
+        let extent = centers.max - centers.min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+
+        self.items[start..start + count].sort_by(|a, b| {
+            a.0.center()[axis]
+                .partial_cmp(&b.0.center()[axis])
+                .unwrap_or(::std::cmp::Ordering::Equal)
+        });
+
+        // The children are filled in after they are built
+        self.nodes.push(BvhNode::Branch {
+            aabb,
+            left: 0,
+            right: 0,
+        });
+        let index = self.nodes.len() - 1;
+
+        let half = count / 2;
+        let l = self.build(start, half);
+        let r = self.build(start + half, count - half);
+
+        if let BvhNode::Branch {
+            ref mut left,
+            ref mut right,
+            ..
+        } = self.nodes[index]
+        {
+            *left = l;
+            *right = r;
+        }
+
+        index
+    }
+
+    /// Call `found` with the items whose bounds pass `test`,
+    /// the subtrees of the nodes failing `test` are skipped
+    pub fn query<F, G>(&self, mut test: F, mut found: G)
+    where
+        F: FnMut(&Aabb) -> bool,
+        G: FnMut(&T),
+    {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let mut stack = vec![0];
+
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i];
+            if !test(node.aabb()) {
+                continue;
+            }
+
+            match node {
+                &BvhNode::Leaf { start, count, .. } => {
+                    for &(ref aabb, ref item) in self.items[start..start + count].iter() {
+                        if test(aabb) {
+                            found(item);
+                        }
+                    }
+                }
+                &BvhNode::Branch { left, right, .. } => {
+                    stack.push(left);
+                    stack.push(right);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use math::{InnerSpace, Vector3f};
+
+    /// Boxes scattered by a small lcg, such that the trees are the same in each run
+    fn boxes(count: usize) -> Vec<(Aabb, usize)> {
+        let mut seed = 12345u32;
+        let mut next = move || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (seed >> 8) as f32 / (1 << 24) as f32
+        };
+
+        (0..count)
+            .map(|i| {
+                let min = Vector3f::new(next(), next(), next()) * 100.0
+                    - Vector3f::new(50.0, 50.0, 50.0);
+                let size = Vector3f::new(next(), next(), next()) * 5.0;
+                (Aabb { min, max: min + size }, i)
+            })
+            .collect()
+    }
+
+    /// Slab test of the ray from `origin` along `dir` against `aabb`
+    fn ray_hits(origin: Vector3f, dir: Vector3f, aabb: &Aabb) -> bool {
+        let (mut t0, mut t1) = (0.0f32, ::std::f32::MAX);
+        for i in 0..3 {
+            if dir[i].abs() < 1e-6 {
+                if origin[i] < aabb.min[i] || origin[i] > aabb.max[i] {
+                    return false;
+                }
+                continue;
+            }
+
+            let a = (aabb.min[i] - origin[i]) / dir[i];
+            let b = (aabb.max[i] - origin[i]) / dir[i];
+            t0 = t0.max(a.min(b));
+            t1 = t1.min(a.max(b));
+        }
+        t0 <= t1
+    }
+
+    /// The planes (normal, offset) of a frustum looking along -z from the origin, with
+    /// the box test of `Frustum::collide_aabb`
+    fn frustum() -> Vec<(Vector3f, f32)> {
+        let side = |x: f32, y: f32| (Vector3f::new(x, y, -1.0).normalize(), 0.0);
+        vec![
+            side(1.0, 0.0),
+            side(-1.0, 0.0),
+            side(0.0, 1.0),
+            side(0.0, -1.0),
+            (Vector3f::new(0.0, 0.0, -1.0), 1.0),
+            (Vector3f::new(0.0, 0.0, 1.0), -40.0),
+        ]
+    }
+
+    fn frustum_hits(planes: &[(Vector3f, f32)], aabb: &Aabb) -> bool {
+        planes.iter().all(|&(n, offset)| {
+            let p = Vector3f::new(
+                if n.x >= 0.0 { aabb.max.x } else { aabb.min.x },
+                if n.y >= 0.0 { aabb.max.y } else { aabb.min.y },
+                if n.z >= 0.0 { aabb.max.z } else { aabb.min.z },
+            );
+            n.dot(p) - offset >= 0.0
+        })
+    }
+
+    /// The items found by the bvh and by testing all of them, sorted
+    fn compare<F>(items: Vec<(Aabb, usize)>, test: F) -> (Vec<usize>, Vec<usize>)
+    where
+        F: Fn(&Aabb) -> bool,
+    {
+        let mut expected: Vec<usize> = items
+            .iter()
+            .filter(|&&(ref aabb, _)| test(aabb))
+            .map(|&(_, i)| i)
+            .collect();
+
+        let bvh = Bvh::new(items);
+        let mut found = Vec::new();
+        bvh.query(|aabb| test(aabb), |&i| found.push(i));
+
+        found.sort();
+        expected.sort();
+        (found, expected)
+    }
+
+    #[test]
+    fn ray_query_matches_brute_force() {
+        // Rays aimed at the centers of some of the boxes, and one pointing away
+        let mut rays: Vec<_> = boxes(500)
+            .iter()
+            .filter(|&&(_, i)| i % 50 == 0)
+            .map(|&(ref aabb, _)| {
+                let origin = Vector3f::new(-60.0, 10.0, -60.0);
+                (origin, aabb.center() - origin)
+            })
+            .collect();
+        rays.push((Vector3f::new(200.0, 0.0, 0.0), Vector3f::unit_x()));
+
+        for (k, &(origin, dir)) in rays.iter().enumerate() {
+            let (found, expected) = compare(boxes(500), |aabb| ray_hits(origin, dir, aabb));
+            assert_eq!(found, expected);
+            assert_eq!(found.is_empty(), k == rays.len() - 1);
+        }
+    }
+
+    #[test]
+    fn frustum_query_matches_brute_force() {
+        let planes = frustum();
+        let (found, expected) = compare(boxes(500), |aabb| frustum_hits(&planes, aabb));
+
+        assert!(!found.is_empty() && found.len() < 500);
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn empty_tree() {
+        let bvh: Bvh<usize> = Bvh::new(Vec::new());
+        assert!(bvh.is_empty());
+
+        let mut found = 0;
+        bvh.query(|_| true, |_| found += 1);
+        assert_eq!(found, 0);
+    }
+
+    #[test]
+    fn single_item_tree() {
+        let items = boxes(1);
+        let aabb = items[0].0;
+        let center = aabb.center();
+
+        let bvh = Bvh::new(items);
+        assert_eq!(bvh.len(), 1);
+
+        let mut found = Vec::new();
+        bvh.query(
+            |aabb| ray_hits(center - Vector3f::new(0.0, 0.0, 100.0), Vector3f::unit_z(), aabb),
+            |&i| found.push(i),
+        );
+        assert_eq!(found, vec![0]);
+
+        found.clear();
+        bvh.query(
+            |aabb| ray_hits(center + Vector3f::new(0.0, 100.0, 0.0), Vector3f::unit_z(), aabb),
+            |&i| found.push(i),
+        );
+        assert!(found.is_empty());
+    }
+}
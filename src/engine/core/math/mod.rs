@@ -1,3 +1,5 @@
 mod aabb;
+mod bvh;
 
 pub use self::aabb::Aabb;
+pub use self::bvh::Bvh;
@@ -1,8 +1,10 @@
+mod animation;
 mod component_arena;
 mod game_object;
 mod math;
 mod scene_tree;
 
+pub use self::animation::{AnimationChannel, AnimationClip, AnimationProperty, Interpolation};
 pub use self::component_arena::ComponentArena;
 pub use self::game_object::{Component, ComponentBased, ComponentType, GameObject, IntoComponentPtr};
 pub use self::math::*;
@@ -1,5 +1,7 @@
 use super::internal::GameObjectUtil;
 use engine::core::{Component, ComponentArena, GameObject};
+use engine::render::mesh_util;
+use engine::render::{Material, Mesh, MeshBuffer, MeshData, MeshPrimitive, MeshSurface};
 use math::*;
 use std::cell::{Cell, Ref, RefCell, RefMut};
 use std::collections::BTreeMap;
@@ -279,6 +281,123 @@ impl SceneTree {
             .collect()
     }
 
+    /// Merge the surfaces of the active static objects of the tree which share a material
+    /// into large buffers, so that the level geometry is drawn in a few draw calls,
+    /// see `GameObject::is_static`. It should be called once the meshes are loaded
+    /// and placed, the ones not loaded yet are skipped.
+    ///
+    /// The vertices are transformed into the space of the first object of each material,
+    /// whose mesh gets the merged surfaces, and the surfaces are removed from the other
+    /// objects. The instanced and morphed meshes and the surfaces with parameters are
+    /// left as they are. Return the number of the surfaces merged
+    pub fn combine_static(&self) -> usize {
+        let objects: Vec<(u64, Rc<RefCell<GameObject>>)> = self.nodes
+            .borrow()
+            .iter()
+            .filter(|&(&id, _)| id != 0)
+            .filter_map(|(&id, node)| node.go.upgrade().map(|go| (id, go)))
+            .collect();
+
+        // The surfaces by material, object layers and vertex layout
+        type Entry = (Rc<RefCell<GameObject>>, Rc<MeshSurface>, Matrix4<f32>);
+        let mut groups: Vec<((*const Material, u32, i32), Vec<Entry>)> = Vec::new();
+
+        for (id, go) in objects {
+            let surfaces: Vec<_> = {
+                let object = match go.try_borrow() {
+                    Ok(object) => object,
+                    Err(_) => continue,
+                };
+                if !object.active || !object.is_static {
+                    continue;
+                }
+
+                let (mesh, _) = match object.find_component::<Mesh>() {
+                    Some(mesh) => mesh,
+                    None => continue,
+                };
+                if mesh.instances().is_some() || !mesh.morph_weights.is_empty() {
+                    continue;
+                }
+
+                mesh.surfaces
+                    .iter()
+                    .filter(|s| {
+                        s.params.is_none() && s.buffer.primitive() == MeshPrimitive::Triangles
+                            && s.buffer
+                                .mesh_data()
+                                .map_or(false, |data| {
+                                    data.morph_targets.is_empty() && data.joints.is_none()
+                                })
+                    })
+                    .map(|s| {
+                        let key = (&*s.material as *const Material, object.layer, mesh.layer);
+                        (key, s.clone())
+                    })
+                    .collect()
+            };
+
+            let m = self.get_global_matrix(id);
+            for (key, surface) in surfaces {
+                let position = groups.iter().position(|&(k, ref entries)| {
+                    let first = entries[0].1.buffer.mesh_data();
+                    match (first, surface.buffer.mesh_data()) {
+                        (Ok(first), Ok(data)) => k == key && first.is_same_layout(&data),
+                        _ => false,
+                    }
+                });
+
+                let entry = (go.clone(), surface, m);
+                match position {
+                    Some(i) => groups[i].1.push(entry),
+                    None => groups.push((key, vec![entry])),
+                }
+            }
+        }
+
+        let mut merged = 0;
+        for (_, entries) in groups.into_iter().filter(|&(_, ref e)| e.len() > 1) {
+            let host = entries[0].0.clone();
+            let to_host = match entries[0].2.invert() {
+                Some(m) => m,
+                None => continue,
+            };
+            let material = entries[0].1.material.clone();
+
+            let mut batches: Vec<MeshData> = Vec::new();
+            for &(ref go, ref surface, ref m) in entries.iter() {
+                {
+                    let data = surface.buffer.mesh_data().unwrap();
+
+                    // The indices are u16, a batch is full at 65536 vertices
+                    let full = batches.last().map_or(true, |batch: &MeshData| {
+                        let count = (batch.vertices.len() + data.vertices.len()) / 3;
+                        count > u16::max_value() as usize
+                    });
+                    if full {
+                        batches.push(mesh_util::empty_like(&data));
+                    }
+
+                    let batch = batches.last_mut().unwrap();
+                    mesh_util::append_transformed(batch, &data, &(to_host * m));
+                }
+
+                if let Some((mut mesh, _)) = go.borrow().find_component_mut::<Mesh>() {
+                    mesh.remove_surface(surface);
+                }
+                merged += 1;
+            }
+
+            let host = host.borrow();
+            let (mut mesh, _) = host.find_component_mut::<Mesh>().unwrap();
+            for batch in batches {
+                mesh.add_surface(MeshBuffer::new(batch), material.clone());
+            }
+        }
+
+        merged
+    }
+
     pub fn len(&self) -> usize {
         self.nodes.borrow().len()
     }
@@ -8,15 +8,24 @@ use std::sync::Arc;
 
 use engine::asset::{AssetError, AssetResult, AssetSystem};
 use engine::context::EngineContext;
-use engine::core::{Component, ComponentArena, ComponentBased, GameObject, SceneTree};
-use engine::render::Camera;
-use engine::render::{DepthTest, DirectionalLight, Light, Material, MaterialState, Mesh,
-                     MeshSurface, ShaderProgram};
-use engine::render::{Frustum, RenderQueue};
+use engine::core::{Bvh, Component, ComponentArena, ComponentBased, GameObject, SceneTree};
+use engine::render::debug::{self, DebugRenderer};
+use engine::render::{Camera, ClearFlags, GLBackend, RenderBackend};
+use engine::render::{CullMode, DepthTest, DirectionalLight, EnvironmentSettings, Fog, Light,
+                     LightClusters, LightProbeGroup, LightUniforms, LodGroup, Material,
+                     MaterialParams, MaterialState, Mesh, MeshPrimitive, MeshSurface, RenderCaps,
+                     ShaderProgram, Sprite, UiBatcher, MAX_POINT_LIGHTS, MAX_SPOT_LIGHTS};
+use engine::render::{Frustum, MeshBuffer, MeshInstances, OcclusionBuffer, PostEffect, PostProcess,
+                     RenderQueue, RenderTexture, Skin, Skybox, Texture};
+use engine::render::{set_srgb_textures, set_vertex_array_objects};
+#[cfg(feature = "timer_query")]
+use engine::render::GpuTimer;
 use image;
 use math::Aabb;
 
+use std::cmp::Ordering;
 use std::default::Default;
+use std::mem;
 
 use super::imgui;
 
@@ -29,18 +38,93 @@ pub trait IEngine {
 
     fn gui_context(&mut self) -> Rc<RefCell<imgui::Context>>;
 
+    /// Register the scene tree of the `imgui::Ui` named `name`, see `Camera::ui`
+    fn register_ui(&mut self, name: &str, tree: &Rc<SceneTree>);
+
     fn screen_size(&self) -> (u32, u32);
 
     fn hidpi_factor(&self) -> f32;
+
+    /// Statistics of the last render pass of the main camera
+    fn stats(&self) -> EngineStats;
+
+    /// Features of the GL context, detected at startup
+    fn render_caps(&self) -> RenderCaps;
+
+    /// Statistics of all the passes rendered since the start of the frame
+    fn render_stats(&self) -> RenderStats;
 }
 
-#[derive(Default, Copy, Clone)]
+#[derive(Default, Copy, Clone, Debug)]
 pub struct EngineStats {
     pub surfaces_count: u32,
     pub opaque_count: u32,
     pub transparent_count: u32,
     pub total_opaque_count: u32,
     pub total_transparent_count: u32,
+    pub draw_calls: u32,
+    pub triangle_count: u32,
+    pub game_object_count: u32,
+    /// Estimated bytes of the mesh buffers and the textures drawn in the pass
+    pub mesh_memory: usize,
+    pub texture_memory: usize,
+    /// Surfaces outside of the camera frustum and surfaces sent to the render queues
+    pub culled_count: u32,
+    pub submitted_count: u32,
+    /// Objects hidden by the occluders, see `Camera::occlusion_culling`
+    pub occluded_count: u32,
+    /// Render states changed, e.g. the blending or the culling, and the shader
+    /// programs and the textures bound
+    pub state_changes: u32,
+    pub program_binds: u32,
+    pub texture_binds: u32,
+}
+
+/// Statistics of a pass of a camera
+#[derive(Copy, Clone, Debug)]
+pub struct PassStats {
+    pub stats: EngineStats,
+    /// The pass was drawn in a single material, e.g. a shadow map
+    pub with_material: bool,
+    /// Milliseconds which the GPU spent on the pass of the same index in a previous
+    /// frame, the timer queries are read back at the earliest a frame late. None
+    /// without `RenderCaps::timer_query` or until the first results are read
+    pub gpu_time: Option<f64>,
+}
+
+/// Statistics of a frame, reset at `Engine::begin`, for profiling overlays
+/// and performance tests
+#[derive(Default, Clone, Debug)]
+pub struct RenderStats {
+    pub draw_calls: u32,
+    pub triangle_count: u32,
+    pub state_changes: u32,
+    pub program_binds: u32,
+    pub texture_binds: u32,
+    /// Passes in the order they were rendered
+    pub passes: Vec<PassStats>,
+}
+
+impl RenderStats {
+    pub fn reset(&mut self) {
+        *self = RenderStats::default();
+    }
+
+    fn add_pass(&mut self, pass: PassStats) {
+        self.draw_calls += pass.stats.draw_calls;
+        self.triangle_count += pass.stats.triangle_count;
+        self.state_changes += pass.stats.state_changes;
+        self.program_binds += pass.stats.program_binds;
+        self.texture_binds += pass.stats.texture_binds;
+        self.passes.push(pass);
+    }
+
+    /// Milliseconds which the GPU spent on the passes, None unless all were measured
+    pub fn gpu_time(&self) -> Option<f64> {
+        self.passes
+            .iter()
+            .fold(Some(0.0), |sum, p| Some(sum? + p.gpu_time?))
+    }
 }
 
 pub struct Engine<A>
@@ -58,12 +142,79 @@ where
     pub arena: Rc<ComponentArena>,
 
     pub stats: EngineStats,
+    render_stats: RenderStats,
+    caps: RenderCaps,
+    /// Timer of the passes, with `RenderCaps::timer_query`
+    #[cfg(feature = "timer_query")]
+    gpu_timer: Option<GpuTimer>,
+
+    /// Batches of the UI queue of each `Camera::ui`
+    ui_batchers: HashMap<Option<String>, UiBatcher>,
+    /// Batches of the sprite queue of each `Camera::order`
+    sprite_batchers: HashMap<Option<i32>, UiBatcher>,
+
+    /// Scene trees of the `imgui::Ui`s other than the primary one
+    uis: HashMap<String, Weak<SceneTree>>,
+
+    /// Render targets of the post effects of each viewport size and whether they are hdr,
+    /// see `Camera::post_effects`
+    post_processes: HashMap<((u32, u32), bool), PostProcess>,
+
+    /// Bvh of the static objects, rebuilt when they change
+    static_bvh: RefCell<Option<StaticBvh>>,
+
+    /// Surfaces of the lines of the `debug` module
+    debug_renderer: Option<DebugRenderer>,
+}
+
+/// The bvh of the static objects of known bounds, see `GameObject::is_static`
+struct StaticBvh {
+    /// The objects and the world aabbs which the bvh was built from
+    objects: Vec<(*const RefCell<GameObject>, Aabb)>,
+    bvh: Bvh<usize>,
+}
+
+impl StaticBvh {
+    fn new(objects: Vec<(*const RefCell<GameObject>, Aabb)>) -> StaticBvh {
+        let bvh = Bvh::new(
+            objects
+                .iter()
+                .enumerate()
+                .map(|(i, &(_, aabb))| (aabb, i))
+                .collect(),
+        );
+
+        StaticBvh { objects, bvh }
+    }
+
+    /// Whether each static object is in `frustum`
+    fn cull(&self, frustum: &Frustum) -> HashMap<*const RefCell<GameObject>, bool> {
+        let mut visible: HashMap<_, _> = self.objects
+            .iter()
+            .map(|&(obj, _)| (obj, false))
+            .collect();
+
+        let objects = &self.objects;
+        self.bvh.query(
+            |aabb| frustum.collide_aabb(aabb),
+            |&i| {
+                visible.insert(objects[i].0, true);
+            },
+        );
+
+        visible
+    }
 }
 
 struct RenderCommand {
     pub surface: Rc<MeshSurface>,
     pub model_m: Matrix4<f32>,
     pub cam_distance: f32,
+    pub layer: i32,
+    /// World space bounding sphere, used to pick the lights of the object
+    pub bounds: Option<(Vector3<f32>, f32)>,
+    /// The instances drawn with the surface, see `MeshInstances`
+    pub instances: Option<Rc<MeshInstances>>,
 }
 
 #[derive(Default)]
@@ -73,12 +224,13 @@ struct RenderQueueState {
 }
 
 impl RenderQueueState {
+    /// Back to front, stable such that the surfaces of an object keep their order
     fn sort_by_cam_distance(&mut self) -> &mut Self {
-        self.commands.sort_unstable_by(|a, b| {
+        self.commands.sort_by(|a, b| {
             let adist: f32 = a.cam_distance;
             let bdist: f32 = b.cam_distance;
 
-            bdist.partial_cmp(&adist).unwrap()
+            bdist.partial_cmp(&adist).unwrap_or(Ordering::Equal)
         });
 
         self
@@ -89,12 +241,70 @@ impl RenderQueueState {
             let adist: f32 = a.cam_distance;
             let bdist: f32 = b.cam_distance;
 
-            adist.partial_cmp(&bdist).unwrap()
+            adist.partial_cmp(&bdist).unwrap_or(Ordering::Equal)
         });
 
         self
     }
 
+    /// Stable sort, such that the commands in the same layer keep the submission order
+    fn sort_by_layer(&mut self) -> &mut Self {
+        self.commands.sort_by_key(|c| c.layer);
+
+        self
+    }
+
+    /// Merge the consecutive commands of equal materials into batches, see `UiBatcher`
+    fn batch(&mut self, batcher: &UiBatcher) -> &mut Self {
+        let commands = mem::replace(&mut self.commands, Vec::new());
+        let mut run: Vec<RenderCommand> = Vec::new();
+
+        for cmd in commands.into_iter() {
+            let mergeable = match run.last() {
+                Some(last) => {
+                    last.instances.is_none() && cmd.instances.is_none()
+                        && UiBatcher::can_batch(&last.surface, &cmd.surface)
+                }
+                None => false,
+            };
+
+            if !mergeable {
+                self.push_batch(&mut run, batcher);
+            }
+            run.push(cmd);
+        }
+        self.push_batch(&mut run, batcher);
+
+        self
+    }
+
+    /// Push the commands of `run` as a single batch if they can be merged
+    fn push_batch(&mut self, run: &mut Vec<RenderCommand>, batcher: &UiBatcher) {
+        let merged = if run.len() > 1 {
+            let surfaces: Vec<_> = run.iter().map(|c| (&c.surface, c.model_m)).collect();
+            batcher.merge(&surfaces)
+        } else {
+            None
+        };
+
+        match merged {
+            Some(surface) => {
+                let (cam_distance, layer) = (run[0].cam_distance, run[0].layer);
+                run.clear();
+
+                self.commands.push(RenderCommand {
+                    surface,
+                    model_m: Matrix4::identity(),
+                    cam_distance,
+                    layer,
+                    bounds: None,
+                    instances: None,
+                });
+            }
+            None => self.commands.extend(run.drain(..)),
+        }
+    }
+
     fn sort_by_material(&mut self) -> &mut Self {
         self.commands.sort_by(|a, b| {
             let prog_a: &Material = &a.surface.material;
@@ -116,36 +326,78 @@ struct RenderQueueList {
     queues: BTreeMap<RenderQueue, RenderQueueState>,
 }
 
-impl RenderQueueList {
-    pub fn new() -> RenderQueueList {
-        let mut qlist = RenderQueueList::default();
+/// The states of the queues drawn like the named queue `base`
+fn queue_states(base: RenderQueue) -> MaterialState {
+    let mut states = MaterialState::default();
 
+    match base {
         // Opaque Queue
-        let mut state = RenderQueueState::default();
-        state.states.alpha_blending = Some(false);
-        qlist.queues.insert(RenderQueue::Opaque, state);
-
+        RenderQueue::Opaque => {
+            states.alpha_blending = Some(false);
+        }
         // Skybox Queue
-        let mut state = RenderQueueState::default();
-        state.states.depth_write = Some(false);
-        state.states.alpha_blending = Some(false);
-        state.states.depth_test = Some(DepthTest::LessEqual);
-        qlist.queues.insert(RenderQueue::Skybox, state);
-
+        RenderQueue::Skybox => {
+            states.depth_write = Some(false);
+            states.alpha_blending = Some(false);
+            states.depth_test = Some(DepthTest::LessEqual);
+        }
         // Transparent Queue
-        let mut state = RenderQueueState::default();
-        state.states.alpha_blending = Some(true);
-        state.states.depth_write = Some(false);
-        qlist.queues.insert(RenderQueue::Transparent, state);
-
+        RenderQueue::Transparent => {
+            states.alpha_blending = Some(true);
+            states.depth_write = Some(false);
+        }
+        // Sprite Queue
+        RenderQueue::Sprite => {
+            states.alpha_blending = Some(true);
+            states.depth_write = Some(false);
+            states.cull = Some(CullMode::Off);
+        }
         // UI Queue
-        let mut state = RenderQueueState::default();
-        state.states.alpha_blending = Some(true);
-        qlist.queues.insert(RenderQueue::UI, state);
+        _ => {
+            states.alpha_blending = Some(true);
+        }
+    }
+
+    states
+}
+
+impl RenderQueueList {
+    pub fn new() -> RenderQueueList {
+        let mut qlist = RenderQueueList::default();
+
+        let named = [
+            RenderQueue::Opaque,
+            RenderQueue::Skybox,
+            RenderQueue::Transparent,
+            RenderQueue::Sprite,
+            RenderQueue::UI,
+        ];
+        for queue in named.iter() {
+            qlist.queue_mut(*queue);
+        }
 
         qlist
     }
 
+    /// The state of `queue`, which is added on its first command
+    fn queue_mut(&mut self, queue: RenderQueue) -> &mut RenderQueueState {
+        self.queues
+            .entry(queue)
+            .or_insert_with(|| RenderQueueState {
+                states: queue_states(queue.base()),
+                commands: Vec::new(),
+            })
+    }
+
+    /// Number of the commands of the queues drawn like the named queue `base`
+    fn command_count(&self, base: RenderQueue) -> usize {
+        self.queues
+            .iter()
+            .filter(|&(queue, _)| queue.base() == base)
+            .map(|(_, q)| q.commands.len())
+            .sum()
+    }
+
     fn surface_count(&self) -> usize {
         let mut n = 0;
         for (_, q) in self.queues.iter() {
@@ -177,6 +429,18 @@ impl Default for ClearOption {
     }
 }
 
+/// The clear option of `camera` in a frame cleared by `frame`
+fn camera_clear_option(camera: &Camera, frame: &ClearOption) -> ClearOption {
+    let flags = camera.clear_flags;
+
+    ClearOption {
+        color: camera.clear_color.or(frame.color),
+        clear_color: frame.clear_color && flags == ClearFlags::Color,
+        clear_depth: frame.clear_depth && flags != ClearFlags::Nothing,
+        clear_stencil: frame.clear_stencil && flags != ClearFlags::Nothing,
+    }
+}
+
 fn get_max_scale(s: &Vector3<f32>) -> f32 {
     s[0].max(s[1]).max(s[2])
 }
@@ -191,67 +455,136 @@ where
 
     #[cfg_attr(feature = "flame_it", flame)]
     pub fn clear(&self, option: ClearOption) {
-        if let Some(col) = option.color {
-            self.gl.clear_color(col.0, col.1, col.2, col.3);
-        }
+        self.backend().clear(&option);
+    }
 
-        if option.clear_color {
-            self.gl.clear(BufferBit::Color);
-        }
-        if option.clear_depth {
-            self.gl.clear(BufferBit::Depth);
-        }
-        if option.clear_stencil {
-            self.gl.clear(BufferBit::Stencil);
-        }
+    /// The backend which the renderer draws with, see `RenderBackend`
+    pub fn backend(&self) -> GLBackend {
+        GLBackend::new(&self.gl)
     }
 
     pub fn resize(&mut self, size: (u32, u32)) {
         self.screen_size = size;
+        self.post_processes.clear();
 
         self.gui_context.borrow_mut().reset();
     }
 
+    /// Bind `material`, with the `UNI_INSTANCED` variant of its program if `instanced`
     #[cfg_attr(feature = "flame_it", flame)]
-    fn setup_material(&self, ctx: &mut EngineContext, material: &Rc<Material>) -> AssetResult<()> {
-        if let Some(ref last_material) = ctx.last_material_bound {
+    fn setup_material(
+        &self,
+        ctx: &mut EngineContext,
+        material: &Rc<Material>,
+        instanced: bool,
+    ) -> AssetResult<()> {
+        if let Some((ref last_material, last_instanced)) = ctx.last_material_bound {
             if let Some(last_material) = last_material.upgrade() {
-                if Rc::ptr_eq(&last_material, &material) {
+                if Rc::ptr_eq(&last_material, &material) && last_instanced == instanced {
                     return Ok(());
                 }
             }
         }
 
-        ctx.prepare_cache(&material.program, |ctx| {
-            material.program.bind(&self.gl)?;
+        let program = if instanced {
+            material.instanced_shader_program()?
+        } else {
+            material.shader_program()?
+        };
+        ctx.prepare_cache(&program, |ctx| {
+            program.bind(&self.gl)?;
             ctx.switch_prog += 1;
             Ok(())
         })?;
 
-        material.bind(|tex| {
-            ctx.prepare_cache_tex(tex, |ctx, unit| {
-                // Binding texture
-                tex.bind(&self.gl, unit)?;
-
-                ctx.switch_tex += 1;
-                Ok(())
-            })
-        })?;
+        material.bind(|tex| self.bind_texture(ctx, tex))?;
 
+        self.setup_environment(ctx, material, &program)?;
         self.setup_light(ctx);
 
-        ctx.last_material_bound = Some(Rc::downgrade(&material));
+        // A fading surface of a `LodGroup` drawn before could have left its fade
+        if material.get("uLodFade").is_none() {
+            program.set("uLodFade", 0.0);
+        }
+        // Likewise the lightmap of a surface, see `Mesh::set_lightmap`
+        if material.get("uLightmapEnabled").is_none() {
+            program.set("uLightmapEnabled", false);
+        }
+
+        ctx.last_material_bound = Some((Rc::downgrade(&material), instanced));
 
         Ok(())
     }
 
+    /// Bind `tex` to a texture unit unless it is bound yet, return the unit
+    fn bind_texture(&self, ctx: &mut EngineContext, tex: &Rc<Texture>) -> AssetResult<u32> {
+        if ctx.counted_assets.insert(&**tex as *const Texture as usize) {
+            ctx.stats.texture_memory += tex.image_size().map_or(0, |(w, h)| (w * h * 4) as usize);
+        }
+
+        ctx.prepare_cache_tex(tex, |ctx, unit| {
+            // Binding texture
+            tex.bind(&self.gl, unit)?;
+
+            ctx.switch_tex += 1;
+            Ok(())
+        })
+    }
+
+    /// Set the parameters of a surface over the ones of its material,
+    /// the material is bound again for the next surface
+    fn setup_params(&self, ctx: &mut EngineContext, params: &MaterialParams) -> AssetResult<()> {
+        let prog = ctx.prog.upgrade().unwrap();
+        ctx.last_material_bound = None;
+
+        params.bind(&prog, |tex| self.bind_texture(ctx, tex))
+    }
+
+    /// Bind the environment of the scene to the materials which use but do not set it
+    fn setup_environment(
+        &self,
+        ctx: &mut EngineContext,
+        material: &Material,
+        program: &ShaderProgram,
+    ) -> AssetResult<()> {
+        if material.get("uEnvironment").is_some() || !program.has_uniform(&self.gl, "uEnvironment")
+        {
+            return Ok(());
+        }
+
+        let env = match ctx.environment {
+            Some(ref env) => env.clone(),
+            None => return Ok(()),
+        };
+
+        let unit = ctx.prepare_cache_tex(&env, |ctx, unit| {
+            env.bind(&self.gl, unit)?;
+            ctx.switch_tex += 1;
+            Ok(())
+        })?;
+
+        program.set("uEnvironment", (Rc::downgrade(&env), unit));
+
+        Ok(())
+    }
+
+    /// Set the uniforms of `camera` which are the same for all the objects,
+    /// once per program in a pass as the program keeps them
     #[cfg_attr(feature = "flame_it", flame)]
-    fn setup_camera(&self, ctx: &mut EngineContext, modelm: Matrix4<f32>, camera: &Camera) {
+    fn setup_camera(&self, ctx: &mut EngineContext, camera: &Camera) {
         let prog = ctx.prog.upgrade().unwrap();
-        // setup_camera
-        let perspective = camera.perspective(self.screen_size);
 
-        prog.set("uMVMatrix", camera.v * modelm);
+        if let Some(ref last_prog) = ctx.last_camera_bound {
+            if let Some(last_prog) = last_prog.upgrade() {
+                if Rc::ptr_eq(&prog, &last_prog) {
+                    return;
+                }
+            }
+        }
+
+        ctx.last_camera_bound = Some(ctx.prog.clone());
+
+        let perspective = camera.perspective(self.screen_size);
         prog.set("uPMatrix", perspective);
 
         let skybox_v: Matrix3<_> = Matrix3::from_cols(
@@ -262,10 +595,20 @@ where
 
         prog.set("uPVMatrix", perspective * camera.v);
         prog.set("uPVSkyboxMatrix", perspective * Matrix4::from(skybox_v));
+        prog.set("uViewPos", camera.eye());
 
+        prog.set("uGamma", camera.gamma);
+        prog.set("uCameraExposure", camera.exposure);
+    }
+
+    /// Set the transforms of an object drawn by `camera`
+    #[cfg_attr(feature = "flame_it", flame)]
+    fn setup_transform(&self, ctx: &mut EngineContext, modelm: Matrix4<f32>, camera: &Camera) {
+        let prog = ctx.prog.upgrade().unwrap();
+
+        prog.set("uMVMatrix", camera.v * modelm);
         prog.set("uNMatrix", modelm.inverse_transform().unwrap().transpose());
         prog.set("uMMatrix", modelm);
-        prog.set("uViewPos", camera.eye());
     }
 
     #[cfg_attr(feature = "flame_it", flame)]
@@ -286,18 +629,138 @@ where
         let light_com = ctx.main_light.as_ref().unwrap();
         let light = light_com.try_as::<Light>().unwrap();
 
-        light.borrow().bind("uDirectionalLight", &prog);
+        let names = LightUniforms::directional();
+        light.borrow().bind(&names.0, &prog);
         // So shader needs to have a vs stage light
-        light.borrow().bind("uDirectionalLightVS", &prog);
+        light.borrow().bind(&names.1, &prog);
 
-        for (i, plight_com) in ctx.point_lights.iter().enumerate() {
-            let plight = plight_com.try_as::<Light>().unwrap();
-            let name = format!("uPointLights[{}]", i);
-            plight.borrow().bind(&name, &prog);
+        Fog::bind(ctx.fog.as_ref(), "uFog", &prog);
+    }
 
-            let name = format!("uPointLightsVS[{}]", i);
-            plight.borrow().bind(&name, &prog);
+    /// Bind the point and spot lights which reach the object of `cmd`, the slots of the
+    /// light arrays used by the previous object of the program are bound as unused.
+    ///
+    /// The lights are looked up in the clusters of the bounds of the object, then binned
+    /// by it: the nearest ones relative to their ranges take the slots first, such that
+    /// the slots go to the brightest of many lights. The cookie of the nearest spot light
+    /// with one is bound with the one of the main light
+    #[cfg_attr(feature = "flame_it", flame)]
+    fn setup_object_lights(&self, ctx: &mut EngineContext, cmd: &RenderCommand) {
+        let prog = ctx.prog.upgrade().unwrap();
+
+        let candidates = match (cmd.bounds, ctx.light_clusters.as_mut()) {
+            (Some((center, r)), Some(clusters)) => clusters.lights_near(&center, r),
+            _ => (0..ctx.lights.len()).collect(),
+        };
+
+        let mut binned: Vec<(f32, usize)> = candidates
+            .into_iter()
+            .filter_map(|i| {
+                let light = ctx.lights[i].try_as::<Light>().unwrap();
+                let light = light.borrow();
+
+                let score = match (cmd.bounds, light.bounding_sphere()) {
+                    (Some((center, r)), Some((p, range))) => {
+                        let d = (p - center).magnitude() - r;
+                        if d > range {
+                            return None;
+                        }
+                        d.max(0.0) / range.max(0.0001)
+                    }
+                    _ => 0.0,
+                };
+
+                Some((score, i))
+            })
+            .collect();
+
+        binned.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+
+        let mut points = 0;
+        let mut spots = 0;
+        let mut spot_cookie: Option<Rc<Texture>> = None;
+
+        for &(_, i) in binned.iter() {
+            let light = ctx.lights[i].try_as::<Light>().unwrap();
+            let light = light.borrow();
+
+            let names = match *light {
+                Light::Point(_) if points < MAX_POINT_LIGHTS => {
+                    points += 1;
+                    LightUniforms::point(points - 1)
+                }
+                Light::Spot(_) if spots < MAX_SPOT_LIGHTS => {
+                    spots += 1;
+                    LightUniforms::spot(spots - 1)
+                }
+                _ => continue,
+            };
+
+            light.bind(&names.0, &prog);
+            light.bind(&names.1, &prog);
+
+            if let (&Light::Spot(_), Some(cookie)) = (&*light, light.cookie()) {
+                if spot_cookie.is_none() {
+                    spot_cookie = Some(cookie.clone());
+                } else {
+                    prog.set(names.0.cookie(), false);
+                }
+            }
+        }
+
+        // Only the slots left over from the previous object of the program are cleared
+        let (last_points, last_spots) = ctx.light_slots
+            .insert(prog.id(), (points, spots))
+            .unwrap_or((MAX_POINT_LIGHTS, MAX_SPOT_LIGHTS));
+
+        for i in points..last_points {
+            Light::bind_unused(&LightUniforms::point(i).0, &prog);
         }
+        for i in spots..last_spots {
+            Light::bind_unused(&LightUniforms::spot(i).0, &prog);
+        }
+
+        let main_cookie = ctx.main_light.as_ref().and_then(|c| {
+            let light = c.try_as::<Light>().unwrap();
+            let cookie = light.borrow().cookie().cloned();
+            cookie
+        });
+
+        self.bind_cookie(ctx, "uDirectionalCookie", main_cookie.as_ref());
+        self.bind_cookie(ctx, "uSpotCookie", spot_cookie.as_ref());
+    }
+
+    /// Bind the cookie of a light to the sampler `name` of the programs which use it,
+    /// a white texture without a cookie or until it is loaded
+    fn bind_cookie(
+        &self,
+        ctx: &mut EngineContext,
+        name: &'static str,
+        cookie: Option<&Rc<Texture>>,
+    ) {
+        let prog = ctx.prog.upgrade().unwrap();
+        if !prog.has_uniform(&self.gl, name) {
+            return;
+        }
+
+        let bound = cookie.and_then(|tex| {
+            self.bind_texture(ctx, tex)
+                .ok()
+                .map(|unit| (tex.clone(), unit))
+        });
+
+        let (tex, unit) = match bound {
+            Some(bound) => bound,
+            None => {
+                let white = self.asset_system.new_texture("default_white");
+                match self.bind_texture(ctx, &white) {
+                    Ok(unit) => (white, unit),
+                    Err(_) => return,
+                }
+            }
+        };
+
+        prog.set(name, (Rc::downgrade(&tex), unit));
     }
 
     #[cfg_attr(feature = "flame_it", flame)]
@@ -321,7 +784,8 @@ where
             ctx.states.apply(&mat.states);
             ctx.states.commit(gl);
 
-            if let Err(err) = self.setup_material(ctx, mat) {
+            let instanced = cmd.instances.is_some() && self.caps.instancing;
+            if let Err(err) = self.setup_material(ctx, mat, instanced) {
                 if let AssetError::NotReady = err {
                     continue;
                 }
@@ -329,6 +793,16 @@ where
                 panic!(format!("Failed to load material, reason {:?}", err));
             }
 
+            if let Some(ref params) = cmd.surface.params {
+                if let Err(err) = self.setup_params(ctx, params) {
+                    if let AssetError::NotReady = err {
+                        continue;
+                    }
+
+                    panic!(format!("Failed to load material params, reason {:?}", err));
+                }
+            }
+
             let prog = ctx.prog.upgrade().unwrap();
 
             let r = ctx.prepare_cache(&cmd.surface.buffer, |ctx| {
@@ -339,11 +813,52 @@ where
 
             match r {
                 Ok(_) => {
-                    self.setup_camera(ctx, cmd.model_m, camera);
+                    self.setup_camera(ctx, camera);
+                    self.setup_transform(ctx, cmd.model_m, camera);
+                    self.setup_object_lights(ctx, cmd);
+                    self.setup_light_probe(ctx, cmd);
                     prog.commit(gl);
-                    // if let RenderQueue::UI = mat.render_queue
-                    {
-                        cmd.surface.buffer.render(gl);
+
+                    let copies = match cmd.instances {
+                        // A shader without the attribute would draw them all at the origin
+                        #[cfg(feature = "instanced_arrays")]
+                        Some(ref instances)
+                            if instanced && prog.attrib_loc(gl, "aInstanceMatrix").is_some() =>
+                        {
+                            let count = instances.transforms().len();
+                            instances.bind(gl);
+                            cmd.surface.buffer.render_instanced(gl, count);
+                            instances.unbind(gl);
+                            ctx.stats.draw_calls += 1;
+                            count
+                        }
+                        // Without instanced arrays each instance is drawn by itself
+                        Some(ref instances) => {
+                            for m in instances.transforms().iter() {
+                                self.setup_transform(ctx, cmd.model_m * *m, camera);
+                                prog.commit(gl);
+                                cmd.surface.buffer.render(gl);
+                                ctx.stats.draw_calls += 1;
+                            }
+                            instances.transforms().len()
+                        }
+                        None => {
+                            cmd.surface.buffer.render(gl);
+                            ctx.stats.draw_calls += 1;
+                            1
+                        }
+                    };
+
+                    if let Ok(data) = cmd.surface.buffer.mesh_data() {
+                        if cmd.surface.buffer.primitive() == MeshPrimitive::Triangles {
+                            let triangles = data.indices.len() / 3 * copies;
+                            ctx.stats.triangle_count += triangles as u32;
+                        }
+
+                        let addr = &*cmd.surface.buffer as *const MeshBuffer as usize;
+                        if ctx.counted_assets.insert(addr) {
+                            ctx.stats.mesh_memory += data.byte_size();
+                        }
                     }
 
                     cmd.surface.buffer.unbind(gl);
@@ -431,53 +946,167 @@ where
 
         ctx.main_light = Some(main_light);
 
-        ctx.point_lights = self.find_all_components::<Light>()
-                .into_iter()
-                .filter(|c| {
-                    let light_com = c.try_as::<Light>().unwrap();
-                    match *light_com.borrow() {
-                        Light::Point(_) => true,
-                        _ => false,
-                    }
-                })
-                .take(4)            // only take 4 points light.
-                .map(
-                    |c| c.clone()
-                )
-                .collect();
+        // prepare the environment map, the default one until the cubemap of the skybox is loaded
+        let skybox_env = self.find_skybox(!0).and_then(|c| {
+            let skybox = c.try_as::<Skybox>().unwrap();
+            let skybox = skybox.borrow();
+            if skybox.environment {
+                Some(skybox.cubemap.clone())
+            } else {
+                None
+            }
+        });
+
+        ctx.environment = match skybox_env {
+            Some(ref env) if env.prepare(&self.gl, 0).is_ok() => Some(env.clone()),
+            _ => Some(self.asset_system.new_texture("default_environment")),
+        };
+
+        ctx.light_probes = self.find_component::<LightProbeGroup>();
+
+        ctx.fog = self.find_component::<EnvironmentSettings>().and_then(|c| {
+            let settings = c.try_as::<EnvironmentSettings>().unwrap();
+            let fog = settings.borrow().fog;
+            fog
+        });
+
+        // The point and spot lights are clustered per pass and culled per object in
+        // `setup_object_lights`
+        ctx.lights = self.find_all_components::<Light>()
+            .into_iter()
+            .filter(|c| {
+                let light_com = c.try_as::<Light>().unwrap();
+                let is_local = light_com.borrow().bounding_sphere().is_some();
+                is_local
+            })
+            .collect();
     }
 
     fn gather_render_commands(
         &self,
         object: &GameObject,
+        camera: &Camera,
         cam_pos: &Vector3<f32>,
         update_bounds_only: bool,
         frustum_opt: &Option<Frustum>,
+        bvh_visible: Option<bool>,
         render_q: &mut RenderQueueList,
         included_render_queues: &Option<BTreeSet<RenderQueue>>,
+        culling_mask: u32,
+        camera_ui: Option<&str>,
         eng_stats: &mut Option<&mut EngineStats>,
     ) {
-        if !object.active {
+        if !object.active || object.layer & culling_mask == 0 {
             return;
         }
 
-        let result = object.find_component::<Mesh>();
-        if let Some((mesh, _)) = result {
+        let mesh = object.find_component::<Mesh>().map(|(mesh, _)| mesh);
+
+        // The surfaces of the sprite and the text with the layers they are sorted by
+        let mut extra_surfaces = Vec::new();
+        if let Some((sprite, _)) = object.find_component::<Sprite>() {
+            if let Some(surface) = sprite.surface() {
+                extra_surfaces.push((surface, sprite.sorting_key()));
+            }
+        }
+        if let Some((text, _)) = object.find_component::<imgui::TextMesh>() {
+            if let Some(surface) = text.surface() {
+                extra_surfaces.push((surface, 0));
+            }
+        }
+
+        if let Some((lod_group, _)) = object.find_component::<LodGroup>() {
+            let mesh_layer = mesh.as_ref().map_or(0, |mesh| mesh.layer);
+            for surface in lod_group.surfaces(&compute_model_m(&*object), camera) {
+                extra_surfaces.push((surface, mesh_layer));
+            }
+        }
+
+        if mesh.is_some() || !extra_surfaces.is_empty() {
             let m = compute_model_m(&*object);
             use math::*;
 
             // TODO: local scale only ?? should be using global scale??
             let scale = get_max_scale(&object.transform.local_scale());
 
-            for surface in mesh.surfaces.iter() {
+            // Whole mesh test of the cached world aabb, unless the bvh did it
+            let mesh_visible = match (frustum_opt, bvh_visible) {
+                (_, Some(visible)) => visible,
+                (&Some(ref frustum), None) => mesh.as_ref()
+                    .and_then(|mesh| mesh.world_bounds(&m))
+                    .map(|aabb| frustum.collide_aabb(&aabb))
+                    .unwrap_or(true),
+                (&None, None) => true,
+            };
+
+            if let Some(ref mesh) = mesh {
+                if !update_bounds_only && !mesh.morph_weights.is_empty() {
+                    for surface in mesh.surfaces.iter() {
+                        surface.buffer.set_morph_weights(&mesh.morph_weights);
+                    }
+                }
+
+                if !update_bounds_only {
+                    if let Some((skin, _)) = object.find_component::<Skin>() {
+                        let joint_matrices = skin.joint_matrices(&m);
+                        for surface in mesh.surfaces.iter() {
+                            surface.buffer.set_joint_matrices(&joint_matrices);
+                        }
+                    }
+                }
+            }
+
+            // The surfaces with the layers they are sorted by and their instances, which
+            // are drawn by the baked batches without instanced arrays
+            let mesh_layer = mesh.as_ref().map_or(0, |mesh| mesh.layer);
+            let instancing = self.caps.instancing;
+            let batches = mesh.iter()
+                .flat_map(|mesh| mesh.instances())
+                .filter(|_| !instancing)
+                .map(|instances| instances.batches())
+                .collect::<Vec<_>>();
+            let surfaces = mesh.iter()
+                .flat_map(|mesh| mesh.surfaces.iter())
+                .map(|surface| (surface, mesh_layer, None))
+                .chain(
+                    mesh.iter()
+                        .flat_map(|mesh| mesh.instances())
+                        .filter(|_| instancing)
+                        .map(|instances| (instances.surface(), mesh_layer, Some(instances))),
+                )
+                .chain(
+                    batches
+                        .iter()
+                        .flat_map(|batches| batches.iter())
+                        .map(|surface| (surface, mesh_layer, None)),
+                )
+                .chain(
+                    extra_surfaces
+                        .iter()
+                        .map(|&(ref surface, layer)| (surface, layer, None)),
+                );
+
+            for (surface, layer, instances) in surfaces {
+                let base_queue = surface.material.render_queue.base();
+
+                // The instances are culled and sorted by the bounds of all of them
+                let local_bounds = match instances {
+                    Some(instances) => instances.bounds(),
+                    None => surface.buffer.bounds(),
+                };
+
                 if let &Some(ref included) = included_render_queues {
-                    if included.get(&surface.material.render_queue).is_none() {
+                    if included.get(&base_queue).is_none() {
                         continue;
                     }
                 }
 
+                if base_queue == RenderQueue::UI && self.ui_name(object) != camera_ui {
+                    continue;
+                }
+
                 if let &mut Some(ref mut stats) = eng_stats {
-                    match surface.material.render_queue {
+                    match base_queue {
                         RenderQueue::Transparent => stats.total_transparent_count += 1,
                         RenderQueue::Opaque => stats.total_opaque_count += 1,
                         _ => (),
@@ -486,10 +1115,10 @@ where
 
                 // TODO: should use a material flag to skip
                 if let &Some(ref frustum) = frustum_opt {
-                    match surface.material.render_queue {
+                    match base_queue {
                         RenderQueue::Skybox | RenderQueue::UI => (),
                         _ => {
-                            let bounds = surface.buffer.bounds();
+                            let bounds = local_bounds;
                             if bounds.is_none() {
                                 continue;
                             }
@@ -500,7 +1129,10 @@ where
                             let scaled_r = r * scale;
                             let p = m.transform_point(Point3::from_vec(center));
 
-                            if !frustum.collide_sphere(&p.to_vec(), scaled_r) {
+                            if !mesh_visible || !frustum.collide_sphere(&p.to_vec(), scaled_r) {
+                                if let &mut Some(ref mut stats) = eng_stats {
+                                    stats.culled_count += 1;
+                                }
                                 continue;
                             }
 
@@ -516,8 +1148,7 @@ where
                         }
                     }
                 } else {
-                    let bounds = surface.buffer.bounds();
-                    if let Some(bounds) = bounds {
+                    if let Some(bounds) = local_bounds {
                         let (center, r) = bounds.local_aabb().sphere();
                         let p = m.transform_point(Point3::from_vec(center));
 
@@ -534,23 +1165,52 @@ where
                 }
 
                 if !update_bounds_only {
-                    let q = render_q
-                        .queues
-                        .get_mut(&surface.material.render_queue)
-                        .unwrap();
+                    let q = render_q.queue_mut(surface.material.render_queue);
+
+                    let bounds = local_bounds.map(|bounds| {
+                        let (center, r) = bounds.local_aabb().sphere();
+                        let p = m.transform_point(Point3::from_vec(center));
+                        (p.to_vec(), r * scale)
+                    });
 
-                    let cam_dist = (cam_pos - object.transform.global().disp).magnitude();
+                    // The surfaces are sorted by the distance to their centers, which
+                    // could be far from the origin of the object
+                    let cam_dist = match bounds {
+                        Some((center, _)) => (cam_pos - center).magnitude(),
+                        None => (cam_pos - object.transform.global().disp).magnitude(),
+                    };
 
                     q.commands.push(RenderCommand {
                         surface: surface.clone(),
                         model_m: m,
                         cam_distance: cam_dist,
-                    })
+                        layer,
+                        bounds,
+                        instances: instances.cloned(),
+                    });
+
+                    if let &mut Some(ref mut stats) = eng_stats {
+                        stats.submitted_count += 1;
+                    }
                 }
             }
         }
     }
 
+    /// Name of the `imgui::Ui` which the object belongs to, None for the primary one
+    /// and the objects out of the uis
+    fn ui_name(&self, object: &GameObject) -> Option<&str> {
+        if self.uis.is_empty() {
+            return None;
+        }
+
+        let tree = object.tree();
+        self.uis
+            .iter()
+            .find(|&(_, t)| t.upgrade().map(|t| Rc::ptr_eq(&t, &tree)).unwrap_or(false))
+            .map(|(name, _)| name.as_str())
+    }
+
     pub fn get_bounds(&self, camera: &Camera) -> Option<Aabb> {
         let render_q = self.gather_all_render_commands(camera, true, None);
 
@@ -572,95 +1232,567 @@ where
             None
         };
 
+        // The static objects are culled by the bvh, the others one by one
+        let static_visible = match frustum {
+            Some(ref frustum) => {
+                self.update_static_bvh();
+                self.static_bvh
+                    .borrow()
+                    .as_ref()
+                    .map(|statics| statics.cull(frustum))
+                    .unwrap_or_default()
+            }
+            None => HashMap::new(),
+        };
+
+        let occlusion = if camera.occlusion_culling && !update_bounds_only {
+            Some(self.draw_occluders(camera))
+        } else {
+            None
+        };
+
         for obj in objects.iter() {
             obj.upgrade().map(|obj| {
+                let bvh_visible = static_visible
+                    .get(&(&*obj as *const RefCell<GameObject>))
+                    .cloned();
+
                 if let Ok(object) = obj.try_borrow() {
+                    if let Some(ref occlusion) = occlusion {
+                        if self.is_occluded(&object, occlusion) {
+                            if let Some(ref mut stats) = eng_stats {
+                                stats.occluded_count += 1;
+                            }
+                            return;
+                        }
+                    }
+
                     self.gather_render_commands(
                         &object,
+                        camera,
                         &camera.eye(),
                         update_bounds_only,
                         &frustum,
+                        bvh_visible,
                         &mut render_q,
                         &camera.included_render_queues,
+                        camera.culling_mask,
+                        camera.ui.as_ref().map(|s| s.as_str()),
                         &mut eng_stats,
                     )
                 }
             });
         }
 
+        if !update_bounds_only {
+            self.gather_skybox_command(
+                &mut render_q,
+                &camera.included_render_queues,
+                camera.culling_mask,
+            );
+        }
+
         render_q
     }
 
+    /// Bind the ambient of the light probes at the center of the object of `cmd`,
+    /// or disable it for the programs using the probes
+    fn setup_light_probe(&self, ctx: &mut EngineContext, cmd: &RenderCommand) {
+        let prog = ctx.prog.upgrade().unwrap();
+        if !prog.has_uniform(&self.gl, "uLightProbeEnabled") {
+            return;
+        }
+
+        let center = match cmd.bounds {
+            Some((center, _)) => center,
+            None => cmd.model_m.w.truncate(),
+        };
+
+        let sh = ctx.light_probes.as_ref().and_then(|c| {
+            let group = c.try_as::<LightProbeGroup>().unwrap();
+            let sh = group.borrow().sample(&center);
+            sh
+        });
+
+        prog.set("uLightProbeEnabled", sh.is_some());
+        if let Some(sh) = sh {
+            for (i, c) in sh.irradiance_coefficients().iter().enumerate() {
+                prog.set(format!("uLightProbeSH[{}]", i), *c);
+            }
+        }
+    }
+
+    /// Rasterize the occluders in the layers of the camera, see `GameObject::occluder`
+    fn draw_occluders(&self, camera: &Camera) -> OcclusionBuffer {
+        let pv = camera.perspective(self.screen_size) * camera.v;
+        let mut occlusion = OcclusionBuffer::new(self.screen_size, pv);
+
+        for obj in self.objects.iter() {
+            let obj = match obj.upgrade() {
+                Some(obj) => obj,
+                None => continue,
+            };
+            let object = match obj.try_borrow() {
+                Ok(object) => object,
+                Err(_) => continue,
+            };
+            if !object.active || !object.occluder || object.layer & camera.culling_mask == 0 {
+                continue;
+            }
+
+            let (mesh, _) = match object.find_component::<Mesh>() {
+                Some(mesh) => mesh,
+                None => continue,
+            };
+
+            let m = compute_model_m(&object);
+            for surface in mesh.surfaces.iter() {
+                if surface.material.render_queue.base() != RenderQueue::Opaque
+                    || surface.buffer.primitive() != MeshPrimitive::Triangles
+                {
+                    continue;
+                }
+
+                if let Ok(data) = surface.buffer.mesh_data() {
+                    occlusion.draw_mesh(&m, &data.vertices, &data.indices);
+                }
+            }
+        }
+
+        occlusion
+    }
+
+    /// Whether the mesh of an occludee is hidden by the occluders, the objects without
+    /// bounds and the occluders themselves are never hidden
+    fn is_occluded(&self, object: &GameObject, occlusion: &OcclusionBuffer) -> bool {
+        if !object.occludee || object.occluder {
+            return false;
+        }
+
+        let (mesh, _) = match object.find_component::<Mesh>() {
+            Some(mesh) => mesh,
+            None => return false,
+        };
+
+        match mesh.world_bounds(&compute_model_m(object)) {
+            Some(aabb) => !occlusion.is_visible(&aabb),
+            None => false,
+        }
+    }
+
+    /// Rebuild the bvh of the static objects when they or their bounds changed
+    fn update_static_bvh(&self) {
+        let statics: Vec<_> = self.objects
+            .iter()
+            .filter_map(|obj| {
+                let obj = obj.upgrade()?;
+                let aabb = {
+                    let object = obj.try_borrow().ok()?;
+                    if !object.is_static {
+                        return None;
+                    }
+
+                    let (mesh, _) = object.find_component::<Mesh>()?;
+                    let aabb = mesh.world_bounds(&compute_model_m(&object))?;
+                    aabb
+                };
+
+                Some((&*obj as *const RefCell<GameObject>, aabb))
+            })
+            .collect();
+
+        let mut static_bvh = self.static_bvh.borrow_mut();
+        let unchanged = match *static_bvh {
+            Some(ref current) => current.objects == statics,
+            None => statics.is_empty(),
+        };
+
+        if !unchanged {
+            *static_bvh = if statics.is_empty() {
+                None
+            } else {
+                Some(StaticBvh::new(statics))
+            };
+        }
+    }
+
+    /// The first active `Skybox` of the scene in the layers of `culling_mask`
+    fn find_skybox(&self, culling_mask: u32) -> Option<Arc<Component>> {
+        let mut r = None;
+        self.map_component::<Skybox, _>(|obj, c| {
+            let visible = obj.try_borrow()
+                .map(|o| o.active && o.layer & culling_mask != 0)
+                .unwrap_or(false);
+            if visible {
+                r = Some(c);
+            }
+            r.is_none()
+        });
+
+        r
+    }
+
+    fn gather_skybox_command(
+        &self,
+        render_q: &mut RenderQueueList,
+        included_render_queues: &Option<BTreeSet<RenderQueue>>,
+        culling_mask: u32,
+    ) {
+        if let &Some(ref included) = included_render_queues {
+            if included.get(&RenderQueue::Skybox).is_none() {
+                return;
+            }
+        }
+
+        if let Some(skybox) = self.find_skybox(culling_mask) {
+            let skybox = skybox.try_as::<Skybox>().unwrap();
+            let q = render_q.queues.get_mut(&RenderQueue::Skybox).unwrap();
+
+            q.commands.push(RenderCommand {
+                surface: skybox.borrow().surface().clone(),
+                model_m: Matrix4::identity(),
+                cam_distance: 0.0,
+                layer: 0,
+                bounds: None,
+                instances: None,
+            });
+        }
+    }
+
     #[cfg_attr(feature = "flame_it", flame)]
     pub fn render_pass_with_material(
         &mut self,
         camera: &Camera,
         material: Option<&Rc<Material>>,
         clear_option: ClearOption,
+    ) -> EngineStats {
+        self.render_camera(camera, material, clear_option, true)
+    }
+
+    /// Render a pass of `camera`, the primary ui is drawn only if `primary_ui` is set
+    fn render_camera(
+        &mut self,
+        camera: &Camera,
+        material: Option<&Rc<Material>>,
+        clear_option: ClearOption,
+        primary_ui: bool,
     ) -> EngineStats {
         let mut ctx: EngineContext = EngineContext::new();
 
-        if let Some(ref rt) = camera.render_texture {
-            rt.bind_frame_buffer(&self.gl);
+        #[cfg(feature = "timer_query")]
+        {
+            if let Some(ref mut timer) = self.gpu_timer {
+                timer.begin_pass();
+            }
         }
 
-        match camera.rect {
-            Some(((x, y), (w, h))) => {
-                self.gl.viewport(x, y, w, h);
-            }
-            None => {
-                self.gl
-                    .viewport(0, 0, self.screen_size.0, self.screen_size.1);
+        // The scene is rendered offscreen for the post effects
+        let post_effects = camera.applied_post_effects();
+        let hdr = camera.hdr && self.caps.float_render_targets;
+        let post_key = match material {
+            None if !post_effects.is_empty() => Some((self.viewport_size(camera), hdr)),
+            _ => None,
+        };
+
+        match post_key {
+            Some(key) => {
+                if !self.post_processes.contains_key(&key) {
+                    let post = PostProcess::new(&*self.asset_system, key.0, key.1);
+                    self.post_processes.insert(key, post);
+                }
+
+                self.bind_post_target(&self.post_processes[&key].scene);
             }
+            None => self.bind_camera_target(camera),
         }
 
-        self.clear(clear_option);
-
         self.prepare_ctx(&mut ctx);
 
+        // The lights outside of the frustum reach no object drawn in the pass
+        if camera.enable_frustum_culling {
+            let frustum = camera.calc_frustum(self.screen_size);
+            ctx.lights.retain(|c| {
+                let light = c.try_as::<Light>().unwrap();
+                let sphere = light.borrow().bounding_sphere();
+                sphere.map_or(true, |(p, range)| frustum.collide_sphere(&p, range))
+            });
+        }
+
+        // The lights are all local, the clusters keep their indices in `ctx.lights`
+        let spheres: Vec<(Vector3<f32>, f32)> = ctx.lights
+            .iter()
+            .map(|c| {
+                let light = c.try_as::<Light>().unwrap();
+                let sphere = light.borrow().bounding_sphere().unwrap();
+                sphere
+            })
+            .collect();
+        ctx.light_clusters = Some(LightClusters::for_camera(camera, self.screen_size, &spheres));
+        // The slots bound by the other passes are not known, they are all cleared again
+        ctx.light_slots.clear();
+
+        if post_key.is_some() || self.covers_target(camera) {
+            self.clear(clear_option);
+        } else {
+            self.clear_viewport(&mut ctx, camera, &clear_option);
+        }
+
         // gather commands
         let mut render_q = self.gather_all_render_commands(&camera, false, Some(&mut ctx.stats));
 
-        // Sort the opaque queue
-        render_q
-            .queues
-            .get_mut(&RenderQueue::Opaque)
-            .unwrap()
-            .sort_by_cam_distance_reverse()
-            .sort_by_material();
+        if !primary_ui && camera.ui.is_none() {
+            for (queue, q) in render_q.queues.iter_mut() {
+                if queue.base() == RenderQueue::UI {
+                    q.commands.clear();
+                }
+            }
+        }
 
-        // Sort the transparent queue
-        render_q
+        // The sprite and ui queues are batched, unless all are drawn in a single material
+        let has_sprites = render_q
             .queues
-            .get_mut(&RenderQueue::Transparent)
-            .unwrap()
-            .sort_by_cam_distance();
+            .iter()
+            .any(|(queue, q)| queue.base() == RenderQueue::Sprite && !q.commands.is_empty());
+
+        let sprite_batcher = match material {
+            None if has_sprites => {
+                let batcher = self
+                    .sprite_batchers
+                    .entry(camera.order)
+                    .or_insert_with(UiBatcher::new);
+                batcher.begin();
+                Some(&*batcher)
+            }
+            _ => None,
+        };
+
+        let ui_batcher = match material {
+            None => {
+                let batcher = self
+                    .ui_batchers
+                    .entry(camera.ui.clone())
+                    .or_insert_with(UiBatcher::new);
+                batcher.begin();
+                Some(&*batcher)
+            }
+            _ => None,
+        };
+
+        for (queue, q) in render_q.queues.iter_mut() {
+            match queue.base() {
+                RenderQueue::Opaque => {
+                    q.sort_by_cam_distance_reverse().sort_by_material();
+                }
+                // Back to front
+                RenderQueue::Transparent => {
+                    q.sort_by_cam_distance();
+                }
+                // By layer then from back to front
+                RenderQueue::Sprite => {
+                    q.sort_by_cam_distance().sort_by_layer();
+                    if let Some(batcher) = sprite_batcher {
+                        q.batch(batcher);
+                    }
+                }
+                RenderQueue::UI => {
+                    q.sort_by_layer();
+                    if let Some(batcher) = ui_batcher {
+                        q.batch(batcher);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        // The debug lines are drawn after the opaque queue
+        let debug_included = camera
+            .included_render_queues
+            .as_ref()
+            .map_or(true, |q| q.contains(&RenderQueue::Opaque));
+
+        match self.debug_renderer {
+            Some(ref debug) if material.is_none() && debug_included => {
+                let q = render_q.queue_mut(RenderQueue::Opaque);
+                for surface in debug.surfaces() {
+                    q.commands.push(RenderCommand {
+                        surface,
+                        model_m: Matrix4::identity(),
+                        cam_distance: 0.0,
+                        layer: 0,
+                        bounds: None,
+                        instances: None,
+                    });
+                }
+            }
+            _ => (),
+        }
 
         ctx.stats.surfaces_count = render_q.surface_count() as u32;
-        ctx.stats.transparent_count = render_q
-            .queues
-            .get(&RenderQueue::Transparent)
-            .unwrap()
-            .commands
-            .len() as u32;
-        ctx.stats.opaque_count = render_q
-            .queues
-            .get(&RenderQueue::Opaque)
-            .unwrap()
-            .commands
-            .len() as u32;
+        ctx.stats.game_object_count = self.objects.len() as u32;
+        ctx.stats.transparent_count = render_q.command_count(RenderQueue::Transparent) as u32;
+        ctx.stats.opaque_count = render_q.command_count(RenderQueue::Opaque) as u32;
+
+        for (queue, q) in render_q.queues.iter() {
+            // The UI is drawn over the post effects
+            if post_key.is_some() && queue.base() == RenderQueue::UI {
+                continue;
+            }
 
-        for (_, q) in render_q.queues.iter() {
             self.render_commands(&mut ctx, &q, camera, material);
         }
 
+        if let Some(key) = post_key {
+            self.render_post_effects(&mut ctx, camera, &post_effects, key);
+
+            for (queue, q) in render_q.queues.iter() {
+                if queue.base() == RenderQueue::UI {
+                    self.render_commands(&mut ctx, q, camera, material);
+                }
+            }
+        }
+
         if let Some(ref rt) = camera.render_texture {
             rt.unbind_frame_buffer(&self.gl);
         }
 
+        ctx.stats.state_changes = ctx.states.changes;
+        ctx.stats.program_binds = ctx.switch_prog;
+        ctx.stats.texture_binds = ctx.switch_tex;
+
+        #[cfg(feature = "timer_query")]
+        let gpu_time = self.gpu_timer.as_mut().and_then(|timer| {
+            timer.end_pass();
+            timer.time(self.render_stats.passes.len())
+        });
+        #[cfg(not(feature = "timer_query"))]
+        let gpu_time = None;
+
+        self.render_stats.add_pass(PassStats {
+            stats: ctx.stats,
+            with_material: material.is_some(),
+            gpu_time,
+        });
+
         ctx.stats
     }
 
+    fn viewport_size(&self, camera: &Camera) -> (u32, u32) {
+        camera.pixel_rect(self.screen_size).1
+    }
+
+    /// Whether the viewport of `camera` is its whole target
+    fn covers_target(&self, camera: &Camera) -> bool {
+        let (pos, size) = camera.pixel_rect(self.screen_size);
+
+        pos == (0, 0) && size == camera.target_size(self.screen_size)
+    }
+
+    /// Bind the render texture of `camera`, if any, and its viewport
+    fn bind_camera_target(&self, camera: &Camera) {
+        if let Some(ref rt) = camera.render_texture {
+            rt.bind_frame_buffer(&self.gl);
+        }
+
+        let (pos, size) = camera.pixel_rect(self.screen_size);
+        self.backend().begin_pass(pos, size, None);
+    }
+
+    /// Clear the viewport of `camera` only, by drawing a quad on the far plane,
+    /// because the whole target is cleared by `clear`
+    fn clear_viewport(&self, ctx: &mut EngineContext, camera: &Camera, option: &ClearOption) {
+        if !option.clear_color && !option.clear_depth {
+            return;
+        }
+
+        let color = option.color.unwrap_or((0.0, 0.0, 0.0, 1.0));
+        let alpha = if option.clear_color { color.3 } else { 0.0 };
+
+        let mut material = Material::new(self.asset_system.new_program("default_clear"));
+        material.set("uColor", Vector4::new(color.0, color.1, color.2, alpha));
+        material.states.depth_write = Some(option.clear_depth);
+        material.states.alpha_blending = Some(!option.clear_color);
+
+        let quad = self.asset_system.new_mesh_buffer("screen_quad");
+        self.render_fullscreen(ctx, camera, &quad, &Rc::new(material));
+    }
+
+    fn bind_post_target(&self, rt: &RenderTexture) {
+        rt.bind_frame_buffer(&self.gl);
+
+        let (w, h) = rt.image_size().unwrap();
+        self.gl.viewport(0, 0, w, h);
+    }
+
+    /// Apply `effects` of `camera` to the scene rendered in the targets of `key`,
+    /// the last effect is drawn to the target of the camera
+    fn render_post_effects(
+        &self,
+        ctx: &mut EngineContext,
+        camera: &Camera,
+        effects: &[PostEffect],
+        key: ((u32, u32), bool),
+    ) {
+        let post = &self.post_processes[&key];
+        let last = effects.len() - 1;
+        let mut src = post.scene.as_texture();
+
+        for (i, effect) in effects.iter().enumerate() {
+            for pass in post.passes(effect, &src) {
+                match pass.target {
+                    Some(rt) => self.bind_post_target(rt),
+                    None if i < last => self.bind_post_target(post.target(i)),
+                    None => {
+                        if camera.render_texture.is_none() {
+                            self.gl.unbind_framebuffer(Buffers::Framebuffer);
+                        }
+                        self.bind_camera_target(camera);
+
+                        // Clear the depth for the UI, the depth mask must be on
+                        ctx.states.apply_defaults();
+                        ctx.states.commit(&self.gl);
+                        self.gl.clear(BufferBit::Depth);
+                    }
+                }
+
+                // Binding a frame buffer changes the texture of the unit 0
+                ctx.reset_texture_cache();
+                self.render_fullscreen(ctx, camera, &post.quad, &pass.material);
+            }
+
+            src = post.target(i).as_texture();
+        }
+    }
+
+    /// Draw `material` on the whole viewport
+    fn render_fullscreen(
+        &self,
+        ctx: &mut EngineContext,
+        camera: &Camera,
+        quad: &Rc<MeshBuffer>,
+        material: &Rc<Material>,
+    ) {
+        let mut q = RenderQueueState::default();
+        q.states.cull = Some(CullMode::Off);
+        q.states.depth_test = Some(DepthTest::Always);
+        q.states.depth_write = Some(false);
+        q.states.alpha_blending = Some(false);
+
+        q.commands.push(RenderCommand {
+            surface: Rc::new(MeshSurface {
+                buffer: quad.clone(),
+                material: material.clone(),
+                params: None,
+            }),
+            model_m: Matrix4::identity(),
+            cam_distance: 0.0,
+            layer: 0,
+            bounds: None,
+            instances: None,
+        });
+
+        self.render_commands(ctx, &q, camera, None);
+    }
+
     #[cfg_attr(feature = "flame_it", flame)]
     pub fn render_pass(&mut self, camera: &Camera, clear_option: ClearOption) -> EngineStats {
         self.render_pass_with_material(camera, None, clear_option)
@@ -680,21 +1812,73 @@ where
         None
     }
 
+    /// The main camera and the active cameras with an order, sorted by their orders
+    fn ordered_cameras(&self, main_camera: &Option<Arc<Component>>) -> Vec<Arc<Component>> {
+        let is_main = |c: &Arc<Component>| {
+            main_camera
+                .as_ref()
+                .map_or(false, |m| Arc::ptr_eq(m, c))
+        };
+        let mut cameras = Vec::new();
+
+        if let Some(ref c) = *main_camera {
+            let order = c.try_as::<Camera>().unwrap().borrow().order.unwrap_or(0);
+            cameras.push((order, c.clone()));
+        }
+
+        self.map_component::<Camera, _>(|obj, c| {
+            let active = obj.try_borrow().map(|o| o.active).unwrap_or(false);
+            let order = c.try_as::<Camera>().unwrap().borrow().order;
+
+            match order {
+                Some(order) if active && !is_main(&c) => cameras.push((order, c)),
+                _ => (),
+            }
+            true
+        });
+
+        // Stable sort, the main camera is the first of its order
+        cameras.sort_by_key(|&(order, _)| order);
+        cameras.into_iter().map(|(_, c)| c).collect()
+    }
+
     #[cfg_attr(feature = "flame_it", flame)]
     pub fn render(&mut self, clear_option: ClearOption) {
         imgui::pre_render(self);
 
-        if let Some(ref camera) = self.main_camera() {
-            self.stats =
-                self.render_pass(&camera.try_as::<Camera>().unwrap().borrow(), clear_option);
-        } else {
+        if self.debug_renderer.is_none() {
+            self.debug_renderer = Some(DebugRenderer::new(&*self.asset_system));
+        }
+        self.debug_renderer.as_ref().unwrap().update();
+
+        let main_camera = self.main_camera();
+        let cameras = self.ordered_cameras(&main_camera);
+
+        if cameras.is_empty() {
             // We dont have a main camera here, just clean the screen.
             self.clear(clear_option);
+            return;
+        }
+
+        for com in cameras.iter() {
+            let is_main = main_camera
+                .as_ref()
+                .map_or(false, |c| Arc::ptr_eq(c, com));
+
+            let camera = com.try_as::<Camera>().unwrap().borrow();
+            let option = camera_clear_option(&camera, &clear_option);
+            let stats = self.render_camera(&camera, None, option, is_main);
+
+            // The primary ui and the stats belong to the main camera
+            if is_main {
+                self.stats = stats;
+            }
         }
     }
 
     pub fn new(webgl_ctx: WebGLContext, size: (u32, u32), hidpi: f32) -> Engine<A> {
         let gl = WebGLRenderingContext::new(webgl_ctx);
+        let caps = RenderCaps::detect(&gl);
 
         /*=========Drawing the triangle===========*/
 
@@ -717,29 +1901,72 @@ where
 
         let gui_tree = SceneTree::new();
 
+        // Before the asset system, which loads the textures and the shaders
+        set_srgb_textures(caps.srgb);
+        set_vertex_array_objects(caps.vertex_array_objects);
+
+        let asset_system = A::new();
+        asset_system.set_compressed_formats(&caps.compressed_formats());
+
+        #[cfg(feature = "timer_query")]
+        let gpu_timer = if caps.timer_query {
+            Some(GpuTimer::new(&gl))
+        } else {
+            None
+        };
+
         Engine {
             gl: gl,
             objects: vec![],
             program_cache: RefCell::new(HashMap::new()),
-            asset_system: Box::new(A::new()),
+            asset_system: Box::new(asset_system),
             gui_context: Rc::new(RefCell::new(imgui::Context::new(gui_tree))),
             screen_size: size,
             hidpi: hidpi,
             current_camera: RefCell::new(None),
             stats: Default::default(),
+            render_stats: Default::default(),
+            #[cfg(feature = "timer_query")]
+            gpu_timer,
+            caps,
             arena: Rc::new(ComponentArena::new()),
+            ui_batchers: HashMap::new(),
+            sprite_batchers: HashMap::new(),
+            uis: HashMap::new(),
+            post_processes: HashMap::new(),
+            static_bvh: RefCell::new(None),
+            debug_renderer: None,
         }
     }
 
     pub fn begin(&mut self) {
         imgui::begin();
 
+        self.render_stats.reset();
+        #[cfg(feature = "timer_query")]
+        {
+            if let Some(ref mut timer) = self.gpu_timer {
+                timer.begin_frame();
+            }
+        }
+
         self.asset_system_mut().step();
     }
 
     pub fn end(&mut self) {
         // drop all gameobjects if there are no other references
         self.objects.retain(|obj| obj.upgrade().is_some());
+        self.uis.retain(|_, tree| tree.upgrade().is_some());
+
+        let uis = &self.uis;
+        self.ui_batchers.retain(|ui, _| {
+            ui.as_ref()
+                .map(|name| uis.contains_key(name))
+                .unwrap_or(true)
+        });
+
+        // The debug lines last a single frame
+        debug::clear();
 
         // drop camera cache if it is only by holded by ourself
         let mut cam_mut = self.current_camera.borrow_mut();
@@ -786,6 +2013,10 @@ impl<A: AssetSystem> IEngine for Engine<A> {
         self.gui_context.clone()
     }
 
+    fn register_ui(&mut self, name: &str, tree: &Rc<SceneTree>) {
+        self.uis.insert(name.to_string(), Rc::downgrade(tree));
+    }
+
     fn asset_system<'a>(&'a self) -> &'a AssetSystem {
         &*self.asset_system
     }
@@ -801,4 +2032,16 @@ impl<A: AssetSystem> IEngine for Engine<A> {
     fn hidpi_factor(&self) -> f32 {
         self.hidpi
     }
+
+    fn stats(&self) -> EngineStats {
+        self.stats
+    }
+
+    fn render_caps(&self) -> RenderCaps {
+        self.caps
+    }
+
+    fn render_stats(&self) -> RenderStats {
+        self.render_stats.clone()
+    }
 }
@@ -0,0 +1,61 @@
+use super::Metric;
+
+/// Screen edge or corner which the following widgets are glued to, see `imgui::anchor`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+/// Insets in pixel of the screen area which is not covered by notches or rounded corners
+#[derive(Default, Debug, PartialEq, Clone, Copy)]
+pub struct SafeArea {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+impl Anchor {
+    /// The anchor point in ratio of the screen size, it is also the pivot of the widgets
+    pub fn ratio(&self) -> (f32, f32) {
+        match self {
+            &Anchor::TopLeft => (0.0, 0.0),
+            &Anchor::Top => (0.5, 0.0),
+            &Anchor::TopRight => (1.0, 0.0),
+            &Anchor::Left => (0.0, 0.5),
+            &Anchor::Center => (0.5, 0.5),
+            &Anchor::Right => (1.0, 0.5),
+            &Anchor::BottomLeft => (0.0, 1.0),
+            &Anchor::Bottom => (0.5, 1.0),
+            &Anchor::BottomRight => (1.0, 1.0),
+        }
+    }
+
+    /// The screen position of a widget at `pos` from the anchor point.
+    /// `pos` points inwards, e.g. Pixel(8, 8) is 8 pixels left and above the BottomRight corner
+    pub fn place(&self, pos: Metric, safe_area: &SafeArea) -> Metric {
+        let (ax, ay) = self.ratio();
+
+        // Edge anchors are moved into the safe area, center ones are not
+        let (ix, sx) = match ax {
+            x if x == 0.0 => (safe_area.left, 1.0),
+            x if x == 1.0 => (-safe_area.right, -1.0),
+            _ => (0.0, 1.0),
+        };
+        let (iy, sy) = match ay {
+            y if y == 0.0 => (safe_area.top, 1.0),
+            y if y == 1.0 => (-safe_area.bottom, -1.0),
+            _ => (0.0, 1.0),
+        };
+
+        Metric::Mixed((ax, ay), (ix, iy)) + pos.scale(sx, sy)
+    }
+}
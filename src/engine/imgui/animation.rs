@@ -0,0 +1,135 @@
+use super::image;
+use super::Metric;
+
+use math::*;
+
+/// The smallest scale of a scaling transition, a zero scale can not be inverted
+const MIN_SCALE: f32 = 0.001;
+
+/// Transition of `imgui::animate`, the durations are in seconds
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Transition {
+    /// Fade in from transparent
+    FadeIn(f32),
+    /// Fade out to transparent
+    FadeOut(f32),
+    /// Slide in from the offset (dx, dy) in pixel
+    SlideIn((f32, f32), f32),
+    /// Slide out to the offset (dx, dy) in pixel
+    SlideOut((f32, f32), f32),
+    /// Scale up from nothing around the center
+    ScaleIn(f32),
+    /// Scale down to nothing around the center
+    ScaleOut(f32),
+}
+
+impl Transition {
+    pub fn duration(&self) -> f32 {
+        match self {
+            &Transition::FadeIn(d)
+            | &Transition::FadeOut(d)
+            | &Transition::SlideIn(_, d)
+            | &Transition::SlideOut(_, d)
+            | &Transition::ScaleIn(d)
+            | &Transition::ScaleOut(d) => d,
+        }
+    }
+
+    /// The tween `elapsed` seconds after the transition is started
+    pub fn tween(&self, elapsed: f32) -> Tween {
+        let d = self.duration();
+        let t = if d > 0.0 {
+            (elapsed / d).max(0.0).min(1.0)
+        } else {
+            1.0
+        };
+
+        // Smoothstep, such that the motion starts and stops gently
+        let t = t * t * (3.0 - 2.0 * t);
+
+        match self {
+            &Transition::FadeIn(_) => Tween {
+                opacity: t,
+                ..Tween::default()
+            },
+            &Transition::FadeOut(_) => Tween {
+                opacity: 1.0 - t,
+                ..Tween::default()
+            },
+            &Transition::SlideIn((dx, dy), _) => Tween {
+                offset: (dx * (1.0 - t), dy * (1.0 - t)),
+                ..Tween::default()
+            },
+            &Transition::SlideOut((dx, dy), _) => Tween {
+                offset: (dx * t, dy * t),
+                ..Tween::default()
+            },
+            &Transition::ScaleIn(_) => Tween {
+                scale: t.max(MIN_SCALE),
+                ..Tween::default()
+            },
+            &Transition::ScaleOut(_) => Tween {
+                scale: (1.0 - t).max(MIN_SCALE),
+                ..Tween::default()
+            },
+        }
+    }
+}
+
+/// The transition of a widget, set by `imgui::animate`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Animation {
+    /// Hash of the id of the animation
+    pub key: u64,
+    pub transition: Transition,
+}
+
+/// Interpolated opacity, position and scale of a widget
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Tween {
+    pub opacity: f32,
+    /// Offset in pixel
+    pub offset: (f32, f32),
+    /// Scale around the center of the widget
+    pub scale: f32,
+}
+
+impl Default for Tween {
+    fn default() -> Tween {
+        Tween {
+            opacity: 1.0,
+            offset: (0.0, 0.0),
+            scale: 1.0,
+        }
+    }
+}
+
+impl Tween {
+    /// The offset in ndc, y is up
+    fn ndc_offset(&self, ssize: (u32, u32), hidpi: f32) -> (f32, f32) {
+        let (x, y) = self.offset;
+        let (ox, oy) = image::compute_size_to_ndc(&Metric::Pixel(x, y), &ssize, hidpi);
+        (ox, -oy)
+    }
+
+    /// Displacement in ndc of a game object scaled around its origin,
+    /// `center` is the center of the widget relative to the origin in ndc
+    pub fn displacement(&self, center: (f32, f32), ssize: (u32, u32), hidpi: f32) -> Vector3f {
+        let (ox, oy) = self.ndc_offset(ssize, hidpi);
+
+        Vector3::new(
+            ox + center.0 * (1.0 - self.scale),
+            oy + center.1 * (1.0 - self.scale),
+            0.0,
+        )
+    }
+
+    /// The matrix which moves and scales ndc vertices around the ndc point `center`
+    pub fn matrix(&self, center: (f32, f32), ssize: (u32, u32), hidpi: f32) -> Matrix4<f32> {
+        let (ox, oy) = self.ndc_offset(ssize, hidpi);
+
+        Matrix4::from_translation(Vector3::new(center.0 + ox, center.1 + oy, 0.0))
+            * Matrix4::from_nonuniform_scale(self.scale, self.scale, 1.0)
+            * Matrix4::from_translation(Vector3::new(-center.0, -center.1, 0.0))
+    }
+}
@@ -0,0 +1,296 @@
+/// Bidirectional class of a char, simplified from the Unicode bidi algorithm
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Class {
+    Ltr,
+    Rtl,
+    Number,
+    Neutral,
+    /// Combining marks, they take the class of the char before them
+    Mark,
+}
+
+/// Joining type of an Arabic char
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Joining {
+    /// Joins both sides, e.g. beh
+    Dual,
+    /// Joins the char before only, e.g. alef
+    Right,
+    NonJoining,
+    /// Combining marks, they are skipped
+    Transparent,
+}
+
+/// Number of the presentation forms (isolated, final, initial, medial) of the Arabic letters
+/// U+0621 to U+064A, which are laid out in this order from U+FE80
+const ARABIC_FORM_COUNTS: [u32; 42] = [
+    1, 2, 2, 2, 2, 4, 2, 4, 2, 4, 4, 4, 4, 4, 2, 2, 2, 2, 4, 4, 4, 4, 4, 4, 4, 4, 0, 0, 0, 0, 0, 0,
+    4, 4, 4, 4, 4, 4, 4, 2, 2, 4,
+];
+
+const TATWEEL: char = '\u{0640}';
+
+fn is_mark(c: char) -> bool {
+    match c as u32 {
+        0x0591..=0x05BD | 0x05BF..=0x05C7 | 0x064B..=0x065F | 0x0670 => true,
+        _ => false,
+    }
+}
+
+/// Whether `c` is a right-to-left char, i.e. in the Hebrew, Arabic and the scripts
+/// between them or their presentation forms
+fn is_rtl_char(c: char) -> bool {
+    match c as u32 {
+        0x0590..=0x08FF | 0xFB1D..=0xFDFF | 0xFE70..=0xFEFF => true,
+        _ => false,
+    }
+}
+
+fn class(c: char) -> Class {
+    if is_mark(c) {
+        Class::Mark
+    } else if c.is_numeric() {
+        Class::Number
+    } else if is_rtl_char(c) {
+        Class::Rtl
+    } else if c.is_alphabetic() {
+        Class::Ltr
+    } else {
+        Class::Neutral
+    }
+}
+
+/// Whether the first strong char of `s` is right-to-left
+pub fn is_rtl_text(s: &str) -> bool {
+    s.chars()
+        .map(class)
+        .find(|&c| c == Class::Ltr || c == Class::Rtl)
+        .map(|c| c == Class::Rtl)
+        .unwrap_or(false)
+}
+
+/// The mirrored glyph of a bracket in right-to-left text
+fn mirror(c: char) -> char {
+    match c {
+        '(' => ')',
+        ')' => '(',
+        '[' => ']',
+        ']' => '[',
+        '{' => '}',
+        '}' => '{',
+        '<' => '>',
+        '>' => '<',
+        '«' => '»',
+        '»' => '«',
+        _ => c,
+    }
+}
+
+/// Reorder a line of chars in logical order into the visual order from left to right.
+/// The right-to-left runs are reversed, the numbers in them are kept left-to-right and
+/// the neutrals between runs of different directions take the base direction
+pub fn visual_line<M>(line: Vec<(char, M)>, base_rtl: bool) -> Vec<(char, M)> {
+    let mut classes: Vec<Class> = line.iter().map(|&(c, _)| class(c)).collect();
+    if !classes.iter().any(|&c| c == Class::Rtl) && !base_rtl {
+        return line;
+    }
+
+    // Marks follow their base char
+    for i in 0..classes.len() {
+        if classes[i] == Class::Mark {
+            classes[i] = if i > 0 {
+                classes[i - 1]
+            } else {
+                Class::Neutral
+            };
+        }
+    }
+
+    // Strong direction of each char, numbers follow the strong char before them
+    let base = if base_rtl { Class::Rtl } else { Class::Ltr };
+    let mut strong = base;
+    let mut dirs: Vec<Option<Class>> = Vec::with_capacity(classes.len());
+    for &c in classes.iter() {
+        dirs.push(match c {
+            Class::Ltr | Class::Rtl => {
+                strong = c;
+                Some(c)
+            }
+            Class::Number => Some(strong),
+            _ => None,
+        });
+    }
+
+    let ltr_level = if base_rtl { 2 } else { 0 };
+    let levels: Vec<u8> = (0..classes.len())
+        .map(|i| {
+            let dir = dirs[i].unwrap_or_else(|| {
+                let prev = dirs[..i].iter().rev().filter_map(|&d| d).next();
+                let next = dirs[i + 1..].iter().filter_map(|&d| d).next();
+                match (prev.unwrap_or(base), next.unwrap_or(base)) {
+                    (p, n) if p == n => p,
+                    _ => base,
+                }
+            });
+
+            match (classes[i], dir) {
+                (Class::Number, Class::Rtl) => 2,
+                (_, Class::Rtl) => 1,
+                _ => ltr_level,
+            }
+        })
+        .collect();
+
+    let mut chars: Vec<(u8, (char, M))> = levels
+        .into_iter()
+        .zip(line.into_iter())
+        .map(|(level, (c, m))| {
+            let c = if level % 2 == 1 { mirror(c) } else { c };
+            (level, (c, m))
+        })
+        .collect();
+
+    // Reverse the runs of each level and above, from the highest level to the lowest odd one
+    let max_level = chars.iter().map(|&(l, _)| l).max().unwrap_or(0);
+    for level in (1..max_level + 1).rev() {
+        let mut start = 0;
+        while start < chars.len() {
+            if chars[start].0 < level {
+                start += 1;
+                continue;
+            }
+
+            let mut end = start;
+            while end < chars.len() && chars[end].0 >= level {
+                end += 1;
+            }
+
+            chars[start..end].reverse();
+            start = end;
+        }
+    }
+
+    chars.into_iter().map(|(_, c)| c).collect()
+}
+
+/// The first presentation form and the number of forms of an Arabic letter
+fn arabic_forms(c: char) -> Option<(u32, u32)> {
+    let code = c as u32;
+    if code < 0x0621 || code > 0x064A {
+        return None;
+    }
+
+    let index = (code - 0x0621) as usize;
+    let first = 0xFE80 + ARABIC_FORM_COUNTS[..index].iter().sum::<u32>();
+    match ARABIC_FORM_COUNTS[index] {
+        0 => None,
+        count => Some((first, count)),
+    }
+}
+
+fn joining(c: char) -> Joining {
+    if c == TATWEEL {
+        return Joining::Dual;
+    }
+
+    if is_mark(c) {
+        return Joining::Transparent;
+    }
+
+    match arabic_forms(c) {
+        Some((_, 4)) => Joining::Dual,
+        Some((_, 2)) => Joining::Right,
+        _ => Joining::NonJoining,
+    }
+}
+
+/// Replace the Arabic letters of `s` by their contextual presentation forms, such that
+/// they are joined by the fonts without shaping. The chars are replaced one by one
+/// to keep the markup of the rich texts, the lam-alef ligatures are not formed
+pub fn shape(s: &str) -> String {
+    if !s.chars().any(|c| arabic_forms(c).is_some()) {
+        return s.into();
+    }
+
+    let chars: Vec<char> = s.chars().collect();
+    let joinings: Vec<Joining> = chars.iter().map(|&c| joining(c)).collect();
+
+    chars
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| {
+            let (first, count) = match arabic_forms(c) {
+                Some(forms) => forms,
+                None => return c,
+            };
+
+            let prev = joinings[..i]
+                .iter()
+                .rev()
+                .find(|&&j| j != Joining::Transparent);
+            let next = joinings[i + 1..]
+                .iter()
+                .find(|&&j| j != Joining::Transparent);
+
+            let joins_prev = count >= 2 && prev == Some(&Joining::Dual);
+            let joins_next =
+                count == 4 && (next == Some(&Joining::Dual) || next == Some(&Joining::Right));
+
+            let form = match (joins_prev, joins_next) {
+                (false, false) => 0,
+                (true, false) => 1,
+                (false, true) => 2,
+                (true, true) => 3,
+            };
+
+            ::std::char::from_u32(first + form).unwrap_or(c)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn visual(s: &str, base_rtl: bool) -> String {
+        let line = s.chars().map(|c| (c, ())).collect();
+        visual_line(line, base_rtl).into_iter().map(|(c, _)| c).collect()
+    }
+
+    #[test]
+    fn ltr_text_is_unchanged() {
+        assert_eq!(visual("a(b) 12", false), "a(b) 12");
+        assert!(!is_rtl_text("abc אב"));
+        assert!(!is_rtl_text("123 ..."));
+    }
+
+    #[test]
+    fn rtl_run_in_ltr_text() {
+        assert_eq!(visual("ab אבג cd", false), "ab גבא cd");
+    }
+
+    #[test]
+    fn ltr_run_in_rtl_text() {
+        assert!(is_rtl_text("12 אב cd"));
+        assert_eq!(visual("אב cd גד", true), "דג cd בא");
+    }
+
+    #[test]
+    fn numbers_in_rtl_text() {
+        assert_eq!(visual("אב 123 גד", false), "דג 123 בא");
+        assert_eq!(visual("123 אב", true), "בא 123");
+    }
+
+    #[test]
+    fn brackets_are_mirrored() {
+        assert_eq!(visual("א(ב)", true), "(ב)א");
+        assert_eq!(visual("א [12] ב", true), "ב [12] א");
+    }
+
+    #[test]
+    fn markup_follows_its_char() {
+        let line = "aאב".chars().enumerate().map(|(i, c)| (c, i)).collect();
+        let order: Vec<usize> = visual_line(line, false).into_iter().map(|(_, i)| i).collect();
+        assert_eq!(order, vec![0, 2, 1]);
+    }
+}
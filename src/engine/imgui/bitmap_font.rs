@@ -0,0 +1,110 @@
+use super::Metric;
+
+/// Glyph index of '?' in the default bitmap font
+const DEFAULT_FALLBACK: u32 = '?' as u32;
+
+/// A range of codepoints mapped to consecutive glyphs of the atlas
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct GlyphRange {
+    first: u32,
+    last: u32,
+    /// Glyph index of the first codepoint
+    start: u32,
+}
+
+/// Layout of a bitmap font atlas and the mapping from codepoints to its glyphs
+///
+/// Glyphs are stored row by row from the top-left of the atlas, the glyph index `i`
+/// is located at column `i % cols` and row `i / cols`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BitmapFont {
+    /// Name of the atlas texture
+    pub texture: String,
+    /// Size of the atlas in pixel
+    pub texture_size: (u32, u32),
+    /// Size of a glyph cell in pixel
+    pub cell_size: (u32, u32),
+    /// Glyph index used for unmapped codepoints
+    pub fallback: u32,
+    ranges: Vec<GlyphRange>,
+}
+
+impl Default for BitmapFont {
+    /// The builtin 8x8 ASCII font
+    fn default() -> BitmapFont {
+        let mut font = BitmapFont::new("default_font_bitmap", (128, 64), (8, 8));
+        font.add_glyph_range('\u{0}', '\u{7f}', 0);
+        font
+    }
+}
+
+impl BitmapFont {
+    /// Create a bitmap font without any mapped glyph
+    pub fn new(texture: &str, texture_size: (u32, u32), cell_size: (u32, u32)) -> BitmapFont {
+        BitmapFont {
+            texture: texture.into(),
+            texture_size,
+            cell_size,
+            fallback: DEFAULT_FALLBACK,
+            ranges: Vec::new(),
+        }
+    }
+
+    /// Map the codepoints `first..=last` to the glyphs starting at `start`,
+    /// ranges registered later have higher priority
+    pub fn add_glyph_range(&mut self, first: char, last: char, start: u32) {
+        self.ranges.push(GlyphRange {
+            first: first as u32,
+            last: last as u32,
+            start,
+        });
+    }
+
+    /// Number of glyphs in a row and a column of the atlas
+    pub fn grid_size(&self) -> (u32, u32) {
+        (
+            self.texture_size.0 / self.cell_size.0,
+            self.texture_size.1 / self.cell_size.1,
+        )
+    }
+
+    /// Glyph index of a char, the fallback glyph is used if it is not mapped
+    pub fn glyph_index(&self, c: char) -> u32 {
+        let c = c as u32;
+        let (cols, rows) = self.grid_size();
+
+        self.ranges
+            .iter()
+            .rev()
+            .find(|r| r.first <= c && c <= r.last)
+            .map(|r| r.start + (c - r.first))
+            .filter(|&i| i < cols * rows)
+            .unwrap_or(self.fallback)
+    }
+
+    /// Texture space (u0, v0, u1, v1) of a glyph
+    pub fn glyph_uv(&self, index: u32) -> (f32, f32, f32, f32) {
+        let (cols, _) = self.grid_size();
+        let icw = (self.cell_size.0 as f32) / (self.texture_size.0 as f32);
+        let ich = (self.cell_size.1 as f32) / (self.texture_size.1 as f32);
+
+        let col = (index % cols) as f32;
+        let row = (index / cols) as f32;
+
+        (col * icw, row * ich, col * icw + icw, row * ich + ich)
+    }
+
+    /// The size of a text in pixel
+    pub fn text_size(&self, s: &str) -> Metric {
+        let lines: Vec<&str> = s.split('\n').collect();
+        let max_len = lines
+            .iter()
+            .fold(0, |acc, line| acc.max(line.chars().count()));
+
+        // Each line is followed by an empty line
+        Metric::Pixel(
+            (max_len as u32 * self.cell_size.0) as f32,
+            ((lines.len() * 2 - 1) as u32 * self.cell_size.1) as f32,
+        )
+    }
+}
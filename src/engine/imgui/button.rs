@@ -0,0 +1,178 @@
+use super::instance::ImguiState;
+use super::widgets;
+use super::widgets::Widget;
+use super::{image, label};
+use super::{Metric, TextAlign};
+
+use engine::core::Aabb;
+use engine::{Asset, GameObject, IEngine, Mesh, MeshBuffer, MeshData};
+use math::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[derive(Debug, PartialEq)]
+pub struct Button {
+    id: u32,
+    pub pos: Metric,
+    pub size: Metric,
+    pub state: ImguiState,
+    pub widget_state: widgets::WidgetState,
+    s: String,
+}
+
+impl Button {
+    pub fn new(
+        id: u32,
+        pos: Metric,
+        size: Metric,
+        state: ImguiState,
+        widget_state: widgets::WidgetState,
+        s: String,
+    ) -> Widget {
+        Widget::Button(Self {
+            id,
+            pos,
+            size,
+            state,
+            widget_state,
+            s,
+        })
+    }
+
+    pub fn rect(&self, ssize: (u32, u32), hidpi: f32) -> Aabb {
+        widgets::compute_rect(&self.pos, &self.size, &self.state.pivot, &ssize, hidpi)
+    }
+
+    /// Text mesh centered in the button of ndc size (w, h)
+    fn make_text_mesh_data(&self, size: (f32, f32), ssize: (u32, u32), hidpi: f32) -> MeshData {
+        let (w, h) = size;
+
+        let mut text = label::make_state_text_mesh_data(
+            &self.s,
+            TextAlign::Center,
+            &self.state,
+            None,
+            ssize,
+            hidpi,
+        );
+        let text_bounds = text.compute_bound().local_aabb();
+        let tw = text_bounds.max.x - text_bounds.min.x;
+        let th = text_bounds.max.y - text_bounds.min.y;
+        text.translate(Vector3::new(
+            (w - tw) * 0.5 - text_bounds.min.x,
+            -(h - th) * 0.5 - text_bounds.max.y,
+            0.0,
+        ));
+
+        text
+    }
+
+    /// Update the text of the game object bound by `old` in place,
+    /// return false if anything but the text and position is changed
+    pub fn update(
+        &self,
+        old: &Button,
+        go: &Rc<RefCell<GameObject>>,
+        ssize: (u32, u32),
+        engine: &mut IEngine,
+    ) -> bool {
+        if self.size != old.size || self.state != old.state || self.widget_state != old.widget_state
+        {
+            return false;
+        }
+
+        if self.s != old.s {
+            let hidpi = engine.hidpi_factor();
+            let size = image::compute_size_to_ndc(&self.size, &ssize, hidpi);
+            let text = self.make_text_mesh_data(size, ssize, hidpi);
+
+            // The text is the last surface
+            let gobj = go.borrow();
+            let mesh = match gobj.find_component::<Mesh>() {
+                Some((mesh, _)) => mesh,
+                None => return false,
+            };
+            match mesh.surfaces.last() {
+                Some(surface) => surface.buffer.update_mesh_data(text),
+                None => return false,
+            }
+        }
+
+        true
+    }
+
+    pub fn bind(
+        &self,
+        ssize: (u32, u32),
+        parent: &GameObject,
+        engine: &mut IEngine,
+    ) -> Rc<RefCell<GameObject>> {
+        let hidpi = engine.hidpi_factor();
+        let (w, h) = image::compute_size_to_ndc(&self.size, &ssize, hidpi);
+        let style = &self.state.style;
+
+        //Mesh
+        let mut mesh = Mesh::new();
+
+        // Background
+        widgets::add_frame(
+            &mut mesh,
+            engine,
+            ssize,
+            style,
+            style.frame_color(self.widget_state),
+            (0.0, 0.0),
+            (w, h),
+        );
+
+        // Text, centered in the button
+        let text = self.make_text_mesh_data((w, h), ssize, hidpi);
+
+        mesh.add_surface(
+            MeshBuffer::new(text),
+            widgets::new_text_material(engine, &self.state),
+        );
+
+        widgets::clip_mesh(&mesh, &self.state, ssize, hidpi);
+        widgets::transform_mesh(&mesh, &self.state, &self.rect(ssize, hidpi), ssize, hidpi);
+        mesh.layer = self.state.layer;
+
+        // Game Object
+        let go = engine.new_game_object(parent);
+        let mut gomut = go.borrow_mut();
+
+        let mut gtrans = gomut.transform.global();
+        let rect = self.rect(ssize, hidpi);
+        gtrans.disp += Vector3::new(rect.min.x, rect.max.y, 0.0);
+        gomut.transform.set_global(gtrans);
+        gomut.add_component(mesh);
+        drop(gomut);
+
+        go
+    }
+}
+
+impl widgets::WidgetBinder for Button {
+    fn id(&self) -> u32 {
+        self.id
+    }
+
+    fn is_same(&self, other: &Widget) -> bool {
+        match other {
+            &Widget::Button(ref btn) => btn == self,
+            _ => false,
+        }
+    }
+
+    fn is_moved(&self, other: &Widget) -> bool {
+        match other {
+            &Widget::Button(ref btn) => {
+                btn.size == self.size
+                    && btn.state == self.state
+                    && btn.widget_state == self.widget_state
+                    && btn.s == self.s
+            }
+            _ => false,
+        }
+    }
+}
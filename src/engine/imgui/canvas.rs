@@ -0,0 +1,247 @@
+use super::image;
+use super::instance::ImguiState;
+use super::mesh_pool::MeshPool;
+use super::widgets;
+use super::widgets::Widget;
+use super::{Color, Metric};
+
+use engine::core::Aabb;
+use engine::{GameObject, IEngine, Mesh, MeshData};
+use math::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::u16;
+
+/// Number of the segments of a circle
+const CIRCLE_SEGMENTS: usize = 32;
+
+/// Shapes drawn by `imgui::canvas`, the positions and sizes are in pixel
+/// from the top-left of the canvas. The shapes are tessellated into triangles
+/// immediately and batched, shapes over the limit of 65536 vertices are dropped
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Draw {
+    vertices: Vec<(f32, f32)>,
+    colors: Vec<Color>,
+    indices: Vec<u16>,
+}
+
+impl Draw {
+    /// Add the vertices of a shape and its triangles in the indices of `vertices`
+    fn add(&mut self, vertices: &[(f32, f32)], indices: &[u16], color: Color) {
+        let base = self.vertices.len();
+        if base + vertices.len() > u16::MAX as usize + 1 {
+            return;
+        }
+
+        self.vertices.extend_from_slice(vertices);
+        self.colors.extend(vertices.iter().map(|_| color));
+        self.indices.extend(indices.iter().map(|i| base as u16 + i));
+    }
+
+    /// Line from `p0` to `p1` of `width`
+    pub fn line(&mut self, p0: (f32, f32), p1: (f32, f32), width: f32, color: Color) {
+        let (dx, dy) = (p1.0 - p0.0, p1.1 - p0.1);
+        let len = (dx * dx + dy * dy).sqrt();
+        if len <= 0.0 {
+            return;
+        }
+
+        // Half width along the normal of the line
+        let (nx, ny) = (-dy / len * width * 0.5, dx / len * width * 0.5);
+
+        self.add(
+            &[
+                (p0.0 + nx, p0.1 + ny),
+                (p0.0 - nx, p0.1 - ny),
+                (p1.0 - nx, p1.1 - ny),
+                (p1.0 + nx, p1.1 + ny),
+            ],
+            &[0, 1, 2, 0, 2, 3],
+            color,
+        );
+    }
+
+    /// Filled rect of `size` with the top-left at `pos`
+    pub fn rect(&mut self, pos: (f32, f32), size: (f32, f32), color: Color) {
+        let ((x0, y0), (x1, y1)) = (pos, (pos.0 + size.0, pos.1 + size.1));
+
+        self.add(
+            &[(x0, y0), (x0, y1), (x1, y1), (x1, y0)],
+            &[0, 1, 2, 0, 2, 3],
+            color,
+        );
+    }
+
+    /// Filled circle
+    pub fn circle(&mut self, center: (f32, f32), radius: f32, color: Color) {
+        let mut vertices = Vec::with_capacity(CIRCLE_SEGMENTS + 1);
+        let mut indices = Vec::with_capacity(CIRCLE_SEGMENTS * 3);
+
+        vertices.push(center);
+        for i in 0..CIRCLE_SEGMENTS {
+            let a = i as f32 / CIRCLE_SEGMENTS as f32 * 2.0 * ::std::f32::consts::PI;
+            vertices.push((center.0 + radius * a.cos(), center.1 + radius * a.sin()));
+
+            let next = (i + 1) % CIRCLE_SEGMENTS;
+            indices.extend_from_slice(&[0, i as u16 + 1, next as u16 + 1]);
+        }
+
+        self.add(&vertices, &indices, color);
+    }
+
+    /// Connected lines through `points` of `width`
+    pub fn polyline(&mut self, points: &[(f32, f32)], width: f32, color: Color) {
+        for pair in points.windows(2) {
+            self.line(pair[0], pair[1], width, color);
+        }
+    }
+
+    /// The mesh data in ndc from the top-left of the canvas,
+    /// the vectors are taken from the pool to avoid the allocations
+    fn make_mesh_data(&self, ssize: (u32, u32), hidpi: f32, pool: &MeshPool) -> MeshData {
+        let mut vertices = pool.f32_vec();
+        vertices.reserve(self.vertices.len() * 3);
+        for &(x, y) in self.vertices.iter() {
+            let (nx, ny) = image::compute_size_to_ndc(&Metric::Pixel(x, y), &ssize, hidpi);
+            vertices.extend_from_slice(&[nx, -ny, 0.0]);
+        }
+
+        let mut colors = pool.f32_vec();
+        colors.reserve(self.colors.len() * 4);
+        for c in self.colors.iter() {
+            colors.extend_from_slice(&[c.r, c.g, c.b, c.a]);
+        }
+
+        let mut indices = pool.u16_vec();
+        indices.extend_from_slice(&self.indices);
+
+        MeshData {
+            vertices: vertices,
+            uvs: None,
+            uvs2: None,
+            normals: None,
+            indices: indices,
+            tangents: None,
+            bitangents: None,
+            colors: Some(colors),
+            joints: None,
+            weights: None,
+            morph_targets: Vec::new(),
+        }
+    }
+}
+
+/// Canvas of `imgui::canvas`, the shapes are clipped to it
+#[derive(Debug, PartialEq)]
+pub struct Canvas {
+    id: u32,
+    pub pos: Metric,
+    pub size: Metric,
+    pub state: ImguiState,
+    draw: Draw,
+}
+
+impl Canvas {
+    pub fn new(id: u32, pos: Metric, size: Metric, state: ImguiState, draw: Draw) -> Widget {
+        Widget::Canvas(Self {
+            id,
+            pos,
+            size,
+            state,
+            draw,
+        })
+    }
+
+    pub fn rect(&self, ssize: (u32, u32), hidpi: f32) -> Aabb {
+        widgets::compute_rect(&self.pos, &self.size, &self.state.pivot, &ssize, hidpi)
+    }
+
+    /// Update the shapes of the game object bound by `old` in place,
+    /// return false if anything but the shapes and position is changed
+    pub fn update(
+        &self,
+        old: &Canvas,
+        go: &Rc<RefCell<GameObject>>,
+        ssize: (u32, u32),
+        pool: &MeshPool,
+        engine: &mut IEngine,
+    ) -> bool {
+        if self.size != old.size || self.state != old.state {
+            return false;
+        }
+
+        if self.draw != old.draw {
+            let data = self.draw.make_mesh_data(ssize, engine.hidpi_factor(), pool);
+
+            let gobj = go.borrow();
+            let mesh = match gobj.find_component::<Mesh>() {
+                Some((mesh, _)) => mesh,
+                None => return false,
+            };
+            match mesh.surfaces.first() {
+                Some(surface) => pool.update(&surface.buffer, data),
+                None => return false,
+            }
+        }
+
+        true
+    }
+
+    pub fn bind(
+        &self,
+        ssize: (u32, u32),
+        parent: &GameObject,
+        pool: &MeshPool,
+        engine: &mut IEngine,
+    ) -> Rc<RefCell<GameObject>> {
+        let hidpi = engine.hidpi_factor();
+
+        // All shapes are in a single surface
+        let mut mesh = Mesh::new();
+        let material = widgets::new_vertex_color_material(engine);
+        material.set("uColor", self.state.color.to_vec4());
+        mesh.add_surface(
+            pool.buffer(self.draw.make_mesh_data(ssize, hidpi, pool)),
+            material,
+        );
+
+        widgets::clip_mesh(&mesh, &self.state, ssize, hidpi);
+        widgets::transform_mesh(&mesh, &self.state, &self.rect(ssize, hidpi), ssize, hidpi);
+        mesh.layer = self.state.layer;
+
+        // Game Object
+        let go = engine.new_game_object(parent);
+        let mut gomut = go.borrow_mut();
+
+        let mut gtrans = gomut.transform.global();
+        let rect = self.rect(ssize, hidpi);
+        gtrans.disp += Vector3::new(rect.min.x, rect.max.y, 0.0);
+        gomut.transform.set_global(gtrans);
+        gomut.add_component(mesh);
+        drop(gomut);
+
+        go
+    }
+}
+
+impl widgets::WidgetBinder for Canvas {
+    fn id(&self) -> u32 {
+        self.id
+    }
+
+    fn is_same(&self, other: &Widget) -> bool {
+        match other {
+            &Widget::Canvas(ref c) => c == self,
+            _ => false,
+        }
+    }
+
+    fn is_moved(&self, other: &Widget) -> bool {
+        match other {
+            &Widget::Canvas(ref c) => {
+                c.size == self.size && c.state == self.state && c.draw == self.draw
+            }
+            _ => false,
+        }
+    }
+}
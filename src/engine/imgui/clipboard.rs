@@ -0,0 +1,191 @@
+use std::cell::RefCell;
+#[cfg(not(target_arch = "wasm32"))]
+use std::process::Child;
+
+/// Clipboard used by the copy, cut and paste of the text fields, see `imgui::set_clipboard`
+pub trait Clipboard {
+    fn get(&mut self) -> Option<String>;
+    fn set(&mut self, s: &str);
+}
+
+/// Clipboard inside the application, e.g. for the tests
+#[derive(Default, Debug)]
+pub struct MemoryClipboard {
+    text: Option<String>,
+}
+
+impl Clipboard for MemoryClipboard {
+    fn get(&mut self) -> Option<String> {
+        self.text.clone()
+    }
+
+    fn set(&mut self, s: &str) {
+        self.text = Some(s.to_string());
+    }
+}
+
+/// Clipboard of the desktop through the clipboard tool of the platform
+/// (pbcopy on macOS, clip on Windows, xclip on Linux), the default on desktop.
+/// The text stays inside the application if the tool is missing
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default, Debug)]
+pub struct NativeClipboard {
+    fallback: MemoryClipboard,
+    /// The copy tools which may still be running, reaped by the next copies
+    children: Vec<Child>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl NativeClipboard {
+    fn copy_command() -> (&'static str, &'static [&'static str]) {
+        if cfg!(target_os = "macos") {
+            ("pbcopy", &[])
+        } else if cfg!(target_os = "windows") {
+            ("clip", &[])
+        } else {
+            ("xclip", &["-selection", "clipboard", "-in"])
+        }
+    }
+
+    fn paste_command() -> (&'static str, &'static [&'static str]) {
+        if cfg!(target_os = "macos") {
+            ("pbpaste", &[])
+        } else if cfg!(target_os = "windows") {
+            ("powershell", &["-NoProfile", "-Command", "Get-Clipboard"])
+        } else {
+            ("xclip", &["-selection", "clipboard", "-out"])
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Clipboard for NativeClipboard {
+    fn get(&mut self) -> Option<String> {
+        use std::process::Command;
+
+        let (cmd, args) = Self::paste_command();
+        match Command::new(cmd).args(args).output() {
+            Ok(ref output) if output.status.success() => {
+                let text = String::from_utf8_lossy(&output.stdout);
+                // The tools end the text with a new line
+                let text = text.trim_right_matches(|c| c == '\r' || c == '\n');
+                Some(text.to_string())
+            }
+            _ => self.fallback.get(),
+        }
+    }
+
+    fn set(&mut self, s: &str) {
+        use std::io::Write;
+        use std::process::{Command, Stdio};
+
+        self.fallback.set(s);
+        self.children = self.children
+            .drain(..)
+            .filter_map(|mut child| match child.try_wait() {
+                Ok(None) => Some(child),
+                _ => None,
+            })
+            .collect();
+
+        let (cmd, args) = Self::copy_command();
+        let child = Command::new(cmd)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn();
+
+        if let Ok(mut child) = child {
+            // The tool copies the text once its stdin is closed
+            if let Some(mut stdin) = child.stdin.take() {
+                let _ = stdin.write_all(s.as_bytes());
+            }
+            self.children.push(child);
+        }
+    }
+}
+
+/// Clipboard of the browser, the default on the web.
+///
+/// The copies are written by `navigator.clipboard.writeText`. The text pasted into the
+/// page is only known from the paste events, it is the one of the last paste, which
+/// comes before the key press is handled, or else the one copied last
+#[cfg(target_arch = "wasm32")]
+#[derive(Debug)]
+pub struct WebClipboard(());
+
+#[cfg(target_arch = "wasm32")]
+impl WebClipboard {
+    pub fn new() -> WebClipboard {
+        js! {
+            if (window.unrust_clipboard === undefined) {
+                window.unrust_clipboard = null;
+                document.addEventListener("paste", function(e) {
+                    var data = e.clipboardData || window.clipboardData;
+                    if (data) {
+                        window.unrust_clipboard = data.getData("text");
+                    }
+                });
+            }
+        };
+
+        WebClipboard(())
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Default for WebClipboard {
+    fn default() -> WebClipboard {
+        WebClipboard::new()
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Clipboard for WebClipboard {
+    fn get(&mut self) -> Option<String> {
+        use stdweb::unstable::TryInto;
+
+        js! { return window.unrust_clipboard; }
+            .try_into()
+            .unwrap_or(None)
+    }
+
+    fn set(&mut self, s: &str) {
+        js! {
+            var text = @{s};
+            window.unrust_clipboard = text;
+            if (navigator.clipboard && navigator.clipboard.writeText) {
+                navigator.clipboard.writeText(text).catch(function(e) {
+                    console.log("warning : the clipboard could not be written, " + e);
+                });
+            }
+        };
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn default_clipboard() -> Box<Clipboard> {
+    Box::new(NativeClipboard::default())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn default_clipboard() -> Box<Clipboard> {
+    Box::new(WebClipboard::new())
+}
+
+thread_local!(
+    static CLIPBOARD: RefCell<Box<Clipboard>> = RefCell::new(default_clipboard())
+);
+
+/// Replace the clipboard, return the previous one
+pub fn set_clipboard(clipboard: Box<Clipboard>) -> Box<Clipboard> {
+    CLIPBOARD.with(|c| c.replace(clipboard))
+}
+
+pub fn get_text() -> Option<String> {
+    CLIPBOARD.with(|c| c.borrow_mut().get())
+}
+
+pub fn set_text(s: &str) {
+    CLIPBOARD.with(|c| c.borrow_mut().set(s))
+}
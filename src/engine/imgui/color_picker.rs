@@ -0,0 +1,240 @@
+use super::image;
+use super::instance::ImguiState;
+use super::widgets;
+use super::widgets::{Widget, WidgetState};
+use super::{Color, Metric};
+
+use engine::core::Aabb;
+use engine::{Asset, GameObject, IEngine, Mesh, MeshBuffer, MeshData};
+use math::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Width of the hue strip in pixel
+pub const HUE_WIDTH: f32 = 16.0;
+/// Space between the saturation/value square and the hue strip in pixel
+pub const SPACING: f32 = 4.0;
+/// Number of cells in each direction of the saturation/value gradient
+const GRID_SIZE: usize = 8;
+/// Size of the markers in pixel
+const MARKER_SIZE: f32 = 6.0;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ColorPickerPart {
+    /// Saturation from left to right, value from bottom to top
+    SaturationValue,
+    /// Hue from top to bottom
+    Hue,
+}
+
+/// A part of the color picker, `hsv` is the picked color
+#[derive(Debug, PartialEq)]
+pub struct ColorPicker {
+    id: u32,
+    pub pos: Metric,
+    pub size: Metric,
+    pub state: ImguiState,
+    pub widget_state: WidgetState,
+    pub part: ColorPickerPart,
+    pub hsv: (f32, f32, f32),
+}
+
+/// Gradient mesh of `colors` on a `cols` x `rows` grid of vertices,
+/// which covers `ndc_size` from the top-left
+fn make_gradient_mesh_data(
+    ndc_size: (f32, f32),
+    cols: usize,
+    rows: usize,
+    colors: &[Color],
+) -> MeshData {
+    let (w, h) = ndc_size;
+
+    let mut vertices = Vec::with_capacity(cols * rows * 3);
+    let mut vcolors = Vec::with_capacity(cols * rows * 4);
+    let mut indices = Vec::with_capacity((cols - 1) * (rows - 1) * 6);
+
+    for row in 0..rows {
+        for col in 0..cols {
+            let x = w * col as f32 / (cols - 1) as f32;
+            let y = -h * row as f32 / (rows - 1) as f32;
+            let c = colors[row * cols + col];
+
+            vertices.extend_from_slice(&[x, y, 0.0]);
+            vcolors.extend_from_slice(&[c.r, c.g, c.b, c.a]);
+        }
+    }
+
+    for row in 0..rows - 1 {
+        for col in 0..cols - 1 {
+            let i = (row * cols + col) as u16;
+            let n = cols as u16;
+            indices.extend_from_slice(&[i, i + n, i + n + 1, i, i + n + 1, i + 1]);
+        }
+    }
+
+    MeshData {
+        vertices: vertices,
+        uvs: None,
+        uvs2: None,
+        normals: None,
+        indices: indices,
+        tangents: None,
+        bitangents: None,
+        colors: Some(vcolors),
+        joints: None,
+        weights: None,
+        morph_targets: Vec::new(),
+    }
+}
+
+impl ColorPicker {
+    pub fn new(
+        id: u32,
+        pos: Metric,
+        size: Metric,
+        state: ImguiState,
+        widget_state: WidgetState,
+        part: ColorPickerPart,
+        hsv: (f32, f32, f32),
+    ) -> Widget {
+        Widget::ColorPicker(Self {
+            id,
+            pos,
+            size,
+            state,
+            widget_state,
+            part,
+            hsv,
+        })
+    }
+
+    pub fn rect(&self, ssize: (u32, u32), hidpi: f32) -> Aabb {
+        widgets::compute_rect(&self.pos, &self.size, &self.state.pivot, &ssize, hidpi)
+    }
+
+    /// Position (0..1, 0..1) of ndc position p in the widget, (0, 0) is the top-left
+    pub fn drag_point(&self, p: (f32, f32), ssize: (u32, u32), hidpi: f32) -> (f32, f32) {
+        let rect = self.rect(ssize, hidpi);
+        let w = rect.max.x - rect.min.x;
+        let h = rect.max.y - rect.min.y;
+
+        if w <= 0.0 || h <= 0.0 {
+            return (0.0, 0.0);
+        }
+
+        (
+            ((p.0 - rect.min.x) / w).max(0.0).min(1.0),
+            ((rect.max.y - p.1) / h).max(0.0).min(1.0),
+        )
+    }
+
+    pub fn bind(
+        &self,
+        ssize: (u32, u32),
+        parent: &GameObject,
+        engine: &mut IEngine,
+    ) -> Rc<RefCell<GameObject>> {
+        let hidpi = engine.hidpi_factor();
+        let (w, h) = image::compute_size_to_ndc(&self.size, &ssize, hidpi);
+        let (h0, s0, v0) = self.hsv;
+
+        let mut mesh = Mesh::new();
+
+        // Gradient and the marker position in 0..1
+        let (gradient, (mx, my)) = match self.part {
+            ColorPickerPart::SaturationValue => {
+                let n = GRID_SIZE + 1;
+                let colors: Vec<Color> = (0..n * n)
+                    .map(|i| {
+                        let s = (i % n) as f32 / GRID_SIZE as f32;
+                        let v = 1.0 - (i / n) as f32 / GRID_SIZE as f32;
+                        Color::from_hsv(h0, s, v, 1.0)
+                    })
+                    .collect();
+
+                let gradient = make_gradient_mesh_data((w, h), n, n, &colors);
+                (gradient, (s0, 1.0 - v0))
+            }
+            ColorPickerPart::Hue => {
+                // Hue is linear between the primary and secondary colors
+                let colors: Vec<Color> = (0..14)
+                    .map(|i| Color::from_hsv((i / 2) as f32 / 6.0, 1.0, 1.0, 1.0))
+                    .collect();
+
+                (make_gradient_mesh_data((w, h), 2, 7, &colors), (0.5, h0))
+            }
+        };
+
+        mesh.add_surface(
+            MeshBuffer::new(gradient),
+            widgets::new_vertex_color_material(engine),
+        );
+
+        // Marker, a white square with a black border
+        let (bw, bh) =
+            image::compute_size_to_ndc(&Metric::Pixel(MARKER_SIZE, MARKER_SIZE), &ssize, hidpi);
+        let (iw, ih) = image::compute_size_to_ndc(
+            &Metric::Pixel(MARKER_SIZE - 2.0, MARKER_SIZE - 2.0),
+            &ssize,
+            hidpi,
+        );
+
+        let markers = [
+            ((bw, bh), Color::new(0.0, 0.0, 0.0, 1.0)),
+            ((iw, ih), Color::default()),
+        ];
+
+        for &((mw, mh), color) in markers.iter() {
+            let mut quad = image::make_quad_mesh_data((mw, mh));
+            quad.translate(Vector3::new(mx * w - mw * 0.5, -my * h + mh * 0.5, 0.0));
+
+            mesh.add_surface(
+                MeshBuffer::new(quad),
+                widgets::new_color_material(engine, color),
+            );
+        }
+
+        widgets::clip_mesh(&mesh, &self.state, ssize, hidpi);
+        widgets::transform_mesh(&mesh, &self.state, &self.rect(ssize, hidpi), ssize, hidpi);
+        mesh.layer = self.state.layer;
+
+        // Game Object
+        let go = engine.new_game_object(parent);
+        let mut gomut = go.borrow_mut();
+
+        let mut gtrans = gomut.transform.global();
+        let rect = self.rect(ssize, hidpi);
+        gtrans.disp += Vector3::new(rect.min.x, rect.max.y, 0.0);
+        gomut.transform.set_global(gtrans);
+        gomut.add_component(mesh);
+        drop(gomut);
+
+        go
+    }
+}
+
+impl widgets::WidgetBinder for ColorPicker {
+    fn id(&self) -> u32 {
+        self.id
+    }
+
+    fn is_same(&self, other: &Widget) -> bool {
+        match other {
+            &Widget::ColorPicker(ref cp) => cp == self,
+            _ => false,
+        }
+    }
+
+    fn is_moved(&self, other: &Widget) -> bool {
+        match other {
+            &Widget::ColorPicker(ref cp) => {
+                cp.size == self.size
+                    && cp.state == self.state
+                    && cp.widget_state == self.widget_state
+                    && cp.part == self.part
+                    && cp.hsv == self.hsv
+            }
+            _ => false,
+        }
+    }
+}
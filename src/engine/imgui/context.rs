@@ -1,71 +1,194 @@
 use engine::{Asset, Component, GameObject, Material, Mesh, MeshBuffer, RenderQueue, SceneTree};
+use engine::{DepthTest, MeshData};
+use math::*;
 
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 use std::sync::Arc;
+use uni_app::now;
 
+use super::animation::{Animation, Tween};
+use super::image;
 use super::instance;
-use super::label::Label;
+use super::instance::{FontRef, NavInput, PointerKind};
+use super::label::{make_offset_copies, Label, TextMeshData};
+use super::mesh_pool::MeshPool;
+use super::recorder::UiEvent;
+use super::rich_text::IconAtlas;
 use super::widgets;
+use super::{Color, Metric};
 
+use engine::asset::AssetSystem;
+use engine::engine::{EngineStats, RenderStats};
+use engine::render::RenderCaps;
 use engine::IEngine;
 
+/// Distance in pixel a touch must be moved to scroll instead of clicking
+const TOUCH_SLOP: f32 = 8.0;
+
+/// Texture of a label material
+#[derive(Debug, PartialEq, Clone)]
+enum LabelTexture {
+    Bitmap(String),
+    Font(FontRef),
+    /// For the background quad
+    White,
+    /// For the icons of the markup
+    Icons(Rc<IconAtlas>),
+}
+
+/// The uniforms which distinguish the label materials
+#[derive(Debug, PartialEq, Clone)]
+struct LabelMaterialKey {
+    tex: LabelTexture,
+    color: Color,
+    clip_rect: Vector4<f32>,
+    transform: Matrix4<f32>,
+    /// The glyphs have their own colors of the markup
+    vertex_color: bool,
+    /// Drawn under the text in the same plane, e.g. the background
+    under: bool,
+    /// Opacity of the animation of the label
+    opacity: f32,
+}
+
 struct LabelRenderer {
-    go: Option<Rc<RefCell<GameObject>>>,
-    mesh: Option<Arc<Component>>,
-    material: Option<Rc<Material>>,
+    /// The game object and the shared mesh of the labels of each layer
+    layers: HashMap<i32, (Rc<RefCell<GameObject>>, Arc<Component>)>,
+    materials: Vec<(LabelMaterialKey, Rc<Material>)>,
+    pool: MeshPool,
 }
 
 struct LabelHandle {
     mesh: Arc<Component>,
     mesh_buffer: Option<Rc<MeshBuffer>>,
+    material: Rc<Material>,
+    /// Surfaces under the text: the background, the shadow and the outline
+    under: Vec<(Rc<MeshBuffer>, Rc<Material>)>,
+    icons: Option<(Rc<MeshBuffer>, Rc<Material>)>,
+    /// The mesh buffers are released to the pool when the label is removed
+    pool: MeshPool,
+}
+
+/// Whether the optional surface uses the material `b`
+fn is_same_surface_material(
+    a: &Option<(Rc<MeshBuffer>, Rc<Material>)>,
+    b: &Option<Rc<Material>>,
+) -> bool {
+    match (a, b) {
+        (&Some((_, ref a)), &Some(ref b)) => Rc::ptr_eq(a, b),
+        (&None, &None) => true,
+        _ => false,
+    }
+}
+
+impl LabelHandle {
+    fn is_same_materials(
+        &self,
+        material: &Rc<Material>,
+        under: &[Rc<Material>],
+        icons: &Option<Rc<Material>>,
+    ) -> bool {
+        self.under.len() == under.len()
+            && self
+                .under
+                .iter()
+                .zip(under.iter())
+                .all(|(&(_, ref a), b)| Rc::ptr_eq(a, b))
+            && is_same_surface_material(&self.icons, icons)
+            && Rc::ptr_eq(&self.material, material)
+    }
 }
 
 impl Drop for LabelHandle {
     fn drop(&mut self) {
-        if let Some(ref mb) = self.mesh_buffer {
-            let mesh = self.mesh.try_as::<Mesh>().unwrap();
+        let mesh = self.mesh.try_as::<Mesh>().unwrap();
+
+        for (mb, _) in self.under.drain(..) {
             mesh.borrow_mut().remove_buffer(&mb);
+            self.pool.release(mb);
+        }
+
+        if let Some((mb, _)) = self.icons.take() {
+            mesh.borrow_mut().remove_buffer(&mb);
+            self.pool.release(mb);
+        }
+
+        if let Some(mb) = self.mesh_buffer.take() {
+            mesh.borrow_mut().remove_buffer(&mb);
+            self.pool.release(mb);
         }
     }
 }
 
 impl LabelRenderer {
-    fn new() -> LabelRenderer {
+    fn new(pool: MeshPool) -> LabelRenderer {
         LabelRenderer {
-            go: None,
-            material: None,
-            mesh: None,
+            layers: HashMap::new(),
+            materials: Vec::new(),
+            pool,
         }
     }
 
+    fn material(&mut self, key: LabelMaterialKey, engine: &mut IEngine) -> Rc<Material> {
+        // Drop the materials which are not used by any label
+        self.materials.retain(|&(_, ref m)| Rc::strong_count(m) > 1);
+
+        if let Some(&(_, ref m)) = self.materials.iter().find(|&&(ref k, _)| *k == key) {
+            return m.clone();
+        }
+
+        let db = engine.asset_system();
+        let texture = match key.tex {
+            LabelTexture::Bitmap(ref name) => db.new_texture(name),
+            LabelTexture::Font(ref font) => font.0.texture.clone(),
+            LabelTexture::White => db.new_texture("default_white"),
+            LabelTexture::Icons(ref atlas) => atlas.texture.clone(),
+        };
+
+        let program = if key.vertex_color {
+            "default_ui_text"
+        } else {
+            "default_ui"
+        };
+
+        let mut material = Material::new(db.new_program(program));
+        material.set("uDiffuse", texture);
+        material.set("uColor", key.color.to_vec4());
+        material.set("uClipRect", key.clip_rect);
+        material.set("uTransform", key.transform);
+        material.set("uOpacity", key.opacity);
+        material.render_queue = RenderQueue::UI;
+        material.states.alpha_blending = Some(true);
+
+        if key.under {
+            // The background must not hide the text in the same plane
+            material.states.depth_test = Some(DepthTest::Always);
+            material.states.depth_write = Some(false);
+        }
+
+        let m = Rc::new(material);
+        self.materials.push((key, m.clone()));
+        m
+    }
+
     fn bind(
         &mut self,
         ssize: (u32, u32),
         label: &Label,
         old_handle: Option<&mut LabelHandle>,
+        tween: &Tween,
         parent: &GameObject,
         engine: &mut IEngine,
     ) -> LabelHandle {
-        let material = self.material.get_or_insert_with(|| {
-            let db = engine.asset_system();
-            let mut material = Material::new(db.new_program("default_ui"));
-            material.set("uDiffuse", db.new_texture("default_font_bitmap"));
-            material.render_queue = RenderQueue::UI;
-            Rc::new(material)
-        });
-
-        let go = self.go
-            .get_or_insert_with(|| engine.new_game_object(parent));
-
-        let mesh = self.mesh.get_or_insert_with(|| {
-            let mesh = Mesh::new();
-            let mut gomut = go.borrow_mut();
-            gomut.add_component(mesh)
-        });
-
+        let tex = match label.state.text_font() {
+            Some(font) => LabelTexture::Font(FontRef(font.clone())),
+            None => LabelTexture::Bitmap(label.bitmap_font().texture.clone()),
+        };
         let hidpi = engine.hidpi_factor();
+
         let mesh_data = {
             let mut mesh_data = label.bind(ssize, hidpi);
             let disp = widgets::compute_translate(
@@ -73,44 +196,225 @@ impl LabelRenderer {
                 &label.state.pivot,
                 &ssize,
                 hidpi,
-                &mesh_data.compute_bound().local_aabb(),
+                &mesh_data.bounds(),
             );
 
             mesh_data.translate(disp);
             mesh_data
         };
 
+        // All labels share the mesh, so the transform is applied around the pivot in ndc,
+        // and the tween of the animation around the center
+        let bounds = mesh_data.bounds();
+        let transform = widgets::transform_matrix(
+            &label.state.transform,
+            &label.state.pivot,
+            &bounds,
+            (bounds.min.x, bounds.max.y),
+            ssize,
+            hidpi,
+        );
+        let center = (
+            (bounds.min.x + bounds.max.x) * 0.5,
+            (bounds.min.y + bounds.max.y) * 0.5,
+        );
+        let transform = tween.matrix(center, ssize, hidpi) * transform;
+
+        let clip_rect = widgets::clip_rect(&label.state.clip, &ssize, hidpi);
+        let key = LabelMaterialKey {
+            tex,
+            color: label.state.text_color(),
+            clip_rect,
+            transform,
+            vertex_color: mesh_data.text.colors.is_some(),
+            under: false,
+            opacity: label.state.alpha.0 * tween.opacity,
+        };
+        let material = self.material(key.clone(), engine);
+
+        // Icons are tinted by the color but not by the text color
+        let icon_material = match label.state.icon_atlas {
+            Some(ref atlas) if mesh_data.icons.vertices.len() > 0 => {
+                let key = LabelMaterialKey {
+                    tex: LabelTexture::Icons(atlas.clone()),
+                    color: label.state.color,
+                    vertex_color: false,
+                    ..key.clone()
+                };
+                Some(self.material(key, engine))
+            }
+            _ => None,
+        };
+        let TextMeshData {
+            text: mesh_data,
+            icons: icon_mesh_data,
+        } = mesh_data;
+
+        let layer = label.state.layer;
+        let mesh = self
+            .layers
+            .entry(layer)
+            .or_insert_with(|| {
+                let go = engine.new_game_object(parent);
+                let mut mesh = Mesh::new();
+                mesh.layer = layer;
+
+                let com = go.borrow_mut().add_component(mesh);
+                (go, com)
+            })
+            .1
+            .clone();
+
+        // Surfaces under the text in the drawing order
+        let mut under: Vec<(MeshData, Rc<Material>)> = Vec::new();
+
+        // Background quad covers the whole text
+        if let Some(color) = label.state.background_color {
+            let quad = if bounds.min.x > bounds.max.x {
+                // Empty text
+                MeshData::default()
+            } else {
+                let size = (bounds.max.x - bounds.min.x, bounds.max.y - bounds.min.y);
+                let mut quad = image::make_quad_mesh_data(size);
+                quad.translate(Vector3::new(bounds.min.x, bounds.max.y, 0.0));
+                quad
+            };
+
+            let key = LabelMaterialKey {
+                tex: LabelTexture::White,
+                color,
+                vertex_color: false,
+                under: true,
+                ..key.clone()
+            };
+            under.push((quad, self.material(key, engine)));
+        }
+
+        // Shadow and outline are copies of the glyphs in a single color
+        let text_style = label.state.text_style;
+        let underlays = text_style
+            .shadow
+            .map(|((x, y), color)| (vec![(x, y)], color))
+            .into_iter()
+            .chain(text_style.outline.map(|(w, color)| {
+                let d = w * ::std::f32::consts::FRAC_1_SQRT_2;
+                let offsets = vec![
+                    (-w, 0.0),
+                    (w, 0.0),
+                    (0.0, -w),
+                    (0.0, w),
+                    (-d, -d),
+                    (d, -d),
+                    (-d, d),
+                    (d, d),
+                ];
+                (offsets, color)
+            }));
+
+        for (offsets, color) in underlays {
+            let offsets: Vec<(f32, f32)> = offsets
+                .into_iter()
+                .map(|(x, y)| {
+                    let (nx, ny) = image::compute_size_to_ndc(&Metric::Pixel(x, y), &ssize, hidpi);
+                    (nx, -ny)
+                })
+                .collect();
+
+            let key = LabelMaterialKey {
+                color,
+                vertex_color: false,
+                under: true,
+                ..key.clone()
+            };
+            under.push((
+                make_offset_copies(&mesh_data, &offsets),
+                self.material(key, engine),
+            ));
+        }
+
+        // The surfaces can be reused only if the materials and the layer are not changed
+        let under_materials: Vec<Rc<Material>> =
+            under.iter().map(|&(_, ref m)| m.clone()).collect();
+        let old_handle = old_handle.filter(|h| {
+            h.is_same_materials(&material, &under_materials, &icon_material)
+                && Arc::ptr_eq(&h.mesh, &mesh)
+        });
+
         match old_handle {
             Some(h) => {
                 if let Some(ref mb) = h.mesh_buffer {
                     let mesh_buffer = mb.clone();
                     h.mesh_buffer = None;
-                    mesh_buffer.update_mesh_data(mesh_data);
+                    self.pool.update(&mesh_buffer, mesh_data);
+
+                    let under = h
+                        .under
+                        .drain(..)
+                        .zip(under.into_iter())
+                        .map(|((mb, m), (data, _))| {
+                            self.pool.update(&mb, data);
+                            (mb, m)
+                        })
+                        .collect();
+
+                    let icons = h.icons.take().map(|(icons, im)| {
+                        self.pool.update(&icons, icon_mesh_data);
+                        (icons, im)
+                    });
 
                     return LabelHandle {
                         mesh: h.mesh.clone(),
                         mesh_buffer: Some(mesh_buffer),
+                        material,
+                        under,
+                        icons,
+                        pool: self.pool.clone(),
                     };
                 }
 
                 LabelHandle {
                     mesh: h.mesh.clone(),
                     mesh_buffer: h.mesh_buffer.clone(),
+                    material,
+                    under: h.under.drain(..).collect(),
+                    icons: h.icons.take(),
+                    pool: self.pool.clone(),
                 }
             }
             None => {
+                let mesh_com = mesh.try_as::<Mesh>().unwrap();
+                let mut mesh_mut = mesh_com.borrow_mut();
+
+                // Background, shadow and outline first, such that they are drawn under the text
+                let under = under
+                    .into_iter()
+                    .map(|(data, m)| {
+                        let mb = self.pool.buffer(data);
+                        mesh_mut.add_surface(mb.clone(), m.clone());
+                        (mb, m)
+                    })
+                    .collect();
+
                 // MeshBuffer
-                let mesh_buffer = MeshBuffer::new(mesh_data);
+                let mesh_buffer = self.pool.buffer(mesh_data);
 
                 // Mesh
-                mesh.try_as::<Mesh>()
-                    .unwrap()
-                    .borrow_mut()
-                    .add_surface(mesh_buffer.clone(), material.clone());
+                mesh_mut.add_surface(mesh_buffer.clone(), material.clone());
+
+                // Icons over the text
+                let icons = icon_material.map(|im| {
+                    let icons = self.pool.buffer(icon_mesh_data);
+                    mesh_mut.add_surface(icons.clone(), im.clone());
+                    (icons, im)
+                });
 
                 LabelHandle {
                     mesh: mesh.clone(),
                     mesh_buffer: Some(mesh_buffer),
+                    material,
+                    under,
+                    icons,
+                    pool: self.pool.clone(),
                 }
             }
         }
@@ -124,40 +428,163 @@ enum WidgetHandle {
 
 type WidgetMap = HashMap<u32, (Rc<widgets::Widget>, WidgetHandle)>;
 
+/// Running animation of a widget
+#[derive(Clone, Copy)]
+struct WidgetAnimation {
+    animation: Animation,
+    /// Time when the animation was started
+    start: f64,
+    /// The tween applied to the game object or the label
+    applied: Tween,
+}
+
+impl WidgetAnimation {
+    fn tween(&self, now: f64) -> Tween {
+        self.animation.transition.tween((now - self.start) as f32)
+    }
+}
+
+/// The engine seen by the widgets, whose screen size and hidpi factor are the ones of
+/// the ui, i.e. the hidpi factor is multiplied by the ui scale
+struct UiEngine<'e> {
+    engine: &'e mut IEngine,
+    screen_size: (u32, u32),
+    hidpi: f32,
+}
+
+impl<'e> IEngine for UiEngine<'e> {
+    fn new_game_object(&mut self, parent: &GameObject) -> Rc<RefCell<GameObject>> {
+        self.engine.new_game_object(parent)
+    }
+
+    fn asset_system<'a>(&'a self) -> &'a AssetSystem {
+        self.engine.asset_system()
+    }
+
+    fn asset_system_mut<'a>(&'a mut self) -> &'a mut AssetSystem {
+        self.engine.asset_system_mut()
+    }
+
+    fn gui_context(&mut self) -> Rc<RefCell<Context>> {
+        self.engine.gui_context()
+    }
+
+    fn register_ui(&mut self, name: &str, tree: &Rc<SceneTree>) {
+        self.engine.register_ui(name, tree)
+    }
+
+    fn screen_size(&self) -> (u32, u32) {
+        self.screen_size
+    }
+
+    fn hidpi_factor(&self) -> f32 {
+        self.hidpi
+    }
+
+    fn stats(&self) -> EngineStats {
+        self.engine.stats()
+    }
+
+    fn render_caps(&self) -> RenderCaps {
+        self.engine.render_caps()
+    }
+
+    fn render_stats(&self) -> RenderStats {
+        self.engine.render_stats()
+    }
+}
+
 pub struct Context {
     go: WidgetMap,
     tree: Rc<SceneTree>,
     label_renderer: LabelRenderer,
+    /// Scroll offsets in pixel of the scroll regions
+    scroll: HashMap<u32, (f32, f32)>,
+    /// Animations of the widgets, see `imgui::animate`
+    animations: HashMap<u32, WidgetAnimation>,
+    /// Mesh buffers reused by the labels and the canvases
+    pool: MeshPool,
+    /// Scaled hidpi factor of the bound widgets
+    hidpi: f32,
 }
 
 impl Context {
     pub fn new(tree: Rc<SceneTree>) -> Context {
+        let pool = MeshPool::new();
+
         Context {
             go: HashMap::new(),
             tree,
-            label_renderer: LabelRenderer::new(),
+            label_renderer: LabelRenderer::new(pool.clone()),
+            scroll: HashMap::new(),
+            animations: HashMap::new(),
+            pool,
+            hidpi: 1.0,
         }
     }
 
     pub fn reset(&mut self) {
-        self.label_renderer = LabelRenderer::new();
+        self.label_renderer = LabelRenderer::new(self.pool.clone());
+        self.animations.clear();
 
         self.go.clear()
     }
 
-    pub fn update(&mut self, inner: &instance::ImguiRaw, engine: &mut IEngine) {
+    pub fn update(&mut self, inner: &mut instance::ImguiRaw, engine: &mut IEngine) {
+        let engine: &mut IEngine = &mut UiEngine {
+            engine,
+            screen_size: inner.screen_size,
+            hidpi: inner.hidpi,
+        };
+
+        // The widgets are bound again for a new ui scale
+        let hidpi = inner.hidpi;
+        if hidpi != self.hidpi {
+            self.reset();
+            self.hidpi = hidpi;
+        }
+
         let (sw, sh) = engine.screen_size();
+        let now = now();
+
+        self.start_animations(&inner.render_list, now);
 
         for w in inner.render_list.iter() {
+            let tween = self
+                .animations
+                .get(&w.id())
+                .map_or(Tween::default(), |anim| anim.tween(now));
+
             let do_insert = {
                 let hm = &self.go;
                 match hm.get(&w.id()) {
                     None => true,
-                    Some(&(ref oldw, _)) => **oldw != **w,
+                    // Labels are rebound to be animated
+                    // The unchanged widgets of the retained nodes are shared
+                    Some(&(ref oldw, WidgetHandle::Label(_))) => {
+                        (!Rc::ptr_eq(oldw, w) && **oldw != **w)
+                            || self.applied_tween(w.id()) != tween
+                    }
+                    Some(&(ref oldw, _)) => !Rc::ptr_eq(oldw, w) && **oldw != **w,
                 }
             };
 
             if do_insert {
+                // Move the game object or update its mesh in place if possible
+                let updated = match self.go.get(&w.id()) {
+                    Some(&(ref oldw, WidgetHandle::GameObject(ref go))) => {
+                        w.update(oldw, go, (sw, sh), &self.pool, engine)
+                    }
+                    _ => false,
+                };
+
+                if updated {
+                    if let Some(entry) = self.go.get_mut(&w.id()) {
+                        entry.0 = w.clone();
+                    }
+                    continue;
+                }
+
                 let handle = match w.as_ref() {
                     &widgets::Widget::Label(ref label) => {
                         let h = self.go.get_mut(&w.id()).and_then(|h| match h {
@@ -169,6 +596,7 @@ impl Context {
                             (sw, sh),
                             label,
                             h,
+                            &tween,
                             &self.tree.root(),
                             engine,
                         ))
@@ -176,13 +604,529 @@ impl Context {
                     &widgets::Widget::Image(ref image) => {
                         WidgetHandle::GameObject(image.bind((sw, sh), &self.tree.root(), engine))
                     }
+                    &widgets::Widget::Button(ref button) => {
+                        WidgetHandle::GameObject(button.bind((sw, sh), &self.tree.root(), engine))
+                    }
+                    &widgets::Widget::Slider(ref slider) => {
+                        WidgetHandle::GameObject(slider.bind((sw, sh), &self.tree.root(), engine))
+                    }
+                    &widgets::Widget::Toggle(ref tgl) => {
+                        WidgetHandle::GameObject(tgl.bind((sw, sh), &self.tree.root(), engine))
+                    }
+                    &widgets::Widget::TextField(ref tf) => {
+                        WidgetHandle::GameObject(tf.bind((sw, sh), &self.tree.root(), engine))
+                    }
+                    &widgets::Widget::Scroll(ref sc) => {
+                        WidgetHandle::GameObject(sc.bind((sw, sh), &self.tree.root(), engine))
+                    }
+                    &widgets::Widget::Modal(ref m) => {
+                        WidgetHandle::GameObject(m.bind((sw, sh), &self.tree.root(), engine))
+                    }
+                    &widgets::Widget::TreeNode(ref node) => {
+                        WidgetHandle::GameObject(node.bind((sw, sh), &self.tree.root(), engine))
+                    }
+                    &widgets::Widget::ColorPicker(ref cp) => {
+                        WidgetHandle::GameObject(cp.bind((sw, sh), &self.tree.root(), engine))
+                    }
+                    &widgets::Widget::Region(ref r) => {
+                        WidgetHandle::GameObject(r.bind((sw, sh), &self.tree.root(), engine))
+                    }
+                    &widgets::Widget::FocusRing(ref ring) => {
+                        WidgetHandle::GameObject(ring.bind((sw, sh), &self.tree.root(), engine))
+                    }
+                    &widgets::Widget::Canvas(ref c) => WidgetHandle::GameObject(c.bind(
+                        (sw, sh),
+                        &self.tree.root(),
+                        &self.pool,
+                        engine,
+                    )),
                 };
 
-                self.go.insert(w.id(), (w.clone(), handle));
+                if let WidgetHandle::GameObject(ref go) = handle {
+                    set_opacity(go, w.alpha());
+                }
+
+                // The new game object is not animated yet
+                if let Some(anim) = self.animations.get_mut(&w.id()) {
+                    anim.applied = match handle {
+                        WidgetHandle::Label(_) => tween,
+                        WidgetHandle::GameObject(_) => Tween::default(),
+                    };
+                }
+
+                if let Some((_, old)) = self.go.insert(w.id(), (w.clone(), handle)) {
+                    self.release(old);
+                }
+            }
+        }
+
+        // Remove the game objects of the widgets which are not in the render list
+        let ids: HashSet<u32> = inner.render_list.iter().map(|w| w.id()).collect();
+        let removed: Vec<u32> = self
+            .go
+            .keys()
+            .filter(|k| !ids.contains(k))
+            .cloned()
+            .collect();
+        for id in removed {
+            if let Some((_, old)) = self.go.remove(&id) {
+                self.release(old);
             }
         }
 
-        // remove all go in hm which id >= last id
-        self.go.retain(|k, _| *k <= inner.id);
+        self.animate(inner, now, (sw, sh), engine.hidpi_factor());
+        self.update_uniforms(inner, now);
+
+        self.hit_test(inner, (sw, sh), engine.hidpi_factor());
+        self.navigate(inner, (sw, sh), engine.hidpi_factor());
+        if let Some(id) = inner.interaction.clicked {
+            inner.recorder.record(&UiEvent::Clicked(id));
+        }
+        // The key chords of this frame were reported by `imgui::shortcut`
+        inner.chords.clear();
+        self.update_scroll(inner, (sw, sh), engine.hidpi_factor());
+    }
+
+    /// Drop the handle of a removed widget and release its mesh buffers to the pool,
+    /// the label handles release their buffers themselves
+    fn release(&self, handle: WidgetHandle) {
+        let buffers: Vec<Rc<MeshBuffer>> = match handle {
+            WidgetHandle::GameObject(ref go) => match go.borrow().find_component::<Mesh>() {
+                Some((mesh, _)) => mesh.surfaces.iter().map(|s| s.buffer.clone()).collect(),
+                None => Vec::new(),
+            },
+            WidgetHandle::Label(_) => Vec::new(),
+        };
+
+        drop(handle);
+
+        for mb in buffers {
+            self.pool.release(mb);
+        }
+    }
+
+    /// The tween applied to the widget `id`
+    fn applied_tween(&self, id: u32) -> Tween {
+        self.animations
+            .get(&id)
+            .map_or(Tween::default(), |anim| anim.applied)
     }
+
+    /// Start the animations of the new widgets and restart the changed ones,
+    /// the animations of the removed widgets are dropped
+    fn start_animations(&mut self, render_list: &[Rc<widgets::Widget>], now: f64) {
+        let mut animations = HashMap::new();
+
+        for w in render_list.iter() {
+            let animation = match w.animation() {
+                Some(animation) => *animation,
+                None => continue,
+            };
+
+            let anim = match self.animations.remove(&w.id()) {
+                Some(anim) if anim.animation == animation => anim,
+                old => WidgetAnimation {
+                    animation,
+                    start: now,
+                    applied: old.map_or(Tween::default(), |anim| anim.applied),
+                },
+            };
+            animations.insert(w.id(), anim);
+        }
+
+        self.animations = animations;
+    }
+
+    /// Move, scale and fade the game objects by the tweens of their animations,
+    /// the labels are already animated by their materials when they are bound
+    fn animate(&mut self, inner: &mut instance::ImguiRaw, now: f64, ssize: (u32, u32), hidpi: f32) {
+        inner.animating.clear();
+
+        for (id, anim) in self.animations.iter_mut() {
+            if ((now - anim.start) as f32) < anim.animation.transition.duration() {
+                inner.animating.insert(anim.animation.key);
+            }
+
+            let tween = anim.tween(now);
+            if tween == anim.applied {
+                continue;
+            }
+
+            let (w, go) = match self.go.get(id) {
+                Some(&(ref w, WidgetHandle::GameObject(ref go))) => (w, go),
+                _ => continue,
+            };
+
+            // The origin of the game object is the top-left of the widget
+            let center = w.bound_rect(ssize, hidpi).map_or((0.0, 0.0), |rect| {
+                (
+                    (rect.max.x - rect.min.x) * 0.5,
+                    -(rect.max.y - rect.min.y) * 0.5,
+                )
+            });
+
+            let mut gomut = go.borrow_mut();
+            let mut gtrans = gomut.transform.global();
+            gtrans.disp += tween.displacement(center, ssize, hidpi)
+                - anim.applied.displacement(center, ssize, hidpi);
+            gomut.transform.set_global(gtrans);
+            gomut
+                .transform
+                .set_local_scale(Vector3::new(tween.scale, tween.scale, 1.0));
+
+            drop(gomut);
+
+            set_opacity(go, w.alpha() * tween.opacity);
+            anim.applied = tween;
+        }
+    }
+
+    /// Show the current frames of the animated images and the fractions of the radial fills
+    /// by the uniforms of their materials, their game objects are not bound again
+    fn update_uniforms(&self, inner: &instance::ImguiRaw, now: f64) {
+        for w in inner.render_list.iter() {
+            let img = match w.as_ref() {
+                &widgets::Widget::Image(ref img) if img.is_dynamic() => img,
+                _ => continue,
+            };
+
+            if let Some(&(_, WidgetHandle::GameObject(ref go))) = self.go.get(&w.id()) {
+                let gobj = go.borrow();
+                // The image is the last surface, after the background
+                if let Some((mesh, _)) = gobj.find_component::<Mesh>() {
+                    if let Some(surface) = mesh.surfaces.last() {
+                        img.update_material(&surface.material, now);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Scroll the hovered scroll region by the mouse wheel and the touched one by the drag,
+    /// the offsets are used by the scroll regions in the next frame
+    fn update_scroll(&mut self, inner: &mut instance::ImguiRaw, ssize: (u32, u32), hidpi: f32) {
+        let mouse = widgets::pixel_to_ndc(inner.pointer.pos, &ssize, hidpi);
+        let wheel = inner.pointer.wheel;
+
+        // The content follows the finger
+        let pointer = inner.pointer;
+        let touched = inner
+            .interaction
+            .active
+            .filter(|_| pointer.kind == PointerKind::Touch && pointer.down);
+        let drag = (
+            pointer.prev_pos.0 - pointer.pos.0,
+            pointer.prev_pos.1 - pointer.pos.1,
+        );
+        let capture = capture_ids(&inner.render_list);
+
+        // The inner most region under the mouse takes the wheel,
+        // max_by_key returns the last added one of the top layer
+        let hovered = inner
+            .render_list
+            .iter()
+            .filter(|w| is_captured(&capture, w.id()))
+            .filter(|w| match w.as_ref() {
+                &widgets::Widget::Scroll(ref sc) => {
+                    widgets::rect_contains(&sc.rect(ssize, hidpi), mouse)
+                        && widgets::clip_contains(&sc.state.clip, mouse, &ssize, hidpi)
+                }
+                _ => false,
+            })
+            .max_by_key(|w| w.layer())
+            .map(|w| w.id());
+
+        let mut scroll = HashMap::new();
+
+        // Regions which are not in the render list are dropped
+        for w in inner.render_list.iter() {
+            if let &widgets::Widget::Scroll(ref sc) = w.as_ref() {
+                let (mut x, mut y) = self.scroll.get(&w.id()).cloned().unwrap_or_default();
+                if hovered == Some(w.id()) {
+                    x += wheel.0;
+                    y += wheel.1;
+                }
+                if touched == Some(w.id()) {
+                    x += drag.0;
+                    y += drag.1;
+                }
+
+                let (mx, my) = sc.max_offset(ssize, hidpi);
+                scroll.insert(w.id(), (x.max(0.0).min(mx), y.max(0.0).min(my)));
+            }
+        }
+
+        self.scroll = scroll;
+        inner.scroll = self.scroll.clone();
+    }
+
+    fn hit_test(&self, inner: &mut instance::ImguiRaw, ssize: (u32, u32), hidpi: f32) {
+        let pos = widgets::pixel_to_ndc(inner.pointer.pos, &ssize, hidpi);
+        let capture = capture_ids(&inner.render_list);
+
+        // The last added widget of the top layer is on the top,
+        // max_by_key returns the last one of the equal keys
+        let hovers = inner.pointer.hovers();
+        let hot = inner
+            .render_list
+            .iter()
+            .filter(|w| hovers && is_captured(&capture, w.id()))
+            .filter(|w| {
+                w.hit_contains(pos, ssize, hidpi)
+                    && widgets::clip_contains(w.clip(), pos, &ssize, hidpi)
+            })
+            .max_by_key(|w| w.layer())
+            .map(|w| w.id());
+
+        // The widgets under a modal lose the keyboard focus and the drag
+        if !inner.focus.map_or(true, |id| is_captured(&capture, id)) {
+            inner.focus = None;
+        }
+        if !inner
+            .interaction
+            .active
+            .map_or(true, |id| is_captured(&capture, id))
+        {
+            inner.interaction.active = None;
+        }
+
+        let render_list = &inner.render_list;
+        let interaction = &mut inner.interaction;
+        interaction.hot = hot;
+        interaction.clicked = None;
+        interaction.released = None;
+
+        if inner.pointer.pressed {
+            interaction.active = hot;
+
+            // Click to focus, click elsewhere to lose the focus
+            let focus = hot.filter(|id| {
+                render_list
+                    .iter()
+                    .find(|w| w.id() == *id)
+                    .map_or(false, |w| w.is_focusable())
+            });
+
+            if focus != inner.focus {
+                inner.focus = focus;
+                inner.caret = usize::max_value();
+                inner.selection_anchor = None;
+                inner.composition = None;
+            }
+        }
+
+        // A touch dragged from a widget which is not draggable scrolls the region under it
+        let pointer = inner.pointer;
+        if pointer.kind == PointerKind::Touch && pointer.down {
+            let (dx, dy) = (
+                pointer.pos.0 - pointer.press_pos.0,
+                pointer.pos.1 - pointer.press_pos.1,
+            );
+            let press_pos = widgets::pixel_to_ndc(pointer.press_pos, &ssize, hidpi);
+
+            let scrolls = interaction.active.map_or(false, |active| {
+                render_list
+                    .iter()
+                    .find(|w| w.id() == active)
+                    .map_or(false, |w| !w.is_draggable())
+            });
+
+            if scrolls && dx * dx + dy * dy >= TOUCH_SLOP * TOUCH_SLOP {
+                let region = render_list
+                    .iter()
+                    .filter(|w| is_captured(&capture, w.id()))
+                    .filter(|w| match w.as_ref() {
+                        &widgets::Widget::Scroll(ref sc) => {
+                            widgets::rect_contains(&sc.rect(ssize, hidpi), press_pos)
+                                && widgets::clip_contains(&sc.state.clip, press_pos, &ssize, hidpi)
+                        }
+                        _ => false,
+                    })
+                    .max_by_key(|w| w.layer())
+                    .map(|w| w.id());
+
+                // The pressed widget is not clicked anymore
+                if region.is_some() {
+                    interaction.active = region;
+                }
+            }
+        }
+
+        // Drag capture: the active widget keep tracking the pointer
+        // even it is outside of the widget
+        interaction.drag_value = interaction.active.and_then(|active| {
+            render_list
+                .iter()
+                .find(|w| w.id() == active)
+                .and_then(|w| w.drag_value(pos, ssize, hidpi))
+        });
+
+        interaction.drag_point = interaction.active.and_then(|active| {
+            render_list
+                .iter()
+                .find(|w| w.id() == active)
+                .and_then(|w| w.drag_point(pos, ssize, hidpi))
+        });
+
+        if inner.pointer.released {
+            if interaction.active.is_some() && interaction.active == hot {
+                interaction.clicked = hot;
+            }
+            interaction.released = interaction.active;
+            interaction.active = None;
+        }
+
+        // A modal takes the pointer even outside of its widgets
+        inner.wants_mouse = hot.is_some()
+            || interaction.active.is_some()
+            || interaction.released.is_some()
+            || inner.drag.is_some()
+            || capture.is_some();
+        inner.wants_keyboard = inner.focus.is_some();
+    }
+
+    /// Move the navigation focus by the navigation inputs of this frame,
+    /// the activate input clicks the focused widget like the mouse
+    fn navigate(&self, inner: &mut instance::ImguiRaw, ssize: (u32, u32), hidpi: f32) {
+        let capture = capture_ids(&inner.render_list);
+
+        // Visible navigable widgets and their centers
+        let candidates: Vec<(Rc<widgets::Widget>, (f32, f32))> = inner
+            .render_list
+            .iter()
+            .filter(|w| w.is_navigable() && is_captured(&capture, w.id()))
+            .filter_map(|w| {
+                w.hit_rect(ssize, hidpi).map(|rect| {
+                    let center = (
+                        (rect.min.x + rect.max.x) * 0.5,
+                        (rect.min.y + rect.max.y) * 0.5,
+                    );
+                    (w.clone(), center)
+                })
+            })
+            .filter(|&(ref w, center)| widgets::clip_contains(w.clip(), center, &ssize, hidpi))
+            .collect();
+
+        // The focus is lost when the widget is gone or the pointer is used
+        let mut nav = inner
+            .interaction
+            .nav
+            .filter(|id| candidates.iter().any(|&(ref w, _)| w.id() == *id));
+        if inner.pointer.pressed {
+            nav = None;
+        }
+
+        inner.interaction.nav_step = 0.0;
+        let inputs: Vec<NavInput> = inner.nav_inputs.drain(..).collect();
+
+        for input in inputs {
+            let current = nav.and_then(|id| candidates.iter().find(|&&(ref w, _)| w.id() == id));
+
+            match (input, current) {
+                // The first input focuses the first widget
+                (NavInput::Activate, None) => (),
+                (_, None) => nav = candidates.first().map(|&(ref w, _)| w.id()),
+                (NavInput::Activate, Some(&(ref w, _))) => {
+                    if !w.is_focusable() {
+                        inner.interaction.clicked = Some(w.id());
+                    } else if inner.focus == Some(w.id()) {
+                        inner.focus = None;
+                    } else {
+                        inner.focus = Some(w.id());
+                        inner.caret = usize::max_value();
+                        inner.selection_anchor = None;
+                    }
+                }
+                // Left and right change the value of a slider
+                (NavInput::Left, Some(&(ref w, _))) if is_slider(w) => {
+                    inner.interaction.nav_step -= 1.0;
+                }
+                (NavInput::Right, Some(&(ref w, _))) if is_slider(w) => {
+                    inner.interaction.nav_step += 1.0;
+                }
+                (_, Some(&(ref w, center))) => {
+                    nav = nearest(&candidates, center, input, ssize).or(nav);
+
+                    // The text field loses the keyboard focus when the navigation leaves it
+                    if nav != Some(w.id()) && inner.focus == Some(w.id()) {
+                        inner.focus = None;
+                    }
+                }
+            }
+        }
+
+        inner.interaction.nav = nav;
+    }
+}
+
+/// Set the opacity of all surfaces of the game object of a widget
+fn set_opacity(go: &Rc<RefCell<GameObject>>, opacity: f32) {
+    let gobj = go.borrow();
+    if let Some((mesh, _)) = gobj.find_component::<Mesh>() {
+        for surface in mesh.surfaces.iter() {
+            surface.material.set("uOpacity", opacity);
+        }
+    }
+}
+
+fn is_slider(w: &widgets::Widget) -> bool {
+    match w {
+        &widgets::Widget::Slider(_) => true,
+        _ => false,
+    }
+}
+
+/// The nearest widget of `candidates` from the ndc point `from` in the direction of `input`.
+/// The distance across the direction is weighted, such that the widgets in line are preferred
+fn nearest(
+    candidates: &[(Rc<widgets::Widget>, (f32, f32))],
+    from: (f32, f32),
+    input: NavInput,
+    ssize: (u32, u32),
+) -> Option<u32> {
+    candidates
+        .iter()
+        .filter_map(|&(ref w, (x, y))| {
+            // Ndc y is upwards
+            let dx = (x - from.0) * ssize.0 as f32;
+            let dy = (y - from.1) * ssize.1 as f32;
+            let (along, across) = match input {
+                NavInput::Up => (dy, dx),
+                NavInput::Down => (-dy, dx),
+                NavInput::Left => (-dx, dy),
+                NavInput::Right => (dx, dy),
+                NavInput::Activate => return None,
+            };
+
+            if along > 0.0 {
+                Some((along + across.abs() * 2.0, w.id()))
+            } else {
+                None
+            }
+        })
+        .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal))
+        .map(|(_, id)| id)
+}
+
+/// Ids of the widgets in the top most modal, None if there is no modal
+fn capture_ids(render_list: &[Rc<widgets::Widget>]) -> Option<HashSet<u32>> {
+    render_list
+        .iter()
+        .enumerate()
+        .rev()
+        .filter_map(|(i, w)| w.capture_len().map(|len| (i, len)))
+        .next()
+        .map(|(i, len)| {
+            render_list
+                .iter()
+                .skip(i)
+                .take(len)
+                .map(|w| w.id())
+                .collect()
+        })
+}
+
+/// Whether the widget `id` can be interacted in the captured widgets
+fn is_captured(capture: &Option<HashSet<u32>>, id: u32) -> bool {
+    capture.as_ref().map_or(true, |ids| ids.contains(&id))
 }
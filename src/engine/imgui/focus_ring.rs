@@ -0,0 +1,120 @@
+use super::image;
+use super::instance::ImguiState;
+use super::widgets;
+use super::widgets::Widget;
+use super::Metric;
+
+use engine::core::Aabb;
+use engine::{Asset, GameObject, IEngine, Mesh, MeshBuffer};
+use math::*;
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+/// Width of the ring in pixel, it is drawn outside of the widget
+const RING_WIDTH: f32 = 2.0;
+
+/// The id of the focus ring of the widget `id`
+pub fn ring_id(id: u32) -> u32 {
+    let mut hasher = DefaultHasher::new();
+    id.hash(&mut hasher);
+    "focus_ring".hash(&mut hasher);
+    hasher.finish() as u32
+}
+
+/// Frame around the widget which has the navigation focus, see `imgui::nav_input`
+#[derive(Debug, PartialEq)]
+pub struct FocusRing {
+    id: u32,
+    pub pos: Metric,
+    pub size: Metric,
+    pub state: ImguiState,
+}
+
+impl FocusRing {
+    /// The ring of the widget `id` placed at `pos` with `size`
+    pub fn new(id: u32, pos: Metric, size: Metric, state: ImguiState) -> Widget {
+        Widget::FocusRing(Self {
+            id: ring_id(id),
+            pos,
+            size,
+            state,
+        })
+    }
+
+    /// The rect of the focused widget
+    pub fn rect(&self, ssize: (u32, u32), hidpi: f32) -> Aabb {
+        widgets::compute_rect(&self.pos, &self.size, &self.state.pivot, &ssize, hidpi)
+    }
+
+    pub fn bind(
+        &self,
+        ssize: (u32, u32),
+        parent: &GameObject,
+        engine: &mut IEngine,
+    ) -> Rc<RefCell<GameObject>> {
+        let hidpi = engine.hidpi_factor();
+        let (w, h) = image::compute_size_to_ndc(&self.size, &ssize, hidpi);
+        let (bx, by) =
+            image::compute_size_to_ndc(&Metric::Pixel(RING_WIDTH, RING_WIDTH), &ssize, hidpi);
+
+        let mut mesh = Mesh::new();
+        let color = self.state.style.focus_color;
+
+        // Top, bottom, left and right edges, the origin is the top-left of the widget
+        let edges = [
+            ((-bx, by), (w + bx * 2.0, by)),
+            ((-bx, -h), (w + bx * 2.0, by)),
+            ((-bx, 0.0), (bx, h)),
+            ((w, 0.0), (bx, h)),
+        ];
+
+        for &((x, y), size) in edges.iter() {
+            let mut quad = image::make_quad_mesh_data(size);
+            quad.translate(Vector3::new(x, y, 0.0));
+
+            mesh.add_surface(
+                MeshBuffer::new(quad),
+                widgets::new_color_material(engine, color),
+            );
+        }
+
+        widgets::clip_mesh(&mesh, &self.state, ssize, hidpi);
+        widgets::transform_mesh(&mesh, &self.state, &self.rect(ssize, hidpi), ssize, hidpi);
+        mesh.layer = self.state.layer;
+
+        // Game Object
+        let go = engine.new_game_object(parent);
+        let mut gomut = go.borrow_mut();
+
+        let mut gtrans = gomut.transform.global();
+        let rect = self.rect(ssize, hidpi);
+        gtrans.disp += Vector3::new(rect.min.x, rect.max.y, 0.0);
+        gomut.transform.set_global(gtrans);
+        gomut.add_component(mesh);
+        drop(gomut);
+
+        go
+    }
+}
+
+impl widgets::WidgetBinder for FocusRing {
+    fn id(&self) -> u32 {
+        self.id
+    }
+
+    fn is_same(&self, other: &Widget) -> bool {
+        match other {
+            &Widget::FocusRing(ref ring) => ring == self,
+            _ => false,
+        }
+    }
+
+    fn is_moved(&self, other: &Widget) -> bool {
+        match other {
+            &Widget::FocusRing(ref ring) => ring.size == self.size && ring.state == self.state,
+            _ => false,
+        }
+    }
+}
@@ -1,58 +1,30 @@
-use super::Metric;
-use super::instance::ImguiState;
+use super::{Color, Metric};
+use super::animation::Animation;
+use super::instance::{ClipRect, ImguiState, WidgetTransform};
 use super::widgets;
 use super::widgets::Widget;
 
+use engine::core::Aabb;
+use engine::mesh_util;
 use engine::{Asset, GameObject, IEngine, Material, Mesh, MeshBuffer, MeshData, RenderQueue,
-             Texture};
+             Texture, TextureWrap};
+use math::{Matrix4, Vector2, Vector3, Vector4};
 use std::cell::RefCell;
 use std::fmt::Debug;
 use std::rc::Rc;
 
-fn make_quad_mesh_data(ndc_size: (f32, f32)) -> MeshData {
-    let w = ndc_size.0;
-    let h = ndc_size.1;
-
-    let vertices: Vec<f32> = vec![
-            0.0, 0.0, 0.0,     // 0
-            0.0, -h, 0.0,    // 1
-            w, -h, 0.0,     // 2
-            w, 0.0, 0.0       // 3
-        ];
-
-    let uvs: Vec<f32> = vec![
-            // Top face
-            0.0, 1.0,
-            0.0, 0.0,
-            1.0, 0.0,
-            1.0, 1.0,
-        ];
-
-    let indices: Vec<u16> = vec![
-        0, 1, 2, 0, 2, 3 // Top face
-    ];
-
-    MeshData {
-        vertices: vertices,
-        uvs: Some(uvs),
-        normals: None,
-        indices: indices,
-        tangents: None,
-        bitangents: None,
-    }
-}
-
-fn compute_size_to_ndc(size: &Metric, ssize: &(u32, u32), hidpi: f32) -> (f32, f32) {
-    let (x, y) = match size {
-        &Metric::Native(px, py) => (px * 2.0, py * 2.0),
-        &Metric::Pixel(px, py) => widgets::to_pixel_pos(px, py, ssize, hidpi),
-        &Metric::Mixed((ax, ay), (bx, by)) => {
-            let vp = widgets::to_pixel_pos(bx, by, ssize, hidpi);
-            (ax * 2.0 + vp.0, ay * 2.0 + vp.1)
-        }
-    };
+pub fn make_quad_mesh_data(ndc_size: (f32, f32)) -> MeshData {
+    make_uv_quad_mesh_data(ndc_size, (0.0, 0.0, 1.0, 1.0))
+}
+
+/// Quad which shows the part `uv_rect` (u0, v0, u1, v1) of the texture,
+/// in fractions of the texture from its top-left
+pub fn make_uv_quad_mesh_data(ndc_size: (f32, f32), uv_rect: (f32, f32, f32, f32)) -> MeshData {
+    mesh_util::uv_quad_mesh_data(ndc_size, uv_rect)
+}
 
-    return (x, y);
+pub fn compute_size_to_ndc(size: &Metric, ssize: &(u32, u32), hidpi: f32) -> (f32, f32) {
+    size.to_ndc(*ssize, hidpi)
 }
 
 #[derive(Debug)]
@@ -88,13 +60,95 @@ impl From<Rc<Texture>> for ImageKind {
     }
 }
 
+/// Border insets of a nine-slice image, in (left, top, right, bottom) order
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum NineSliceBorder {
+    /// Insets in texture pixels, the corners are drawn in the same size in logical pixels
+    Pixel(f32, f32, f32, f32),
+    /// Insets in uv fractions of the texture
+    Uv(f32, f32, f32, f32),
+}
+
+impl NineSliceBorder {
+    /// Return (pixel insets, uv insets)
+    fn insets(&self, tex_size: (u32, u32)) -> ((f32, f32, f32, f32), (f32, f32, f32, f32)) {
+        let (tw, th) = (tex_size.0 as f32, tex_size.1 as f32);
+
+        match self {
+            &NineSliceBorder::Pixel(l, t, r, b) => ((l, t, r, b), (l / tw, t / th, r / tw, b / th)),
+            &NineSliceBorder::Uv(l, t, r, b) => ((l * tw, t * th, r * tw, b * th), (l, t, r, b)),
+        }
+    }
+}
+
+/// How `imgui::image` fits the texture to the size of the image
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FitMode {
+    /// Stretch the texture to the size
+    Stretch,
+    /// Scale the texture to fit in the size keeping its aspect ratio, centered
+    Contain,
+    /// Scale the texture to cover the size keeping its aspect ratio, centered and cropped
+    Cover,
+    /// Repeat the texture in its size in logical pixels from the top-left.
+    /// The wrap of the texture is set to repeat, WebGL 1 requires a power of two size for it
+    TileRepeat,
+}
+
+/// A value which is set to the material every frame, such that the widget
+/// is not bound again when it changes
+#[derive(Debug, Clone, Copy)]
+struct Uniform<T>(T);
+
+impl<T> PartialEq for Uniform<T> {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+/// Frames of a sprite sheet, laid out from left to right in a single row of the texture
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct SpriteSheet {
+    frame_count: u32,
+    fps: f32,
+}
+
+#[derive(Debug, PartialEq)]
+struct NineSlice {
+    border: NineSliceBorder,
+    /// The texture size, None if the texture is not loaded yet
+    tex_size: Option<(u32, u32)>,
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Image {
     id: u32,
     pos: Metric,
     size: Metric,
     pivot: Metric,
+    /// Tint color, it is not applied to custom materials
+    color: Color,
+    background_color: Option<Color>,
+    /// Clip rects of the enclosing scroll regions, custom materials are not clipped
+    pub clip: Vec<ClipRect>,
+    /// Rotation and scale around the pivot, it is not applied to custom materials
+    transform: WidgetTransform,
+    pub layer: i32,
+    /// Transition of `imgui::animate`, the opacity is also applied to custom materials
+    pub animation: Option<Animation>,
+    /// Opacity of `imgui::alpha`, it is also applied to custom materials
+    pub alpha: f32,
     kind: ImageKind,
+    nine_slice: Option<NineSlice>,
+    /// The part (u0, v0, u1, v1) of the texture from its top-left, e.g. a sprite of an atlas
+    uv_rect: (f32, f32, f32, f32),
+    fit: FitMode,
+    /// The texture size of the fit modes except `Stretch`, None if it is not loaded yet
+    tex_size: Option<(u32, u32)>,
+    /// The frames of `imgui::image_animated`, the quad shows the first one
+    sprite: Option<SpriteSheet>,
+    /// The filled fraction of `imgui::radial_fill`, clockwise from the top
+    radial: Option<Uniform<f32>>,
 }
 
 impl Image {
@@ -107,19 +161,300 @@ impl Image {
             pos,
             size,
             pivot: state.pivot,
+            color: state.color,
+            background_color: state.background_color,
+            clip: state.clip,
+            transform: state.transform,
+            layer: state.layer,
+            animation: state.animation,
+            alpha: state.alpha.0,
             kind: t.into(),
+            nine_slice: None,
+            uv_rect: (0.0, 0.0, 1.0, 1.0),
+            fit: FitMode::Stretch,
+            tex_size: None,
+            sprite: None,
+            radial: None,
+        })
+    }
+
+    /// Image of the texture fitted to `size` by `fit`
+    pub fn new_fit(
+        id: u32,
+        pos: Metric,
+        size: Metric,
+        state: ImguiState,
+        tex: Rc<Texture>,
+        fit: FitMode,
+    ) -> Widget {
+        let tex_size = match fit {
+            FitMode::Stretch => None,
+            _ => tex.image_size(),
+        };
+
+        Widget::Image(Self {
+            id,
+            pos,
+            size,
+            pivot: state.pivot,
+            color: state.color,
+            background_color: state.background_color,
+            clip: state.clip,
+            transform: state.transform,
+            layer: state.layer,
+            animation: state.animation,
+            alpha: state.alpha.0,
+            kind: tex.into(),
+            nine_slice: None,
+            uv_rect: (0.0, 0.0, 1.0, 1.0),
+            fit,
+            tex_size,
+            sprite: None,
+            radial: None,
+        })
+    }
+
+    /// Image of the part `uv_rect` (u0, v0, u1, v1) of the texture
+    pub fn new_region(
+        id: u32,
+        pos: Metric,
+        size: Metric,
+        state: ImguiState,
+        tex: Rc<Texture>,
+        uv_rect: (f32, f32, f32, f32),
+    ) -> Widget {
+        Widget::Image(Self {
+            id,
+            pos,
+            size,
+            pivot: state.pivot,
+            color: state.color,
+            background_color: state.background_color,
+            clip: state.clip,
+            transform: state.transform,
+            layer: state.layer,
+            animation: state.animation,
+            alpha: state.alpha.0,
+            kind: tex.into(),
+            nine_slice: None,
+            uv_rect,
+            fit: FitMode::Stretch,
+            tex_size: None,
+            sprite: None,
+            radial: None,
         })
     }
 
-    fn create_material(&self, engine: &mut IEngine) -> Rc<Material> {
+    /// Image playing the `frame_count` frames of a sprite sheet at `fps`
+    pub fn new_animated(
+        id: u32,
+        pos: Metric,
+        size: Metric,
+        state: ImguiState,
+        tex: Rc<Texture>,
+        frame_count: u32,
+        fps: f32,
+    ) -> Widget {
+        let frame_count = frame_count.max(1);
+
+        Widget::Image(Self {
+            id,
+            pos,
+            size,
+            pivot: state.pivot,
+            color: state.color,
+            background_color: state.background_color,
+            clip: state.clip,
+            transform: state.transform,
+            layer: state.layer,
+            animation: state.animation,
+            alpha: state.alpha.0,
+            kind: tex.into(),
+            nine_slice: None,
+            uv_rect: (0.0, 0.0, 1.0 / frame_count as f32, 1.0),
+            fit: FitMode::Stretch,
+            tex_size: None,
+            sprite: Some(SpriteSheet { frame_count, fps }),
+            radial: None,
+        })
+    }
+
+    /// Image of the texture filled like a pie by `fraction` (0..1), clockwise from the top
+    pub fn new_radial(
+        id: u32,
+        pos: Metric,
+        size: Metric,
+        state: ImguiState,
+        tex: Rc<Texture>,
+        fraction: f32,
+    ) -> Widget {
+        Widget::Image(Self {
+            id,
+            pos,
+            size,
+            pivot: state.pivot,
+            color: state.color,
+            background_color: state.background_color,
+            clip: state.clip,
+            transform: state.transform,
+            layer: state.layer,
+            animation: state.animation,
+            alpha: state.alpha.0,
+            kind: tex.into(),
+            nine_slice: None,
+            uv_rect: (0.0, 0.0, 1.0, 1.0),
+            fit: FitMode::Stretch,
+            tex_size: None,
+            sprite: None,
+            radial: Some(Uniform(fraction.max(0.0).min(1.0))),
+        })
+    }
+
+    pub fn new_nine_slice(
+        id: u32,
+        pos: Metric,
+        size: Metric,
+        state: ImguiState,
+        tex: Rc<Texture>,
+        border: NineSliceBorder,
+    ) -> Widget {
+        let tex_size = tex.image_size();
+
+        Widget::Image(Self {
+            id,
+            pos,
+            size,
+            pivot: state.pivot,
+            color: state.color,
+            background_color: state.background_color,
+            clip: state.clip,
+            transform: state.transform,
+            layer: state.layer,
+            animation: state.animation,
+            alpha: state.alpha.0,
+            kind: tex.into(),
+            nine_slice: Some(NineSlice { border, tex_size }),
+            uv_rect: (0.0, 0.0, 1.0, 1.0),
+            fit: FitMode::Stretch,
+            tex_size: None,
+            sprite: None,
+            radial: None,
+        })
+    }
+
+    pub fn rect(&self, ssize: (u32, u32), hidpi: f32) -> Aabb {
+        widgets::compute_rect(&self.pos, &self.size, &self.pivot, &ssize, hidpi)
+    }
+
+    /// The uv offset of the frame of the sprite sheet shown at `now`, None if not animated
+    fn sprite_offset(&self, now: f64) -> Option<Vector2<f32>> {
+        self.sprite.map(|sprite| {
+            let frame = (now * sprite.fps as f64) as u64 % sprite.frame_count as u64;
+            Vector2::new(frame as f32 / sprite.frame_count as f32, 0.0)
+        })
+    }
+
+    /// Whether the material is updated every frame by `update_material`
+    pub fn is_dynamic(&self) -> bool {
+        self.sprite.is_some() || self.radial.is_some()
+    }
+
+    /// Set the sprite frame shown at `now` and the filled fraction to the material of the image
+    pub fn update_material(&self, material: &Material, now: f64) {
+        if let Some(offset) = self.sprite_offset(now) {
+            material.set("uUvOffset", offset);
+        }
+
+        if let Some(Uniform(fraction)) = self.radial {
+            material.set("uFraction", fraction);
+        }
+    }
+
+    fn make_mesh_data(&self, ssize: (u32, u32), hidpi: f32) -> MeshData {
+        let ndc_size = compute_size_to_ndc(&self.size, &ssize, hidpi);
+
+        match self.nine_slice {
+            Some(NineSlice {
+                border,
+                tex_size: Some(tex_size),
+            }) => {
+                let ((l, t, r, b), uv_insets) = border.insets(tex_size);
+                let (nl, nt) = compute_size_to_ndc(&Metric::Pixel(l, t), &ssize, hidpi);
+                let (nr, nb) = compute_size_to_ndc(&Metric::Pixel(r, b), &ssize, hidpi);
+
+                widgets::make_nine_slice_mesh_data(ndc_size, (nl, nt, nr, nb), uv_insets)
+            }
+            // Stretch the whole texture until the size of texture is known
+            _ => match self.tex_size {
+                Some(tex_size) => self.make_fit_mesh_data(tex_size, ssize, hidpi),
+                None => make_uv_quad_mesh_data(ndc_size, self.uv_rect),
+            },
+        }
+    }
+
+    /// The quad of the fit mode with the known texture size
+    fn make_fit_mesh_data(&self, tex_size: (u32, u32), ssize: (u32, u32), hidpi: f32) -> MeshData {
+        let ndc_size = compute_size_to_ndc(&self.size, &ssize, hidpi);
+        let (w, h) = widgets::to_pixel_size(&self.size, &ssize, hidpi);
+        let (tw, th) = (tex_size.0.max(1) as f32, tex_size.1.max(1) as f32);
+
+        match self.fit {
+            FitMode::Stretch => make_uv_quad_mesh_data(ndc_size, self.uv_rect),
+            FitMode::Contain => {
+                let s = (w / tw).min(h / th);
+                let (cw, ch) = (tw * s, th * s);
+                let mut quad =
+                    make_quad_mesh_data(compute_size_to_ndc(&Metric::Pixel(cw, ch), &ssize, hidpi));
+
+                let offset = Metric::Pixel((w - cw) * 0.5, (h - ch) * 0.5);
+                let (ox, oy) = compute_size_to_ndc(&offset, &ssize, hidpi);
+                quad.translate(Vector3::new(ox, -oy, 0.0));
+                quad
+            }
+            FitMode::Cover => {
+                // The visible part of the texture in uv
+                let s = (w / tw).max(h / th);
+                let (uw, vh) = (w / (tw * s), h / (th * s));
+                let uv_rect = (
+                    (1.0 - uw) * 0.5,
+                    (1.0 - vh) * 0.5,
+                    (1.0 + uw) * 0.5,
+                    (1.0 + vh) * 0.5,
+                );
+                make_uv_quad_mesh_data(ndc_size, uv_rect)
+            }
+            FitMode::TileRepeat => make_uv_quad_mesh_data(ndc_size, (0.0, 0.0, w / tw, h / th)),
+        }
+    }
+
+    fn create_material(
+        &self,
+        engine: &mut IEngine,
+        clip_rect: Vector4<f32>,
+        transform: Matrix4<f32>,
+    ) -> Rc<Material> {
         match self.kind {
             ImageKind::Material(ref m) => m.0.clone(),
             ImageKind::Texture(ref t) => {
                 let db = engine.asset_system();
 
-                let mut m = Material::new(db.new_program("default_ui"));
+                let program = match self.radial {
+                    Some(_) => "default_ui_radial",
+                    None => "default_ui",
+                };
+
+                let mut m = Material::new(db.new_program(program));
                 m.render_queue = RenderQueue::UI;
+                m.states.alpha_blending = Some(true);
                 m.set("uDiffuse", t.0.clone());
+                m.set("uColor", self.color.to_vec4());
+                m.set("uClipRect", clip_rect);
+                m.set("uTransform", transform);
+                m.set("uUvOffset", Vector2::new(0.0, 0.0));
+                m.set("uOpacity", 1.0);
+                if let Some(Uniform(fraction)) = self.radial {
+                    m.set("uFraction", fraction);
+                }
                 Rc::new(m)
             }
         }
@@ -134,14 +469,39 @@ impl Image {
         let hidpi = engine.hidpi_factor();
 
         // Mesh Data
-        let meshdata = make_quad_mesh_data(compute_size_to_ndc(&self.size, &ssize, hidpi));
+        let meshdata = self.make_mesh_data(ssize, hidpi);
+
+        // The tiles are repeated by the texture wrap
+        if let (FitMode::TileRepeat, &ImageKind::Texture(ref t)) = (self.fit, &self.kind) {
+            t.0.set_wrap(TextureWrap::Repeat, TextureWrap::Repeat);
+        }
 
         // Material
-        let material = self.create_material(engine);
+        let clip_rect = widgets::clip_rect(&self.clip, &ssize, hidpi);
+        let transform = widgets::transform_matrix(
+            &self.transform,
+            &self.pivot,
+            &meshdata.compute_bound().local_aabb(),
+            (0.0, 0.0),
+            ssize,
+            hidpi,
+        );
+        let material = self.create_material(engine, clip_rect, transform);
 
         //Mesh
         let mut mesh = Mesh::new();
+
+        // Background
+        if let Some(bg) = self.background_color {
+            let quad = make_quad_mesh_data(compute_size_to_ndc(&self.size, &ssize, hidpi));
+            let bg_material = widgets::new_color_material(engine, bg);
+            bg_material.set("uClipRect", clip_rect);
+            bg_material.set("uTransform", transform);
+            mesh.add_surface(MeshBuffer::new(quad), bg_material);
+        }
+
         mesh.add_surface(MeshBuffer::new(meshdata), material);
+        mesh.layer = self.layer;
 
         // Game Object
         let go = engine.new_game_object(parent);
@@ -174,4 +534,28 @@ impl widgets::WidgetBinder for Image {
             _ => false,
         }
     }
+
+    fn is_moved(&self, other: &Widget) -> bool {
+        match other {
+            &Widget::Image(ref img) => {
+                img.size == self.size
+                    && img.pivot == self.pivot
+                    && img.color == self.color
+                    && img.background_color == self.background_color
+                    && img.clip == self.clip
+                    && img.transform == self.transform
+                    && img.layer == self.layer
+                    && img.animation == self.animation
+                    && img.alpha == self.alpha
+                    && img.kind == self.kind
+                    && img.nine_slice == self.nine_slice
+                    && img.uv_rect == self.uv_rect
+                    && img.fit == self.fit
+                    && img.tex_size == self.tex_size
+                    && img.sprite == self.sprite
+                    && img.radial == self.radial
+            }
+            _ => false,
+        }
+    }
 }
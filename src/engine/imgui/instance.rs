@@ -1,20 +1,431 @@
+use super::anchor::{Anchor, SafeArea};
+use super::animation::Animation;
+use super::bitmap_font::BitmapFont;
+use super::recorder::Recorder;
+use super::rich_text::IconAtlas;
+use super::shortcut::KeyChord;
+use super::style::{Style, TextStyle};
 use super::widgets;
+use super::Color;
+use engine::core::Aabb;
+use engine::{Font, Texture};
+use math::*;
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
-#[derive(Default, Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, Clone)]
+pub struct FontRef(pub Rc<Font>);
+
+impl PartialEq for FontRef {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Clip rect of a scroll region, `pos` is the top-left corner
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ClipRect {
+    pub pos: super::Metric,
+    pub size: super::Metric,
+}
+
+/// Rotation and scale of a widget around its pivot
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct WidgetTransform {
+    /// Counterclockwise rotation in radians
+    pub rotation: f32,
+    pub scale: (f32, f32),
+}
+
+impl Default for WidgetTransform {
+    fn default() -> WidgetTransform {
+        WidgetTransform {
+            rotation: 0.0,
+            scale: (1.0, 1.0),
+        }
+    }
+}
+
+impl WidgetTransform {
+    /// The matrix which transforms the ndc vertices of a widget around the ndc point `pivot`.
+    /// It is applied in screen space, such that the rotation is not skewed by the aspect ratio
+    pub fn matrix(&self, pivot: (f32, f32), ssize: &(u32, u32)) -> Matrix4<f32> {
+        let (sw, sh) = (ssize.0.max(1) as f32, ssize.1.max(1) as f32);
+
+        Matrix4::from_translation(Vector3::new(pivot.0, pivot.1, 0.0))
+            * Matrix4::from_nonuniform_scale(1.0 / sw, 1.0 / sh, 1.0)
+            * Matrix4::from_angle_z(Rad(self.rotation))
+            * Matrix4::from_nonuniform_scale(self.scale.0, self.scale.1, 1.0)
+            * Matrix4::from_nonuniform_scale(sw, sh, 1.0)
+            * Matrix4::from_translation(Vector3::new(-pivot.0, -pivot.1, 0.0))
+    }
+}
+
+/// Opacity of a widget, see `imgui::alpha`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Alpha(pub f32);
+
+impl Default for Alpha {
+    fn default() -> Alpha {
+        Alpha(1.0)
+    }
+}
+
+/// State of `imgui::debug_overlay`
+#[derive(Default, Debug, Clone, Copy)]
+pub struct DebugOverlay {
+    pub enabled: bool,
+    /// Time of the last frame
+    pub last_time: Option<f64>,
+    /// Smoothed frame time in seconds
+    pub frame_time: f64,
+}
+
+/// User scale of the pixel metrics and the fonts, see `imgui::set_ui_scale`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct UiScale(pub f32);
+
+impl Default for UiScale {
+    fn default() -> UiScale {
+        UiScale(1.0)
+    }
+}
+
+#[derive(Default, Debug, PartialEq, Clone)]
 pub struct ImguiState {
     pub pivot: super::Metric,
     pub text_align: super::TextAlign,
+    /// The locale is right-to-left, see `imgui::set_rtl_locale`
+    pub rtl: bool,
+    pub layout: Option<super::layout::Layout>,
+    /// TrueType font of labels, None for the font of the style
+    pub font: Option<FontRef>,
+    /// Font size in pixel, None for the default size of the font
+    pub font_size: Option<f32>,
+    /// Bitmap font of labels, it is used if there is no TrueType font
+    pub bitmap_font: Rc<BitmapFont>,
+    /// Atlas of the `[icon=name]` markup of labels
+    pub icon_atlas: Option<Rc<IconAtlas>>,
+    /// Tint color of labels and images
+    pub color: super::Color,
+    /// Background color of labels and images, None for no background
+    pub background_color: Option<super::Color>,
+    /// Shadow and outline of labels
+    pub text_style: TextStyle,
+    pub style: Rc<Style>,
+    /// Clip rects of the enclosing scroll regions, from outer to inner
+    pub clip: Vec<ClipRect>,
+    pub transform: WidgetTransform,
+    /// Drawing order, widgets of higher layers are drawn later
+    pub layer: i32,
+    /// Transition of the widgets, see `imgui::animate`
+    pub animation: Option<Animation>,
+    /// Opacity of the widgets, it is multiplied into all of their surfaces
+    pub alpha: Alpha,
+    /// Show the value of the hovered sample of the plots
+    pub plot_readout: bool,
+    /// Shape of the interactive widgets inside their rects, see `imgui::hit_shape`
+    pub hit_shape: HitShape,
+    /// False if the font is still loading, the widget will be rebound when it is ready
+    pub font_ready: bool,
+}
+
+impl ImguiState {
+    /// The TrueType font of texts, None for the bitmap font
+    pub fn text_font(&self) -> Option<&Rc<Font>> {
+        self.font
+            .as_ref()
+            .map(|f| &f.0)
+            .or(self.style.font.as_ref())
+    }
+
+    /// The text color of the style tinted by the color state
+    pub fn text_color(&self) -> Color {
+        self.color * self.style.text_color
+    }
+}
+
+/// Region of an interactive widget which takes the pointer, see `imgui::hit_shape`
+#[derive(Debug, Clone)]
+pub enum HitShape {
+    /// The whole rect of the widget
+    Rect,
+    /// The ellipse inscribed in the rect, e.g. for round buttons
+    Circle,
+    /// Polygon of the points (0..1, 0..1) in fractions of the rect from its top-left
+    Polygon(Vec<(f32, f32)>),
+    /// The pixels of the texture stretched to the rect whose alpha is above the
+    /// threshold (0..1), e.g. for irregular icons. The whole rect is hit until it is loaded
+    AlphaMask(Rc<Texture>, f32),
+}
+
+impl Default for HitShape {
+    fn default() -> HitShape {
+        HitShape::Rect
+    }
+}
+
+impl PartialEq for HitShape {
+    fn eq(&self, other: &HitShape) -> bool {
+        match (self, other) {
+            (&HitShape::Rect, &HitShape::Rect) | (&HitShape::Circle, &HitShape::Circle) => true,
+            (&HitShape::Polygon(ref a), &HitShape::Polygon(ref b)) => a == b,
+            (&HitShape::AlphaMask(ref a, ta), &HitShape::AlphaMask(ref b, tb)) => {
+                Rc::ptr_eq(a, b) && ta == tb
+            }
+            _ => false,
+        }
+    }
+}
+
+impl HitShape {
+    /// Whether the point `p` (0..1, 0..1) in fractions of the rect from its top-left
+    /// is inside the shape
+    pub fn contains(&self, p: (f32, f32)) -> bool {
+        match self {
+            &HitShape::Rect => true,
+            &HitShape::Circle => {
+                let (dx, dy) = (p.0 - 0.5, p.1 - 0.5);
+                dx * dx + dy * dy <= 0.25
+            }
+            &HitShape::Polygon(ref points) => {
+                // Even-odd rule, count the edges crossing the ray to the right
+                let mut inside = false;
+                let mut j = points.len().wrapping_sub(1);
+                for i in 0..points.len() {
+                    let (a, b) = (points[i], points[j]);
+                    if (a.1 > p.1) != (b.1 > p.1)
+                        && p.0 < (b.0 - a.0) * (p.1 - a.1) / (b.1 - a.1) + a.0
+                    {
+                        inside = !inside;
+                    }
+                    j = i;
+                }
+                inside
+            }
+            &HitShape::AlphaMask(ref tex, threshold) => tex
+                .alpha_at(p.0, p.1)
+                .map(|a| a as f32 / 255.0 > threshold)
+                .unwrap_or(true),
+        }
+    }
+}
+
+/// Input device which drives the pointer
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum PointerKind {
+    Mouse,
+    Touch,
+}
+
+impl Default for PointerKind {
+    fn default() -> PointerKind {
+        PointerKind::Mouse
+    }
+}
+
+/// Pointer state of the mouse or the touch, fed by `imgui::handle_event`
+/// and `imgui::touch_start` etc. Position is in logical pixels
+#[derive(Default, Debug, Copy, Clone)]
+pub struct PointerState {
+    pub kind: PointerKind,
+    pub pos: (f32, f32),
+    /// Position at the beginning of the frame
+    pub prev_pos: (f32, f32),
+    /// Position where the pointer was pressed
+    pub press_pos: (f32, f32),
+    pub down: bool,
+    pub pressed: bool,
+    pub released: bool,
+    /// Wheel delta of this frame in pixel, see `imgui::mouse_wheel`
+    pub wheel: (f32, f32),
+    /// The touch which drives the pointer, the other fingers are ignored
+    pub touch_id: Option<u64>,
+    /// Time when the last touch was ended
+    pub touch_end: f64,
+}
+
+impl PointerState {
+    /// Whether the pointer hovers the widgets, a finger only hovers while touching
+    pub fn hovers(&self) -> bool {
+        self.kind == PointerKind::Mouse || self.down || self.released
+    }
+
+    pub fn press(&mut self, kind: PointerKind, pos: (f32, f32)) {
+        self.kind = kind;
+        self.pos = pos;
+        self.press_pos = pos;
+        self.down = true;
+        self.pressed = true;
+    }
+
+    pub fn release(&mut self) {
+        self.down = false;
+        self.released = true;
+    }
+}
+
+/// Result of the hit-testing done in `pre_render`, it is consumed by
+/// the widgets in the next frame
+#[derive(Default, Debug, Copy, Clone)]
+pub struct Interaction {
+    pub hot: Option<u32>,
+    pub active: Option<u32>,
+    pub clicked: Option<u32>,
+    /// The widget which was active when the mouse is released, even outside of it
+    pub released: Option<u32>,
+    /// Value (0..1) under the mouse of the active draggable widget
+    pub drag_value: Option<f32>,
+    /// Position (0..1, 0..1) under the mouse of the active 2d draggable widget
+    pub drag_point: Option<(f32, f32)>,
+    /// The widget which has the navigation focus
+    pub nav: Option<u32>,
+    /// Step (-1, 0 or 1) of the navigated slider by the left and right inputs
+    pub nav_step: f32,
+}
+
+/// Keyboard input fed by `imgui::handle_event`, the caret moves
+/// extend the selection if their flag is set (shift is held)
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum KeyInput {
+    Char(char),
+    Backspace,
+    Delete,
+    Left(bool),
+    Right(bool),
+    Home(bool),
+    End(bool),
+    SelectAll,
+    Copy,
+    Cut,
+    Paste,
+}
+
+/// Navigation input fed by `imgui::handle_event`, `imgui::handle_gamepad`
+/// or `imgui::nav_input`
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum NavInput {
+    Up,
+    Down,
+    Left,
+    Right,
+    /// Click the focused widget, or toggle the keyboard focus of a text field
+    Activate,
+}
+
+/// Hover timer of the widget which has a tooltip
+#[derive(Debug, Copy, Clone)]
+pub struct HoverTimer {
+    pub id: u32,
+    /// Time when the hovering is started
+    pub since: f64,
+    /// Whether the tooltip is requested in the current frame
+    pub alive: bool,
+}
+
+/// The payload dragged from a drag source
+#[derive(Debug, Clone)]
+pub struct DragState {
+    pub payload: String,
+    /// Mouse position where the drag is started
+    pub origin: (f32, f32),
+    /// False until the mouse is moved far enough
+    pub dragging: bool,
+}
+
+/// Scope of widget ids, see `imgui::push_id`
+#[derive(Default, Debug, Copy, Clone)]
+pub struct IdScope {
+    /// Hash of the labels of the scope and its parents
+    pub seed: u64,
+    /// Number of widgets added in the scope
+    pub count: u32,
 }
 
 #[derive(Default, Debug)]
 pub struct ImguiRaw {
-    pub id: u32,
+    /// Number of widgets added in this frame
+    pub count: u32,
+    pub id_scope: IdScope,
+    /// The parent id scopes
+    pub id_stack: Vec<IdScope>,
     pub state: ImguiState,
+    pub pointer: PointerState,
+    pub interaction: Interaction,
+    /// The widget which has the keyboard focus
+    pub focus: Option<u32>,
+    /// Caret position of the focused widget in chars
+    pub caret: usize,
+    /// The other end of the selected chars of the focused widget, None if nothing is selected
+    pub selection_anchor: Option<usize>,
+    /// The IME composition in progress, which is shown at the caret of the focused widget,
+    /// see `imgui::composition_update`
+    pub composition: Option<String>,
+    /// Whether the pointer and the keyboard were used by the ui in the last `pre_render`,
+    /// see `imgui::wants_mouse`
+    pub wants_mouse: bool,
+    pub wants_keyboard: bool,
+    pub keys: Vec<KeyInput>,
+    /// Navigation inputs of this frame, they are consumed in `pre_render`
+    pub nav_inputs: Vec<NavInput>,
+    /// Key chords pressed in this frame for `imgui::shortcut`, they are consumed in `pre_render`
+    pub chords: Vec<KeyChord>,
+    /// Recorded and replayed inputs, see `imgui::start_recording`
+    pub recorder: Recorder,
+    /// Navigation buttons of the gamepad in the last frame, see `imgui::handle_gamepad`
+    pub pad_buttons: [bool; 5],
+    pub layout_stack: Vec<Option<super::layout::Layout>>,
+    pub style_stack: Vec<Rc<Style>>,
+    /// Indices in the render list of the scroll regions in the scope
+    pub scroll_stack: Vec<usize>,
+    /// Scroll offsets in pixel of the last frame, mirrored from `Context`
+    pub scroll: HashMap<u32, (f32, f32)>,
+    /// Position and size of the last placed widget, used by `imgui::tooltip`
+    pub last_rect: Option<(super::Metric, super::Metric)>,
+    pub hover: Option<HoverTimer>,
+    /// Hover time in seconds before a tooltip is shown, None for the default delay
+    pub tooltip_delay: Option<f64>,
+    /// Last picked (hue, saturation, value) of the color pickers,
+    /// such that the hue is kept for gray colors
+    pub picker_hsv: HashMap<u32, (f32, f32, f32)>,
+    /// Names of the opened modals
+    pub modals: Vec<String>,
+    /// Keys of the expanded tree nodes
+    pub tree_open: HashSet<u64>,
+    /// Keys of the tree nodes in the scope, from root to leaf
+    pub tree_stack: Vec<u64>,
+    /// Ndc rects of the widgets in the groups, see `imgui::drag_source`
+    pub group_stack: Vec<Aabb>,
+    pub drag: Option<DragState>,
+    /// Anchor of the widgets out of layouts, None to place them by the pivot
+    pub anchor: Option<Anchor>,
+    pub safe_area: SafeArea,
+    /// Keys of the animations which are still running, mirrored from `Context`
+    pub animating: HashSet<u64>,
+    /// Screen size and hidpi factor of the last rendered frame,
+    /// the hidpi factor is multiplied by the ui scale
+    pub screen_size: (u32, u32),
+    pub hidpi: f32,
+    pub ui_scale: UiScale,
+    pub debug_overlay: DebugOverlay,
     pub render_list: Vec<Rc<widgets::Widget>>,
 }
 
+impl ImguiRaw {
+    /// The id of the `n`-th next widget in the current id scope,
+    /// it only depends on the labels of the scopes and the position in the scope
+    pub fn next_id(&self, n: u32) -> u32 {
+        let mut hasher = DefaultHasher::new();
+        self.id_scope.seed.hash(&mut hasher);
+        (self.id_scope.count + n).hash(&mut hasher);
+        hasher.finish() as u32
+    }
+}
+
 pub struct Imgui {
     pub inner: Arc<Mutex<ImguiRaw>>,
 }
@@ -23,8 +434,21 @@ thread_local!(
     static INSTANCE: Arc<Mutex<ImguiRaw>> = Arc::new(Mutex::new(Default::default()))
 );
 
+thread_local!(
+    /// Instance of the `Ui` in scope, which the free functions use instead of the primary one
+    static CURRENT: RefCell<Option<Arc<Mutex<ImguiRaw>>>> = RefCell::new(None)
+);
+
 pub fn imgui_inst() -> Imgui {
+    let current = CURRENT.with(|c| c.borrow().clone());
+
     return Imgui {
-        inner: INSTANCE.with(|f| f.clone()),
+        inner: current.unwrap_or_else(|| INSTANCE.with(|f| f.clone())),
     };
 }
+
+/// Make `inner` the instance of the free functions, None for the primary one.
+/// Returns the previous one
+pub fn set_current(inner: Option<Arc<Mutex<ImguiRaw>>>) -> Option<Arc<Mutex<ImguiRaw>>> {
+    CURRENT.with(|c| c.replace(inner))
+}
@@ -1,128 +1,551 @@
+use super::bitmap_font::BitmapFont;
+use super::image;
 use super::instance::ImguiState;
+use super::rich_text::{IconAtlas, Markup, RichText, ICON_CHAR};
 use super::widgets;
 use super::widgets::Widget;
-use super::{Metric, TextAlign};
+use super::{Color, Metric, TextAlign};
 
-use engine::MeshData;
+use engine::core::Aabb;
+use engine::{Font, MeshData};
+use math::*;
 
-struct BitmapFontData {
+struct BitmapFontData<'a> {
     hidpi: f32,
     screen_size: (u32, u32),
-    texture_size: (u32, u32),
-    font_size: (u32, u32),
+    font: &'a BitmapFont,
+    scale: f32,
 }
 
-impl BitmapFontData {
-    fn texture_space_glyph_size(&self) -> (f32, f32) {
-        let icw = (self.font_size.0 as f32) / (self.texture_size.0 as f32);
-        let ich = (self.font_size.1 as f32) / (self.texture_size.1 as f32);
+impl<'a> BitmapFontData<'a> {
+    fn ndc_glyph_size(&self) -> (f32, f32) {
+        let s = self.scale * self.hidpi;
+        let cell_size = self.font.cell_size;
 
-        (icw, ich)
+        (
+            ((cell_size.0 as f32) / self.screen_size.0 as f32) * 2.0 * s,
+            ((cell_size.1 as f32) / self.screen_size.1 as f32) * 2.0 * s,
+        )
     }
+}
 
-    fn n_glyph_per_row(&self) -> u8 {
-        (self.texture_size.0 / self.font_size.0) as u8
+/// Advance of a char in pixel of a bitmap font scaled by `scale`,
+/// icons are squares of the glyph height
+fn bitmap_advance(font: &BitmapFont, scale: f32, c: char) -> f32 {
+    if c == ICON_CHAR {
+        font.cell_size.1 as f32 * scale
+    } else {
+        font.cell_size.0 as f32 * scale
     }
+}
 
-    fn ndc_glyph_size(&self) -> (f32, f32) {
-        (
-            ((self.font_size.0 as f32) / self.screen_size.0 as f32) * 2.0 * self.hidpi,
-            ((self.font_size.1 as f32) / self.screen_size.1 as f32) * 2.0 * self.hidpi,
-        )
+struct TextData<'a> {
+    text: &'a RichText,
+    align: TextAlign,
+    font_data: BitmapFontData<'a>,
+    /// Maximum width of a line in NDC, lines are word wrapped to fit in it
+    max_width: Option<f32>,
+    /// Atlas of the icons in the text, the icons are blank without it
+    icons: Option<&'a IconAtlas>,
+}
+
+/// Mesh data of a text, the icons are separated for the texture of the icon atlas
+#[derive(Default, Debug)]
+pub struct TextMeshData {
+    pub text: MeshData,
+    pub icons: MeshData,
+}
+
+impl TextMeshData {
+    /// The local bounds of the glyphs and the icons
+    pub fn bounds(&self) -> Aabb {
+        let mut bounds = self.text.compute_bound().local_aabb();
+        if self.icons.vertices.len() > 0 {
+            bounds.merge(&self.icons.compute_bound().local_aabb());
+        }
+
+        bounds
+    }
+
+    pub fn translate(&mut self, disp: Vector3f) {
+        self.text.translate(disp);
+        self.icons.translate(disp);
     }
 }
 
-struct TextData {
-    s: String,
-    align: TextAlign,
-    font_data: BitmapFontData,
+/// Copies of the glyph quads of `mesh` moved by each of the ndc `offsets`,
+/// e.g. for the shadow and the outline of a text. The vertex colors are dropped
+pub fn make_offset_copies(mesh: &MeshData, offsets: &[(f32, f32)]) -> MeshData {
+    let n = (mesh.vertices.len() / 3) as u16;
+    let mut vertices = Vec::with_capacity(mesh.vertices.len() * offsets.len());
+    let mut uvs = Vec::new();
+    let mut indices = Vec::with_capacity(mesh.indices.len() * offsets.len());
+
+    for (i, &(dx, dy)) in offsets.iter().enumerate() {
+        for v in mesh.vertices.chunks(3) {
+            vertices.extend_from_slice(&[v[0] + dx, v[1] + dy, v[2]]);
+        }
+        if let Some(ref u) = mesh.uvs {
+            uvs.extend_from_slice(u);
+        }
+        indices.extend(mesh.indices.iter().map(|&k| k + n * i as u16));
+    }
+
+    MeshData {
+        vertices: vertices,
+        uvs: mesh.uvs.as_ref().map(|_| uvs),
+        uvs2: None,
+        normals: None,
+        indices: indices,
+        tangents: None,
+        bitangents: None,
+        colors: None,
+        joints: None,
+        weights: None,
+        morph_targets: Vec::new(),
+    }
+}
+
+/// Quads of the glyphs or the icons of a text
+#[derive(Default)]
+struct QuadList {
+    vertices: Vec<f32>,
+    uvs: Vec<f32>,
+    colors: Vec<f32>,
+    indices: Vec<u16>,
+}
+
+impl QuadList {
+    /// Add a quad from the top-left `p0` to the bottom-right `p1` in ndc
+    fn push(&mut self, p0: (f32, f32), p1: (f32, f32), uv: (f32, f32, f32, f32), color: Color) {
+        let i = (self.vertices.len() / 3) as u16;
+        let ((x0, y0), (x1, y1)) = (p0, p1);
+        let (u0, v0, u1, v1) = uv;
+
+        self.vertices.extend_from_slice(&[
+            x0, y0, 0.0, // 0
+            x0, y1, 0.0, // 1
+            x1, y1, 0.0, // 2
+            x1, y0, 0.0, // 3
+        ]);
+
+        self.uvs.extend_from_slice(&[
+            u0, v0, // 0
+            u0, v1, // 1
+            u1, v1, // 2
+            u1, v0, // 3
+        ]);
+
+        for _ in 0..4 {
+            self.colors
+                .extend_from_slice(&[color.r, color.g, color.b, color.a]);
+        }
+
+        self.indices
+            .extend_from_slice(&[i, i + 1, i + 2, i, i + 2, i + 3]);
+    }
+
+    /// The mesh data, the vertex colors are kept only if `vertex_color`
+    fn into_mesh_data(self, vertex_color: bool) -> MeshData {
+        MeshData {
+            vertices: self.vertices,
+            uvs: Some(self.uvs),
+            uvs2: None,
+            normals: None,
+            indices: self.indices,
+            tangents: None,
+            bitangents: None,
+            colors: if vertex_color {
+                Some(self.colors)
+            } else {
+                None
+            },
+            joints: None,
+            weights: None,
+            morph_targets: Vec::new(),
+        }
+    }
+}
+
+/// Texture space (u0, v0, u1, v1) of the icon of `markup`, None if it is not in the atlas
+fn icon_uv(atlas: Option<&IconAtlas>, markup: &Markup) -> Option<(f32, f32, f32, f32)> {
+    match (atlas, markup.icon.as_ref()) {
+        (Some(atlas), Some(name)) => atlas.icon_uv(name),
+        _ => None,
+    }
+}
+
+/// Split `s` into lines, which are word wrapped to `max_width` if it is set.
+/// A word wider than `max_width` is broken into multiple lines.
+fn wrap_lines<F>(s: &str, max_width: Option<f32>, width: F) -> Vec<String>
+where
+    F: Fn(&str) -> f32,
+{
+    let max_width = match max_width {
+        Some(w) => w,
+        None => return s.split('\n').map(|l| l.into()).collect(),
+    };
+
+    let mut lines = Vec::new();
+
+    for para in s.split('\n') {
+        let mut line = String::new();
+
+        for word in para.split(' ') {
+            let candidate = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", line, word)
+            };
+
+            if width(&candidate) <= max_width {
+                line = candidate;
+                continue;
+            }
+
+            if !line.is_empty() {
+                lines.push(line);
+                line = String::new();
+            }
+
+            // Break the word if it is still too wide
+            for c in word.chars() {
+                line.push(c);
+                if width(&line) > max_width && line.chars().count() > 1 {
+                    line.pop();
+                    lines.push(line);
+                    line = c.to_string();
+                }
+            }
+        }
+
+        lines.push(line);
+    }
+
+    lines
 }
 
-fn make_text_mesh_data(text_data: TextData) -> MeshData {
-    let mut vertices = vec![];
-    let mut uvs = vec![];
-    let mut indices = vec![];
+/// Offset of a line of width `w` in a text of `max_width`, `align` is resolved
+/// by `TextAlign::resolve` before
+fn align_offset(align: TextAlign, max_width: f32, w: f32) -> f32 {
+    match align {
+        TextAlign::Left | TextAlign::Start => 0.0,
+        TextAlign::Right | TextAlign::End => max_width - w,
+        TextAlign::Center => (max_width - w) * 0.5,
+    }
+}
 
+fn make_text_mesh_data(text_data: TextData) -> TextMeshData {
     let bfont = &text_data.font_data;
 
-    let (icw, ich) = bfont.texture_space_glyph_size();
     let (gw, gh) = bfont.ndc_glyph_size();
+    let (sw, _) = bfont.screen_size;
+    let s = bfont.scale * bfont.hidpi;
+    let char_width = |c: char| bitmap_advance(bfont.font, s, c) * 2.0 / sw as f32;
+    let iw = char_width(ICON_CHAR);
+
+    let wrapped = wrap_lines(&text_data.text.plain, text_data.max_width, |l| {
+        l.chars().map(&char_width).sum()
+    });
+    let lines = text_data.text.visual_lines(&wrapped);
+
+    let widths: Vec<f32> = lines
+        .iter()
+        .map(|line| line.iter().map(|&(c, _)| char_width(c)).sum())
+        .collect();
+    let max_width = widths.iter().fold(0.0, |acc: f32, &w| acc.max(w));
 
-    let nrow = bfont.n_glyph_per_row();
+    let mut glyphs = QuadList::default();
+    let mut icons = QuadList::default();
     let mut base_y = 0.0;
 
-    let lines: Vec<&str> = text_data.s.split('\n').collect();
+    for (line, w) in lines.into_iter().zip(widths.into_iter()) {
+        let mut x = align_offset(text_data.align, max_width, w);
 
-    let max_len = lines.iter().fold(0, |acc, line| acc.max(line.len()));
+        for (c, markup) in line.into_iter() {
+            let color = markup.color.unwrap_or_default();
 
-    let mut i = 0;
-    for line in lines.into_iter() {
-        let x_offset = match text_data.align {
-            TextAlign::Left => 0.0,
-            TextAlign::Right => (max_len - line.len()) as f32 * gw,
-            TextAlign::Center => (max_len - line.len()) as f32 * gw * 0.5,
-        };
+            if c == ICON_CHAR {
+                if let Some(uv) = icon_uv(text_data.icons, markup) {
+                    icons.push((x, base_y), (x + iw, base_y - gh), uv, color);
+                }
+                x += iw;
+                continue;
+            }
 
-        for (cidx, c) in line.chars().enumerate() {
-            let mut c: u8 = c as u8;
-
-            // only support up to ascii 128
-            c = c.min(128);
-
-            let g_row = (c / nrow) as f32;
-            let g_col = (c % nrow) as f32;
-
-            let gx = (cidx as f32) * gw + x_offset;
-
-            vertices.append(&mut vec![
-                gx + 0.0, // 0
-                base_y,
-                0.0,
-                gx + 0.0, // 1
-                base_y - gh,
-                0.0,
-                gx + gw, // 2
-                base_y - gh,
-                0.0,
-                gx + gw, // 3
-                base_y,
-                0.0,
-            ]);
-
-            uvs.append(&mut vec![
-                g_col * icw + 0.0, // 0
-                g_row * ich,
-                g_col * icw + 0.0, // 1
-                g_row * ich + ich,
-                g_col * icw + icw, // 2
-                g_row * ich + ich,
-                g_col * icw + icw, // 3
-                g_row * ich,
-            ]);
-
-            indices.append(&mut vec![
-                i * 4,
-                i * 4 + 1,
-                i * 4 + 2,
-                i * 4 + 0,
-                i * 4 + 2,
-                i * 4 + 3, // Top face
-            ]);
-
-            i += 1;
+            let glyph = bfont.font.glyph_index(c);
+            glyphs.push(
+                (x, base_y),
+                (x + gw, base_y - gh),
+                bfont.font.glyph_uv(glyph),
+                color,
+            );
+            x += gw;
         }
 
         base_y -= gh * 2.0;
     }
 
-    MeshData {
-        vertices: vertices,
-        uvs: Some(uvs),
-        normals: None,
-        indices: indices,
-        tangents: None,
-        bitangents: None,
+    TextMeshData {
+        text: glyphs.into_mesh_data(text_data.text.has_colors()),
+        icons: icons.into_mesh_data(false),
+    }
+}
+
+fn make_bitmap_text_mesh_data(
+    text: &RichText,
+    align: TextAlign,
+    font: &BitmapFont,
+    scale: f32,
+    max_width: Option<f32>,
+    icons: Option<&IconAtlas>,
+    ssize: (u32, u32),
+    hidpi: f32,
+) -> TextMeshData {
+    make_text_mesh_data(TextData {
+        text,
+        align: align,
+        font_data: BitmapFontData {
+            hidpi,
+            screen_size: ssize,
+            font,
+            scale,
+        },
+        max_width,
+        icons,
+    })
+}
+
+/// Width in pixel of a line using a TrueType font, icons are squares of the font size
+fn ttf_line_width(font: &Font, line: &str, px: f32) -> f32 {
+    let icons = line.chars().filter(|&c| c == ICON_CHAR).count() as f32;
+    let line: String = line.chars().filter(|&c| c != ICON_CHAR).collect();
+
+    font.measure(&line, px).map(|(w, _)| w).unwrap_or(0.0) + icons * px
+}
+
+/// Build the mesh data of a text using a TrueType font, lines are word wrapped to
+/// `max_width` in NDC if it is set. The mesh is empty if the font is not loaded yet
+fn make_ttf_text_mesh_data(
+    text: &RichText,
+    align: TextAlign,
+    font: &Font,
+    px: f32,
+    max_width: Option<f32>,
+    icons: Option<&IconAtlas>,
+    ssize: (u32, u32),
+    hidpi: f32,
+) -> TextMeshData {
+    // Rasterize in physical pixels to keep the glyphs sharp
+    let px = px * hidpi;
+
+    let line_height = match font.line_height(px) {
+        Ok(h) => h,
+        Err(_) => return TextMeshData::default(),
+    };
+
+    let max_width = max_width.map(|w| w * ssize.0 as f32 * 0.5);
+    let wrapped = wrap_lines(&text.plain, max_width, |l| ttf_line_width(font, l, px));
+
+    // Glyph quads and icons of each line in pixel, the runs of the same color
+    // are laid out together
+    let mut lines = Vec::new();
+    let mut max_width: f32 = 0.0;
+    for (i, line) in text.visual_lines(&wrapped).into_iter().enumerate() {
+        let y = i as f32 * line_height;
+        let mut x = 0.0;
+        let mut glyphs = Vec::new();
+        let mut line_icons = Vec::new();
+
+        let mut k = 0;
+        while k < line.len() {
+            let (c, markup) = line[k];
+
+            if c == ICON_CHAR {
+                line_icons.push((x, y + (line_height - px) * 0.5, markup));
+                x += px;
+                k += 1;
+                continue;
+            }
+
+            let run: String = line[k..]
+                .iter()
+                .take_while(|&&(c, m)| c != ICON_CHAR && m.color == markup.color)
+                .map(|&(c, _)| c)
+                .collect();
+            k += run.chars().count();
+
+            match font.layout_line(&run, px, y) {
+                Ok((quads, w)) => {
+                    let color = markup.color.unwrap_or_default();
+                    glyphs.extend(quads.into_iter().map(|q| (x, q, color)));
+                    x += w;
+                }
+                Err(_) => return TextMeshData::default(),
+            }
+        }
+
+        max_width = max_width.max(x);
+        lines.push((glyphs, line_icons, x));
+    }
+
+    let mut glyph_quads = QuadList::default();
+    let mut icon_quads = QuadList::default();
+
+    let sx = 2.0 / ssize.0 as f32;
+    let sy = 2.0 / ssize.1 as f32;
+
+    for (glyphs, line_icons, w) in lines.into_iter() {
+        let x_offset = align_offset(align, max_width, w);
+
+        for (x, q, color) in glyphs.into_iter() {
+            let x = x + x_offset;
+            glyph_quads.push(
+                ((q.min.0 + x) * sx, -q.min.1 * sy),
+                ((q.max.0 + x) * sx, -q.max.1 * sy),
+                q.uv,
+                color,
+            );
+        }
+
+        for (x, y, markup) in line_icons.into_iter() {
+            if let Some(uv) = icon_uv(icons, markup) {
+                let x = x + x_offset;
+                icon_quads.push(
+                    (x * sx, -y * sy),
+                    ((x + px) * sx, -(y + px) * sy),
+                    uv,
+                    Color::default(),
+                );
+            }
+        }
+    }
+
+    TextMeshData {
+        text: glyph_quads.into_mesh_data(text.has_colors()),
+        icons: icon_quads.into_mesh_data(false),
+    }
+}
+
+/// Default font size in pixel of TrueType fonts
+const DEFAULT_FONT_SIZE: f32 = 16.0;
+
+fn make_text(
+    text: &RichText,
+    align: TextAlign,
+    state: &ImguiState,
+    max_width: Option<f32>,
+    ssize: (u32, u32),
+    hidpi: f32,
+) -> TextMeshData {
+    let icons = state.icon_atlas.as_ref().map(|a| a.as_ref());
+    let align = align.resolve(state.rtl);
+
+    match state.text_font() {
+        Some(font) => {
+            let px = state.font_size.unwrap_or(DEFAULT_FONT_SIZE);
+            make_ttf_text_mesh_data(text, align, font, px, max_width, icons, ssize, hidpi)
+        }
+        None => {
+            let bfont = &state.bitmap_font;
+            let scale = state
+                .font_size
+                .map_or(1.0, |px| px / bfont.cell_size.1 as f32);
+            make_bitmap_text_mesh_data(text, align, bfont, scale, max_width, icons, ssize, hidpi)
+        }
+    }
+}
+
+/// Build the mesh data of a text with the color markup in pixels, from the top-left of
+/// the text at the origin towards -y, e.g. for the texts out of the screen space.
+/// The TrueType `font` is rasterized at `px` if set, else `bitmap_font` is scaled to `px`
+pub fn make_pixel_text_mesh_data(
+    s: &str,
+    align: TextAlign,
+    font: Option<&Font>,
+    bitmap_font: &BitmapFont,
+    px: f32,
+) -> TextMeshData {
+    let text = RichText::parse(s);
+    let align = align.resolve(false);
+
+    // The ndc of a screen of 2 x 2 pixels are the pixels
+    let ssize = (2, 2);
+
+    match font {
+        Some(font) => make_ttf_text_mesh_data(&text, align, font, px, None, None, ssize, 1.0),
+        None => {
+            let scale = px / bitmap_font.cell_size.1 as f32;
+            make_bitmap_text_mesh_data(&text, align, bitmap_font, scale, None, None, ssize, 1.0)
+        }
+    }
+}
+
+/// Build the mesh data of a text using the font settings of `state`,
+/// lines are word wrapped to `max_width` in NDC if it is set
+pub fn make_state_text_mesh_data(
+    s: &str,
+    align: TextAlign,
+    state: &ImguiState,
+    max_width: Option<f32>,
+    ssize: (u32, u32),
+    hidpi: f32,
+) -> MeshData {
+    make_text(&RichText::plain(s), align, state, max_width, ssize, hidpi).text
+}
+
+/// Build the mesh data of a text with the color and icon markup, see `RichText::parse`
+pub fn make_rich_text_mesh_data(
+    s: &str,
+    align: TextAlign,
+    state: &ImguiState,
+    max_width: Option<f32>,
+    ssize: (u32, u32),
+    hidpi: f32,
+) -> TextMeshData {
+    make_text(&RichText::parse(s), align, state, max_width, ssize, hidpi)
+}
+
+fn text_size(text: &RichText, state: &ImguiState, max_width: Option<f32>) -> Metric {
+    if let Some(font) = state.text_font() {
+        let px = state.font_size.unwrap_or(DEFAULT_FONT_SIZE);
+        let lines = wrap_lines(&text.plain, max_width, |l| ttf_line_width(font, l, px));
+        let w = lines
+            .iter()
+            .fold(0.0, |acc: f32, l| acc.max(ttf_line_width(font, l, px)));
+
+        return match font.measure(&lines.join("\n"), px) {
+            Ok((_, h)) => Metric::Pixel(w, h),
+            Err(_) => Metric::Pixel(0.0, 0.0),
+        };
     }
+
+    let bfont = &state.bitmap_font;
+    let scale = state
+        .font_size
+        .map_or(1.0, |px| px / bfont.cell_size.1 as f32);
+    let line_width = |l: &str| -> f32 { l.chars().map(|c| bitmap_advance(bfont, scale, c)).sum() };
+
+    let lines = wrap_lines(&text.plain, max_width, &line_width);
+    let w = lines
+        .iter()
+        .fold(0.0, |acc: f32, l| acc.max(line_width(l.as_str())));
+
+    match bfont.text_size(&lines.join("\n")) {
+        Metric::Pixel(_, h) => Metric::Pixel(w, h * scale),
+        size => size,
+    }
+}
+
+/// The size of a text in pixel using the font settings of `state`,
+/// lines are word wrapped to `max_width` in pixel if it is set
+pub fn label_size(s: &str, state: &ImguiState, max_width: Option<f32>) -> Metric {
+    text_size(&RichText::plain(s), state, max_width)
+}
+
+/// The size of a text with the markup in pixel, see `label_size`
+pub fn rich_label_size(s: &str, state: &ImguiState, max_width: Option<f32>) -> Metric {
+    text_size(&RichText::parse(s), state, max_width)
 }
 
 #[derive(Debug, PartialEq)]
@@ -131,34 +554,50 @@ pub struct Label {
     pub pos: Metric,
     pub state: ImguiState,
     s: String,
+    /// Maximum width of the label, None if it is not word wrapped
+    max_width: Option<Metric>,
 }
 
 impl Label {
     pub fn new(id: u32, pos: Metric, state: ImguiState, s: String) -> Widget {
+        Self::new_wrapped(id, pos, None, state, s)
+    }
+
+    pub fn new_wrapped(
+        id: u32,
+        pos: Metric,
+        max_width: Option<Metric>,
+        state: ImguiState,
+        s: String,
+    ) -> Widget {
         Widget::Label(Self {
             id: id,
             pos: pos,
             state,
             s: s,
+            max_width,
         })
     }
 
-    pub fn bind(&self, ssize: (u32, u32), hidpi: f32) -> MeshData {
-        // Mesh Data
-        let meshdata = {
-            make_text_mesh_data(TextData {
-                s: self.s.clone(),
-                align: self.state.text_align,
-                font_data: BitmapFontData {
-                    hidpi,
-                    screen_size: ssize,
-                    texture_size: (128, 64),
-                    font_size: (8, 8),
-                },
-            })
-        };
+    /// The bitmap font of the label, it is used only if there is no TrueType font
+    pub fn bitmap_font(&self) -> &BitmapFont {
+        &self.state.bitmap_font
+    }
 
-        return meshdata;
+    /// The mesh data of the text and the icons
+    pub fn bind(&self, ssize: (u32, u32), hidpi: f32) -> TextMeshData {
+        let max_width = self
+            .max_width
+            .map(|w| image::compute_size_to_ndc(&w.horizontal(), &ssize, hidpi).0);
+
+        make_rich_text_mesh_data(
+            &self.s,
+            self.state.text_align,
+            &self.state,
+            max_width,
+            ssize,
+            hidpi,
+        )
     }
 }
 
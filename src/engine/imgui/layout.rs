@@ -0,0 +1,65 @@
+use super::Metric;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LayoutKind {
+    Vertical,
+    Horizontal,
+}
+
+/// Automatic layout, the cursor is advanced by each widget in the scope
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Layout {
+    pub kind: LayoutKind,
+    pub start: Metric,
+    pub cursor: Metric,
+    /// Spacing between widgets in pixel
+    pub spacing: f32,
+    /// The largest extent of placed widgets perpendicular to the layout direction
+    cross: Metric,
+}
+
+impl Layout {
+    pub fn new(kind: LayoutKind, pos: Metric, spacing: f32) -> Layout {
+        Layout {
+            kind,
+            start: pos,
+            cursor: pos,
+            spacing,
+            cross: Metric::Pixel(0.0, 0.0),
+        }
+    }
+
+    /// Place a widget of `size` at the cursor, `offset` is relative to the cursor.
+    /// Return the position of the widget
+    pub fn place(&mut self, offset: Metric, size: Metric) -> Metric {
+        let pos = self.cursor + offset;
+        let extent = offset + size;
+
+        match self.kind {
+            LayoutKind::Vertical => {
+                self.cursor = self.cursor + extent.vertical() + Metric::Pixel(0.0, self.spacing);
+                self.cross = self.cross.max(&extent.horizontal());
+            }
+            LayoutKind::Horizontal => {
+                self.cursor = self.cursor + extent.horizontal() + Metric::Pixel(self.spacing, 0.0);
+                self.cross = self.cross.max(&extent.vertical());
+            }
+        };
+
+        pos
+    }
+
+    /// The total size of all placed widgets
+    pub fn size(&self) -> Metric {
+        let (trailing, cross) = match self.kind {
+            LayoutKind::Vertical => (Metric::Pixel(0.0, self.spacing), self.cross.horizontal()),
+            LayoutKind::Horizontal => (Metric::Pixel(self.spacing, 0.0), self.cross.vertical()),
+        };
+
+        if self.cursor == self.start {
+            return cross;
+        }
+
+        self.cursor - self.start - trailing + cross
+    }
+}
@@ -0,0 +1,93 @@
+use engine::{Asset, MeshBuffer, MeshData};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Maximum number of the released mesh buffers kept for reuse
+const MAX_FREE_BUFFERS: usize = 64;
+/// Maximum number of the vectors of each kind kept for reuse
+const MAX_FREE_VECS: usize = 64;
+
+#[derive(Default)]
+struct PoolInner {
+    buffers: Vec<Rc<MeshBuffer>>,
+    f32s: Vec<Vec<f32>>,
+    u16s: Vec<Vec<u16>>,
+}
+
+/// Mesh buffers and vectors of the dynamic widgets which are reused across the frames,
+/// such that a widget changed every frame does not allocate new GPU buffers.
+/// The pool is shared by the context and the label handles
+#[derive(Clone, Default)]
+pub struct MeshPool(Rc<RefCell<PoolInner>>);
+
+fn recycle_vec<T>(free: &mut Vec<Vec<T>>, mut v: Vec<T>) {
+    if free.len() < MAX_FREE_VECS && v.capacity() > 0 {
+        v.clear();
+        free.push(v);
+    }
+}
+
+impl MeshPool {
+    pub fn new() -> MeshPool {
+        MeshPool::default()
+    }
+
+    /// An empty vector, which keeps the capacity of a recycled one
+    pub fn f32_vec(&self) -> Vec<f32> {
+        self.0.borrow_mut().f32s.pop().unwrap_or_default()
+    }
+
+    /// An empty vector, which keeps the capacity of a recycled one
+    pub fn u16_vec(&self) -> Vec<u16> {
+        self.0.borrow_mut().u16s.pop().unwrap_or_default()
+    }
+
+    /// Keep the vectors of `data` for reuse
+    pub fn recycle(&self, data: MeshData) {
+        let mut inner = self.0.borrow_mut();
+        let f32s = Some(data.vertices)
+            .into_iter()
+            .chain(data.uvs)
+            .chain(data.normals)
+            .chain(data.tangents)
+            .chain(data.bitangents)
+            .chain(data.colors);
+
+        for v in f32s {
+            recycle_vec(&mut inner.f32s, v);
+        }
+        recycle_vec(&mut inner.u16s, data.indices);
+    }
+
+    /// A mesh buffer of `data`, a released buffer of the same layout is reused if any
+    pub fn buffer(&self, data: MeshData) -> Rc<MeshBuffer> {
+        let found = {
+            let mut inner = self.0.borrow_mut();
+            let i = inner.buffers.iter().position(|mb| mb.is_same_layout(&data));
+            i.map(|i| inner.buffers.swap_remove(i))
+        };
+
+        match found {
+            Some(mb) => {
+                self.update(&mb, data);
+                mb
+            }
+            None => MeshBuffer::new(data),
+        }
+    }
+
+    /// Update the mesh data of `mb` in place and recycle the old one
+    pub fn update(&self, mb: &Rc<MeshBuffer>, data: MeshData) {
+        if let Some(old) = mb.swap_mesh_data(data) {
+            self.recycle(old);
+        }
+    }
+
+    /// Release a mesh buffer for reuse, it is kept only if it is not used anymore
+    pub fn release(&self, mb: Rc<MeshBuffer>) {
+        let mut inner = self.0.borrow_mut();
+        if Rc::strong_count(&mb) == 1 && inner.buffers.len() < MAX_FREE_BUFFERS {
+            inner.buffers.push(mb);
+        }
+    }
+}
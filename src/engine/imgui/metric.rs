@@ -7,6 +7,10 @@ pub enum Metric {
     /// Left Top = (0,0), Right, Bottom = (screen width,screen height)
     Pixel(f32, f32),
     Mixed((f32, f32), (f32, f32)),
+    /// Percent of the size of the parent container, which is the innermost scroll region
+    /// or the screen. E.g. Percent(30.0, 100.0) is 30% of the width and the full height.
+    /// Mixed with other metrics, it is relative to the screen
+    Percent(f32, f32),
 }
 
 impl Default for Metric {
@@ -21,30 +25,140 @@ impl From<(f32, f32)> for Metric {
     }
 }
 
+impl Metric {
+    /// The horizontal component only
+    pub fn horizontal(&self) -> Metric {
+        match self {
+            &Metric::Native(x, _) => Metric::Native(x, 0.0),
+            &Metric::Pixel(x, _) => Metric::Pixel(x, 0.0),
+            &Metric::Mixed((ax, _), (bx, _)) => Metric::Mixed((ax, 0.0), (bx, 0.0)),
+            &Metric::Percent(x, _) => Metric::Percent(x, 0.0),
+        }
+    }
+
+    /// Scale both the native and pixel parts
+    pub fn scale(&self, sx: f32, sy: f32) -> Metric {
+        match self {
+            &Metric::Native(x, y) => Metric::Native(x * sx, y * sy),
+            &Metric::Pixel(x, y) => Metric::Pixel(x * sx, y * sy),
+            &Metric::Mixed((ax, ay), (bx, by)) => {
+                Metric::Mixed((ax * sx, ay * sy), (bx * sx, by * sy))
+            }
+            &Metric::Percent(x, y) => Metric::Percent(x * sx, y * sy),
+        }
+    }
+
+    /// Offset of the pivot `self` from the top-left of an element of `size`,
+    /// the native part of the pivot is in ratio of the size
+    pub fn pivot_offset(&self, size: &Metric) -> Metric {
+        match self {
+            &Metric::Native(px, py) => size.scale(px, py),
+            &Metric::Pixel(px, py) => Metric::Pixel(px, py),
+            &Metric::Mixed((ax, ay), (bx, by)) => size.scale(ax, ay) + Metric::Pixel(bx, by),
+            &Metric::Percent(px, py) => size.scale(px / 100.0, py / 100.0),
+        }
+    }
+
+    /// Convert to (native, pixel) pair
+    fn split(&self) -> ((f32, f32), (f32, f32)) {
+        match self {
+            &Metric::Native(x, y) => ((x, y), (0.0, 0.0)),
+            &Metric::Pixel(x, y) => ((0.0, 0.0), (x, y)),
+            &Metric::Mixed(a, b) => (a, b),
+            &Metric::Percent(x, y) => ((x / 100.0, y / 100.0), (0.0, 0.0)),
+        }
+    }
+
+    /// Component-wise maximum of native and pixel parts,
+    /// which is always not smaller than both metrics
+    pub fn max(&self, other: &Metric) -> Metric {
+        let ((ax, ay), (bx, by)) = self.split();
+        let ((oax, oay), (obx, oby)) = other.split();
+
+        Metric::Mixed((ax.max(oax), ay.max(oay)), (bx.max(obx), by.max(oby)))
+    }
+
+    /// The vertical component only
+    pub fn vertical(&self) -> Metric {
+        match self {
+            &Metric::Native(_, y) => Metric::Native(0.0, y),
+            &Metric::Pixel(_, y) => Metric::Pixel(0.0, y),
+            &Metric::Mixed((_, ay), (_, by)) => Metric::Mixed((0.0, ay), (0.0, by)),
+            &Metric::Percent(_, y) => Metric::Percent(0.0, y),
+        }
+    }
+
+    /// Resolve a percent metric against the size of the parent container
+    pub fn resolve_percent(&self, parent: &Metric) -> Metric {
+        match self {
+            &Metric::Percent(x, y) => parent.scale(x / 100.0, y / 100.0),
+            m => *m,
+        }
+    }
+
+    /// The native metric of the logical pixels (x, y) on a screen of `ssize`
+    pub fn from_pixels(x: f32, y: f32, ssize: (u32, u32), hidpi: f32) -> Metric {
+        if ssize.0 == 0 || ssize.1 == 0 {
+            return Metric::Native(0.0, 0.0);
+        }
+
+        Metric::Native(x * hidpi / ssize.0 as f32, y * hidpi / ssize.1 as f32)
+    }
+
+    /// Convert to logical pixels on a screen of `ssize` in physical pixels
+    pub fn to_pixels(&self, ssize: (u32, u32), hidpi: f32) -> (f32, f32) {
+        let ((ax, ay), (bx, by)) = self.split();
+        if hidpi <= 0.0 {
+            return (bx, by);
+        }
+
+        (
+            ax * ssize.0 as f32 / hidpi + bx,
+            ay * ssize.1 as f32 / hidpi + by,
+        )
+    }
+
+    /// Convert to native on a screen of `ssize`, see `to_pixels`
+    pub fn to_native(&self, ssize: (u32, u32), hidpi: f32) -> (f32, f32) {
+        let (x, y) = self.to_pixels(ssize, hidpi);
+        match Metric::from_pixels(x, y, ssize, hidpi) {
+            Metric::Native(nx, ny) => (nx, ny),
+            _ => (0.0, 0.0),
+        }
+    }
+
+    /// Convert a size to ndc, which is twice the native size as ndc is from -1 to 1
+    pub fn to_ndc(&self, ssize: (u32, u32), hidpi: f32) -> (f32, f32) {
+        let ((ax, ay), (bx, by)) = self.split();
+
+        (
+            ax * 2.0 + (bx * 2.0 * hidpi) / ssize.0 as f32,
+            ay * 2.0 + (by * 2.0 * hidpi) / ssize.1 as f32,
+        )
+    }
+
+    /// Resolve a mixed metric to a pixel metric on a screen of `ssize`
+    pub fn resolve(&self, ssize: (u32, u32), hidpi: f32) -> Metric {
+        let (x, y) = self.to_pixels(ssize, hidpi);
+        Metric::Pixel(x, y)
+    }
+}
+
 impl Add for Metric {
     type Output = Metric;
 
     fn add(self, other: Metric) -> Metric {
-        match self {
-            Metric::Native(x, y) => match other {
-                Metric::Native(ox, oy) => Metric::Native(x + ox, y + oy),
-                Metric::Pixel(ox, oy) => Metric::Mixed((x, y), (ox, oy)),
-                Metric::Mixed((oax, oay), b) => Metric::Mixed((oax + x, oay + y), b),
-            },
-
-            Metric::Pixel(x, y) => match other {
-                Metric::Native(ox, oy) => Metric::Mixed((ox, oy), (x, y)),
-                Metric::Pixel(ox, oy) => Metric::Pixel(x + ox, y + oy),
-                Metric::Mixed(a, (obx, oby)) => Metric::Mixed(a, (obx + x, oby + x)),
-            },
-
-            Metric::Mixed((ax, ay), (bx, by)) => match other {
-                Metric::Native(ox, oy) => Metric::Mixed((ax + ox, ay + oy), (bx, by)),
-                Metric::Pixel(ox, oy) => Metric::Mixed((ax, ay), (bx + ox, by + oy)),
-                Metric::Mixed((oax, oay), (obx, oby)) => {
-                    Metric::Mixed((ax + oax, ay + oay), (bx + obx, by + oby))
-                }
-            },
+        match (self, other) {
+            (Metric::Native(x, y), Metric::Native(ox, oy)) => Metric::Native(x + ox, y + oy),
+            (Metric::Pixel(x, y), Metric::Pixel(ox, oy)) => Metric::Pixel(x + ox, y + oy),
+            (Metric::Percent(x, y), Metric::Percent(ox, oy)) => Metric::Percent(x + ox, y + oy),
+            // Sum the native and pixel parts, a percent is relative to the screen
+            (a, b) => {
+                let ((ax, ay), (bx, by)) = a.split();
+                let ((oax, oay), (obx, oby)) = b.split();
+
+                Metric::Mixed((ax + oax, ay + oay), (bx + obx, by + oby))
+            }
         }
     }
 }
@@ -57,6 +171,7 @@ impl Sub for Metric {
             Metric::Native(px, py) => self + Metric::Native(-px, -py),
             Metric::Pixel(px, py) => self + Metric::Pixel(-px, -py),
             Metric::Mixed((ax, ay), (bx, by)) => self + Metric::Mixed((-ax, -ay), (-bx, -by)),
+            Metric::Percent(px, py) => self + Metric::Percent(-px, -py),
         }
     }
 }
@@ -5,35 +5,219 @@
 //!
 //! Supported elements
 //!
-//! Label
+//! Label (bitmap font or TrueType font, with color and icon markup)
+//! Image / Nine-slice image / Sprite sheet region
+//! Button
+//! Slider
+//! Color picker
+//! Checkbox / Radio
+//! TextField
+//! Scroll region / List view
+//! Modal
+//! Tree node
+//! Interactive region (`interact`) for custom widgets
+//! Canvas of lines, rects, circles and polylines
+//! Plot of lines / Histogram
 //!
 //! Positioning
 //!     Pivot to control how the element is positiion related to itself.
 //!     E.g: let the `position` of the element is (x,y)
 //!     pivot(0,0) => represent the top-left corner of element will be placed in (x,y)
 //!     pivot(1,1) => represent the bottom-right corner of element will be place in (x,y)
+//!     pivot(Pixel(8,8)) => represent the point 8 pixels right and below the top-left corner
+//!     will be placed in (x,y), Mixed combines both
+//!     `to_pixels` / `to_native` resolve a metric against the current screen size.
+//!     `Percent` positions and sizes are relative to the innermost scroll region.
+//!     `set_ui_scale` scales the pixel metrics and the fonts on top of the hidpi factor.
 //!
+//! Anchor
+//!     `anchor` glues the following elements to a screen edge or corner, their positions
+//!     are offsets from it towards the screen center. `safe_area` keeps them out of
+//!     notches and rounded corners.
+//!
+//! Rich text
+//!     Labels support the markup `[color=#rrggbb]text[/color]` to color a part of the
+//!     text and `[icon=name]` to inline an icon of the atlas set by `icon_atlas`.
+//!     `text_style` adds a shadow or an outline to them. `measure_text` returns the size
+//!     of a text before its label is added.
+//!
+//! Transform
+//!     `rotation` and `scale` rotate and scale the following elements around their pivot,
+//!     e.g. for compasses and dial gauges. Hit-testing still uses the untransformed rect.
+//!
+//! Animation
+//!     `animate` fades, slides or scales the following widgets in or out until
+//!     `end_animate`. It is started when a widget appears or its transition is changed.
+//!
+//! Layout
+//!     `begin_vertical` / `begin_horizontal` and `end_layout` stack the widgets
+//!     in the scope automatically.
+//!
+//! Scroll
+//!     `begin_scroll` / `end_scroll` stack the widgets in the scope vertically,
+//!     clip them to the region and scroll them by the mouse wheel.
+//!     `list_view` is a scroll region which only adds the widgets of the visible rows.
+//!
+//! Drag and drop
+//!     `drag_source` makes its widgets draggable with a payload id, which is
+//!     delivered to the `drop_target` where the pointer is released.
+//!
+//! Tooltip
+//!     `tooltip` shows a text near the mouse when the widget placed just before
+//!     is hovered for a while.
+//!
+//! Modal
+//!     `open_modal` opens a modal and `modal` shows its widgets over a dimmed screen,
+//!     the widgets beneath it cannot be interacted until it is closed.
+//!
+//! Style
+//!     `push_style` / `pop_style` change the font, colors, padding and border of
+//!     the following widgets. `alpha` makes them translucent.
+//!
+//! Id
+//!     Widgets are identified by their order in the frame. `push_id` / `pop_id` begin
+//!     a scope of a label, such that conditional widgets in it do not change the ids
+//!     of the widgets after it and their game objects can be reused.
+//!
+//! Retained
+//!     `Node` trees of panels, labels, images and buttons are added by `retained`,
+//!     the widgets of the unchanged nodes are not compiled and bound again.
+//!
+//! Debug overlay
+//!     `debug_overlay` shows the fps and the statistics of the renderer of the last frame.
+//!
+//! Testing
+//!     `start_recording` records the inputs and the clicked widgets per frame into a `UiLog`,
+//!     which `start_playback` replays without the platform events. `layout_snapshot` lists
+//!     the widgets and their rects resolved by the last `pre_render`.
+//!
+//! Layer
+//!     Widgets are drawn in the order they are added, `layer` draws the following
+//!     widgets over the ones of lower layers, e.g. for popups and HUD backgrounds.
+//!
+//! Text direction
+//!     Arabic and Hebrew texts are shaped and laid out right-to-left, mixed with the
+//!     left-to-right runs. `set_rtl_locale` makes `TextAlign::Start` / `End` align to the
+//!     right and the left.
+//!
+//! Input
+//!     Platform events are fed by `handle_event`, hit-testing is done in `pre_render`,
+//!     such that interactive widgets report the result of the previous frame.
+//!     The mouse and the touches (`touch_start` / `touch_move` / `touch_end`) drive
+//!     the same pointer, a tap clicks and a drag moves a slider or scrolls.
+//!     `wants_mouse` / `wants_keyboard` tell whether the ui used the input,
+//!     such that the world input handling can be skipped.
+//!     `shortcut` tells whether a key chord like "Ctrl+S" was pressed in the frame.
+//!     `hit_shape` limits the hits to a circle, a polygon or the opaque pixels of a texture.
+//!     Text fields select by dragging or shift with the caret keys, and copy, cut and paste
+//!     with Ctrl+C / X / V through the clipboard of the platform on desktop. The clipboard
+//!     is kept inside the application on the web, `set_clipboard` replaces it.
+//!     IME compositions (`composition_start` / `composition_update` / `composition_end`)
+//!     are shown underlined at the caret and inserted when they end.
+//!
+//! Navigation
+//!     The arrow keys and the gamepad d-pad (`handle_gamepad`) move the focus between
+//!     buttons, sliders, checkboxes, text fields and tree nodes, Enter or A clicks the
+//!     focused widget. Left and right change the value of a focused slider.
+//!
+//! Multiple UIs
+//!     The free functions use the primary instance, which is drawn by the cameras without
+//!     a `ui`. `Ui::new` creates another one with its own widgets, `Ui::frame` adds its
+//!     widgets and `Ui::pre_render` updates them for the cameras whose `ui` is its name.
+//!     `WorldUi` renders a ui into a texture shown on a quad in the world, which can face
+//!     the camera like a billboard.
 //!
 
+mod anchor;
+mod animation;
+mod bidi;
+mod bitmap_font;
+mod button;
+mod canvas;
+mod clipboard;
+mod color_picker;
 mod context;
+mod focus_ring;
 mod image;
 mod instance;
 mod label;
+mod layout;
+mod mesh_pool;
 mod metric;
+mod modal;
+mod region;
+mod recorder;
+mod retained;
+mod rich_text;
+mod scroll;
+mod shortcut;
+mod snapshot;
+mod slider;
+mod style;
+mod text_field;
+mod text_mesh;
+mod toggle;
+mod tree_node;
+mod ui;
 mod widgets;
+mod world_ui;
 
+use engine::core::Aabb;
+use engine::engine::EngineStats;
 use engine::IEngine;
-use engine::render::{Material, Texture};
+use engine::render::{Font, Material, Texture, TextureAtlas};
+use math::Vector4;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::ops::Mul;
 use std::rc::Rc;
+use uni_app::{now, AppEvent};
 
+pub use self::anchor::Anchor;
+pub use self::animation::Transition;
+pub use self::bitmap_font::BitmapFont;
+pub use self::canvas::Draw;
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::clipboard::NativeClipboard;
+#[cfg(target_arch = "wasm32")]
+pub use self::clipboard::WebClipboard;
+pub use self::clipboard::{set_clipboard, Clipboard, MemoryClipboard};
 pub use self::context::Context;
+pub use self::image::{FitMode, NineSliceBorder};
+pub use self::instance::{HitShape, NavInput};
+pub use self::instance::{KeyInput, PointerKind};
 pub use self::metric::*;
+pub use self::recorder::{UiEvent, UiLog, UiRecord};
+pub use self::retained::{Node, NodeKind};
+pub use self::rich_text::IconAtlas;
+pub use self::shortcut::KeyChord;
+pub use self::snapshot::{WidgetKind, WidgetRect};
+pub use self::style::{Style, TextStyle};
+pub use self::text_mesh::TextMesh;
+pub use self::ui::Ui;
+pub use self::world_ui::WorldUi;
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum TextAlign {
     Left,
     Right,
     Center,
+    /// Left, or right in a right-to-left locale
+    Start,
+    /// Right, or left in a right-to-left locale
+    End,
+}
+
+impl TextAlign {
+    /// The left, right or center alignment in a locale of the direction `rtl`
+    pub fn resolve(self, rtl: bool) -> TextAlign {
+        match (self, rtl) {
+            (TextAlign::Start, false) | (TextAlign::End, true) => TextAlign::Left,
+            (TextAlign::Start, true) | (TextAlign::End, false) => TextAlign::Right,
+            (align, _) => align,
+        }
+    }
 }
 
 impl Default for TextAlign {
@@ -42,69 +226,2047 @@ impl Default for TextAlign {
     }
 }
 
+/// RGBA color, each component is in 0..1
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Default for Color {
+    fn default() -> Color {
+        Color::new(1.0, 1.0, 1.0, 1.0)
+    }
+}
+
+impl Color {
+    pub fn new(r: f32, g: f32, b: f32, a: f32) -> Color {
+        Color { r, g, b, a }
+    }
+
+    pub fn to_vec4(&self) -> Vector4<f32> {
+        Vector4::new(self.r, self.g, self.b, self.a)
+    }
+
+    /// Color from hue, saturation and value, each is in 0..1
+    pub fn from_hsv(h: f32, s: f32, v: f32, a: f32) -> Color {
+        let h = (h - h.floor()) * 6.0;
+        let f = h - h.floor();
+        let p = v * (1.0 - s);
+        let q = v * (1.0 - s * f);
+        let t = v * (1.0 - s * (1.0 - f));
+
+        let (r, g, b) = match h as u32 {
+            0 => (v, t, p),
+            1 => (q, v, p),
+            2 => (p, v, t),
+            3 => (p, q, v),
+            4 => (t, p, v),
+            _ => (v, p, q),
+        };
+
+        Color::new(r, g, b, a)
+    }
+
+    /// (hue, saturation, value) of the color, each is in 0..1
+    pub fn to_hsv(&self) -> (f32, f32, f32) {
+        let max = self.r.max(self.g).max(self.b);
+        let min = self.r.min(self.g).min(self.b);
+        let d = max - min;
+
+        let h = if d <= 0.0 {
+            0.0
+        } else if max == self.r {
+            let h = (self.g - self.b) / d;
+            if h < 0.0 {
+                h + 6.0
+            } else {
+                h
+            }
+        } else if max == self.g {
+            (self.b - self.r) / d + 2.0
+        } else {
+            (self.r - self.g) / d + 4.0
+        };
+
+        let s = if max > 0.0 { d / max } else { 0.0 };
+
+        (h / 6.0, s, max)
+    }
+}
+
+impl Mul for Color {
+    type Output = Color;
+
+    fn mul(self, other: Color) -> Color {
+        Color::new(
+            self.r * other.r,
+            self.g * other.g,
+            self.b * other.b,
+            self.a * other.a,
+        )
+    }
+}
+
 pub fn begin() {
     let imgui = instance::imgui_inst();
     let mut inner = imgui.inner.lock().unwrap();
-    inner.id = 0;
+    inner.count = 0;
+    inner.id_scope = instance::IdScope::default();
+    inner.id_stack.clear();
+    inner.pointer.pressed = false;
+    inner.pointer.released = false;
+    inner.pointer.wheel = (0.0, 0.0);
+    inner.pointer.prev_pos = inner.pointer.pos;
+    inner.keys.clear();
+    inner.state.layout = None;
+    inner.layout_stack.clear();
+    inner.state.clip.clear();
+    inner.scroll_stack.clear();
+    inner.last_rect = None;
+    inner.tree_stack.clear();
+    inner.group_stack.clear();
+
+    // The replayed inputs are fed as if they came from the platform
+    let replayed = inner.recorder.next_frame();
+    for event in replayed {
+        recorder::apply(&mut inner, event);
+    }
+
+    // The drag is ended in the frame after the pointer is released
+    if !inner.pointer.down {
+        inner.drag = None;
+    }
+
+    // Forget the hover timer if its tooltip was not requested in the last frame
+    inner.hover = match inner.hover {
+        Some(timer) if timer.alive => Some(instance::HoverTimer {
+            alive: false,
+            ..timer
+        }),
+        _ => None,
+    };
+
+    // Restore the base style if some styles were not popped
+    if inner.style_stack.len() > 0 {
+        inner.state.style = inner.style_stack[0].clone();
+        inner.style_stack.clear();
+    }
 
     inner.render_list.clear();
 }
 
+/// Begin an id scope of `label` until `pop_id`. The ids of the widgets in the scope
+/// only depend on the labels and their order in the scope, so conditional widgets
+/// in a scope do not change the ids of the widgets after it.
+/// The labels must be unique in the parent scope
+pub fn push_id(label: &str) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+
+    let mut hasher = DefaultHasher::new();
+    inner.id_scope.seed.hash(&mut hasher);
+    label.hash(&mut hasher);
+
+    let parent = inner.id_scope;
+    inner.id_stack.push(parent);
+    inner.id_scope = instance::IdScope {
+        seed: hasher.finish(),
+        count: 0,
+    };
+}
+
+/// End the current id scope
+pub fn pop_id() {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+
+    if let Some(parent) = inner.id_stack.pop() {
+        inner.id_scope = parent;
+    }
+}
+
+/// Time in seconds after a touch in which the mouse events are ignored
+const EMULATED_MOUSE_DELAY: f64 = 0.5;
+
+/// Feed a platform event to imgui
+pub fn handle_event(evt: &AppEvent) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+
+    if inner.recorder.is_playing() {
+        return;
+    }
+
+    // Browsers emulate mouse events after a touch, they must not click again
+    let emulated =
+        inner.pointer.touch_id.is_some() || now() - inner.pointer.touch_end < EMULATED_MOUSE_DELAY;
+
+    match evt {
+        &AppEvent::MousePos((x, y)) if !emulated => {
+            let s = inner.ui_scale.0;
+            let event = UiEvent::Move(PointerKind::Mouse, x as f32 / s, y as f32 / s);
+            recorder::apply(&mut inner, event);
+        }
+        &AppEvent::MouseDown(ref e) if e.button == 0 && !emulated => {
+            let (x, y) = inner.pointer.pos;
+            recorder::apply(&mut inner, UiEvent::Press(PointerKind::Mouse, x, y));
+        }
+        &AppEvent::MouseUp(ref e) if e.button == 0 && !emulated => {
+            recorder::apply(&mut inner, UiEvent::Release);
+        }
+        // The keys belong to the IME while composing
+        &AppEvent::KeyDown(_) if inner.composition.is_some() => (),
+        &AppEvent::KeyDown(ref e) => {
+            let chord = KeyChord {
+                code: e.code.clone(),
+                ctrl: e.ctrl,
+                shift: e.shift,
+                alt: e.alt,
+            };
+            recorder::apply(&mut inner, UiEvent::Chord(chord));
+
+            // Left and right move the caret of the focused text field instead
+            let nav = match e.code.as_str() {
+                "ArrowUp" => Some(NavInput::Up),
+                "ArrowDown" => Some(NavInput::Down),
+                "ArrowLeft" if inner.focus.is_none() => Some(NavInput::Left),
+                "ArrowRight" if inner.focus.is_none() => Some(NavInput::Right),
+                "Enter" | "NumpadEnter" => Some(NavInput::Activate),
+                _ => None,
+            };
+
+            if let Some(nav) = nav {
+                recorder::apply(&mut inner, UiEvent::Nav(nav));
+            }
+
+            let key = match e.code.as_str() {
+                "Backspace" => Some(KeyInput::Backspace),
+                "Delete" => Some(KeyInput::Delete),
+                "ArrowLeft" => Some(KeyInput::Left(e.shift)),
+                "ArrowRight" => Some(KeyInput::Right(e.shift)),
+                "Home" => Some(KeyInput::Home(e.shift)),
+                "End" => Some(KeyInput::End(e.shift)),
+                "KeyA" if e.ctrl => Some(KeyInput::SelectAll),
+                "KeyC" if e.ctrl => Some(KeyInput::Copy),
+                "KeyX" if e.ctrl => Some(KeyInput::Cut),
+                "KeyV" if e.ctrl => Some(KeyInput::Paste),
+                _ => {
+                    // Printable key has a single char as key value
+                    let mut chars = e.key.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) if !e.ctrl && !e.alt => Some(KeyInput::Char(c)),
+                        _ => None,
+                    }
+                }
+            };
+
+            if let Some(key) = key {
+                recorder::apply(&mut inner, UiEvent::Key(key));
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Whether the key chord `chord` (e.g. "Ctrl+S", "Ctrl+Shift+Z" or "F5") was pressed in
+/// this frame. The chords used for editing by the focused text field are not reported,
+/// e.g. typing and Ctrl+C, such that they do not trigger the shortcuts
+pub fn shortcut(chord: &str) -> bool {
+    let chord = match KeyChord::parse(chord) {
+        Some(chord) => chord,
+        None => return false,
+    };
+
+    let imgui = instance::imgui_inst();
+    let inner = imgui.inner.lock().unwrap();
+
+    if inner.focus.is_some() && chord.is_text_editing() {
+        return false;
+    }
+
+    inner.chords.iter().any(|c| *c == chord)
+}
+
+/// Feed the start of an IME composition, e.g. from the `compositionstart` event of the browser.
+/// Composition events are not reported by the platform, the application should feed them.
+pub fn composition_start() {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    recorder::apply(&mut inner, UiEvent::Composition(Some(String::new())));
+}
+
+/// Feed the text of the IME composition in progress, e.g. from `compositionupdate`
+pub fn composition_update(s: &str) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    recorder::apply(&mut inner, UiEvent::Composition(Some(s.to_string())));
+}
+
+/// Feed the end of the IME composition, `s` is the committed text, e.g. from `compositionend`
+pub fn composition_end(s: &str) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    recorder::apply(&mut inner, UiEvent::Composition(None));
+    for c in s.chars() {
+        recorder::apply(&mut inner, UiEvent::Key(KeyInput::Char(c)));
+    }
+}
+
+/// Feed a mouse wheel delta in pixel, positive `dy` scrolls towards the end of the content.
+/// Wheel events are not reported by the platform, the application should feed them.
+pub fn mouse_wheel(dx: f32, dy: f32) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    recorder::apply(&mut inner, UiEvent::Wheel(dx, dy));
+}
+
+/// Feed the start of the touch `id` at (x, y) in logical pixels.
+/// Touch events are not reported by the platform, the application should feed them.
+/// The first finger drives the pointer: tap to click, drag to move sliders or to scroll
+pub fn touch_start(id: u64, x: f32, y: f32) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+
+    if inner.pointer.touch_id.is_none() {
+        inner.pointer.touch_id = Some(id);
+        let s = inner.ui_scale.0;
+        recorder::apply(&mut inner, UiEvent::Press(PointerKind::Touch, x / s, y / s));
+    }
+}
+
+/// Feed the move of the touch `id` to (x, y) in logical pixels
+pub fn touch_move(id: u64, x: f32, y: f32) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+
+    if inner.pointer.touch_id == Some(id) {
+        let s = inner.ui_scale.0;
+        recorder::apply(&mut inner, UiEvent::Move(PointerKind::Touch, x / s, y / s));
+    }
+}
+
+/// Feed the end of the touch `id` at (x, y) in logical pixels, also for a cancelled touch
+pub fn touch_end(id: u64, x: f32, y: f32) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+
+    if inner.pointer.touch_id == Some(id) {
+        let s = inner.ui_scale.0;
+        recorder::apply(&mut inner, UiEvent::Move(PointerKind::Touch, x / s, y / s));
+        inner.pointer.touch_id = None;
+        inner.pointer.touch_end = now();
+        recorder::apply(&mut inner, UiEvent::Release);
+    }
+}
+
+/// Feed a navigation input, e.g. from a custom controller mapping
+pub fn nav_input(input: NavInput) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    recorder::apply(&mut inner, UiEvent::Nav(input));
+}
+
+/// Standard mapping buttons of the d-pad and A, the order is kept in `pad_buttons`
+const PAD_NAV_BUTTONS: [(i32, NavInput); 5] = [
+    (12, NavInput::Up),
+    (13, NavInput::Down),
+    (14, NavInput::Left),
+    (15, NavInput::Right),
+    (0, NavInput::Activate),
+];
+
+/// Feed the gamepad state to the navigation once per frame, `button` returns whether
+/// the button of the standard mapping is down. A button is fed when it is pressed
+pub fn handle_gamepad<F>(button: F)
+where
+    F: Fn(i32) -> bool,
+{
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+
+    for (i, &(b, input)) in PAD_NAV_BUTTONS.iter().enumerate() {
+        let down = button(b);
+        if down && !inner.pad_buttons[i] {
+            recorder::apply(&mut inner, UiEvent::Nav(input));
+        }
+        inner.pad_buttons[i] = down;
+    }
+}
+
+/// Start recording the inputs fed to imgui and the clicked widgets, per frame from the
+/// next `imgui::begin`. A recording in progress is discarded
+pub fn start_recording() {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    inner.recorder.start_recording();
+}
+
+/// Stop the recording and return its log, None if nothing was recorded
+pub fn stop_recording() -> Option<UiLog> {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    inner.recorder.stop_recording()
+}
+
+/// Replay the inputs of `log` in the frames they were recorded, from the next `imgui::begin`.
+/// The events of `imgui::handle_event` are ignored until the replay is finished, such that it can run
+/// headlessly. Record it at the same time to compare the clicks with `UiLog::clicks`
+pub fn start_playback(log: UiLog) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    inner.recorder.start_playback(log);
+}
+
+/// Whether a log of `imgui::start_playback` is still being replayed
+pub fn is_playing() -> bool {
+    let imgui = instance::imgui_inst();
+    let inner = imgui.inner.lock().unwrap();
+    inner.recorder.is_playing()
+}
+
 fn add_widget<F>(f: F)
 where
     F: FnOnce(u32, instance::ImguiState) -> widgets::Widget,
 {
+    let (id, state) = next_widget_state();
+    push_widget(Rc::new(f(id, state.clone())), state);
+}
+
+/// The id and the state of the next widget
+fn next_widget_state() -> (u32, instance::ImguiState) {
+    let imgui = instance::imgui_inst();
+    let inner = imgui.inner.lock().unwrap();
+
+    let mut state = inner.state.clone();
+    state.font_ready = state.text_font().map_or(true, |f| f.is_ready());
+    // The layout is resolved into the position, a moved cursor must not rebind the widget
+    state.layout = None;
+
+    (inner.next_id(0), state)
+}
+
+/// Add the widget made of `next_widget_state`
+fn push_widget(w: Rc<widgets::Widget>, state: instance::ImguiState) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+
+    let id = w.id();
+    inner.id_scope.count += 1;
+    inner.count += 1;
+
+    inner.render_list.push(w);
+
+    // The focus ring is drawn just over the widget which has the navigation focus
+    if inner.interaction.nav == Some(id) {
+        if let Some((pos, size)) = inner.last_rect {
+            inner.count += 1;
+            inner
+                .render_list
+                .push(Rc::new(focus_ring::FocusRing::new(id, pos, size, state)));
+        }
+    }
+}
+
+/// Place a widget of `size` at `pos` in the layout or by the anchor,
+/// return the position and the size with the percent metrics resolved
+fn place(pos: Metric, size: Metric) -> (Metric, Metric) {
     let imgui = instance::imgui_inst();
     let mut inner = imgui.inner.lock().unwrap();
-    inner.id += 1;
 
-    let id: u32 = inner.id;
-    let state = inner.state;
+    // Percent of the innermost scroll region, or the screen
+    let parent = inner
+        .state
+        .clip
+        .last()
+        .map_or(Metric::Native(1.0, 1.0), |c| c.size);
+    let (pos, size) = (pos.resolve_percent(&parent), size.resolve_percent(&parent));
+
+    let anchor = inner.anchor;
+    let safe_area = inner.safe_area;
+    let pos = match inner.state.layout {
+        Some(ref mut layout) => layout.place(pos, size),
+        None => anchor.map_or(pos, |a| a.place(pos, &safe_area)),
+    };
 
-    if id as usize >= inner.render_list.len() {
-        inner.render_list.push(Rc::new(f(id, state)));
+    // Widgets in a group extend its rect
+    if inner.group_stack.len() > 0 {
+        let rect = widgets::compute_rect(
+            &pos,
+            &size,
+            &inner.state.pivot,
+            &inner.screen_size,
+            inner.hidpi,
+        );
+        if let Some(group) = inner.group_stack.last_mut() {
+            group.merge(&rect);
+        }
     }
+
+    inner.last_rect = Some((pos, size));
+    (pos, size)
 }
 
-/// Pivot controls how to place the ui element
-pub fn pivot(p: (f32, f32)) {
+fn begin_group() {
     let imgui = instance::imgui_inst();
     let mut inner = imgui.inner.lock().unwrap();
-    inner.state.pivot = Metric::Native(p.0, p.1);
+    inner.group_stack.push(Aabb::empty());
 }
 
-/// Text align setting
-pub fn text_align(align: TextAlign) {
+/// End the current group, return the ndc rect of its widgets
+fn end_group() -> Aabb {
     let imgui = instance::imgui_inst();
     let mut inner = imgui.inner.lock().unwrap();
-    inner.state.text_align = align;
+
+    let rect = inner.group_stack.pop().unwrap_or_default();
+    if let Some(parent) = inner.group_stack.last_mut() {
+        parent.merge(&rect);
+    }
+
+    rect
 }
 
-/// Label
-pub fn label(pos: Metric, s: &str) {
-    add_widget(|id, state| label::Label::new(id, pos, state, s.into()));
+fn begin_layout(kind: layout::LayoutKind, pos: Metric, spacing: f32) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
 
-    // reset text settings
-    text_align(TextAlign::default());
+    let parent = inner.state.layout;
+
+    // Nested layout is relative to the parent cursor
+    let pos = match parent {
+        Some(ref p) => p.cursor + pos,
+        None => pos,
+    };
+
+    inner.layout_stack.push(parent);
+    inner.state.layout = Some(layout::Layout::new(kind, pos, spacing));
 }
 
-/// Image
-pub fn image(pos: Metric, size: Metric, tex: Rc<Texture>) {
-    add_widget(|id, state| image::Image::new(id, pos, size, state, tex));
+/// Begin a vertical layout at `pos`, widgets are stacked from top to bottom.
+/// `spacing` is in pixel.
+/// Layout assumes the pivot is (0, 0)
+pub fn begin_vertical(pos: Metric, spacing: f32) {
+    begin_layout(layout::LayoutKind::Vertical, pos, spacing);
 }
 
-/// Image with material
-pub fn image_with_material(pos: Metric, size: Metric, material: Rc<Material>) {
-    add_widget(|id, state| image::Image::new(id, pos, size, state, material));
+/// Begin a horizontal layout at `pos`, widgets are stacked from left to right.
+/// `spacing` is in pixel.
+/// Layout assumes the pivot is (0, 0)
+pub fn begin_horizontal(pos: Metric, spacing: f32) {
+    begin_layout(layout::LayoutKind::Horizontal, pos, spacing);
 }
 
-pub fn pre_render(engine: &mut IEngine) {
+/// End the current layout
+pub fn end_layout() {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+
+    let layout = inner.state.layout.take();
+    inner.state.layout = inner.layout_stack.pop().unwrap_or(None);
+
+    // Advance the parent layout by the extent of the nested one
+    if let (Some(layout), Some(parent)) = (layout, inner.state.layout.as_mut()) {
+        let offset = layout.start - parent.cursor;
+        parent.place(offset, layout.size());
+    }
+}
+
+/// Begin a scroll region of `size` at `pos`, the widgets until `end_scroll` are
+/// stacked vertically from the top-left of the region and clipped to it.
+/// The content is scrolled by the mouse wheel, see `mouse_wheel`.
+/// Like layout, the widgets in the region assume the pivot is (0, 0)
+pub fn begin_scroll(pos: Metric, size: Metric) {
+    let (pos, size) = place(pos, size);
+
+    let imgui = instance::imgui_inst();
+    let inner = imgui.inner.lock().unwrap();
+
+    let index = inner.count as usize;
+    let offset = inner
+        .scroll
+        .get(&inner.next_id(0))
+        .cloned()
+        .unwrap_or_default();
+    drop(inner);
+
+    add_widget(|id, state| scroll::ScrollRegion::new(id, pos, size, state, offset));
+
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+
+    let top_left = pos - inner.state.pivot.pivot_offset(&size);
+
+    inner.scroll_stack.push(index);
+    inner.state.clip.push(instance::ClipRect {
+        pos: top_left,
+        size,
+    });
+
+    let parent = inner.state.layout;
+    inner.layout_stack.push(parent);
+    inner.state.layout = Some(layout::Layout::new(
+        layout::LayoutKind::Vertical,
+        top_left - Metric::Pixel(offset.0, offset.1),
+        0.0,
+    ));
+}
+
+/// End the current scroll region
+pub fn end_scroll() {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+
+    let index = match inner.scroll_stack.pop() {
+        Some(index) => index,
+        None => return,
+    };
+
+    let layout = inner.state.layout.take();
+    inner.state.layout = inner.layout_stack.pop().unwrap_or(None);
+    inner.state.clip.pop();
+
+    // The content size is known only after all widgets are placed
+    let content = layout.map_or(Metric::Pixel(0.0, 0.0), |l| l.size());
+    let region = match inner.render_list.get(index).map(|w| w.as_ref()) {
+        Some(&widgets::Widget::Scroll(ref sc)) => Some(sc.with_content(content)),
+        _ => None,
+    };
+
+    if let Some(region) = region {
+        inner.render_list[index] = Rc::new(region);
+    }
+}
+
+/// Move the cursor of the current layout to `pos`
+fn move_layout_cursor(pos: Metric) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    if let Some(ref mut layout) = inner.state.layout {
+        layout.cursor = pos;
+    }
+}
+
+/// Virtualized list of `item_count` rows in a scroll region, each row is `item_height`
+/// pixel high. `f` is called with the index of the visible rows only to add their widgets.
+/// The widget ids only depend on the visible rows, such that the game objects of
+/// the rows are reused while the list is scrolled
+pub fn list_view<F>(pos: Metric, size: Metric, item_count: usize, item_height: f32, mut f: F)
+where
+    F: FnMut(usize),
+{
+    let (offset, view_height) = {
+        let imgui = instance::imgui_inst();
+        let inner = imgui.inner.lock().unwrap();
+
+        // The scroll region is the next widget
+        let offset = inner.scroll.get(&inner.next_id(0)).map_or(0.0, |o| o.1);
+        let (_, h) = widgets::to_pixel_size(&size, &inner.screen_size, inner.hidpi);
+        (offset, h)
+    };
+
+    begin_scroll(pos, size);
+
+    let start = current_state(|state| state.layout.map(|l| l.start)).unwrap_or_default();
+    let row = |i: usize| start + Metric::Pixel(0.0, i as f32 * item_height);
+
+    let (first, last) = if item_height > 0.0 {
+        let first = (offset / item_height).floor() as usize;
+        let last = ((offset + view_height) / item_height).ceil() as usize;
+        (first.min(item_count), last.min(item_count))
+    } else {
+        (0, 0)
+    };
+
+    for i in first..last {
+        move_layout_cursor(row(i));
+        f(i);
+    }
+
+    // The content size covers all rows
+    move_layout_cursor(row(item_count));
+    end_scroll();
+}
+
+/// Handle of an opened modal, see `imgui::modal`
+pub struct Modal {
+    name: String,
+}
+
+impl Modal {
+    /// Close the modal, it is hidden from the next frame
+    pub fn close(&self) {
+        close_modal(&self.name);
+    }
+}
+
+/// Open the modal `name`, it is shown by `modal` until it is closed
+pub fn open_modal(name: &str) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    if !inner.modals.iter().any(|m| m == name) {
+        inner.modals.push(name.into());
+    }
+}
+
+/// Close the modal `name`
+pub fn close_modal(name: &str) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    inner.modals.retain(|m| m != name);
+}
+
+/// Modal `name`, if it is opened, the screen is dimmed and the widgets added in `f`
+/// are shown over it. Only the widgets in the modal can be interacted.
+/// Widgets are drawn in order, call it after the other widgets to be on the top.
+/// Return true if the modal is opened
+pub fn modal<F>(name: &str, f: F) -> bool
+where
+    F: FnOnce(&Modal),
+{
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+
+    if !inner.modals.iter().any(|m| m == name) {
+        return false;
+    }
+
+    // The modal is not placed by the layout and not clipped by the scroll regions
+    let layout = inner.state.layout.take();
+    let clip = mem::replace(&mut inner.state.clip, Vec::new());
+    let index = inner.count as usize;
+    drop(inner);
+
+    add_widget(|id, state| modal::Backdrop::new(id, state));
+
+    f(&Modal { name: name.into() });
+
+    let mut inner = imgui.inner.lock().unwrap();
+    inner.state.layout = layout;
+    inner.state.clip = clip;
+
+    // Widgets from the backdrop to the last one capture the input
+    let len = inner.count as usize - index;
+    let backdrop = match inner.render_list.get(index).map(|w| w.as_ref()) {
+        Some(&widgets::Widget::Modal(ref m)) => Some(m.with_len(len)),
+        _ => None,
+    };
+
+    if let Some(backdrop) = backdrop {
+        inner.render_list[index] = Rc::new(backdrop);
+    }
+
+    true
+}
+
+/// Scale the pixel metrics and the font sizes by `scale` on top of the hidpi factor,
+/// e.g. for a "Large UI" setting. The widgets are bound again in the next `pre_render`
+pub fn set_ui_scale(scale: f32) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+
+    let scale = scale.max(0.1);
+    let ratio = inner.ui_scale.0 / scale;
+    if ratio == 1.0 {
+        return;
+    }
+
+    // The pointer is kept in the scaled pixels
+    let rescale = |p: (f32, f32)| (p.0 * ratio, p.1 * ratio);
+    inner.pointer.pos = rescale(inner.pointer.pos);
+    inner.pointer.prev_pos = rescale(inner.pointer.prev_pos);
+    inner.pointer.press_pos = rescale(inner.pointer.press_pos);
+
+    inner.ui_scale = instance::UiScale(scale);
+}
+
+/// The scale set by `set_ui_scale`
+pub fn ui_scale() -> f32 {
+    let imgui = instance::imgui_inst();
+    let inner = imgui.inner.lock().unwrap();
+    inner.ui_scale.0
+}
+
+/// Convert a metric to logical pixels on the current screen, e.g. for custom placement
+pub fn to_pixels(m: Metric) -> (f32, f32) {
+    let imgui = instance::imgui_inst();
+    let inner = imgui.inner.lock().unwrap();
+    m.to_pixels(inner.screen_size, inner.hidpi)
+}
+
+/// Convert a metric to native on the current screen, see `to_pixels`
+pub fn to_native(m: Metric) -> (f32, f32) {
+    let imgui = instance::imgui_inst();
+    let inner = imgui.inner.lock().unwrap();
+    m.to_native(inner.screen_size, inner.hidpi)
+}
+
+/// Pivot controls how to place the ui element,
+/// a pixel pivot is an offset in pixel from the top-left of the element.
+/// It clears the anchor
+pub fn pivot(p: Metric) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    inner.state.pivot = p;
+    inner.anchor = None;
+}
+
+/// Glue the following widgets to a screen edge or corner within the safe area.
+/// The position of a widget is the offset from the anchor towards the screen center,
+/// and the pivot is set to the same corner of the widget. It is ignored in layouts
+pub fn anchor(a: Anchor) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    let (px, py) = a.ratio();
+    inner.state.pivot = Metric::Native(px, py);
+    inner.anchor = Some(a);
+}
+
+/// Insets in pixel of the safe area, e.g. for notched mobile screens.
+/// Only the anchored widgets are moved into it
+pub fn safe_area(left: f32, top: f32, right: f32, bottom: f32) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    inner.safe_area = anchor::SafeArea {
+        left,
+        top,
+        right,
+        bottom,
+    };
+}
+
+/// Rotate the following widgets counterclockwise around their pivot, in radians
+pub fn rotation(radians: f32) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    inner.state.transform.rotation = radians;
+}
+
+/// Scale the following widgets around their pivot
+pub fn scale(sx: f32, sy: f32) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    inner.state.transform.scale = (sx, sy);
+}
+
+/// Layer of the following widgets, higher layers are drawn over lower ones.
+/// Widgets in the same layer are drawn in the order they are added
+pub fn layer(layer: i32) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    inner.state.layer = layer;
+}
+
+/// Shape of the following interactive widgets inside their rects, the pointer outside of it
+/// does not hover nor click them, e.g. `HitShape::Circle` for round buttons
+pub fn hit_shape(shape: HitShape) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    inner.state.hit_shape = shape;
+}
+
+/// Animate the following widgets by `transition` until `end_animate`. Each widget
+/// starts the transition when it appears or when its `id` or transition is changed,
+/// e.g. call `animate("menu", Transition::SlideIn((-200.0, 0.0), 0.3))` before a menu
+/// to slide it in whenever it is opened. Hit-testing uses the rects at the end
+pub fn animate(id: &str, transition: Transition) {
+    let mut hasher = DefaultHasher::new();
+    id.hash(&mut hasher);
+
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    inner.state.animation = Some(animation::Animation {
+        key: hasher.finish(),
+        transition,
+    });
+}
+
+/// Stop animating the following widgets
+pub fn end_animate() {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    inner.state.animation = None;
+}
+
+/// Whether a widget animated by `id` was still in transition in the last frame,
+/// e.g. to keep a fading out menu until it is faded out
+pub fn is_animating(id: &str) -> bool {
+    let mut hasher = DefaultHasher::new();
+    id.hash(&mut hasher);
+
+    let imgui = instance::imgui_inst();
+    let inner = imgui.inner.lock().unwrap();
+    inner.animating.contains(&hasher.finish())
+}
+
+/// Text align setting
+pub fn text_align(align: TextAlign) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    inner.state.text_align = align;
+}
+
+/// Set the direction of the locale, `TextAlign::Start` and `End` align to the right
+/// and the left in a right-to-left locale
+pub fn set_rtl_locale(rtl: bool) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    inner.state.rtl = rtl;
+}
+
+/// Font of labels, None to use the font of the style
+pub fn font(font: Option<Rc<Font>>) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    inner.state.font = font.map(instance::FontRef);
+}
+
+/// Font size of labels in pixel
+pub fn font_size(px: f32) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    inner.state.font_size = Some(px);
+}
+
+/// Bitmap font of labels, it is used when no TrueType font is set
+pub fn bitmap_font(font: BitmapFont) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    inner.state.bitmap_font = Rc::new(font);
+}
+
+/// Shadow and outline of the following labels, to keep them readable over bright scenes
+pub fn text_style(style: TextStyle) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    inner.state.text_style = style;
+}
+
+/// Icon atlas of the `[icon=name]` markup of labels
+pub fn icon_atlas(atlas: IconAtlas) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    inner.state.icon_atlas = Some(Rc::new(atlas));
+}
+
+/// Push a style, which is used by the following widgets until `pop_style`
+pub fn push_style(style: Style) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    let old = inner.state.style.clone();
+    inner.style_stack.push(old);
+    inner.state.style = Rc::new(style);
+}
+
+/// Restore the style before the last `push_style`
+pub fn pop_style() {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    if let Some(style) = inner.style_stack.pop() {
+        inner.state.style = style;
+    }
+}
+
+/// Tint color of labels and images
+pub fn color(r: f32, g: f32, b: f32, a: f32) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    inner.state.color = Color::new(r, g, b, a);
+}
+
+/// Opacity of the following widgets from 0.0 (transparent) to 1.0 (opaque),
+/// it is multiplied into all of their colors including the frames and the texts
+pub fn alpha(a: f32) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    inner.state.alpha = instance::Alpha(a.max(0.0).min(1.0));
+}
+
+/// Background color of labels and images, a transparent color disables the background
+pub fn background_color(r: f32, g: f32, b: f32, a: f32) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    inner.state.background_color = if a > 0.0 {
+        Some(Color::new(r, g, b, a))
+    } else {
+        None
+    };
+}
+
+/// Label, `s` can have the markup `[color=#ff0000]text[/color]` and `[icon=name]`
+pub fn label(pos: Metric, s: &str) {
+    let size = current_state(|state| label::rich_label_size(s, state, None));
+
+    let (pos, _) = place(pos, size);
+    add_widget(|id, state| label::Label::new(id, pos, state, s.into()));
+
+    // reset text settings
+    text_align(TextAlign::default());
+}
+
+/// Label which is word wrapped to `max_width`
+pub fn label_wrapped(pos: Metric, max_width: Metric, s: &str) {
+    let size = {
+        let imgui = instance::imgui_inst();
+        let inner = imgui.inner.lock().unwrap();
+        let w = widgets::to_pixel_width(&max_width, &inner.screen_size, inner.hidpi);
+        label::rich_label_size(s, &inner.state, Some(w))
+    };
+
+    let (pos, _) = place(pos, size);
+    add_widget(|id, state| label::Label::new_wrapped(id, pos, Some(max_width), state, s.into()));
+
+    // reset text settings
+    text_align(TextAlign::default());
+}
+
+/// The size in pixel of the label `s` in `font` of `size` pixel, None for the current
+/// font or font size. E.g. to size a background or center a text before adding the label
+pub fn measure_text(s: &str, font: Option<Rc<Font>>, size: Option<f32>) -> (f32, f32) {
+    let imgui = instance::imgui_inst();
+    let inner = imgui.inner.lock().unwrap();
+
+    let mut state = inner.state.clone();
+    if let Some(font) = font {
+        state.font = Some(instance::FontRef(font));
+    }
+    if size.is_some() {
+        state.font_size = size;
+    }
+
+    let size = label::rich_label_size(s, &state, None);
+    size.to_pixels(inner.screen_size, inner.hidpi)
+}
+
+/// The size of the label `s` in native metric, see `measure_text`
+pub fn measure_text_native(s: &str, font: Option<Rc<Font>>, size: Option<f32>) -> (f32, f32) {
+    let (w, h) = measure_text(s, font, size);
+    to_native(Metric::Pixel(w, h))
+}
+
+/// Distance in pixel the pointer must be moved to start dragging
+const DRAG_THRESHOLD: f32 = 4.0;
+
+/// Handle of a drag source, see `imgui::drag_source`
+pub struct DragSource {
+    dragging: bool,
+}
+
+impl DragSource {
+    /// Whether the payload of the source is being dragged
+    pub fn is_dragging(&self) -> bool {
+        self.dragging
+    }
+}
+
+/// Drag source of the widgets added in `f`, press and move the pointer on them
+/// to drag `payload_id`, which is delivered to the `drop_target` where it is dropped.
+/// `payload_id` should be unique in the frame.
+/// Return true if it is being dragged
+pub fn drag_source<F>(payload_id: &str, f: F) -> bool
+where
+    F: FnOnce(&DragSource),
+{
+    let dragging = drag_payload().map_or(false, |p| p == payload_id);
+
+    begin_group();
+    f(&DragSource { dragging });
+    let rect = end_group();
+
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+
+    let pointer = inner.pointer;
+    let p = widgets::pixel_to_ndc(pointer.pos, &inner.screen_size, inner.hidpi);
+    let hovered = widgets::rect_contains(&rect, p)
+        && widgets::clip_contains(&inner.state.clip, p, &inner.screen_size, inner.hidpi);
+
+    if pointer.pressed && hovered && inner.drag.is_none() {
+        inner.drag = Some(instance::DragState {
+            payload: payload_id.into(),
+            origin: pointer.pos,
+            dragging: false,
+        });
+    }
+
+    if let Some(ref mut drag) = inner.drag {
+        if drag.payload == payload_id && !drag.dragging && pointer.down {
+            let (dx, dy) = (pointer.pos.0 - drag.origin.0, pointer.pos.1 - drag.origin.1);
+            drag.dragging = dx * dx + dy * dy >= DRAG_THRESHOLD * DRAG_THRESHOLD;
+        }
+    }
+
+    dragging
+}
+
+/// Drop target of the widget placed just before, `f` is called with the payload id
+/// when a dragged payload is dropped on it.
+/// Return true if a payload was dropped
+pub fn drop_target<F>(f: F) -> bool
+where
+    F: FnOnce(&str),
+{
+    let payload = {
+        let imgui = instance::imgui_inst();
+        let inner = imgui.inner.lock().unwrap();
+
+        let (pos, size) = match inner.last_rect {
+            Some(rect) => rect,
+            None => return false,
+        };
+
+        let ssize = inner.screen_size;
+        let p = widgets::pixel_to_ndc(inner.pointer.pos, &ssize, inner.hidpi);
+        let rect = widgets::compute_rect(&pos, &size, &inner.state.pivot, &ssize, inner.hidpi);
+
+        match inner.drag {
+            Some(ref drag)
+                if drag.dragging && inner.pointer.released && widgets::rect_contains(&rect, p) =>
+            {
+                Some(drag.payload.clone())
+            }
+            _ => None,
+        }
+    };
+
+    match payload {
+        Some(payload) => {
+            f(&payload);
+            true
+        }
+        None => false,
+    }
+}
+
+/// The payload id being dragged, if any
+pub fn drag_payload() -> Option<String> {
+    let imgui = instance::imgui_inst();
+    let inner = imgui.inner.lock().unwrap();
+
+    inner
+        .drag
+        .as_ref()
+        .filter(|drag| drag.dragging)
+        .map(|drag| drag.payload.clone())
+}
+
+/// Default hover time in seconds before a tooltip is shown
+const TOOLTIP_DELAY: f64 = 0.5;
+/// Offset of a tooltip from the mouse in pixel
+const TOOLTIP_OFFSET: (f32, f32) = (12.0, 16.0);
+
+/// Hover time in seconds before a tooltip is shown
+pub fn tooltip_delay(secs: f64) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    inner.tooltip_delay = Some(secs);
+}
+
+/// Tooltip of the widget placed just before, the text is shown near the mouse
+/// after the widget is hovered for the tooltip delay
+pub fn tooltip(s: &str) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+
+    let (pos, size) = match inner.last_rect {
+        Some(rect) => rect,
+        None => return,
+    };
+
+    let id = inner.render_list.last().map_or(0, |w| w.id());
+    let ssize = inner.screen_size;
+    let hidpi = inner.hidpi;
+    let p = widgets::pixel_to_ndc(inner.pointer.pos, &ssize, hidpi);
+    let rect = widgets::compute_rect(&pos, &size, &inner.state.pivot, &ssize, hidpi);
+
+    let hovered = inner.pointer.hovers()
+        && widgets::rect_contains(&rect, p)
+        && widgets::clip_contains(&inner.state.clip, p, &ssize, hidpi);
+
+    if !hovered {
+        if inner.hover.map_or(false, |timer| timer.id == id) {
+            inner.hover = None;
+        }
+        return;
+    }
+
+    let t = now();
+    let since = match inner.hover {
+        Some(timer) if timer.id == id => timer.since,
+        _ => t,
+    };
+    inner.hover = Some(instance::HoverTimer {
+        id,
+        since,
+        alive: true,
+    });
+
+    if t - since < inner.tooltip_delay.unwrap_or(TOOLTIP_DELAY) {
+        return;
+    }
+
+    let pos = Metric::Pixel(
+        inner.pointer.pos.0 + TOOLTIP_OFFSET.0,
+        inner.pointer.pos.1 + TOOLTIP_OFFSET.1,
+    );
+    drop(inner);
+
+    floating_label(pos, s);
+}
+
+/// Label which floats above the layout and scroll regions, e.g. a tooltip
+fn floating_label(pos: Metric, s: &str) {
+    add_widget(|id, mut state| {
+        state.pivot = Metric::Native(0.0, 0.0);
+        state.text_align = TextAlign::Left;
+        state.layout = None;
+        state.clip.clear();
+        state.background_color = Some(state.style.normal_color);
+        label::Label::new(id, pos, state, s.into())
+    });
+}
+
+/// Canvas of `size`, `f` draws the shapes in pixel from the top-left of the canvas.
+/// The shapes are clipped to the canvas and batched into a single mesh,
+/// which is updated in place when they are changed
+pub fn canvas<F>(pos: Metric, size: Metric, f: F)
+where
+    F: FnOnce(&mut Draw),
+{
+    let mut draw = Draw::default();
+    f(&mut draw);
+
+    let (pos, size) = place(pos, size);
+    add_canvas(pos, size, draw);
+}
+
+/// Add the canvas at the placed `pos`
+fn add_canvas(pos: Metric, size: Metric, draw: Draw) {
+    add_widget(|id, mut state| {
+        let top_left = pos - state.pivot.pivot_offset(&size);
+        state.clip.push(instance::ClipRect {
+            pos: top_left,
+            size,
+        });
+        canvas::Canvas::new(id, pos, size, state, draw)
+    });
+}
+
+/// Width of the lines of `plot_lines` in pixel
+const PLOT_LINE_WIDTH: f32 = 2.0;
+/// Space between the bars of `plot_histogram` in pixel
+const PLOT_BAR_SPACING: f32 = 1.0;
+/// Radius of the marker of the hovered sample in pixel
+const PLOT_MARKER_RADIUS: f32 = 3.0;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum PlotKind {
+    Lines,
+    Histogram,
+}
+
+/// Show the value of the hovered sample of the following plots near the pointer
+pub fn plot_readout(enabled: bool) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    inner.state.plot_readout = enabled;
+}
+
+/// Plot of `values` connected by lines, scaled from their min to max.
+/// Return the index of the hovered sample
+pub fn plot_lines(pos: Metric, size: Metric, values: &[f32]) -> Option<usize> {
+    plot(pos, size, values, PlotKind::Lines)
+}
+
+/// Plot of `values` as bars, scaled from zero or their min to their max.
+/// Return the index of the hovered sample
+pub fn plot_histogram(pos: Metric, size: Metric, values: &[f32]) -> Option<usize> {
+    plot(pos, size, values, PlotKind::Histogram)
+}
+
+fn plot(pos: Metric, size: Metric, values: &[f32], kind: PlotKind) -> Option<usize> {
+    let (pos, size) = place(pos, size);
+
+    // The canvas is followed by a region to hit-test the plot
+    let (hover_x, pointer, readout, style, (w, h)) = {
+        let imgui = instance::imgui_inst();
+        let inner = imgui.inner.lock().unwrap();
+        let ssize = inner.screen_size;
+        let hidpi = inner.hidpi;
+        let rect = widgets::compute_rect(&pos, &size, &inner.state.pivot, &ssize, hidpi);
+        let p = widgets::pixel_to_ndc(inner.pointer.pos, &ssize, hidpi);
+
+        let hover_x = if inner.interaction.hot == Some(inner.next_id(1)) {
+            Some((p.0 - rect.min.x) / (rect.max.x - rect.min.x))
+        } else {
+            None
+        };
+
+        (
+            hover_x,
+            inner.pointer.pos,
+            inner.state.plot_readout,
+            inner.state.style.clone(),
+            widgets::to_pixel_size(&size, &ssize, hidpi),
+        )
+    };
+
+    let n = values.len();
+    let (mut min, mut max) = values.iter().filter(|v| v.is_finite()).fold(
+        (::std::f32::INFINITY, ::std::f32::NEG_INFINITY),
+        |(min, max), &v| (min.min(v), max.max(v)),
+    );
+    if min > max {
+        min = 0.0;
+        max = 1.0;
+    }
+    if kind == PlotKind::Histogram {
+        min = min.min(0.0);
+    }
+    if max - min < ::std::f32::EPSILON {
+        max = min + 1.0;
+    }
+
+    let value_y = |v: f32| h - (v.max(min).min(max) - min) / (max - min) * h;
+
+    let hovered = hover_x.filter(|_| n > 0).map(|x| {
+        let i = match kind {
+            PlotKind::Lines => (x * (n - 1) as f32).round(),
+            PlotKind::Histogram => (x * n as f32).floor(),
+        };
+        (i.max(0.0) as usize).min(n - 1)
+    });
+
+    let mut draw = Draw::default();
+    draw.rect((0.0, 0.0), (w, h), style.normal_color);
+
+    match kind {
+        PlotKind::Lines => {
+            let step = if n > 1 { w / (n - 1) as f32 } else { 0.0 };
+            let points: Vec<(f32, f32)> = values
+                .iter()
+                .enumerate()
+                .map(|(i, &v)| (i as f32 * step, value_y(v)))
+                .collect();
+
+            draw.polyline(&points, PLOT_LINE_WIDTH, style.plot_color);
+
+            if let Some(i) = hovered {
+                draw.circle(points[i], PLOT_MARKER_RADIUS, style.text_color);
+            }
+        }
+        PlotKind::Histogram => {
+            let bar = if n > 0 { w / n as f32 } else { 0.0 };
+            let base = value_y(0.0);
+
+            for (i, &v) in values.iter().enumerate() {
+                let y = value_y(v);
+                let color = if hovered == Some(i) {
+                    style.text_color
+                } else {
+                    style.plot_color
+                };
+
+                draw.rect(
+                    (i as f32 * bar, y.min(base)),
+                    ((bar - PLOT_BAR_SPACING).max(1.0), (base - y).abs()),
+                    color,
+                );
+            }
+        }
+    }
+
+    add_canvas(pos, size, draw);
+    add_widget(|id, state| region::Region::new(id, pos, size, state));
+
+    if let (true, Some(i)) = (readout, hovered) {
+        floating_label(
+            Metric::Pixel(pointer.0 + TOOLTIP_OFFSET.0, pointer.1 + TOOLTIP_OFFSET.1),
+            &format!("{:.2}", values[i]),
+        );
+    }
+
+    hovered
+}
+
+/// Add the widgets of a retained tree, `root` is placed by its top-left corner at its
+/// position like the other widgets. Unchanged nodes reuse their widgets and game objects
+pub fn retained(root: &Node) {
+    let (pos, size) = place(root.pos(), root.size());
+    retained::add_node(root, pos, size);
+}
+
+/// Image, `fit` fits the texture to `size` by stretching, scaling or tiling
+pub fn image(pos: Metric, size: Metric, tex: Rc<Texture>, fit: FitMode) {
+    let (pos, size) = place(pos, size);
+    add_widget(|id, state| image::Image::new_fit(id, pos, size, state, tex, fit));
+}
+
+/// Image with material
+pub fn image_with_material(pos: Metric, size: Metric, material: Rc<Material>) {
+    let (pos, size) = place(pos, size);
+    add_widget(|id, state| image::Image::new(id, pos, size, state, material));
+}
+
+/// Image of the part `uv_rect` (u0, v0, u1, v1) of the texture, in fractions of the texture
+/// from its top-left, e.g. a frame of a sprite sheet
+pub fn image_region(pos: Metric, size: Metric, tex: Rc<Texture>, uv_rect: (f32, f32, f32, f32)) {
+    let (pos, size) = place(pos, size);
+    add_widget(|id, state| image::Image::new_region(id, pos, size, state, tex, uv_rect));
+}
+
+/// Image of the region `name` of an atlas, nothing is drawn until the atlas is loaded
+pub fn image_atlas(pos: Metric, size: Metric, atlas: &TextureAtlas, name: &str) {
+    if let (Some(tex), Some(uv_rect)) = (atlas.texture(), atlas.uv_rect(name)) {
+        image_region(pos, size, tex, uv_rect);
+    }
+}
+
+/// Image playing the `frame_count` frames of the sprite sheet `atlas` at `fps`, the frames
+/// are laid out from left to right in a single row. The frames are changed without
+/// binding the image again
+pub fn image_animated(pos: Metric, size: Metric, atlas: Rc<Texture>, frame_count: u32, fps: f32) {
+    let (pos, size) = place(pos, size);
+    add_widget(|id, state| {
+        image::Image::new_animated(id, pos, size, state, atlas, frame_count, fps)
+    });
+}
+
+/// Image filled like a pie by `fraction` (0..1) clockwise from the top, e.g. for cooldown
+/// indicators and circular health bars. The fraction is changed without binding the image again
+pub fn radial_fill(pos: Metric, size: Metric, tex: Rc<Texture>, fraction: f32) {
+    let (pos, size) = place(pos, size);
+    add_widget(|id, state| image::Image::new_radial(id, pos, size, state, tex, fraction));
+}
+
+/// Nine-slice image, the corners defined by `border` are not stretched
+pub fn image_nine_slice(pos: Metric, size: Metric, tex: Rc<Texture>, border: NineSliceBorder) {
+    let (pos, size) = place(pos, size);
+    add_widget(|id, state| image::Image::new_nine_slice(id, pos, size, state, tex, border));
+}
+
+/// Evaluate `f` with the current state
+fn current_state<F, R>(f: F) -> R
+where
+    F: FnOnce(&instance::ImguiState) -> R,
+{
+    let imgui = instance::imgui_inst();
+    let inner = imgui.inner.lock().unwrap();
+    f(&inner.state)
+}
+
+/// The id of next widget and the interaction result of last frame
+fn next_interaction() -> (u32, instance::Interaction) {
+    let imgui = instance::imgui_inst();
+    let inner = imgui.inner.lock().unwrap();
+    (inner.next_id(0), inner.interaction)
+}
+
+fn widget_state(interaction: &instance::Interaction, id: u32) -> widgets::WidgetState {
+    if interaction.active == Some(id) {
+        widgets::WidgetState::Active
+    } else if interaction.hot == Some(id) {
+        widgets::WidgetState::Hover
+    } else {
+        widgets::WidgetState::Normal
+    }
+}
+
+/// Visual state of the widgets which are triggered by click
+fn click_state(interaction: &instance::Interaction, id: u32) -> widgets::WidgetState {
+    match widget_state(interaction, id) {
+        // Pressed but the pointer moved out
+        widgets::WidgetState::Active if interaction.hot != Some(id) => {
+            widgets::WidgetState::Normal
+        }
+        s => s,
+    }
+}
+
+/// Result of `imgui::interact`
+#[derive(Default, Debug, PartialEq, Clone, Copy)]
+pub struct Interaction {
+    /// The pointer is over the region
+    pub hovered: bool,
+    /// The pointer was pressed on the region and it is still down
+    pub pressed: bool,
+    /// The pointer is released after it was pressed on the region, even outside of it
+    pub released: bool,
+    /// Mouse movement in pixel in this frame while the region is pressed
+    pub drag_delta: (f32, f32),
+}
+
+/// Invisible region of `size` at `pos`, which is hit-tested like the other widgets.
+/// It is the building block of custom widgets, e.g. joysticks and minimaps
+pub fn interact(pos: Metric, size: Metric) -> Interaction {
+    let (pos, size) = place(pos, size);
+    let (id, interaction) = next_interaction();
+
+    let drag_delta = if interaction.active == Some(id) {
+        let imgui = instance::imgui_inst();
+        let inner = imgui.inner.lock().unwrap();
+        let (x, y) = inner.pointer.pos;
+        let (px, py) = inner.pointer.prev_pos;
+        (x - px, y - py)
+    } else {
+        (0.0, 0.0)
+    };
+
+    add_widget(|id, state| region::Region::new(id, pos, size, state));
+
+    Interaction {
+        hovered: interaction.hot == Some(id),
+        pressed: interaction.active == Some(id),
+        released: interaction.released == Some(id),
+        drag_delta,
+    }
+}
+
+/// Button, return true in the frame it was clicked
+pub fn button(pos: Metric, size: Metric, s: &str) -> bool {
+    let (pos, size) = place(pos, size);
+    let (id, interaction) = next_interaction();
+
+    let wstate = click_state(&interaction, id);
+
+    add_widget(|id, state| button::Button::new(id, pos, size, state, wstate, s.into()));
+
+    interaction.clicked == Some(id)
+}
+
+/// Checkbox, return true if the value was changed in this frame
+pub fn checkbox(pos: Metric, s: &str, value: &mut bool) -> bool {
+    let (pos, _) = place(pos, current_state(|state| toggle::toggle_size(s, state)));
+    let (id, interaction) = next_interaction();
+
+    let changed = interaction.clicked == Some(id);
+    if changed {
+        *value = !*value;
+    }
+
+    let wstate = click_state(&interaction, id);
+    let checked = *value;
+
+    add_widget(|id, state| {
+        toggle::Toggle::new(
+            id,
+            pos,
+            state,
+            wstate,
+            toggle::ToggleKind::Checkbox,
+            checked,
+            s.into(),
+        )
+    });
+
+    changed
+}
+
+/// Radio button, `selection` is set to `value` when clicked,
+/// return true if the selection was changed in this frame
+pub fn radio<T>(pos: Metric, s: &str, group_id: u32, value: T, selection: &mut T) -> bool
+where
+    T: PartialEq,
+{
+    let (pos, _) = place(pos, current_state(|state| toggle::toggle_size(s, state)));
+    let (id, interaction) = next_interaction();
+
+    let mut changed = false;
+    if interaction.clicked == Some(id) && *selection != value {
+        *selection = value;
+        changed = true;
+    }
+
+    let wstate = click_state(&interaction, id);
+    let checked = changed || *selection == value;
+
+    add_widget(|id, state| {
+        toggle::Toggle::new(
+            id,
+            pos,
+            state,
+            wstate,
+            toggle::ToggleKind::Radio { group_id },
+            checked,
+            s.into(),
+        )
+    });
+
+    changed
+}
+
+/// Collapsible tree node, click to expand or collapse it.
+/// Return true if it is expanded, then the following widgets are its children
+/// which are indented from the layout cursor until `tree_pop`.
+/// The expansion state is kept by the labels of the node and its parents
+pub fn tree_node(s: &str) -> bool {
+    let (pos, _) = place(
+        Metric::Pixel(0.0, 0.0),
+        current_state(|state| tree_node::tree_node_size(s, state)),
+    );
+    let (id, interaction) = next_interaction();
+
+    let key = {
+        let imgui = instance::imgui_inst();
+        let inner = imgui.inner.lock().unwrap();
+
+        let mut hasher = DefaultHasher::new();
+        inner.tree_stack.last().hash(&mut hasher);
+        s.hash(&mut hasher);
+        hasher.finish()
+    };
+
+    let open = {
+        let imgui = instance::imgui_inst();
+        let mut inner = imgui.inner.lock().unwrap();
+
+        if interaction.clicked == Some(id) && !inner.tree_open.remove(&key) {
+            inner.tree_open.insert(key);
+        }
+
+        inner.tree_open.contains(&key)
+    };
+
+    let wstate = click_state(&interaction, id);
+
+    add_widget(|id, state| tree_node::TreeNode::new(id, pos, state, wstate, open, s.into()));
+
+    if open {
+        let spacing = current_state(|state| state.layout.map_or(0.0, |l| l.spacing));
+        begin_vertical(Metric::Pixel(tree_node::INDENT, 0.0), spacing);
+
+        let imgui = instance::imgui_inst();
+        let mut inner = imgui.inner.lock().unwrap();
+        inner.tree_stack.push(key);
+    }
+
+    open
+}
+
+/// End the children of the last expanded `tree_node`
+pub fn tree_pop() {
+    let popped = {
+        let imgui = instance::imgui_inst();
+        let mut inner = imgui.inner.lock().unwrap();
+        inner.tree_stack.pop().is_some()
+    };
+
+    if popped {
+        end_layout();
+    }
+}
+
+/// The selected range of chars between the caret and the anchor, None if it is empty
+fn selection_range(caret: usize, anchor: Option<usize>) -> Option<(usize, usize)> {
+    anchor
+        .filter(|&a| a != caret)
+        .map(|a| (a.min(caret), a.max(caret)))
+}
+
+/// Remove the selected chars, return them if any
+fn take_selection(
+    chars: &mut Vec<char>,
+    caret: &mut usize,
+    anchor: &mut Option<usize>,
+) -> Option<String> {
+    let range = selection_range(*caret, *anchor);
+    *anchor = None;
+
+    range.map(|(start, end)| {
+        *caret = start;
+        chars.drain(start..end).collect()
+    })
+}
+
+/// Move the caret to `to`, extend the selection if `select` is set or clear it
+fn move_caret(caret: &mut usize, anchor: &mut Option<usize>, select: bool, to: usize) {
+    if !select {
+        *anchor = None;
+    } else if anchor.is_none() {
+        *anchor = Some(*caret);
+    }
+
+    *caret = to;
+}
+
+/// Apply the keyboard inputs of this frame to the focused text
+fn edit_text(
+    s: &mut String,
+    caret: &mut usize,
+    anchor: &mut Option<usize>,
+    keys: &[KeyInput],
+) -> bool {
+    let mut chars: Vec<char> = s.chars().collect();
+    let mut changed = false;
+
+    *caret = (*caret).min(chars.len());
+    *anchor = anchor.map(|a| a.min(chars.len()));
+
+    for key in keys.iter() {
+        match key {
+            &KeyInput::Char(c) => {
+                take_selection(&mut chars, caret, anchor);
+                chars.insert(*caret, c);
+                *caret += 1;
+                changed = true;
+            }
+            &KeyInput::Backspace => {
+                if take_selection(&mut chars, caret, anchor).is_some() {
+                    changed = true;
+                } else if *caret > 0 {
+                    *caret -= 1;
+                    chars.remove(*caret);
+                    changed = true;
+                }
+            }
+            &KeyInput::Delete => {
+                if take_selection(&mut chars, caret, anchor).is_some() {
+                    changed = true;
+                } else if *caret < chars.len() {
+                    chars.remove(*caret);
+                    changed = true;
+                }
+            }
+            &KeyInput::Left(select) => {
+                let to = caret.saturating_sub(1);
+                move_caret(caret, anchor, select, to);
+            }
+            &KeyInput::Right(select) => {
+                let to = (*caret + 1).min(chars.len());
+                move_caret(caret, anchor, select, to);
+            }
+            &KeyInput::Home(select) => move_caret(caret, anchor, select, 0),
+            &KeyInput::End(select) => move_caret(caret, anchor, select, chars.len()),
+            &KeyInput::SelectAll => {
+                *anchor = Some(0);
+                *caret = chars.len();
+            }
+            &KeyInput::Copy => {
+                if let Some((start, end)) = selection_range(*caret, *anchor) {
+                    let text: String = chars[start..end].iter().collect();
+                    clipboard::set_text(&text);
+                }
+            }
+            &KeyInput::Cut => {
+                if let Some(text) = take_selection(&mut chars, caret, anchor) {
+                    clipboard::set_text(&text);
+                    changed = true;
+                }
+            }
+            &KeyInput::Paste => {
+                if let Some(text) = clipboard::get_text() {
+                    take_selection(&mut chars, caret, anchor);
+                    // Single line, the line breaks are dropped
+                    for c in text.chars().filter(|&c| c != '\n' && c != '\r') {
+                        chars.insert(*caret, c);
+                        *caret += 1;
+                    }
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    if changed {
+        *s = chars.into_iter().collect();
+    }
+
+    changed
+}
+
+/// Single line text field, click to get the keyboard focus and drag to select,
+/// return true if the text was changed in this frame
+pub fn text_field(pos: Metric, size: Metric, s: &mut String) -> bool {
+    let (pos, size) = place(pos, size);
+    let (id, state) = next_widget_state();
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+
+    let mut changed = false;
+    let mut caret = None;
+    let mut selection = None;
+    let mut composition = None;
+    let mut text = None;
+
+    if inner.focus == Some(id) {
+        let mut index = inner.caret;
+        let mut anchor = inner.selection_anchor;
+        let typing = inner.keys.len() > 0;
+
+        // Dragging the pressed field selects from the pressed char
+        if inner.interaction.active == Some(id) && inner.pointer.down {
+            let ssize = inner.screen_size;
+            let hidpi = inner.hidpi;
+            let rect = widgets::compute_rect(&pos, &size, &state.pivot, &ssize, hidpi);
+            let index_at = |p: (f32, f32)| {
+                let (x, _) = widgets::pixel_to_ndc(p, &ssize, hidpi);
+                text_field::char_index_at(s, &state, x - rect.min.x, ssize, hidpi)
+            };
+
+            anchor = Some(index_at(inner.pointer.press_pos));
+            index = index_at(inner.pointer.pos);
+        }
+
+        changed = edit_text(s, &mut index, &mut anchor, &inner.keys);
+        inner.caret = index;
+        inner.selection_anchor = anchor;
+        inner.keys.clear();
+        selection = selection_range(index, anchor);
+
+        // Blink every half second, keep showing while typing
+        let blink = (now() * 2.0) as u64 % 2 == 0;
+        if blink || typing {
+            caret = Some(index);
+        }
+
+        // The composition is shown inline at the caret, which is kept after it
+        if let Some(ref comp) = inner.composition {
+            let len = comp.chars().count();
+            if len > 0 {
+                let mut shown: String = s.chars().take(index).collect();
+                shown.push_str(comp);
+                shown.extend(s.chars().skip(index));
+
+                text = Some(shown);
+                composition = Some((index, index + len));
+                caret = Some(index + len);
+                selection = None;
+            }
+        }
+    }
+
+    let wstate = if inner.focus == Some(id) {
+        widgets::WidgetState::Active
+    } else {
+        widget_state(&inner.interaction, id)
+    };
+
+    drop(inner);
+
+    let w = text_field::TextField::new(
+        id,
+        pos,
+        size,
+        state.clone(),
+        wstate,
+        caret,
+        selection,
+        composition,
+        text.unwrap_or_else(|| s.clone()),
+    );
+    push_widget(Rc::new(w), state);
+
+    changed
+}
+
+/// Slider, the value is updated while the handle is dragged
+pub fn slider(pos: Metric, size: Metric, min: f32, max: f32, value: &mut f32) {
+    let (pos, size) = place(pos, size);
+    let (id, interaction) = next_interaction();
+
+    if interaction.active == Some(id) {
+        if let Some(t) = interaction.drag_value {
+            *value = min + t * (max - min);
+        }
+    }
+
+    if interaction.nav == Some(id) && interaction.nav_step != 0.0 && max != min {
+        let t = (*value - min) / (max - min) + interaction.nav_step * slider::NAV_STEP;
+        *value = min + t.max(0.0).min(1.0) * (max - min);
+    }
+
+    let t = if max != min {
+        ((*value - min) / (max - min)).max(0.0).min(1.0)
+    } else {
+        0.0
+    };
+
+    let wstate = widget_state(&interaction, id);
+
+    add_widget(|id, state| slider::Slider::new(id, pos, size, state, wstate, t));
+}
+
+/// Color picker, a saturation/value square and a hue strip on the right.
+/// The alpha is not changed. Return true if the color was changed in this frame
+pub fn color_picker(pos: Metric, size: Metric, color: &mut Color) -> bool {
+    use self::color_picker::{ColorPicker, ColorPickerPart, HUE_WIDTH, SPACING};
+
+    let (pos, size) = place(pos, size);
+    let (id, interaction) = next_interaction();
+
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    let hue_id = inner.next_id(1);
+
+    // Use the last picked hsv if it is still the same color, since
+    // the hue and saturation are lost in gray and black colors
+    let (mut h, mut s, mut v) = match inner.picker_hsv.get(&id) {
+        Some(&(h, s, v)) if Color::from_hsv(h, s, v, color.a) == *color => (h, s, v),
+        _ => color.to_hsv(),
+    };
+
+    let mut changed = false;
+    if let Some((x, y)) = interaction.drag_point {
+        if interaction.active == Some(id) {
+            s = x;
+            v = 1.0 - y;
+            changed = true;
+        } else if interaction.active == Some(hue_id) {
+            h = y;
+            changed = true;
+        }
+    }
+
+    if changed {
+        let picked = Color::from_hsv(h, s, v, color.a);
+        changed = picked != *color;
+        *color = picked;
+    }
+    inner.picker_hsv.insert(id, (h, s, v));
+
+    // The parts are placed from the top-left
+    let top_left = pos - inner.state.pivot.pivot_offset(&size);
+    drop(inner);
+
+    let hue_width = Metric::Pixel(HUE_WIDTH + SPACING, 0.0);
+    let sv_size = size - hue_width;
+    let hue_pos = top_left + sv_size.horizontal() + Metric::Pixel(SPACING, 0.0);
+    let hue_size = Metric::Pixel(HUE_WIDTH, 0.0) + size.vertical();
+
+    let sv_state = widget_state(&interaction, id);
+    let hue_state = widget_state(&interaction, hue_id);
+
+    let parts = [
+        (
+            ColorPickerPart::SaturationValue,
+            top_left,
+            sv_size,
+            sv_state,
+        ),
+        (ColorPickerPart::Hue, hue_pos, hue_size, hue_state),
+    ];
+
+    for &(part, pos, size, wstate) in parts.iter() {
+        add_widget(|id, mut state| {
+            state.pivot = Metric::Native(0.0, 0.0);
+            ColorPicker::new(id, pos, size, state, wstate, part, (h, s, v))
+        });
+    }
+
+    changed
+}
+
+/// Whether the ui used the pointer in the last `pre_render`, i.e. it is over an interactive
+/// widget or a modal, or a widget is pressed or dragged. The world input should be skipped
+pub fn wants_mouse() -> bool {
+    let imgui = instance::imgui_inst();
+    let inner = imgui.inner.lock().unwrap();
+    inner.wants_mouse
+}
+
+/// Whether a widget had the keyboard focus in the last `pre_render`, e.g. a text field
+pub fn wants_keyboard() -> bool {
+    let imgui = instance::imgui_inst();
+    let inner = imgui.inner.lock().unwrap();
+    inner.wants_keyboard
+}
+
+/// The widgets of the last frame and their rects in logical pixels, resolved by the last
+/// `pre_render`, e.g. to assert the layout in tests. Empty before the first `pre_render`
+pub fn layout_snapshot() -> Vec<WidgetRect> {
+    let imgui = instance::imgui_inst();
+    let inner = imgui.inner.lock().unwrap();
+    snapshot::snapshot(&inner.render_list, inner.screen_size, inner.hidpi)
+}
+
+/// Show the statistics of the renderer in the top-right corner over the other widgets,
+/// e.g. the fps, the draw calls and the memory estimates of the drawn meshes and textures
+pub fn debug_overlay(enabled: bool) {
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    inner.debug_overlay.enabled = enabled;
+}
+
+/// Weight of the last frame in the smoothed frame time of the debug overlay
+const DEBUG_FRAME_TIME_WEIGHT: f64 = 0.1;
+
+/// Add the label of the debug overlay at the end of the frame
+fn add_debug_overlay(stats: &EngineStats) {
+    let (text, saved) = {
+        let imgui = instance::imgui_inst();
+        let mut inner = imgui.inner.lock().unwrap();
+        if !inner.debug_overlay.enabled {
+            return;
+        }
+
+        let frame_time = {
+            let now = now();
+            let overlay = &mut inner.debug_overlay;
+            if let Some(last) = overlay.last_time {
+                let dt = now - last;
+                overlay.frame_time = if overlay.frame_time > 0.0 {
+                    overlay.frame_time + (dt - overlay.frame_time) * DEBUG_FRAME_TIME_WEIGHT
+                } else {
+                    dt
+                };
+            }
+            overlay.last_time = Some(now);
+            overlay.frame_time
+        };
+
+        let text = format!(
+            "fps: {:.0} ({:.2}ms)\ndraw calls: {}\ntriangles: {}\nstate changes: {}\n\
+             binds: {} programs, {} textures\ngame objects: {}\n\
+             surfaces: {} (culled: {}, occluded objects: {})\nmeshes: {:.1}KB\ntextures: {:.1}KB",
+            1.0 / frame_time.max(1e-6),
+            frame_time * 1000.0,
+            stats.draw_calls,
+            stats.triangle_count,
+            stats.state_changes,
+            stats.program_binds,
+            stats.texture_binds,
+            stats.game_object_count,
+            stats.submitted_count,
+            stats.culled_count,
+            stats.occluded_count,
+            stats.mesh_memory as f32 / 1024.0,
+            stats.texture_memory as f32 / 1024.0
+        );
+
+        // The overlay does not depend on the state left by the frame
+        let saved = (
+            mem::replace(&mut inner.state, instance::ImguiState::default()),
+            inner.anchor.take(),
+        );
+        inner.state.pivot = Metric::Native(1.0, 0.0);
+        inner.state.layer = i32::max_value();
+        inner.state.background_color = Some(Color::new(0.0, 0.0, 0.0, 0.6));
+
+        (text, saved)
+    };
+
+    push_id("debug_overlay");
+    label(Metric::Native(1.0, 0.0) + Metric::Pixel(-8.0, 8.0), &text);
+    pop_id();
+
+    let imgui = instance::imgui_inst();
+    let mut inner = imgui.inner.lock().unwrap();
+    inner.state = saved.0;
+    inner.anchor = saved.1;
+}
+
+pub fn pre_render(engine: &mut IEngine) {
+    add_debug_overlay(&engine.stats());
+
     let imgui = instance::imgui_inst();
     let mut inner = imgui.inner.lock().unwrap();
 
     let ctx = { &mut engine.gui_context() };
     let mut ctx_mut = ctx.borrow_mut();
 
+    inner.screen_size = engine.screen_size();
+    inner.hidpi = engine.hidpi_factor() * inner.ui_scale.0;
     ctx_mut.update(&mut inner, engine);
 }
 
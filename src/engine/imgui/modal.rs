@@ -0,0 +1,91 @@
+use super::instance::ImguiState;
+use super::widgets;
+use super::widgets::Widget;
+use super::{image, Metric};
+
+use engine::core::Aabb;
+use engine::{Asset, GameObject, IEngine, Mesh, MeshBuffer};
+use math::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Fullscreen translucent quad of a modal, which captures the input.
+/// Only the backdrop and the `len - 1` widgets after it can be interacted
+#[derive(Debug, PartialEq, Clone)]
+pub struct Backdrop {
+    id: u32,
+    pub state: ImguiState,
+    /// Number of the widgets in the modal, including the backdrop
+    pub len: usize,
+}
+
+impl Backdrop {
+    pub fn new(id: u32, state: ImguiState) -> Widget {
+        Widget::Modal(Self { id, state, len: 1 })
+    }
+
+    /// The same backdrop with the number of the widgets in the modal
+    pub fn with_len(&self, len: usize) -> Widget {
+        Widget::Modal(Self {
+            len,
+            ..self.clone()
+        })
+    }
+
+    pub fn rect(&self, ssize: (u32, u32), hidpi: f32) -> Aabb {
+        widgets::compute_rect(
+            &Metric::Native(0.0, 0.0),
+            &Metric::Native(1.0, 1.0),
+            &Metric::Native(0.0, 0.0),
+            &ssize,
+            hidpi,
+        )
+    }
+
+    pub fn bind(
+        &self,
+        ssize: (u32, u32),
+        parent: &GameObject,
+        engine: &mut IEngine,
+    ) -> Rc<RefCell<GameObject>> {
+        let hidpi = engine.hidpi_factor();
+        let quad = image::make_quad_mesh_data(image::compute_size_to_ndc(
+            &Metric::Native(1.0, 1.0),
+            &ssize,
+            hidpi,
+        ));
+
+        let mut mesh = Mesh::new();
+        mesh.add_surface(
+            MeshBuffer::new(quad),
+            widgets::new_color_material(engine, self.state.style.dim_color),
+        );
+        mesh.layer = self.state.layer;
+
+        // Game Object
+        let go = engine.new_game_object(parent);
+        let mut gomut = go.borrow_mut();
+
+        let mut gtrans = gomut.transform.global();
+        let rect = self.rect(ssize, hidpi);
+        gtrans.disp += Vector3::new(rect.min.x, rect.max.y, 0.0);
+        gomut.transform.set_global(gtrans);
+        gomut.add_component(mesh);
+        drop(gomut);
+
+        go
+    }
+}
+
+impl widgets::WidgetBinder for Backdrop {
+    fn id(&self) -> u32 {
+        self.id
+    }
+
+    fn is_same(&self, other: &Widget) -> bool {
+        match other {
+            &Widget::Modal(ref m) => m == self,
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,387 @@
+use super::instance::{ImguiRaw, KeyInput, NavInput, PointerKind};
+use super::shortcut::KeyChord;
+use std::char;
+use std::fmt;
+
+/// An input fed to imgui or a result of it, which is recorded by `imgui::start_recording`
+#[derive(Debug, PartialEq, Clone)]
+pub enum UiEvent {
+    /// The pointer moved to (x, y) in ui pixels
+    Move(PointerKind, f32, f32),
+    /// The pointer was pressed at (x, y) in ui pixels
+    Press(PointerKind, f32, f32),
+    Release,
+    Wheel(f32, f32),
+    Key(KeyInput),
+    Chord(KeyChord),
+    Nav(NavInput),
+    /// The IME composition in progress, None when it is ended
+    Composition(Option<String>),
+    /// The widget was clicked, it is a result of the inputs and not replayed
+    Clicked(u32),
+}
+
+/// An event and the frame it happened in, counted from the start of the recording
+#[derive(Debug, PartialEq, Clone)]
+pub struct UiRecord {
+    pub frame: u64,
+    pub event: UiEvent,
+}
+
+/// Recorded events of imgui, which are replayed by `imgui::start_playback`.
+/// It is serialized into a text of a record per line by `to_string` and `parse`
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct UiLog {
+    pub records: Vec<UiRecord>,
+}
+
+impl UiLog {
+    /// The frames and the ids of the clicked widgets, e.g. to compare a replay
+    /// with the recording
+    pub fn clicks(&self) -> Vec<(u64, u32)> {
+        self.records
+            .iter()
+            .filter_map(|r| match r.event {
+                UiEvent::Clicked(id) => Some((r.frame, id)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Parse the text of `to_string`, the error is the number of the invalid line from 1
+    pub fn parse(s: &str) -> Result<UiLog, usize> {
+        let mut records = Vec::new();
+
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let record = parse_record(line).ok_or(i + 1)?;
+            records.push(record);
+        }
+
+        Ok(UiLog { records })
+    }
+}
+
+fn kind_name(kind: PointerKind) -> &'static str {
+    match kind {
+        PointerKind::Mouse => "mouse",
+        PointerKind::Touch => "touch",
+    }
+}
+
+fn nav_name(nav: NavInput) -> &'static str {
+    match nav {
+        NavInput::Up => "up",
+        NavInput::Down => "down",
+        NavInput::Left => "left",
+        NavInput::Right => "right",
+        NavInput::Activate => "activate",
+    }
+}
+
+impl fmt::Display for UiEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let b = |v: bool| if v { 1 } else { 0 };
+
+        match self {
+            &UiEvent::Move(kind, x, y) => write!(f, "move {} {} {}", kind_name(kind), x, y),
+            &UiEvent::Press(kind, x, y) => write!(f, "press {} {} {}", kind_name(kind), x, y),
+            &UiEvent::Release => write!(f, "release"),
+            &UiEvent::Wheel(dx, dy) => write!(f, "wheel {} {}", dx, dy),
+            &UiEvent::Key(key) => match key {
+                KeyInput::Char(c) => write!(f, "key char {}", c as u32),
+                KeyInput::Backspace => write!(f, "key backspace"),
+                KeyInput::Delete => write!(f, "key delete"),
+                KeyInput::Left(s) => write!(f, "key left {}", b(s)),
+                KeyInput::Right(s) => write!(f, "key right {}", b(s)),
+                KeyInput::Home(s) => write!(f, "key home {}", b(s)),
+                KeyInput::End(s) => write!(f, "key end {}", b(s)),
+                KeyInput::SelectAll => write!(f, "key selectall"),
+                KeyInput::Copy => write!(f, "key copy"),
+                KeyInput::Cut => write!(f, "key cut"),
+                KeyInput::Paste => write!(f, "key paste"),
+            },
+            &UiEvent::Chord(ref c) => write!(
+                f,
+                "chord {} {} {} {}",
+                c.code,
+                b(c.ctrl),
+                b(c.shift),
+                b(c.alt)
+            ),
+            &UiEvent::Nav(nav) => write!(f, "nav {}", nav_name(nav)),
+            // The text is written in char codes to keep the spaces
+            &UiEvent::Composition(None) => write!(f, "compose end"),
+            &UiEvent::Composition(Some(ref s)) => {
+                write!(f, "compose text")?;
+                for c in s.chars() {
+                    write!(f, " {}", c as u32)?;
+                }
+                Ok(())
+            }
+            &UiEvent::Clicked(id) => write!(f, "clicked {}", id),
+        }
+    }
+}
+
+impl fmt::Display for UiLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for r in self.records.iter() {
+            writeln!(f, "{} {}", r.frame, r.event)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_record(line: &str) -> Option<UiRecord> {
+    let mut words = line.split_whitespace();
+    let frame = words.next()?.parse().ok()?;
+    let args: Vec<&str> = words.collect();
+
+    let f32_at = |i: usize| args.get(i).and_then(|s| s.parse::<f32>().ok());
+    let bool_at = |i: usize| args.get(i).map(|s| *s == "1");
+    let kind_at = |i: usize| match args.get(i) {
+        Some(&"mouse") => Some(PointerKind::Mouse),
+        Some(&"touch") => Some(PointerKind::Touch),
+        _ => None,
+    };
+
+    let event = match *args.get(0)? {
+        "move" => UiEvent::Move(kind_at(1)?, f32_at(2)?, f32_at(3)?),
+        "press" => UiEvent::Press(kind_at(1)?, f32_at(2)?, f32_at(3)?),
+        "release" => UiEvent::Release,
+        "wheel" => UiEvent::Wheel(f32_at(1)?, f32_at(2)?),
+        "key" => UiEvent::Key(match *args.get(1)? {
+            "char" => KeyInput::Char(char::from_u32(args.get(2)?.parse().ok()?)?),
+            "backspace" => KeyInput::Backspace,
+            "delete" => KeyInput::Delete,
+            "left" => KeyInput::Left(bool_at(2)?),
+            "right" => KeyInput::Right(bool_at(2)?),
+            "home" => KeyInput::Home(bool_at(2)?),
+            "end" => KeyInput::End(bool_at(2)?),
+            "selectall" => KeyInput::SelectAll,
+            "copy" => KeyInput::Copy,
+            "cut" => KeyInput::Cut,
+            "paste" => KeyInput::Paste,
+            _ => return None,
+        }),
+        "chord" => UiEvent::Chord(KeyChord {
+            code: args.get(1)?.to_string(),
+            ctrl: bool_at(2)?,
+            shift: bool_at(3)?,
+            alt: bool_at(4)?,
+        }),
+        "nav" => UiEvent::Nav(match *args.get(1)? {
+            "up" => NavInput::Up,
+            "down" => NavInput::Down,
+            "left" => NavInput::Left,
+            "right" => NavInput::Right,
+            "activate" => NavInput::Activate,
+            _ => return None,
+        }),
+        "compose" => match *args.get(1)? {
+            "end" => UiEvent::Composition(None),
+            "text" => {
+                let mut s = String::new();
+                for code in args[2..].iter() {
+                    s.push(char::from_u32(code.parse().ok()?)?);
+                }
+                UiEvent::Composition(Some(s))
+            }
+            _ => return None,
+        },
+        "clicked" => UiEvent::Clicked(args.get(1)?.parse().ok()?),
+        _ => return None,
+    };
+
+    Some(UiRecord { frame, event })
+}
+
+/// Recording and playback state of an imgui instance
+#[derive(Debug, Default)]
+pub struct Recorder {
+    /// Frames begun since the recording or the playback was started
+    frame: u64,
+    recording: Option<UiLog>,
+    /// The log being replayed and the index of the next record
+    playback: Option<(UiLog, usize)>,
+}
+
+impl Recorder {
+    pub fn start_recording(&mut self) {
+        self.frame = 0;
+        self.recording = Some(UiLog::default());
+    }
+
+    pub fn stop_recording(&mut self) -> Option<UiLog> {
+        self.recording.take()
+    }
+
+    pub fn start_playback(&mut self, log: UiLog) {
+        self.frame = 0;
+        self.playback = Some((log, 0));
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    pub fn record(&mut self, event: &UiEvent) {
+        let frame = self.frame;
+        if let Some(ref mut log) = self.recording {
+            log.records.push(UiRecord {
+                frame,
+                event: event.clone(),
+            });
+        }
+    }
+
+    /// Count a frame, return the replayed inputs which are due until it
+    pub fn next_frame(&mut self) -> Vec<UiEvent> {
+        self.frame += 1;
+
+        let frame = self.frame;
+        let mut due = Vec::new();
+        let mut finished = false;
+
+        if let Some((ref log, ref mut next)) = self.playback {
+            while *next < log.records.len() && log.records[*next].frame <= frame {
+                match log.records[*next].event {
+                    UiEvent::Clicked(_) => (),
+                    ref event => due.push(event.clone()),
+                }
+                *next += 1;
+            }
+            finished = *next >= log.records.len();
+        }
+
+        if finished {
+            self.playback = None;
+        }
+
+        due
+    }
+}
+
+/// Record the input `event` and apply it to the state of the instance
+pub fn apply(inner: &mut ImguiRaw, event: UiEvent) {
+    inner.recorder.record(&event);
+
+    match event {
+        UiEvent::Move(kind, x, y) => {
+            inner.pointer.kind = kind;
+            inner.pointer.pos = (x, y);
+        }
+        UiEvent::Press(kind, x, y) => inner.pointer.press(kind, (x, y)),
+        UiEvent::Release => inner.pointer.release(),
+        UiEvent::Wheel(dx, dy) => {
+            inner.pointer.wheel.0 += dx;
+            inner.pointer.wheel.1 += dy;
+        }
+        UiEvent::Key(key) => inner.keys.push(key),
+        UiEvent::Chord(chord) => inner.chords.push(chord),
+        UiEvent::Nav(nav) => inner.nav_inputs.push(nav),
+        UiEvent::Composition(s) => inner.composition = s,
+        UiEvent::Clicked(_) => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(frame: u64, event: UiEvent) -> UiRecord {
+        UiRecord { frame, event }
+    }
+
+    fn sample() -> UiLog {
+        UiLog {
+            records: vec![
+                record(0, UiEvent::Move(PointerKind::Mouse, 10.5, -3.0)),
+                record(1, UiEvent::Press(PointerKind::Touch, 1.0, 2.25)),
+                record(1, UiEvent::Release),
+                record(2, UiEvent::Wheel(0.0, -120.0)),
+                record(3, UiEvent::Key(KeyInput::Char('é'))),
+                record(3, UiEvent::Key(KeyInput::Left(true))),
+                record(3, UiEvent::Key(KeyInput::End(false))),
+                record(3, UiEvent::Key(KeyInput::Paste)),
+                record(
+                    4,
+                    UiEvent::Chord(KeyChord {
+                        code: "KeyS".to_string(),
+                        ctrl: true,
+                        shift: false,
+                        alt: true,
+                    }),
+                ),
+                record(5, UiEvent::Nav(NavInput::Activate)),
+                record(6, UiEvent::Composition(Some("か な".to_string()))),
+                record(6, UiEvent::Composition(None)),
+                record(7, UiEvent::Clicked(42)),
+            ],
+        }
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let log = sample();
+        let text = log.to_string();
+
+        assert_eq!(text.lines().count(), log.records.len());
+        assert_eq!(UiLog::parse(&text), Ok(log));
+        assert_eq!(UiLog::parse(""), Ok(UiLog::default()));
+    }
+
+    #[test]
+    fn blank_lines_are_skipped() {
+        let log = UiLog::parse("\n  0 release  \n\n2 clicked 7\n").unwrap();
+        assert_eq!(
+            log.records,
+            vec![record(0, UiEvent::Release), record(2, UiEvent::Clicked(7))]
+        );
+        assert_eq!(log.clicks(), vec![(2, 7)]);
+    }
+
+    #[test]
+    fn malformed_lines() {
+        let lines = [
+            "release",
+            "x release",
+            "-1 release",
+            "0",
+            "0 jump",
+            "0 move pen 1 2",
+            "0 move mouse 1",
+            "0 wheel a 1",
+            "0 key",
+            "0 key char 55296",
+            "0 key left",
+            "0 key f1",
+            "0 chord KeyS 1 0",
+            "0 nav back",
+            "0 compose",
+            "0 compose text 0x41",
+            "0 clicked -1",
+        ];
+
+        for line in lines.iter() {
+            let text = format!("0 release\n\n{}\n1 release", line);
+            assert_eq!(UiLog::parse(&text), Err(3), "{}", line);
+        }
+    }
+
+    #[test]
+    fn playback_skips_the_results() {
+        let mut recorder = Recorder::default();
+        recorder.start_playback(UiLog::parse("1 release\n1 clicked 3\n2 key copy").unwrap());
+
+        assert_eq!(recorder.next_frame(), vec![UiEvent::Release]);
+        assert!(recorder.is_playing());
+        assert_eq!(recorder.next_frame(), vec![UiEvent::Key(KeyInput::Copy)]);
+        assert!(!recorder.is_playing());
+    }
+}
@@ -0,0 +1,63 @@
+use super::instance::ImguiState;
+use super::widgets;
+use super::widgets::Widget;
+use super::Metric;
+
+use engine::core::Aabb;
+use engine::{GameObject, IEngine};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Invisible interactive region of `imgui::interact`
+#[derive(Debug, PartialEq)]
+pub struct Region {
+    id: u32,
+    pub pos: Metric,
+    pub size: Metric,
+    pub state: ImguiState,
+}
+
+impl Region {
+    pub fn new(id: u32, pos: Metric, size: Metric, state: ImguiState) -> Widget {
+        Widget::Region(Self {
+            id,
+            pos,
+            size,
+            state,
+        })
+    }
+
+    pub fn rect(&self, ssize: (u32, u32), hidpi: f32) -> Aabb {
+        widgets::compute_rect(&self.pos, &self.size, &self.state.pivot, &ssize, hidpi)
+    }
+
+    /// Nothing is drawn, the game object only holds the place of the region
+    pub fn bind(
+        &self,
+        _ssize: (u32, u32),
+        parent: &GameObject,
+        engine: &mut IEngine,
+    ) -> Rc<RefCell<GameObject>> {
+        engine.new_game_object(parent)
+    }
+}
+
+impl widgets::WidgetBinder for Region {
+    fn id(&self) -> u32 {
+        self.id
+    }
+
+    fn is_same(&self, other: &Widget) -> bool {
+        match other {
+            &Widget::Region(ref r) => r == self,
+            _ => false,
+        }
+    }
+
+    fn is_moved(&self, other: &Widget) -> bool {
+        match other {
+            &Widget::Region(ref r) => r.size == self.size && r.state == self.state,
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use super::button::Button;
+use super::image::{FitMode, Image, NineSliceBorder};
+use super::instance::{self, ImguiState};
+use super::label::Label;
+use super::region::Region;
+use super::widgets::{Widget, WidgetState};
+use super::Metric;
+use engine::Texture;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+/// Kind of a retained `Node`
+#[derive(Debug, Clone)]
+pub enum NodeKind {
+    /// Container of the children, drawn as the nine-slice texture if any.
+    /// It is hit-tested, such that it takes the pointer over it
+    Panel(Option<(Rc<Texture>, NineSliceBorder)>),
+    Label(String),
+    Image(Rc<Texture>, FitMode),
+    Button(String),
+}
+
+/// The widget of a node and the inputs it was compiled from
+struct Compiled {
+    id: u32,
+    pos: Metric,
+    size: Metric,
+    state: ImguiState,
+    wstate: WidgetState,
+    widget: Rc<Widget>,
+}
+
+/// A node of a retained ui tree, e.g. for static menus, which is added by `imgui::retained`.
+/// Its widget is compiled once and reused until the node is changed, such that its game
+/// object is kept without comparing the widgets. Nodes are placed by their top-left corner,
+/// the children relative to their parent, and the percent metrics of the children are
+/// relative to the size of their parent
+pub struct Node {
+    kind: RefCell<NodeKind>,
+    pos: Cell<Metric>,
+    size: Cell<Metric>,
+    children: RefCell<Vec<Rc<Node>>>,
+    /// The widget of the last frame, None if the node is changed
+    compiled: RefCell<Option<Compiled>>,
+    /// The button was clicked in the last frame
+    clicked: Cell<bool>,
+}
+
+impl Node {
+    pub fn new(kind: NodeKind, pos: Metric, size: Metric) -> Rc<Node> {
+        Rc::new(Node {
+            kind: RefCell::new(kind),
+            pos: Cell::new(pos),
+            size: Cell::new(size),
+            children: RefCell::new(Vec::new()),
+            compiled: RefCell::new(None),
+            clicked: Cell::new(false),
+        })
+    }
+
+    /// Invisible container of the children
+    pub fn panel(pos: Metric, size: Metric) -> Rc<Node> {
+        Node::new(NodeKind::Panel(None), pos, size)
+    }
+
+    pub fn label(pos: Metric, s: &str) -> Rc<Node> {
+        Node::new(NodeKind::Label(s.into()), pos, Metric::Native(0.0, 0.0))
+    }
+
+    pub fn image(pos: Metric, size: Metric, tex: Rc<Texture>) -> Rc<Node> {
+        Node::new(NodeKind::Image(tex, FitMode::Stretch), pos, size)
+    }
+
+    pub fn button(pos: Metric, size: Metric, s: &str) -> Rc<Node> {
+        Node::new(NodeKind::Button(s.into()), pos, size)
+    }
+
+    pub fn add_child(&self, child: Rc<Node>) {
+        self.children.borrow_mut().push(child);
+    }
+
+    pub fn remove_child(&self, child: &Rc<Node>) {
+        self.children.borrow_mut().retain(|c| !Rc::ptr_eq(c, child));
+    }
+
+    pub fn children(&self) -> Vec<Rc<Node>> {
+        self.children.borrow().clone()
+    }
+
+    pub fn kind(&self) -> NodeKind {
+        self.kind.borrow().clone()
+    }
+
+    pub fn set_kind(&self, kind: NodeKind) {
+        self.kind.replace(kind);
+        self.mark_dirty();
+    }
+
+    /// Change the text of a label or a button
+    pub fn set_text(&self, s: &str) {
+        let changed = match *self.kind.borrow_mut() {
+            NodeKind::Label(ref mut text) | NodeKind::Button(ref mut text) => {
+                let changed = text.as_str() != s;
+                if changed {
+                    *text = s.into();
+                }
+                changed
+            }
+            _ => false,
+        };
+
+        if changed {
+            self.mark_dirty();
+        }
+    }
+
+    pub fn pos(&self) -> Metric {
+        self.pos.get()
+    }
+
+    pub fn set_pos(&self, pos: Metric) {
+        self.pos.set(pos);
+    }
+
+    pub fn size(&self) -> Metric {
+        self.size.get()
+    }
+
+    pub fn set_size(&self, size: Metric) {
+        self.size.set(size);
+    }
+
+    /// Whether the button was clicked in the last frame
+    pub fn clicked(&self) -> bool {
+        self.clicked.get()
+    }
+
+    /// Compile the widget again in the next frame, e.g. after the texture is changed
+    pub fn mark_dirty(&self) {
+        self.compiled.replace(None);
+    }
+
+    fn compile(
+        &self,
+        id: u32,
+        pos: Metric,
+        size: Metric,
+        state: ImguiState,
+        wstate: WidgetState,
+    ) -> Widget {
+        match *self.kind.borrow() {
+            NodeKind::Panel(None) => Region::new(id, pos, size, state),
+            NodeKind::Panel(Some((ref tex, border))) => {
+                Image::new_nine_slice(id, pos, size, state, tex.clone(), border)
+            }
+            NodeKind::Label(ref s) => Label::new(id, pos, state, s.clone()),
+            NodeKind::Image(ref tex, fit) => Image::new_fit(id, pos, size, state, tex.clone(), fit),
+            NodeKind::Button(ref s) => Button::new(id, pos, size, state, wstate, s.clone()),
+        }
+    }
+}
+
+/// Add the widget of `node` at `pos` of `size`, then its children
+pub fn add_node(node: &Node, pos: Metric, size: Metric) {
+    let (id, mut state) = super::next_widget_state();
+    state.pivot = Metric::Native(0.0, 0.0);
+
+    let wstate = match *node.kind.borrow() {
+        NodeKind::Button(_) => {
+            let (_, interaction) = super::next_interaction();
+            node.clicked.set(interaction.clicked == Some(id));
+            super::click_state(&interaction, id)
+        }
+        _ => WidgetState::Normal,
+    };
+
+    let cached = node.compiled.borrow().as_ref().and_then(|c| {
+        let same =
+            c.id == id && c.pos == pos && c.size == size && c.wstate == wstate && c.state == state;
+        if same {
+            Some(c.widget.clone())
+        } else {
+            None
+        }
+    });
+
+    let widget = match cached {
+        Some(widget) => widget,
+        None => {
+            let widget = Rc::new(node.compile(id, pos, size, state.clone(), wstate));
+            node.compiled.replace(Some(Compiled {
+                id,
+                pos,
+                size,
+                state: state.clone(),
+                wstate,
+                widget: widget.clone(),
+            }));
+            widget
+        }
+    };
+
+    {
+        let imgui = instance::imgui_inst();
+        imgui.inner.lock().unwrap().last_rect = Some((pos, size));
+    }
+    super::push_widget(widget, state);
+
+    for child in node.children.borrow().iter() {
+        let child_pos = pos + child.pos().resolve_percent(&size);
+        let child_size = child.size().resolve_percent(&size);
+        add_node(child, child_pos, child_size);
+    }
+}
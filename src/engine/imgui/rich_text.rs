@@ -0,0 +1,182 @@
+use super::bidi;
+use super::Color;
+
+use engine::Texture;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Placeholder of an icon in the plain text
+pub const ICON_CHAR: char = '\u{fffc}';
+
+/// Atlas of the icons of the labels, see `imgui::icon_atlas`
+#[derive(Debug, Clone)]
+pub struct IconAtlas {
+    pub texture: Rc<Texture>,
+    /// Texture space (u0, v0, u1, v1) of the icons
+    icons: HashMap<String, (f32, f32, f32, f32)>,
+}
+
+impl PartialEq for IconAtlas {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.texture, &other.texture) && self.icons == other.icons
+    }
+}
+
+impl IconAtlas {
+    /// Create an icon atlas without any icon
+    pub fn new(texture: Rc<Texture>) -> IconAtlas {
+        IconAtlas {
+            texture,
+            icons: HashMap::new(),
+        }
+    }
+
+    /// Register the icon `name` at texture space (u0, v0, u1, v1)
+    pub fn add_icon(&mut self, name: &str, uv: (f32, f32, f32, f32)) {
+        self.icons.insert(name.into(), uv);
+    }
+
+    /// Register the icons of a grid of `cols` x `rows` cells,
+    /// `names` are in row by row from the top-left cell
+    pub fn add_grid(&mut self, cols: u32, rows: u32, names: &[&str]) {
+        let (cw, ch) = (1.0 / cols as f32, 1.0 / rows as f32);
+
+        for (i, name) in names.iter().enumerate().take((cols * rows) as usize) {
+            let col = (i as u32 % cols) as f32;
+            let row = (i as u32 / cols) as f32;
+            self.add_icon(name, (col * cw, row * ch, col * cw + cw, row * ch + ch));
+        }
+    }
+
+    /// Texture space (u0, v0, u1, v1) of the icon `name`
+    pub fn icon_uv(&self, name: &str) -> Option<(f32, f32, f32, f32)> {
+        self.icons.get(name).cloned()
+    }
+}
+
+/// Markup of a char of a rich text
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Markup {
+    /// Color of the glyph, None for the color of the label
+    pub color: Option<Color>,
+    /// Name of the icon of an `ICON_CHAR`
+    pub icon: Option<String>,
+}
+
+/// A text with the markup of each char, icons are `ICON_CHAR` in the plain text.
+/// The Arabic letters of the plain text are shaped, see `bidi::shape`
+#[derive(Debug)]
+pub struct RichText {
+    pub plain: String,
+    markup: Vec<Markup>,
+}
+
+/// Color of `#rrggbb` or `#rrggbbaa`
+fn parse_hex_color(s: &str) -> Option<Color> {
+    if !s.starts_with('#') || !(s.len() == 7 || s.len() == 9) {
+        return None;
+    }
+
+    let mut c = [1.0; 4];
+    for i in 0..(s.len() - 1) / 2 {
+        let v = u8::from_str_radix(s.get(1 + i * 2..3 + i * 2)?, 16).ok()?;
+        c[i] = v as f32 / 255.0;
+    }
+
+    Some(Color::new(c[0], c[1], c[2], c[3]))
+}
+
+impl RichText {
+    /// A text without markup
+    pub fn plain(s: &str) -> RichText {
+        RichText {
+            plain: bidi::shape(s),
+            markup: vec![Markup::default(); s.chars().count()],
+        }
+    }
+
+    /// Parse the markup `[color=#rrggbb]text[/color]` (or `#rrggbbaa`) and `[icon=name]`,
+    /// colors can be nested. Unknown tags are kept as text
+    pub fn parse(s: &str) -> RichText {
+        let mut plain = String::new();
+        let mut markup = Vec::new();
+        let mut colors: Vec<Color> = Vec::new();
+        let mut rest = s;
+
+        while let Some(c) = rest.chars().next() {
+            let tag = if c == '[' {
+                rest.find(']').map(|end| (&rest[1..end], end + 1))
+            } else {
+                None
+            };
+
+            let parsed = tag.and_then(|(tag, len)| {
+                if tag == "/color" {
+                    colors.pop();
+                } else if tag.starts_with("color=") {
+                    colors.push(parse_hex_color(&tag["color=".len()..])?);
+                } else if tag.starts_with("icon=") {
+                    plain.push(ICON_CHAR);
+                    markup.push(Markup {
+                        color: colors.last().cloned(),
+                        icon: Some(tag["icon=".len()..].into()),
+                    });
+                } else {
+                    return None;
+                }
+
+                Some(len)
+            });
+
+            let len = match parsed {
+                Some(len) => len,
+                None => {
+                    plain.push(c);
+                    markup.push(Markup {
+                        color: colors.last().cloned(),
+                        icon: None,
+                    });
+                    c.len_utf8()
+                }
+            };
+
+            rest = &rest[len..];
+        }
+
+        RichText {
+            plain: bidi::shape(&plain),
+            markup,
+        }
+    }
+
+    /// Whether some chars have their own colors
+    pub fn has_colors(&self) -> bool {
+        self.markup.iter().any(|m| m.color.is_some())
+    }
+
+    /// The chars and their markup of `lines`, which are wrapped from the plain text.
+    /// The spaces and the line breaks dropped by the wrapping are skipped
+    pub fn lines(&self, lines: &[String]) -> Vec<Vec<(char, &Markup)>> {
+        let mut chars = self.plain.chars().zip(self.markup.iter());
+
+        lines
+            .iter()
+            .map(|line| {
+                line.chars()
+                    .filter_map(|c| chars.find(|&(pc, _)| pc == c))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The chars and their markup of `lines` like `lines`, in the visual order from left
+    /// to right. The base direction is the direction of the first strong char of the text
+    pub fn visual_lines(&self, lines: &[String]) -> Vec<Vec<(char, &Markup)>> {
+        let rtl = bidi::is_rtl_text(&self.plain);
+
+        self.lines(lines)
+            .into_iter()
+            .map(|line| bidi::visual_line(line, rtl))
+            .collect()
+    }
+}
@@ -0,0 +1,148 @@
+use super::instance::ImguiState;
+use super::widgets;
+use super::widgets::Widget;
+use super::{image, Metric};
+
+use engine::core::Aabb;
+use engine::{Asset, GameObject, IEngine, Mesh, MeshBuffer};
+use math::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Width of the scroll bars in pixel
+const BAR_WIDTH: f32 = 4.0;
+
+/// Scroll region, the widgets between `begin_scroll` and `end_scroll` are clipped to it
+#[derive(Debug, PartialEq, Clone)]
+pub struct ScrollRegion {
+    id: u32,
+    pub pos: Metric,
+    pub size: Metric,
+    pub state: ImguiState,
+    /// Scroll offset of the content in pixel
+    pub offset: (f32, f32),
+    /// Size of the content, it is known at `end_scroll`
+    pub content: Metric,
+}
+
+impl ScrollRegion {
+    pub fn new(
+        id: u32,
+        pos: Metric,
+        size: Metric,
+        state: ImguiState,
+        offset: (f32, f32),
+    ) -> Widget {
+        Widget::Scroll(Self {
+            id,
+            pos,
+            size,
+            state,
+            offset,
+            content: Metric::Pixel(0.0, 0.0),
+        })
+    }
+
+    /// The same region with the content size
+    pub fn with_content(&self, content: Metric) -> Widget {
+        Widget::Scroll(Self {
+            content,
+            ..self.clone()
+        })
+    }
+
+    pub fn rect(&self, ssize: (u32, u32), hidpi: f32) -> Aabb {
+        widgets::compute_rect(&self.pos, &self.size, &self.state.pivot, &ssize, hidpi)
+    }
+
+    /// The largest scroll offset in pixel
+    pub fn max_offset(&self, ssize: (u32, u32), hidpi: f32) -> (f32, f32) {
+        let (w, h) = widgets::to_pixel_size(&self.size, &ssize, hidpi);
+        let (cw, ch) = widgets::to_pixel_size(&self.content, &ssize, hidpi);
+
+        ((cw - w).max(0.0), (ch - h).max(0.0))
+    }
+
+    pub fn bind(
+        &self,
+        ssize: (u32, u32),
+        parent: &GameObject,
+        engine: &mut IEngine,
+    ) -> Rc<RefCell<GameObject>> {
+        let hidpi = engine.hidpi_factor();
+        let (w, h) = widgets::to_pixel_size(&self.size, &ssize, hidpi);
+        let (cw, ch) = widgets::to_pixel_size(&self.content, &ssize, hidpi);
+
+        let mut mesh = Mesh::new();
+        let color = self.state.style.hover_color;
+
+        // Scroll bars are shown only if the content is larger than the region,
+        // the bars are placed in pixel then converted to ndc
+        let mut bars = Vec::new();
+        if ch > h && h > 0.0 {
+            let len = h * h / ch;
+            bars.push(((w - BAR_WIDTH, self.offset.1 / ch * h), (BAR_WIDTH, len)));
+        }
+        if cw > w && w > 0.0 {
+            let len = w * w / cw;
+            bars.push(((self.offset.0 / cw * w, h - BAR_WIDTH), (len, BAR_WIDTH)));
+        }
+
+        for ((x, y), (bw, bh)) in bars.into_iter() {
+            let (nx, ny) = image::compute_size_to_ndc(&Metric::Pixel(x, y), &ssize, hidpi);
+            let mut quad = image::make_quad_mesh_data(image::compute_size_to_ndc(
+                &Metric::Pixel(bw, bh),
+                &ssize,
+                hidpi,
+            ));
+            quad.translate(Vector3::new(nx, -ny, 0.0));
+
+            mesh.add_surface(
+                MeshBuffer::new(quad),
+                widgets::new_color_material(engine, color),
+            );
+        }
+
+        widgets::clip_mesh(&mesh, &self.state, ssize, hidpi);
+        widgets::transform_mesh(&mesh, &self.state, &self.rect(ssize, hidpi), ssize, hidpi);
+        mesh.layer = self.state.layer;
+
+        // Game Object
+        let go = engine.new_game_object(parent);
+        let mut gomut = go.borrow_mut();
+
+        let mut gtrans = gomut.transform.global();
+        let rect = self.rect(ssize, hidpi);
+        gtrans.disp += Vector3::new(rect.min.x, rect.max.y, 0.0);
+        gomut.transform.set_global(gtrans);
+        gomut.add_component(mesh);
+        drop(gomut);
+
+        go
+    }
+}
+
+impl widgets::WidgetBinder for ScrollRegion {
+    fn id(&self) -> u32 {
+        self.id
+    }
+
+    fn is_same(&self, other: &Widget) -> bool {
+        match other {
+            &Widget::Scroll(ref sc) => sc == self,
+            _ => false,
+        }
+    }
+
+    fn is_moved(&self, other: &Widget) -> bool {
+        match other {
+            &Widget::Scroll(ref sc) => {
+                sc.size == self.size
+                    && sc.state == self.state
+                    && sc.offset == self.offset
+                    && sc.content == self.content
+            }
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,93 @@
+/// A key with its modifiers, e.g. Ctrl+S, see `imgui::shortcut`
+#[derive(Debug, PartialEq, Clone)]
+pub struct KeyChord {
+    /// The `code` of the key event, e.g. "KeyS"
+    pub code: String,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl KeyChord {
+    /// Parse a chord like "Ctrl+S", "Ctrl+Shift+Z", "Alt+F4" or "Escape". The modifiers
+    /// are case insensitive, the key is a letter, a digit, a named key or a key code.
+    /// None if it is empty or the modifiers are unknown
+    pub fn parse(s: &str) -> Option<KeyChord> {
+        let mut parts: Vec<&str> = s.split('+').map(|p| p.trim()).collect();
+        let key = parts.pop().filter(|k| !k.is_empty())?;
+
+        let mut chord = KeyChord {
+            code: key_code(key),
+            ctrl: false,
+            shift: false,
+            alt: false,
+        };
+
+        for modifier in parts {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" | "cmd" => chord.ctrl = true,
+                "shift" => chord.shift = true,
+                "alt" | "option" => chord.alt = true,
+                _ => return None,
+            }
+        }
+
+        Some(chord)
+    }
+
+    /// Whether a focused text field uses the chord for editing,
+    /// i.e. typing, the caret moves, select all and the clipboard
+    pub fn is_text_editing(&self) -> bool {
+        if self.alt {
+            return false;
+        }
+
+        if self.ctrl {
+            return match self.code.as_str() {
+                "KeyA" | "KeyC" | "KeyV" | "KeyX" => true,
+                _ => false,
+            };
+        }
+
+        !is_function_key(&self.code) && self.code != "Escape" && self.code != "Tab"
+    }
+}
+
+/// Whether `key` is a function key like F5
+fn is_function_key(key: &str) -> bool {
+    (key.starts_with('F') || key.starts_with('f')) && key[1..].parse::<u32>().is_ok()
+}
+
+/// The code of the key event of a key name
+fn key_code(key: &str) -> String {
+    let mut chars = key.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if c.is_ascii_alphabetic() {
+            return format!("Key{}", c.to_ascii_uppercase());
+        }
+        if c.is_ascii_digit() {
+            return format!("Digit{}", c);
+        }
+    }
+
+    match key.to_lowercase().as_str() {
+        "esc" | "escape" => "Escape",
+        "space" => "Space",
+        "enter" | "return" => "Enter",
+        "tab" => "Tab",
+        "del" | "delete" => "Delete",
+        "backspace" => "Backspace",
+        "up" => "ArrowUp",
+        "down" => "ArrowDown",
+        "left" => "ArrowLeft",
+        "right" => "ArrowRight",
+        "home" => "Home",
+        "end" => "End",
+        "pageup" => "PageUp",
+        "pagedown" => "PageDown",
+        _ if is_function_key(key) => return key.to_uppercase(),
+        // The key code itself
+        _ => key,
+    }
+    .into()
+}
@@ -0,0 +1,144 @@
+use super::image;
+use super::instance::ImguiState;
+use super::widgets;
+use super::widgets::{Widget, WidgetState};
+use super::Metric;
+
+use engine::core::Aabb;
+use engine::{Asset, GameObject, IEngine, Mesh, MeshBuffer};
+use math::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Ratio of the track height to the slider height
+const TRACK_RATIO: f32 = 0.3;
+/// Ratio of the range changed by a left or right navigation input
+pub const NAV_STEP: f32 = 0.1;
+
+#[derive(Debug, PartialEq)]
+pub struct Slider {
+    id: u32,
+    pub pos: Metric,
+    pub size: Metric,
+    pub state: ImguiState,
+    pub widget_state: WidgetState,
+    /// Normalized value in 0..1
+    pub t: f32,
+}
+
+impl Slider {
+    pub fn new(
+        id: u32,
+        pos: Metric,
+        size: Metric,
+        state: ImguiState,
+        widget_state: WidgetState,
+        t: f32,
+    ) -> Widget {
+        Widget::Slider(Self {
+            id,
+            pos,
+            size,
+            state,
+            widget_state,
+            t,
+        })
+    }
+
+    pub fn rect(&self, ssize: (u32, u32), hidpi: f32) -> Aabb {
+        widgets::compute_rect(&self.pos, &self.size, &self.state.pivot, &ssize, hidpi)
+    }
+
+    /// The handle is a square in pixel space
+    fn handle_width(w: f32, h: f32, ssize: (u32, u32)) -> f32 {
+        (h * (ssize.1 as f32) / (ssize.0 as f32)).min(w)
+    }
+
+    pub fn drag_value(&self, p: (f32, f32), ssize: (u32, u32), hidpi: f32) -> f32 {
+        let rect = self.rect(ssize, hidpi);
+        let w = rect.max.x - rect.min.x;
+        let h = rect.max.y - rect.min.y;
+        let hw = Self::handle_width(w, h, ssize);
+
+        if w <= hw {
+            return 0.0;
+        }
+
+        ((p.0 - rect.min.x - hw * 0.5) / (w - hw)).max(0.0).min(1.0)
+    }
+
+    pub fn bind(
+        &self,
+        ssize: (u32, u32),
+        parent: &GameObject,
+        engine: &mut IEngine,
+    ) -> Rc<RefCell<GameObject>> {
+        let hidpi = engine.hidpi_factor();
+        let (w, h) = image::compute_size_to_ndc(&self.size, &ssize, hidpi);
+        let hw = Self::handle_width(w, h, ssize);
+        let style = &self.state.style;
+
+        // Track
+        let th = h * TRACK_RATIO;
+        let mut track = image::make_quad_mesh_data((w, th));
+        track.translate(Vector3::new(0.0, -(h - th) * 0.5, 0.0));
+
+        //Mesh
+        let mut mesh = Mesh::new();
+        mesh.add_surface(
+            MeshBuffer::new(track),
+            widgets::new_color_material(engine, style.active_color),
+        );
+        widgets::add_frame(
+            &mut mesh,
+            engine,
+            ssize,
+            style,
+            style.frame_color(self.widget_state),
+            ((w - hw) * self.t, 0.0),
+            (hw, h),
+        );
+
+        widgets::clip_mesh(&mesh, &self.state, ssize, hidpi);
+        widgets::transform_mesh(&mesh, &self.state, &self.rect(ssize, hidpi), ssize, hidpi);
+        mesh.layer = self.state.layer;
+
+        // Game Object
+        let go = engine.new_game_object(parent);
+        let mut gomut = go.borrow_mut();
+
+        let mut gtrans = gomut.transform.global();
+        let rect = self.rect(ssize, hidpi);
+        gtrans.disp += Vector3::new(rect.min.x, rect.max.y, 0.0);
+        gomut.transform.set_global(gtrans);
+        gomut.add_component(mesh);
+        drop(gomut);
+
+        go
+    }
+}
+
+impl widgets::WidgetBinder for Slider {
+    fn id(&self) -> u32 {
+        self.id
+    }
+
+    fn is_same(&self, other: &Widget) -> bool {
+        match other {
+            &Widget::Slider(ref sld) => sld == self,
+            _ => false,
+        }
+    }
+
+    fn is_moved(&self, other: &Widget) -> bool {
+        match other {
+            &Widget::Slider(ref sld) => {
+                sld.size == self.size
+                    && sld.state == self.state
+                    && sld.widget_state == self.widget_state
+                    && sld.t == self.t
+            }
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use engine::core::Aabb;
+
+use super::widgets::{self, Widget};
+use std::rc::Rc;
+
+/// Type of a widget in the layout snapshot
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum WidgetKind {
+    Image,
+    Label,
+    Button,
+    Slider,
+    Toggle,
+    TextField,
+    Scroll,
+    Modal,
+    TreeNode,
+    ColorPicker,
+    Region,
+    FocusRing,
+    Canvas,
+}
+
+/// A widget and its resolved rect in logical pixels, see `imgui::layout_snapshot`
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct WidgetRect {
+    pub id: u32,
+    pub kind: WidgetKind,
+    /// Top-left in logical pixels
+    pub pos: (f32, f32),
+    pub size: (f32, f32),
+}
+
+impl WidgetRect {
+    pub fn contains(&self, p: (f32, f32)) -> bool {
+        p.0 >= self.pos.0
+            && p.0 <= self.pos.0 + self.size.0
+            && p.1 >= self.pos.1
+            && p.1 <= self.pos.1 + self.size.1
+    }
+}
+
+fn widget_kind(w: &Widget) -> WidgetKind {
+    match w {
+        &Widget::Image(_) => WidgetKind::Image,
+        &Widget::Label(_) => WidgetKind::Label,
+        &Widget::Button(_) => WidgetKind::Button,
+        &Widget::Slider(_) => WidgetKind::Slider,
+        &Widget::Toggle(_) => WidgetKind::Toggle,
+        &Widget::TextField(_) => WidgetKind::TextField,
+        &Widget::Scroll(_) => WidgetKind::Scroll,
+        &Widget::Modal(_) => WidgetKind::Modal,
+        &Widget::TreeNode(_) => WidgetKind::TreeNode,
+        &Widget::ColorPicker(_) => WidgetKind::ColorPicker,
+        &Widget::Region(_) => WidgetKind::Region,
+        &Widget::FocusRing(_) => WidgetKind::FocusRing,
+        &Widget::Canvas(_) => WidgetKind::Canvas,
+    }
+}
+
+/// The ndc rect of the widget, labels are measured like they are bound
+fn widget_rect(w: &Widget, ssize: (u32, u32), hidpi: f32) -> Option<Aabb> {
+    match w {
+        &Widget::Label(ref label) => {
+            let mut mesh_data = label.bind(ssize, hidpi);
+            let disp = widgets::compute_translate(
+                &label.pos,
+                &label.state.pivot,
+                &ssize,
+                hidpi,
+                &mesh_data.bounds(),
+            );
+
+            mesh_data.translate(disp);
+            Some(mesh_data.bounds())
+        }
+        _ => w.bound_rect(ssize, hidpi),
+    }
+}
+
+/// Convert a ndc rect to the top-left and the size in logical pixels
+fn ndc_to_pixel_rect(rect: &Aabb, ssize: (u32, u32), hidpi: f32) -> ((f32, f32), (f32, f32)) {
+    let sx = ssize.0 as f32 / (2.0 * hidpi);
+    let sy = ssize.1 as f32 / (2.0 * hidpi);
+
+    (
+        ((rect.min.x + 1.0) * sx, (1.0 - rect.max.y) * sy),
+        (
+            (rect.max.x - rect.min.x) * sx,
+            (rect.max.y - rect.min.y) * sy,
+        ),
+    )
+}
+
+/// The rects of the widgets of `render_list` in the order they were added,
+/// the untransformed rects are reported like hit-testing
+pub fn snapshot(render_list: &[Rc<Widget>], ssize: (u32, u32), hidpi: f32) -> Vec<WidgetRect> {
+    if hidpi <= 0.0 || ssize.0 == 0 || ssize.1 == 0 {
+        return Vec::new();
+    }
+
+    render_list
+        .iter()
+        .filter_map(|w| {
+            widget_rect(w, ssize, hidpi).map(|rect| {
+                let (pos, size) = ndc_to_pixel_rect(&rect, ssize, hidpi);
+                WidgetRect {
+                    id: w.id(),
+                    kind: widget_kind(w),
+                    pos,
+                    size,
+                }
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,96 @@
+use super::widgets::WidgetState;
+use super::Color;
+
+use engine::Font;
+use std::rc::Rc;
+
+/// Visual settings shared by the widgets, see `imgui::push_style`
+#[derive(Debug, Clone)]
+pub struct Style {
+    /// TrueType font of texts, None for the bitmap font
+    pub font: Option<Rc<Font>>,
+    pub text_color: Color,
+    /// Frame colors of interactive widgets
+    pub normal_color: Color,
+    pub hover_color: Color,
+    pub active_color: Color,
+    /// Space between the frame and the content in pixel
+    pub padding: f32,
+    /// Width of the frame border in pixel, 0 for no border
+    pub border: f32,
+    pub border_color: Color,
+    /// Color of the quad covering the screen behind a modal
+    pub dim_color: Color,
+    /// Color of the ring around the widget which has the navigation focus
+    pub focus_color: Color,
+    /// Color of the lines and the bars of the plots
+    pub plot_color: Color,
+    /// Color of the highlight behind the selected text of a text field
+    pub selection_color: Color,
+}
+
+/// Shadow and outline of the texts of labels, see `imgui::text_style`
+#[derive(Default, Debug, PartialEq, Clone, Copy)]
+pub struct TextStyle {
+    /// Offset in pixel and color of the shadow, positive y is downwards
+    pub shadow: Option<((f32, f32), Color)>,
+    /// Width in pixel and color of the outline
+    pub outline: Option<(f32, Color)>,
+}
+
+impl PartialEq for Style {
+    fn eq(&self, other: &Self) -> bool {
+        let same_font = match (&self.font, &other.font) {
+            (&Some(ref a), &Some(ref b)) => Rc::ptr_eq(a, b),
+            (&None, &None) => true,
+            _ => false,
+        };
+
+        same_font
+            && self.text_color == other.text_color
+            && self.normal_color == other.normal_color
+            && self.hover_color == other.hover_color
+            && self.active_color == other.active_color
+            && self.padding == other.padding
+            && self.border == other.border
+            && self.border_color == other.border_color
+            && self.dim_color == other.dim_color
+            && self.focus_color == other.focus_color
+            && self.plot_color == other.plot_color
+    }
+}
+
+impl Default for Style {
+    fn default() -> Style {
+        let gray = |v: u8| {
+            let v = v as f32 / 255.0;
+            Color::new(v, v, v, 0xe0 as f32 / 255.0)
+        };
+
+        Style {
+            font: None,
+            text_color: Color::default(),
+            normal_color: gray(0x44),
+            hover_color: gray(0x66),
+            active_color: gray(0x22),
+            padding: 4.0,
+            border: 0.0,
+            border_color: Color::default(),
+            dim_color: Color::new(0.0, 0.0, 0.0, 0.5),
+            focus_color: Color::new(1.0, 0.8, 0.2, 1.0),
+            plot_color: Color::new(0.4, 0.7, 1.0, 1.0),
+            selection_color: Color::new(0.2, 0.4, 0.8, 0.6),
+        }
+    }
+}
+
+impl Style {
+    /// The frame color of a widget in `state`
+    pub fn frame_color(&self, state: WidgetState) -> Color {
+        match state {
+            WidgetState::Normal => self.normal_color,
+            WidgetState::Hover => self.hover_color,
+            WidgetState::Active => self.active_color,
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use super::instance::ImguiState;
+use super::widgets;
+use super::widgets::{Widget, WidgetState};
+use super::{image, label};
+use super::{Metric, TextAlign};
+
+use engine::core::Aabb;
+use engine::{Asset, GameObject, IEngine, Mesh, MeshBuffer};
+use math::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Width of the caret in pixel
+const CARET_WIDTH: f32 = 1.0;
+/// Height of the underline of the IME composition in pixel
+const UNDERLINE_HEIGHT: f32 = 1.0;
+
+#[derive(Debug, PartialEq)]
+pub struct TextField {
+    id: u32,
+    pub pos: Metric,
+    pub size: Metric,
+    pub state: ImguiState,
+    pub widget_state: WidgetState,
+    /// Caret position in chars, None if the caret is hidden
+    pub caret: Option<usize>,
+    /// Selected range of chars, None if nothing is selected
+    pub selection: Option<(usize, usize)>,
+    /// Range of chars of the IME composition in progress, which is underlined
+    pub composition: Option<(usize, usize)>,
+    s: String,
+}
+
+impl TextField {
+    pub fn new(
+        id: u32,
+        pos: Metric,
+        size: Metric,
+        state: ImguiState,
+        widget_state: WidgetState,
+        caret: Option<usize>,
+        selection: Option<(usize, usize)>,
+        composition: Option<(usize, usize)>,
+        s: String,
+    ) -> Widget {
+        Widget::TextField(Self {
+            id,
+            pos,
+            size,
+            state,
+            widget_state,
+            caret,
+            selection,
+            composition,
+            s,
+        })
+    }
+
+    pub fn rect(&self, ssize: (u32, u32), hidpi: f32) -> Aabb {
+        widgets::compute_rect(&self.pos, &self.size, &self.state.pivot, &ssize, hidpi)
+    }
+
+    pub fn bind(
+        &self,
+        ssize: (u32, u32),
+        parent: &GameObject,
+        engine: &mut IEngine,
+    ) -> Rc<RefCell<GameObject>> {
+        let hidpi = engine.hidpi_factor();
+        let style = &self.state.style;
+        let (w, h) = image::compute_size_to_ndc(&self.size, &ssize, hidpi);
+        let (px, _) = image::compute_size_to_ndc(&Metric::Pixel(style.padding, 0.0), &ssize, hidpi);
+        // Height of a line of text
+        let (_, gh) =
+            image::compute_size_to_ndc(&label::label_size("", &self.state, None), &ssize, hidpi);
+
+        let mut mesh = Mesh::new();
+
+        // Frame
+        widgets::add_frame(
+            &mut mesh,
+            engine,
+            ssize,
+            style,
+            style.frame_color(self.widget_state),
+            (0.0, 0.0),
+            (w, h),
+        );
+
+        // Selection, behind the text
+        let text_y = -(h - gh) * 0.5;
+        if let Some((start, end)) = self.selection {
+            let sx = prefix_width(&self.s, start, &self.state, ssize, hidpi);
+            let ex = prefix_width(&self.s, end, &self.state, ssize, hidpi);
+
+            let mut quad = image::make_quad_mesh_data((ex - sx, gh));
+            quad.translate(Vector3::new(px + sx, text_y, 0.0));
+
+            mesh.add_surface(
+                MeshBuffer::new(quad),
+                widgets::new_color_material(engine, style.selection_color),
+            );
+        }
+
+        // Text, vertical centered
+        if self.s.len() > 0 {
+            let mut text = label::make_state_text_mesh_data(
+                &self.s,
+                TextAlign::Left,
+                &self.state,
+                None,
+                ssize,
+                hidpi,
+            );
+            text.translate(Vector3::new(px, text_y, 0.0));
+
+            mesh.add_surface(
+                MeshBuffer::new(text),
+                widgets::new_text_material(engine, &self.state),
+            );
+        }
+
+        // Composition underline, at the bottom of the line
+        if let Some((start, end)) = self.composition {
+            let sx = prefix_width(&self.s, start, &self.state, ssize, hidpi);
+            let ex = prefix_width(&self.s, end, &self.state, ssize, hidpi);
+            let (_, uh) =
+                image::compute_size_to_ndc(&Metric::Pixel(0.0, UNDERLINE_HEIGHT), &ssize, hidpi);
+
+            let mut quad = image::make_quad_mesh_data((ex - sx, uh));
+            quad.translate(Vector3::new(px + sx, text_y - gh + uh, 0.0));
+
+            mesh.add_surface(
+                MeshBuffer::new(quad),
+                widgets::new_color_material(engine, self.state.text_color()),
+            );
+        }
+
+        // Caret
+        if let Some(caret) = self.caret {
+            let (cw, _) =
+                image::compute_size_to_ndc(&Metric::Pixel(CARET_WIDTH, 0.0), &ssize, hidpi);
+
+            // Width of the text before the caret
+            let bw = prefix_width(&self.s, caret, &self.state, ssize, hidpi);
+
+            let mut quad = image::make_quad_mesh_data((cw, gh));
+            quad.translate(Vector3::new(px + bw, text_y, 0.0));
+
+            mesh.add_surface(
+                MeshBuffer::new(quad),
+                widgets::new_color_material(engine, self.state.text_color()),
+            );
+        }
+
+        widgets::clip_mesh(&mesh, &self.state, ssize, hidpi);
+        widgets::transform_mesh(&mesh, &self.state, &self.rect(ssize, hidpi), ssize, hidpi);
+        mesh.layer = self.state.layer;
+
+        // Game Object
+        let go = engine.new_game_object(parent);
+        let mut gomut = go.borrow_mut();
+
+        let mut gtrans = gomut.transform.global();
+        let rect = self.rect(ssize, hidpi);
+        gtrans.disp += Vector3::new(rect.min.x, rect.max.y, 0.0);
+        gomut.transform.set_global(gtrans);
+        gomut.add_component(mesh);
+        drop(gomut);
+
+        go
+    }
+}
+
+/// Width in ndc of the first `n` chars of `s`
+fn prefix_width(s: &str, n: usize, state: &ImguiState, ssize: (u32, u32), hidpi: f32) -> f32 {
+    let before: String = s.chars().take(n).collect();
+    let (w, _) = image::compute_size_to_ndc(
+        &label::label_size(&before, state, None).horizontal(),
+        &ssize,
+        hidpi,
+    );
+
+    w
+}
+
+/// The caret position in chars nearest to `x`, the ndc offset from the left of the field
+pub fn char_index_at(s: &str, state: &ImguiState, x: f32, ssize: (u32, u32), hidpi: f32) -> usize {
+    let (px, _) =
+        image::compute_size_to_ndc(&Metric::Pixel(state.style.padding, 0.0), &ssize, hidpi);
+    let x = x - px;
+
+    let count = s.chars().count();
+    let mut prev = 0.0;
+
+    for i in 1..count + 1 {
+        let w = prefix_width(s, i, state, ssize, hidpi);
+        if x < (prev + w) * 0.5 {
+            return i - 1;
+        }
+        prev = w;
+    }
+
+    count
+}
+
+impl widgets::WidgetBinder for TextField {
+    fn id(&self) -> u32 {
+        self.id
+    }
+
+    fn is_same(&self, other: &Widget) -> bool {
+        match other {
+            &Widget::TextField(ref tf) => tf == self,
+            _ => false,
+        }
+    }
+
+    fn is_moved(&self, other: &Widget) -> bool {
+        match other {
+            &Widget::TextField(ref tf) => {
+                tf.size == self.size
+                    && tf.state == self.state
+                    && tf.widget_state == self.widget_state
+                    && tf.caret == self.caret
+                    && tf.selection == self.selection
+                    && tf.composition == self.composition
+                    && tf.s == self.s
+            }
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use super::bitmap_font::BitmapFont;
+use super::label::make_pixel_text_mesh_data;
+use super::TextAlign;
+
+use engine::asset::{Asset, AssetSystem};
+use engine::render::{CullMode, Font, Material, MeshBuffer, MeshData, MeshSurface, RenderQueue,
+                     ShaderProgram, Texture};
+use math::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Pixels of the font size which the glyphs are rasterized at
+const DEFAULT_RESOLUTION: f32 = 32.0;
+
+/// The properties which the mesh of a text is built from
+#[derive(Clone, PartialEq)]
+struct TextKey {
+    text: String,
+    font: Option<*const Font>,
+    font_ready: bool,
+    size: f32,
+    resolution: f32,
+    align: TextAlign,
+    pivot: (f32, f32),
+    color: Vector4<f32>,
+    billboard: bool,
+}
+
+/// A text drawn in the world by the glyphs of the imgui labels, e.g. for name tags
+/// and signs. It supports the color markup of the labels, see `RichText::parse`.
+///
+/// The text lies in the xy plane of the object, or always faces the camera upright
+/// in the billboard mode. It is drawn in the transparent queue
+#[derive(Component)]
+pub struct TextMesh {
+    pub text: String,
+    /// TrueType font of the text, the builtin bitmap font if None
+    pub font: Option<Rc<Font>>,
+    /// Height of the font in world units
+    pub size: f32,
+    /// Font size in pixels which the TrueType glyphs are rasterized at
+    pub resolution: f32,
+    pub align: TextAlign,
+    /// Point of the text at the origin of the object, normalized from its top-left
+    pub pivot: (f32, f32),
+    pub color: Vector4<f32>,
+    /// Face the camera, the object rotation is ignored
+    pub billboard: bool,
+
+    bitmap_font: BitmapFont,
+    bitmap_texture: Rc<Texture>,
+    text_program: Rc<ShaderProgram>,
+    billboard_program: Rc<ShaderProgram>,
+
+    built: RefCell<Option<(TextKey, Option<Rc<MeshSurface>>)>>,
+}
+
+impl TextMesh {
+    pub fn new(asys: &AssetSystem, text: &str) -> TextMesh {
+        let bitmap_font = BitmapFont::default();
+        let bitmap_texture = asys.new_texture(&bitmap_font.texture);
+
+        TextMesh {
+            text: text.into(),
+            font: None,
+            size: 0.25,
+            resolution: DEFAULT_RESOLUTION,
+            align: TextAlign::Left,
+            pivot: (0.5, 0.5),
+            color: Vector4::new(1.0, 1.0, 1.0, 1.0),
+            billboard: false,
+
+            bitmap_font,
+            bitmap_texture,
+            text_program: asys.new_program("default_sprite"),
+            billboard_program: asys.new_program("default_text_billboard"),
+
+            built: RefCell::new(None),
+        }
+    }
+
+    fn key(&self) -> TextKey {
+        TextKey {
+            text: self.text.clone(),
+            font: self.font.as_ref().map(|f| &**f as *const Font),
+            font_ready: self.font.as_ref().map_or(true, |f| f.is_ready()),
+            size: self.size,
+            resolution: self.resolution,
+            align: self.align,
+            pivot: self.pivot,
+            color: self.color,
+            billboard: self.billboard,
+        }
+    }
+
+    /// The glyphs in world units around the pivot, None if there is nothing to draw
+    fn mesh_data(&self) -> Option<MeshData> {
+        let px = self.resolution.max(1.0);
+        let font = self.font.as_ref().map(|f| &**f);
+        let text = make_pixel_text_mesh_data(&self.text, self.align, font, &self.bitmap_font, px);
+        let mut data = text.text;
+
+        if data.vertices.is_empty() {
+            return None;
+        }
+
+        let bounds = data.compute_bound().local_aabb();
+        let (w, h) = (bounds.max.x - bounds.min.x, bounds.max.y - bounds.min.y);
+        let origin = (
+            bounds.min.x + self.pivot.0 * w,
+            bounds.max.y - self.pivot.1 * h,
+        );
+
+        let scale = self.size / px;
+        for v in data.vertices.chunks_mut(3) {
+            v[0] = (v[0] - origin.0) * scale;
+            v[1] = (v[1] - origin.1) * scale;
+        }
+
+        // The colors of the markup are tinted by the color of the text
+        let c = self.color;
+        let count = data.vertices.len() / 3;
+        data.colors = Some(match data.colors.take() {
+            Some(colors) => colors
+                .chunks(4)
+                .flat_map(|v| vec![v[0] * c.x, v[1] * c.y, v[2] * c.z, v[3] * c.w])
+                .collect(),
+            None => (0..count).flat_map(|_| vec![c.x, c.y, c.z, c.w]).collect(),
+        });
+
+        Some(data)
+    }
+
+    fn material(&self) -> Material {
+        let program = if self.billboard {
+            self.billboard_program.clone()
+        } else {
+            self.text_program.clone()
+        };
+
+        let texture = match self.font {
+            Some(ref font) => font.texture.clone(),
+            None => self.bitmap_texture.clone(),
+        };
+
+        let mut material = Material::new(program);
+        material.set("uDiffuse", texture);
+        material.render_queue = RenderQueue::Transparent;
+        material.states.alpha_blending = Some(true);
+        material.states.depth_write = Some(false);
+        material.states.cull = Some(CullMode::Off);
+
+        material
+    }
+
+    /// The surface of the text, rebuilt when any of its properties is changed.
+    /// None until the font is loaded and for an empty text
+    pub fn surface(&self) -> Option<Rc<MeshSurface>> {
+        let key = self.key();
+
+        let mut built = self.built.borrow_mut();
+        if let Some((ref built_key, ref surface)) = *built {
+            if *built_key == key {
+                return surface.clone();
+            }
+        }
+
+        let surface = self.mesh_data().map(|data| {
+            Rc::new(MeshSurface {
+                buffer: MeshBuffer::new(data),
+                material: Rc::new(self.material()),
+                params: None,
+            })
+        });
+
+        *built = Some((key, surface.clone()));
+        surface
+    }
+}
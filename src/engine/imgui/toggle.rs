@@ -0,0 +1,187 @@
+use super::instance::ImguiState;
+use super::widgets;
+use super::widgets::{Widget, WidgetState};
+use super::{image, label};
+use super::{Metric, TextAlign};
+
+use engine::core::Aabb;
+use engine::{Asset, GameObject, IEngine, Mesh, MeshBuffer};
+use math::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Size of the box in pixel
+const BOX_SIZE: f32 = 16.0;
+/// Space between the box and the label in pixel
+const LABEL_SPACING: f32 = 4.0;
+
+/// The size of a checkbox or radio button with label `s`
+pub fn toggle_size(s: &str, state: &ImguiState) -> Metric {
+    let (w, h) = match label::label_size(s, state, None) {
+        Metric::Pixel(w, h) => (w, h),
+        _ => (0.0, 0.0),
+    };
+
+    Metric::Pixel(BOX_SIZE + LABEL_SPACING + w, BOX_SIZE.max(h))
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ToggleKind {
+    Checkbox,
+    Radio { group_id: u32 },
+}
+
+/// Checkbox and radio button
+#[derive(Debug, PartialEq)]
+pub struct Toggle {
+    id: u32,
+    pub pos: Metric,
+    pub state: ImguiState,
+    pub widget_state: WidgetState,
+    pub kind: ToggleKind,
+    pub checked: bool,
+    s: String,
+}
+
+impl Toggle {
+    pub fn new(
+        id: u32,
+        pos: Metric,
+        state: ImguiState,
+        widget_state: WidgetState,
+        kind: ToggleKind,
+        checked: bool,
+        s: String,
+    ) -> Widget {
+        Widget::Toggle(Self {
+            id,
+            pos,
+            state,
+            widget_state,
+            kind,
+            checked,
+            s,
+        })
+    }
+
+    pub fn rect(&self, ssize: (u32, u32), hidpi: f32) -> Aabb {
+        widgets::compute_rect(
+            &self.pos,
+            &toggle_size(&self.s, &self.state),
+            &self.state.pivot,
+            &ssize,
+            hidpi,
+        )
+    }
+
+    pub fn bind(
+        &self,
+        ssize: (u32, u32),
+        parent: &GameObject,
+        engine: &mut IEngine,
+    ) -> Rc<RefCell<GameObject>> {
+        let hidpi = engine.hidpi_factor();
+        let (bw, bh) =
+            image::compute_size_to_ndc(&Metric::Pixel(BOX_SIZE, BOX_SIZE), &ssize, hidpi);
+
+        let mut mesh = Mesh::new();
+        let style = &self.state.style;
+
+        // Box
+        widgets::add_frame(
+            &mut mesh,
+            engine,
+            ssize,
+            style,
+            style.frame_color(self.widget_state),
+            (0.0, 0.0),
+            (bw, bh),
+        );
+
+        // Check mark, radio mark is smaller than the checkbox one
+        if self.checked {
+            let ratio = match self.kind {
+                ToggleKind::Checkbox => 0.6,
+                ToggleKind::Radio { .. } => 0.4,
+            };
+
+            let (mw, mh) = (bw * ratio, bh * ratio);
+            let mut mark = image::make_quad_mesh_data((mw, mh));
+            mark.translate(Vector3::new((bw - mw) * 0.5, -(bh - mh) * 0.5, 0.0));
+
+            mesh.add_surface(
+                MeshBuffer::new(mark),
+                widgets::new_color_material(engine, self.state.text_color()),
+            );
+        }
+
+        // Label, vertical centered to the box
+        if self.s.len() > 0 {
+            let (lx, _) = image::compute_size_to_ndc(
+                &Metric::Pixel(BOX_SIZE + LABEL_SPACING, 0.0),
+                &ssize,
+                hidpi,
+            );
+
+            let mut text = label::make_state_text_mesh_data(
+                &self.s,
+                TextAlign::Left,
+                &self.state,
+                None,
+                ssize,
+                hidpi,
+            );
+            let text_bounds = text.compute_bound().local_aabb();
+            let th = text_bounds.max.y - text_bounds.min.y;
+            text.translate(Vector3::new(lx, -(bh - th) * 0.5 - text_bounds.max.y, 0.0));
+
+            mesh.add_surface(
+                MeshBuffer::new(text),
+                widgets::new_text_material(engine, &self.state),
+            );
+        }
+
+        widgets::clip_mesh(&mesh, &self.state, ssize, hidpi);
+        widgets::transform_mesh(&mesh, &self.state, &self.rect(ssize, hidpi), ssize, hidpi);
+        mesh.layer = self.state.layer;
+
+        // Game Object
+        let go = engine.new_game_object(parent);
+        let mut gomut = go.borrow_mut();
+
+        let mut gtrans = gomut.transform.global();
+        let rect = self.rect(ssize, hidpi);
+        gtrans.disp += Vector3::new(rect.min.x, rect.max.y, 0.0);
+        gomut.transform.set_global(gtrans);
+        gomut.add_component(mesh);
+        drop(gomut);
+
+        go
+    }
+}
+
+impl widgets::WidgetBinder for Toggle {
+    fn id(&self) -> u32 {
+        self.id
+    }
+
+    fn is_same(&self, other: &Widget) -> bool {
+        match other {
+            &Widget::Toggle(ref tgl) => tgl == self,
+            _ => false,
+        }
+    }
+
+    fn is_moved(&self, other: &Widget) -> bool {
+        match other {
+            &Widget::Toggle(ref tgl) => {
+                tgl.state == self.state
+                    && tgl.widget_state == self.widget_state
+                    && tgl.kind == self.kind
+                    && tgl.checked == self.checked
+                    && tgl.s == self.s
+            }
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use super::instance::ImguiState;
+use super::widgets;
+use super::widgets::{Widget, WidgetState};
+use super::{image, label};
+use super::{Metric, TextAlign};
+
+use engine::core::Aabb;
+use engine::{Asset, GameObject, IEngine, Mesh, MeshBuffer, MeshData};
+use math::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Size of the arrow in pixel
+const ARROW_SIZE: f32 = 12.0;
+/// Space between the arrow and the label in pixel
+const LABEL_SPACING: f32 = 4.0;
+/// Indentation of the children in pixel
+pub const INDENT: f32 = 16.0;
+
+/// The size of a tree node with label `s`
+pub fn tree_node_size(s: &str, state: &ImguiState) -> Metric {
+    let (w, h) = match label::label_size(s, state, None) {
+        Metric::Pixel(w, h) => (w, h),
+        _ => (0.0, 0.0),
+    };
+
+    Metric::Pixel(ARROW_SIZE + LABEL_SPACING + w, ARROW_SIZE.max(h))
+}
+
+/// Triangle pointing to the right, or pointing down if `open`
+fn make_arrow_mesh_data(ndc_size: (f32, f32), open: bool) -> MeshData {
+    let (w, h) = ndc_size;
+
+    let vertices: Vec<f32> = if open {
+        vec![0.0, 0.0, 0.0, w * 0.5, -h, 0.0, w, 0.0, 0.0]
+    } else {
+        vec![0.0, 0.0, 0.0, 0.0, -h, 0.0, w, -h * 0.5, 0.0]
+    };
+
+    MeshData {
+        vertices: vertices,
+        uvs: Some(vec![0.0, 1.0, 0.0, 0.0, 1.0, 0.0]),
+        uvs2: None,
+        normals: None,
+        indices: vec![0, 1, 2],
+        tangents: None,
+        bitangents: None,
+        colors: None,
+        joints: None,
+        weights: None,
+        morph_targets: Vec::new(),
+    }
+}
+
+/// Collapsible node of a tree view
+#[derive(Debug, PartialEq)]
+pub struct TreeNode {
+    id: u32,
+    pub pos: Metric,
+    pub state: ImguiState,
+    pub widget_state: WidgetState,
+    pub open: bool,
+    s: String,
+}
+
+impl TreeNode {
+    pub fn new(
+        id: u32,
+        pos: Metric,
+        state: ImguiState,
+        widget_state: WidgetState,
+        open: bool,
+        s: String,
+    ) -> Widget {
+        Widget::TreeNode(Self {
+            id,
+            pos,
+            state,
+            widget_state,
+            open,
+            s,
+        })
+    }
+
+    pub fn rect(&self, ssize: (u32, u32), hidpi: f32) -> Aabb {
+        widgets::compute_rect(
+            &self.pos,
+            &tree_node_size(&self.s, &self.state),
+            &self.state.pivot,
+            &ssize,
+            hidpi,
+        )
+    }
+
+    pub fn bind(
+        &self,
+        ssize: (u32, u32),
+        parent: &GameObject,
+        engine: &mut IEngine,
+    ) -> Rc<RefCell<GameObject>> {
+        let hidpi = engine.hidpi_factor();
+        let style = &self.state.style;
+        let (w, h) =
+            image::compute_size_to_ndc(&tree_node_size(&self.s, &self.state), &ssize, hidpi);
+        let (aw, ah) =
+            image::compute_size_to_ndc(&Metric::Pixel(ARROW_SIZE, ARROW_SIZE), &ssize, hidpi);
+
+        let mut mesh = Mesh::new();
+
+        // Highlight the row only when it is hovered or pressed
+        if self.widget_state != WidgetState::Normal {
+            widgets::add_frame(
+                &mut mesh,
+                engine,
+                ssize,
+                style,
+                style.frame_color(self.widget_state),
+                (0.0, 0.0),
+                (w, h),
+            );
+        }
+
+        // Arrow, vertical centered to the row
+        let (mw, mh) = (aw * 0.5, ah * 0.5);
+        let mut arrow = make_arrow_mesh_data((mw, mh), self.open);
+        arrow.translate(Vector3::new((aw - mw) * 0.5, -(h - mh) * 0.5, 0.0));
+
+        mesh.add_surface(
+            MeshBuffer::new(arrow),
+            widgets::new_color_material(engine, self.state.text_color()),
+        );
+
+        // Label
+        if self.s.len() > 0 {
+            let (lx, _) = image::compute_size_to_ndc(
+                &Metric::Pixel(ARROW_SIZE + LABEL_SPACING, 0.0),
+                &ssize,
+                hidpi,
+            );
+
+            let mut text = label::make_state_text_mesh_data(
+                &self.s,
+                TextAlign::Left,
+                &self.state,
+                None,
+                ssize,
+                hidpi,
+            );
+            let text_bounds = text.compute_bound().local_aabb();
+            let th = text_bounds.max.y - text_bounds.min.y;
+            text.translate(Vector3::new(lx, -(h - th) * 0.5 - text_bounds.max.y, 0.0));
+
+            mesh.add_surface(
+                MeshBuffer::new(text),
+                widgets::new_text_material(engine, &self.state),
+            );
+        }
+
+        widgets::clip_mesh(&mesh, &self.state, ssize, hidpi);
+        widgets::transform_mesh(&mesh, &self.state, &self.rect(ssize, hidpi), ssize, hidpi);
+        mesh.layer = self.state.layer;
+
+        // Game Object
+        let go = engine.new_game_object(parent);
+        let mut gomut = go.borrow_mut();
+
+        let mut gtrans = gomut.transform.global();
+        let rect = self.rect(ssize, hidpi);
+        gtrans.disp += Vector3::new(rect.min.x, rect.max.y, 0.0);
+        gomut.transform.set_global(gtrans);
+        gomut.add_component(mesh);
+        drop(gomut);
+
+        go
+    }
+}
+
+impl widgets::WidgetBinder for TreeNode {
+    fn id(&self) -> u32 {
+        self.id
+    }
+
+    fn is_same(&self, other: &Widget) -> bool {
+        match other {
+            &Widget::TreeNode(ref node) => node == self,
+            _ => false,
+        }
+    }
+
+    fn is_moved(&self, other: &Widget) -> bool {
+        match other {
+            &Widget::TreeNode(ref node) => {
+                node.state == self.state
+                    && node.widget_state == self.widget_state
+                    && node.open == self.open
+                    && node.s == self.s
+            }
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use super::context::Context;
+use super::instance::{self, ImguiRaw};
+use engine::core::SceneTree;
+use engine::IEngine;
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex};
+
+/// An imgui instance with its own widgets and game objects, e.g. for a VR HUD and a
+/// diegetic screen drawn by different cameras. The free functions add widgets to it
+/// in `Ui::frame`, and it is drawn by the cameras whose `ui` is its name
+pub struct Ui {
+    name: String,
+    inner: Arc<Mutex<ImguiRaw>>,
+    context: RefCell<Context>,
+}
+
+/// Restores the previous instance of the free functions, even if the scope panics
+struct CurrentGuard(Option<Option<Arc<Mutex<ImguiRaw>>>>);
+
+impl Drop for CurrentGuard {
+    fn drop(&mut self) {
+        if let Some(prev) = self.0.take() {
+            instance::set_current(prev);
+        }
+    }
+}
+
+impl Ui {
+    pub fn new(name: &str, engine: &mut IEngine) -> Ui {
+        let tree = SceneTree::new();
+        engine.register_ui(name, &tree);
+
+        Ui {
+            name: name.to_string(),
+            inner: Arc::new(Mutex::new(Default::default())),
+            context: RefCell::new(Context::new(tree)),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Run `f` with this ui as the instance of the free functions,
+    /// e.g. `ui.with(|| imgui::handle_event(evt))`
+    pub fn with<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        let _guard = CurrentGuard(Some(instance::set_current(Some(self.inner.clone()))));
+        f()
+    }
+
+    /// Begin a frame of this ui, the widgets are added in `f`
+    pub fn frame<F>(&self, f: F)
+    where
+        F: FnOnce(),
+    {
+        self.with(|| {
+            super::begin();
+            f()
+        })
+    }
+
+    /// Update the game objects of the widgets, call it before `Engine::render`
+    pub fn pre_render(&self, engine: &mut IEngine) {
+        let ssize = engine.screen_size();
+        let hidpi = engine.hidpi_factor();
+        self.pre_render_sized(engine, ssize, hidpi);
+    }
+
+    /// Update the game objects of the widgets for a target of `ssize` pixels,
+    /// e.g. a render texture
+    pub fn pre_render_sized(&self, engine: &mut IEngine, ssize: (u32, u32), hidpi: f32) {
+        let mut inner = self.inner.lock().unwrap();
+        let mut ctx = self.context.borrow_mut();
+
+        // The primary context is reset by `Engine::resize`
+        if inner.screen_size != ssize {
+            ctx.reset();
+        }
+
+        inner.screen_size = ssize;
+        inner.hidpi = hidpi * inner.ui_scale.0;
+        ctx.update(&mut inner, engine);
+    }
+}
@@ -1,21 +1,67 @@
 use engine::core::Aabb;
+use engine::{
+    Asset, DepthTest, GameObject, IEngine, Material, Mesh, MeshBuffer, MeshData, RenderQueue,
+    Texture,
+};
 use math::*;
 
-use super::Metric;
+use super::animation::Animation;
+use super::instance::{ClipRect, HitShape, ImguiState, WidgetTransform};
+use super::mesh_pool::MeshPool;
+use super::style::Style;
+use super::{Color, Metric};
+use std::cell::RefCell;
 use std::fmt::Debug;
+use std::rc::Rc;
 
+use super::button;
+use super::canvas;
+use super::color_picker;
+use super::focus_ring;
 use super::image;
 use super::label;
+use super::modal;
+use super::region;
+use super::scroll;
+use super::slider;
+use super::text_field;
+use super::toggle;
+use super::tree_node;
+
+/// Visual state of an interactive widget
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum WidgetState {
+    Normal,
+    Hover,
+    Active,
+}
 
 pub trait WidgetBinder: Debug {
     fn id(&self) -> u32;
     fn is_same(&self, other: &Widget) -> bool;
+
+    /// Whether the widget differs from `other` only by the position,
+    /// such that its game object can be moved instead of rebound
+    fn is_moved(&self, _other: &Widget) -> bool {
+        false
+    }
 }
 
 #[derive(Debug)]
 pub enum Widget {
     Image(image::Image),
     Label(label::Label),
+    Button(button::Button),
+    Slider(slider::Slider),
+    Toggle(toggle::Toggle),
+    TextField(text_field::TextField),
+    Scroll(scroll::ScrollRegion),
+    Modal(modal::Backdrop),
+    TreeNode(tree_node::TreeNode),
+    ColorPicker(color_picker::ColorPicker),
+    Region(region::Region),
+    FocusRing(focus_ring::FocusRing),
+    Canvas(canvas::Canvas),
 }
 
 impl Widget {
@@ -23,6 +69,269 @@ impl Widget {
         match self {
             &Widget::Image(ref img) => img.id(),
             &Widget::Label(ref lbl) => lbl.id(),
+            &Widget::Button(ref btn) => btn.id(),
+            &Widget::Slider(ref sld) => sld.id(),
+            &Widget::Toggle(ref tgl) => tgl.id(),
+            &Widget::TextField(ref tf) => tf.id(),
+            &Widget::Scroll(ref sc) => sc.id(),
+            &Widget::Modal(ref m) => m.id(),
+            &Widget::TreeNode(ref node) => node.id(),
+            &Widget::ColorPicker(ref cp) => cp.id(),
+            &Widget::Region(ref r) => r.id(),
+            &Widget::FocusRing(ref ring) => ring.id(),
+            &Widget::Canvas(ref c) => c.id(),
+        }
+    }
+
+    fn is_moved(&self, other: &Widget) -> bool {
+        match self {
+            &Widget::Image(ref img) => img.is_moved(other),
+            &Widget::Label(ref lbl) => lbl.is_moved(other),
+            &Widget::Button(ref btn) => btn.is_moved(other),
+            &Widget::Slider(ref sld) => sld.is_moved(other),
+            &Widget::Toggle(ref tgl) => tgl.is_moved(other),
+            &Widget::TextField(ref tf) => tf.is_moved(other),
+            &Widget::Scroll(ref sc) => sc.is_moved(other),
+            &Widget::Modal(ref m) => m.is_moved(other),
+            &Widget::TreeNode(ref node) => node.is_moved(other),
+            &Widget::ColorPicker(ref cp) => cp.is_moved(other),
+            &Widget::Region(ref r) => r.is_moved(other),
+            &Widget::FocusRing(ref ring) => ring.is_moved(other),
+            &Widget::Canvas(ref c) => c.is_moved(other),
+        }
+    }
+
+    /// The ndc rect of the game object of the widget, None for labels
+    pub fn bound_rect(&self, ssize: (u32, u32), hidpi: f32) -> Option<Aabb> {
+        match self {
+            &Widget::Image(ref img) => Some(img.rect(ssize, hidpi)),
+            &Widget::Label(_) => None,
+            &Widget::FocusRing(ref ring) => Some(ring.rect(ssize, hidpi)),
+            &Widget::Canvas(ref c) => Some(c.rect(ssize, hidpi)),
+            _ => self.hit_rect(ssize, hidpi),
+        }
+    }
+
+    /// Update the game object `go` bound by the `old` widget in place,
+    /// return false if the change is structural and the widget must be rebound
+    pub fn update(
+        &self,
+        old: &Widget,
+        go: &Rc<RefCell<GameObject>>,
+        ssize: (u32, u32),
+        pool: &MeshPool,
+        engine: &mut IEngine,
+    ) -> bool {
+        let updated = match (self, old) {
+            (&Widget::Button(ref btn), &Widget::Button(ref old_btn)) => {
+                btn.update(old_btn, go, ssize, engine)
+            }
+            (&Widget::Canvas(ref c), &Widget::Canvas(ref old_c)) => {
+                c.update(old_c, go, ssize, pool, engine)
+            }
+            _ => self.is_moved(old),
+        };
+
+        if !updated {
+            return false;
+        }
+
+        // Move the game object by the displacement of the top-left corner
+        let hidpi = engine.hidpi_factor();
+        if let (Some(rect), Some(old_rect)) =
+            (self.bound_rect(ssize, hidpi), old.bound_rect(ssize, hidpi))
+        {
+            let mut gomut = go.borrow_mut();
+            let mut gtrans = gomut.transform.global();
+            gtrans.disp += Vector3::new(
+                rect.min.x - old_rect.min.x,
+                rect.max.y - old_rect.max.y,
+                0.0,
+            );
+            gomut.transform.set_global(gtrans);
+        }
+
+        true
+    }
+
+    /// Clip rects of the enclosing scroll regions
+    pub fn clip(&self) -> &[ClipRect] {
+        match self {
+            &Widget::Image(ref img) => &img.clip,
+            &Widget::Label(ref lbl) => &lbl.state.clip,
+            &Widget::Button(ref btn) => &btn.state.clip,
+            &Widget::Slider(ref sld) => &sld.state.clip,
+            &Widget::Toggle(ref tgl) => &tgl.state.clip,
+            &Widget::TextField(ref tf) => &tf.state.clip,
+            &Widget::Scroll(ref sc) => &sc.state.clip,
+            &Widget::Modal(ref m) => &m.state.clip,
+            &Widget::TreeNode(ref node) => &node.state.clip,
+            &Widget::ColorPicker(ref cp) => &cp.state.clip,
+            &Widget::Region(ref r) => &r.state.clip,
+            &Widget::FocusRing(ref ring) => &ring.state.clip,
+            &Widget::Canvas(ref c) => &c.state.clip,
+        }
+    }
+
+    /// Drawing order of the widget, see `imgui::layer`
+    pub fn layer(&self) -> i32 {
+        match self {
+            &Widget::Image(ref img) => img.layer,
+            &Widget::Label(ref lbl) => lbl.state.layer,
+            &Widget::Button(ref btn) => btn.state.layer,
+            &Widget::Slider(ref sld) => sld.state.layer,
+            &Widget::Toggle(ref tgl) => tgl.state.layer,
+            &Widget::TextField(ref tf) => tf.state.layer,
+            &Widget::Scroll(ref sc) => sc.state.layer,
+            &Widget::Modal(ref m) => m.state.layer,
+            &Widget::TreeNode(ref node) => node.state.layer,
+            &Widget::ColorPicker(ref cp) => cp.state.layer,
+            &Widget::Region(ref r) => r.state.layer,
+            &Widget::FocusRing(ref ring) => ring.state.layer,
+            &Widget::Canvas(ref c) => c.state.layer,
+        }
+    }
+
+    /// Transition of the widget, see `imgui::animate`
+    pub fn animation(&self) -> Option<&Animation> {
+        match self {
+            &Widget::Image(ref img) => img.animation.as_ref(),
+            &Widget::Label(ref lbl) => lbl.state.animation.as_ref(),
+            &Widget::Button(ref btn) => btn.state.animation.as_ref(),
+            &Widget::Slider(ref sld) => sld.state.animation.as_ref(),
+            &Widget::Toggle(ref tgl) => tgl.state.animation.as_ref(),
+            &Widget::TextField(ref tf) => tf.state.animation.as_ref(),
+            &Widget::Scroll(ref sc) => sc.state.animation.as_ref(),
+            &Widget::Modal(ref m) => m.state.animation.as_ref(),
+            &Widget::TreeNode(ref node) => node.state.animation.as_ref(),
+            &Widget::ColorPicker(ref cp) => cp.state.animation.as_ref(),
+            &Widget::Region(ref r) => r.state.animation.as_ref(),
+            &Widget::FocusRing(ref ring) => ring.state.animation.as_ref(),
+            &Widget::Canvas(ref c) => c.state.animation.as_ref(),
+        }
+    }
+
+    /// Opacity of the widget, see `imgui::alpha`
+    pub fn alpha(&self) -> f32 {
+        match self {
+            &Widget::Image(ref img) => img.alpha,
+            &Widget::Label(ref lbl) => lbl.state.alpha.0,
+            &Widget::Button(ref btn) => btn.state.alpha.0,
+            &Widget::Slider(ref sld) => sld.state.alpha.0,
+            &Widget::Toggle(ref tgl) => tgl.state.alpha.0,
+            &Widget::TextField(ref tf) => tf.state.alpha.0,
+            &Widget::Scroll(ref sc) => sc.state.alpha.0,
+            &Widget::Modal(ref m) => m.state.alpha.0,
+            &Widget::TreeNode(ref node) => node.state.alpha.0,
+            &Widget::ColorPicker(ref cp) => cp.state.alpha.0,
+            &Widget::Region(ref r) => r.state.alpha.0,
+            &Widget::FocusRing(ref ring) => ring.state.alpha.0,
+            &Widget::Canvas(ref c) => c.state.alpha.0,
+        }
+    }
+
+    /// The screen space (ndc) rect used for hit-testing,
+    /// None if the widget is not interactive
+    pub fn hit_rect(&self, ssize: (u32, u32), hidpi: f32) -> Option<Aabb> {
+        match self {
+            &Widget::Button(ref btn) => Some(btn.rect(ssize, hidpi)),
+            &Widget::Slider(ref sld) => Some(sld.rect(ssize, hidpi)),
+            &Widget::Toggle(ref tgl) => Some(tgl.rect(ssize, hidpi)),
+            &Widget::TextField(ref tf) => Some(tf.rect(ssize, hidpi)),
+            &Widget::Scroll(ref sc) => Some(sc.rect(ssize, hidpi)),
+            &Widget::Modal(ref m) => Some(m.rect(ssize, hidpi)),
+            &Widget::TreeNode(ref node) => Some(node.rect(ssize, hidpi)),
+            &Widget::ColorPicker(ref cp) => Some(cp.rect(ssize, hidpi)),
+            &Widget::Region(ref r) => Some(r.rect(ssize, hidpi)),
+            _ => None,
+        }
+    }
+
+    /// Shape of the interactive widget inside its hit rect, see `imgui::hit_shape`
+    pub fn hit_shape(&self) -> Option<&HitShape> {
+        match self {
+            &Widget::Button(ref btn) => Some(&btn.state.hit_shape),
+            &Widget::Slider(ref sld) => Some(&sld.state.hit_shape),
+            &Widget::Toggle(ref tgl) => Some(&tgl.state.hit_shape),
+            &Widget::TextField(ref tf) => Some(&tf.state.hit_shape),
+            &Widget::TreeNode(ref node) => Some(&node.state.hit_shape),
+            &Widget::ColorPicker(ref cp) => Some(&cp.state.hit_shape),
+            &Widget::Region(ref r) => Some(&r.state.hit_shape),
+            _ => None,
+        }
+    }
+
+    /// Whether the ndc point `p` hits the widget, in its hit rect and its hit shape
+    pub fn hit_contains(&self, p: (f32, f32), ssize: (u32, u32), hidpi: f32) -> bool {
+        let rect = match self.hit_rect(ssize, hidpi) {
+            Some(rect) => rect,
+            None => return false,
+        };
+
+        if !rect_contains(&rect, p) {
+            return false;
+        }
+
+        let (w, h) = (rect.max.x - rect.min.x, rect.max.y - rect.min.y);
+        match self.hit_shape() {
+            Some(shape) if w > 0.0 && h > 0.0 => {
+                shape.contains(((p.0 - rect.min.x) / w, (rect.max.y - p.1) / h))
+            }
+            _ => true,
+        }
+    }
+
+    /// Number of the widgets from this one which capture the input,
+    /// such that the other widgets cannot be interacted
+    pub fn capture_len(&self) -> Option<usize> {
+        match self {
+            &Widget::Modal(ref m) => Some(m.len),
+            _ => None,
+        }
+    }
+
+    /// Whether the widget can take the keyboard focus
+    pub fn is_focusable(&self) -> bool {
+        match self {
+            &Widget::TextField(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the widget can take the navigation focus, see `imgui::nav_input`
+    pub fn is_navigable(&self) -> bool {
+        match self {
+            &Widget::Button(_)
+            | &Widget::Slider(_)
+            | &Widget::Toggle(_)
+            | &Widget::TextField(_)
+            | &Widget::TreeNode(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the widget tracks the pointer while it is pressed,
+    /// a touch drag on the other widgets scrolls the region instead
+    pub fn is_draggable(&self) -> bool {
+        match self {
+            &Widget::Slider(_) | &Widget::ColorPicker(_) | &Widget::Region(_) => true,
+            _ => false,
+        }
+    }
+
+    /// The value (0..1) of a draggable widget at ndc position p
+    pub fn drag_value(&self, p: (f32, f32), ssize: (u32, u32), hidpi: f32) -> Option<f32> {
+        match self {
+            &Widget::Slider(ref sld) => Some(sld.drag_value(p, ssize, hidpi)),
+            _ => None,
+        }
+    }
+
+    /// The position (0..1, 0..1) of a 2d draggable widget at ndc position p
+    pub fn drag_point(&self, p: (f32, f32), ssize: (u32, u32), hidpi: f32) -> Option<(f32, f32)> {
+        match self {
+            &Widget::ColorPicker(ref cp) => Some(cp.drag_point(p, ssize, hidpi)),
+            _ => None,
         }
     }
 }
@@ -32,15 +341,53 @@ impl PartialEq for Widget {
         match self {
             &Widget::Image(ref img) => img.is_same(other),
             &Widget::Label(ref lbl) => lbl.is_same(other),
+            &Widget::Button(ref btn) => btn.is_same(other),
+            &Widget::Slider(ref sld) => sld.is_same(other),
+            &Widget::Toggle(ref tgl) => tgl.is_same(other),
+            &Widget::TextField(ref tf) => tf.is_same(other),
+            &Widget::Scroll(ref sc) => sc.is_same(other),
+            &Widget::Modal(ref m) => m.is_same(other),
+            &Widget::TreeNode(ref node) => node.is_same(other),
+            &Widget::ColorPicker(ref cp) => cp.is_same(other),
+            &Widget::Region(ref r) => r.is_same(other),
+            &Widget::FocusRing(ref ring) => ring.is_same(other),
+            &Widget::Canvas(ref c) => c.is_same(other),
         }
     }
 }
 
 pub fn to_pixel_pos(px: f32, py: f32, ssize: &(u32, u32), hidpi: f32) -> (f32, f32) {
-    ((
-        (px * 2.0 * hidpi) / (ssize.0 as f32),
-        (py * 2.0 * hidpi) / (ssize.1 as f32),
-    ))
+    Metric::Pixel(px, py).to_ndc(*ssize, hidpi)
+}
+
+/// Convert the horizontal component of a metric to logical pixels
+pub fn to_pixel_width(m: &Metric, ssize: &(u32, u32), hidpi: f32) -> f32 {
+    to_pixel_size(m, ssize, hidpi).0
+}
+
+/// Convert a metric to logical pixels
+pub fn to_pixel_size(m: &Metric, ssize: &(u32, u32), hidpi: f32) -> (f32, f32) {
+    m.to_pixels(*ssize, hidpi)
+}
+
+/// Offset in ndc of the pivot from the top-left of a widget of ndc `size`
+pub fn pivot_offset(
+    pivot: &Metric,
+    size: (f32, f32),
+    ssize: &(u32, u32),
+    hidpi: f32,
+) -> (f32, f32) {
+    let (w, h) = size;
+
+    match pivot {
+        &Metric::Native(px, py) => (px * w, py * h),
+        &Metric::Pixel(px, py) => to_pixel_pos(px, py, ssize, hidpi),
+        &Metric::Mixed((ax, ay), (bx, by)) => {
+            let vp = to_pixel_pos(bx, by, ssize, hidpi);
+            (ax * w + vp.0, ay * h + vp.1)
+        }
+        &Metric::Percent(px, py) => (px / 100.0 * w, py / 100.0 * h),
+    }
 }
 
 pub fn compute_translate(
@@ -53,19 +400,273 @@ pub fn compute_translate(
     let w = bounds.max.x - bounds.min.x;
     let h = bounds.max.y - bounds.min.y;
 
-    let (x, y) = match pos {
-        &Metric::Native(px, py) => (px * 2.0, py * 2.0),
-        &Metric::Pixel(px, py) => to_pixel_pos(px, py, ssize, hidpi),
-        &Metric::Mixed((ax, ay), (bx, by)) => {
-            let vp = to_pixel_pos(bx, by, ssize, hidpi);
-            (ax * 2.0 + vp.0, ay * 2.0 + vp.1)
-        }
+    let (x, y) = pos.to_ndc(*ssize, hidpi);
+
+    let (offsetx, offsety) = pivot_offset(pivot, (w, h), ssize, hidpi);
+
+    Vector3::new(x - 1.0 - offsetx, y * -1.0 + 1.0 + offsety, 0.0)
+}
+
+/// Compute the ndc rect of a widget with given position and size
+pub fn compute_rect(
+    pos: &Metric,
+    size: &Metric,
+    pivot: &Metric,
+    ssize: &(u32, u32),
+    hidpi: f32,
+) -> Aabb {
+    let (w, h) = image::compute_size_to_ndc(size, ssize, hidpi);
+
+    let bounds = Aabb {
+        min: Vector3::new(0.0, -h, 0.0),
+        max: Vector3::new(w, 0.0, 0.0),
     };
 
-    let (offsetx, offsety) = match pivot {
-        &Metric::Native(px, py) => (px * w, py * h),
-        _ => unreachable!(),
+    let disp = compute_translate(pos, pivot, ssize, hidpi, &bounds);
+
+    Aabb {
+        min: bounds.min + disp,
+        max: bounds.max + disp,
+    }
+}
+
+/// Convert a logical pixel position (e.g. mouse position) to ndc
+pub fn pixel_to_ndc(p: (f32, f32), ssize: &(u32, u32), hidpi: f32) -> (f32, f32) {
+    let (x, y) = to_pixel_pos(p.0, p.1, ssize, hidpi);
+    (x - 1.0, 1.0 - y)
+}
+
+pub fn rect_contains(rect: &Aabb, p: (f32, f32)) -> bool {
+    p.0 >= rect.min.x && p.0 <= rect.max.x && p.1 >= rect.min.y && p.1 <= rect.max.y
+}
+
+/// Value of `uClipRect` for the widgets which are not clipped
+fn no_clip_rect() -> Vector4<f32> {
+    Vector4::new(-2.0, -2.0, 2.0, 2.0)
+}
+
+/// The intersection of the clip rects in ndc, None if there is no clip rect
+pub fn clip_aabb(clip: &[ClipRect], ssize: &(u32, u32), hidpi: f32) -> Option<Aabb> {
+    let origin = Metric::Native(0.0, 0.0);
+
+    clip.iter()
+        .map(|c| compute_rect(&c.pos, &c.size, &origin, ssize, hidpi))
+        .fold(None, |acc: Option<Aabb>, r| match acc {
+            Some(acc) => Some(Aabb {
+                min: Vector3::new(acc.min.x.max(r.min.x), acc.min.y.max(r.min.y), 0.0),
+                max: Vector3::new(acc.max.x.min(r.max.x), acc.max.y.min(r.max.y), 0.0),
+            }),
+            None => Some(r),
+        })
+}
+
+/// Whether the ndc position p is visible in the clip rects
+pub fn clip_contains(clip: &[ClipRect], p: (f32, f32), ssize: &(u32, u32), hidpi: f32) -> bool {
+    clip_aabb(clip, ssize, hidpi).map_or(true, |rect| rect_contains(&rect, p))
+}
+
+/// The `uClipRect` (xmin, ymin, xmax, ymax) of the widgets in the clip rects
+pub fn clip_rect(clip: &[ClipRect], ssize: &(u32, u32), hidpi: f32) -> Vector4<f32> {
+    match clip_aabb(clip, ssize, hidpi) {
+        Some(rect) => Vector4::new(rect.min.x, rect.min.y, rect.max.x, rect.max.y),
+        None => no_clip_rect(),
+    }
+}
+
+/// Clip all surfaces of a composite widget to the clip rect of `state`
+pub fn clip_mesh(mesh: &Mesh, state: &ImguiState, ssize: (u32, u32), hidpi: f32) {
+    let rect = clip_rect(&state.clip, &ssize, hidpi);
+
+    for surface in mesh.surfaces.iter() {
+        surface.material.set("uClipRect", rect);
+    }
+}
+
+/// The `uTransform` of a widget with ndc `rect`,
+/// `origin` is the top-left of the widget in the vertices
+pub fn transform_matrix(
+    transform: &WidgetTransform,
+    pivot: &Metric,
+    rect: &Aabb,
+    origin: (f32, f32),
+    ssize: (u32, u32),
+    hidpi: f32,
+) -> Matrix4<f32> {
+    let w = rect.max.x - rect.min.x;
+    let h = rect.max.y - rect.min.y;
+    let (px, py) = pivot_offset(pivot, (w, h), &ssize, hidpi);
+
+    transform.matrix((origin.0 + px, origin.1 - py), &ssize)
+}
+
+/// Rotate and scale all surfaces of a composite widget by the transform of `state`
+pub fn transform_mesh(mesh: &Mesh, state: &ImguiState, rect: &Aabb, ssize: (u32, u32), hidpi: f32) {
+    let m = transform_matrix(
+        &state.transform,
+        &state.pivot,
+        rect,
+        (0.0, 0.0),
+        ssize,
+        hidpi,
+    );
+
+    for surface in mesh.surfaces.iter() {
+        surface.material.set("uTransform", m);
+    }
+}
+
+/// Material for the quads of a composite widget
+pub fn new_ui_material(engine: &mut IEngine, tex: &str) -> Rc<Material> {
+    let tex = engine.asset_system().new_texture(tex);
+    new_ui_texture_material(engine, tex)
+}
+
+fn new_ui_texture_material(engine: &mut IEngine, tex: Rc<Texture>) -> Rc<Material> {
+    let db = engine.asset_system();
+
+    let mut m = Material::new(db.new_program("default_ui"));
+    m.render_queue = RenderQueue::UI;
+    // Blend the translucent widgets of `imgui::alpha` and the animations
+    m.states.alpha_blending = Some(true);
+    // All parts of the widget are in the same plane, draw in order instead.
+    m.states.depth_test = Some(DepthTest::Always);
+    m.states.depth_write = Some(false);
+    m.set("uDiffuse", tex);
+    m.set("uColor", Color::default().to_vec4());
+    m.set("uClipRect", no_clip_rect());
+    m.set("uTransform", Matrix4::<f32>::identity());
+    m.set("uUvOffset", Vector2::new(0.0, 0.0));
+    m.set("uOpacity", 1.0);
+    Rc::new(m)
+}
+
+/// Material for the solid color quads of a composite widget
+pub fn new_color_material(engine: &mut IEngine, color: Color) -> Rc<Material> {
+    let m = new_ui_material(engine, "default_white");
+    m.set("uColor", color.to_vec4());
+    m
+}
+
+/// Material for the vertex colored meshes of a composite widget
+pub fn new_vertex_color_material(engine: &mut IEngine) -> Rc<Material> {
+    let db = engine.asset_system();
+
+    let mut m = Material::new(db.new_program("default_ui_color"));
+    m.render_queue = RenderQueue::UI;
+    m.states.alpha_blending = Some(true);
+    m.states.depth_test = Some(DepthTest::Always);
+    m.states.depth_write = Some(false);
+    m.set("uColor", Color::default().to_vec4());
+    m.set("uClipRect", no_clip_rect());
+    m.set("uTransform", Matrix4::<f32>::identity());
+    m.set("uOpacity", 1.0);
+    Rc::new(m)
+}
+
+/// Material for the texts of a composite widget, using the font and the text color of `state`
+pub fn new_text_material(engine: &mut IEngine, state: &ImguiState) -> Rc<Material> {
+    let m = match state.text_font() {
+        Some(font) => new_ui_texture_material(engine, font.texture.clone()),
+        None => new_ui_material(engine, &state.bitmap_font.texture),
     };
 
-    Vector3::new(x - 1.0 - offsetx, y * -1.0 + 1.0 + offsety, 0.0)
+    m.set("uColor", state.text_color().to_vec4());
+    m
+}
+
+/// Add a frame with the border of `style` to `mesh`,
+/// `offset` is the top-left corner and `ndc_size` is the size of the frame
+pub fn add_frame(
+    mesh: &mut Mesh,
+    engine: &mut IEngine,
+    ssize: (u32, u32),
+    style: &Style,
+    color: Color,
+    offset: (f32, f32),
+    ndc_size: (f32, f32),
+) {
+    let hidpi = engine.hidpi_factor();
+    let (bx, by) =
+        image::compute_size_to_ndc(&Metric::Pixel(style.border, style.border), &ssize, hidpi);
+    let (w, h) = ndc_size;
+
+    let mut frame = if style.border > 0.0 && w > bx * 2.0 && h > by * 2.0 {
+        let mut border = image::make_quad_mesh_data((w, h));
+        border.translate(Vector3::new(offset.0, offset.1, 0.0));
+        mesh.add_surface(
+            MeshBuffer::new(border),
+            new_color_material(engine, style.border_color),
+        );
+
+        let mut quad = image::make_quad_mesh_data((w - bx * 2.0, h - by * 2.0));
+        quad.translate(Vector3::new(bx, -by, 0.0));
+        quad
+    } else {
+        image::make_quad_mesh_data((w, h))
+    };
+
+    frame.translate(Vector3::new(offset.0, offset.1, 0.0));
+    mesh.add_surface(MeshBuffer::new(frame), new_color_material(engine, color));
+}
+
+/// Build a 9-patch mesh, the corners keep their size while the edges and center are stretched.
+/// `insets` are (left, top, right, bottom) in ndc, `uv_insets` are the same in uv space
+pub fn make_nine_slice_mesh_data(
+    ndc_size: (f32, f32),
+    insets: (f32, f32, f32, f32),
+    uv_insets: (f32, f32, f32, f32),
+) -> MeshData {
+    let (w, h) = ndc_size;
+    let (mut l, mut t, mut r, mut b) = insets;
+
+    // Shrink the corners if the widget is smaller than the borders
+    if l + r > w {
+        let k = w / (l + r);
+        l *= k;
+        r *= k;
+    }
+    if t + b > h {
+        let k = h / (t + b);
+        t *= k;
+        b *= k;
+    }
+
+    let xs = [0.0, l, w - r, w];
+    let ys = [0.0, -t, -(h - b), -h];
+
+    let (ul, ut, ur, ub) = uv_insets;
+    let us = [0.0, ul, 1.0 - ur, 1.0];
+    let vs = [1.0, 1.0 - ut, ub, 0.0];
+
+    let mut vertices = Vec::with_capacity(16 * 3);
+    let mut uvs = Vec::with_capacity(16 * 2);
+    let mut indices = Vec::with_capacity(9 * 6);
+
+    for row in 0..4 {
+        for col in 0..4 {
+            vertices.extend_from_slice(&[xs[col], ys[row], 0.0]);
+            uvs.extend_from_slice(&[us[col], vs[row]]);
+        }
+    }
+
+    for row in 0..3 {
+        for col in 0..3 {
+            let i = (row * 4 + col) as u16;
+            indices.extend_from_slice(&[i, i + 4, i + 5, i, i + 5, i + 1]);
+        }
+    }
+
+    MeshData {
+        vertices: vertices,
+        uvs: Some(uvs),
+        uvs2: None,
+        normals: None,
+        indices: indices,
+        tangents: None,
+        bitangents: None,
+        colors: None,
+        joints: None,
+        weights: None,
+        morph_targets: Vec::new(),
+    }
 }
@@ -0,0 +1,110 @@
+use super::ui::Ui;
+use engine::asset::AssetSystem;
+use engine::engine::Engine;
+use engine::render::{Camera, Material, Mesh, RenderQueue, RenderTexture, TextureAttachment};
+use engine::{ClearOption, GameObject, IEngine};
+use math::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// A `Ui` rendered into a texture, which is shown on a quad in the world,
+/// e.g. for health bars above the enemies and control panels in the scene.
+/// Its widgets are not interactive unless the events are fed by `ui().with`
+pub struct WorldUi {
+    ui: Ui,
+    size: (u32, u32),
+    camera: Camera,
+    quad: Rc<RefCell<GameObject>>,
+}
+
+impl WorldUi {
+    /// A ui of `size` pixels named `name`, shown on a quad of `world_size` (width, height)
+    /// centered at `parent`
+    pub fn new(
+        name: &str,
+        size: (u32, u32),
+        world_size: (f32, f32),
+        parent: &GameObject,
+        engine: &mut IEngine,
+    ) -> WorldUi {
+        let ui = Ui::new(name, engine);
+        let texture = Rc::new(RenderTexture::new(
+            size.0,
+            size.1,
+            TextureAttachment::Color0,
+        ));
+
+        let mut camera = Camera::new();
+        camera.ui = Some(name.to_string());
+        camera.rect = Some(((0, 0), size));
+        camera.enable_frustum_culling = false;
+        camera.included_render_queues = Some([RenderQueue::UI].iter().cloned().collect());
+        camera.render_texture = Some(texture.clone());
+
+        let quad = engine.new_game_object(parent);
+        {
+            let db = engine.asset_system();
+
+            let mut material = Material::new(db.new_program("default_unlit"));
+            material.set("uDiffuse", texture.as_texture());
+            material.render_queue = RenderQueue::Transparent;
+            material.states.alpha_blending = Some(true);
+
+            let mut mesh = Mesh::new();
+            mesh.add_surface(db.new_mesh_buffer("screen_quad"), material);
+
+            let mut go = quad.borrow_mut();
+            go.add_component(mesh);
+            // The screen quad is 2 x 2
+            go.transform
+                .set_local_scale(Vector3::new(world_size.0 / 2.0, world_size.1 / 2.0, 1.0));
+        }
+
+        WorldUi {
+            ui,
+            size,
+            camera,
+            quad,
+        }
+    }
+
+    pub fn ui(&self) -> &Ui {
+        &self.ui
+    }
+
+    /// The game object of the quad, a child of the parent
+    pub fn game_object(&self) -> Rc<RefCell<GameObject>> {
+        self.quad.clone()
+    }
+
+    /// Begin a frame of the ui, the widgets are added in `f`
+    pub fn frame<F>(&self, f: F)
+    where
+        F: FnOnce(),
+    {
+        self.ui.frame(f)
+    }
+
+    /// Turn the quad to face `camera` like a billboard
+    pub fn face(&self, camera: &Camera) {
+        let v = camera.v;
+        let view_rot = Matrix3::from_cols(v.x.truncate(), v.y.truncate(), v.z.truncate());
+
+        let mut go = self.quad.borrow_mut();
+        let mut global = go.transform.global();
+        global.rot = Quaternion::from(view_rot.transpose());
+        go.transform.set_global(global);
+    }
+
+    /// Update the widgets of the last frame and render them into the texture of the quad,
+    /// the pixel metrics are the pixels of the texture
+    pub fn render<A: AssetSystem>(&self, engine: &mut Engine<A>) {
+        self.ui.pre_render_sized(engine, self.size, 1.0);
+
+        let clear = ClearOption {
+            color: Some((0.0, 0.0, 0.0, 0.0)),
+            ..ClearOption::default()
+        };
+        engine.render_pass(&self.camera, clear);
+    }
+}
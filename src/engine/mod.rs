@@ -10,12 +10,13 @@ pub mod sound;
 pub use self::imgui::Metric;
 
 pub use self::asset::*;
-pub use self::core::Aabb;
+pub use self::core::{Aabb, Bvh};
+pub use self::core::{AnimationChannel, AnimationClip, AnimationProperty, Interpolation};
 pub use self::core::{Component, ComponentArena, ComponentBased, ComponentEvent, ComponentType,
                      GameObject, IntoComponentPtr, SceneTree};
 pub use self::render::*;
 
-pub use self::engine::{ClearOption, IEngine};
+pub use self::engine::{ClearOption, IEngine, PassStats, RenderStats};
 
 pub use self::sound::{SoundHandle, SoundSystem};
 
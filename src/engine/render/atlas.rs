@@ -0,0 +1,283 @@
+use engine::asset::loader::AtlasData;
+use engine::asset::{Asset, AssetResult, AssetSystem, FileFuture, LoadState, LoadableAsset,
+                    Resource};
+use engine::render::{Texture, TextureImage};
+
+use image::{GenericImage, ImageBuffer, Rgba, RgbaImage};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// A named part of the texture of an atlas, in pixels from its top-left
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AtlasRegion {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+    /// The image is stored rotated 90 degrees clockwise, it is drawn as stored
+    pub rotated: bool,
+}
+
+impl AtlasRegion {
+    /// (x, y, w, h) of the region in the texture, see `Sprite::region`
+    pub fn rect(&self) -> (u32, u32, u32, u32) {
+        if self.rotated {
+            (self.x, self.y, self.h, self.w)
+        } else {
+            (self.x, self.y, self.w, self.h)
+        }
+    }
+}
+
+/// A texture of many images, e.g. the sprites of a 2D game or the icons of the UI,
+/// which are drawn without binding another texture.
+///
+/// The atlases are imported from the JSON files of TexturePacker, in the hash or the
+/// array format, their image is the `meta.image` next to the JSON file. They are built
+/// at runtime by `AtlasPacker`. The regions are used by `Sprite::from_atlas` and
+/// `imgui::image_atlas`
+#[derive(Debug)]
+pub struct TextureAtlas {
+    data: Resource<AtlasData>,
+}
+
+impl Asset for TextureAtlas {
+    type Resource = Resource<AtlasData>;
+
+    fn new_from_resource(r: Self::Resource) -> Rc<Self> {
+        Rc::new(TextureAtlas { data: r })
+    }
+}
+
+impl LoadableAsset for TextureAtlas {
+    fn load<T: AssetSystem + Clone + 'static>(
+        asys: &T,
+        mut files: Vec<FileFuture>,
+    ) -> Self::Resource {
+        Self::load_resource::<AtlasData, T>(asys.clone(), files.remove(0))
+    }
+
+    fn gather<T: AssetSystem>(asys: &T, fname: &str) -> Vec<FileFuture> {
+        vec![asys.new_file(fname)]
+    }
+}
+
+/// The atlas is loaded with its texture
+impl LoadState for TextureAtlas {
+    fn load_state(&self) -> AssetResult<()> {
+        let texture = self.data.try_borrow()?.texture.clone();
+        texture.load_state()
+    }
+}
+
+impl TextureAtlas {
+    pub fn is_ready(&self) -> bool {
+        self.data.try_borrow().is_ok()
+    }
+
+    /// The texture of the regions, None until the atlas is loaded
+    pub fn texture(&self) -> Option<Rc<Texture>> {
+        let data = self.data.try_borrow().ok()?;
+        Some(data.texture.clone())
+    }
+
+    pub fn region(&self, name: &str) -> Option<AtlasRegion> {
+        let data = self.data.try_borrow().ok()?;
+        data.regions.get(name).cloned()
+    }
+
+    /// The region (u0, v0, u1, v1) in fractions of the texture from its top-left,
+    /// see `imgui::image_region`. None until the atlas and its texture are loaded
+    pub fn uv_rect(&self, name: &str) -> Option<(f32, f32, f32, f32)> {
+        let data = self.data.try_borrow().ok()?;
+        let region = data.regions.get(name)?;
+        let (tw, th) = data.size.or_else(|| data.texture.image_size())?;
+
+        let (x, y, w, h) = region.rect();
+        let (tw, th) = (tw.max(1) as f32, th.max(1) as f32);
+
+        Some((
+            x as f32 / tw,
+            y as f32 / th,
+            (x + w) as f32 / tw,
+            (y + h) as f32 / th,
+        ))
+    }
+
+    /// The names of the regions, empty until the atlas is loaded
+    pub fn names(&self) -> Vec<String> {
+        match self.data.try_borrow() {
+            Ok(data) => data.regions.keys().cloned().collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+/// Pack images into the texture of an atlas at runtime, by shelves of decreasing height
+pub struct AtlasPacker {
+    /// Maximum width and height of the texture in pixels
+    pub max_size: u32,
+    /// Transparent pixels between the images against the bleeding of the filtering
+    pub padding: u32,
+    images: Vec<(String, RgbaImage)>,
+}
+
+impl AtlasPacker {
+    pub fn new(max_size: u32) -> AtlasPacker {
+        AtlasPacker {
+            max_size,
+            padding: 1,
+            images: Vec::new(),
+        }
+    }
+
+    /// Add an image of the region `name`
+    pub fn add(&mut self, name: &str, image: RgbaImage) {
+        self.images.push((name.to_string(), image));
+    }
+
+    /// The positions of the images by shelf packing and the size of the texture,
+    /// None if they do not fit in `max_size`
+    fn layout(&self) -> Option<(Vec<(u32, u32)>, (u32, u32))> {
+        let mut order: Vec<usize> = (0..self.images.len()).collect();
+        order.sort_by_key(|&i| Reverse(self.images[i].1.height()));
+
+        let mut positions = vec![(0, 0); self.images.len()];
+        let mut cursor = (0, 0);
+        let mut shelf_height = 0;
+        let mut used = (1, 1);
+
+        for i in order {
+            let (w, h) = self.images[i].1.dimensions();
+
+            if cursor.0 + w > self.max_size {
+                cursor = (0, cursor.1 + shelf_height + self.padding);
+                shelf_height = 0;
+            }
+            if cursor.0 + w > self.max_size || cursor.1 + h > self.max_size {
+                return None;
+            }
+
+            positions[i] = cursor;
+            used = (used.0.max(cursor.0 + w), used.1.max(cursor.1 + h));
+            cursor.0 += w + self.padding;
+            shelf_height = shelf_height.max(h);
+        }
+
+        let size = (
+            used.0.next_power_of_two().min(self.max_size),
+            used.1.next_power_of_two().min(self.max_size),
+        );
+
+        Some((positions, size))
+    }
+
+    /// Build the atlas of the images added, None if they do not fit in `max_size`
+    pub fn pack(&self) -> Option<Rc<TextureAtlas>> {
+        let (positions, size) = self.layout()?;
+
+        let mut canvas: RgbaImage = ImageBuffer::from_pixel(size.0, size.1, Rgba([0, 0, 0, 0]));
+        let mut regions = HashMap::new();
+
+        for (&(ref name, ref image), &(x, y)) in self.images.iter().zip(positions.iter()) {
+            canvas.copy_from(image, x, y);
+
+            regions.insert(
+                name.clone(),
+                AtlasRegion {
+                    x,
+                    y,
+                    w: image.width(),
+                    h: image.height(),
+                    rotated: false,
+                },
+            );
+        }
+
+        Some(TextureAtlas::new(AtlasData {
+            texture: Texture::new(TextureImage::Rgba(canvas)),
+            size: Some(size),
+            regions,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packer(max_size: u32, sizes: &[(u32, u32)]) -> AtlasPacker {
+        let mut packer = AtlasPacker::new(max_size);
+        for (i, &(w, h)) in sizes.iter().enumerate() {
+            packer.add(&format!("image{}", i), RgbaImage::new(w, h));
+        }
+        packer
+    }
+
+    #[test]
+    fn layout_in_bounds_without_overlap() {
+        let sizes: Vec<(u32, u32)> = (0..60)
+            .map(|i| (4 + (i * 7) % 29, 3 + (i * 13) % 23))
+            .collect();
+        let packer = packer(256, &sizes);
+
+        let (positions, size) = packer.layout().unwrap();
+        assert!(size.0.is_power_of_two() && size.1.is_power_of_two());
+        assert!(size.0 <= 256 && size.1 <= 256);
+
+        // The rects grown by the padding do not overlap
+        let pad = packer.padding;
+        let rects: Vec<_> = positions
+            .iter()
+            .zip(sizes.iter())
+            .map(|(&(x, y), &(w, h))| (x, y, x + w, y + h))
+            .collect();
+
+        for (i, a) in rects.iter().enumerate() {
+            assert!(a.2 <= size.0 && a.3 <= size.1);
+
+            for b in rects[i + 1..].iter() {
+                let apart = a.2 + pad <= b.0 || b.2 + pad <= a.0 || a.3 + pad <= b.1
+                    || b.3 + pad <= a.1;
+                assert!(apart, "{:?} overlaps {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn out_of_space() {
+        assert!(packer(64, &[(65, 8)]).pack().is_none());
+        assert!(packer(64, &[(8, 65)]).pack().is_none());
+
+        // Four 32x32 images only fit without padding
+        let mut full = packer(64, &[(32, 32); 4]);
+        assert!(full.layout().is_none());
+        full.padding = 0;
+        assert_eq!(full.layout().unwrap().1, (64, 64));
+        full.add("more", RgbaImage::new(1, 1));
+        assert!(full.pack().is_none());
+    }
+
+    #[test]
+    fn pack_regions() {
+        let mut packer = packer(128, &[(10, 20), (30, 5)]);
+        let mut red = RgbaImage::new(3, 2);
+        for p in red.pixels_mut() {
+            *p = Rgba([255, 0, 0, 255]);
+        }
+        packer.add("red", red);
+
+        let atlas = packer.pack().unwrap();
+        let mut names = atlas.names();
+        names.sort();
+        assert_eq!(names, vec!["image0", "image1", "red"]);
+
+        let red = atlas.region("red").unwrap();
+        assert_eq!((red.w, red.h, red.rotated), (3, 2, false));
+        assert!(atlas.region("missing").is_none());
+
+        let (u0, v0, u1, v1) = atlas.uv_rect("image0").unwrap();
+        assert!(0.0 <= u0 && u0 < u1 && u1 <= 1.0 && 0.0 <= v0 && v0 < v1 && v1 <= 1.0);
+    }
+}
@@ -0,0 +1,157 @@
+use engine::ClearOption;
+use uni_gl::*;
+
+/// What a buffer of a `RenderBackend` holds
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BufferUsage {
+    Vertex,
+    Index,
+}
+
+/// The graphics API which the buffers, the textures, the pipelines and the passes of
+/// the renderer are made with.
+///
+/// `GLBackend` is the default one, over WebGL and OpenGL ES. A wgpu backend, i.e.
+/// Vulkan, Metal and DX12 on native and WebGPU on the web, is not implemented yet
+pub trait RenderBackend {
+    type Buffer;
+    type Texture;
+    type Pipeline;
+
+    /// A static buffer of `data`
+    fn create_buffer(&self, usage: BufferUsage, data: &[u8]) -> Self::Buffer;
+    fn delete_buffer(&self, buffer: &Self::Buffer);
+
+    /// A 2D texture of `width` x `height` rgba pixels, in sRGB if `srgb` is set
+    fn create_texture(&self, width: u32, height: u32, srgb: bool, pixels: &[u8])
+        -> Self::Texture;
+    fn delete_texture(&self, texture: &Self::Texture);
+
+    /// The pipeline of the vertex shader `vs` and the fragment shader `fs`, whose
+    /// vertex attributes are bound to the locations of `attribs`
+    fn create_pipeline(&self, vs: &str, fs: &str, attribs: &[(&str, u32)]) -> Self::Pipeline;
+
+    /// Start a pass drawing to the viewport at `pos` of `size` in the current target,
+    /// cleared as `clear`
+    fn begin_pass(&self, pos: (i32, i32), size: (u32, u32), clear: Option<&ClearOption>);
+    fn clear(&self, option: &ClearOption);
+}
+
+/// The backend of a WebGL context, the objects are the ones of uni-gl
+pub struct GLBackend<'a> {
+    gl: &'a WebGLRenderingContext,
+}
+
+impl<'a> GLBackend<'a> {
+    pub fn new(gl: &'a WebGLRenderingContext) -> GLBackend<'a> {
+        GLBackend { gl }
+    }
+}
+
+impl<'a> RenderBackend for GLBackend<'a> {
+    type Buffer = WebGLBuffer;
+    type Texture = WebGLTexture;
+    type Pipeline = WebGLProgram;
+
+    fn create_buffer(&self, usage: BufferUsage, data: &[u8]) -> WebGLBuffer {
+        let kind = match usage {
+            BufferUsage::Vertex => BufferKind::Array,
+            BufferUsage::Index => BufferKind::ElementArray,
+        };
+
+        let buffer = self.gl.create_buffer();
+        self.gl.bind_buffer(kind, &buffer);
+        self.gl.buffer_data(kind, data, DrawMode::Static);
+        self.gl.unbind_buffer(kind);
+
+        buffer
+    }
+
+    fn delete_buffer(&self, buffer: &WebGLBuffer) {
+        self.gl.delete_buffer(buffer);
+    }
+
+    fn create_texture(&self, width: u32, height: u32, srgb: bool, pixels: &[u8]) -> WebGLTexture {
+        let format = if srgb {
+            PixelFormat::SrgbAlpha
+        } else {
+            PixelFormat::Rgba
+        };
+
+        let tex = self.gl.create_texture();
+        self.gl.active_texture(0);
+        self.gl.bind_texture(&tex);
+        self.gl.tex_image2d(
+            TextureBindPoint::Texture2d,
+            0,
+            width as u16,
+            height as u16,
+            format,
+            PixelType::UnsignedByte,
+            pixels,
+        );
+        self.gl.tex_parameteri(
+            TextureKind::Texture2d,
+            TextureParameter::TextureMinFilter,
+            TextureMinFilter::Linear as i32,
+        );
+        self.gl.tex_parameteri(
+            TextureKind::Texture2d,
+            TextureParameter::TextureMagFilter,
+            TextureMagFilter::Linear as i32,
+        );
+        self.gl.unbind_texture();
+
+        tex
+    }
+
+    fn delete_texture(&self, texture: &WebGLTexture) {
+        self.gl.delete_texture(texture);
+    }
+
+    fn create_pipeline(&self, vs: &str, fs: &str, attribs: &[(&str, u32)]) -> WebGLProgram {
+        let vert_shader = self.gl.create_shader(ShaderKind::Vertex);
+        self.gl.shader_source(&vert_shader, vs);
+        self.gl.compile_shader(&vert_shader);
+
+        let frag_shader = self.gl.create_shader(ShaderKind::Fragment);
+        self.gl.shader_source(&frag_shader, fs);
+        self.gl.compile_shader(&frag_shader);
+
+        let program = self.gl.create_program();
+        self.gl.attach_shader(&program, &vert_shader);
+        self.gl.attach_shader(&program, &frag_shader);
+
+        // The locations are bound before the link
+        for &(name, loc) in attribs.iter() {
+            self.gl.bind_attrib_location(&program, name, loc);
+        }
+        self.gl.link_program(&program);
+
+        program
+    }
+
+    fn begin_pass(&self, pos: (i32, i32), size: (u32, u32), clear: Option<&ClearOption>) {
+        self.gl.viewport(pos.0, pos.1, size.0, size.1);
+
+        if let Some(option) = clear {
+            self.clear(option);
+        }
+    }
+
+    fn clear(&self, option: &ClearOption) {
+        if let Some(col) = option.color {
+            self.gl.clear_color(col.0, col.1, col.2, col.3);
+        }
+
+        if option.clear_color {
+            self.gl.clear(BufferBit::Color);
+        }
+        if option.clear_depth {
+            self.gl.clear(BufferBit::Depth);
+        }
+        if option.clear_stencil {
+            self.gl.clear(BufferBit::Stencil);
+        }
+    }
+}
@@ -1,6 +1,8 @@
-use engine::render::{RenderQueue, RenderTexture};
+use engine::render::{PostEffect, RenderQueue, RenderTexture};
 use math::*;
+use std::borrow::Cow;
 use std::collections::BTreeSet;
+use std::f32::consts::PI;
 use std::rc::Rc;
 
 pub struct Plane {
@@ -33,6 +35,43 @@ impl Frustum {
 
         true
     }
+
+    /// Whether `aabb` is at least partly in the frustum, tested with the corner
+    /// of the box the most along the normal of each plane
+    pub fn collide_aabb(&self, aabb: &Aabb) -> bool {
+        for plane in self.planes.iter() {
+            let p = Vector3::new(
+                if plane.n.x >= 0.0 { aabb.max.x } else { aabb.min.x },
+                if plane.n.y >= 0.0 { aabb.max.y } else { aabb.min.y },
+                if plane.n.z >= 0.0 { aabb.max.z } else { aabb.min.z },
+            );
+
+            if plane.n.dot(p) - plane.offset < 0.0 {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// What a camera clears before rendering
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ClearFlags {
+    /// Clear the color and the depth
+    Color,
+    /// Clear the depth only, to draw over the cameras rendered before
+    Depth,
+    /// Keep the color and the depth
+    Nothing,
+}
+
+/// How a camera smooths the edges of the geometry
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Antialiasing {
+    None,
+    /// A FXAA pass after the post effects of the camera
+    Fxaa,
 }
 
 #[derive(Component)]
@@ -40,18 +79,69 @@ pub struct Camera {
     pub v: Matrix4<f32>,
 
     pub enable_frustum_culling: bool,
+    /// Skip the objects hidden by the occluders, which are rasterized in a small
+    /// depth buffer on the cpu, see `GameObject::occluder`. False by default
+    pub occlusion_culling: bool,
+
+    /// Bit mask of the layers rendered by this camera, see `GameObject::layer`.
+    /// All the layers by default
+    pub culling_mask: u32,
 
     /// Optional viewport of this camera,  (pos, size) in pixels
     /// from 0 (left/top) to screen width/height (right/bottom)
     pub rect: Option<((i32, i32), (u32, u32))>,
+    /// Optional viewport (pos, size) normalized from 0 to 1 of the target,
+    /// used when `rect` is None, e.g. ((0.5, 0.0), (0.5, 1.0)) for the right half
+    pub viewport: Option<((f32, f32), (f32, f32))>,
     pub znear: f32,
     pub zfar: f32,
-
+    /// Vertical field of view of the perspective projection in radians
+    pub fovy: f32,
+
+    /// Half of the height of the view in world units for an orthographic projection,
+    /// a perspective projection if None
+    pub orthographic_size: Option<f32>,
+    /// Projection used instead of the perspective or orthographic one, the frustum
+    /// culling still follows the latter
+    pub custom_projection: Option<Matrix4<f32>>,
+    /// World space plane (normal in xyz, n.p + w = 0) replacing the near plane of the
+    /// projection, e.g. the surface of a mirror or water so that nothing behind it is
+    /// reflected. The geometry on the side opposite to the normal is clipped
+    pub oblique_near_plane: Option<Vector4<f32>>,
+
+    /// The named queues drawn by this camera, with the queues offset from them,
+    /// all of them if None
     pub included_render_queues: Option<BTreeSet<RenderQueue>>,
 
     eye: Point3<f32>,
 
+    /// Render to this texture instead of the screen, the viewport is the
+    /// whole texture unless `rect` is set
     pub render_texture: Option<Rc<RenderTexture>>,
+
+    /// Name of the `imgui::Ui` drawn by this camera, None for the primary one
+    pub ui: Option<String>,
+
+    /// Fullscreen passes applied in order to the rendered scene, before the UI is drawn
+    pub post_effects: Vec<PostEffect>,
+
+    /// Render order among the cameras of a frame, the lower first. Only the main camera
+    /// is rendered, as 0 if it has no order, and the other cameras with an order
+    pub order: Option<i32>,
+    pub clear_flags: ClearFlags,
+    /// Clear color of the camera, the one of the frame if None
+    pub clear_color: Option<(f32, f32, f32, f32)>,
+    pub antialiasing: Antialiasing,
+    /// Render the scene of the post effects in floating point targets, which keep the
+    /// colors above 1 for the tone mapping, if `RenderCaps::float_render_targets`
+    pub hdr: bool,
+
+    /// Multiplier of the colors of the lit materials, in linear space
+    pub exposure: f32,
+    /// Gamma of the display. The lit materials decode the colors of the lights by it,
+    /// light in linear space, then encode the result back. Their sRGB textures are
+    /// decoded by the sampling with `RenderCaps::srgb`, by the gamma otherwise
+    pub gamma: f32,
 }
 
 impl Default for Camera {
@@ -107,46 +197,244 @@ impl Camera {
         // );
     }
 
-    fn calc_aspect(&self, screen_size: (u32, u32)) -> f32 {
-        let mut aspect: f32 = (screen_size.0 as f32) / (screen_size.1 as f32);
+    /// Size of the target, the render texture or the screen
+    pub fn target_size(&self, screen_size: (u32, u32)) -> (u32, u32) {
+        self.render_texture
+            .as_ref()
+            .map_or(screen_size, |rt| rt.size())
+    }
 
-        if let Some(((_, _), (w, h))) = self.rect {
-            aspect = w as f32 / h as f32;
+    /// The viewport (pos, size) in pixels, from `rect`, `viewport` or the whole target
+    pub fn pixel_rect(&self, screen_size: (u32, u32)) -> ((i32, i32), (u32, u32)) {
+        let (tw, th) = self.target_size(screen_size);
+
+        match (self.rect, self.viewport) {
+            (Some(rect), _) => rect,
+            (None, Some(((x, y), (w, h)))) => (
+                ((x * tw as f32) as i32, (y * th as f32) as i32),
+                ((w * tw as f32) as u32, (h * th as f32) as u32),
+            ),
+            (None, None) => ((0, 0), (tw, th)),
         }
+    }
+
+    fn calc_aspect(&self, screen_size: (u32, u32)) -> f32 {
+        let (_, (w, h)) = self.pixel_rect(screen_size);
 
-        aspect
+        (w as f32) / (h.max(1) as f32)
     }
 
     pub fn perspective(&self, screen_size: (u32, u32)) -> Matrix4<f32> {
-        use math::*;
+        let p = match self.custom_projection {
+            Some(p) => p,
+            None => self.default_projection(screen_size),
+        };
+
+        match self.oblique_near_plane {
+            Some(plane) => self.oblique(p, plane),
+            None => p,
+        }
+    }
 
+    fn default_projection(&self, screen_size: (u32, u32)) -> Matrix4<f32> {
         let aspect = self.calc_aspect(screen_size).max(0.001);
 
+        if let Some(size) = self.orthographic_size {
+            let w = size * aspect;
+            return ortho(-w, w, -size, size, self.znear, self.zfar);
+        }
+
         PerspectiveFov {
-            fovy: Rad(3.1415 / 4.0),
+            fovy: Rad(self.fovy),
             aspect,
             near: self.znear,
             far: self.zfar,
         }.into()
     }
 
+    /// Replace the near plane of the projection `p` by a world space plane,
+    /// by the method of Eric Lengyel
+    fn oblique(&self, p: Matrix4<f32>, plane: Vector4<f32>) -> Matrix4<f32> {
+        let inv_v = match self.v.invert() {
+            Some(m) => m,
+            None => return p,
+        };
+        let inv_p = match p.invert() {
+            Some(m) => m,
+            None => return p,
+        };
+
+        // A plane is transformed by the inverse transpose of the points' matrix
+        let c = inv_v.transpose() * plane;
+        let corner = inv_p * Vector4::new(c.x.signum(), c.y.signum(), 1.0, 1.0);
+        let d = c.dot(corner);
+        if d.abs() < 1e-6 {
+            return p;
+        }
+        let c = c * (2.0 / d);
+
+        let mut m = p;
+        m.x.z = c.x - m.x.w;
+        m.y.z = c.y - m.y.w;
+        m.z.z = c.z - m.z.w;
+        m.w.z = c.w - m.w.w;
+        m
+    }
+
+    /// Vertical field of view of the perspective projection
+    pub fn set_fov<A: Into<Rad<f32>>>(&mut self, fovy: A) {
+        self.fovy = fovy.into().0;
+    }
+
+    pub fn set_clip_planes(&mut self, znear: f32, zfar: f32) {
+        self.znear = znear;
+        self.zfar = zfar;
+    }
+
+    /// Switch to an orthographic projection of half height `size`,
+    /// or back to the perspective one with None
+    pub fn set_orthographic(&mut self, size: Option<f32>) {
+        self.orthographic_size = size;
+    }
+
+    /// A world space point in pixels of the target from its left/top, with its depth
+    /// from 0 at the near plane to 1 at the far plane. None behind the camera
+    pub fn world_to_screen(
+        &self,
+        p: &Vector3<f32>,
+        screen_size: (u32, u32),
+    ) -> Option<Vector3<f32>> {
+        let c = self.perspective(screen_size) * self.v * p.extend(1.0);
+        if c.w <= 1e-6 {
+            return None;
+        }
+
+        let ndc = c.truncate() / c.w;
+        let ((x, y), (w, h)) = self.pixel_rect(screen_size);
+
+        Some(Vector3::new(
+            x as f32 + (ndc.x * 0.5 + 0.5) * w as f32,
+            y as f32 + (0.5 - ndc.y * 0.5) * h as f32,
+            ndc.z * 0.5 + 0.5,
+        ))
+    }
+
+    /// The world space ray (origin on the near plane, unit direction) through a point
+    /// in pixels of the target from its left/top, e.g. to pick the object under the mouse
+    pub fn screen_to_ray(
+        &self,
+        pos: (f32, f32),
+        screen_size: (u32, u32),
+    ) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        let ((x, y), (w, h)) = self.pixel_rect(screen_size);
+        let ndc_x = (pos.0 - x as f32) / w.max(1) as f32 * 2.0 - 1.0;
+        let ndc_y = 1.0 - (pos.1 - y as f32) / h.max(1) as f32 * 2.0;
+
+        let inv = (self.perspective(screen_size) * self.v).invert()?;
+        let unproject = |z: f32| {
+            let p = inv * Vector4::new(ndc_x, ndc_y, z, 1.0);
+            p.truncate() / p.w
+        };
+
+        let near = unproject(-1.0);
+        let far = unproject(1.0);
+        let dir = far - near;
+        if dir.magnitude2() <= 0.0 {
+            return None;
+        }
+
+        Some((near, dir.normalize()))
+    }
+
     pub fn new() -> Camera {
         Camera {
             v: Matrix4::identity(),
             eye: Point3::new(0.0, 0.0, 0.0),
             rect: None,
+            viewport: None,
             znear: 0.03,
             zfar: 1000.0,
+            fovy: PI / 4.0,
+            orthographic_size: None,
+            custom_projection: None,
+            oblique_near_plane: None,
             enable_frustum_culling: true,
+            occlusion_culling: false,
+            culling_mask: !0,
             included_render_queues: None,
             render_texture: None,
+            ui: None,
+            post_effects: Vec::new(),
+            order: None,
+            clear_flags: ClearFlags::Color,
+            clear_color: None,
+            antialiasing: Antialiasing::None,
+            hdr: false,
+            exposure: 1.0,
+            gamma: 2.2,
         }
     }
 
+    /// The post effects with the FXAA pass of the antialiasing, if it needs one
+    pub fn applied_post_effects(&self) -> Cow<[PostEffect]> {
+        let fxaa = match self.antialiasing {
+            Antialiasing::None => false,
+            Antialiasing::Fxaa => true,
+        };
+
+        let has_fxaa = self.post_effects.iter().any(|e| match *e {
+            PostEffect::Fxaa => true,
+            _ => false,
+        });
+
+        if fxaa && !has_fxaa {
+            let mut effects = self.post_effects.clone();
+            effects.push(PostEffect::Fxaa);
+            Cow::Owned(effects)
+        } else {
+            Cow::Borrowed(&self.post_effects)
+        }
+    }
+
+    /// An orthographic camera showing `size` world units above and below its center,
+    /// looking down the -z axis at the origin as in a 2D scene
+    pub fn orthographic(size: f32) -> Camera {
+        let mut cam = Camera::new();
+        cam.orthographic_size = Some(size);
+        cam.lookat(
+            &Point3::new(0.0, 0.0, 10.0),
+            &Point3::new(0.0, 0.0, 0.0),
+            &Vector3::new(0.0, 1.0, 0.0),
+        );
+
+        cam
+    }
+
     pub fn eye(&self) -> Vector3<f32> {
         Vector3::new(self.eye.x, self.eye.y, self.eye.z)
     }
 
+    /// Height of a sphere on the screen as a fraction of the height of the view,
+    /// e.g. to select the level of a `LodGroup`. It is more than 1 when the sphere
+    /// covers the view
+    pub fn projected_size(&self, center: &Vector3<f32>, radius: f32) -> f32 {
+        let fovy = self.fovy;
+
+        let view_height = match self.orthographic_size {
+            Some(size) => 2.0 * size,
+            None => {
+                let dist = (center - self.eye()).magnitude();
+                if dist <= radius {
+                    return ::std::f32::INFINITY;
+                }
+
+                2.0 * (fovy * 0.5).tan() * dist
+            }
+        };
+
+        2.0 * radius / view_height
+    }
+
     pub fn calc_frustum(&self, screen_size: (u32, u32)) -> Frustum {
         let forward = extract_forward(&self.v);
         let up = extract_up(&self.v);
@@ -156,10 +444,15 @@ impl Camera {
         let near_center = self.eye.to_vec() + forward * self.znear;
         let far_center = self.eye.to_vec() + forward * self.zfar;
 
-        let fovy: f32 = 3.1415 / 4.0;
+        let fovy = self.fovy;
 
-        let near_height = 2.0 * (fovy * 0.5).tan() * self.znear;
-        let far_height = 2.0 * (fovy * 0.5).tan() * self.zfar;
+        let (near_height, far_height) = match self.orthographic_size {
+            Some(size) => (2.0 * size, 2.0 * size),
+            None => (
+                2.0 * (fovy * 0.5).tan() * self.znear,
+                2.0 * (fovy * 0.5).tan() * self.zfar,
+            ),
+        };
         let near_width = near_height * aspect;
         let far_width = far_height * aspect;
 
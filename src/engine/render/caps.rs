@@ -0,0 +1,191 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use uni_gl;
+use uni_gl::WebGLRenderingContext;
+
+/// Whether the color textures are sRGB ones, see `set_srgb_textures`
+static SRGB_TEXTURES: AtomicBool = AtomicBool::new(false);
+
+/// Upload the color textures as sRGB and define `UNI_SRGB_TEXTURES` in the shaders,
+/// which then sample them as linear. It is set by the engine from `RenderCaps::srgb`
+/// before any texture or shader is loaded
+pub(crate) fn set_srgb_textures(srgb: bool) {
+    SRGB_TEXTURES.store(srgb, Ordering::Relaxed);
+}
+
+pub(crate) fn srgb_textures() -> bool {
+    SRGB_TEXTURES.load(Ordering::Relaxed)
+}
+
+/// Whether the mesh buffers keep their attributes in vertex arrays, see
+/// `set_vertex_array_objects`
+static VERTEX_ARRAY_OBJECTS: AtomicBool = AtomicBool::new(false);
+
+/// Bind the attributes of a mesh buffer once into its vertex array, which is then only
+/// bound to draw it. It is set by the engine from `RenderCaps::vertex_array_objects`
+pub(crate) fn set_vertex_array_objects(vao: bool) {
+    VERTEX_ARRAY_OBJECTS.store(vao, Ordering::Relaxed);
+}
+
+pub(crate) fn vertex_array_objects() -> bool {
+    VERTEX_ARRAY_OBJECTS.load(Ordering::Relaxed)
+}
+
+/// Features of the GL context which the engine is running on, detected at startup.
+///
+/// The engine takes the WebGL2 paths (e.g. vertex array objects kept bound to the
+/// meshes) when the context has the features, otherwise it falls back to WebGL1.
+/// Materials could use it to pick a shader variant, e.g. one written in GLSL 300 es,
+/// see `Engine::render_caps`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RenderCaps {
+    /// The context is a WebGL2 or a desktop GL context
+    pub webgl2: bool,
+    /// The shaders are compiled as GLSL ES, i.e. the context is a WebGL one
+    pub gl_es: bool,
+    /// Vertex array objects, which keep the attributes of the mesh buffers bound
+    pub vertex_array_objects: bool,
+    /// Uniform blocks, in GLSL 300 es
+    pub uniform_buffers: bool,
+    /// Many color attachments of a frame buffer, i.e. `WEBGL_draw_buffers` on WebGL1
+    pub multiple_render_targets: bool,
+    /// 3D and 2D array textures
+    pub texture_3d: bool,
+    /// Floating point color attachments, which WebGL only has by an extension
+    pub float_render_targets: bool,
+    /// The compressed texture formats, see `CompressedFormat`. ETC2 and ASTC are only
+    /// uploaded with the `compressed_formats` feature
+    pub s3tc: bool,
+    pub etc2: bool,
+    pub astc: bool,
+    /// sRGB textures, i.e. SRGB8_ALPHA8 or `EXT_sRGB` on WebGL1, and the sRGB formats
+    /// of the supported compressed ones. The color textures are then decoded to linear
+    /// by the sampling instead of the shaders, see `Texture::set_srgb`
+    pub srgb: bool,
+    /// Instanced arrays, i.e. `ANGLE_instanced_arrays` on WebGL1, which draw the
+    /// instances of a `MeshInstances` in a single draw call. They are only drawn with the
+    /// `instanced_arrays` feature
+    pub instancing: bool,
+    /// Timer queries, i.e. `EXT_disjoint_timer_query` on WebGL1, which measure the
+    /// time of the passes on the GPU, see `PassStats::gpu_time`. They are only recorded
+    /// with the `timer_query` feature
+    pub timer_query: bool,
+}
+
+/// Whether the context has one of the extensions `names`, which enables it on WebGL
+fn has_extension(gl: &WebGLRenderingContext, names: &[&str]) -> bool {
+    names.iter().any(|name| gl.get_extension(name).is_some())
+}
+
+/// The block compressed texture formats which the gpus may support, loaded from the
+/// KTX and dds files
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CompressedFormat {
+    /// DXT1 and DXT5, mostly on desktops
+    S3tc,
+    /// ETC2 and EAC, on OpenGL ES 3 devices
+    Etc2,
+    /// ASTC LDR, on the recent mobiles
+    Astc,
+}
+
+impl CompressedFormat {
+    /// The extensions of WebGL and desktop GL which provide the format
+    fn extensions(&self) -> &'static [&'static str] {
+        match *self {
+            CompressedFormat::S3tc => &[
+                "WEBGL_compressed_texture_s3tc",
+                "WEBKIT_WEBGL_compressed_texture_s3tc",
+                "GL_EXT_texture_compression_s3tc",
+            ],
+            CompressedFormat::Etc2 => &["WEBGL_compressed_texture_etc", "GL_ARB_ES3_compatibility"],
+            CompressedFormat::Astc => &[
+                "WEBGL_compressed_texture_astc",
+                "GL_KHR_texture_compression_astc_ldr",
+            ],
+        }
+    }
+
+    /// Whether the context can upload the textures of the format
+    pub fn is_supported(&self, gl: &WebGLRenderingContext) -> bool {
+        has_extension(gl, self.extensions())
+    }
+
+    /// The end of the names of the files of the format, see `AssetSystem::new_texture`
+    pub fn file_suffix(&self) -> &'static str {
+        match *self {
+            CompressedFormat::S3tc => "s3tc.ktx",
+            CompressedFormat::Etc2 => "etc2.ktx",
+            CompressedFormat::Astc => "astc.ktx",
+        }
+    }
+}
+
+impl RenderCaps {
+    pub fn detect(gl: &WebGLRenderingContext) -> RenderCaps {
+        let webgl2 = gl.is_webgl2 || !uni_gl::IS_GL_ES;
+        let s3tc = CompressedFormat::S3tc.is_supported(gl);
+
+        // The sRGB formats of ETC2 and ASTC come with their extensions, not the ones
+        // of S3TC. Only the linear S3TC formats are uploaded without the
+        // `compressed_formats` feature
+        let s3tc_srgb = ["WEBGL_compressed_texture_s3tc_srgb", "GL_EXT_texture_sRGB"];
+        let s3tc_srgb = cfg!(feature = "compressed_formats") && has_extension(gl, &s3tc_srgb);
+        let srgb = (webgl2 || has_extension(gl, &["EXT_sRGB"])) && (!s3tc || s3tc_srgb);
+
+        // Desktop GL renders to float textures, WebGL only by the extensions
+        let float_render_targets = !uni_gl::IS_GL_ES || if webgl2 {
+            has_extension(gl, &["EXT_color_buffer_float"])
+        } else {
+            has_extension(gl, &["WEBGL_color_buffer_float"])
+                && has_extension(gl, &["OES_texture_float"])
+        };
+
+        let timer_query = if !cfg!(feature = "timer_query") {
+            false
+        } else if !uni_gl::IS_GL_ES {
+            has_extension(gl, &["GL_ARB_timer_query", "GL_EXT_timer_query"])
+        } else if webgl2 {
+            has_extension(gl, &["EXT_disjoint_timer_query_webgl2"])
+        } else {
+            has_extension(gl, &["EXT_disjoint_timer_query"])
+        };
+
+        RenderCaps {
+            webgl2,
+            gl_es: uni_gl::IS_GL_ES,
+            vertex_array_objects: webgl2,
+            uniform_buffers: webgl2,
+            multiple_render_targets: webgl2 || has_extension(gl, &["WEBGL_draw_buffers"]),
+            texture_3d: webgl2,
+            float_render_targets,
+            s3tc,
+            etc2: cfg!(feature = "compressed_formats") && CompressedFormat::Etc2.is_supported(gl),
+            astc: cfg!(feature = "compressed_formats") && CompressedFormat::Astc.is_supported(gl),
+            srgb,
+            instancing: cfg!(feature = "instanced_arrays")
+                && (webgl2 || has_extension(gl, &["ANGLE_instanced_arrays"])),
+            timer_query,
+        }
+    }
+
+    /// The supported compressed formats in the order of preference, ASTC first
+    pub fn compressed_formats(&self) -> Vec<CompressedFormat> {
+        let formats = [
+            (self.astc, CompressedFormat::Astc),
+            (self.etc2, CompressedFormat::Etc2),
+            (self.s3tc, CompressedFormat::S3tc),
+        ];
+
+        formats
+            .iter()
+            .filter(|&&(supported, _)| supported)
+            .map(|&(_, format)| format)
+            .collect()
+    }
+
+    /// Whether a shader written in GLSL 300 es could be compiled,
+    /// i.e. one starting with `#define USE_GLSL_300ES`
+    pub fn glsl_300es(&self) -> bool {
+        self.webgl2
+    }
+}
@@ -0,0 +1,221 @@
+//! Lines drawn for debugging, e.g. the shapes of the physics or the paths of the AI.
+//!
+//! The lines are accumulated during a frame and drawn by every camera after the opaque
+//! queue, then cleared at the end of the frame.
+
+use engine::asset::{Asset, AssetSystem};
+use engine::render::{DepthTest, Material, MeshBuffer, MeshData, MeshPrimitive, MeshSurface};
+
+use math::*;
+use std::cell::RefCell;
+use std::f32::consts::PI;
+use std::mem;
+use std::rc::Rc;
+use std::u16;
+
+/// Segments of the circles of `draw_sphere`
+const CIRCLE_SEGMENTS: usize = 24;
+
+#[derive(Default)]
+struct LineList {
+    vertices: Vec<f32>,
+    colors: Vec<f32>,
+}
+
+impl LineList {
+    fn clear(&mut self) {
+        self.vertices.clear();
+        self.colors.clear();
+    }
+}
+
+struct DebugLines {
+    depth_test: bool,
+    /// The lines tested against the depth buffer and the ones drawn over the scene
+    tested: LineList,
+    overlay: LineList,
+}
+
+thread_local!(
+    static LINES: RefCell<DebugLines> = RefCell::new(DebugLines {
+        depth_test: true,
+        tested: LineList::default(),
+        overlay: LineList::default(),
+    })
+);
+
+/// Whether the lines drawn after this call are hidden by the scene, true by default.
+/// It is reset at the end of the frame
+pub fn set_depth_test(enabled: bool) {
+    LINES.with(|lines| lines.borrow_mut().depth_test = enabled);
+}
+
+pub fn draw_line(a: Vector3<f32>, b: Vector3<f32>, color: Vector4<f32>) {
+    LINES.with(|lines| {
+        let mut lines = lines.borrow_mut();
+        let list = if lines.depth_test {
+            &mut lines.tested
+        } else {
+            &mut lines.overlay
+        };
+
+        // The lines are drawn from a single buffer of u16 indices
+        if list.vertices.len() / 3 + 2 > u16::MAX as usize + 1 {
+            return;
+        }
+
+        list.vertices.extend_from_slice(&[a.x, a.y, a.z, b.x, b.y, b.z]);
+        for _ in 0..2 {
+            list.colors
+                .extend_from_slice(&[color.x, color.y, color.z, color.w]);
+        }
+    });
+}
+
+/// The 12 edges of `aabb`
+pub fn draw_aabb(aabb: &Aabb, color: Vector4<f32>) {
+    let c = aabb.corners();
+    let edges = [
+        (0, 1), (1, 2), (2, 3), (3, 0),
+        (4, 5), (5, 6), (6, 7), (7, 4),
+        (0, 4), (1, 5), (2, 6), (3, 7),
+    ];
+
+    for &(i, j) in edges.iter() {
+        draw_line(c[i], c[j], color);
+    }
+}
+
+/// Three circles of the sphere in the planes of the axes
+pub fn draw_sphere(center: Vector3<f32>, r: f32, color: Vector4<f32>) {
+    let point = |axis: usize, t: f32| {
+        let (s, c) = (t.sin() * r, t.cos() * r);
+        center + match axis {
+            0 => Vector3::new(0.0, c, s),
+            1 => Vector3::new(c, 0.0, s),
+            _ => Vector3::new(c, s, 0.0),
+        }
+    };
+
+    for axis in 0..3 {
+        for i in 0..CIRCLE_SEGMENTS {
+            let t0 = i as f32 / CIRCLE_SEGMENTS as f32 * 2.0 * PI;
+            let t1 = (i + 1) as f32 / CIRCLE_SEGMENTS as f32 * 2.0 * PI;
+            draw_line(point(axis, t0), point(axis, t1), color);
+        }
+    }
+}
+
+/// The x (red), y (green) and z (blue) axes of the transform `m`, `size` long
+pub fn draw_axis(m: &Matrix4<f32>, size: f32) {
+    let origin = m.transform_point(Point3::origin());
+    let axes = [
+        (Vector3::unit_x(), Vector4::new(1.0, 0.0, 0.0, 1.0)),
+        (Vector3::unit_y(), Vector4::new(0.0, 1.0, 0.0, 1.0)),
+        (Vector3::unit_z(), Vector4::new(0.0, 0.0, 1.0, 1.0)),
+    ];
+
+    for &(axis, color) in axes.iter() {
+        let end = m.transform_point(Point3::from_vec(axis * size));
+        draw_line(origin.to_vec(), end.to_vec(), color);
+    }
+}
+
+/// Drop the lines of the frame
+pub fn clear() {
+    LINES.with(|lines| {
+        let mut lines = lines.borrow_mut();
+        lines.depth_test = true;
+        lines.tested.clear();
+        lines.overlay.clear();
+    });
+}
+
+fn empty_mesh_data() -> MeshData {
+    MeshData {
+        vertices: Vec::new(),
+        uvs: None,
+        uvs2: None,
+        normals: None,
+        indices: Vec::new(),
+        tangents: None,
+        bitangents: None,
+        colors: Some(Vec::new()),
+        joints: None,
+        weights: None,
+        morph_targets: Vec::new(),
+    }
+}
+
+/// The line-list surfaces of the debug lines of a frame
+pub struct DebugRenderer {
+    tested: Rc<MeshSurface>,
+    overlay: Rc<MeshSurface>,
+}
+
+fn line_surface(asys: &AssetSystem, depth_test: DepthTest) -> Rc<MeshSurface> {
+    let mut material = Material::new(asys.new_program("default_line"));
+    material.states.depth_write = Some(false);
+    material.states.depth_test = Some(depth_test);
+
+    let buffer = MeshBuffer::new(empty_mesh_data());
+    buffer.set_primitive(MeshPrimitive::Lines);
+
+    Rc::new(MeshSurface {
+        buffer,
+        material: Rc::new(material),
+        params: None,
+    })
+}
+
+/// Move the lines of `list` into the buffer of `surface`,
+/// the vectors of the previous frame are given back to `list`
+fn update_surface(surface: &MeshSurface, list: &mut LineList) {
+    let count = list.vertices.len() / 3;
+    let data = MeshData {
+        vertices: mem::replace(&mut list.vertices, Vec::new()),
+        colors: Some(mem::replace(&mut list.colors, Vec::new())),
+        indices: (0..count).map(|i| i as u16).collect(),
+        ..empty_mesh_data()
+    };
+
+    if let Some(old) = surface.buffer.swap_mesh_data(data) {
+        *list = LineList {
+            vertices: old.vertices,
+            colors: old.colors.unwrap_or_default(),
+        };
+        list.clear();
+    }
+}
+
+impl DebugRenderer {
+    pub fn new(asys: &AssetSystem) -> DebugRenderer {
+        DebugRenderer {
+            tested: line_surface(asys, DepthTest::LessEqual),
+            overlay: line_surface(asys, DepthTest::Always),
+        }
+    }
+
+    /// Upload the lines drawn so far in the frame
+    pub fn update(&self) {
+        LINES.with(|lines| {
+            let mut lines = lines.borrow_mut();
+            update_surface(&self.tested, &mut lines.tested);
+            update_surface(&self.overlay, &mut lines.overlay);
+        });
+    }
+
+    /// The surfaces which have any line
+    pub fn surfaces(&self) -> Vec<Rc<MeshSurface>> {
+        [&self.tested, &self.overlay]
+            .iter()
+            .filter(|s| {
+                s.buffer
+                    .mesh_data()
+                    .map(|data| !data.indices.is_empty())
+                    .unwrap_or(false)
+            })
+            .map(|s| (*s).clone())
+            .collect()
+    }
+}
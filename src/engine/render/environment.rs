@@ -0,0 +1,108 @@
+use engine::render::ShaderProgram;
+
+use math::*;
+
+/// How the fog thickens with the distance from the camera
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FogMode {
+    /// From none at `start` to full at `end`
+    Linear,
+    /// 1 - exp(-density * distance)
+    Exponential,
+    /// 1 - exp(-(density * distance)^2)
+    ExponentialSquared,
+}
+
+/// Fog which thins out above a height, e.g. mist in a valley
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct HeightFog {
+    /// World height below which the fog is the thickest
+    pub base: f32,
+    /// Rate the fog thins out by per world unit above `base`
+    pub falloff: f32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Fog {
+    pub mode: FogMode,
+    /// Color of the fog, decoded by the gamma of the camera like the light colors
+    pub color: Vector3<f32>,
+    /// Density of the exponential modes
+    pub density: f32,
+    /// Distances of the linear mode
+    pub start: f32,
+    pub end: f32,
+    pub height: Option<HeightFog>,
+}
+
+impl Fog {
+    pub fn linear(color: Vector3<f32>, start: f32, end: f32) -> Fog {
+        Fog {
+            mode: FogMode::Linear,
+            color,
+            density: 0.0,
+            start,
+            end,
+            height: None,
+        }
+    }
+
+    pub fn exponential(color: Vector3<f32>, density: f32) -> Fog {
+        Fog {
+            mode: FogMode::Exponential,
+            color,
+            density,
+            start: 0.0,
+            end: 0.0,
+            height: None,
+        }
+    }
+
+    pub fn exponential_squared(color: Vector3<f32>, density: f32) -> Fog {
+        Fog {
+            mode: FogMode::ExponentialSquared,
+            ..Fog::exponential(color, density)
+        }
+    }
+
+    /// Bind the fog in the uniform struct `name`, or no fog with None
+    pub fn bind(fog: Option<&Fog>, name: &str, prog: &ShaderProgram) {
+        let fog = match fog {
+            Some(fog) => fog,
+            None => {
+                prog.set(name.to_string() + ".mode", 0);
+                return;
+            }
+        };
+
+        let mode = match fog.mode {
+            FogMode::Linear => 1,
+            FogMode::Exponential => 2,
+            FogMode::ExponentialSquared => 3,
+        };
+
+        prog.set(name.to_string() + ".mode", mode);
+        prog.set(name.to_string() + ".color", fog.color);
+        prog.set(name.to_string() + ".density", fog.density);
+        prog.set(name.to_string() + ".start", fog.start);
+        prog.set(name.to_string() + ".end", fog.end);
+
+        let (base, falloff) = fog.height.map_or((0.0, 0.0), |h| (h.base, h.falloff));
+        prog.set(name.to_string() + ".heightBase", base);
+        prog.set(name.to_string() + ".heightFalloff", falloff);
+    }
+}
+
+/// Settings of the rendering of the whole scene, the first one found in the scene is
+/// used. The default programs get the fog in the `uFog` uniform struct
+#[derive(Component, Default)]
+pub struct EnvironmentSettings {
+    /// Fog of the lit materials, none if None
+    pub fog: Option<Fog>,
+}
+
+impl EnvironmentSettings {
+    pub fn new() -> EnvironmentSettings {
+        EnvironmentSettings::default()
+    }
+}
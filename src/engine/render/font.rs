@@ -0,0 +1,244 @@
+use engine::asset::loader::FontData;
+use engine::asset::{Asset, AssetResult, AssetSystem, FileFuture, LoadState, LoadableAsset,
+                    Resource};
+use engine::render::{Texture, TextureImage};
+
+use image::{ImageBuffer, Rgba, RgbaImage};
+use rusttype::{point, GlyphId, PositionedGlyph, Scale};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Size of the glyph atlas texture in pixel
+const ATLAS_SIZE: u32 = 1024;
+/// Space between glyphs in the atlas to avoid bleeding
+const ATLAS_PADDING: u32 = 1;
+
+#[derive(Debug, Copy, Clone)]
+struct GlyphInfo {
+    /// (u0, v0, u1, v1) in the atlas
+    uv: (f32, f32, f32, f32),
+    /// Offset of the top-left corner from the pen position on baseline
+    offset: (f32, f32),
+    size: (f32, f32),
+}
+
+/// A glyph quad in pixel, y is pointing down and the origin is the top-left of the text
+#[derive(Debug, Copy, Clone)]
+pub struct GlyphQuad {
+    pub min: (f32, f32),
+    pub max: (f32, f32),
+    /// (u0, v0, u1, v1) in the atlas
+    pub uv: (f32, f32, f32, f32),
+}
+
+#[derive(Debug)]
+struct GlyphAtlas {
+    image: RgbaImage,
+    glyphs: HashMap<(char, u32), Option<GlyphInfo>>,
+    cursor: (u32, u32),
+    row_height: u32,
+}
+
+impl GlyphAtlas {
+    fn new() -> GlyphAtlas {
+        GlyphAtlas {
+            image: ImageBuffer::from_pixel(ATLAS_SIZE, ATLAS_SIZE, Rgba([0xff, 0xff, 0xff, 0])),
+            glyphs: HashMap::new(),
+            cursor: (0, 0),
+            row_height: 0,
+        }
+    }
+
+    /// Find a free space in the atlas with shelf packing
+    fn allocate(&mut self, w: u32, h: u32) -> Option<(u32, u32)> {
+        if self.cursor.0 + w > ATLAS_SIZE {
+            self.cursor = (0, self.cursor.1 + self.row_height + ATLAS_PADDING);
+            self.row_height = 0;
+        }
+
+        if self.cursor.1 + h > ATLAS_SIZE || w > ATLAS_SIZE {
+            return None;
+        }
+
+        let pos = self.cursor;
+        self.cursor.0 += w + ATLAS_PADDING;
+        self.row_height = self.row_height.max(h);
+
+        Some(pos)
+    }
+}
+
+/// TrueType font with a runtime glyph atlas
+#[derive(Debug)]
+pub struct Font {
+    data: Resource<FontData>,
+    atlas: RefCell<GlyphAtlas>,
+    /// The glyph atlas texture, alpha channel is the glyph coverage
+    pub texture: Rc<Texture>,
+}
+
+impl Asset for Font {
+    type Resource = Resource<FontData>;
+
+    fn new_from_resource(r: Self::Resource) -> Rc<Self> {
+        let atlas = GlyphAtlas::new();
+        let texture = Texture::new(TextureImage::Rgba(atlas.image.clone()));
+
+        Rc::new(Font {
+            data: r,
+            atlas: RefCell::new(atlas),
+            texture,
+        })
+    }
+}
+
+impl LoadableAsset for Font {
+    fn load<T: AssetSystem + Clone + 'static>(
+        asys: &T,
+        mut files: Vec<FileFuture>,
+    ) -> Self::Resource {
+        Self::load_resource::<FontData, T>(asys.clone(), files.remove(0))
+    }
+
+    fn gather<T: AssetSystem>(asys: &T, fname: &str) -> Vec<FileFuture> {
+        vec![asys.new_file(fname)]
+    }
+}
+
+impl LoadState for Font {
+    fn load_state(&self) -> AssetResult<()> {
+        self.data.try_borrow().map(|_| ())
+    }
+}
+
+impl Font {
+    pub fn is_ready(&self) -> bool {
+        self.data.try_borrow().is_ok()
+    }
+
+    /// The line height in pixel
+    pub fn line_height(&self, px: f32) -> AssetResult<f32> {
+        let data = self.data.try_borrow()?;
+        let vm = data.0.v_metrics(Scale::uniform(px));
+
+        Ok(vm.ascent - vm.descent + vm.line_gap)
+    }
+
+    /// The size of a text in pixel
+    pub fn measure(&self, s: &str, px: f32) -> AssetResult<(f32, f32)> {
+        let data = self.data.try_borrow()?;
+        let scale = Scale::uniform(px);
+        let vm = data.0.v_metrics(scale);
+        let line_height = vm.ascent - vm.descent + vm.line_gap;
+
+        let mut w: f32 = 0.0;
+        let mut nlines = 0;
+
+        for line in s.split('\n') {
+            w = w.max(line_width(&data, line, scale));
+            nlines += 1;
+        }
+
+        Ok((
+            w,
+            line_height * (nlines - 1) as f32 + vm.ascent - vm.descent,
+        ))
+    }
+
+    /// Layout a single line of text, glyphs which are not in the atlas are rasterized
+    /// and the atlas texture is updated.
+    /// Return the glyph quads and the width of the line
+    pub fn layout_line(&self, s: &str, px: f32, y: f32) -> AssetResult<(Vec<GlyphQuad>, f32)> {
+        let data = self.data.try_borrow()?;
+        let scale = Scale::uniform(px);
+        let ascent = data.0.v_metrics(scale).ascent;
+
+        let mut atlas = self.atlas.borrow_mut();
+        let mut dirty = false;
+        let mut quads = Vec::new();
+        let mut x = 0.0;
+        let mut last: Option<GlyphId> = None;
+
+        for c in s.chars() {
+            let glyph = data.0.glyph(c).scaled(scale);
+            let id = glyph.id();
+
+            if let Some(last) = last {
+                x += data.0.pair_kerning(scale, last, id);
+            }
+            last = Some(id);
+
+            let key = (c, px.round() as u32);
+            if !atlas.glyphs.contains_key(&key) {
+                let info = rasterize(&mut atlas, glyph.clone().positioned(point(0.0, 0.0)));
+                atlas.glyphs.insert(key, info);
+                dirty = true;
+            }
+
+            if let Some(info) = atlas.glyphs[&key] {
+                let min = (x + info.offset.0, y + ascent + info.offset.1);
+                quads.push(GlyphQuad {
+                    min,
+                    max: (min.0 + info.size.0, min.1 + info.size.1),
+                    uv: info.uv,
+                });
+            }
+
+            x += glyph.h_metrics().advance_width;
+        }
+
+        if dirty {
+            self.texture
+                .update_image(TextureImage::Rgba(atlas.image.clone()));
+        }
+
+        Ok((quads, x))
+    }
+}
+
+fn line_width(data: &FontData, s: &str, scale: Scale) -> f32 {
+    let mut x = 0.0;
+    let mut last: Option<GlyphId> = None;
+
+    for c in s.chars() {
+        let glyph = data.0.glyph(c).scaled(scale);
+        let id = glyph.id();
+
+        if let Some(last) = last {
+            x += data.0.pair_kerning(scale, last, id);
+        }
+        last = Some(id);
+
+        x += glyph.h_metrics().advance_width;
+    }
+
+    x
+}
+
+fn rasterize(atlas: &mut GlyphAtlas, glyph: PositionedGlyph) -> Option<GlyphInfo> {
+    let bb = glyph.pixel_bounding_box()?;
+    let (w, h) = (bb.width() as u32, bb.height() as u32);
+    let (ax, ay) = atlas.allocate(w, h)?;
+
+    {
+        let image = &mut atlas.image;
+        glyph.draw(|x, y, v| {
+            let a = (v * 255.0).round().max(0.0).min(255.0) as u8;
+            image.put_pixel(ax + x, ay + y, Rgba([0xff, 0xff, 0xff, a]));
+        });
+    }
+
+    let size = ATLAS_SIZE as f32;
+
+    Some(GlyphInfo {
+        uv: (
+            ax as f32 / size,
+            ay as f32 / size,
+            (ax + w) as f32 / size,
+            (ay + h) as f32 / size,
+        ),
+        offset: (bb.min.x as f32, bb.min.y as f32),
+        size: (w as f32, h as f32),
+    })
+}
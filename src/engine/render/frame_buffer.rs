@@ -6,6 +6,8 @@ use engine::render::{Texture, TextureAttachment};
 
 pub struct FrameBuffer {
     pub texture: Rc<Texture>,
+    /// Depth texture of a color frame buffer which is depth tested
+    pub depth: Option<Rc<Texture>>,
     handle: RefCell<Option<WebGLFrameBuffer>>,
 }
 
@@ -13,7 +15,30 @@ impl FrameBuffer {
     pub fn new(width: u32, height: u32, attach: TextureAttachment) -> FrameBuffer {
         let texture = Texture::new_render_texture(width, height, attach);
         let handle = RefCell::new(None);
-        FrameBuffer { texture, handle }
+        FrameBuffer {
+            texture,
+            depth: None,
+            handle,
+        }
+    }
+
+    /// A color frame buffer with a depth texture
+    pub fn new_with_depth(width: u32, height: u32, color: TextureAttachment) -> FrameBuffer {
+        let mut fb = FrameBuffer::new(width, height, color);
+        fb.depth = Some(Texture::new_render_texture(
+            width,
+            height,
+            TextureAttachment::Depth,
+        ));
+        fb
+    }
+
+    /// Resize the attached textures, they are created again in next bind
+    pub fn resize(&self, width: u32, height: u32) {
+        self.texture.resize(width, height);
+        if let Some(ref depth) = self.depth {
+            depth.resize(width, height);
+        }
     }
 
     fn create_fb(&self, gl: &WebGLRenderingContext) {
@@ -36,6 +61,9 @@ impl FrameBuffer {
 
         gl.bind_framebuffer(Buffers::Framebuffer, &h);
         self.texture.bind_with_frame_buffer(gl, 0).unwrap();
+        if let Some(ref depth) = self.depth {
+            depth.attach_depth(gl, 0).unwrap();
+        }
     }
 
     pub fn unbind(&self, gl: &WebGLRenderingContext) {
@@ -0,0 +1,101 @@
+use std::collections::VecDeque;
+use uni_gl::*;
+
+/// Frames whose queries may still be in flight, the older ones are dropped
+const MAX_PENDING_FRAMES: usize = 4;
+
+/// Times the passes on the GPU by timer queries, i.e. `EXT_disjoint_timer_query`
+/// on WebGL1 and `EXT_disjoint_timer_query_webgl2` on WebGL2.
+///
+/// A query is recorded around each pass, their results are read back when the GPU
+/// has finished the frame, at the earliest in the next one
+pub struct GpuTimer {
+    gl: WebGLRenderingContext,
+    /// The queries of the passes of the current frame
+    current: Vec<WebGLQuery>,
+    pending: VecDeque<Vec<WebGLQuery>>,
+    /// Milliseconds of the passes of the last frame which was read back
+    times: Vec<Option<f64>>,
+}
+
+impl GpuTimer {
+    pub fn new(gl: &WebGLRenderingContext) -> GpuTimer {
+        GpuTimer {
+            gl: gl.clone(),
+            current: Vec::new(),
+            pending: VecDeque::new(),
+            times: Vec::new(),
+        }
+    }
+
+    /// Start the frame, reading back the results of the frames the GPU has finished
+    pub fn begin_frame(&mut self) {
+        if !self.current.is_empty() {
+            let queries = self.current.drain(..).collect();
+            self.pending.push_back(queries);
+        }
+
+        // The results are invalid if the GPU was interrupted while measuring
+        if self.gl.get_gpu_disjoint() {
+            while let Some(queries) = self.pending.pop_front() {
+                self.delete(queries);
+            }
+            self.times.clear();
+            return;
+        }
+
+        while self.pending.front().map_or(false, |q| self.available(q)) {
+            let queries = self.pending.pop_front().unwrap();
+            self.times = queries
+                .iter()
+                .map(|q| Some(self.gl.get_query_result(q) as f64 / 1_000_000.0))
+                .collect();
+            self.delete(queries);
+        }
+
+        while self.pending.len() > MAX_PENDING_FRAMES {
+            let queries = self.pending.pop_front().unwrap();
+            self.delete(queries);
+        }
+    }
+
+    /// Start measuring the next pass
+    pub fn begin_pass(&mut self) {
+        let query = self.gl.create_query();
+        self.gl.begin_query(QueryTarget::TimeElapsed, &query);
+        self.current.push(query);
+    }
+
+    pub fn end_pass(&mut self) {
+        self.gl.end_query(QueryTarget::TimeElapsed);
+    }
+
+    /// Milliseconds which the GPU spent on the pass of the index `pass` in the last
+    /// frame read back, None until it is
+    pub fn time(&self, pass: usize) -> Option<f64> {
+        self.times.get(pass).cloned().unwrap_or(None)
+    }
+
+    fn available(&self, queries: &[WebGLQuery]) -> bool {
+        // The queries are finished in order
+        queries
+            .last()
+            .map_or(true, |q| self.gl.get_query_result_available(q))
+    }
+
+    fn delete(&self, queries: Vec<WebGLQuery>) {
+        for q in queries.iter() {
+            self.gl.delete_query(q);
+        }
+    }
+}
+
+impl Drop for GpuTimer {
+    fn drop(&mut self) {
+        let current = self.current.drain(..).collect();
+        self.delete(current);
+        while let Some(queries) = self.pending.pop_front() {
+            self.delete(queries);
+        }
+    }
+}
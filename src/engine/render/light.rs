@@ -1,4 +1,4 @@
-use super::ShaderProgram;
+use super::{ShaderProgram, Texture};
 use math::*;
 use std::rc::Rc;
 use std::sync::Arc;
@@ -8,6 +8,7 @@ use unrust::engine::{Component, ComponentArena, IntoComponentPtr};
 pub enum Light {
     Directional(DirectionalLight),
     Point(PointLight),
+    Spot(SpotLight),
 }
 
 macro_rules! impl_light {
@@ -33,6 +34,7 @@ macro_rules! impl_light {
 impl Light {
     impl_light!(directional, directional_mut, Directional, DirectionalLight);
     impl_light!(point, point_mut, Point, PointLight);
+    impl_light!(spot, spot_mut, Spot, SpotLight);
 
     pub fn new<T>(a: T) -> Light
     where
@@ -45,15 +47,159 @@ impl Light {
         match *self {
             Light::Directional(ref mut l) => l.update(model),
             Light::Point(ref mut l) => l.update(model),
+            Light::Spot(ref mut l) => l.update(model),
         }
     }
 
-    pub fn bind(&self, lightname: &str, prog: &ShaderProgram) {
+    /// Set the light to the uniforms `names`, see `LightUniforms`
+    pub fn bind(&self, names: &'static LightUniforms, prog: &ShaderProgram) {
         match *self {
-            Light::Directional(ref l) => l.bind(lightname, prog),
-            Light::Point(ref l) => l.bind(lightname, prog),
+            Light::Directional(ref l) => l.bind(names, prog),
+            Light::Point(ref l) => l.bind(names, prog),
+            Light::Spot(ref l) => l.bind(names, prog),
         }
     }
+
+    /// World space sphere lit by the light, None if it lits everything
+    pub fn bounding_sphere(&self) -> Option<(Vector3f, f32)> {
+        match *self {
+            Light::Directional(_) => None,
+            Light::Point(ref l) => Some((l.world_space_position, l.range)),
+            Light::Spot(ref l) => Some((l.world_space_position, l.range)),
+        }
+    }
+
+    /// Whether the light reaches the world space sphere at `center` of radius `r`
+    pub fn affects(&self, center: &Vector3f, r: f32) -> bool {
+        match self.bounding_sphere() {
+            Some((p, range)) => (p - center).magnitude() <= range + r,
+            None => true,
+        }
+    }
+
+    /// Bind a light of zero intensity in the slot `names`, such that it is unused
+    pub fn bind_unused(names: &'static LightUniforms, prog: &ShaderProgram) {
+        prog.set(names.rate.as_str(), 0.0);
+    }
+
+    /// Texture modulating the light, see `DirectionalLight::cookie` and `SpotLight::cookie`
+    pub fn cookie(&self) -> Option<&Rc<Texture>> {
+        match *self {
+            Light::Directional(ref l) => l.cookie.as_ref(),
+            Light::Point(_) => None,
+            Light::Spot(ref l) => l.cookie.as_ref(),
+        }
+    }
+}
+
+/// Sizes of the point and spot light arrays of the forward shaders,
+/// see `UNI_POINT_LIGHTS` and `UNI_SPOT_LIGHTS`
+pub const MAX_POINT_LIGHTS: usize = 4;
+pub const MAX_SPOT_LIGHTS: usize = 4;
+
+/// The names of the uniforms of the members of a light, e.g. "uPointLights[0].position".
+/// They are built once for the lights of the forward shaders, such that binding a light
+/// does not format them
+pub struct LightUniforms {
+    position: String,
+    direction: String,
+    ambient: String,
+    diffuse: String,
+    specular: String,
+    constant: String,
+    linear: String,
+    quadratic: String,
+    range: String,
+    cut_off: String,
+    outer_cut_off: String,
+    cookie: String,
+    cookie_up: String,
+    cookie_size: String,
+    cookie_scale: String,
+    rate: String,
+}
+
+/// The uniforms of a light in the fragment and in the vertex stage
+pub type LightStageUniforms = (LightUniforms, LightUniforms);
+
+fn stage_uniforms(name: &str, index: Option<usize>) -> LightStageUniforms {
+    match index {
+        Some(i) => (
+            LightUniforms::new(&format!("{}[{}]", name, i)),
+            LightUniforms::new(&format!("{}VS[{}]", name, i)),
+        ),
+        None => (
+            LightUniforms::new(name),
+            LightUniforms::new(&format!("{}VS", name)),
+        ),
+    }
+}
+
+lazy_static! {
+    static ref DIRECTIONAL_UNIFORMS: LightStageUniforms =
+        stage_uniforms("uDirectionalLight", None);
+    static ref POINT_UNIFORMS: Vec<LightStageUniforms> = (0..MAX_POINT_LIGHTS)
+        .map(|i| stage_uniforms("uPointLights", Some(i)))
+        .collect();
+    static ref SPOT_UNIFORMS: Vec<LightStageUniforms> = (0..MAX_SPOT_LIGHTS)
+        .map(|i| stage_uniforms("uSpotLights", Some(i)))
+        .collect();
+}
+
+impl LightUniforms {
+    fn new(name: &str) -> LightUniforms {
+        let member = |m: &str| format!("{}.{}", name, m);
+
+        LightUniforms {
+            position: member("position"),
+            direction: member("direction"),
+            ambient: member("ambient"),
+            diffuse: member("diffuse"),
+            specular: member("specular"),
+            constant: member("constant"),
+            linear: member("linear"),
+            quadratic: member("quadratic"),
+            range: member("range"),
+            cut_off: member("cutOff"),
+            outer_cut_off: member("outerCutOff"),
+            cookie: member("cookie"),
+            cookie_up: member("cookieUp"),
+            cookie_size: member("cookieSize"),
+            cookie_scale: member("cookieScale"),
+            rate: member("rate"),
+        }
+    }
+
+    /// `uDirectionalLight` and `uDirectionalLightVS`, the main light
+    pub fn directional() -> &'static LightStageUniforms {
+        &DIRECTIONAL_UNIFORMS
+    }
+
+    /// The slot `i` of `uPointLights` and `uPointLightsVS`
+    pub fn point(i: usize) -> &'static LightStageUniforms {
+        &POINT_UNIFORMS[i]
+    }
+
+    /// The slot `i` of `uSpotLights` and `uSpotLightsVS`
+    pub fn spot(i: usize) -> &'static LightStageUniforms {
+        &SPOT_UNIFORMS[i]
+    }
+
+    /// The uniform of the cookie flag of a spot light
+    pub fn cookie(&self) -> &str {
+        &self.cookie
+    }
+}
+
+/// A unit vector perpendicular to `d`, the up of the cookie of a light along `d`
+fn cookie_up(d: &Vector3f) -> Vector3f {
+    let reference = if d.y.abs() < 0.99 {
+        Vector3::new(0.0, 1.0, 0.0)
+    } else {
+        Vector3::new(0.0, 0.0, 1.0)
+    };
+
+    d.cross(reference).cross(*d).normalize()
 }
 
 pub struct DirectionalLight {
@@ -62,7 +208,16 @@ pub struct DirectionalLight {
     pub diffuse: Vector3<f32>,
     pub specular: Vector3<f32>,
 
+    /// Texture tiled over the world across the light, whose rgb modulates it, e.g. the
+    /// caustics under water or the shadows of clouds. It should repeat, see
+    /// `Texture::set_wrap`
+    pub cookie: Option<Rc<Texture>>,
+    /// Size in world units of a tile of the cookie
+    pub cookie_size: f32,
+
     pub world_space_direction: Vector3f,
+    /// Up of the cookie in the world, across the light
+    pub world_space_up: Vector3f,
 }
 
 impl Default for DirectionalLight {
@@ -80,7 +235,11 @@ impl Default for DirectionalLight {
             diffuse: Vector3::new(1.0, 0.957, 0.839),
             specular: Vector3::new(1.0, 1.0, 1.0),
 
+            cookie: None,
+            cookie_size: 10.0,
+
             world_space_direction: light_dir,
+            world_space_up: cookie_up(&light_dir),
         }
     }
 }
@@ -92,19 +251,21 @@ impl From<DirectionalLight> for Light {
 }
 
 impl DirectionalLight {
-    fn bind(&self, lightname: &str, prog: &ShaderProgram) {
-        prog.set(
-            lightname.to_string() + ".direction",
-            self.world_space_direction,
-        );
-        prog.set(lightname.to_string() + ".ambient", self.ambient);
-        prog.set(lightname.to_string() + ".diffuse", self.diffuse);
-        prog.set(lightname.to_string() + ".specular", self.specular);
+    fn bind(&self, names: &'static LightUniforms, prog: &ShaderProgram) {
+        prog.set(names.direction.as_str(), self.world_space_direction);
+        prog.set(names.ambient.as_str(), self.ambient);
+        prog.set(names.diffuse.as_str(), self.diffuse);
+        prog.set(names.specular.as_str(), self.specular);
+
+        prog.set(names.cookie.as_str(), self.cookie.is_some());
+        prog.set(names.cookie_up.as_str(), self.world_space_up);
+        prog.set(names.cookie_size.as_str(), self.cookie_size);
     }
 
     fn update(&mut self, modelm: &Matrix4f) {
         let m = modelm.inverse_transform().unwrap().transpose();
         self.world_space_direction = m.transform_vector(self.direction);
+        self.world_space_up = m.transform_vector(cookie_up(&self.direction)).normalize();
     }
 }
 
@@ -115,9 +276,12 @@ pub struct PointLight {
     pub diffuse: Vector3<f32>,
     pub specular: Vector3<f32>,
 
+    /// Attenuation by the distance d, 1 / (constant + linear * d + quadratic * d^2)
     pub constant: f32,
     pub linear: f32,
     pub quadratic: f32,
+    /// Distance where the light fades out, the objects out of it are not lit
+    pub range: f32,
 
     pub world_space_position: Vector3f,
 }
@@ -139,32 +303,127 @@ impl Default for PointLight {
             constant: 1.0,
             linear: 0.022,
             quadratic: 0.0019,
+            range: 200.0,
         }
     }
 }
 
 impl PointLight {
-    fn bind(&self, lightname: &str, prog: &ShaderProgram) {
-        prog.set(
-            lightname.to_string() + ".position",
-            self.world_space_position,
-        );
+    fn bind(&self, names: &'static LightUniforms, prog: &ShaderProgram) {
+        prog.set(names.position.as_str(), self.world_space_position);
+
+        prog.set(names.ambient.as_str(), self.ambient);
+        prog.set(names.diffuse.as_str(), self.diffuse);
+        prog.set(names.specular.as_str(), self.specular);
+
+        prog.set(names.constant.as_str(), self.constant);
+        prog.set(names.linear.as_str(), self.linear);
+        prog.set(names.quadratic.as_str(), self.quadratic);
+        prog.set(names.range.as_str(), self.range);
+
+        prog.set(names.rate.as_str(), 1.0);
+    }
+
+    fn update(&mut self, modelm: &Matrix4f) {
+        self.world_space_position = modelm
+            .transform_point(Point3::from_vec(self.position))
+            .to_vec();
+    }
+}
 
-        prog.set(lightname.to_string() + ".ambient", self.ambient);
-        prog.set(lightname.to_string() + ".diffuse", self.diffuse);
-        prog.set(lightname.to_string() + ".specular", self.specular);
+pub struct SpotLight {
+    pub position: Vector3<f32>,
+    pub direction: Vector3<f32>,
 
-        prog.set(lightname.to_string() + ".constant", self.constant);
-        prog.set(lightname.to_string() + ".linear", self.linear);
-        prog.set(lightname.to_string() + ".quadratic", self.quadratic);
+    pub ambient: Vector3<f32>,
+    pub diffuse: Vector3<f32>,
+    pub specular: Vector3<f32>,
+
+    /// Attenuation by the distance d, 1 / (constant + linear * d + quadratic * d^2)
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+    /// Distance where the light fades out, the objects out of it are not lit
+    pub range: f32,
+
+    /// Half angle of the cone in radians
+    pub angle: f32,
+    /// Part of the cone from its edge (0..1) where the light fades out
+    pub falloff: f32,
+
+    /// Texture projected over the cone, whose rgb modulates the light, e.g. the pattern
+    /// of a flashlight or the shape of a window. Only one cookie of the spot lights
+    /// reaching an object is drawn on it, the other ones light it without their cookies
+    pub cookie: Option<Rc<Texture>>,
+
+    pub world_space_position: Vector3f,
+    pub world_space_direction: Vector3f,
+    /// Up of the cookie in the world, across the cone
+    pub world_space_up: Vector3f,
+}
 
-        prog.set(lightname.to_string() + ".rate", 1.0);
+impl From<SpotLight> for Light {
+    fn from(w: SpotLight) -> Light {
+        Light::Spot(w)
+    }
+}
+
+impl Default for SpotLight {
+    fn default() -> SpotLight {
+        SpotLight {
+            position: Vector3::new(0.0, 0.0, 0.0),
+            direction: Vector3::new(0.0, -1.0, 0.0),
+            ambient: Vector3::new(0.0, 0.0, 0.0),
+            diffuse: Vector3::new(1.0, 1.0, 1.0),
+            specular: Vector3::new(1.0, 1.0, 1.0),
+            world_space_position: Vector3f::zero(),
+            world_space_direction: Vector3::new(0.0, -1.0, 0.0),
+            world_space_up: Vector3::new(0.0, 0.0, 1.0),
+            constant: 1.0,
+            linear: 0.045,
+            quadratic: 0.0075,
+            range: 100.0,
+            angle: 0.5,
+            falloff: 0.2,
+            cookie: None,
+        }
+    }
+}
+
+impl SpotLight {
+    fn bind(&self, names: &'static LightUniforms, prog: &ShaderProgram) {
+        prog.set(names.position.as_str(), self.world_space_position);
+        prog.set(names.direction.as_str(), self.world_space_direction);
+
+        prog.set(names.ambient.as_str(), self.ambient);
+        prog.set(names.diffuse.as_str(), self.diffuse);
+        prog.set(names.specular.as_str(), self.specular);
+
+        prog.set(names.constant.as_str(), self.constant);
+        prog.set(names.linear.as_str(), self.linear);
+        prog.set(names.quadratic.as_str(), self.quadratic);
+        prog.set(names.range.as_str(), self.range);
+
+        // The cone is passed as cosines, the light is full inside the inner one
+        let falloff = self.falloff.max(0.0).min(1.0);
+        prog.set(names.outer_cut_off.as_str(), self.angle.cos());
+        prog.set(names.cut_off.as_str(), (self.angle * (1.0 - falloff)).cos());
+
+        prog.set(names.cookie.as_str(), self.cookie.is_some());
+        prog.set(names.cookie_up.as_str(), self.world_space_up);
+        prog.set(names.cookie_scale.as_str(), self.angle.tan());
+
+        prog.set(names.rate.as_str(), 1.0);
     }
 
     fn update(&mut self, modelm: &Matrix4f) {
         self.world_space_position = modelm
             .transform_point(Point3::from_vec(self.position))
             .to_vec();
+        self.world_space_direction = modelm.transform_vector(self.direction).normalize();
+        self.world_space_up = modelm
+            .transform_vector(cookie_up(&self.direction))
+            .normalize();
     }
 }
 
@@ -181,3 +440,10 @@ impl IntoComponentPtr for PointLight {
         Component::new(light, arena)
     }
 }
+
+impl IntoComponentPtr for SpotLight {
+    fn into_component_ptr(self, arena: &Rc<ComponentArena>) -> Arc<Component> {
+        let light: Light = self.into();
+        Component::new(light, arena)
+    }
+}
@@ -0,0 +1,245 @@
+use engine::render::Camera;
+
+use math::*;
+
+/// Tiles of the clusters across the width and the height of the screen
+pub const CLUSTER_TILES: usize = 8;
+/// Exponential depth slices of the clusters between the near and the far planes
+pub const CLUSTER_SLICES: usize = 16;
+
+/// Inclusive ranges of the tiles in x and y and of the slices of some clusters
+type ClusterRange = [(usize, usize); 3];
+
+/// The point and spot lights of a pass culled into clusters, the cells of a grid of
+/// screen tiles by depth slices over the frustum of the camera.
+///
+/// Each light is added once per pass to the clusters which its sphere overlaps. An
+/// object then only considers the lights of the clusters of its bounds, instead of all
+/// the lights of the scene. The ranges are conservative, a light may be found in a
+/// cluster it does not reach but never missed in one it does
+pub struct LightClusters {
+    view: Matrix4<f32>,
+    projection: Matrix4<f32>,
+    znear: f32,
+    zfar: f32,
+    /// Indices of the lights of each cluster, by x then y then the slice
+    clusters: Vec<Vec<usize>>,
+    /// Whether a light was found yet for the current query, by index
+    found: Vec<bool>,
+}
+
+impl LightClusters {
+    /// Cluster the world space spheres of `lights` in the frustum of `camera`
+    pub fn for_camera(
+        camera: &Camera,
+        screen_size: (u32, u32),
+        lights: &[(Vector3<f32>, f32)],
+    ) -> LightClusters {
+        let projection = camera.perspective(screen_size);
+        LightClusters::new(camera.v, projection, camera.znear, camera.zfar, lights)
+    }
+
+    pub fn new(
+        view: Matrix4<f32>,
+        projection: Matrix4<f32>,
+        znear: f32,
+        zfar: f32,
+        lights: &[(Vector3<f32>, f32)],
+    ) -> LightClusters {
+        // The slices are exponential, from a near plane in front of the eye
+        let znear = znear.max(0.01);
+
+        let mut clusters = LightClusters {
+            view,
+            projection,
+            znear,
+            zfar: zfar.max(znear * 2.0),
+            clusters: vec![Vec::new(); CLUSTER_TILES * CLUSTER_TILES * CLUSTER_SLICES],
+            found: vec![false; lights.len()],
+        };
+
+        for (i, &(center, range)) in lights.iter().enumerate() {
+            if let Some(r) = clusters.range(&center, range) {
+                for_each_cluster(&r, |c| clusters.clusters[c].push(i));
+            }
+        }
+
+        clusters
+    }
+
+    /// The slice of the view space depth `d`
+    fn slice(&self, d: f32) -> usize {
+        if d <= self.znear {
+            return 0;
+        }
+
+        let s = (d / self.znear).ln() / (self.zfar / self.znear).ln() * CLUSTER_SLICES as f32;
+        (s.max(0.0) as usize).min(CLUSTER_SLICES - 1)
+    }
+
+    /// The clusters overlapped by the world space sphere at `center` of radius `r`,
+    /// None out of the frustum
+    fn range(&self, center: &Vector3<f32>, r: f32) -> Option<ClusterRange> {
+        let c = (self.view * center.extend(1.0)).truncate();
+        let (dmin, dmax) = (-c.z - r, -c.z + r);
+        if dmax < self.znear || dmin > self.zfar {
+            return None;
+        }
+
+        let slices = (self.slice(dmin), self.slice(dmax));
+        let all = (0, CLUSTER_TILES - 1);
+
+        // A sphere through the near plane could cover the whole screen
+        if dmin <= self.znear {
+            return Some([all, all, slices]);
+        }
+
+        let (mut min, mut max) = (Vector2::new(1.0f32, 1.0), Vector2::new(-1.0f32, -1.0));
+        for i in 0..8 {
+            let corner = Vector3::new(
+                if i & 1 == 0 { c.x - r } else { c.x + r },
+                if i & 2 == 0 { c.y - r } else { c.y + r },
+                if i & 4 == 0 { c.z - r } else { c.z + r },
+            );
+
+            let p = self.projection * corner.extend(1.0);
+            let ndc = Vector2::new(p.x / p.w, p.y / p.w);
+            min = Vector2::new(min.x.min(ndc.x), min.y.min(ndc.y));
+            max = Vector2::new(max.x.max(ndc.x), max.y.max(ndc.y));
+        }
+
+        if max.x < -1.0 || max.y < -1.0 || min.x > 1.0 || min.y > 1.0 {
+            return None;
+        }
+
+        let tile = |ndc: f32| {
+            let t = (ndc * 0.5 + 0.5) * CLUSTER_TILES as f32;
+            (t.max(0.0) as usize).min(CLUSTER_TILES - 1)
+        };
+
+        Some([(tile(min.x), tile(max.x)), (tile(min.y), tile(max.y)), slices])
+    }
+
+    /// The indices of the lights in the clusters of the world space sphere at `center`
+    /// of radius `r`, each once
+    pub fn lights_near(&mut self, center: &Vector3<f32>, r: f32) -> Vec<usize> {
+        let mut lights = Vec::new();
+
+        if let Some(range) = self.range(center, r) {
+            let (clusters, found) = (&self.clusters, &mut self.found);
+            for_each_cluster(&range, |c| {
+                for &i in clusters[c].iter() {
+                    if !found[i] {
+                        found[i] = true;
+                        lights.push(i);
+                    }
+                }
+            });
+        }
+
+        for &i in lights.iter() {
+            self.found[i] = false;
+        }
+
+        lights
+    }
+}
+
+fn for_each_cluster<F: FnMut(usize)>(range: &ClusterRange, mut f: F) {
+    let ((x0, x1), (y0, y1), (z0, z1)) = (range[0], range[1], range[2]);
+
+    for z in z0..z1 + 1 {
+        for y in y0..y1 + 1 {
+            for x in x0..x1 + 1 {
+                f((z * CLUSTER_TILES + y) * CLUSTER_TILES + x);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clusters(lights: &[(Vector3<f32>, f32)]) -> LightClusters {
+        let view = Matrix4::look_at(
+            Point3::new(0.0, 2.0, 10.0),
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        );
+        let projection = Matrix4::from(PerspectiveFov {
+            fovy: Deg(60.0).into(),
+            aspect: 1.5,
+            near: 0.1,
+            far: 100.0,
+        });
+
+        LightClusters::new(view, projection, 0.1, 100.0, lights)
+    }
+
+    /// Deterministic spheres around the origin
+    fn spheres(n: usize, max_radius: f32) -> Vec<(Vector3<f32>, f32)> {
+        let mut seed = 12345u32;
+        let mut next = || {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 8) as f32 / (1 << 24) as f32
+        };
+
+        (0..n)
+            .map(|_| {
+                let x = next() * 40.0 - 20.0;
+                let y = next() * 10.0 - 5.0;
+                let z = next() * 40.0 - 30.0;
+                (Vector3::new(x, y, z), 0.1 + next() * max_radius)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_every_light_reaching_an_object() {
+        let lights = spheres(200, 4.0);
+        let mut clusters = clusters(&lights);
+
+        for &(center, r) in spheres(100, 2.0).iter() {
+            let mut near = clusters.lights_near(&center, r);
+            near.sort();
+
+            let mut dedup = near.clone();
+            dedup.dedup();
+            assert_eq!(near, dedup);
+
+            // The objects in the frustum find every light reaching them
+            if clusters.range(&center, r).is_none() {
+                continue;
+            }
+            for (i, &(p, range)) in lights.iter().enumerate() {
+                let reaches = (p - center).magnitude() <= range + r;
+                if reaches && clusters.range(&p, range).is_some() {
+                    assert!(near.contains(&i), "light {} missed", i);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn culls_the_lights_far_away() {
+        let lights = vec![
+            (Vector3::new(0.0, 0.0, 0.0), 1.0),
+            (Vector3::new(15.0, 0.0, -20.0), 1.0),
+            // Behind the camera
+            (Vector3::new(0.0, 0.0, 30.0), 1.0),
+        ];
+        let mut clusters = clusters(&lights);
+
+        assert_eq!(clusters.lights_near(&Vector3::new(0.5, 0.0, 0.0), 0.5), vec![0]);
+        assert_eq!(clusters.lights_near(&Vector3::new(15.0, 0.0, -20.0), 0.5), vec![1]);
+        assert!(clusters.lights_near(&Vector3::new(0.0, 0.0, 30.0), 0.5).is_empty());
+        assert!(clusters.range(&Vector3::new(0.0, 0.0, 30.0), 1.0).is_none());
+    }
+
+    #[test]
+    fn no_lights() {
+        let mut clusters = clusters(&[]);
+        assert!(clusters.lights_near(&Vector3::new(0.0, 0.0, 0.0), 100.0).is_empty());
+    }
+}
@@ -0,0 +1,192 @@
+use engine::render::Texture;
+
+use math::*;
+use std::f32::consts::PI;
+
+/// Number of the samples of a panorama on its longest side, see `from_panorama`
+const PANORAMA_SAMPLES: u32 = 128;
+
+/// Number of the nearest probes which the ambient of an object is blended from
+const BLENDED_PROBES: usize = 4;
+
+/// The real spherical harmonics basis up to the second band, for a unit direction
+fn sh_basis(d: &Vector3<f32>) -> [f32; 9] {
+    [
+        0.282095,
+        0.488603 * d.y,
+        0.488603 * d.z,
+        0.488603 * d.x,
+        1.092548 * d.x * d.y,
+        1.092548 * d.y * d.z,
+        0.315392 * (3.0 * d.z * d.z - 1.0),
+        1.092548 * d.x * d.z,
+        0.546274 * (d.x * d.x - d.y * d.y),
+    ]
+}
+
+/// Radiance around a point projected on the first 3 bands (9 coefficients) of the
+/// spherical harmonics, in linear rgb
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SphericalHarmonics {
+    pub coefficients: [Vector3<f32>; 9],
+}
+
+impl SphericalHarmonics {
+    pub fn zero() -> SphericalHarmonics {
+        SphericalHarmonics {
+            coefficients: [Vector3::zero(); 9],
+        }
+    }
+
+    /// The same radiance from all the directions, e.g. a flat ambient color
+    pub fn ambient(color: Vector3<f32>) -> SphericalHarmonics {
+        let mut sh = SphericalHarmonics::zero();
+        sh.coefficients[0] = color * (0.282095 * 4.0 * PI);
+        sh
+    }
+
+    /// Add the radiance from a direction over a solid angle `weight`,
+    /// the weights of the samples of the whole sphere sum to 4 pi
+    pub fn add_sample(&mut self, dir: &Vector3<f32>, color: Vector3<f32>, weight: f32) {
+        let basis = sh_basis(&dir.normalize());
+        for (c, y) in self.coefficients.iter_mut().zip(basis.iter()) {
+            *c += color * (y * weight);
+        }
+    }
+
+    /// Project an equirectangular panorama of rgb pixels from its top-left, mapped like
+    /// the panoramas of the skyboxes, and decoded from `gamma` to linear
+    pub fn from_panorama(width: u32, height: u32, rgb: &[u8], gamma: f32) -> SphericalHarmonics {
+        let mut sh = SphericalHarmonics::zero();
+        if width == 0 || height == 0 || rgb.len() < (width * height * 3) as usize {
+            return sh;
+        }
+
+        let step = (width.max(height) / PANORAMA_SAMPLES).max(1);
+        let (cols, rows) = ((width + step - 1) / step, (height + step - 1) / step);
+
+        for y in 0..rows {
+            let v = (y as f32 + 0.5) / rows as f32;
+            let theta = v * PI;
+            let weight = (2.0 * PI / cols as f32) * (PI / rows as f32) * theta.sin();
+
+            for x in 0..cols {
+                let u = (x as f32 + 0.5) / cols as f32;
+                let phi = (u - 0.5) * 2.0 * PI;
+                let dir = Vector3::new(
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                    -theta.sin() * phi.cos(),
+                );
+
+                let i = (((y * step).min(height - 1) * width + (x * step).min(width - 1)) * 3)
+                    as usize;
+                let color = Vector3::new(
+                    (rgb[i] as f32 / 255.0).powf(gamma),
+                    (rgb[i + 1] as f32 / 255.0).powf(gamma),
+                    (rgb[i + 2] as f32 / 255.0).powf(gamma),
+                );
+
+                sh.add_sample(&dir, color, weight);
+            }
+        }
+
+        sh
+    }
+
+    /// Project the panorama image of a texture, None until it is loaded, see
+    /// `Texture::rgb_channels`. It should be read before the texture is uploaded
+    pub fn from_texture(texture: &Texture, gamma: f32) -> Option<SphericalHarmonics> {
+        let (w, h, rgb) = texture.rgb_channels()?;
+        Some(SphericalHarmonics::from_panorama(w, h, &rgb, gamma))
+    }
+
+    pub fn scaled(&self, s: f32) -> SphericalHarmonics {
+        let mut sh = *self;
+        for c in sh.coefficients.iter_mut() {
+            *c *= s;
+        }
+        sh
+    }
+
+    pub fn add(&mut self, other: &SphericalHarmonics) {
+        for (c, o) in self.coefficients.iter_mut().zip(other.coefficients.iter()) {
+            *c += *o;
+        }
+    }
+
+    /// The coefficients convolved by the cosine lobe and divided by pi, such that
+    /// their sum by the basis of a normal is the diffuse ambient of a white surface
+    pub fn irradiance_coefficients(&self) -> [Vector3<f32>; 9] {
+        let bands = [1.0, 2.0 / 3.0, 2.0 / 3.0, 2.0 / 3.0, 0.25, 0.25, 0.25, 0.25, 0.25];
+
+        let mut coefficients = self.coefficients;
+        for (c, band) in coefficients.iter_mut().zip(bands.iter()) {
+            *c *= *band;
+        }
+        coefficients
+    }
+
+    /// The diffuse ambient of a white surface of the normal `n`
+    pub fn irradiance(&self, n: &Vector3<f32>) -> Vector3<f32> {
+        let basis = sh_basis(&n.normalize());
+        self.irradiance_coefficients()
+            .iter()
+            .zip(basis.iter())
+            .fold(Vector3::zero(), |acc, (c, y)| acc + c * *y)
+    }
+}
+
+/// Ambient lighting captured at a point of the scene
+#[derive(Copy, Clone, Debug)]
+pub struct LightProbe {
+    pub position: Vector3<f32>,
+    pub sh: SphericalHarmonics,
+}
+
+/// The light probes of a scene, baked offline or captured at load. The ambient of each
+/// object is blended from the nearest probes to the center of its bounds, and replaces
+/// the flat ambient of the main light in the default programs.
+///
+/// The programs get it in the `uLightProbeSH` array of the irradiance coefficients,
+/// and `uLightProbeEnabled` tells whether there are probes
+#[derive(Component)]
+pub struct LightProbeGroup {
+    pub probes: Vec<LightProbe>,
+    /// Factor of the ambient of the probes
+    pub intensity: f32,
+}
+
+impl LightProbeGroup {
+    pub fn new(probes: Vec<LightProbe>) -> LightProbeGroup {
+        LightProbeGroup {
+            probes,
+            intensity: 1.0,
+        }
+    }
+
+    /// The ambient at a world position, blended from the nearest probes by their
+    /// inverse squared distances. None without probes
+    pub fn sample(&self, p: &Vector3<f32>) -> Option<SphericalHarmonics> {
+        let mut nearest: Vec<(f32, &LightProbe)> = self.probes
+            .iter()
+            .map(|probe| ((probe.position - p).magnitude2(), probe))
+            .collect();
+        nearest.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(::std::cmp::Ordering::Equal));
+        nearest.truncate(BLENDED_PROBES);
+
+        let &(d, probe) = nearest.first()?;
+        if d < 1e-6 {
+            return Some(probe.sh.scaled(self.intensity));
+        }
+
+        let total: f32 = nearest.iter().map(|&(d, _)| 1.0 / d).sum();
+
+        let mut sh = SphericalHarmonics::zero();
+        for &(d, probe) in nearest.iter() {
+            sh.add(&probe.sh.scaled(1.0 / d / total));
+        }
+
+        Some(sh.scaled(self.intensity))
+    }
+}
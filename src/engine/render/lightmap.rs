@@ -0,0 +1,173 @@
+use engine::asset::Asset;
+use engine::render::{DirectionalLight, MeshData, Texture, TextureImage};
+
+use image::{ImageBuffer, Rgba, RgbaImage};
+use math::*;
+use std::rc::Rc;
+
+/// A simple offline baker of the lighting of the static meshes in their second uv set,
+/// see `MeshData::uvs2` and `Mesh::set_lightmap`.
+///
+/// Each texel gets the ambient and the diffuse of the directional lights by the normal
+/// of the surface, without shadows or bounces. The lightmaps baked by external tools
+/// are imported as any other texture, encoded by the same `gamma`
+pub struct LightmapBaker {
+    /// Width and height of the lightmaps in pixels
+    pub size: u32,
+    /// The lighting of every texel, in linear rgb
+    pub ambient: Vector3<f32>,
+    /// Directions the lights shine along and their linear colors
+    pub lights: Vec<(Vector3<f32>, Vector3<f32>)>,
+    /// Gamma of the encoded texels, see `Camera::gamma`
+    pub gamma: f32,
+    /// Pixels the charts are grown by against the seams of the bilinear filtering
+    pub padding: u32,
+}
+
+fn edge(a: &Vector2<f32>, b: &Vector2<f32>, p: &Vector2<f32>) -> f32 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+impl LightmapBaker {
+    pub fn new(size: u32) -> LightmapBaker {
+        LightmapBaker {
+            size,
+            ambient: Vector3::zero(),
+            lights: Vec::new(),
+            gamma: 2.2,
+            padding: 2,
+        }
+    }
+
+    /// Add the ambient and the diffuse of a light, its colors are decoded by `gamma`
+    pub fn add_light(&mut self, light: &DirectionalLight) {
+        let linear = |c: Vector3<f32>| {
+            Vector3::new(c.x.powf(self.gamma), c.y.powf(self.gamma), c.z.powf(self.gamma))
+        };
+
+        self.ambient += linear(light.ambient);
+        let diffuse = linear(light.diffuse);
+        self.lights.push((light.direction, diffuse));
+    }
+
+    /// The lighting of a world space normal in linear rgb
+    pub fn irradiance(&self, n: &Vector3<f32>) -> Vector3<f32> {
+        self.lights
+            .iter()
+            .fold(self.ambient, |acc, &(dir, color)| {
+                acc + color * n.dot(-dir.normalize()).max(0.0)
+            })
+    }
+
+    /// Bake the lightmap of a mesh placed by `model_m`, None without second uvs
+    /// or normals
+    pub fn bake_image(&self, data: &MeshData, model_m: &Matrix4<f32>) -> Option<RgbaImage> {
+        let uvs2 = data.uvs2.as_ref()?;
+        let normals = data.normals.as_ref()?;
+
+        let size = self.size.max(1);
+        let normal_m = model_m
+            .invert()
+            .map(|inv| inv.transpose())
+            .unwrap_or(*model_m);
+
+        let mut texels: Vec<Option<Vector3<f32>>> = vec![None; (size * size) as usize];
+
+        for tri in data.indices.chunks(3).filter(|tri| tri.len() == 3) {
+            let uv = |i: u16| {
+                let i = i as usize * 2;
+                Vector2::new(uvs2[i] * size as f32, uvs2[i + 1] * size as f32)
+            };
+            let normal = |i: u16| {
+                let i = i as usize * 3;
+                normal_m.transform_vector(Vector3::new(normals[i], normals[i + 1], normals[i + 2]))
+            };
+
+            let (a, b, c) = (uv(tri[0]), uv(tri[1]), uv(tri[2]));
+            let (na, nb, nc) = (normal(tri[0]), normal(tri[1]), normal(tri[2]));
+
+            let area = edge(&a, &b, &c);
+            if area.abs() < 1e-8 {
+                continue;
+            }
+
+            let s = size as f32;
+            let min_x = a.x.min(b.x).min(c.x).max(0.0).min(s) as u32;
+            let max_x = a.x.max(b.x).max(c.x).ceil().max(0.0).min(s) as u32;
+            let min_y = a.y.min(b.y).min(c.y).max(0.0).min(s) as u32;
+            let max_y = a.y.max(b.y).max(c.y).ceil().max(0.0).min(s) as u32;
+
+            for y in min_y..max_y {
+                for x in min_x..max_x {
+                    let p = Vector2::new(x as f32 + 0.5, y as f32 + 0.5);
+
+                    let w0 = edge(&b, &c, &p) / area;
+                    let w1 = edge(&c, &a, &p) / area;
+                    let w2 = edge(&a, &b, &p) / area;
+                    if w0 < 0.0 || w1 < 0.0 || w2 < 0.0 {
+                        continue;
+                    }
+
+                    let n = na * w0 + nb * w1 + nc * w2;
+                    if n.magnitude2() <= 0.0 {
+                        continue;
+                    }
+
+                    texels[(y * size + x) as usize] = Some(self.irradiance(&n.normalize()));
+                }
+            }
+        }
+
+        for _ in 0..self.padding {
+            texels = dilate(&texels, size);
+        }
+
+        let encode = |c: f32| (c.max(0.0).powf(1.0 / self.gamma).min(1.0) * 255.0).round() as u8;
+
+        Some(ImageBuffer::from_fn(size, size, |x, y| {
+            match texels[(y * size + x) as usize] {
+                Some(c) => Rgba([encode(c.x), encode(c.y), encode(c.z), 255]),
+                None => Rgba([0, 0, 0, 255]),
+            }
+        }))
+    }
+
+    /// Bake the lightmap of a mesh into a texture, see `bake_image`
+    pub fn bake(&self, data: &MeshData, model_m: &Matrix4<f32>) -> Option<Rc<Texture>> {
+        let img = self.bake_image(data, model_m)?;
+        Some(Texture::new(TextureImage::Rgba(img)))
+    }
+}
+
+/// Fill the empty texels next to the baked ones by the average of their neighbours
+fn dilate(texels: &[Option<Vector3<f32>>], size: u32) -> Vec<Option<Vector3<f32>>> {
+    let mut out = texels.to_vec();
+    let size = size as i32;
+
+    for y in 0..size {
+        for x in 0..size {
+            if texels[(y * size + x) as usize].is_some() {
+                continue;
+            }
+
+            let mut sum = Vector3::zero();
+            let mut count = 0;
+            for &(dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)].iter() {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || nx >= size || ny >= size {
+                    continue;
+                }
+                if let Some(c) = texels[(ny * size + nx) as usize] {
+                    sum += c;
+                    count += 1;
+                }
+            }
+
+            if count > 0 {
+                out[(y * size + x) as usize] = Some(sum / count as f32);
+            }
+        }
+    }
+
+    out
+}
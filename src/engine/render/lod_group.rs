@@ -0,0 +1,134 @@
+use engine::core::Aabb;
+use engine::render::{Camera, Mesh, MeshSurface};
+
+use math::*;
+use std::rc::Rc;
+
+/// The surfaces of a level of detail, drawn while the object is at least
+/// `screen_size` of the height of the view
+pub struct LodLevel {
+    pub surfaces: Vec<Rc<MeshSurface>>,
+    pub screen_size: f32,
+}
+
+/// The levels of detail of an object, the level drawn by a camera is selected by the
+/// projected size of the bounding sphere of the first level. The levels are ordered
+/// from the most detailed one by decreasing `screen_size`, and the object is not drawn
+/// below the size of the last one.
+///
+/// The levels are cross-faded by a dither over `fade_width` (a fraction of the
+/// threshold) above each threshold, by the `uLodFade` uniform of the default programs
+#[derive(Component)]
+pub struct LodGroup {
+    pub levels: Vec<LodLevel>,
+    /// Width of the cross-fades as a fraction of the thresholds, e.g. 0.1,
+    /// the levels are switched at once if None
+    pub fade_width: Option<f32>,
+    /// Factor of the projected size, more than 1 to keep the detailed levels farther
+    pub bias: f32,
+}
+
+impl LodGroup {
+    pub fn new() -> LodGroup {
+        LodGroup {
+            levels: Vec::new(),
+            fade_width: None,
+            bias: 1.0,
+        }
+    }
+
+    /// Add the surfaces of `mesh` as the next level
+    pub fn add_level(&mut self, mesh: &Mesh, screen_size: f32) {
+        self.levels.push(LodLevel {
+            surfaces: mesh.surfaces.clone(),
+            screen_size,
+        });
+    }
+
+    /// Local bounding sphere of the first level, None until its buffers are loaded
+    pub fn local_sphere(&self) -> Option<(Vector3<f32>, f32)> {
+        let level = self.levels.first()?;
+
+        let mut aabb = Aabb::empty();
+        for surface in level.surfaces.iter() {
+            aabb.merge(&surface.buffer.bounds()?.local_aabb());
+        }
+
+        Some(aabb.sphere())
+    }
+
+    /// Projected size of the object for `camera`, see `Camera::projected_size`
+    pub fn screen_size(&self, model_m: &Matrix4<f32>, camera: &Camera) -> Option<f32> {
+        let (center, r) = self.local_sphere()?;
+
+        let scale = model_m
+            .x
+            .truncate()
+            .magnitude()
+            .max(model_m.y.truncate().magnitude())
+            .max(model_m.z.truncate().magnitude());
+        let center = model_m.transform_point(Point3::from_vec(center)).to_vec();
+
+        Some(camera.projected_size(&center, r * scale) * self.bias)
+    }
+
+    /// The levels drawn at a projected size with their `uLodFade`, the second one is
+    /// the level faded out to
+    pub fn select(&self, size: f32) -> Vec<(usize, f32)> {
+        let i = match self.levels.iter().position(|l| size >= l.screen_size) {
+            Some(i) => i,
+            None => return Vec::new(),
+        };
+
+        let threshold = self.levels[i].screen_size;
+        let band = self.fade_width.unwrap_or(0.0) * threshold;
+
+        if band > 0.0 && size < threshold + band {
+            let fade = (size - threshold) / band;
+            if fade > 0.0 {
+                let mut selected = vec![(i, fade)];
+                if i + 1 < self.levels.len() {
+                    selected.push((i + 1, -fade));
+                }
+                return selected;
+            }
+        }
+
+        vec![(i, 0.0)]
+    }
+
+    /// The surfaces drawn by `camera`, the ones of the first level until the bounds
+    /// are known. The fading surfaces are copies with their own parameters
+    pub fn surfaces(&self, model_m: &Matrix4<f32>, camera: &Camera) -> Vec<Rc<MeshSurface>> {
+        let selected = match self.screen_size(model_m, camera) {
+            Some(size) => self.select(size),
+            None if self.levels.is_empty() => Vec::new(),
+            None => vec![(0, 0.0)],
+        };
+
+        let mut surfaces = Vec::new();
+        for (i, fade) in selected {
+            for surface in self.levels[i].surfaces.iter() {
+                if fade == 0.0 {
+                    surfaces.push(surface.clone());
+                    continue;
+                }
+
+                let params = surface
+                    .params
+                    .as_ref()
+                    .map(|params| (**params).clone())
+                    .unwrap_or_default();
+                params.set("uLodFade", fade);
+
+                surfaces.push(Rc::new(MeshSurface {
+                    buffer: surface.buffer.clone(),
+                    material: surface.material.clone(),
+                    params: Some(Rc::new(params)),
+                }));
+            }
+        }
+
+        surfaces
+    }
+}
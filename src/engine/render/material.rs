@@ -83,10 +83,83 @@ impl Default for DepthTest {
     }
 }
 
+/// Factor which the source or the destination color is multiplied by in a blending
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum BlendFactor {
+    Zero,
+    One,
+    SrcColor,
+    OneMinusSrcColor,
+    SrcAlpha,
+    OneMinusSrcAlpha,
+    DstColor,
+    OneMinusDstColor,
+    DstAlpha,
+    OneMinusDstAlpha,
+}
+
+/// How the weighted source and destination colors are combined
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum BlendEquation {
+    /// src + dst
+    Add,
+    /// src - dst
+    Subtract,
+    /// dst - src
+    ReverseSubtract,
+}
+
+/// How the color of a surface is blended into the target, if `alpha_blending` is set
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct BlendMode {
+    pub src: BlendFactor,
+    pub dst: BlendFactor,
+    pub equation: BlendEquation,
+}
+
+impl BlendMode {
+    pub fn new(src: BlendFactor, dst: BlendFactor) -> BlendMode {
+        BlendMode {
+            src,
+            dst,
+            equation: BlendEquation::Add,
+        }
+    }
+
+    /// The default blending by the alpha of the surface
+    pub fn alpha() -> BlendMode {
+        BlendMode::new(BlendFactor::SrcAlpha, BlendFactor::OneMinusSrcAlpha)
+    }
+
+    /// The colors premultiplied by their alpha, e.g. of the render textures
+    pub fn premultiplied() -> BlendMode {
+        BlendMode::new(BlendFactor::One, BlendFactor::OneMinusSrcAlpha)
+    }
+
+    /// Add the color weighted by its alpha, e.g. for fire and glow particles
+    pub fn additive() -> BlendMode {
+        BlendMode::new(BlendFactor::SrcAlpha, BlendFactor::One)
+    }
+
+    /// Multiply the target by the color, e.g. for the decals of dirt and shadows
+    pub fn multiply() -> BlendMode {
+        BlendMode::new(BlendFactor::DstColor, BlendFactor::Zero)
+    }
+}
+
+impl Default for BlendMode {
+    fn default() -> BlendMode {
+        BlendMode::alpha()
+    }
+}
+
+/// The fixed function states of a material, the states which are None are inherited
+/// from the render queue or the defaults
 #[derive(Copy, Clone, Eq, PartialEq, Default, Debug)]
 pub struct MaterialState {
     pub cull: Option<CullMode>,
     pub alpha_blending: Option<bool>,
+    pub blend: Option<BlendMode>,
     pub depth_write: Option<bool>,
     pub depth_test: Option<DepthTest>,
 }
@@ -98,6 +171,8 @@ pub struct Material {
     pub states: MaterialState,
 
     params: RefCell<MaterialParamMap>,
+    /// Defines of the variant of the program, sorted
+    keywords: RefCell<Vec<String>>,
 }
 
 impl PartialEq for Material {
@@ -105,6 +180,7 @@ impl PartialEq for Material {
         Rc::ptr_eq(&self.program, &other.program) && self.render_queue == other.render_queue
             && self.states == other.states
             && *self.params.borrow() == *other.params.borrow()
+            && *self.keywords.borrow() == *other.keywords.borrow()
     }
 }
 
@@ -115,9 +191,45 @@ impl Material {
             program: program,
             params: RefCell::new(FnvHashMap::default()),
             states: MaterialState::default(),
+            keywords: RefCell::new(Vec::new()),
         };
     }
 
+    /// Define `keyword` in the shaders of the material, e.g. `SHADOWS_ON`
+    pub fn enable_keyword(&self, keyword: &str) {
+        let mut keywords = self.keywords.borrow_mut();
+        if let Err(i) = keywords.binary_search_by(|k| k.as_str().cmp(keyword)) {
+            keywords.insert(i, keyword.to_string());
+        }
+    }
+
+    pub fn disable_keyword(&self, keyword: &str) {
+        let mut keywords = self.keywords.borrow_mut();
+        if let Ok(i) = keywords.binary_search_by(|k| k.as_str().cmp(keyword)) {
+            keywords.remove(i);
+        }
+    }
+
+    pub fn has_keyword(&self, keyword: &str) -> bool {
+        self.keywords.borrow().iter().any(|k| k == keyword)
+    }
+
+    /// The variant of the program for the keywords of the material
+    pub fn shader_program(&self) -> AssetResult<Rc<ShaderProgram>> {
+        ShaderProgram::variant(&self.program, &self.keywords.borrow())
+    }
+
+    /// The variant of the program for the keywords of the material and `UNI_INSTANCED`,
+    /// which draws the instances of a `MeshInstances`
+    pub fn instanced_shader_program(&self) -> AssetResult<Rc<ShaderProgram>> {
+        let mut keywords = self.keywords.borrow().clone();
+        if let Err(i) = keywords.binary_search_by(|k| k.as_str().cmp("UNI_INSTANCED")) {
+            keywords.insert(i, "UNI_INSTANCED".to_string());
+        }
+
+        ShaderProgram::variant(&self.program, &keywords)
+    }
+
     pub fn set<T, S>(&self, name: S, t: T)
     where
         T: Into<MaterialParam>,
@@ -126,60 +238,107 @@ impl Material {
         self.params.borrow_mut().insert(name.into(), t.into());
     }
 
-    fn bind_params<F>(
-        &self,
-        params: &MaterialParamMap,
-        request_tex_unit: &mut F,
-        level: u32,
-    ) -> AssetResult<()>
+    pub fn get(&self, name: &str) -> Option<MaterialParam> {
+        self.params.borrow().get(name).cloned()
+    }
+
+    pub fn bind<F>(&self, mut request_tex_unit: F) -> AssetResult<()>
     where
         F: FnMut(&Rc<Texture>) -> AssetResult<u32>,
     {
-        for (name, param) in params.iter() {
-            match param {
-                &MaterialParam::Texture(ref tex) => {
-                    let new_unit = request_tex_unit(&tex.0)?;
-                    self.program
-                        .set(name.clone(), (Rc::downgrade(&tex.0), new_unit));
-                }
-                &MaterialParam::Bool(v) => {
-                    self.program.set(name.clone(), v);
-                }
-                &MaterialParam::Float(f) => {
-                    self.program.set(name.clone(), f);
-                }
-                &MaterialParam::Int(v) => {
-                    self.program.set(name.clone(), v);
-                }
-                &MaterialParam::Vec2(v) => {
-                    self.program.set(name.clone(), v);
-                }
-                &MaterialParam::Vec3(v) => {
-                    self.program.set(name.clone(), v);
-                }
-                &MaterialParam::Vec4(v) => {
-                    self.program.set(name.clone(), v);
-                }
-                &MaterialParam::Matrix4(v) => {
-                    self.program.set(name.clone(), v);
-                }
-                &MaterialParam::Params(ref pm) => {
-                    self.bind_params(&pm, request_tex_unit, level + 1)?;
-                }
-            }
-        }
+        let program = self.shader_program()?;
+        bind_params(&program, &self.params.borrow(), &mut request_tex_unit, 0)?;
 
         Ok(())
     }
+}
 
-    pub fn bind<F>(&self, mut request_tex_unit: F) -> AssetResult<()>
+/// Parameters of a surface set over the ones of its shared material when it is drawn,
+/// e.g. a tint or an uv offset per object without a material per object,
+/// see `Mesh::set_params`. A parameter should also be set to the material as its value
+/// for the other surfaces, as the uniforms are kept by the program between draw calls
+#[derive(Debug, Default, Clone)]
+pub struct MaterialParams {
+    params: RefCell<MaterialParamMap>,
+}
+
+impl MaterialParams {
+    pub fn new() -> MaterialParams {
+        MaterialParams::default()
+    }
+
+    pub fn set<T, S>(&self, name: S, t: T)
+    where
+        T: Into<MaterialParam>,
+        S: Into<Cow<'static, str>>,
+    {
+        self.params.borrow_mut().insert(name.into(), t.into());
+    }
+
+    pub fn get(&self, name: &str) -> Option<MaterialParam> {
+        self.params.borrow().get(name).cloned()
+    }
+
+    pub fn remove(&self, name: &str) {
+        self.params.borrow_mut().remove(name);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.params.borrow().is_empty()
+    }
+
+    /// Set the parameters to `program`, the one of the material bound before
+    pub fn bind<F>(&self, program: &ShaderProgram, mut request_tex_unit: F) -> AssetResult<()>
     where
         F: FnMut(&Rc<Texture>) -> AssetResult<u32>,
     {
-        self.bind_params(&self.params.borrow(), &mut request_tex_unit, 0)?;
+        bind_params(program, &self.params.borrow(), &mut request_tex_unit, 0)
+    }
+}
 
-        Ok(())
+fn bind_params<F>(
+    program: &ShaderProgram,
+    params: &MaterialParamMap,
+    request_tex_unit: &mut F,
+    level: u32,
+) -> AssetResult<()>
+where
+    F: FnMut(&Rc<Texture>) -> AssetResult<u32>,
+{
+    for (name, param) in params.iter() {
+        match param {
+            &MaterialParam::Texture(ref tex) => {
+                let new_unit = request_tex_unit(&tex.0)?;
+                program.set(name.clone(), (Rc::downgrade(&tex.0), new_unit));
+            }
+            &MaterialParam::Bool(v) => {
+                program.set(name.clone(), v);
+            }
+            &MaterialParam::Float(f) => {
+                program.set(name.clone(), f);
+            }
+            &MaterialParam::Int(v) => {
+                program.set(name.clone(), v);
+            }
+            &MaterialParam::Vec2(v) => {
+                program.set(name.clone(), v);
+            }
+            &MaterialParam::Vec3(v) => {
+                program.set(name.clone(), v);
+            }
+            &MaterialParam::Vec4(v) => {
+                program.set(name.clone(), v);
+            }
+            &MaterialParam::Matrix4(v) => {
+                program.set(name.clone(), v);
+            }
+            &MaterialParam::Params(ref pm) => {
+                bind_params(program, &pm, request_tex_unit, level + 1)?;
+            }
+        }
     }
+
+    Ok(())
 }
 
 impl Asset for Material {
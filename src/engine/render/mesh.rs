@@ -1,5 +1,6 @@
 use engine::core::Aabb;
-use engine::render::{Material, MeshBuffer};
+use engine::render::{Material, MaterialParams, MeshBuffer, MeshInstances, Texture};
+use math::{Matrix4, Vector4};
 use std::cell::Cell;
 use std::rc::Rc;
 
@@ -30,12 +31,24 @@ impl MeshBound {
 pub struct MeshSurface {
     pub buffer: Rc<MeshBuffer>,
     pub material: Rc<Material>,
+    /// Parameters set over the ones of the material for this surface only
+    pub params: Option<Rc<MaterialParams>>,
 }
 
 #[derive(Component, Clone)]
 pub struct Mesh {
     pub surfaces: Vec<Rc<MeshSurface>>,
     pub mesh_bounds: Cell<Option<MeshBound>>,
+    /// Generations of the buffers which `mesh_bounds` was computed from
+    bounds_generation: Cell<u32>,
+    /// World space aabb of the last model matrix and local aabb, see `world_bounds`
+    world_bounds: Cell<Option<(Matrix4<f32>, Aabb, Aabb)>>,
+    /// Order in the UI queue, meshes of higher layers are drawn later
+    pub layer: i32,
+    /// Weights of the morph targets of the surfaces, applied when they are rendered
+    /// unless empty, see `MeshBuffer::set_morph_weights`
+    pub morph_weights: Vec<f32>,
+    instances: Option<Rc<MeshInstances>>,
 }
 
 impl Mesh {
@@ -43,9 +56,52 @@ impl Mesh {
         Mesh {
             surfaces: Vec::new(),
             mesh_bounds: Cell::new(None),
+            bounds_generation: Cell::new(0),
+            world_bounds: Cell::new(None),
+            layer: 0,
+            morph_weights: Vec::new(),
+            instances: None,
         }
     }
 
+    /// A mesh of copies of `buffer` at each of `transforms`, in the local space of the
+    /// object. They are drawn in a single draw call where instanced arrays are
+    /// supported, see `MeshInstances`
+    pub fn instanced<U, T>(buffer: U, material: T, transforms: Vec<Matrix4<f32>>) -> Mesh
+    where
+        U: Into<Rc<MeshBuffer>>,
+        T: Into<Rc<Material>>,
+    {
+        let mut mesh = Mesh::new();
+        mesh.instances = Some(Rc::new(MeshInstances::new(
+            buffer.into(),
+            material.into(),
+            transforms,
+        )));
+
+        mesh
+    }
+
+    pub fn instances(&self) -> Option<&Rc<MeshInstances>> {
+        self.instances.as_ref()
+    }
+
+    /// Replace the transforms of the instances of an instanced mesh
+    pub fn set_instances(&mut self, transforms: Vec<Matrix4<f32>>) {
+        let instances = match self.instances {
+            Some(ref instances) => MeshInstances::new(
+                instances.buffer().clone(),
+                instances.material().clone(),
+                transforms,
+            ),
+            None => return,
+        };
+
+        self.instances = Some(Rc::new(instances));
+        self.mesh_bounds.set(None);
+        self.world_bounds.set(None);
+    }
+
     pub fn add_surface<U, T>(&mut self, buffer: U, material: T)
     where
         U: Into<Rc<MeshBuffer>>,
@@ -54,18 +110,96 @@ impl Mesh {
         self.surfaces.push(Rc::new(MeshSurface {
             buffer: buffer.into(),
             material: material.into(),
+            params: None,
         }));
+        self.mesh_bounds.set(None);
+        self.world_bounds.set(None);
+    }
+
+    /// Set `params` over the materials of all the surfaces, e.g. a tint of this mesh only.
+    /// The surfaces with parameters are not batched in the UI and the sprite queues
+    pub fn set_params(&mut self, params: Option<Rc<MaterialParams>>) {
+        self.surfaces = self.surfaces
+            .iter()
+            .map(|s| {
+                Rc::new(MeshSurface {
+                    buffer: s.buffer.clone(),
+                    material: s.material.clone(),
+                    params: params.clone(),
+                })
+            })
+            .collect();
+    }
+
+    /// Light the surfaces by a lightmap in their second uvs, see `MeshData::uvs2`, or
+    /// back by the lights with None. The uvs are scaled by `xy` and offset by `zw` of
+    /// `scale_offset` into the lightmap, e.g. for a region of an atlas of lightmaps.
+    ///
+    /// It is set in the parameters of the surfaces as `uLightmap`, `uLightmapEnabled`
+    /// and `uLightmapScaleOffset`, which replace the ambient and the diffuse of the
+    /// main light in the default programs. The lightmap is marked as an sRGB texture
+    pub fn set_lightmap(&mut self, lightmap: Option<Rc<Texture>>, scale_offset: Vector4<f32>) {
+        if let Some(ref lightmap) = lightmap {
+            lightmap.set_srgb(true);
+        }
+
+        self.surfaces = self.surfaces
+            .iter()
+            .map(|s| {
+                let params = s.params
+                    .as_ref()
+                    .map(|params| (**params).clone())
+                    .unwrap_or_default();
+
+                params.set("uLightmapEnabled", lightmap.is_some());
+                params.set("uLightmapScaleOffset", scale_offset);
+                if let Some(ref lightmap) = lightmap {
+                    params.set("uLightmap", lightmap.clone());
+                }
+
+                Rc::new(MeshSurface {
+                    buffer: s.buffer.clone(),
+                    material: s.material.clone(),
+                    params: Some(Rc::new(params)),
+                })
+            })
+            .collect();
+    }
+
+    pub fn remove_surface(&mut self, surface: &Rc<MeshSurface>) {
+        self.surfaces.retain(|s| !Rc::ptr_eq(surface, s));
+        self.mesh_bounds.set(None);
+        self.world_bounds.set(None);
     }
 
     pub fn remove_buffer(&mut self, buffer: &Rc<MeshBuffer>) {
         self.surfaces
-            .retain(|surface| !Rc::ptr_eq(buffer, &surface.buffer))
+            .retain(|surface| !Rc::ptr_eq(buffer, &surface.buffer));
+        self.mesh_bounds.set(None);
+        self.world_bounds.set(None);
+    }
+
+    /// Combined generations of the buffers, which change when any of them is updated
+    fn buffers_generation(&self) -> u32 {
+        let generation = self.surfaces.iter().fold(0u32, |acc, s| {
+            acc.wrapping_mul(31).wrapping_add(s.buffer.generation())
+        });
+
+        match self.instances {
+            Some(ref instances) => generation
+                .wrapping_mul(31)
+                .wrapping_add(instances.buffer().generation()),
+            None => generation,
+        }
     }
 
     /// bounds return (vmin, vmax)
     pub fn bounds(&self) -> Option<MeshBound> {
+        let generation = self.buffers_generation();
         if let Some(_) = self.mesh_bounds.get() {
-            return self.mesh_bounds.get();
+            if self.bounds_generation.get() == generation {
+                return self.mesh_bounds.get();
+            }
         }
 
         if self.surfaces
@@ -76,13 +210,34 @@ impl Mesh {
             return None;
         }
 
-        let bb = self.surfaces.iter().fold(MeshBound::empty(), |mut acc, s| {
+        let mut bb = self.surfaces.iter().fold(MeshBound::empty(), |mut acc, s| {
             s.buffer.bounds().map(|b| acc.merge(&b));
 
             acc
         });
 
+        if let Some(ref instances) = self.instances {
+            bb.merge(&instances.bounds()?);
+        }
+
         self.mesh_bounds.set(Some(bb));
+        self.bounds_generation.set(generation);
         self.mesh_bounds.get()
     }
+
+    /// World space aabb of the mesh for the model matrix `m`,
+    /// cached until the matrix or the bounds change
+    pub fn world_bounds(&self, m: &Matrix4<f32>) -> Option<Aabb> {
+        let local = self.bounds()?.local_aabb();
+
+        if let Some((last_m, last_local, aabb)) = self.world_bounds.get() {
+            if last_m == *m && last_local == local {
+                return Some(aabb);
+            }
+        }
+
+        let aabb = local.transform(m);
+        self.world_bounds.set(Some((*m, local, aabb)));
+        Some(aabb)
+    }
 }
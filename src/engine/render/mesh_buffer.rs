@@ -1,7 +1,7 @@
 use std::mem::size_of;
 use uni_gl::*;
 
-use super::ShaderProgram;
+use super::{vertex_array_objects, BufferUsage, GLBackend, RenderBackend, ShaderProgram};
 use engine::asset::{Asset, AssetResult, AssetSystem, FileFuture, LoadableAsset, Resource};
 use engine::core::Aabb;
 use engine::render::mesh::MeshBound;
@@ -9,7 +9,7 @@ use engine::render::shader_program::ShaderAttrib;
 
 use math::*;
 use std::cell::Cell;
-use std::cell::RefCell;
+use std::cell::{Ref, RefCell};
 use std::f32::{MAX, MIN};
 use std::rc::Rc;
 use std::rc::Weak;
@@ -28,12 +28,22 @@ impl<T> IntoBytes for Vec<T> {
     }
 }
 
+/// How the indices of a mesh buffer are assembled into primitives
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MeshPrimitive {
+    Triangles,
+    /// Each pair of indices is a line segment
+    Lines,
+}
+
 enum RebindAction {
     Vertices,
     UV,
+    UV2,
     Normal,
     Tangent,
     Bitangent,
+    Color,
     Indices,
 }
 
@@ -41,10 +51,12 @@ struct MeshGLState {
     pub vao: WebGLVertexArray,
     pub vb: WebGLBuffer,
     pub uvb: Option<WebGLBuffer>,
+    pub uv2b: Option<WebGLBuffer>,
 
     pub nb: Option<WebGLBuffer>,
     pub tb: Option<WebGLBuffer>,
     pub btb: Option<WebGLBuffer>,
+    pub cb: Option<WebGLBuffer>,
 
     pub ib: WebGLBuffer,
     pub gl: WebGLRenderingContext,
@@ -56,12 +68,14 @@ impl MeshGLState {
     fn rebind_buffer(
         &mut self,
         data: &MeshData,
+        weights: &[f32],
+        joint_matrices: &[Matrix4<f32>],
         tt: &RebindAction,
     ) -> (BufferKind, Vec<u8>, &mut WebGLBuffer) {
         match *tt {
             RebindAction::Vertices => (
                 BufferKind::Array,
-                data.vertices.clone().into_bytes(),
+                data.deformed_vertices(weights, joint_matrices).into_bytes(),
                 &mut self.vb,
             ),
             RebindAction::UV => (
@@ -69,9 +83,16 @@ impl MeshGLState {
                 data.uvs.clone().unwrap().into_bytes(),
                 self.uvb.as_mut().unwrap(),
             ),
+            RebindAction::UV2 => (
+                BufferKind::Array,
+                data.uvs2.clone().unwrap().into_bytes(),
+                self.uv2b.as_mut().unwrap(),
+            ),
             RebindAction::Normal => (
                 BufferKind::Array,
-                data.normals.clone().unwrap().into_bytes(),
+                data.deformed_normals(weights, joint_matrices)
+                    .unwrap()
+                    .into_bytes(),
                 self.nb.as_mut().unwrap(),
             ),
             RebindAction::Tangent => (
@@ -84,6 +105,11 @@ impl MeshGLState {
                 data.bitangents.clone().unwrap().into_bytes(),
                 self.btb.as_mut().unwrap(),
             ),
+            RebindAction::Color => (
+                BufferKind::Array,
+                data.colors.clone().unwrap().into_bytes(),
+                self.cb.as_mut().unwrap(),
+            ),
             RebindAction::Indices => (
                 BufferKind::ElementArray,
                 data.indices.clone().into_bytes(),
@@ -92,12 +118,20 @@ impl MeshGLState {
         }
     }
 
-    fn rebind(&mut self, actions: &Vec<RebindAction>, data: &MeshData, gl: &WebGLRenderingContext) {
+    fn rebind(
+        &mut self,
+        actions: &Vec<RebindAction>,
+        data: &MeshData,
+        weights: &[f32],
+        joint_matrices: &[Matrix4<f32>],
+        gl: &WebGLRenderingContext,
+    ) {
         for action in actions.iter() {
-            let (k, p, buf) = self.rebind_buffer(data, action);
+            let (k, p, buf) = self.rebind_buffer(data, weights, joint_matrices, action);
 
+            // Rebound buffers are likely to be changed again, e.g. the meshes of the UI
             gl.bind_buffer(k, &buf);
-            gl.buffer_data(k, &p, DrawMode::Static);
+            gl.buffer_data(k, &p, DrawMode::Dynamic);
             gl.unbind_buffer(k);
         }
     }
@@ -107,25 +141,49 @@ impl Drop for MeshGLState {
     fn drop(&mut self) {
         self.gl.delete_buffer(&self.vb);
         self.uvb.as_ref().map(|b| self.gl.delete_buffer(&b));
+        self.uv2b.as_ref().map(|b| self.gl.delete_buffer(&b));
         self.nb.as_ref().map(|b| self.gl.delete_buffer(&b));
         self.tb.as_ref().map(|b| self.gl.delete_buffer(&b));
         self.btb.as_ref().map(|b| self.gl.delete_buffer(&b));
+        self.cb.as_ref().map(|b| self.gl.delete_buffer(&b));
         self.gl.delete_buffer(&self.ib);
 
         self.gl.delete_vertex_array(&self.vao);
     }
 }
 
-#[derive(Default, Debug)]
+/// Displacements of the vertices of a mesh blended by a weight, a blend shape
+#[derive(Default, Debug, Clone)]
+pub struct MorphTarget {
+    pub name: Option<String>,
+    /// Position deltas, one per vertex
+    pub positions: Vec<f32>,
+    /// Normal deltas, one per vertex
+    pub normals: Option<Vec<f32>>,
+}
+
+#[derive(Default, Debug, Clone)]
 pub struct MeshData {
     pub vertices: Vec<f32>,
     pub uvs: Option<Vec<f32>>,
+    /// Second texture coordinates, e.g. of a lightmap, see `Mesh::set_lightmap`
+    pub uvs2: Option<Vec<f32>>,
     pub normals: Option<Vec<f32>>,
 
     pub tangents: Option<Vec<f32>>,
     pub bitangents: Option<Vec<f32>>,
 
+    /// Vertex colors in rgba
+    pub colors: Option<Vec<f32>>,
+
+    /// Indices of the 4 joints of the skin which move each vertex, see `Skin`
+    pub joints: Option<Vec<u16>>,
+    /// Weights of the 4 joints of each vertex, which sum up to 1
+    pub weights: Option<Vec<f32>>,
+
     pub indices: Vec<u16>,
+
+    pub morph_targets: Vec<MorphTarget>,
 }
 
 impl MeshData {
@@ -145,12 +203,171 @@ impl MeshData {
             }
         }
 
+        // The bounds contain the targets at their full weights
+        for target in self.morph_targets.iter() {
+            for (i, v) in self.vertices.chunks(3).enumerate() {
+                let d = match target.positions.get(i * 3..i * 3 + 3) {
+                    Some(d) => d,
+                    None => break,
+                };
+
+                let p = vec3(v[0] + d[0], v[1] + d[1], v[2] + d[2]);
+                for k in 0..3 {
+                    min[k] = p[k].min(min[k]);
+                    max[k] = p[k].max(max[k]);
+                }
+                r = r.max(p.magnitude());
+            }
+        }
+
         MeshBound {
             aabb: Aabb { min, max },
             r,
         }
     }
 
+    /// The vertices moved by the morph targets of non zero `weights`
+    pub fn morphed_vertices(&self, weights: &[f32]) -> Vec<f32> {
+        let mut vertices = self.vertices.clone();
+
+        for (target, &w) in self.morph_targets.iter().zip(weights.iter()) {
+            if w == 0.0 {
+                continue;
+            }
+
+            for (v, d) in vertices.iter_mut().zip(target.positions.iter()) {
+                *v += d * w;
+            }
+        }
+
+        vertices
+    }
+
+    /// The normals moved by the morph targets of non zero `weights`
+    pub fn morphed_normals(&self, weights: &[f32]) -> Option<Vec<f32>> {
+        let mut normals = self.normals.clone()?;
+
+        let mut morphed = false;
+        for (target, &w) in self.morph_targets.iter().zip(weights.iter()) {
+            if w == 0.0 {
+                continue;
+            }
+
+            if let Some(ref deltas) = target.normals {
+                for (n, d) in normals.iter_mut().zip(deltas.iter()) {
+                    *n += d * w;
+                }
+                morphed = true;
+            }
+        }
+
+        if morphed {
+            for n in normals.chunks_mut(3) {
+                let v = vec3(n[0], n[1], n[2]);
+                if v.magnitude2() > 0.0 {
+                    let v = v.normalize();
+                    n.copy_from_slice(&[v.x, v.y, v.z]);
+                }
+            }
+        }
+
+        Some(normals)
+    }
+
+    /// Transform the xyz triples of `values` by the joint matrices blended by the
+    /// weights of their vertices, `w` is 1 for the positions and 0 for the directions
+    fn skin(&self, values: &mut [f32], joint_matrices: &[Matrix4<f32>], w: f32) {
+        let (joints, weights) = match (&self.joints, &self.weights) {
+            (&Some(ref joints), &Some(ref weights)) => (joints, weights),
+            _ => return,
+        };
+
+        let vertices = values
+            .chunks_mut(3)
+            .zip(joints.chunks(4))
+            .zip(weights.chunks(4));
+
+        for ((v, joints), weights) in vertices {
+            let p = Vector4::new(v[0], v[1], v[2], w);
+            let mut skinned = Vector4::zero();
+
+            for (&joint, &weight) in joints.iter().zip(weights.iter()) {
+                if weight == 0.0 {
+                    continue;
+                }
+                if let Some(m) = joint_matrices.get(joint as usize) {
+                    skinned += (m * p) * weight;
+                }
+            }
+
+            v.copy_from_slice(&[skinned.x, skinned.y, skinned.z]);
+        }
+    }
+
+    /// The vertices moved by the morph targets, then by the skin of `joint_matrices`
+    /// unless empty
+    pub fn deformed_vertices(
+        &self,
+        weights: &[f32],
+        joint_matrices: &[Matrix4<f32>],
+    ) -> Vec<f32> {
+        let mut vertices = self.morphed_vertices(weights);
+        if !joint_matrices.is_empty() {
+            self.skin(&mut vertices, joint_matrices, 1.0);
+        }
+
+        vertices
+    }
+
+    /// The normals moved by the morph targets, then by the skin of `joint_matrices`
+    /// unless empty. The joints are assumed to be scaled uniformly
+    pub fn deformed_normals(
+        &self,
+        weights: &[f32],
+        joint_matrices: &[Matrix4<f32>],
+    ) -> Option<Vec<f32>> {
+        let mut normals = self.morphed_normals(weights)?;
+
+        if !joint_matrices.is_empty() && self.joints.is_some() {
+            self.skin(&mut normals, joint_matrices, 0.0);
+            for n in normals.chunks_mut(3) {
+                let v = vec3(n[0], n[1], n[2]);
+                if v.magnitude2() > 0.0 {
+                    let v = v.normalize();
+                    n.copy_from_slice(&[v.x, v.y, v.z]);
+                }
+            }
+        }
+
+        Some(normals)
+    }
+
+    /// Whether the mesh data have the same optional attributes as `other`
+    pub fn is_same_layout(&self, other: &MeshData) -> bool {
+        self.uvs.is_some() == other.uvs.is_some()
+            && self.uvs2.is_some() == other.uvs2.is_some()
+            && self.normals.is_some() == other.normals.is_some()
+            && self.tangents.is_some() == other.tangents.is_some()
+            && self.bitangents.is_some() == other.bitangents.is_some()
+            && self.colors.is_some() == other.colors.is_some()
+            && self.joints.is_some() == other.joints.is_some()
+    }
+
+    /// Estimated size in bytes of the vertex attributes and the indices
+    pub fn byte_size(&self) -> usize {
+        let floats = self.vertices.len()
+            + self.uvs.as_ref().map_or(0, |v| v.len())
+            + self.uvs2.as_ref().map_or(0, |v| v.len())
+            + self.normals.as_ref().map_or(0, |v| v.len())
+            + self.tangents.as_ref().map_or(0, |v| v.len())
+            + self.bitangents.as_ref().map_or(0, |v| v.len())
+            + self.colors.as_ref().map_or(0, |v| v.len())
+            + self.weights.as_ref().map_or(0, |v| v.len());
+        let shorts = self.indices.len() + self.joints.as_ref().map_or(0, |v| v.len());
+
+        floats * 4 + shorts * 2
+    }
+
     pub fn translate(&mut self, disp: Vector3f) {
         for (i, v) in self.vertices.iter_mut().enumerate() {
             *v += disp[i % 3];
@@ -162,6 +379,13 @@ pub struct MeshBuffer {
     data: Resource<MeshData>,
     gl_state: RefCell<Option<MeshGLState>>,
     bounds: Cell<Option<MeshBound>>,
+    /// Incremented whenever the mesh data is updated
+    generation: Cell<u32>,
+    /// Weights of the morph targets of the mesh data
+    morph_weights: RefCell<Vec<f32>>,
+    /// Matrices of the joints of the skin of the mesh data
+    joint_matrices: RefCell<Vec<Matrix4<f32>>>,
+    primitive: Cell<MeshPrimitive>,
 
     bound_prog: RefCell<Weak<ShaderProgram>>,
 }
@@ -174,6 +398,10 @@ impl Asset for MeshBuffer {
             data: r,
             gl_state: Default::default(),
             bounds: Default::default(),
+            generation: Cell::new(0),
+            morph_weights: RefCell::new(Vec::new()),
+            joint_matrices: RefCell::new(Vec::new()),
+            primitive: Cell::new(MeshPrimitive::Triangles),
             bound_prog: RefCell::new(Weak::new()),
         })
     }
@@ -206,6 +434,11 @@ pub fn bind_buffer(
 
 impl MeshBuffer {
     pub fn update_mesh_data(&self, mesh_data: MeshData) {
+        self.swap_mesh_data(mesh_data);
+    }
+
+    /// Update the mesh data and return the old one, such that its vectors can be reused
+    pub fn swap_mesh_data(&self, mesh_data: MeshData) -> Option<MeshData> {
         let mut actions = Vec::new();
 
         match self.data.try_borrow() {
@@ -217,6 +450,10 @@ impl MeshBuffer {
                     actions.push(RebindAction::UV);
                 });
 
+                mesh_data.uvs2.as_ref().map(|_| {
+                    actions.push(RebindAction::UV2);
+                });
+
                 mesh_data.normals.as_ref().map(|_| {
                     actions.push(RebindAction::Normal);
                 });
@@ -229,11 +466,17 @@ impl MeshBuffer {
                     actions.push(RebindAction::Bitangent);
                 });
 
+                mesh_data.colors.as_ref().map(|_| {
+                    actions.push(RebindAction::Color);
+                });
+
                 actions.push(RebindAction::Indices);
             }
         };
 
-        self.data.replace(mesh_data);
+        let old = self.data.replace(mesh_data);
+        self.bounds.set(None);
+        self.generation.set(self.generation.get().wrapping_add(1));
 
         // check whether the state is ready
         match *self.gl_state.borrow_mut() {
@@ -243,6 +486,73 @@ impl MeshBuffer {
                 *self.bound_prog.borrow_mut() = Weak::new();
             }
         }
+
+        old
+    }
+
+    /// Set the weights of the morph targets, the vertices and the normals are blended
+    /// when the buffer is prepared. The buffer is morphed in place, so the objects
+    /// of different weights need their own buffers
+    pub fn set_morph_weights(&self, weights: &[f32]) {
+        if *self.morph_weights.borrow() == weights {
+            return;
+        }
+
+        self.morph_weights.replace(weights.to_vec());
+
+        if let Some(ref mut state) = *self.gl_state.borrow_mut() {
+            state.rebind_actions.push(RebindAction::Vertices);
+            if state.nb.is_some() {
+                state.rebind_actions.push(RebindAction::Normal);
+            }
+        }
+    }
+
+    pub fn morph_weights(&self) -> Ref<Vec<f32>> {
+        self.morph_weights.borrow()
+    }
+
+    /// Set the matrices of the joints of the skin, from the bind pose to the local space
+    /// of the mesh, see `Skin::joint_matrices`. The vertices and the normals are skinned
+    /// when the buffer is prepared, in place like the morph targets
+    pub fn set_joint_matrices(&self, matrices: &[Matrix4<f32>]) {
+        if *self.joint_matrices.borrow() == matrices {
+            return;
+        }
+
+        self.joint_matrices.replace(matrices.to_vec());
+
+        if let Some(ref mut state) = *self.gl_state.borrow_mut() {
+            state.rebind_actions.push(RebindAction::Vertices);
+            if state.nb.is_some() {
+                state.rebind_actions.push(RebindAction::Normal);
+            }
+        }
+    }
+
+    pub fn primitive(&self) -> MeshPrimitive {
+        self.primitive.get()
+    }
+
+    pub fn set_primitive(&self, primitive: MeshPrimitive) {
+        self.primitive.set(primitive);
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation.get()
+    }
+
+    pub fn mesh_data(&self) -> AssetResult<Ref<MeshData>> {
+        self.data.try_borrow()
+    }
+
+    /// Whether `mesh_data` can replace the data of the buffer,
+    /// the buffers of the optional attributes are created only when the buffer is prepared
+    pub fn is_same_layout(&self, mesh_data: &MeshData) -> bool {
+        match self.data.try_borrow() {
+            Ok(data) => data.is_same_layout(mesh_data),
+            Err(_) => false,
+        }
     }
 
     pub fn prepare(&self, gl: &WebGLRenderingContext) -> AssetResult<()> {
@@ -254,20 +564,30 @@ impl MeshBuffer {
                 let rebind_actions = state.rebind_actions.drain(..).collect();
 
                 // Rebind the mesh
-                state.rebind(&rebind_actions, &data, gl);
+                state.rebind(
+                    &rebind_actions,
+                    &data,
+                    &self.morph_weights.borrow(),
+                    &self.joint_matrices.borrow(),
+                    gl,
+                );
             }
 
             return Ok(());
         }
 
         let data = self.data.try_borrow()?;
+        let weights = self.morph_weights.borrow();
+        let joint_matrices = self.joint_matrices.borrow();
 
         self.gl_state.replace(Some(mesh_bind_buffer(
-            &data.vertices,
+            &data.deformed_vertices(&weights, &joint_matrices),
             &data.uvs,
-            &data.normals,
+            &data.uvs2,
+            &data.deformed_normals(&weights, &joint_matrices),
             &data.tangents,
             &data.bitangents,
+            &data.colors,
             &data.indices,
             gl,
         )));
@@ -302,7 +622,8 @@ impl MeshBuffer {
         /*======= Associating shaders to buffer objects =======*/
         gl.bind_vertex_array(&state.vao);
 
-        if gl.is_webgl2 {
+        // The vertex array keeps the attributes bound before
+        if vertex_array_objects() {
             if let Some(_) = self.bound_prog.borrow().upgrade() {
                 return Ok(());
             }
@@ -322,6 +643,11 @@ impl MeshBuffer {
             bind_buffer(gl, uvb, ShaderAttrib::UV0 as u32, AttributeSize::Two);
         }
 
+        // "aTextureCoord2"
+        if let Some(ref uv2b) = state.uv2b {
+            bind_buffer(gl, uv2b, ShaderAttrib::UV1 as u32, AttributeSize::Two);
+        }
+
         // "aVertexNormal"
         if let Some(ref nb) = state.nb {
             bind_buffer(gl, nb, ShaderAttrib::Normal as u32, AttributeSize::Three);
@@ -342,6 +668,11 @@ impl MeshBuffer {
             );
         }
 
+        // "aVertexColor"
+        if let Some(ref cb) = state.cb {
+            bind_buffer(gl, cb, ShaderAttrib::Color as u32, AttributeSize::Four);
+        }
+
         // Bind index buffer object
         gl.bind_buffer(BufferKind::ElementArray, &state.ib);
 
@@ -350,11 +681,34 @@ impl MeshBuffer {
         Ok(())
     }
 
+    fn gl_primitives(&self) -> Primitives {
+        match self.primitive.get() {
+            MeshPrimitive::Triangles => Primitives::Triangles,
+            MeshPrimitive::Lines => Primitives::Lines,
+        }
+    }
+
     #[cfg_attr(feature = "flame_it", flame)]
     pub fn render(&self, gl: &WebGLRenderingContext) {
         let data = self.data.try_borrow().unwrap();
 
-        gl.draw_elements(Primitives::Triangles, data.indices.len(), DataType::U16, 0);
+        gl.draw_elements(self.gl_primitives(), data.indices.len(), DataType::U16, 0);
+    }
+
+    /// Draw `count` instances of the buffer in a single draw call, the attributes of the
+    /// instances are bound by `MeshInstances::bind`
+    #[cfg(feature = "instanced_arrays")]
+    #[cfg_attr(feature = "flame_it", flame)]
+    pub fn render_instanced(&self, gl: &WebGLRenderingContext, count: usize) {
+        let data = self.data.try_borrow().unwrap();
+
+        gl.draw_elements_instanced(
+            self.gl_primitives(),
+            data.indices.len(),
+            DataType::U16,
+            0,
+            count,
+        );
     }
 
     pub fn unbind(&self, _gl: &WebGLRenderingContext) {
@@ -366,30 +720,18 @@ impl MeshBuffer {
     }
 }
 
-fn bind_f32_array(gl: &WebGLRenderingContext, data: &Vec<f32>) -> WebGLBuffer {
-    // Create an empty buffer object to store vertex buffer
-    let vb = gl.create_buffer();
-    {
-        // Bind appropriate array buffer to it
-        gl.bind_buffer(BufferKind::Array, &vb);
-
-        // Pass the vertex data to the buffer
-        let cv = data.clone();
-        gl.buffer_data(BufferKind::Array, &cv.into_bytes(), DrawMode::Static);
-
-        // Unbind the buffer
-        gl.unbind_buffer(BufferKind::Array);
-    }
-
-    vb
+pub fn bind_f32_array(gl: &WebGLRenderingContext, data: &Vec<f32>) -> WebGLBuffer {
+    GLBackend::new(gl).create_buffer(BufferUsage::Vertex, &data.clone().into_bytes())
 }
 
 fn mesh_bind_buffer(
     vertices: &Vec<f32>,
     uvs: &Option<Vec<f32>>,
+    uvs2: &Option<Vec<f32>>,
     normals: &Option<Vec<f32>>,
     tangents: &Option<Vec<f32>>,
     bitangents: &Option<Vec<f32>>,
+    colors: &Option<Vec<f32>>,
     indices: &Vec<u16>,
     gl: &WebGLRenderingContext,
 ) -> MeshGLState {
@@ -399,32 +741,25 @@ fn mesh_bind_buffer(
 
     let vertex_buffer = bind_f32_array(&gl, vertices);
     let uv_buffer = uvs.as_ref().map(|data| bind_f32_array(gl, data));
+    let uv2_buffer = uvs2.as_ref().map(|data| bind_f32_array(gl, data));
     let normal_buffer = normals.as_ref().map(|data| bind_f32_array(gl, data));
     let tangent_buffer = tangents.as_ref().map(|data| bind_f32_array(gl, data));
     let bitangent_buffer = bitangents.as_ref().map(|data| bind_f32_array(gl, data));
+    let color_buffer = colors.as_ref().map(|data| bind_f32_array(gl, data));
 
-    // Create an empty buffer object to store Index buffer
-    let index_buffer = gl.create_buffer();
-    {
-        // Bind appropriate array buffer to it
-        gl.bind_buffer(BufferKind::ElementArray, &index_buffer);
-
-        // Pass the vertex data to the buffer
-        let ci = indices.clone();
-        gl.buffer_data(BufferKind::ElementArray, &ci.into_bytes(), DrawMode::Static);
-
-        // Unbind the buffer
-        gl.unbind_buffer(BufferKind::ElementArray);
-    }
+    let index_buffer =
+        GLBackend::new(gl).create_buffer(BufferUsage::Index, &indices.clone().into_bytes());
 
     MeshGLState {
         vao,
         vb: vertex_buffer,
         uvb: uv_buffer,
+        uv2b: uv2_buffer,
 
         nb: normal_buffer,
         tb: tangent_buffer,
         btb: bitangent_buffer,
+        cb: color_buffer,
 
         ib: index_buffer,
         gl: gl.clone(),
@@ -0,0 +1,268 @@
+use engine::asset::Asset;
+use engine::render::mesh::MeshBound;
+#[cfg(feature = "instanced_arrays")]
+use engine::render::mesh_buffer::bind_f32_array;
+#[cfg(feature = "instanced_arrays")]
+use engine::render::shader_program::ShaderAttrib;
+use engine::render::{Material, MeshBuffer, MeshData, MeshSurface};
+
+use math::*;
+use std::cell::{Cell, Ref, RefCell};
+use std::rc::Rc;
+use std::u16;
+#[cfg(feature = "instanced_arrays")]
+use uni_gl::*;
+
+/// Copies of a mesh buffer at the transforms of the instances, see `Mesh::instanced`.
+///
+/// The transforms are uploaded once into a buffer of per instance matrices, bound to
+/// the `aInstanceMatrix` attribute of the shaders. The instances are then drawn in a
+/// single draw call by the `UNI_INSTANCED` variant of the program of the material.
+/// Without instanced arrays, i.e. on WebGL1 without `ANGLE_instanced_arrays` or without
+/// the `instanced_arrays` feature, which needs a uni-gl exporting them, the copies are
+/// baked in the local space of the object into batches of up to 65536
+/// vertices instead, see `batches`. With a shader which has no such attribute, e.g. the
+/// one of a shadow pass, each instance is drawn by itself
+pub struct MeshInstances {
+    surface: Rc<MeshSurface>,
+    transforms: Vec<Matrix4<f32>>,
+    /// Bounds of the instances and the generation of the mesh buffer they are from
+    bounds: Cell<Option<(u32, MeshBound)>>,
+
+    /// The buffer of the transforms, created when the instances are first drawn
+    #[cfg(feature = "instanced_arrays")]
+    gl_state: RefCell<Option<InstancesGLState>>,
+
+    /// Generation of the mesh buffer which the batches were baked from
+    baked: Cell<Option<u32>>,
+    batches: RefCell<Vec<Rc<MeshSurface>>>,
+}
+
+#[cfg(feature = "instanced_arrays")]
+struct InstancesGLState {
+    buffer: WebGLBuffer,
+    gl: WebGLRenderingContext,
+}
+
+#[cfg(feature = "instanced_arrays")]
+impl Drop for InstancesGLState {
+    fn drop(&mut self) {
+        self.gl.delete_buffer(&self.buffer);
+    }
+}
+
+/// The matrix of the normals of the model matrix `m`
+fn normal_matrix(m: &Matrix4<f32>) -> Matrix3<f32> {
+    let m3 = Matrix3::from_cols(m.x.truncate(), m.y.truncate(), m.z.truncate());
+
+    m3.invert().map(|inv| inv.transpose()).unwrap_or(m3)
+}
+
+fn extend_directions(dst: &mut Vec<f32>, src: &[f32], m: &Matrix3<f32>) {
+    for v in src.chunks(3) {
+        let d = *m * Vector3::new(v[0], v[1], v[2]);
+        let d = if d.magnitude2() > 0.0 { d.normalize() } else { d };
+        dst.extend_from_slice(&[d.x, d.y, d.z]);
+    }
+}
+
+/// The mesh data of `data` copied at each of `transforms`
+fn bake_batch(data: &MeshData, transforms: &[Matrix4<f32>]) -> MeshData {
+    let count = data.vertices.len() / 3;
+    let mut baked = MeshData::default();
+
+    for (i, m) in transforms.iter().enumerate() {
+        let base = (i * count) as u16;
+
+        for v in data.vertices.chunks(3) {
+            let p = m.transform_point(Point3::new(v[0], v[1], v[2]));
+            baked.vertices.extend_from_slice(&[p.x, p.y, p.z]);
+        }
+        baked
+            .indices
+            .extend(data.indices.iter().map(|i| base + i));
+
+        // The tangents follow the model matrix, the normals its inverse transpose
+        let tm = Matrix3::from_cols(m.x.truncate(), m.y.truncate(), m.z.truncate());
+        if let Some(ref normals) = data.normals {
+            extend_directions(
+                baked.normals.get_or_insert_with(Vec::new),
+                normals,
+                &normal_matrix(m),
+            );
+        }
+        if let Some(ref tangents) = data.tangents {
+            extend_directions(baked.tangents.get_or_insert_with(Vec::new), tangents, &tm);
+        }
+        if let Some(ref bitangents) = data.bitangents {
+            extend_directions(
+                baked.bitangents.get_or_insert_with(Vec::new),
+                bitangents,
+                &tm,
+            );
+        }
+
+        if let Some(ref uvs) = data.uvs {
+            baked
+                .uvs
+                .get_or_insert_with(Vec::new)
+                .extend_from_slice(uvs);
+        }
+        if let Some(ref uvs2) = data.uvs2 {
+            baked
+                .uvs2
+                .get_or_insert_with(Vec::new)
+                .extend_from_slice(uvs2);
+        }
+        if let Some(ref colors) = data.colors {
+            baked
+                .colors
+                .get_or_insert_with(Vec::new)
+                .extend_from_slice(colors);
+        }
+    }
+
+    baked
+}
+
+impl MeshInstances {
+    pub fn new(
+        buffer: Rc<MeshBuffer>,
+        material: Rc<Material>,
+        transforms: Vec<Matrix4<f32>>,
+    ) -> MeshInstances {
+        MeshInstances {
+            surface: Rc::new(MeshSurface {
+                buffer,
+                material,
+                params: None,
+            }),
+            transforms,
+            bounds: Cell::new(None),
+            #[cfg(feature = "instanced_arrays")]
+            gl_state: RefCell::new(None),
+            baked: Cell::new(None),
+            batches: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn buffer(&self) -> &Rc<MeshBuffer> {
+        &self.surface.buffer
+    }
+
+    pub fn material(&self) -> &Rc<Material> {
+        &self.surface.material
+    }
+
+    /// The surface which all the instances are drawn with
+    pub fn surface(&self) -> &Rc<MeshSurface> {
+        &self.surface
+    }
+
+    pub fn transforms(&self) -> &[Matrix4<f32>] {
+        &self.transforms
+    }
+
+    /// Bounds of all the instances in the local space of the object
+    pub fn bounds(&self) -> Option<MeshBound> {
+        let generation = self.surface.buffer.generation();
+        if let Some((g, bounds)) = self.bounds.get() {
+            if g == generation {
+                return Some(bounds);
+            }
+        }
+
+        let local = self.surface.buffer.bounds()?.local_aabb();
+        let bounds = self.transforms
+            .iter()
+            .fold(MeshBound::empty(), |mut acc, m| {
+                let aabb = local.transform(m);
+                acc.aabb.merge(&aabb);
+                acc.r = aabb.corners()
+                    .iter()
+                    .fold(acc.r, |r, c| r.max(c.magnitude()));
+                acc
+            });
+
+        self.bounds.set(Some((generation, bounds)));
+        Some(bounds)
+    }
+
+    /// Bind the transforms to the `aInstanceMatrix` attribute, advanced once per
+    /// instance. The buffer is uploaded by the first bind
+    #[cfg(feature = "instanced_arrays")]
+    pub fn bind(&self, gl: &WebGLRenderingContext) {
+        let mut gl_state = self.gl_state.borrow_mut();
+
+        if gl_state.is_none() {
+            let matrices: Vec<f32> = self.transforms
+                .iter()
+                .flat_map(|m| AsRef::<[f32; 16]>::as_ref(m).to_vec())
+                .collect();
+
+            *gl_state = Some(InstancesGLState {
+                buffer: bind_f32_array(gl, &matrices),
+                gl: gl.clone(),
+            });
+        }
+
+        gl.bind_buffer(BufferKind::Array, &gl_state.as_ref().unwrap().buffer);
+
+        // A mat4 attribute takes the locations of its 4 columns
+        let loc = ShaderAttrib::InstanceMatrix as u32;
+        for i in 0..4 {
+            let column = loc + i;
+            gl.enable_vertex_attrib_array(column);
+            gl.vertex_attrib_pointer(
+                column,
+                AttributeSize::Four,
+                DataType::Float,
+                false,
+                64,
+                i * 16,
+            );
+            gl.vertex_attrib_divisor(column, 1);
+        }
+    }
+
+    /// Disable the `aInstanceMatrix` attribute, which the vertex array of the mesh buffer
+    /// would keep otherwise
+    #[cfg(feature = "instanced_arrays")]
+    pub fn unbind(&self, gl: &WebGLRenderingContext) {
+        let loc = ShaderAttrib::InstanceMatrix as u32;
+        for i in 0..4 {
+            gl.vertex_attrib_divisor(loc + i, 0);
+            gl.disable_vertex_attrib_array(loc + i);
+        }
+    }
+
+    /// The surfaces of the batches of the copies baked on the cpu, for the contexts
+    /// without instanced arrays. They are baked once the mesh buffer is loaded and
+    /// again when its data is updated
+    pub fn batches(&self) -> Ref<Vec<Rc<MeshSurface>>> {
+        let generation = self.surface.buffer.generation();
+
+        if self.baked.get() != Some(generation) {
+            if let Ok(data) = self.surface.buffer.mesh_data() {
+                let count = data.vertices.len() / 3;
+                let per_batch = ((u16::MAX as usize + 1) / count.max(1)).max(1);
+
+                let batches = self.transforms
+                    .chunks(per_batch)
+                    .map(|chunk| {
+                        Rc::new(MeshSurface {
+                            buffer: MeshBuffer::new(bake_batch(&data, chunk)),
+                            material: self.surface.material.clone(),
+                            params: None,
+                        })
+                    })
+                    .collect();
+
+                self.batches.replace(batches);
+                self.baked.set(Some(generation));
+            }
+        }
+
+        self.batches.borrow()
+    }
+}
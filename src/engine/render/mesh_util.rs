@@ -30,3 +30,107 @@ impl QuadBuilder for MeshData {
         self.indices.push(self.indices.len() as u16);
     }
 }
+
+/// Quad of `size` from the origin to (w, -h), which shows the part `uv_rect`
+/// (u0, v0, u1, v1) of the texture, in fractions of the texture from its top-left
+pub fn uv_quad_mesh_data(size: (f32, f32), uv_rect: (f32, f32, f32, f32)) -> MeshData {
+    let (w, h) = size;
+    let (u0, v0, u1, v1) = uv_rect;
+
+    let vertices: Vec<f32> = vec![
+            0.0, 0.0, 0.0,     // 0
+            0.0, -h, 0.0,    // 1
+            w, -h, 0.0,     // 2
+            w, 0.0, 0.0       // 3
+        ];
+
+    let uvs: Vec<f32> = vec![
+            u0, 1.0 - v0,
+            u0, 1.0 - v1,
+            u1, 1.0 - v1,
+            u1, 1.0 - v0,
+        ];
+
+    let indices: Vec<u16> = vec![
+        0, 1, 2, 0, 2, 3
+    ];
+
+    MeshData {
+        vertices: vertices,
+        uvs: Some(uvs),
+        uvs2: None,
+        normals: None,
+        indices: indices,
+        tangents: None,
+        bitangents: None,
+        colors: None,
+        joints: None,
+        weights: None,
+        morph_targets: Vec::new(),
+    }
+}
+
+/// An empty mesh data with the same optional attributes as `layout`
+pub fn empty_like(layout: &MeshData) -> MeshData {
+    MeshData {
+        vertices: Vec::new(),
+        uvs: layout.uvs.as_ref().map(|_| Vec::new()),
+        uvs2: layout.uvs2.as_ref().map(|_| Vec::new()),
+        normals: layout.normals.as_ref().map(|_| Vec::new()),
+        tangents: layout.tangents.as_ref().map(|_| Vec::new()),
+        bitangents: layout.bitangents.as_ref().map(|_| Vec::new()),
+        colors: layout.colors.as_ref().map(|_| Vec::new()),
+        indices: Vec::new(),
+        joints: None,
+        weights: None,
+        morph_targets: Vec::new(),
+    }
+}
+
+fn append_directions(dst: &mut Option<Vec<f32>>, src: &Option<Vec<f32>>, m: &Matrix4f) {
+    if let (&mut Some(ref mut dst), &Some(ref src)) = (dst, src) {
+        for v in src.chunks(3).filter(|v| v.len() == 3) {
+            let d = m.transform_vector(Vector3::new(v[0], v[1], v[2]));
+            let d = if d.magnitude2() > 0.0 { d.normalize() } else { d };
+            add_v(dst, &d);
+        }
+    }
+}
+
+/// Append the vertices of `src` transformed by `m` and its triangles to `dst`, which
+/// should have the same layout, see `MeshData::is_same_layout`. The triangles are
+/// flipped by a mirroring matrix so that they keep facing outwards
+pub fn append_transformed(dst: &mut MeshData, src: &MeshData, m: &Matrix4f) {
+    let base = (dst.vertices.len() / 3) as u16;
+
+    for v in src.vertices.chunks(3).filter(|v| v.len() == 3) {
+        let p = m.transform_point(Point3::new(v[0], v[1], v[2]));
+        add_v(&mut dst.vertices, &p.to_vec());
+    }
+
+    let normal_m = m.invert().map(|inv| inv.transpose()).unwrap_or(*m);
+    append_directions(&mut dst.normals, &src.normals, &normal_m);
+    append_directions(&mut dst.tangents, &src.tangents, m);
+    append_directions(&mut dst.bitangents, &src.bitangents, m);
+
+    if let (&mut Some(ref mut dst), &Some(ref src)) = (&mut dst.uvs, &src.uvs) {
+        dst.extend_from_slice(src);
+    }
+    if let (&mut Some(ref mut dst), &Some(ref src)) = (&mut dst.uvs2, &src.uvs2) {
+        dst.extend_from_slice(src);
+    }
+    if let (&mut Some(ref mut dst), &Some(ref src)) = (&mut dst.colors, &src.colors) {
+        dst.extend_from_slice(src);
+    }
+
+    let flip = m.determinant() < 0.0;
+    for tri in src.indices.chunks(3).filter(|tri| tri.len() == 3) {
+        if flip {
+            dst.indices
+                .extend_from_slice(&[base + tri[0], base + tri[2], base + tri[1]]);
+        } else {
+            dst.indices
+                .extend_from_slice(&[base + tri[0], base + tri[1], base + tri[2]]);
+        }
+    }
+}
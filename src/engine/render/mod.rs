@@ -4,31 +4,111 @@ mod shader_program;
 mod texture;
 mod material;
 mod light;
+mod light_cluster;
 mod shader;
 mod uniforms;
 mod frame_buffer;
 mod render_texture;
 mod mesh_buffer;
+mod font;
+mod ui_batch;
+mod standard_material;
+mod skybox;
+mod post_effect;
+mod mesh_instances;
+mod sprite;
+mod caps;
+mod lod_group;
+mod occlusion;
+mod light_probe;
+mod lightmap;
+mod environment;
+mod atlas;
+mod skin;
+mod backend;
+#[cfg(feature = "timer_query")]
+mod gpu_timer;
 
+use std::ops::{Add, Sub};
+
+/// Queue of a material, the queues are drawn in ascending order.
+///
+/// A queue could be offset from one of the named queues to order some materials
+/// between them, e.g. `RenderQueue::Transparent + 10` is drawn after the transparent
+/// queue. It is sorted and drawn like the named queue within 500 of it
 #[derive(Hash, Eq, Ord, PartialOrd, PartialEq, Copy, Clone, Debug)]
-pub enum RenderQueue {
-    Opaque = 1000,
-    Skybox = 2000,
-    Transparent = 3000,
-    UI = 5000,
+pub struct RenderQueue(pub i32);
+
+#[allow(non_upper_case_globals)]
+impl RenderQueue {
+    pub const Opaque: RenderQueue = RenderQueue(1000);
+    pub const Skybox: RenderQueue = RenderQueue(2000);
+    pub const Transparent: RenderQueue = RenderQueue(3000);
+    pub const Sprite: RenderQueue = RenderQueue(4000);
+    pub const UI: RenderQueue = RenderQueue(5000);
+
+    /// The named queue which this queue is sorted and drawn like
+    pub fn base(self) -> RenderQueue {
+        match self.0 {
+            n if n < 1500 => RenderQueue::Opaque,
+            n if n < 2500 => RenderQueue::Skybox,
+            n if n < 3500 => RenderQueue::Transparent,
+            n if n < 4500 => RenderQueue::Sprite,
+            _ => RenderQueue::UI,
+        }
+    }
+}
+
+impl Add<i32> for RenderQueue {
+    type Output = RenderQueue;
+
+    fn add(self, offset: i32) -> RenderQueue {
+        RenderQueue(self.0 + offset)
+    }
+}
+
+impl Sub<i32> for RenderQueue {
+    type Output = RenderQueue;
+
+    fn sub(self, offset: i32) -> RenderQueue {
+        RenderQueue(self.0 - offset)
+    }
 }
 
 pub mod mesh_util;
+pub mod debug;
 
-pub use self::camera::{Camera, Frustum};
+pub use self::backend::{BufferUsage, GLBackend, RenderBackend};
+pub use self::camera::{Antialiasing, Camera, ClearFlags, Frustum};
+pub use self::caps::{CompressedFormat, RenderCaps};
+pub(crate) use self::caps::{set_srgb_textures, set_vertex_array_objects, srgb_textures,
+                            vertex_array_objects};
+#[cfg(feature = "timer_query")]
+pub(crate) use self::gpu_timer::GpuTimer;
 pub use self::shader::{PreprocessedShaderCode, Shader, ShaderFs, ShaderKind, ShaderKindFs,
                        ShaderKindProvider, ShaderKindVs, ShaderVs};
 pub use self::shader_program::ShaderProgram;
 pub use self::texture::{Texture, TextureAsset, TextureAttachment, TextureFiltering, TextureImage,
                         TextureWrap};
 pub use self::mesh::{Mesh, MeshSurface};
-pub use self::mesh_buffer::{MeshBuffer, MeshData};
-pub use self::material::{CullMode, DepthTest, Material, MaterialParam, MaterialParamMap,
-                         MaterialState};
-pub use self::light::{DirectionalLight, Light, PointLight};
-pub use self::render_texture::RenderTexture;
+pub use self::mesh_instances::MeshInstances;
+pub use self::sprite::Sprite;
+pub use self::lod_group::{LodGroup, LodLevel};
+pub use self::occlusion::{OcclusionBuffer, OCCLUSION_BUFFER_WIDTH};
+pub use self::light_probe::{LightProbe, LightProbeGroup, SphericalHarmonics};
+pub use self::lightmap::LightmapBaker;
+pub use self::environment::{EnvironmentSettings, Fog, FogMode, HeightFog};
+pub use self::mesh_buffer::{MeshBuffer, MeshData, MeshPrimitive, MorphTarget};
+pub use self::skin::Skin;
+pub use self::material::{BlendEquation, BlendFactor, BlendMode, CullMode, DepthTest, Material,
+                         MaterialParam, MaterialParamMap, MaterialParams, MaterialState};
+pub use self::light::{DirectionalLight, Light, LightStageUniforms, LightUniforms, PointLight,
+                      SpotLight, MAX_POINT_LIGHTS, MAX_SPOT_LIGHTS};
+pub use self::light_cluster::LightClusters;
+pub use self::render_texture::{RenderTexture, RenderTextureFormat};
+pub use self::font::{Font, GlyphQuad};
+pub use self::atlas::{AtlasPacker, AtlasRegion, TextureAtlas};
+pub use self::ui_batch::UiBatcher;
+pub use self::standard_material::StandardMaterial;
+pub use self::skybox::Skybox;
+pub use self::post_effect::{PostEffect, PostPass, PostProcess};
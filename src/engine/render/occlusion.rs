@@ -0,0 +1,147 @@
+use engine::core::Aabb;
+
+use math::*;
+
+/// Width in pixels of the depth buffer of the occluders, its height follows the aspect
+pub const OCCLUSION_BUFFER_WIDTH: u32 = 256;
+
+/// A small depth buffer of the occluders rasterized on the cpu, in which the bounds
+/// of the other objects are tested before they are drawn, see `Camera::occlusion_culling`.
+///
+/// The occluders are rasterized at the pixel centers and the bounds are tested one
+/// pixel wider than they are, so that an object is not hidden at the edges of the
+/// occluders by the low resolution
+pub struct OcclusionBuffer {
+    width: u32,
+    height: u32,
+    /// Depth in normalized device coordinates of the nearest occluder of each pixel
+    depth: Vec<f32>,
+    pv_m: Matrix4<f32>,
+}
+
+/// A point in the pixels of the buffer with its depth, None behind the eye
+fn project(pv_m: &Matrix4<f32>, p: Vector3<f32>, size: (u32, u32)) -> Option<Vector3<f32>> {
+    let c = pv_m * p.extend(1.0);
+    if c.w <= 1e-5 {
+        return None;
+    }
+
+    Some(Vector3::new(
+        (c.x / c.w * 0.5 + 0.5) * size.0 as f32,
+        (c.y / c.w * 0.5 + 0.5) * size.1 as f32,
+        c.z / c.w,
+    ))
+}
+
+fn edge(a: &Vector3<f32>, b: &Vector3<f32>, p: (f32, f32)) -> f32 {
+    (b.x - a.x) * (p.1 - a.y) - (b.y - a.y) * (p.0 - a.x)
+}
+
+impl OcclusionBuffer {
+    /// An empty buffer for the projection and view matrix `pv_m` of a camera
+    pub fn new(screen_size: (u32, u32), pv_m: Matrix4<f32>) -> OcclusionBuffer {
+        let width = OCCLUSION_BUFFER_WIDTH;
+        let height = (width * screen_size.1.max(1) / screen_size.0.max(1)).max(1);
+
+        OcclusionBuffer {
+            width,
+            height,
+            depth: vec![::std::f32::INFINITY; (width * height) as usize],
+            pv_m,
+        }
+    }
+
+    /// Rasterize the triangles of a mesh in the world by its model matrix
+    pub fn draw_mesh(&mut self, model_m: &Matrix4<f32>, vertices: &[f32], indices: &[u16]) {
+        let m = self.pv_m * model_m;
+        let size = (self.width, self.height);
+
+        let points: Vec<Option<Vector3<f32>>> = vertices
+            .chunks(3)
+            .filter(|v| v.len() == 3)
+            .map(|v| project(&m, Vector3::new(v[0], v[1], v[2]), size))
+            .collect();
+
+        for tri in indices.chunks(3).filter(|tri| tri.len() == 3) {
+            let p = |i: u16| points.get(i as usize).cloned().and_then(|p| p);
+
+            // The triangles crossing the near plane are skipped, which only makes
+            // the occluders smaller
+            if let (Some(a), Some(b), Some(c)) = (p(tri[0]), p(tri[1]), p(tri[2])) {
+                self.draw_triangle(&a, &b, &c);
+            }
+        }
+    }
+
+    /// Rasterize a triangle of either winding
+    fn draw_triangle(&mut self, a: &Vector3<f32>, b: &Vector3<f32>, c: &Vector3<f32>) {
+        let area = edge(a, b, (c.x, c.y));
+        if area.abs() < 1e-6 {
+            return;
+        }
+
+        let (w, h) = (self.width as f32, self.height as f32);
+        let min_x = a.x.min(b.x).min(c.x).max(0.0).min(w) as u32;
+        let max_x = a.x.max(b.x).max(c.x).ceil().max(0.0).min(w) as u32;
+        let min_y = a.y.min(b.y).min(c.y).max(0.0).min(h) as u32;
+        let max_y = a.y.max(b.y).max(c.y).ceil().max(0.0).min(h) as u32;
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let p = (x as f32 + 0.5, y as f32 + 0.5);
+
+                let w0 = edge(b, c, p) / area;
+                let w1 = edge(c, a, p) / area;
+                let w2 = edge(a, b, p) / area;
+                if w0 < 0.0 || w1 < 0.0 || w2 < 0.0 {
+                    continue;
+                }
+
+                let z = w0 * a.z + w1 * b.z + w2 * c.z;
+                let d = &mut self.depth[(y * self.width + x) as usize];
+                if z < *d {
+                    *d = z;
+                }
+            }
+        }
+    }
+
+    /// Whether any part of a world space aabb could be in front of the occluders
+    pub fn is_visible(&self, aabb: &Aabb) -> bool {
+        let size = (self.width, self.height);
+
+        let mut min = Vector3::new(::std::f32::MAX, ::std::f32::MAX, ::std::f32::MAX);
+        let mut max = Vector3::new(::std::f32::MIN, ::std::f32::MIN, ::std::f32::MIN);
+
+        for corner in aabb.corners().iter() {
+            let p = match project(&self.pv_m, *corner, size) {
+                Some(p) => p,
+                None => return true,
+            };
+
+            min = Vector3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+            max = Vector3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+        }
+
+        let (w, h) = (self.width as f32, self.height as f32);
+        if max.x < 0.0 || max.y < 0.0 || min.x > w || min.y > h {
+            // Out of the view, which the frustum culling decides
+            return true;
+        }
+
+        let min_x = (min.x - 1.0).max(0.0) as u32;
+        let max_x = (max.x + 1.0).ceil().max(0.0).min(w) as u32;
+        let min_y = (min.y - 1.0).max(0.0) as u32;
+        let max_y = (max.y + 1.0).ceil().max(0.0).min(h) as u32;
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                if self.depth[(y * self.width + x) as usize] >= min.z {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+}
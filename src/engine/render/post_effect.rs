@@ -0,0 +1,281 @@
+use engine::asset::AssetSystem;
+use engine::render::{Material, MeshBuffer, RenderTexture, RenderTextureFormat, Texture};
+
+use math::*;
+use std::cell::Cell;
+use std::rc::Rc;
+use uni_app::now;
+
+/// Size of the first target of the luminance chain of the auto exposure,
+/// which is downsampled to a single pixel
+const LUMINANCE_SIZE: u32 = 64;
+
+/// A fullscreen pass of the post-processing stack of a camera, see `Camera::post_effects`.
+///
+/// The effects are applied in order to the image of the scene, the UI is drawn after them.
+#[derive(Clone)]
+pub enum PostEffect {
+    /// Add the blurred parts of the image brighter than `threshold` (0..1)
+    Bloom { threshold: f32, intensity: f32 },
+    /// ACES filmic tone mapping of the exposed image
+    Tonemap { exposure: f32 },
+    /// ACES filmic tone mapping of the image exposed by its average luminance, which
+    /// adapts over time like the eye. `key` is the luminance which the average is exposed
+    /// to, `speed` the rate of the adaptation per second
+    AutoExposure {
+        key: f32,
+        speed: f32,
+        min_exposure: f32,
+        max_exposure: f32,
+    },
+    /// Darken the borders of the image, `smoothness` (0..1) is the width of the falloff
+    Vignette { intensity: f32, smoothness: f32 },
+    /// Fast approximate anti-aliasing
+    Fxaa,
+    /// A custom fullscreen material of the "screen_quad" mesh, the image of the previous
+    /// pass is set to `uScreen` and its size in pixels to `uScreenSize`
+    Custom(Rc<Material>),
+}
+
+impl PostEffect {
+    pub fn bloom() -> PostEffect {
+        PostEffect::Bloom {
+            threshold: 0.8,
+            intensity: 1.0,
+        }
+    }
+
+    pub fn tonemap() -> PostEffect {
+        PostEffect::Tonemap { exposure: 1.0 }
+    }
+
+    pub fn auto_exposure() -> PostEffect {
+        PostEffect::AutoExposure {
+            key: 0.18,
+            speed: 1.5,
+            min_exposure: 0.1,
+            max_exposure: 10.0,
+        }
+    }
+
+    pub fn vignette() -> PostEffect {
+        PostEffect::Vignette {
+            intensity: 0.5,
+            smoothness: 0.5,
+        }
+    }
+}
+
+/// A fullscreen draw of a post effect, to `target` or to the output of the effect if None
+pub struct PostPass<'a> {
+    pub target: Option<&'a RenderTexture>,
+    pub material: Rc<Material>,
+}
+
+/// The render targets and the materials of the post effects of a viewport size
+pub struct PostProcess {
+    size: (u32, u32),
+    /// The scene is rendered in it before the effects
+    pub scene: RenderTexture,
+    /// The effects are applied back and forth between these targets
+    swap: [RenderTexture; 2],
+    /// Half sized targets of the bloom
+    bloom: [RenderTexture; 2],
+    /// The luminance of the image downsampled from `LUMINANCE_SIZE` to 1 pixel
+    luminance: Vec<RenderTexture>,
+    /// The adapted luminance of the last frame and of the current one, swapped each frame
+    adapted: [RenderTexture; 2],
+    adapted_frame: Cell<usize>,
+    adapted_time: Cell<Option<f64>>,
+
+    pub quad: Rc<MeshBuffer>,
+
+    threshold: Rc<Material>,
+    blur_h: Rc<Material>,
+    blur_v: Rc<Material>,
+    bloom_composite: Rc<Material>,
+    tonemap: Rc<Material>,
+    vignette: Rc<Material>,
+    fxaa: Rc<Material>,
+    luminance_pass: Rc<Material>,
+    downsample: Vec<Rc<Material>>,
+    adapt: Rc<Material>,
+    auto_exposure: Rc<Material>,
+}
+
+impl PostProcess {
+    /// The targets are floating point ones for a `hdr` scene
+    pub fn new(asys: &AssetSystem, size: (u32, u32), hdr: bool) -> PostProcess {
+        let (color, color_depth) = if hdr {
+            (RenderTextureFormat::HdrColor, RenderTextureFormat::HdrColorDepth)
+        } else {
+            (RenderTextureFormat::Color, RenderTextureFormat::ColorDepth)
+        };
+        let color_target = |(w, h): (u32, u32)| RenderTexture::new(w.max(1), h.max(1), color);
+        let half = ((size.0 / 2).max(1), (size.1 / 2).max(1));
+        let material = |name: &str| Rc::new(Material::new(asys.new_program(name)));
+
+        let mut luminance = vec![color_target((LUMINANCE_SIZE, LUMINANCE_SIZE))];
+        let mut lum_size = LUMINANCE_SIZE;
+        while lum_size > 1 {
+            lum_size /= 2;
+            luminance.push(color_target((lum_size, lum_size)));
+        }
+
+        PostProcess {
+            size,
+            scene: RenderTexture::new(size.0.max(1), size.1.max(1), color_depth),
+            swap: [color_target(size), color_target(size)],
+            bloom: [color_target(half), color_target(half)],
+            downsample: (1..luminance.len())
+                .map(|_| material("default_post_downsample"))
+                .collect(),
+            luminance,
+            adapted: [color_target((1, 1)), color_target((1, 1))],
+            adapted_frame: Cell::new(0),
+            adapted_time: Cell::new(None),
+
+            quad: asys.new_mesh_buffer("screen_quad"),
+
+            threshold: material("default_post_threshold"),
+            blur_h: material("default_post_blur"),
+            blur_v: material("default_post_blur"),
+            bloom_composite: material("default_post_bloom"),
+            tonemap: material("default_post_tonemap"),
+            vignette: material("default_post_vignette"),
+            fxaa: material("default_post_fxaa"),
+            luminance_pass: material("default_post_luminance"),
+            adapt: material("default_post_adapt"),
+            auto_exposure: material("default_post_auto_exposure"),
+        }
+    }
+
+    /// The target of the effect `i` of the stack, unless it is the last one
+    pub fn target(&self, i: usize) -> &RenderTexture {
+        &self.swap[i % 2]
+    }
+
+    /// The passes of `effect` applied to `src`
+    pub fn passes(&self, effect: &PostEffect, src: &Rc<Texture>) -> Vec<PostPass> {
+        let size = Vector2::new(self.size.0 as f32, self.size.1 as f32);
+
+        let output = |material: &Rc<Material>| {
+            material.set("uScreen", src.clone());
+            material.set("uScreenSize", size);
+            PostPass {
+                target: None,
+                material: material.clone(),
+            }
+        };
+
+        match effect {
+            &PostEffect::Bloom {
+                threshold,
+                intensity,
+            } => {
+                let half_size = Vector2::new(
+                    (self.size.0 / 2).max(1) as f32,
+                    (self.size.1 / 2).max(1) as f32,
+                );
+
+                self.threshold.set("uScreen", src.clone());
+                self.threshold.set("uThreshold", threshold);
+
+                self.blur_h.set("uScreen", self.bloom[0].as_texture());
+                self.blur_h.set("uScreenSize", half_size);
+                self.blur_h.set("uDirection", Vector2::new(1.0f32, 0.0));
+
+                self.blur_v.set("uScreen", self.bloom[1].as_texture());
+                self.blur_v.set("uScreenSize", half_size);
+                self.blur_v.set("uDirection", Vector2::new(0.0f32, 1.0));
+
+                self.bloom_composite
+                    .set("uBloom", self.bloom[0].as_texture());
+                self.bloom_composite.set("uIntensity", intensity);
+
+                vec![
+                    PostPass {
+                        target: Some(&self.bloom[0]),
+                        material: self.threshold.clone(),
+                    },
+                    PostPass {
+                        target: Some(&self.bloom[1]),
+                        material: self.blur_h.clone(),
+                    },
+                    PostPass {
+                        target: Some(&self.bloom[0]),
+                        material: self.blur_v.clone(),
+                    },
+                    output(&self.bloom_composite),
+                ]
+            }
+            &PostEffect::Tonemap { exposure } => {
+                self.tonemap.set("uExposure", exposure);
+                vec![output(&self.tonemap)]
+            }
+            &PostEffect::AutoExposure {
+                key,
+                speed,
+                min_exposure,
+                max_exposure,
+            } => {
+                let mut passes = Vec::new();
+
+                self.luminance_pass.set("uScreen", src.clone());
+                passes.push(PostPass {
+                    target: Some(&self.luminance[0]),
+                    material: self.luminance_pass.clone(),
+                });
+
+                for (i, material) in self.downsample.iter().enumerate() {
+                    let (w, h) = self.luminance[i].size();
+                    material.set("uScreen", self.luminance[i].as_texture());
+                    material.set("uScreenSize", Vector2::new(w as f32, h as f32));
+                    passes.push(PostPass {
+                        target: Some(&self.luminance[i + 1]),
+                        material: material.clone(),
+                    });
+                }
+
+                // The first frame is not adapted from anything
+                let t = now();
+                let rate = match self.adapted_time.replace(Some(t)) {
+                    Some(last) => 1.0 - (-((t - last) as f32).max(0.0) * speed).exp(),
+                    None => 1.0,
+                };
+
+                let frame = self.adapted_frame.get();
+                self.adapted_frame.set(frame + 1);
+                let (last, current) = (&self.adapted[frame % 2], &self.adapted[(frame + 1) % 2]);
+
+                self.adapt
+                    .set("uScreen", self.luminance.last().unwrap().as_texture());
+                self.adapt.set("uAdapted", last.as_texture());
+                self.adapt.set("uRate", rate);
+                passes.push(PostPass {
+                    target: Some(current),
+                    material: self.adapt.clone(),
+                });
+
+                self.auto_exposure.set("uLuminance", current.as_texture());
+                self.auto_exposure.set("uKey", key);
+                self.auto_exposure.set("uMinExposure", min_exposure);
+                self.auto_exposure.set("uMaxExposure", max_exposure);
+                passes.push(output(&self.auto_exposure));
+
+                passes
+            }
+            &PostEffect::Vignette {
+                intensity,
+                smoothness,
+            } => {
+                self.vignette.set("uIntensity", intensity);
+                self.vignette
+                    .set("uSmoothness", smoothness.max(0.001).min(1.0));
+                vec![output(&self.vignette)]
+            }
+            &PostEffect::Fxaa => vec![output(&self.fxaa)],
+            &PostEffect::Custom(ref material) => vec![output(material)],
+        }
+    }
+}
@@ -4,7 +4,37 @@ use std::ops::Deref;
 use engine::render::frame_buffer::FrameBuffer;
 use uni_gl::WebGLRenderingContext;
 
-pub struct RenderTexture(FrameBuffer);
+/// Format of the images of a `RenderTexture`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RenderTextureFormat {
+    /// Rgba color
+    Color,
+    /// Rgba color with a depth texture, to render a scene with depth testing
+    ColorDepth,
+    /// Depth only, like the shadow maps
+    Depth,
+    /// Floating point rgba color, which keeps the colors above 1 of a hdr scene.
+    /// Only if `RenderCaps::float_render_targets` is supported
+    HdrColor,
+    /// Floating point rgba color with a depth texture
+    HdrColorDepth,
+}
+
+impl From<TextureAttachment> for RenderTextureFormat {
+    fn from(attach: TextureAttachment) -> RenderTextureFormat {
+        match attach {
+            TextureAttachment::Color0 => RenderTextureFormat::Color,
+            TextureAttachment::FloatColor0 => RenderTextureFormat::HdrColor,
+            TextureAttachment::Depth => RenderTextureFormat::Depth,
+        }
+    }
+}
+
+/// A texture rendered by a camera, see `Camera::render_texture`.
+///
+/// It derefs to its color (or depth) `Texture`, which could be set to any material
+/// for mirrors, minimaps or previews.
+pub struct RenderTexture(FrameBuffer, RenderTextureFormat);
 
 impl Deref for RenderTexture {
     type Target = Rc<Texture>;
@@ -15,8 +45,40 @@ impl Deref for RenderTexture {
 }
 
 impl RenderTexture {
-    pub fn new(width: u32, height: u32, attach: TextureAttachment) -> RenderTexture {
-        RenderTexture(FrameBuffer::new(width, height, attach))
+    pub fn new<F>(width: u32, height: u32, format: F) -> RenderTexture
+    where
+        F: Into<RenderTextureFormat>,
+    {
+        let format = format.into();
+        let fb = match format {
+            RenderTextureFormat::Color => FrameBuffer::new(width, height, TextureAttachment::Color0),
+            RenderTextureFormat::ColorDepth => {
+                FrameBuffer::new_with_depth(width, height, TextureAttachment::Color0)
+            }
+            RenderTextureFormat::Depth => FrameBuffer::new(width, height, TextureAttachment::Depth),
+            RenderTextureFormat::HdrColor => {
+                FrameBuffer::new(width, height, TextureAttachment::FloatColor0)
+            }
+            RenderTextureFormat::HdrColorDepth => {
+                FrameBuffer::new_with_depth(width, height, TextureAttachment::FloatColor0)
+            }
+        };
+
+        RenderTexture(fb, format)
+    }
+
+    pub fn format(&self) -> RenderTextureFormat {
+        self.1
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.0.texture.image_size().unwrap()
+    }
+
+    /// Resize the render texture, e.g. to follow the screen size. The materials
+    /// keep using it, its images are created again in next bind
+    pub fn resize(&self, width: u32, height: u32) {
+        self.0.resize(width.max(1), height.max(1));
     }
 
     pub fn bind_frame_buffer(&self, gl: &WebGLRenderingContext) {
@@ -5,6 +5,7 @@ use uni_glsl::preprocessor::PreprocessError;
 // use uni_glsl::TypeQualifier;
 // use uni_glsl::query::*;
 
+use engine::render::srgb_textures;
 use uni_gl;
 use std::collections::HashMap;
 use std::marker::PhantomData;
@@ -36,17 +37,35 @@ impl ShaderKindProvider for ShaderKindFs {
 }
 
 #[derive(Debug)]
-pub struct PreprocessedShaderCode(String);
+pub struct PreprocessedShaderCode {
+    code: String,
+    kind: ShaderKind,
+    /// The source and the included files which the code was preprocessed from,
+    /// kept for the keyword variants
+    source: String,
+    external_files: HashMap<String, String>,
+}
 
 impl PreprocessedShaderCode {
     pub fn as_string(&self) -> &String {
-        &self.0
+        &self.code
     }
 
     pub fn new(
         kind: ShaderKind,
         s: &str,
         external_files: &HashMap<String, String>,
+    ) -> Result<PreprocessedShaderCode, PreprocessError> {
+        Self::with_keywords(kind, s, external_files, &[])
+    }
+
+    /// Preprocess `s` with each of `keywords` defined, e.g. `SKINNED` for
+    /// `#ifdef SKINNED` blocks of the shader
+    pub fn with_keywords(
+        kind: ShaderKind,
+        s: &str,
+        external_files: &HashMap<String, String>,
+        keywords: &[String],
     ) -> Result<PreprocessedShaderCode, PreprocessError> {
         let prefix = match kind {
             ShaderKind::Vertex => if !uni_gl::IS_GL_ES {
@@ -74,10 +93,29 @@ impl PreprocessedShaderCode {
         if uni_gl::IS_GL_ES {
             predefs.insert("GL_ES".to_string(), "".to_string());
         }
+        if srgb_textures() {
+            predefs.insert("UNI_SRGB_TEXTURES".to_string(), "".to_string());
+        }
+        for keyword in keywords.iter() {
+            predefs.insert(keyword.clone(), "".to_string());
+        }
 
         let processed = preprocessor::preprocess(&s, &predefs, external_files);
 
-        processed.map(|s| PreprocessedShaderCode(prefix + &s))
+        processed.map(|code| PreprocessedShaderCode {
+            code: prefix + &code,
+            kind,
+            source: s.to_owned(),
+            external_files: external_files.clone(),
+        })
+    }
+
+    /// The same source preprocessed with `keywords` defined.
+    ///
+    /// Only the files included by the code without the keywords are known, so a file
+    /// included only under a keyword should also be included by another variant
+    pub fn variant(&self, keywords: &[String]) -> Result<PreprocessedShaderCode, PreprocessError> {
+        Self::with_keywords(self.kind, &self.source, &self.external_files, keywords)
     }
 }
 
@@ -107,6 +145,15 @@ where
         }
     }
 
+    /// The shader preprocessed again with `keywords` defined
+    pub fn variant(&self, keywords: &[String]) -> Result<Shader<T>, PreprocessError> {
+        Ok(Shader {
+            filename: self.filename.clone(),
+            code: self.code.variant(keywords)?,
+            phantom: PhantomData,
+        })
+    }
+
     pub fn from_preprocessed(filename: &str, code: PreprocessedShaderCode) -> Shader<T> {
         uni_gl::print(&format!("preprocessing {}...\n", filename));
 
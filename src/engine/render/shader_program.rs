@@ -1,13 +1,18 @@
-use engine::asset::{Asset, AssetResult, AssetSystem, FileFuture, LoadableAsset, Resource};
+use engine::asset::{Asset, AssetError, AssetResult, AssetSystem, FileFuture, LoadState,
+                    LoadableAsset, Resource};
 use engine::render::shader::{ShaderFs, ShaderVs};
 use engine::render::uniforms::*;
+use engine::render::{GLBackend, RenderBackend};
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
-use uni_gl::{ShaderKind as WebGLShaderKind, WebGLProgram, WebGLRenderingContext};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use uni_gl::{WebGLProgram, WebGLRenderingContext};
+use uni_glsl::preprocessor::PreprocessError;
 
 use std::borrow::Cow;
 
+use futures::Async;
 use uni_app;
 
 pub enum ShaderAttrib {
@@ -16,13 +21,21 @@ pub enum ShaderAttrib {
     Normal = 2,
     Tangent = 3,
     Bitangent = 4,
+    Color = 5,
+    UV1 = 6,
+    /// A mat4 of the instances, which takes the locations 7 to 10, see `MeshInstances`
+    InstanceMatrix = 7,
 }
 
+/// The id of the next program, see `ShaderProgram::id`
+static NEXT_PROGRAM_ID: AtomicUsize = AtomicUsize::new(0);
+
 impl Asset for ShaderProgram {
     type Resource = (Resource<ShaderVs>, Resource<ShaderFs>);
 
     fn new_from_resource((vs, fs): Self::Resource) -> Rc<ShaderProgram> {
         Rc::new(ShaderProgram {
+            id: NEXT_PROGRAM_ID.fetch_add(1, Ordering::Relaxed),
             gl_state: RefCell::new(None),
 
             coord_map: Default::default(),
@@ -30,6 +43,8 @@ impl Asset for ShaderProgram {
 
             vs_shader: vs,
             fs_shader: fs,
+
+            variants: Default::default(),
         })
     }
 }
@@ -53,6 +68,15 @@ impl LoadableAsset for ShaderProgram {
     }
 }
 
+impl LoadState for ShaderProgram {
+    fn load_state(&self) -> AssetResult<()> {
+        self.vs_shader.try_borrow()?;
+        self.fs_shader.try_borrow()?;
+
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub struct ShaderProgramGLState {
     prog: WebGLProgram,
@@ -60,6 +84,7 @@ pub struct ShaderProgramGLState {
 
 #[derive(Debug)]
 pub struct ShaderProgram {
+    id: usize,
     gl_state: RefCell<Option<ShaderProgramGLState>>,
 
     coord_map: RefCell<HashMap<String, Option<u32>>>,
@@ -68,9 +93,17 @@ pub struct ShaderProgram {
     fs_shader: Resource<ShaderFs>,
 
     uniform_cache: UniformCache,
+
+    /// Programs compiled with the keywords defined, by the sorted keywords
+    variants: RefCell<HashMap<Vec<String>, Rc<ShaderProgram>>>,
 }
 
 impl ShaderProgram {
+    /// Unique id of the program, which unlike its address is never reused
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
     pub fn bind(&self, gl: &WebGLRenderingContext) -> AssetResult<()> {
         self.prepare(gl)?;
 
@@ -98,6 +131,69 @@ impl ShaderProgram {
         Ok(())
     }
 
+    /// The variant of `program` preprocessed with `keywords` defined, `program` itself
+    /// if there is no keyword. The variants are cached by the program
+    pub fn variant(
+        program: &Rc<ShaderProgram>,
+        keywords: &[String],
+    ) -> AssetResult<Rc<ShaderProgram>> {
+        if keywords.is_empty() {
+            return Ok(program.clone());
+        }
+
+        if let Some(variant) = program.variants.borrow().get(keywords) {
+            return Ok(variant.clone());
+        }
+
+        let (vs, fs) = {
+            let vs = program.vs_shader.try_borrow()?;
+            let fs = program.fs_shader.try_borrow()?;
+
+            let invalid = |filename: &str, e: PreprocessError| AssetError::InvalidFormat {
+                path: filename.to_string(),
+                len: 0,
+                reason: format!("{:?} with keywords {:?}", e, keywords),
+            };
+
+            (
+                vs.variant(keywords).map_err(|e| invalid(&vs.filename, e))?,
+                fs.variant(keywords).map_err(|e| invalid(&fs.filename, e))?,
+            )
+        };
+
+        let variant = ShaderProgram::new((Resource::new(vs), Resource::new(fs)));
+        program
+            .variants
+            .borrow_mut()
+            .insert(keywords.to_vec(), variant.clone());
+
+        Ok(variant)
+    }
+
+    /// Swap in the shaders of `resource` once both of them are loaded, e.g. the shaders
+    /// of the changed files of the program. The program is linked again in next bind
+    pub fn poll_reload(&self, resource: &<Self as Asset>::Resource) -> AssetResult<Async<()>> {
+        let (ref vs, ref fs) = *resource;
+
+        match (vs.try_borrow(), fs.try_borrow()) {
+            (Ok(_), Ok(_)) => (),
+            (Err(AssetError::NotReady), _) | (_, Err(AssetError::NotReady)) => {
+                return Ok(Async::NotReady)
+            }
+            (Err(e), _) | (_, Err(e)) => return Err(e),
+        }
+
+        self.vs_shader.swap(vs);
+        self.fs_shader.swap(fs);
+
+        *self.gl_state.borrow_mut() = None;
+        self.coord_map.borrow_mut().clear();
+        self.uniform_cache.reset();
+        self.variants.borrow_mut().clear();
+
+        Ok(Async::Ready(()))
+    }
+
     pub fn attrib_loc(&self, gl: &WebGLRenderingContext, s: &str) -> Option<u32> {
         let mut m = self.coord_map.borrow_mut();
 
@@ -122,6 +218,14 @@ impl ShaderProgram {
         self.uniform_cache.set(s, data);
     }
 
+    /// Whether the program is linked and has an active uniform named `s`
+    pub fn has_uniform(&self, gl: &WebGLRenderingContext, s: &'static str) -> bool {
+        self.gl_state.borrow().as_ref().map_or(false, |gl_state| {
+            self.uniform_cache
+                .has_uniform(gl, &gl_state.prog, &Cow::Borrowed(s))
+        })
+    }
+
     pub fn commit(&self, gl: &WebGLRenderingContext) {
         self.gl_state.borrow().as_ref().map(|gl_state| {
             self.uniform_cache.commit(gl, &gl_state.prog);
@@ -135,37 +239,8 @@ impl ShaderProgramGLState {
         vs_unit: &ShaderVs,
         fs_unit: &ShaderFs,
     ) -> ShaderProgramGLState {
-        /*================ Shaders ====================*/
-
-        // Create a vertex shader object
-        let vert_shader = gl.create_shader(WebGLShaderKind::Vertex);
-
-        // Attach vertex shader source code
-        gl.shader_source(&vert_shader, &vs_unit.code.as_string());
-
-        // Compile the vertex shader
         uni_app::App::print(format!("Compiling shader file : {}\n", vs_unit.filename));
-        gl.compile_shader(&vert_shader);
-
-        // Create fragment shader object
-        let frag_shader = gl.create_shader(WebGLShaderKind::Fragment);
-
-        // Attach fragment shader source code
-        gl.shader_source(&frag_shader, &fs_unit.code.as_string());
-
-        // Compile the fragmentt shader
         uni_app::App::print(format!("Compiling shader file : {}\n", fs_unit.filename));
-        gl.compile_shader(&frag_shader);
-
-        // Create a shader program object to store
-        // the combined shader program
-        let shader_program = gl.create_program();
-
-        // Attach a vertex shader
-        gl.attach_shader(&shader_program, &vert_shader);
-
-        // Attach a fragment shader
-        gl.attach_shader(&shader_program, &frag_shader);
 
         // We bind the position to 0
         // see: https://developer.mozilla.org/en-US/docs/Web/API/WebGL_API/WebGL_best_practices
@@ -176,26 +251,22 @@ impl ShaderProgramGLState {
         // This is because in desktop OpenGL, nothing gets drawn if vertex attrib 0 is not array-enabled.
         // You can use bindAttribLocation() to force a vertex attribute to use location 0,
         // and use enableVertexAttribArray() to make it array-enabled.
-        gl.bind_attrib_location(
-            &shader_program,
-            "aVertexPosition",
-            ShaderAttrib::Position as _,
-        );
-        gl.bind_attrib_location(&shader_program, "aTextureCoord", ShaderAttrib::UV0 as _);
-        gl.bind_attrib_location(&shader_program, "aVertexNormal", ShaderAttrib::Normal as _);
-        gl.bind_attrib_location(
-            &shader_program,
-            "aVertexTangent",
-            ShaderAttrib::Tangent as _,
+        let attribs = [
+            ("aVertexPosition", ShaderAttrib::Position as u32),
+            ("aTextureCoord", ShaderAttrib::UV0 as u32),
+            ("aVertexNormal", ShaderAttrib::Normal as u32),
+            ("aVertexTangent", ShaderAttrib::Tangent as u32),
+            ("aVertexBitangent", ShaderAttrib::Bitangent as u32),
+            ("aVertexColor", ShaderAttrib::Color as u32),
+            ("aTextureCoord2", ShaderAttrib::UV1 as u32),
+            ("aInstanceMatrix", ShaderAttrib::InstanceMatrix as u32),
+        ];
+
+        let shader_program = GLBackend::new(gl).create_pipeline(
+            &vs_unit.code.as_string(),
+            &fs_unit.code.as_string(),
+            &attribs,
         );
-        gl.bind_attrib_location(
-            &shader_program,
-            "aVertexBitangent",
-            ShaderAttrib::Bitangent as _,
-        );
-
-        // Link both the programs
-        gl.link_program(&shader_program);
 
         let prog = ShaderProgramGLState {
             prog: shader_program,
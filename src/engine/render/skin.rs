@@ -0,0 +1,59 @@
+use engine::core::GameObject;
+
+use math::*;
+use std::cell::RefCell;
+use std::rc::Weak;
+
+/// The joints which move the vertices of the mesh of the object, by the `joints` and
+/// `weights` of its mesh data. The vertices are skinned on the cpu when the joints move,
+/// the bounds of the mesh stay the ones of the bind pose
+#[derive(Component)]
+pub struct Skin {
+    /// Objects of the joints, in the order of the joint indices of the mesh data
+    pub joints: Vec<Weak<RefCell<GameObject>>>,
+    /// Matrices from the local space of the mesh to the one of each joint in the bind pose
+    pub inverse_bind_matrices: Vec<Matrix4<f32>>,
+}
+
+impl Skin {
+    pub fn new(
+        joints: Vec<Weak<RefCell<GameObject>>>,
+        inverse_bind_matrices: Vec<Matrix4<f32>>,
+    ) -> Skin {
+        Skin {
+            joints,
+            inverse_bind_matrices,
+        }
+    }
+
+    /// The matrices of the joints in the local space of the mesh placed by `model_m`,
+    /// the joints which are gone or borrowed keep the bind pose
+    pub fn joint_matrices(&self, model_m: &Matrix4<f32>) -> Vec<Matrix4<f32>> {
+        let to_mesh = model_m.invert().unwrap_or_else(Matrix4::identity);
+
+        self.joints
+            .iter()
+            .enumerate()
+            .map(|(i, joint)| {
+                let ibm = self.inverse_bind_matrices
+                    .get(i)
+                    .cloned()
+                    .unwrap_or_else(Matrix4::identity);
+
+                let joint_m = joint
+                    .upgrade()
+                    .and_then(|joint| {
+                        joint
+                            .try_borrow()
+                            .ok()
+                            .map(|joint| joint.transform.as_global_matrix())
+                    });
+
+                match joint_m {
+                    Some(joint_m) => to_mesh * joint_m * ibm,
+                    None => Matrix4::identity(),
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,50 @@
+use engine::asset::AssetSystem;
+use engine::render::{Material, MeshSurface, RenderQueue, Texture};
+
+use std::rc::Rc;
+
+/// The background of the scene, a cubemap drawn on the far plane in the skybox queue.
+///
+/// The first active skybox is also the environment map of the scene: the materials
+/// whose program has a `uEnvironment` sampler but which do not set it get the cubemap
+/// of the skybox, or the grey `default_environment` without a skybox.
+#[derive(Component)]
+pub struct Skybox {
+    pub cubemap: Rc<Texture>,
+    /// Use the cubemap as the environment map of the materials
+    pub environment: bool,
+
+    surface: Rc<MeshSurface>,
+}
+
+impl Skybox {
+    /// A skybox of `cubemap`, which could be a six-face `*_cubemap` texture
+    /// or an equirectangular `.hdr` / `*_equirect` texture
+    pub fn new(asys: &AssetSystem, cubemap: Rc<Texture>) -> Skybox {
+        cubemap.set_srgb(true);
+
+        let mut material = Material::new(asys.new_program("default_skybox"));
+        material.set("uSkybox", cubemap.clone());
+        material.set("uSkyboxIntensity", 1.0);
+        material.render_queue = RenderQueue::Skybox;
+
+        Skybox {
+            cubemap,
+            environment: true,
+            surface: Rc::new(MeshSurface {
+                buffer: asys.new_mesh_buffer("skybox"),
+                material: Rc::new(material),
+                params: None,
+            }),
+        }
+    }
+
+    /// Brightness of the drawn sky, the environment map is scaled by the materials
+    pub fn set_intensity(&self, intensity: f32) {
+        self.surface.material.set("uSkyboxIntensity", intensity);
+    }
+
+    pub fn surface(&self) -> &Rc<MeshSurface> {
+        &self.surface
+    }
+}
@@ -0,0 +1,181 @@
+use engine::asset::{Asset, AssetSystem};
+use engine::mesh_util;
+use engine::render::{CullMode, Material, MeshBuffer, MeshData, MeshSurface, RenderQueue, Texture,
+                     TextureAtlas};
+
+use math::*;
+use std::cell::RefCell;
+use std::mem;
+use std::rc::Rc;
+
+/// The properties which the quad of a sprite is built from
+#[derive(Copy, Clone, PartialEq)]
+struct SpriteKey {
+    texture: *const Texture,
+    image_size: (u32, u32),
+    region: Option<(u32, u32, u32, u32)>,
+    pixels_per_unit: f32,
+    pivot: (f32, f32),
+    flip_x: bool,
+    flip_y: bool,
+    color: Vector4<f32>,
+}
+
+/// A textured quad in the xy plane of the object, drawn in the sprite queue.
+///
+/// The sprites are sorted by sorting layer, then by order in layer, then from back to
+/// front. The consecutive sprites of the same texture are merged into a single draw
+/// call, the tint is baked into the vertex colors so that it does not break the batches.
+#[derive(Component)]
+pub struct Sprite {
+    pub texture: Rc<Texture>,
+    /// Part of the texture (x, y, w, h) in pixels from its top-left, the whole texture if None
+    pub region: Option<(u32, u32, u32, u32)>,
+    /// Pixels of the texture in a world unit
+    pub pixels_per_unit: f32,
+    /// Origin of the sprite normalized from the bottom-left of the region, (0.5, 0.5) by default
+    pub pivot: (f32, f32),
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub color: Vector4<f32>,
+    /// Atlas and name of the region drawn instead of `texture` and `region`,
+    /// see `from_atlas`
+    pub atlas: Option<(Rc<TextureAtlas>, String)>,
+
+    pub sorting_layer: i16,
+    pub order_in_layer: i16,
+
+    material: Rc<Material>,
+    built: RefCell<Option<(SpriteKey, Rc<MeshSurface>)>>,
+}
+
+impl Sprite {
+    pub fn new(asys: &AssetSystem, texture: Rc<Texture>) -> Sprite {
+        let mut material = Material::new(asys.new_program("default_sprite"));
+        material.set("uDiffuse", texture.clone());
+        material.render_queue = RenderQueue::Sprite;
+        material.states.cull = Some(CullMode::Off);
+
+        Sprite {
+            texture,
+            region: None,
+            pixels_per_unit: 100.0,
+            pivot: (0.5, 0.5),
+            flip_x: false,
+            flip_y: false,
+            color: Vector4::new(1.0, 1.0, 1.0, 1.0),
+            atlas: None,
+
+            sorting_layer: 0,
+            order_in_layer: 0,
+
+            material: Rc::new(material),
+            built: RefCell::new(None),
+        }
+    }
+
+    /// A sprite of the region `name` of an atlas, it is drawn once the atlas is loaded.
+    /// The sprites of an atlas are merged into the same batches
+    pub fn from_atlas(asys: &AssetSystem, atlas: Rc<TextureAtlas>, name: &str) -> Sprite {
+        let texture = atlas
+            .texture()
+            .unwrap_or_else(|| asys.new_texture("default_white"));
+
+        let mut sprite = Sprite::new(asys, texture);
+        sprite.atlas = Some((atlas, name.to_string()));
+        sprite
+    }
+
+    /// The texture and the region drawn, None until the atlas is loaded
+    fn source(&self) -> Option<(Rc<Texture>, Option<(u32, u32, u32, u32)>)> {
+        match self.atlas {
+            Some((ref atlas, ref name)) => {
+                let region = atlas.region(name)?;
+                Some((atlas.texture()?, Some(region.rect())))
+            }
+            None => Some((self.texture.clone(), self.region)),
+        }
+    }
+
+    /// Sort key of the sprite in its render queue, by sorting layer then order in layer
+    pub fn sorting_key(&self) -> i32 {
+        self.sorting_layer as i32 * 65536 + self.order_in_layer as i32
+    }
+
+    /// Size of the sprite in world units, None until the image of the texture is loaded
+    pub fn size(&self) -> Option<(f32, f32)> {
+        let (texture, region) = self.source()?;
+        let image_size = texture.image_size()?;
+        let (_, _, w, h) = region.unwrap_or((0, 0, image_size.0, image_size.1));
+        let ppu = self.pixels_per_unit.max(1e-6);
+
+        Some((w as f32 / ppu, h as f32 / ppu))
+    }
+
+    fn mesh_data(&self, image_size: (u32, u32), region: Option<(u32, u32, u32, u32)>) -> MeshData {
+        let (tw, th) = (image_size.0.max(1) as f32, image_size.1.max(1) as f32);
+        let (x, y, w, h) = region.unwrap_or((0, 0, image_size.0, image_size.1));
+
+        let (mut u0, mut u1) = (x as f32 / tw, (x + w) as f32 / tw);
+        let (mut v0, mut v1) = (y as f32 / th, (y + h) as f32 / th);
+        if self.flip_x {
+            mem::swap(&mut u0, &mut u1);
+        }
+        if self.flip_y {
+            mem::swap(&mut v0, &mut v1);
+        }
+
+        let ppu = self.pixels_per_unit.max(1e-6);
+        let (w, h) = (w as f32 / ppu, h as f32 / ppu);
+
+        // The quad goes from its top-left at the origin to (w, -h)
+        let mut data = mesh_util::uv_quad_mesh_data((w, h), (u0, v0, u1, v1));
+        data.translate(Vector3::new(-self.pivot.0 * w, (1.0 - self.pivot.1) * h, 0.0));
+
+        let c = self.color;
+        data.colors = Some((0..4).flat_map(|_| vec![c.x, c.y, c.z, c.w]).collect());
+
+        data
+    }
+
+    /// The surface of the sprite, rebuilt when any of its properties is changed.
+    /// None until the image of the texture is loaded
+    pub fn surface(&self) -> Option<Rc<MeshSurface>> {
+        let (texture, region) = self.source()?;
+        let image_size = texture.image_size()?;
+        let key = SpriteKey {
+            texture: &*texture as *const Texture,
+            image_size,
+            region,
+            pixels_per_unit: self.pixels_per_unit,
+            pivot: self.pivot,
+            flip_x: self.flip_x,
+            flip_y: self.flip_y,
+            color: self.color,
+        };
+
+        let mut built = self.built.borrow_mut();
+        let surface = match built.take() {
+            Some((ref built_key, ref surface)) if *built_key == key => surface.clone(),
+            Some((_, surface)) => {
+                // The surface is kept, its buffer is updated in place
+                self.material.set("uDiffuse", texture);
+                surface
+                    .buffer
+                    .update_mesh_data(self.mesh_data(image_size, region));
+                surface
+            }
+            None => {
+                self.material.set("uDiffuse", texture);
+                Rc::new(MeshSurface {
+                    buffer: MeshBuffer::new(self.mesh_data(image_size, region)),
+                    material: self.material.clone(),
+                    params: None,
+                })
+            }
+        };
+
+        *built = Some((key, surface.clone()));
+        Some(surface)
+    }
+}
@@ -0,0 +1,121 @@
+use engine::asset::AssetSystem;
+use engine::render::{CullMode, Material, RenderQueue, Texture};
+
+use math::*;
+use std::rc::Rc;
+
+/// Preset of a metallic-roughness PBR material of the `default_pbr` program,
+/// the missing maps are replaced by neutral default textures.
+///
+/// The metallic-roughness map follows glTF: the roughness is in green and the metallic
+/// in blue, both are multiplied by the factors. The environment cubemap is used for
+/// the image based lighting, its mipmaps stand for the blurred reflections. Without
+/// an environment the one of the scene is used, see `Skybox`.
+pub struct StandardMaterial {
+    pub base_color: Vector4<f32>,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive: Vector3<f32>,
+    /// Weight of the occlusion map from 0 (none) to 1
+    pub occlusion_strength: f32,
+
+    pub albedo_map: Option<Rc<Texture>>,
+    pub normal_map: Option<Rc<Texture>>,
+    pub metallic_roughness_map: Option<Rc<Texture>>,
+    pub occlusion_map: Option<Rc<Texture>>,
+    pub emissive_map: Option<Rc<Texture>>,
+
+    pub environment: Option<Rc<Texture>>,
+    pub environment_intensity: f32,
+
+    /// Blend by the alpha of the base color in the transparent queue
+    pub transparent: bool,
+    /// The fragments of a lower alpha are discarded, e.g. of foliage
+    pub alpha_cutoff: f32,
+    /// Draw the back faces too
+    pub double_sided: bool,
+}
+
+impl Default for StandardMaterial {
+    fn default() -> StandardMaterial {
+        StandardMaterial {
+            base_color: Vector4::new(1.0, 1.0, 1.0, 1.0),
+            metallic: 0.0,
+            roughness: 0.5,
+            emissive: Vector3::new(0.0, 0.0, 0.0),
+            occlusion_strength: 1.0,
+
+            albedo_map: None,
+            normal_map: None,
+            metallic_roughness_map: None,
+            occlusion_map: None,
+            emissive_map: None,
+
+            environment: None,
+            environment_intensity: 1.0,
+
+            transparent: false,
+            alpha_cutoff: 0.0,
+            double_sided: false,
+        }
+    }
+}
+
+impl StandardMaterial {
+    /// Build the material, the default textures are taken from `asys`.
+    /// The albedo, the emissive and the environment maps are marked as sRGB ones
+    pub fn build(&self, asys: &AssetSystem) -> Material {
+        let or_default = |tex: &Option<Rc<Texture>>, name: &str| {
+            tex.clone().unwrap_or_else(|| asys.new_texture(name))
+        };
+
+        let color_maps = [&self.albedo_map, &self.emissive_map, &self.environment];
+        for tex in color_maps.iter().filter_map(|tex| tex.as_ref()) {
+            tex.set_srgb(true);
+        }
+
+        let mut material = Material::new(asys.new_program("default_pbr"));
+
+        material.set(
+            "uMaterial.albedo",
+            or_default(&self.albedo_map, "default_white"),
+        );
+        material.set(
+            "uMaterial.normal",
+            or_default(&self.normal_map, "default_normal_map"),
+        );
+        material.set(
+            "uMaterial.metallicRoughness",
+            or_default(&self.metallic_roughness_map, "default_white"),
+        );
+        material.set(
+            "uMaterial.occlusion",
+            or_default(&self.occlusion_map, "default_white"),
+        );
+        material.set(
+            "uMaterial.emissive",
+            or_default(&self.emissive_map, "default_white"),
+        );
+
+        material.set("uMaterial.baseColor", self.base_color);
+        material.set("uMaterial.metallic", self.metallic);
+        material.set("uMaterial.roughness", self.roughness);
+        material.set("uMaterial.occlusionStrength", self.occlusion_strength);
+        material.set("uMaterial.emissiveFactor", self.emissive);
+        material.set("uMaterial.alphaCutoff", self.alpha_cutoff);
+
+        if let Some(ref env) = self.environment {
+            material.set("uEnvironment", env.clone());
+        }
+        material.set("uEnvironmentIntensity", self.environment_intensity);
+
+        if self.transparent {
+            material.render_queue = RenderQueue::Transparent;
+        }
+        if self.double_sided {
+            material.states.cull = Some(CullMode::Off);
+        }
+
+        material
+    }
+}
@@ -1,20 +1,26 @@
 use uni_gl;
 use uni_gl::*;
 
-use image::{RgbImage, RgbaImage};
+use image::{imageops, DynamicImage, FilterType, RgbImage, RgbaImage};
 
-use engine::asset::{Asset, AssetResult, AssetSystem, FileFuture, LoadableAsset, Resource, DDS};
+use engine::asset::{Asset, AssetError, AssetResult, AssetSystem, DDSFormat, FileFuture,
+                    LoadState, LoadableAsset, Resource, DDS};
+use engine::render::{srgb_textures, CompressedFormat};
+use futures::Async;
 use std::cell::{Cell, RefCell};
 use std::path::Path;
 use std::rc::Rc;
+use std::fmt;
+use std::slice;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum TextureFiltering {
     Nearest,
+    /// Linear within and between the mipmap levels
     Linear,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum TextureWrap {
     Repeat,
     ClampToEdge,
@@ -27,11 +33,136 @@ pub enum TextureImage {
     Rgb(RgbImage),
     DXT1(DDS),
     DXT5(DDS),
+    ETC2(DDS),
+    ASTC(DDS),
+    /// Equirectangular (latitude-longitude) panorama, uploaded as a cubemap
+    Equirect(RgbImage),
+}
+
+impl TextureImage {
+    pub fn size(&self) -> (u32, u32) {
+        match self {
+            &TextureImage::Rgba(ref img) => (img.width(), img.height()),
+            &TextureImage::Rgb(ref img) | &TextureImage::Equirect(ref img) => {
+                (img.width(), img.height())
+            }
+            &TextureImage::DXT1(ref dds)
+            | &TextureImage::DXT5(ref dds)
+            | &TextureImage::ETC2(ref dds)
+            | &TextureImage::ASTC(ref dds) => (dds.images[0].width, dds.images[0].height),
+        }
+    }
+
+    /// The compressed levels of the image, None if it is not compressed
+    fn compressed(&self) -> Option<&DDS> {
+        match *self {
+            TextureImage::DXT1(ref dds)
+            | TextureImage::DXT5(ref dds)
+            | TextureImage::ETC2(ref dds)
+            | TextureImage::ASTC(ref dds) => Some(dds),
+            _ => None,
+        }
+    }
+}
+
+/// The linear and the sRGB formats of the ASTC blocks
+#[cfg(feature = "compressed_formats")]
+fn astc_compression(
+    block_width: u32,
+    block_height: u32,
+) -> (TextureCompression, TextureCompression) {
+    use uni_gl::TextureCompression::*;
+
+    match (block_width, block_height) {
+        (5, 4) => (RgbaAstc5x4, Srgb8Alpha8Astc5x4),
+        (5, 5) => (RgbaAstc5x5, Srgb8Alpha8Astc5x5),
+        (6, 5) => (RgbaAstc6x5, Srgb8Alpha8Astc6x5),
+        (6, 6) => (RgbaAstc6x6, Srgb8Alpha8Astc6x6),
+        (8, 5) => (RgbaAstc8x5, Srgb8Alpha8Astc8x5),
+        (8, 6) => (RgbaAstc8x6, Srgb8Alpha8Astc8x6),
+        (8, 8) => (RgbaAstc8x8, Srgb8Alpha8Astc8x8),
+        (10, 5) => (RgbaAstc10x5, Srgb8Alpha8Astc10x5),
+        (10, 6) => (RgbaAstc10x6, Srgb8Alpha8Astc10x6),
+        (10, 8) => (RgbaAstc10x8, Srgb8Alpha8Astc10x8),
+        (10, 10) => (RgbaAstc10x10, Srgb8Alpha8Astc10x10),
+        (12, 10) => (RgbaAstc12x10, Srgb8Alpha8Astc12x10),
+        (12, 12) => (RgbaAstc12x12, Srgb8Alpha8Astc12x12),
+        _ => (RgbaAstc4x4, Srgb8Alpha8Astc4x4),
+    }
+}
+
+/// The linear and the sRGB gl formats of the compressed levels of `dds`
+#[cfg(feature = "compressed_formats")]
+fn compression_formats(dds: &DDS) -> Option<(TextureCompression, TextureCompression)> {
+    use uni_gl::TextureCompression::*;
+
+    Some(match dds.format {
+        DDSFormat::DXT1 if dds.has_alpha => (RgbaDxt1, SrgbaDxt1),
+        DDSFormat::DXT1 => (RgbDxt1, SrgbDxt1),
+        DDSFormat::DXT5 => (RgbaDxt5, SrgbaDxt5),
+        DDSFormat::ETC2 if dds.has_alpha => (Rgba8Etc2Eac, Srgb8Alpha8Etc2Eac),
+        DDSFormat::ETC2 => (Rgb8Etc2, Srgb8Etc2),
+        DDSFormat::ASTC(w, h) => astc_compression(w, h),
+    })
+}
+
+/// The GL layer of uni-gl 0.1 has only the linear S3TC formats, the others need the
+/// `compressed_formats` feature and a uni-gl which has them. The compressed textures
+/// are not sRGB without it, see `RenderCaps::srgb`
+#[cfg(not(feature = "compressed_formats"))]
+fn compression_formats(dds: &DDS) -> Option<(TextureCompression, TextureCompression)> {
+    use uni_gl::TextureCompression::*;
+
+    let linear = match dds.format {
+        DDSFormat::DXT1 if dds.has_alpha => RgbaDxt1,
+        DDSFormat::DXT1 => RgbDxt1,
+        DDSFormat::DXT5 => RgbaDxt5,
+        _ => return None,
+    };
+    Some((linear, linear))
+}
+
+/// The gl format of compressed levels, the sRGB one if `srgb`. An error if the context
+/// does not support it
+fn gl_compression(
+    gl: &WebGLRenderingContext,
+    dds: &DDS,
+    srgb: bool,
+) -> AssetResult<TextureCompression> {
+    let required = match dds.format {
+        DDSFormat::DXT1 | DDSFormat::DXT5 => CompressedFormat::S3tc,
+        DDSFormat::ETC2 => CompressedFormat::Etc2,
+        DDSFormat::ASTC(_, _) => CompressedFormat::Astc,
+    };
+
+    let error = |reason: String| AssetError::InvalidFormat {
+        path: format!("{:?} texture", dds.format),
+        len: dds.images.iter().map(|img| img.data.len()).sum(),
+        reason,
+    };
+
+    if !required.is_supported(gl) {
+        return Err(error(format!(
+            "{:?} compression is not supported by the gpu",
+            required
+        )));
+    }
+
+    let (linear, srgb_compression) = compression_formats(dds).ok_or_else(|| {
+        error(format!(
+            "{:?} compression needs the compressed_formats feature",
+            required
+        ))
+    })?;
+
+    Ok(if srgb { srgb_compression } else { linear })
 }
 
 #[derive(Debug)]
 pub enum TextureAttachment {
     Color0,
+    /// Floating point rgba color, see `RenderCaps::float_render_targets`
+    FloatColor0,
     Depth,
 }
 
@@ -40,19 +171,43 @@ enum TextureKind {
     Image(Resource<TextureImage>),
     CubeMap([Resource<TextureImage>; 6]),
     RenderTexture {
-        size: (u32, u32),
+        size: Cell<(u32, u32)>,
         attach: TextureAttachment,
     },
 }
 
+/// The sampling states of a texture, which are applied in next bind when changed
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Sampler {
+    min_filter: TextureFiltering,
+    mag_filter: TextureFiltering,
+    wrap_u: TextureWrap,
+    wrap_v: TextureWrap,
+    wrap_w: Option<TextureWrap>,
+    anisotropy: u32,
+    mipmaps: bool,
+}
+
 #[derive(Debug)]
 pub struct Texture {
+    /// Filter of the minification, and the magnification unless `mag_filtering` is set
     pub filtering: Cell<TextureFiltering>,
+    pub mag_filtering: Cell<Option<TextureFiltering>>,
     pub wrap_u: Cell<TextureWrap>,
     pub wrap_v: Cell<TextureWrap>,
     pub wrap_w: Cell<Option<TextureWrap>>,
+    /// Maximum anisotropy of the linear filtering, 1 to disable it.
+    /// It is ignored by the browsers without `EXT_texture_filter_anisotropic`
+    pub anisotropy: Cell<u32>,
+    /// Whether the mipmaps are generated when the image is uploaded and sampled,
+    /// the compressed images have their own levels
+    pub mipmaps: Cell<bool>,
+    /// The texels are sRGB encoded colors, see `set_srgb`
+    srgb: Cell<bool>,
 
     gl_state: RefCell<Option<TextureGLState>>,
+    /// The image was updated and need to be uploaded again
+    dirty: Cell<bool>,
     kind: TextureKind,
 }
 
@@ -74,16 +229,24 @@ impl Asset for Texture {
         return match r {
             TextureAsset::Single(res) => Rc::new(Texture {
                 filtering: Cell::new(TextureFiltering::Linear),
+                mag_filtering: Cell::new(None),
                 wrap_u: Cell::new(TextureWrap::ClampToEdge),
                 wrap_v: Cell::new(TextureWrap::ClampToEdge),
                 wrap_w: Cell::new(None),
+                anisotropy: Cell::new(1),
+                mipmaps: Cell::new(true),
                 gl_state: RefCell::new(None),
+                dirty: Cell::new(false),
                 kind: TextureKind::Image(res),
             }),
 
             TextureAsset::Cube(res) => Rc::new(Texture {
                 filtering: Cell::new(TextureFiltering::Linear),
+                mag_filtering: Cell::new(None),
+                anisotropy: Cell::new(1),
+                mipmaps: Cell::new(true),
                 gl_state: RefCell::new(None),
+                dirty: Cell::new(false),
                 kind: TextureKind::CubeMap(res),
                 wrap_u: Cell::new(TextureWrap::ClampToEdge),
                 wrap_v: Cell::new(TextureWrap::ClampToEdge),
@@ -146,22 +309,69 @@ impl LoadableAsset for Texture {
     }
 }
 
-#[derive(Debug)]
+impl LoadState for Texture {
+    fn load_state(&self) -> AssetResult<()> {
+        // The images are consumed once uploaded to gpu
+        if self.size().is_some() {
+            return Ok(());
+        }
+
+        match self.kind {
+            TextureKind::Image(ref res) => res.try_borrow().map(|_| ()),
+            TextureKind::CubeMap(ref res) => {
+                for face in res.iter() {
+                    face.try_borrow()?;
+                }
+                Ok(())
+            }
+            TextureKind::RenderTexture { .. } => Ok(()),
+        }
+    }
+}
+
 struct TextureGLState {
     tex: WebGLTexture,
     size: (u32, u32),
+    cube: bool,
+    has_mipmap: bool,
+    /// The depth textures are always sampled by the nearest filter
+    force_nearest: bool,
+    /// The sampler which was applied last
+    sampler: Sampler,
+    gl: WebGLRenderingContext,
+}
+
+impl fmt::Debug for TextureGLState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TextureGLState")
+            .field("tex", &self.tex)
+            .field("size", &self.size)
+            .field("cube", &self.cube)
+            .finish()
+    }
+}
+
+/// The texture is deleted from gpu once the texture is dropped or uploaded again
+impl Drop for TextureGLState {
+    fn drop(&mut self) {
+        self.gl.delete_texture(&self.tex);
+    }
 }
 
 impl Texture {
     pub fn new_render_texture(width: u32, height: u32, attach: TextureAttachment) -> Rc<Self> {
         Rc::new(Texture {
             filtering: Cell::new(TextureFiltering::Linear),
+            mag_filtering: Cell::new(None),
+            anisotropy: Cell::new(1),
+            mipmaps: Cell::new(false),
             gl_state: RefCell::new(None),
+            dirty: Cell::new(false),
             wrap_u: Cell::new(TextureWrap::ClampToEdge),
             wrap_v: Cell::new(TextureWrap::ClampToEdge),
             wrap_w: Cell::new(None),
             kind: TextureKind::RenderTexture {
-                size: (width, height),
+                size: Cell::new((width, height)),
                 attach: attach,
             },
         })
@@ -171,6 +381,98 @@ impl Texture {
         self.gl_state.borrow().as_ref().map(|s| s.size)
     }
 
+    /// Size of the image, available as soon as the image is loaded
+    /// even it is not yet uploaded to gpu
+    pub fn image_size(&self) -> Option<(u32, u32)> {
+        if let Some(size) = self.size() {
+            return Some(size);
+        }
+
+        match self.kind {
+            TextureKind::Image(ref res) => res.try_borrow().ok().map(|img| img.size()),
+            TextureKind::RenderTexture { ref size, .. } => Some(size.get()),
+            _ => None,
+        }
+    }
+
+    /// Whether the texture is sampled as a cubemap, an equirectangular image
+    /// is known as a cubemap only after it is loaded
+    pub fn is_cubemap(&self) -> bool {
+        match self.kind {
+            TextureKind::CubeMap(_) => true,
+            TextureKind::Image(ref res) => is_equirect(res),
+            TextureKind::RenderTexture { .. } => false,
+        }
+    }
+
+    /// Alpha (0..255) of the pixel at (u, v) from the top-left of the image, None if the
+    /// image is not loaded or compressed. The images without alpha are opaque
+    pub fn alpha_at(&self, u: f32, v: f32) -> Option<u8> {
+        let img = match self.kind {
+            TextureKind::Image(ref res) => res.try_borrow().ok()?,
+            _ => return None,
+        };
+
+        let (w, h) = img.size();
+        if w == 0 || h == 0 {
+            return None;
+        }
+
+        let x = ((u.max(0.0).min(1.0) * w as f32) as u32).min(w - 1);
+        let y = ((v.max(0.0).min(1.0) * h as f32) as u32).min(h - 1);
+
+        match *img {
+            TextureImage::Rgba(ref img) => Some(img.get_pixel(x, y).data[3]),
+            TextureImage::Rgb(_) => Some(0xff),
+            _ => None,
+        }
+    }
+
+    /// Red channel (0..255) of the image rows from the top-left, e.g. the heights of a
+    /// heightmap. None if the image is not loaded, compressed or already uploaded to gpu
+    pub fn red_channel(&self) -> Option<(u32, u32, Vec<u8>)> {
+        let img = match self.kind {
+            TextureKind::Image(ref res) => res.try_borrow().ok()?,
+            _ => return None,
+        };
+
+        match *img {
+            TextureImage::Rgba(ref img) => Some((
+                img.width(),
+                img.height(),
+                img.pixels().map(|p| p.data[0]).collect(),
+            )),
+            TextureImage::Rgb(ref img) => Some((
+                img.width(),
+                img.height(),
+                img.pixels().map(|p| p.data[0]).collect(),
+            )),
+            _ => None,
+        }
+    }
+
+    /// Interleaved rgb channels of the image rows from the top-left, e.g. of a panorama
+    /// captured in light probes. None if the image is not loaded, compressed or already
+    /// uploaded to gpu
+    pub fn rgb_channels(&self) -> Option<(u32, u32, Vec<u8>)> {
+        let img = match self.kind {
+            TextureKind::Image(ref res) => res.try_borrow().ok()?,
+            _ => return None,
+        };
+
+        match *img {
+            TextureImage::Rgba(ref img) => Some((
+                img.width(),
+                img.height(),
+                img.pixels().flat_map(|p| p.data[0..3].to_vec()).collect(),
+            )),
+            TextureImage::Rgb(ref img) | TextureImage::Equirect(ref img) => {
+                Some((img.width(), img.height(), img.clone().into_raw()))
+            }
+            _ => None,
+        }
+    }
+
     pub fn bind(&self, gl: &WebGLRenderingContext, unit: u32) -> AssetResult<()> {
         self.prepare(gl, unit)?;
 
@@ -178,9 +480,10 @@ impl Texture {
         let state = state_option.as_ref().unwrap();
 
         gl.active_texture(unit);
-        match self.kind {
-            TextureKind::CubeMap(_) => gl.bind_texture_cube(&state.tex),
-            _ => gl.bind_texture(&state.tex),
+        if state.cube {
+            gl.bind_texture_cube(&state.tex);
+        } else {
+            gl.bind_texture(&state.tex);
         }
 
         Ok(())
@@ -193,14 +496,15 @@ impl Texture {
         let state = state_option.as_ref().unwrap();
 
         gl.active_texture(unit);
-        match self.kind {
-            TextureKind::CubeMap(_) => gl.bind_texture_cube(&state.tex),
-            _ => gl.bind_texture(&state.tex),
+        if state.cube {
+            gl.bind_texture_cube(&state.tex);
+        } else {
+            gl.bind_texture(&state.tex);
         }
 
         if let TextureKind::RenderTexture { ref attach, .. } = self.kind {
             match attach {
-                &TextureAttachment::Color0 => {
+                &TextureAttachment::Color0 | &TextureAttachment::FloatColor0 => {
                     bind_to_framebuffer(gl, &state.tex, Buffers::ColorAttachment0);
                 }
                 &TextureAttachment::Depth => {
@@ -213,22 +517,131 @@ impl Texture {
         Ok(())
     }
 
+    /// Attach the depth render texture to the bound frame buffer, next to its color texture
+    pub fn attach_depth(&self, gl: &WebGLRenderingContext, unit: u32) -> AssetResult<()> {
+        self.prepare(gl, unit)?;
+
+        let state_option = self.gl_state.borrow();
+        let state = state_option.as_ref().unwrap();
+
+        bind_to_framebuffer(gl, &state.tex, Buffers::DepthAttachment);
+
+        Ok(())
+    }
+
+    /// Set the wrap modes of u and v, they are applied in next bind
+    pub fn set_wrap(&self, u: TextureWrap, v: TextureWrap) {
+        self.wrap_u.set(u);
+        self.wrap_v.set(v);
+    }
+
+    /// Set the filters of the minification and the magnification,
+    /// they are applied in next bind
+    pub fn set_filtering(&self, min: TextureFiltering, mag: TextureFiltering) {
+        self.filtering.set(min);
+        self.mag_filtering.set(Some(mag));
+    }
+
+    fn sampler(&self) -> Sampler {
+        Sampler {
+            min_filter: self.filtering.get(),
+            mag_filter: self.mag_filtering.get().unwrap_or(self.filtering.get()),
+            wrap_u: self.wrap_u.get(),
+            wrap_v: self.wrap_v.get(),
+            wrap_w: self.wrap_w.get(),
+            anisotropy: self.anisotropy.get().max(1),
+            mipmaps: self.mipmaps.get(),
+        }
+    }
+
+    /// Mark the texels as sRGB encoded colors, e.g. of an albedo map, which the
+    /// sampling decodes to linear with `RenderCaps::srgb`. The data maps, e.g. the
+    /// normal maps, stay linear. It applies to the next upload of the image, so it is
+    /// set before the texture is first drawn
+    pub fn set_srgb(&self, srgb: bool) {
+        self.srgb.set(srgb);
+    }
+
+    pub fn is_srgb(&self) -> bool {
+        self.srgb.get()
+    }
+
+    /// Change the size of a render texture, it is created again in next bind
+    pub fn resize(&self, width: u32, height: u32) {
+        if let TextureKind::RenderTexture { ref size, .. } = self.kind {
+            if size.get() == (width, height) {
+                return;
+            }
+
+            size.set((width, height));
+            if self.gl_state.borrow().is_some() {
+                self.dirty.set(true);
+            }
+        }
+    }
+
+    /// Replace the image of the texture, it will be uploaded again in next bind
+    pub fn update_image(&self, img: TextureImage) {
+        if let TextureKind::Image(ref res) = self.kind {
+            res.replace(img);
+            self.dirty.set(true);
+        }
+    }
+
+    /// Swap in the images of `asset` once all of them are loaded, e.g. the images of the
+    /// changed files of the texture. They will be uploaded again in next bind
+    pub fn poll_reload(&self, asset: &TextureAsset) -> AssetResult<Async<()>> {
+        let (old, new): (&[Resource<TextureImage>], &[Resource<TextureImage>]) =
+            match (&self.kind, asset) {
+                (&TextureKind::Image(ref old), &TextureAsset::Single(ref new)) => {
+                    (slice::from_ref(old), slice::from_ref(new))
+                }
+                (&TextureKind::CubeMap(ref old), &TextureAsset::Cube(ref new)) => (old, new),
+                _ => return Ok(Async::Ready(())),
+            };
+
+        for res in new.iter() {
+            match res.try_borrow() {
+                Ok(_) => (),
+                Err(AssetError::NotReady) => return Ok(Async::NotReady),
+                Err(e) => return Err(e),
+            }
+        }
+
+        for (old, new) in old.iter().zip(new.iter()) {
+            old.swap(new);
+        }
+        self.dirty.set(true);
+
+        Ok(Async::Ready(()))
+    }
+
     pub fn prepare(&self, gl: &WebGLRenderingContext, unit: u32) -> AssetResult<()> {
-        if self.gl_state.borrow().is_some() {
-            return Ok(());
+        let sampler = self.sampler();
+
+        if !self.dirty.get() {
+            if let Some(ref mut state) = *self.gl_state.borrow_mut() {
+                if state.sampler != sampler {
+                    gl.active_texture(unit);
+                    if state.cube {
+                        gl.bind_texture_cube(&state.tex);
+                    } else {
+                        gl.bind_texture(&state.tex);
+                    }
+
+                    apply_sampler(gl, state, sampler);
+                }
+
+                return Ok(());
+            }
         }
 
-        let new_state = Some(texture_bind_buffer(
-            gl,
-            &self.filtering.get(),
-            self.wrap_u.get(),
-            self.wrap_v.get(),
-            self.wrap_w.get(),
-            &self.kind,
-            unit,
-        )?);
+        let color = ColorUpload::new(gl, self.srgb.get());
+        let new_state = Some(texture_bind_buffer(gl, sampler, color, &self.kind, unit)?);
 
+        // The old texture is deleted as it is dropped
         self.gl_state.replace(new_state);
+        self.dirty.set(false);
 
         Ok(())
     }
@@ -258,21 +671,276 @@ fn bind_to_framebuffer(gl: &WebGLRenderingContext, tex: &WebGLTexture, buffer: B
 //     }
 // }
 
+/// Whether the image of `res` is loaded and equirectangular
+fn is_equirect(res: &Resource<TextureImage>) -> bool {
+    match res.try_borrow() {
+        Ok(img) => match *img {
+            TextureImage::Equirect(_) => true,
+            _ => false,
+        },
+        Err(_) => false,
+    }
+}
+
+/// Upper bound of the face size of the cubemaps converted from equirectangular images
+const MAX_EQUIRECT_FACE_SIZE: u32 = 1024;
+
+/// Resample an equirectangular image to the six faces of a cubemap,
+/// in the order of +x, -x, +y, -y, +z, -z
+fn equirect_to_cube_faces(img: &RgbImage) -> Vec<RgbImage> {
+    use image::ImageBuffer;
+    use std::f32::consts::PI;
+
+    let (w, h) = (img.width(), img.height());
+    let size = (w / 4).max(1).next_power_of_two().min(MAX_EQUIRECT_FACE_SIZE);
+
+    // The direction of the texel (s, t) of each face, following the gl cubemap layout
+    let dirs: [fn(f32, f32) -> (f32, f32, f32); 6] = [
+        |s, t| (1.0, -t, -s),
+        |s, t| (-1.0, -t, s),
+        |s, t| (s, 1.0, t),
+        |s, t| (s, -1.0, -t),
+        |s, t| (s, -t, 1.0),
+        |s, t| (-s, -t, -1.0),
+    ];
+
+    dirs.iter()
+        .map(|dir| {
+            ImageBuffer::from_fn(size, size, |x, y| {
+                let s = 2.0 * (x as f32 + 0.5) / size as f32 - 1.0;
+                let t = 2.0 * (y as f32 + 0.5) / size as f32 - 1.0;
+                let (dx, dy, dz) = dir(s, t);
+                let len = (dx * dx + dy * dy + dz * dz).sqrt();
+
+                let u = 0.5 + dx.atan2(-dz) / (2.0 * PI);
+                let v = (dy / len).max(-1.0).min(1.0).acos() / PI;
+
+                let px = ((u * w as f32) as u32).min(w - 1);
+                let py = ((v * h as f32) as u32).min(h - 1);
+
+                *img.get_pixel(px, py)
+            })
+        })
+        .collect()
+}
+
+/// Apply `sampler` to the bound texture of `state`
+fn apply_sampler(gl: &WebGLRenderingContext, state: &mut TextureGLState, sampler: Sampler) {
+    let gl_tex_kind = if state.cube {
+        uni_gl::TextureKind::TextureCubeMap
+    } else {
+        uni_gl::TextureKind::Texture2d
+    };
+
+    let mipmapped = state.has_mipmap && sampler.mipmaps;
+    let (min_filter, mag_filter) = if state.force_nearest {
+        (TextureFiltering::Nearest, TextureFiltering::Nearest)
+    } else {
+        (sampler.min_filter, sampler.mag_filter)
+    };
+
+    let min = match min_filter {
+        TextureFiltering::Nearest => TextureMinFilter::Nearest as i32,
+        TextureFiltering::Linear if mipmapped => TextureMinFilter::LinearMipmapLinear as i32,
+        TextureFiltering::Linear => TextureMinFilter::Linear as i32,
+    };
+    let mag = match mag_filter {
+        TextureFiltering::Nearest => TextureMagFilter::Nearest as i32,
+        TextureFiltering::Linear => TextureMagFilter::Linear as i32,
+    };
+
+    let to_gl_wrap = |w: TextureWrap| match w {
+        TextureWrap::Repeat => uni_gl::TextureWrap::Repeat as i32,
+        TextureWrap::ClampToEdge => uni_gl::TextureWrap::ClampToEdge as i32,
+        TextureWrap::MirroredRepeat => uni_gl::TextureWrap::MirroredRepeat as i32,
+    };
+
+    gl.tex_parameteri(gl_tex_kind, TextureParameter::TextureMinFilter, min);
+    gl.tex_parameteri(gl_tex_kind, TextureParameter::TextureMagFilter, mag);
+
+    gl.tex_parameteri(
+        gl_tex_kind,
+        TextureParameter::TextureWrapS,
+        to_gl_wrap(sampler.wrap_u),
+    );
+    gl.tex_parameteri(
+        gl_tex_kind,
+        TextureParameter::TextureWrapT,
+        to_gl_wrap(sampler.wrap_v),
+    );
+
+    if state.cube {
+        gl.tex_parameteri(
+            gl_tex_kind,
+            TextureParameter::TextureWrapR,
+            to_gl_wrap(sampler.wrap_w.unwrap_or(TextureWrap::ClampToEdge)),
+        );
+    }
+
+    if sampler.anisotropy > 1 || state.sampler.anisotropy > 1 {
+        gl.tex_parameteri(
+            gl_tex_kind,
+            TextureParameter::TextureMaxAnisotropyExt,
+            sampler.anisotropy as i32,
+        );
+    }
+
+    state.sampler = sampler;
+}
+
+/// How the images of a texture are uploaded
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum ColorUpload {
+    Linear,
+    /// sRGB, the mipmaps are generated by the gpu
+    Srgb,
+    /// sRGB with the mipmaps downsized here, WebGL1 does not generate the ones of the
+    /// sRGB textures of `EXT_sRGB`
+    SrgbLevels,
+}
+
+impl ColorUpload {
+    fn new(gl: &WebGLRenderingContext, srgb: bool) -> ColorUpload {
+        if !srgb || !srgb_textures() {
+            ColorUpload::Linear
+        } else if gl.is_webgl2 || !uni_gl::IS_GL_ES {
+            ColorUpload::Srgb
+        } else {
+            ColorUpload::SrgbLevels
+        }
+    }
+}
+
+/// Upload `img` to the level 0 of `target`, and its smaller levels with
+/// `ColorUpload::SrgbLevels`. Return whether the levels were uploaded, otherwise the
+/// mipmaps are generated once all the faces are.
+///
+/// `PixelFormat::SrgbAlpha` is SRGB8_ALPHA8 on WebGL2 and the desktop and SRGB_ALPHA_EXT
+/// on WebGL1
+fn upload_rgba(
+    gl: &WebGLRenderingContext,
+    target: TextureBindPoint,
+    img: &RgbaImage,
+    color: ColorUpload,
+    mipmaps: bool,
+) -> bool {
+    let format = match color {
+        ColorUpload::Linear => PixelFormat::Rgba,
+        ColorUpload::Srgb | ColorUpload::SrgbLevels => PixelFormat::SrgbAlpha,
+    };
+
+    gl.tex_image2d(
+        target,                  // target
+        0,                       // level
+        img.width() as u16,      // width
+        img.height() as u16,     // height
+        format,                  // format
+        PixelType::UnsignedByte, // type
+        &*img,                   // data
+    );
+
+    if color != ColorUpload::SrgbLevels || !mipmaps {
+        return false;
+    }
+
+    let mut level = img.clone();
+    let mut lvl = 0;
+    while level.width() > 1 || level.height() > 1 {
+        let (w, h) = ((level.width() / 2).max(1), (level.height() / 2).max(1));
+        level = imageops::resize(&level, w, h, FilterType::Triangle);
+        lvl += 1;
+
+        gl.tex_image2d(
+            target,
+            lvl,
+            w as u16,
+            h as u16,
+            format,
+            PixelType::UnsignedByte,
+            &*level,
+        );
+    }
+
+    true
+}
+
+/// Upload `img` like `upload_rgba`, the sRGB images as rgba ones as SRGB8 is not
+/// renderable and its mipmaps can not be generated
+fn upload_rgb(
+    gl: &WebGLRenderingContext,
+    target: TextureBindPoint,
+    img: &RgbImage,
+    color: ColorUpload,
+    mipmaps: bool,
+) -> bool {
+    if color != ColorUpload::Linear {
+        let rgba = DynamicImage::ImageRgb8(img.clone()).to_rgba();
+        return upload_rgba(gl, target, &rgba, color, mipmaps);
+    }
+
+    gl.tex_image2d(
+        target,                  // target
+        0,                       // level
+        img.width() as u16,      // width
+        img.height() as u16,     // height
+        PixelFormat::Rgb,        // format
+        PixelType::UnsignedByte, // type
+        &*img,                   // data
+    );
+
+    false
+}
+
 fn texture_bind_buffer(
     gl: &WebGLRenderingContext,
-    texfilter: &TextureFiltering,
-    wrap_u: TextureWrap,
-    wrap_v: TextureWrap,
-    wrap_w: Option<TextureWrap>,
+    sampler: Sampler,
+    color: ColorUpload,
     kind: &TextureKind,
     unit: u32,
 ) -> AssetResult<TextureGLState> {
-    let mut gl_tex_kind: uni_gl::TextureKind = uni_gl::TextureKind::Texture2d;
     let mut force_nearest_filtering = false;
+    let mut is_cube = false;
 
     let (tex, size, has_midmap) = match kind {
+        &TextureKind::Image(ref img_res) if is_equirect(img_res) => {
+            let faces = match img_res.try_into()? {
+                TextureImage::Equirect(img) => equirect_to_cube_faces(&img),
+                _ => unreachable!(),
+            };
+
+            let bindpoints = [
+                TextureBindPoint::TextureCubeMapPositiveX,
+                TextureBindPoint::TextureCubeMapNegativeX,
+                TextureBindPoint::TextureCubeMapPositiveY,
+                TextureBindPoint::TextureCubeMapNegativeY,
+                TextureBindPoint::TextureCubeMapPositiveZ,
+                TextureBindPoint::TextureCubeMapNegativeZ,
+            ];
+
+            let tex = gl.create_texture();
+            gl.active_texture(unit);
+            gl.bind_texture_cube(&tex);
+
+            let mut levels = false;
+            for (face, bindpoint) in faces.iter().zip(bindpoints.iter()) {
+                levels = upload_rgb(gl, *bindpoint, face, color, sampler.mipmaps);
+            }
+
+            if sampler.mipmaps && !levels {
+                gl.generate_mipmap_cube();
+            }
+
+            is_cube = true;
+
+            (tex, (faces[0].width(), faces[0].height()), sampler.mipmaps)
+        }
         &TextureKind::Image(ref img_res) => {
             let teximg = img_res.try_into()?;
+            let compression = match teximg.compressed() {
+                Some(dds) => Some(gl_compression(gl, dds, color != ColorUpload::Linear)?),
+                None => None,
+            };
+
             let tex = gl.create_texture();
             let size: (u32, u32);
             let has_midmap;
@@ -283,66 +951,36 @@ fn texture_bind_buffer(
             match teximg {
                 TextureImage::Rgba(img) => {
                     size = (img.width(), img.height());
-                    gl.tex_image2d(
-                        TextureBindPoint::Texture2d, // target
-                        0,                           // level
-                        img.width() as u16,          // width
-                        img.height() as u16,         // height
-                        PixelFormat::Rgba,           // format
-                        PixelType::UnsignedByte,     // type
-                        &*img,                       // data
-                    );
-
-                    gl.generate_mipmap();
-                    has_midmap = true;
+                    let target = TextureBindPoint::Texture2d;
+                    let levels = upload_rgba(gl, target, &img, color, sampler.mipmaps);
+
+                    if sampler.mipmaps && !levels {
+                        gl.generate_mipmap();
+                    }
+                    has_midmap = sampler.mipmaps;
                 }
                 TextureImage::Rgb(img) => {
                     size = (img.width(), img.height());
-                    gl.tex_image2d(
-                        TextureBindPoint::Texture2d, // target
-                        0,                           // level
-                        img.width() as u16,          // width
-                        img.height() as u16,         // height
-                        PixelFormat::Rgb,            // format
-                        PixelType::UnsignedByte,     // type
-                        &*img,                       // data
-                    );
-
-                    gl.generate_mipmap();
-                    has_midmap = true;
-                }
+                    let target = TextureBindPoint::Texture2d;
+                    let levels = upload_rgb(gl, target, &img, color, sampler.mipmaps);
 
-                TextureImage::DXT1(dds) => {
-                    size = (dds.images[0].width, dds.images[0].height);
-
-                    let format = if dds.has_alpha {
-                        TextureCompression::RgbaDxt1
-                    } else {
-                        TextureCompression::RgbDxt1
-                    };
-
-                    for (lvl, img) in dds.images.iter().enumerate() {
-                        gl.compressed_tex_image2d(
-                            TextureBindPoint::Texture2d,
-                            lvl as u8,
-                            format,
-                            img.width as u16,
-                            img.height as u16,
-                            &img.data,
-                        );
+                    if sampler.mipmaps && !levels {
+                        gl.generate_mipmap();
                     }
-
-                    has_midmap = dds.images.len() > 1;
+                    has_midmap = sampler.mipmaps;
                 }
 
-                TextureImage::DXT5(dds) => {
+                TextureImage::DXT1(dds)
+                | TextureImage::DXT5(dds)
+                | TextureImage::ETC2(dds)
+                | TextureImage::ASTC(dds) => {
                     size = (dds.images[0].width, dds.images[0].height);
 
                     for (lvl, img) in dds.images.iter().enumerate() {
                         gl.compressed_tex_image2d(
                             TextureBindPoint::Texture2d,
                             lvl as u8,
-                            TextureCompression::RgbaDxt5,
+                            compression.unwrap(),
                             img.width as u16,
                             img.height as u16,
                             &img.data,
@@ -351,6 +989,8 @@ fn texture_bind_buffer(
 
                     has_midmap = dds.images.len() > 1;
                 }
+
+                TextureImage::Equirect(_) => unreachable!(),
             }
 
             (tex, size, has_midmap)
@@ -379,6 +1019,13 @@ fn texture_bind_buffer(
                 imgs.push(res.try_into()?);
             }
 
+            let compressions = imgs.iter()
+                .map(|img| match img.compressed() {
+                    Some(dds) => gl_compression(gl, dds, color != ColorUpload::Linear).map(Some),
+                    None => Ok(None),
+                })
+                .collect::<AssetResult<Vec<_>>>()?;
+
             let tex = gl.create_texture();
             gl.active_texture(0);
             gl.bind_texture_cube(&tex);
@@ -389,56 +1036,28 @@ fn texture_bind_buffer(
                 match teximg {
                     &TextureImage::Rgba(ref img) => {
                         size = (img.width(), img.height());
-                        gl.tex_image2d(
-                            bindpoints[i],           // target
-                            0,                       // level
-                            img.width() as u16,      // width
-                            img.height() as u16,     // height
-                            PixelFormat::Rgba,       // format
-                            PixelType::UnsignedByte, // type
-                            &*img,                   // data
-                        );
-                        need_gen_mipmap = true;
+                        let levels = upload_rgba(gl, bindpoints[i], img, color, sampler.mipmaps);
+                        has_midmap |= levels;
+                        need_gen_mipmap = !levels;
                     }
-                    &TextureImage::Rgb(ref img) => {
+                    &TextureImage::Rgb(ref img) | &TextureImage::Equirect(ref img) => {
                         size = (img.width(), img.height());
-                        gl.tex_image2d(
-                            bindpoints[i],           // target
-                            0,                       // level
-                            img.width() as u16,      // width
-                            img.height() as u16,     // height
-                            PixelFormat::Rgb,        // format
-                            PixelType::UnsignedByte, // type
-                            &*img,                   // data
-                        );
-                        need_gen_mipmap = true;
+                        let levels = upload_rgb(gl, bindpoints[i], img, color, sampler.mipmaps);
+                        has_midmap |= levels;
+                        need_gen_mipmap = !levels;
                     }
 
-                    &TextureImage::DXT1(ref dds) => {
+                    &TextureImage::DXT1(ref dds)
+                    | &TextureImage::DXT5(ref dds)
+                    | &TextureImage::ETC2(ref dds)
+                    | &TextureImage::ASTC(ref dds) => {
                         size = (dds.images[0].width, dds.images[0].height);
 
                         for (lvl, img) in dds.images.iter().enumerate() {
                             gl.compressed_tex_image2d(
                                 bindpoints[i],
                                 lvl as u8,
-                                TextureCompression::RgbaDxt1,
-                                img.width as u16,
-                                img.height as u16,
-                                &img.data,
-                            );
-                        }
-
-                        has_midmap = dds.images.len() > 1;
-                    }
-
-                    &TextureImage::DXT5(ref dds) => {
-                        size = (dds.images[0].width, dds.images[0].height);
-
-                        for (lvl, img) in dds.images.iter().enumerate() {
-                            gl.compressed_tex_image2d(
-                                bindpoints[i],
-                                lvl as u8,
-                                TextureCompression::RgbaDxt5,
+                                compressions[i].unwrap(),
                                 img.width as u16,
                                 img.height as u16,
                                 &img.data,
@@ -450,19 +1069,24 @@ fn texture_bind_buffer(
                 }
             }
 
-            if need_gen_mipmap {
+            if need_gen_mipmap && sampler.mipmaps {
                 gl.generate_mipmap_cube();
                 has_midmap = true;
             }
 
-            gl_tex_kind = uni_gl::TextureKind::TextureCubeMap;
+            is_cube = true;
 
             (tex, size, has_midmap)
         }
 
-        &TextureKind::RenderTexture { size, ref attach } => {
+        &TextureKind::RenderTexture {
+            ref size,
+            ref attach,
+        } => {
+            let size = size.get();
             let (fmt, data_type) = match attach {
                 &TextureAttachment::Color0 => (PixelFormat::Rgba, PixelType::UnsignedByte),
+                &TextureAttachment::FloatColor0 => (PixelFormat::Rgba, PixelType::Float),
                 &TextureAttachment::Depth => {
                     force_nearest_filtering = true;
                     (PixelFormat::DepthComponent, PixelType::UnsignedShort)
@@ -486,64 +1110,21 @@ fn texture_bind_buffer(
         }
     };
 
-    let mut filtering: (i32, i32) = match texfilter {
-        &TextureFiltering::Nearest => (
-            TextureMinFilter::Nearest as i32,
-            TextureMagFilter::Nearest as i32,
-        ),
-        _ => {
-            if has_midmap {
-                (
-                    TextureMinFilter::LinearMipmapLinear as i32,
-                    TextureMagFilter::Linear as i32,
-                )
-            } else {
-                (
-                    TextureMinFilter::Linear as i32,
-                    TextureMagFilter::Linear as i32,
-                )
-            }
-        }
-    };
-
-    if force_nearest_filtering {
-        filtering = (
-            TextureMinFilter::Nearest as i32,
-            TextureMagFilter::Nearest as i32,
-        )
-    }
-
-    let to_gl_wrap = |w: TextureWrap| match w {
-        TextureWrap::Repeat => uni_gl::TextureWrap::Repeat as i32,
-        TextureWrap::ClampToEdge => uni_gl::TextureWrap::ClampToEdge as i32,
-        TextureWrap::MirroredRepeat => uni_gl::TextureWrap::MirroredRepeat as i32,
+    let mut state = TextureGLState {
+        tex,
+        size,
+        cube: is_cube,
+        has_mipmap: has_midmap,
+        force_nearest: force_nearest_filtering,
+        sampler: Sampler {
+            anisotropy: 1,
+            ..sampler
+        },
+        gl: gl.clone(),
     };
-
-    gl.tex_parameteri(gl_tex_kind, TextureParameter::TextureMinFilter, filtering.0);
-    gl.tex_parameteri(gl_tex_kind, TextureParameter::TextureMagFilter, filtering.1);
-
-    gl.tex_parameteri(
-        gl_tex_kind,
-        TextureParameter::TextureWrapS,
-        to_gl_wrap(wrap_u),
-    );
-    gl.tex_parameteri(
-        gl_tex_kind,
-        TextureParameter::TextureWrapT,
-        to_gl_wrap(wrap_v),
-    );
-
-    if let &TextureKind::CubeMap(..) = kind {
-        if let Some(wrap_w) = wrap_w {
-            gl.tex_parameteri(
-                gl_tex_kind,
-                TextureParameter::TextureWrapR,
-                to_gl_wrap(wrap_w),
-            );
-        }
-    }
+    apply_sampler(gl, &mut state, sampler);
 
     //unbind_texture(gl, kind);
 
-    Ok(TextureGLState { tex, size })
+    Ok(state)
 }
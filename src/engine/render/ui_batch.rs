@@ -0,0 +1,221 @@
+use engine::asset::Asset;
+use engine::render::{MaterialParam, MeshBuffer, MeshData, MeshPrimitive, MeshSurface};
+
+use math::*;
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+use std::u16;
+
+/// A surface merged into a batch
+struct BatchSource {
+    buffer: Weak<MeshBuffer>,
+    generation: u32,
+    model_m: Matrix4<f32>,
+}
+
+impl BatchSource {
+    fn new(surface: &MeshSurface, model_m: Matrix4<f32>) -> BatchSource {
+        BatchSource {
+            buffer: Rc::downgrade(&surface.buffer),
+            generation: surface.buffer.generation(),
+            model_m,
+        }
+    }
+
+    fn is_same(&self, other: &BatchSource) -> bool {
+        match (self.buffer.upgrade(), other.buffer.upgrade()) {
+            (Some(a), Some(b)) => {
+                Rc::ptr_eq(&a, &b)
+                    && self.generation == other.generation
+                    && self.model_m == other.model_m
+            }
+            _ => false,
+        }
+    }
+}
+
+struct Batch {
+    sources: Vec<BatchSource>,
+    /// The vertices are baked for the `uTransform` of the material
+    transform: Matrix4<f32>,
+    surface: Rc<MeshSurface>,
+}
+
+impl Batch {
+    fn is_same(&self, sources: &[BatchSource], transform: &Matrix4<f32>) -> bool {
+        self.transform == *transform
+            && self.sources.len() == sources.len()
+            && self
+                .sources
+                .iter()
+                .zip(sources.iter())
+                .all(|(a, b)| a.is_same(b))
+    }
+}
+
+/// Merges the consecutive surfaces of the UI or sprite queue with equal materials into a single
+/// mesh buffer, such that they are drawn in one draw call. The model matrices are baked
+/// into the vertices, and a batch is kept until any of its surfaces is changed
+#[derive(Default)]
+pub struct UiBatcher {
+    /// Batches of the last render pass
+    last: RefCell<Vec<Batch>>,
+    /// Batches of the current render pass
+    current: RefCell<Vec<Batch>>,
+}
+
+/// The vertex attributes (uvs, colors) of a surface, or None if it can not be batched
+fn batch_layout(surface: &MeshSurface) -> Option<(bool, bool)> {
+    if surface.buffer.primitive() != MeshPrimitive::Triangles {
+        return None;
+    }
+
+    let data = surface.buffer.mesh_data().ok()?;
+
+    if data.normals.is_some() || data.tangents.is_some() || data.bitangents.is_some() {
+        return None;
+    }
+
+    Some((data.uvs.is_some(), data.colors.is_some()))
+}
+
+/// Merge the mesh data of the surfaces, the vertices are moved by their model matrices
+/// in the space before `transform`, which is the `uTransform` of the shared material
+fn merge_mesh_data(
+    surfaces: &[(&Rc<MeshSurface>, Matrix4<f32>)],
+    transform: Matrix4<f32>,
+) -> Option<MeshData> {
+    let inv = transform.invert()?;
+    let mut merged = MeshData::default();
+
+    for &(surface, model_m) in surfaces.iter() {
+        let data = surface.buffer.mesh_data().ok()?;
+
+        let base = merged.vertices.len() / 3;
+        if base + data.vertices.len() / 3 > u16::MAX as usize + 1 {
+            return None;
+        }
+
+        let m = inv * model_m * transform;
+        for v in data.vertices.chunks(3) {
+            let p = m.transform_point(Point3::new(v[0], v[1], v[2]));
+            merged.vertices.extend_from_slice(&[p.x, p.y, p.z]);
+        }
+        merged
+            .indices
+            .extend(data.indices.iter().map(|i| base as u16 + i));
+
+        if let Some(ref uvs) = data.uvs {
+            merged
+                .uvs
+                .get_or_insert_with(Vec::new)
+                .extend_from_slice(uvs);
+        }
+        if let Some(ref colors) = data.colors {
+            merged
+                .colors
+                .get_or_insert_with(Vec::new)
+                .extend_from_slice(colors);
+        }
+    }
+
+    Some(merged)
+}
+
+impl UiBatcher {
+    pub fn new() -> UiBatcher {
+        UiBatcher::default()
+    }
+
+    /// Start a render pass, the batches which are not used in the last pass are dropped
+    pub fn begin(&self) {
+        let current = self.current.replace(Vec::new());
+        self.last.replace(current);
+    }
+
+    /// Whether the surface `b` drawn right after `a` can be merged into the same batch
+    pub fn can_batch(a: &MeshSurface, b: &MeshSurface) -> bool {
+        // The parameters of a surface are set per draw call
+        if a.params.is_some() || b.params.is_some() {
+            return false;
+        }
+
+        let same_material = Rc::ptr_eq(&a.material, &b.material) || *a.material == *b.material;
+        if !same_material {
+            return false;
+        }
+
+        match (batch_layout(a), batch_layout(b)) {
+            (Some(la), Some(lb)) => la == lb,
+            _ => false,
+        }
+    }
+
+    /// The surface of the batch of `surfaces` and their model matrices, which is drawn with
+    /// the identity model matrix. None if they can not be merged
+    pub fn merge(&self, surfaces: &[(&Rc<MeshSurface>, Matrix4<f32>)]) -> Option<Rc<MeshSurface>> {
+        let sources: Vec<BatchSource> = surfaces
+            .iter()
+            .map(|&(s, m)| BatchSource::new(s, m))
+            .collect();
+
+        let material = surfaces.first()?.0.material.clone();
+        let transform = match material.get("uTransform") {
+            Some(MaterialParam::Matrix4(m)) => m,
+            _ => Matrix4::identity(),
+        };
+
+        let mut last = self.last.borrow_mut();
+
+        // Nothing is changed since the last pass but maybe the material
+        let unchanged = last.iter().position(|b| b.is_same(&sources, &transform));
+        if let Some(i) = unchanged {
+            let mut batch = last.swap_remove(i);
+            if !Rc::ptr_eq(&batch.surface.material, &material) {
+                batch.surface = Rc::new(MeshSurface {
+                    buffer: batch.surface.buffer.clone(),
+                    material,
+                    params: None,
+                });
+            }
+
+            let surface = batch.surface.clone();
+            self.current.borrow_mut().push(batch);
+            return Some(surface);
+        }
+
+        let data = merge_mesh_data(surfaces, transform)?;
+
+        // Update the buffer of a changed batch of the same material in place
+        let changed = last.iter().position(|b| {
+            *b.surface.material == *material
+                && b.surface
+                    .buffer
+                    .mesh_data()
+                    .map(|d| d.is_same_layout(&data))
+                    .unwrap_or(false)
+        });
+
+        let buffer = match changed {
+            Some(i) => {
+                let batch = last.swap_remove(i);
+                batch.surface.buffer.update_mesh_data(data);
+                batch.surface.buffer.clone()
+            }
+            None => MeshBuffer::new(data),
+        };
+
+        let surface = Rc::new(MeshSurface {
+            buffer,
+            material,
+            params: None,
+        });
+        self.current.borrow_mut().push(Batch {
+            sources,
+            transform,
+            surface: surface.clone(),
+        });
+
+        Some(surface)
+    }
+}
@@ -139,6 +139,18 @@ impl UniformCache {
         }
     }
 
+    /// Forget the uniform locations after the program is linked again,
+    /// the values are committed again to the new program
+    pub fn reset(&self) {
+        let entries = self.uniform_entries.borrow_mut().drain().collect::<Vec<_>>();
+        let mut pending = self.pending_entries.borrow_mut();
+        for (key, adapter) in entries.into_iter() {
+            pending.entry(key).or_insert(adapter);
+        }
+
+        self.uniform_map.borrow_mut().clear();
+    }
+
     pub fn commit(&self, gl: &WebGLRenderingContext, prog: &WebGLProgram) {
         {
             let mut pending = self.pending_entries.borrow_mut();
@@ -155,6 +167,15 @@ impl UniformCache {
         }
     }
 
+    pub fn has_uniform(
+        &self,
+        gl: &WebGLRenderingContext,
+        prog: &WebGLProgram,
+        s: &Cow<'static, str>,
+    ) -> bool {
+        self.get_uniform(gl, prog, s).is_some()
+    }
+
     fn get_uniform(
         &self,
         gl: &WebGLRenderingContext,
@@ -6,11 +6,16 @@
 #![cfg_attr(feature = "flame_it", plugin(flamer))]
 
 /* common */
+extern crate base64;
+extern crate flate2;
 extern crate fnv;
 extern crate futures;
+extern crate gltf;
 extern crate hound;
 extern crate image;
 extern crate obj;
+extern crate rusttype;
+extern crate serde_json;
 extern crate typed_arena;
 extern crate uni_app;
 extern crate uni_glsl;
@@ -24,9 +29,16 @@ extern crate unrust_derive;
 #[macro_use]
 extern crate bitflags;
 
+#[macro_use]
+extern crate lazy_static;
+
 #[cfg(feature = "flame_it")]
 extern crate flame;
 
+#[cfg(target_arch = "wasm32")]
+#[macro_use]
+extern crate stdweb;
+
 // This is here so that our procedural macros
 // can work within the crate.
 pub(crate) mod unrust {
@@ -6,6 +6,8 @@ use futures::future;
 use std::collections::BTreeSet;
 use std::cell::RefCell;
 use std::rc::Rc;
+use std::time::SystemTime;
+use std;
 
 // unrust engine support different file system.
 #[derive(Default)]
@@ -38,13 +40,7 @@ impl FileSystem for AppFileSystem {
     type File = AppFile;
 
     fn open(&self, filename: &str) -> FileFuture {
-        let mut abs_filename = filename.to_string();
-
-        if cfg!(not(target_arch = "wasm32")) {
-            abs_filename = "static/".to_string() + filename;
-        }
-
-        abs_filename = abs_filename.replace("\\", "/");
+        let abs_filename = abs_filename(filename);
 
         let f = fs::FileSystem::open(&abs_filename)
             .map_err(|_| FileIoError::NoSuchFile(filename.to_string()));
@@ -69,6 +65,26 @@ impl FileSystem for AppFileSystem {
             .map(|s| s.clone())
             .collect()
     }
+
+    fn modified(&self, filename: &str) -> Option<SystemTime> {
+        if cfg!(target_arch = "wasm32") {
+            return None;
+        }
+
+        std::fs::metadata(abs_filename(filename))
+            .and_then(|m| m.modified())
+            .ok()
+    }
+}
+
+fn abs_filename(filename: &str) -> String {
+    let mut abs_filename = filename.to_string();
+
+    if cfg!(not(target_arch = "wasm32")) {
+        abs_filename = "static/".to_string() + filename;
+    }
+
+    abs_filename.replace("\\", "/")
 }
 
 impl File for AppFile {
@@ -68,9 +68,13 @@ impl Watcher for MaterialWatcher {
             for &(_, ref c) in objects.iter() {
                 let mesh = c.try_as::<Mesh>().unwrap();
 
-                for surface in mesh.borrow().surfaces.iter() {
+                let mesh = mesh.borrow();
+                for surface in mesh.surfaces.iter() {
                     materials.push(surface.material.clone());
                 }
+                if let Some(instances) = mesh.instances() {
+                    materials.push(instances.material().clone());
+                }
             }
         }
 
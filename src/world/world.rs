@@ -1,11 +1,12 @@
 use std::cell::{Ref, RefCell, RefMut};
 use std::ops::Deref;
-use std::rc::Rc;
+use std::rc::{Rc, Weak};
 use std::sync::Arc;
 
+use actors::Animator;
 use engine::{
-    AssetSystem, Camera, ClearOption, Component, ComponentBased, ComponentType, Engine, GameObject,
-    IEngine, SceneTree,
+    Asset, AssetSystem, Camera, ClearOption, Component, ComponentBased, ComponentType, Engine,
+    GameObject, IEngine, Mesh, MeshBuffer, Prefab, Resource, SceneTree, Skin,
 };
 use world::app_fs::AppEngine;
 
@@ -268,9 +269,13 @@ impl<'a> World {
                 _ => (),
             }
 
+            imgui::handle_event(evt);
             profile::dump(evt);
         }
 
+        // The d-pad and the A button of the first gamepad navigate the ui
+        imgui::handle_gamepad(|button| pad::gamepad_button(0, button));
+
         let watcher = self.watcher.clone();
         watcher.step(self);
 
@@ -293,7 +298,7 @@ impl<'a> World {
                 loading_stats = format!("{}", files.join("\n"));
             }
 
-            imgui::pivot((0.0, 0.0));
+            imgui::pivot(Native(0.0, 0.0));
             imgui::label(
                 Native(0.0, 0.0) + Pixel(8.0, 8.0),
                 &format!(
@@ -393,6 +398,83 @@ impl<'a> World {
         go
     }
 
+    /// Create the objects of the nodes of a prefab under a new object, which is returned.
+    /// The skinned meshes get a `Skin` of the objects of their joints, and the new object
+    /// an `Animator` of the animations of the prefab which plays the first one.
+    ///
+    /// The skinned and the morphed meshes are deformed in place, so each instance gets
+    /// its own copies of their buffers
+    pub fn instantiate(&mut self, prefab: &Prefab) -> Handle<GameObject> {
+        let root = self.new_game_object();
+
+        // Without nodes, e.g. an OBJ file, the meshes are on the new object
+        if prefab.nodes.is_empty() {
+            for mesh in prefab.meshes.iter() {
+                root.borrow_mut().add_component(mesh.clone());
+            }
+            return root;
+        }
+
+        let objects: Vec<_> = prefab.nodes.iter().map(|_| self.new_game_object()).collect();
+
+        for &i in prefab.roots.iter() {
+            root.borrow().add_child(&objects[i].borrow());
+        }
+
+        for (node, go) in prefab.nodes.iter().zip(objects.iter()) {
+            for &child in node.children.iter() {
+                go.borrow().add_child(&objects[child].borrow());
+            }
+
+            let mut go = go.borrow_mut();
+            go.transform.set_local(node.transform);
+            go.transform.set_local_scale(node.scale);
+
+            let mesh = match node.mesh.and_then(|i| prefab.meshes.get(i)) {
+                Some(mesh) => mesh,
+                None => continue,
+            };
+            let skin = node.skin.and_then(|i| prefab.skins.get(i));
+
+            let deformed = skin.is_some() || mesh.surfaces.iter().any(|s| {
+                s.buffer
+                    .mesh_data()
+                    .map_or(false, |data| !data.morph_targets.is_empty())
+            });
+
+            if deformed {
+                let mut copy = Mesh::new();
+                copy.morph_weights = mesh.morph_weights.clone();
+                for surface in mesh.surfaces.iter() {
+                    if let Ok(data) = surface.buffer.mesh_data() {
+                        let buffer = MeshBuffer::new_from_resource(Resource::new(data.clone()));
+                        copy.add_surface(buffer, surface.material.clone());
+                    }
+                }
+                go.add_component(copy);
+            } else {
+                go.add_component(mesh.clone());
+            }
+
+            if let Some(skin) = skin {
+                let joints = skin.joints
+                    .iter()
+                    .map(|&j| objects.get(j).map_or_else(Weak::new, Rc::downgrade))
+                    .collect();
+                go.add_component(Skin::new(joints, skin.inverse_bind_matrices.clone()));
+            }
+        }
+
+        if !prefab.animations.is_empty() {
+            let targets = objects.iter().map(Rc::downgrade).collect();
+            let mut animator = Animator::new(prefab.animations.clone(), targets);
+            animator.play_index(0);
+            root.borrow_mut().add_component(animator);
+        }
+
+        root
+    }
+
     pub fn remove_game_object(&mut self, go: &Handle<GameObject>) {
         self.golist.retain(|x| !Rc::ptr_eq(&x, go));
     }
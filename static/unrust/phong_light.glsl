@@ -12,10 +12,31 @@ struct PointLight {
     float constant;
     float linear;
     float quadratic;
+    float range;
 	
     vec3 ambient;
     vec3 diffuse;
     vec3 specular;
 
+    float rate;
+};
+
+struct SpotLight {
+    vec3 position;
+    vec3 direction;
+
+    float constant;
+    float linear;
+    float quadratic;
+    float range;
+
+    // Cosines of the inner and the outer cone
+    float cutOff;
+    float outerCutOff;
+
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+
     float rate;
 };
\ No newline at end of file
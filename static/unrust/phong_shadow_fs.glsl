@@ -5,6 +5,7 @@
 out vec4 FragColor;
 
 #define UNI_POINT_LIGHTS 4
+#define UNI_SPOT_LIGHTS 4
 
 #include "unrust/phong_light.glsl"
 #include "unrust/shadow_utils.glsl"
@@ -24,9 +25,12 @@ in vec3 vNormal;
 // Lights
 uniform DirectionalLight uDirectionalLight;
 uniform PointLight uPointLights[UNI_POINT_LIGHTS];
+uniform SpotLight uSpotLights[UNI_SPOT_LIGHTS];
 
 vec3 CalcDirectionalLight(DirectionalLight light, vec3 normal, vec3 viewDir);
 vec3 CalcPointLight(PointLight light, vec3 normal, vec3 fragPos, vec3 viewDir);
+vec3 CalcSpotLight(SpotLight light, vec3 normal, vec3 fragPos, vec3 viewDir);
+float CalcAttenuation(float constant, float linear, float quadratic, float range, float distance);
 
 void main(void) {
     vec3 norm = normalize(vNormal);
@@ -39,6 +43,10 @@ void main(void) {
     for(int i = 0; i < UNI_POINT_LIGHTS; i++)
         result += CalcPointLight(uPointLights[i], norm, vFragPos, viewDir);
 
+    // Spot Lights
+    for(int i = 0; i < UNI_SPOT_LIGHTS; i++)
+        result += CalcSpotLight(uSpotLights[i], norm, vFragPos, viewDir);
+
     gl_FragColor = vec4(result, 1.0);           
 }
 
@@ -73,8 +81,7 @@ vec3 CalcPointLight(PointLight light, vec3 normal, vec3 fragPos, vec3 viewDir)
     
     // attenuation
     float distance = length(light.position - fragPos);
-    float d = (light.constant + light.linear * distance + light.quadratic * (distance * distance));
-    float attenuation = 1.0 / max(d, 0.001);
+    float attenuation = CalcAttenuation(light.constant, light.linear, light.quadratic, light.range, distance);
     
     // combine results
     vec3 ambient = light.ambient * vec3(texture2D(uMaterial.diffuse, vTexCoords));
@@ -87,3 +94,42 @@ vec3 CalcPointLight(PointLight light, vec3 normal, vec3 fragPos, vec3 viewDir)
     
     return (ambient + diffuse + specular) * light.rate;        
 }
+
+vec3 CalcSpotLight(SpotLight light, vec3 normal, vec3 fragPos, vec3 viewDir)
+{
+    vec3 lightDir = normalize(light.position - fragPos);
+
+    // diffuse shading
+    float diff = max(dot(normal, lightDir), 0.0);
+    // specular shading
+    vec3 reflectDir = reflect(-lightDir, normal);
+    float spec = pow(max(dot(viewDir, reflectDir), 0.0), uMaterial.shininess);
+
+    // attenuation
+    float distance = length(light.position - fragPos);
+    float attenuation = CalcAttenuation(light.constant, light.linear, light.quadratic, light.range, distance);
+
+    // cone
+    float theta = dot(lightDir, normalize(-light.direction));
+    float epsilon = max(light.cutOff - light.outerCutOff, 0.0001);
+    float intensity = clamp((theta - light.outerCutOff) / epsilon, 0.0, 1.0);
+
+    // combine results
+    vec3 ambient = light.ambient * vec3(texture2D(uMaterial.diffuse, vTexCoords));
+    vec3 diffuse = light.diffuse * diff * vec3(texture2D(uMaterial.diffuse, vTexCoords));
+    vec3 specular = light.specular * spec;
+
+    ambient *= attenuation;
+    diffuse *= attenuation * intensity;
+    specular *= attenuation * intensity;
+
+    return (ambient + diffuse + specular) * light.rate;
+}
+
+float CalcAttenuation(float constant, float linear, float quadratic, float range, float distance)
+{
+    float d = (constant + linear * distance + quadratic * (distance * distance));
+    // fade out smoothly towards the range
+    float window = clamp(1.0 - pow(distance / max(range, 0.001), 4.0), 0.0, 1.0);
+    return window * window / max(d, 0.001);
+}